@@ -16,6 +16,8 @@
 
 //! Basic runtime calls.
 
+use crate::BatchCallKind;
+
 use codec::{Decode, Encode};
 use scale_info::TypeInfo;
 use sp_std::{boxed::Box, vec::Vec};
@@ -35,9 +37,26 @@ pub enum SystemCall {
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, TypeInfo)]
 #[allow(non_camel_case_types)]
 pub enum UtilityCall<Call> {
+	/// `pallet-utility::Call::batch`
+	#[codec(index = 0)]
+	batch(Vec<Call>),
 	/// `pallet-utility::Call::batch_all`
 	#[codec(index = 2)]
 	batch_all(Vec<Call>),
+	/// `pallet-utility::Call::force_batch`
+	#[codec(index = 4)]
+	force_batch(Vec<Call>),
+}
+
+impl<Call> UtilityCall<Call> {
+	/// Create batch call of given kind.
+	pub fn new(kind: BatchCallKind, calls: Vec<Call>) -> Self {
+		match kind {
+			BatchCallKind::Batch => UtilityCall::batch(calls),
+			BatchCallKind::BatchAll => UtilityCall::batch_all(calls),
+			BatchCallKind::ForceBatch => UtilityCall::force_batch(calls),
+		}
+	}
 }
 
 /// A minimized version of `pallet-sudo::Call` that can be used without a runtime.
@@ -57,3 +76,53 @@ pub enum XcmCall {
 	#[codec(index = 0)]
 	send(Box<VersionedLocation>, Box<VersionedXcm<()>>),
 }
+
+/// A minimized version of `pallet-proxy::Call` that can be used without a runtime.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, TypeInfo)]
+#[allow(non_camel_case_types)]
+pub enum ProxyCall<AccountLookup, ProxyType, Call> {
+	/// `pallet-proxy::Call::proxy`
+	#[codec(index = 0)]
+	proxy(AccountLookup, Option<ProxyType>, Box<Call>),
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_runtime::MultiAddress;
+
+	#[test]
+	fn proxy_call_is_encoded() {
+		let real = MultiAddress::<u64, ()>::Id(42);
+		let call = SystemCall::remark(vec![1, 2, 3]);
+		let proxy_call = ProxyCall::proxy(real.clone(), Some(5u8), Box::new(call.clone()));
+
+		let mut expected = vec![0u8];
+		expected.extend(real.encode());
+		expected.extend([1u8, 5]);
+		expected.extend(call.encode());
+		assert_eq!(proxy_call.encode(), expected);
+		assert_eq!(ProxyCall::decode(&mut &expected[..]), Ok(proxy_call));
+	}
+
+	#[test]
+	fn batch_calls_are_encoded() {
+		let calls = vec![SystemCall::remark(vec![1]), SystemCall::remark(vec![2, 3])];
+		let mut encoded_calls = vec![8u8];
+		encoded_calls.extend(calls[0].encode());
+		encoded_calls.extend(calls[1].encode());
+
+		for (kind, index) in [
+			(BatchCallKind::Batch, 0u8),
+			(BatchCallKind::BatchAll, 2),
+			(BatchCallKind::ForceBatch, 4),
+		] {
+			let batch_call = UtilityCall::new(kind, calls.clone());
+
+			let mut expected = vec![index];
+			expected.extend(&encoded_calls);
+			assert_eq!(batch_call.encode(), expected);
+			assert_eq!(UtilityCall::decode(&mut &expected[..]), Ok(batch_call));
+		}
+	}
+}
@@ -20,10 +20,11 @@ use crate::SimpleRuntimeVersion;
 use bp_header_chain::ChainWithGrandpa as ChainWithGrandpaBase;
 use bp_messages::ChainWithMessages as ChainWithMessagesBase;
 use bp_runtime::{
-	Chain as ChainBase, EncodedOrDecodedCall, HashOf, Parachain as ParachainBase, TransactionEra,
-	TransactionEraOf, UnderlyingChainProvider,
+	AccountIdOf, Chain as ChainBase, EncodedOrDecodedCall, HashOf, Parachain as ParachainBase,
+	TransactionEra, TransactionEraOf, UnderlyingChainProvider,
 };
 use codec::{Codec, Decode, Encode};
+use frame_support::traits::InstanceFilter;
 use jsonrpsee::core::{DeserializeOwned, Serialize};
 use num_traits::Zero;
 use sc_transaction_pool_api::TransactionStatus;
@@ -32,9 +33,10 @@ use sp_core::{storage::StorageKey, Pair};
 use sp_runtime::{
 	generic::SignedBlock,
 	traits::{Block as BlockT, Member},
+	transaction_validity::TransactionPriority,
 	ConsensusEngineId, EncodedJustification,
 };
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, str::FromStr, time::Duration};
 
 /// Signed block type of given chain.
 pub type SignedBlockOf<C> = <C as Chain>::SignedBlock;
@@ -168,12 +170,16 @@ pub struct UnsignedTransaction<C: Chain> {
 	pub tip: C::Balance,
 	/// Transaction era used by the chain.
 	pub era: TransactionEraOf<C>,
+	/// Explicit transaction priority. Only used by chains with the priority signed extension
+	/// (see `ChainWithTransactions::HAS_PRIORITY_EXTENSION`).
+	pub priority: Option<TransactionPriority>,
 }
 
 impl<C: Chain> UnsignedTransaction<C> {
-	/// Create new unsigned transaction with given call, nonce, era and zero tip.
+	/// Create new unsigned transaction with given call, nonce, era, zero tip and no explicit
+	/// priority.
 	pub fn new(call: EncodedOrDecodedCall<C::Call>, nonce: C::Nonce) -> Self {
-		Self { call, nonce, era: TransactionEra::Immortal, tip: Zero::zero() }
+		Self { call, nonce, era: TransactionEra::Immortal, tip: Zero::zero(), priority: None }
 	}
 
 	/// Convert to the transaction of the other compatible chain.
@@ -191,6 +197,7 @@ impl<C: Chain> UnsignedTransaction<C> {
 			nonce: self.nonce,
 			tip: self.tip,
 			era: self.era,
+			priority: self.priority,
 		}
 	}
 
@@ -207,6 +214,13 @@ impl<C: Chain> UnsignedTransaction<C> {
 		self.era = era;
 		self
 	}
+
+	/// Set explicit transaction priority.
+	#[must_use]
+	pub fn priority(mut self, priority: Option<TransactionPriority>) -> Self {
+		self.priority = priority;
+		self
+	}
 }
 
 /// Account key pair used by transactions signing scheme.
@@ -219,6 +233,11 @@ pub trait ChainWithTransactions: Chain {
 	/// Signed transaction.
 	type SignedTransaction: Clone + Debug + Codec + Send + 'static;
 
+	/// If `true`, the runtime has signed extension with explicit transaction priority and
+	/// `sign_transaction` puts `UnsignedTransaction::priority` there. Otherwise, the priority
+	/// is computed by the runtime and the explicit priority is ignored.
+	const HAS_PRIORITY_EXTENSION: bool = false;
+
 	/// Create transaction for given runtime call, signed by given account.
 	fn sign_transaction(
 		param: SignParam<Self>,
@@ -240,6 +259,17 @@ pub struct SignParam<C: ChainWithTransactions> {
 	pub signer: AccountKeyPairOf<C>,
 }
 
+impl<C: ChainWithTransactions> Clone for SignParam<C> {
+	fn clone(&self) -> Self {
+		SignParam {
+			spec_version: self.spec_version,
+			transaction_version: self.transaction_version,
+			genesis_hash: self.genesis_hash,
+			signer: self.signer.clone(),
+		}
+	}
+}
+
 impl<Block: BlockT> BlockWithJustification<Block::Header> for SignedBlock<Block> {
 	fn header(&self) -> Block::Header {
 		self.block.header().clone()
@@ -254,10 +284,30 @@ impl<Block: BlockT> BlockWithJustification<Block::Header> for SignedBlock<Block>
 	}
 }
 
+/// Dispatchable of `pallet-utility`, used to batch calls.
+///
+/// The batch transaction itself succeeds even if some of `batch` or `force_batch` calls fail, so
+/// callers must not assume that all batched calls have been dispatched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BatchCallKind {
+	/// Calls are dispatched until the first failed call. Calls that precede the failed call
+	/// are not reverted and calls that follow it are not dispatched.
+	Batch,
+	/// If any call fails, all calls are reverted.
+	BatchAll,
+	/// Failed calls are skipped and all other calls are dispatched.
+	ForceBatch,
+}
+
 /// Trait that provides functionality defined inside `pallet-utility`
 pub trait UtilityPallet<C: Chain> {
 	/// Create batch call from given calls vector.
-	fn build_batch_call(calls: Vec<C::Call>) -> C::Call;
+	fn build_batch_call(calls: Vec<C::Call>) -> C::Call {
+		Self::build_batch_call_of_kind(BatchCallKind::BatchAll, calls)
+	}
+
+	/// Create batch call of given kind from given calls vector.
+	fn build_batch_call_of_kind(kind: BatchCallKind, calls: Vec<C::Call>) -> C::Call;
 }
 
 /// Structure that implements `UtilityPalletProvider` based on a full runtime.
@@ -271,8 +321,13 @@ where
 	R: pallet_utility::Config<RuntimeCall = C::Call>,
 	<R as pallet_utility::Config>::RuntimeCall: From<pallet_utility::Call<R>>,
 {
-	fn build_batch_call(calls: Vec<C::Call>) -> C::Call {
-		pallet_utility::Call::batch_all { calls }.into()
+	fn build_batch_call_of_kind(kind: BatchCallKind, calls: Vec<C::Call>) -> C::Call {
+		match kind {
+			BatchCallKind::Batch => pallet_utility::Call::batch { calls },
+			BatchCallKind::BatchAll => pallet_utility::Call::batch_all { calls },
+			BatchCallKind::ForceBatch => pallet_utility::Call::force_batch { calls },
+		}
+		.into()
 	}
 }
 
@@ -286,8 +341,8 @@ where
 	C: Chain,
 	C::Call: From<UtilityCall<C::Call>>,
 {
-	fn build_batch_call(calls: Vec<C::Call>) -> C::Call {
-		UtilityCall::batch_all(calls).into()
+	fn build_batch_call_of_kind(kind: BatchCallKind, calls: Vec<C::Call>) -> C::Call {
+		UtilityCall::new(kind, calls).into()
 	}
 }
 
@@ -296,3 +351,21 @@ pub trait ChainWithUtilityPallet: Chain {
 	/// The utility pallet provider.
 	type UtilityPallet: UtilityPallet<Self>;
 }
+
+/// Substrate-based chain that uses `pallet-proxy`.
+///
+/// The relay may sign transactions with the proxy account and dispatch its calls on behalf
+/// of the (real) proxied account, using the `proxy.proxy` call.
+pub trait ChainWithProxyPallet: Chain {
+	/// Type of the proxy. The filter must match the filter of the runtime proxy type, so that
+	/// the relay is able to check that the call may be dispatched by the proxy.
+	type ProxyType: Clone + Debug + Encode + FromStr + InstanceFilter<Self::Call>;
+
+	/// Wrap the call into the `proxy.proxy` call, which is dispatched on behalf of `real`
+	/// account. Normally it is built using the `crate::calls::ProxyCall`.
+	fn build_proxy_call(
+		real: AccountIdOf<Self>,
+		proxy_type: Self::ProxyType,
+		call: Self::Call,
+	) -> Self::Call;
+}
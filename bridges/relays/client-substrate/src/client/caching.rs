@@ -19,10 +19,12 @@
 
 use crate::{
 	client::{Client, SubscriptionBroadcaster},
+	endpoints::Endpoints,
 	error::{Error, Result},
-	AccountIdOf, AccountKeyPairOf, BlockNumberOf, Chain, ChainWithGrandpa, ChainWithTransactions,
-	HashOf, HeaderIdOf, HeaderOf, NonceOf, SignedBlockOf, SimpleRuntimeVersion, Subscription,
-	TransactionTracker, UnsignedTransaction, ANCIENT_BLOCK_THRESHOLD,
+	AccountIdOf, AccountKeyPairOf, BalanceOf, BlockNumberOf, Chain, ChainWithGrandpa,
+	ChainWithTransactions, HashOf, HeaderIdOf, HeaderOf, InFlightTransactions, NonceOf,
+	SignedBlockOf, SimpleRuntimeVersion, Subscription, TransactionTracker, UnsignedTransaction,
+	ANCIENT_BLOCK_THRESHOLD,
 };
 use std::{cmp::Ordering, future::Future, task::Poll};
 
@@ -431,6 +433,14 @@ impl<C: Chain, B: Client<C>> Client<C> for CachingClient<C, B> {
 			.map(|t| t.switch_environment(self.clone()))
 	}
 
+	fn in_flight_transactions(&self) -> InFlightTransactions {
+		self.backend.in_flight_transactions()
+	}
+
+	fn endpoints(&self) -> Endpoints<C> {
+		self.backend.endpoints()
+	}
+
 	async fn validate_transaction<SignedTransaction: Encode + Send + 'static>(
 		&self,
 		at: HashOf<C>,
@@ -447,6 +457,14 @@ impl<C: Chain, B: Client<C>> Client<C> for CachingClient<C, B> {
 		self.backend.estimate_extrinsic_weight(at, transaction).await
 	}
 
+	async fn estimate_extrinsic_fee<SignedTransaction: Encode + Send + 'static>(
+		&self,
+		at: HashOf<C>,
+		transaction: SignedTransaction,
+	) -> Result<BalanceOf<C>> {
+		self.backend.estimate_extrinsic_fee(at, transaction).await
+	}
+
 	async fn raw_state_call<Args: Encode + Send>(
 		&self,
 		at: HashOf<C>,
@@ -22,14 +22,18 @@ use caching::CachingClient;
 use num_traits::Saturating;
 use rpc::RpcClient;
 use sp_version::RuntimeVersion;
+use split::SplitClient;
 
 pub mod caching;
 pub mod rpc;
+pub mod split;
 
 mod rpc_api;
+mod rpc_trace;
 mod subscription;
 mod traits;
 
+pub use rpc_trace::RpcTraceParams;
 pub use subscription::{StreamDescription, Subscription, SubscriptionBroadcaster};
 pub use traits::Client;
 
@@ -42,6 +46,23 @@ pub async fn rpc_with_caching<C: Chain>(params: ConnectionParams) -> RpcWithCach
 	caching::CachingClient::new(rpc).await
 }
 
+/// Type of RPC client with caching support, which may send read requests to the read replica.
+pub type RpcWithCachingSplitClient<C> = SplitClient<C, RpcWithCachingClient<C>>;
+
+/// Creates new RPC client with caching support, which is sending transactions to the primary
+/// node and read requests to the (optional) read replica.
+pub async fn split_rpc_with_caching<C: Chain>(
+	primary: ConnectionParams,
+	replica: Option<ConnectionParams>,
+) -> RpcWithCachingSplitClient<C> {
+	let primary = rpc_with_caching(primary).await;
+	let replica = match replica {
+		Some(replica) => Some(rpc_with_caching(replica).await),
+		None => None,
+	};
+	SplitClient::new(primary, replica)
+}
+
 /// The difference between best block number and number of its ancestor, that is enough
 /// for us to consider that ancestor an "ancient" block with dropped state.
 ///
@@ -63,7 +84,7 @@ pub fn is_ancient_block<N: From<u32> + PartialOrd + Saturating>(block: N, best:
 pub type OpaqueGrandpaAuthoritiesSet = Vec<u8>;
 
 /// A simple runtime version. It only includes the `spec_version` and `transaction_version`.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct SimpleRuntimeVersion {
 	/// Version of the runtime specification.
 	pub spec_version: u32,
@@ -89,3 +110,65 @@ pub enum ChainRuntimeVersion {
 	/// Custom runtime version, defined by user.
 	Custom(SimpleRuntimeVersion),
 }
+
+/// What the client does when the transaction version of the chain runtime is changed, while
+/// transactions are signed using the custom (or bundled) runtime version.
+///
+/// The transaction version and the spec version are a part of the signed transaction payload,
+/// so transactions, signed using the outdated version, are rejected by the upgraded runtime.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TransactionVersionBump {
+	/// Keep signing transactions using the configured runtime version.
+	#[default]
+	Keep,
+	/// Sign transactions using the version of the upgraded runtime. The runtime version is
+	/// polled by the client and the spec version guard is not started.
+	Refresh,
+	/// Abort the process.
+	Abort,
+}
+
+impl std::str::FromStr for TransactionVersionBump {
+	type Err = String;
+
+	fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+		match s {
+			"keep" => Ok(TransactionVersionBump::Keep),
+			"refresh" => Ok(TransactionVersionBump::Refresh),
+			"abort" => Ok(TransactionVersionBump::Abort),
+			_ => Err(format!(
+				"Invalid transaction version bump action '{s}'. Expected 'keep', 'refresh' or \
+				'abort'"
+			)),
+		}
+	}
+}
+
+/// State, used to read nonces of transaction signers.
+///
+/// The best state (together with the transaction pool) gives the nonce right after previous
+/// transactions are submitted, but the nonce may be reused if the best block is reorganized.
+/// The finalized state is never reorganized, but it ignores transactions that are in the pool
+/// or in non-finalized blocks. So with the finalized state, the signer shall not submit the
+/// next transaction until the previous one is finalized.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NonceSource {
+	/// Read nonce using the `system_accountNextIndex` RPC, which is using the best state and
+	/// the transaction pool.
+	#[default]
+	Best,
+	/// Read nonce from the `System::Account` storage at the best finalized block.
+	Finalized,
+}
+
+impl std::str::FromStr for NonceSource {
+	type Err = String;
+
+	fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+		match s {
+			"best" => Ok(NonceSource::Best),
+			"finalized" => Ok(NonceSource::Finalized),
+			_ => Err(format!("Invalid nonce source '{s}'. Expected 'best' or 'finalized'")),
+		}
+	}
+}
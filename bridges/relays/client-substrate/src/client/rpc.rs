@@ -24,26 +24,31 @@ use crate::{
 			SubstrateFrameSystemClient, SubstrateGrandpaClient, SubstrateStateClient,
 			SubstrateSystemClient,
 		},
+		rpc_trace::TracedWsClient,
 		subscription::{StreamDescription, Subscription},
 		Client,
 	},
+	endpoints::Endpoints,
 	error::{Error, Result},
-	guard::Environment,
+	guard::{on_transaction_version_change, Environment, SharedRuntimeVersion},
+	nonce_gaps::{remark_call, NonceGaps},
+	signing_cache::SigningCache,
 	transaction_stall_timeout, AccountIdOf, AccountKeyPairOf, BalanceOf, BlockNumberOf, Chain,
 	ChainRuntimeVersion, ChainWithGrandpa, ChainWithTransactions, ConnectionParams, HashOf,
-	HeaderIdOf, HeaderOf, NonceOf, SignParam, SignedBlockOf, SimpleRuntimeVersion,
-	TransactionTracker, UnsignedTransaction,
+	HeaderIdOf, HeaderOf, InFlightTransactions, NonceOf, NonceSource, SignParam, SignedBlockOf,
+	SimpleRuntimeVersion, TransactionEra, TransactionTracker, TransactionVersionBump,
+	UnsignedTransaction,
 };
 
 use async_std::sync::{Arc, Mutex, RwLock};
 use async_trait::async_trait;
-use bp_runtime::HeaderIdProvider;
-use codec::Encode;
-use frame_support::weights::Weight;
+use bp_runtime::{storage_map_final_key, HeaderIdProvider};
+use codec::{Decode, Encode};
+use frame_support::{weights::Weight, Blake2_128Concat};
 use futures::TryFutureExt;
 use jsonrpsee::{
 	core::{client::Subscription as RpcSubscription, ClientError},
-	ws_client::{WsClient, WsClientBuilder},
+	ws_client::WsClientBuilder,
 };
 use num_traits::Zero;
 use pallet_transaction_payment::RuntimeDispatchInfo;
@@ -52,10 +57,12 @@ use sp_core::{
 	storage::{StorageData, StorageKey},
 	Bytes, Hasher, Pair,
 };
-use sp_runtime::transaction_validity::{TransactionSource, TransactionValidity};
+use sp_runtime::transaction_validity::{
+	InvalidTransaction, TransactionSource, TransactionValidity, TransactionValidityError,
+};
 use sp_trie::StorageProof;
 use sp_version::RuntimeVersion;
-use std::{cmp::Ordering, future::Future, marker::PhantomData};
+use std::{cmp::Ordering, future::Future, marker::PhantomData, time::Duration};
 
 const MAX_SUBSCRIPTION_CAPACITY: usize = 4096;
 
@@ -70,13 +77,26 @@ pub struct RpcClient<C: Chain> {
 	// Lock order: `submit_signed_extrinsic_lock`, `data`
 	/// Client connection params.
 	params: Arc<ConnectionParams>,
+	/// Endpoints of the chain nodes, that the client is switching between.
+	endpoints: Endpoints<C>,
 	/// If several tasks are submitting their transactions simultaneously using
 	/// `submit_signed_extrinsic` method, they may get the same transaction nonce. So one of
 	/// transactions will be rejected from the pool. This lock is here to prevent situations like
 	/// that.
 	submit_signed_extrinsic_lock: Arc<Mutex<()>>,
+	/// Transactions, submitted using `submit_and_watch_signed_extrinsic`, which are not yet
+	/// finalized or lost.
+	in_flight: InFlightTransactions,
+	/// If set, gaps in nonces of submitted transactions are filled with no-op transactions.
+	nonce_gaps: Option<NonceGaps<C>>,
 	/// Genesis block hash.
 	genesis_hash: HashOf<C>,
+	/// Custom runtime version, used to sign transactions. It may be refreshed after runtime
+	/// upgrades. If `None`, the version is read from the node.
+	custom_runtime_version: Option<SharedRuntimeVersion>,
+	/// If set, the runtime version that has been read from the node is reused to sign
+	/// transactions until the runtime is upgraded.
+	signing_cache: Option<SigningCache<C>>,
 	/// Shared dynamic data.
 	data: Arc<RwLock<ClientData>>,
 	/// Generic arguments dump.
@@ -88,7 +108,9 @@ struct ClientData {
 	/// Tokio runtime handle.
 	tokio: Arc<tokio::runtime::Runtime>,
 	/// Substrate RPC client.
-	client: Arc<WsClient>,
+	client: Arc<TracedWsClient>,
+	/// Client of the direct inclusion endpoint, if it is configured and available.
+	direct_inclusion: Option<Arc<TracedWsClient>>,
 }
 
 /// Already encoded value.
@@ -112,17 +134,24 @@ impl<C: Chain> RpcClient<C> {
 	/// This function will keep connecting to given Substrate node until connection is established
 	/// and is functional. If attempt fail, it will wait for `RECONNECT_DELAY` and retry again.
 	pub async fn new(params: ConnectionParams) -> Self {
+		let endpoints = Endpoints::new(endpoint_uris(&params));
 		let params = Arc::new(params);
 		loop {
-			match Self::try_connect(params.clone()).await {
+			match Self::try_connect(params.clone(), endpoints.clone()).await {
 				Ok(client) => return client,
-				Err(error) => log::error!(
-					target: "bridge",
-					"Failed to connect to {} node: {:?}. Going to retry in {}s",
-					C::NAME,
-					error,
-					RECONNECT_DELAY.as_secs(),
-				),
+				Err(error) => {
+					log::error!(
+						target: "bridge",
+						"Failed to connect to {} node: {:?}. Going to retry in {}s",
+						C::NAME,
+						error,
+						RECONNECT_DELAY.as_secs(),
+					);
+					// incompatible endpoint has already been marked bad and we have switched
+					if !matches!(error, Error::IncompatibleEndpoint { .. }) {
+						endpoints.switch();
+					}
+				},
 			}
 
 			async_std::task::sleep(RECONNECT_DELAY).await;
@@ -131,34 +160,79 @@ impl<C: Chain> RpcClient<C> {
 
 	/// Try to connect to Substrate node over websocket. Returns Substrate RPC client if connection
 	/// has been established or error otherwise.
-	async fn try_connect(params: Arc<ConnectionParams>) -> Result<Self> {
-		let (tokio, client) = Self::build_client(&params).await?;
-
-		let genesis_hash_client = client.clone();
-		let genesis_hash = tokio
-			.spawn(async move {
-				SubstrateChainClient::<C>::block_hash(&*genesis_hash_client, Some(Zero::zero()))
-					.await
-			})
-			.await??;
+	async fn try_connect(params: Arc<ConnectionParams>, endpoints: Endpoints<C>) -> Result<Self> {
+		let (tokio, client) = Self::build_client(&params, endpoints.current()?).await?;
+		let genesis_hash = Self::read_genesis_hash(&tokio, &client).await?;
+		endpoints.ensure_same_chain(genesis_hash)?;
+		let direct_inclusion = Self::build_direct_inclusion_client(&tokio, &params).await;
 
 		let chain_runtime_version = params.chain_runtime_version;
+		let in_flight = InFlightTransactions::new(params.max_in_flight_transactions);
+		let nonce_gaps = params.max_nonce_gap_fillers.map(NonceGaps::new);
+		let custom_runtime_version = match chain_runtime_version {
+			ChainRuntimeVersion::Auto => None,
+			ChainRuntimeVersion::Custom(version) =>
+				Some(Arc::new(parking_lot::Mutex::new(version))),
+		};
+		let signing_cache =
+			(custom_runtime_version.is_none() && params.signing_cache).then(SigningCache::default);
 		let mut client = Self {
 			params,
+			endpoints,
 			submit_signed_extrinsic_lock: Arc::new(Mutex::new(())),
+			in_flight,
+			nonce_gaps,
 			genesis_hash,
-			data: Arc::new(RwLock::new(ClientData { tokio, client })),
+			custom_runtime_version,
+			signing_cache,
+			data: Arc::new(RwLock::new(ClientData { tokio, client, direct_inclusion })),
 			_phantom: PhantomData,
 		};
-		Self::ensure_correct_runtime_version(&mut client, chain_runtime_version).await?;
+		let endpoints = client.endpoints.clone();
+		Self::ensure_correct_runtime_version(&mut client, chain_runtime_version, &endpoints)
+			.await?;
+		if let Some(ref custom_runtime_version) = client.custom_runtime_version {
+			on_transaction_version_change::<C>(
+				client.clone(),
+				client.params.transaction_version_bump,
+				custom_runtime_version.clone(),
+			);
+		}
+
+		// the task stops when all clones of the client are dropped and keeps using the
+		// current connection after reconnects
+		if let Some(keepalive_interval) = client.params.keepalive_interval {
+			let data = Arc::downgrade(&client.data);
+			async_std::task::spawn(keepalive(keepalive_interval, move || {
+				let data = data.clone();
+				async move {
+					let data = match data.upgrade() {
+						Some(data) => data,
+						None => return false,
+					};
+					if let Err(e) = ping_node::<C>(&data).await {
+						log::warn!(
+							target: "bridge",
+							"Keepalive request to {} node has failed: {:?}",
+							C::NAME,
+							e,
+						);
+					}
+					true
+				}
+			}));
+		}
+
 		Ok(client)
 	}
 
 	// Check runtime version to understand if we need are connected to expected version, or we
-	// need to wait for upgrade, we need to abort immediately.
+	// need to wait for upgrade, we need to abort immediately. If there are other endpoints, the
+	// node with newer runtime is marked bad instead of aborting.
 	async fn ensure_correct_runtime_version<E: Environment<C, Error = Error>>(
 		env: &mut E,
 		expected: ChainRuntimeVersion,
+		endpoints: &Endpoints<C>,
 	) -> Result<()> {
 		// we are only interested if version mode is bundled or passed using CLI
 		let expected = match expected {
@@ -173,6 +247,10 @@ impl<C: Chain> RpcClient<C> {
 			Ordering::Less =>
 				Err(Error::WaitingForRuntimeUpgrade { chain: C::NAME.into(), expected, actual }),
 			Ordering::Equal => Ok(()),
+			Ordering::Greater if endpoints.has_alternatives() => Err(endpoints.mark_bad(format!(
+				"node runs runtime version {actual:?} and the client is configured to use \
+				{expected:?}"
+			))),
 			Ordering::Greater => {
 				log::error!(
 					target: "bridge",
@@ -189,23 +267,9 @@ impl<C: Chain> RpcClient<C> {
 	/// Build client to use in connection.
 	async fn build_client(
 		params: &ConnectionParams,
-	) -> Result<(Arc<tokio::runtime::Runtime>, Arc<WsClient>)> {
+		uri: String,
+	) -> Result<(Arc<tokio::runtime::Runtime>, Arc<TracedWsClient>)> {
 		let tokio = tokio::runtime::Runtime::new()?;
-		let uri = match params.uri {
-			Some(ref uri) => uri.clone(),
-			None => {
-				format!(
-					"{}://{}:{}{}",
-					if params.secure { "wss" } else { "ws" },
-					params.host,
-					params.port,
-					match params.path {
-						Some(ref path) => format!("/{}", path),
-						None => String::new(),
-					},
-				)
-			},
-		};
 		log::info!(target: "bridge", "Connecting to {} node at {}", C::NAME, uri);
 
 		let client = tokio
@@ -216,14 +280,66 @@ impl<C: Chain> RpcClient<C> {
 					.await
 			})
 			.await??;
+		let client = TracedWsClient::new(C::NAME, client, params.rpc_trace);
 
 		Ok((Arc::new(tokio), Arc::new(client)))
 	}
 
+	/// Read genesis hash of the chain, served by the node.
+	async fn read_genesis_hash(
+		tokio: &tokio::runtime::Runtime,
+		client: &Arc<TracedWsClient>,
+	) -> Result<HashOf<C>> {
+		let client = client.clone();
+		Ok(tokio
+			.spawn(async move {
+				SubstrateChainClient::<C>::block_hash(&*client, Some(Zero::zero())).await
+			})
+			.await??)
+	}
+
+	/// Build client of the direct inclusion endpoint. The endpoint is optional, so connection
+	/// errors are only logged.
+	async fn build_direct_inclusion_client(
+		tokio: &tokio::runtime::Runtime,
+		params: &ConnectionParams,
+	) -> Option<Arc<TracedWsClient>> {
+		let uri = params.direct_inclusion_uri.clone()?;
+		log::info!(target: "bridge", "Connecting to {} direct inclusion endpoint at {}", C::NAME, uri);
+
+		let connect_uri = uri.clone();
+		let connect = async {
+			Ok::<_, Error>(
+				tokio
+					.spawn(async move {
+						WsClientBuilder::default()
+							.max_buffer_capacity_per_subscription(MAX_SUBSCRIPTION_CAPACITY)
+							.build(&connect_uri)
+							.await
+					})
+					.await??,
+			)
+		};
+		match connect.await {
+			Ok(client) => Some(Arc::new(TracedWsClient::new(C::NAME, client, params.rpc_trace))),
+			Err(e) => {
+				log::warn!(
+					target: "bridge",
+					"Failed to connect to {} direct inclusion endpoint at {}: {:?}. Transactions \
+					are submitted to the node",
+					C::NAME,
+					uri,
+					e,
+				);
+				None
+			},
+		}
+	}
+
 	/// Execute jsonrpsee future in tokio context.
 	async fn jsonrpsee_execute<MF, F, T>(&self, make_jsonrpsee_future: MF) -> Result<T>
 	where
-		MF: FnOnce(Arc<WsClient>) -> F + Send + 'static,
+		MF: FnOnce(Arc<TracedWsClient>) -> F + Send + 'static,
 		F: Future<Output = Result<T>> + Send + 'static,
 		T: Send + 'static,
 	{
@@ -232,12 +348,36 @@ impl<C: Chain> RpcClient<C> {
 		data.tokio.spawn(make_jsonrpsee_future(client)).await?
 	}
 
+	/// Execute jsonrpsee transaction submission future in tokio context. If the direct inclusion
+	/// endpoint is available, the transaction is submitted there first.
+	async fn jsonrpsee_submit<MF, F, T>(&self, make_jsonrpsee_future: MF) -> Result<T>
+	where
+		MF: Fn(Arc<TracedWsClient>) -> F + Send + 'static,
+		F: Future<Output = Result<T>> + Send + 'static,
+		T: Send + 'static,
+	{
+		let data = self.data.read().await;
+		let client = data.client.clone();
+		let direct_inclusion = data.direct_inclusion.clone();
+		data.tokio
+			.spawn(submit_with_fallback::<C, _, _, _, _>(
+				direct_inclusion,
+				client,
+				make_jsonrpsee_future,
+			))
+			.await?
+	}
+
 	/// Prepare parameters used to sign chain transactions.
 	async fn build_sign_params(&self, signer: AccountKeyPairOf<C>) -> Result<SignParam<C>>
 	where
 		C: ChainWithTransactions,
 	{
-		let runtime_version = self.simple_runtime_version().await?;
+		let runtime_version = match self.signing_cache {
+			Some(ref signing_cache) =>
+				signing_cache.runtime_version(|| self.simple_runtime_version()).await?,
+			None => self.simple_runtime_version().await?,
+		};
 		Ok(SignParam::<C> {
 			spec_version: runtime_version.spec_version,
 			transaction_version: runtime_version.transaction_version,
@@ -246,19 +386,103 @@ impl<C: Chain> RpcClient<C> {
 		})
 	}
 
-	/// Get the nonce of the given Substrate account.
+	/// Invalidate the signing cache if the runtime has been upgraded at the best block.
+	fn note_best_header_for_signing(&self, best_header: &HeaderOf<C>) {
+		if let Some(ref signing_cache) = self.signing_cache {
+			signing_cache.note_best_header(best_header);
+		}
+	}
+
+	/// Convert error of signing or validating the transaction into the submission error. If the
+	/// signature has been rejected, the signing cache is invalidated.
+	fn signing_error(&self, error: Error) -> Error {
+		if let Some(ref signing_cache) = self.signing_cache {
+			signing_cache.note_transaction_error(&error);
+		}
+		Error::failed_to_submit_transaction::<C>(error)
+	}
+
+	/// Get the nonce of the given Substrate account from the state, selected by the
+	/// `nonce_source` connection parameter.
 	pub async fn next_account_index(&self, account: AccountIdOf<C>) -> Result<NonceOf<C>> {
-		self.jsonrpsee_execute(move |client| async move {
-			Ok(SubstrateFrameSystemClient::<C>::account_next_index(&*client, account).await?)
-		})
+		let best_account_index = {
+			let account = account.clone();
+			self.jsonrpsee_execute(move |client| async move {
+				Ok(SubstrateFrameSystemClient::<C>::account_next_index(&*client, account).await?)
+			})
+		};
+		read_account_index(
+			self.params.nonce_source,
+			best_account_index,
+			self.finalized_account_index(account),
+		)
 		.await
 	}
 
+	/// Get the nonce of the given Substrate account at the best finalized block.
+	async fn finalized_account_index(&self, account: AccountIdOf<C>) -> Result<NonceOf<C>> {
+		let at = self.best_finalized_header_hash().await?;
+		let account_info = self.raw_storage_value(at, account_info_key(&account)).await?;
+		// the nonce is the first field of the `frame_system::AccountInfo`
+		Ok(account_info
+			.map(|account_info| NonceOf::<C>::decode(&mut &account_info.0[..]))
+			.transpose()?
+			.unwrap_or_default())
+	}
+
+	/// Returns nonce of the new transaction, signed by given signer. If the signer has
+	/// transactions that are stuck in the pool because of the nonce gap, the gap is filled
+	/// first.
+	///
+	/// Must be called under the `submit_signed_extrinsic_lock`.
+	async fn next_transaction_nonce(&self, signer: &AccountKeyPairOf<C>) -> Result<NonceOf<C>>
+	where
+		C: ChainWithTransactions,
+		AccountIdOf<C>: From<<AccountKeyPairOf<C> as Pair>::Public>,
+	{
+		let account_id: AccountIdOf<C> = signer.public().into();
+		loop {
+			let nonce = self.next_account_index(account_id.clone()).await?;
+			let missing_nonce = match self
+				.nonce_gaps
+				.as_ref()
+				.and_then(|nonce_gaps| nonce_gaps.missing_nonce(&account_id, nonce))
+			{
+				Some(missing_nonce) => missing_nonce,
+				None => return Ok(nonce),
+			};
+
+			log::warn!(
+				target: "bridge",
+				"{} transaction with nonce {} of {:?} is missing and later transactions are stuck \
+				in the pool. Submitting no-op transaction to fill the gap",
+				C::NAME,
+				missing_nonce,
+				account_id,
+			);
+			let signing_data = self.build_sign_params(signer.clone()).await?;
+			let filler = UnsignedTransaction::new(remark_call::<C>(), missing_nonce);
+			let signed_filler = C::sign_transaction(signing_data, filler)?.encode();
+			self.submit_unsigned_extrinsic(Bytes(signed_filler)).await?;
+		}
+	}
+
+	/// Remember that the transaction with given nonce has been submitted.
+	fn note_submitted_nonce(&self, signer: &AccountKeyPairOf<C>, nonce: NonceOf<C>)
+	where
+		C: ChainWithTransactions,
+		AccountIdOf<C>: From<<AccountKeyPairOf<C> as Pair>::Public>,
+	{
+		if let Some(ref nonce_gaps) = self.nonce_gaps {
+			nonce_gaps.note_submitted(signer.public().into(), nonce);
+		}
+	}
+
 	/// Subscribe to finality justifications.
 	async fn subscribe_finality_justifications<Fut>(
 		&self,
 		gadget_name: &str,
-		do_subscribe: impl FnOnce(Arc<WsClient>) -> Fut + Send + 'static,
+		do_subscribe: impl FnOnce(Arc<TracedWsClient>) -> Fut + Send + 'static,
 	) -> Result<Subscription<Bytes>>
 	where
 		Fut: Future<Output = std::result::Result<RpcSubscription<Bytes>, ClientError>> + Send,
@@ -278,7 +502,7 @@ impl<C: Chain> RpcClient<C> {
 	async fn subscribe_headers<Fut>(
 		&self,
 		stream_name: &str,
-		do_subscribe: impl FnOnce(Arc<WsClient>) -> Fut + Send + 'static,
+		do_subscribe: impl FnOnce(Arc<TracedWsClient>) -> Fut + Send + 'static,
 		map_err: impl FnOnce(Error) -> Error,
 	) -> Result<Subscription<HeaderOf<C>>>
 	where
@@ -294,14 +518,102 @@ impl<C: Chain> RpcClient<C> {
 			subscription,
 		))
 	}
+
+	/// Submit transaction to the node without any checks.
+	async fn send_extrinsic(&self, transaction: Bytes) -> Result<HashOf<C>> {
+		self.jsonrpsee_submit(move |client| {
+			let transaction = transaction.clone();
+			async move {
+				let tx_hash = SubstrateAuthorClient::<C>::submit_extrinsic(&*client, transaction)
+					.await
+					.map_err(|e| {
+						log::error!(target: "bridge", "Failed to send transaction to {} node: {:?}", C::NAME, e);
+						e
+					})?;
+				log::trace!(target: "bridge", "Sent transaction to {} node: {:?}", C::NAME, tx_hash);
+				Ok(tx_hash)
+			}
+		})
+		.await
+		.map_err(|e| Error::failed_to_submit_transaction::<C>(e))
+	}
+}
+
+/// Sign transaction and check that it is valid at the `at` block.
+///
+/// If the mortal transaction is rejected for the reason that may be caused by its era (the
+/// reference block is unknown or has been replaced by reorg), the era, the reference block and
+/// the current reference block are logged. Then the transaction is signed against the refreshed
+/// reference block and checked once more at the refreshed best block.
+async fn sign_and_validate_transaction<C, ValidateFut, RefreshFut>(
+	extrinsic: UnsignedTransaction<C>,
+	at: HashOf<C>,
+	sign: impl Fn(UnsignedTransaction<C>) -> Result<Vec<u8>>,
+	validate: impl Fn(HashOf<C>, Vec<u8>) -> ValidateFut,
+	refresh_reference: impl FnOnce() -> RefreshFut,
+) -> Result<(UnsignedTransaction<C>, Vec<u8>)>
+where
+	C: Chain,
+	ValidateFut: Future<Output = Result<TransactionValidity>>,
+	RefreshFut: Future<Output = Result<(HashOf<C>, HeaderIdOf<C>)>>,
+{
+	let signed_extrinsic = sign(extrinsic.clone())?;
+	let error = match validate(at, signed_extrinsic.clone()).await? {
+		Ok(_) => return Ok((extrinsic, signed_extrinsic)),
+		Err(error) => error,
+	};
+	let (reference, mortality_period) = match extrinsic.era {
+		TransactionEra::Mortal(reference, mortality_period) if is_mortality_error(&error) =>
+			(reference, mortality_period),
+		_ => return Err(Error::TransactionInvalid(error)),
+	};
+
+	let (refreshed_at, refreshed_reference) = refresh_reference().await?;
+	log::warn!(
+		target: "bridge",
+		"{} transaction with era {:?} has been rejected: {:?}. Reference block: {:?}, current \
+		reference block: {:?}. Retrying with the current reference block",
+		C::NAME,
+		extrinsic.era.frame_era(),
+		error,
+		reference,
+		refreshed_reference,
+	);
+
+	let extrinsic = UnsignedTransaction {
+		era: TransactionEra::Mortal(refreshed_reference, mortality_period),
+		..extrinsic
+	};
+	let signed_extrinsic = sign(extrinsic.clone())?;
+	validate(refreshed_at, signed_extrinsic.clone())
+		.await?
+		.map_err(Error::TransactionInvalid)?;
+	Ok((extrinsic, signed_extrinsic))
+}
+
+/// Returns true if the transaction may have been rejected because of its mortality era. The
+/// reference block hash is a part of the signature payload, so the wrong reference block leads
+/// to the bad signature.
+fn is_mortality_error(error: &TransactionValidityError) -> bool {
+	matches!(
+		error,
+		TransactionValidityError::Invalid(
+			InvalidTransaction::AncientBirthBlock | InvalidTransaction::BadProof
+		)
+	)
 }
 
 impl<C: Chain> Clone for RpcClient<C> {
 	fn clone(&self) -> Self {
 		RpcClient {
 			params: self.params.clone(),
+			endpoints: self.endpoints.clone(),
 			submit_signed_extrinsic_lock: self.submit_signed_extrinsic_lock.clone(),
+			in_flight: self.in_flight.clone(),
+			nonce_gaps: self.nonce_gaps.clone(),
 			genesis_hash: self.genesis_hash,
+			custom_runtime_version: self.custom_runtime_version.clone(),
+			signing_cache: self.signing_cache.clone(),
 			data: self.data.clone(),
 			_phantom: PhantomData,
 		}
@@ -328,10 +640,32 @@ impl<C: Chain> Client<C> for RpcClient<C> {
 
 	async fn reconnect(&self) -> Result<()> {
 		let mut data = self.data.write().await;
-		let (tokio, client) = Self::build_client(&self.params).await?;
+		let (tokio, client) =
+			match Self::build_client(&self.params, self.endpoints.current()?).await {
+				Ok(connection) => connection,
+				Err(error) => {
+					// next reconnect attempt is made to the other endpoint
+					self.endpoints.switch();
+					return Err(error)
+				},
+			};
+		self.endpoints
+			.ensure_same_chain(Self::read_genesis_hash(&tokio, &client).await?)?;
+		data.direct_inclusion = Self::build_direct_inclusion_client(&tokio, &self.params).await;
 		data.tokio = tokio;
 		data.client = client;
-		Ok(())
+		drop(data);
+		if let Some(ref signing_cache) = self.signing_cache {
+			signing_cache.invalidate("client has reconnected");
+		}
+
+		// the node that we have switched to may be running other runtime
+		Self::ensure_correct_runtime_version(
+			&mut self.clone(),
+			self.params.chain_runtime_version,
+			&self.endpoints,
+		)
+		.await
 	}
 
 	fn genesis_hash(&self) -> HashOf<C> {
@@ -446,17 +780,19 @@ impl<C: Chain> Client<C> for RpcClient<C> {
 	}
 
 	async fn simple_runtime_version(&self) -> Result<SimpleRuntimeVersion> {
-		Ok(match self.params.chain_runtime_version {
-			ChainRuntimeVersion::Auto => {
+		Ok(match self.custom_runtime_version {
+			Some(ref version) => *version.lock(),
+			None => {
 				let runtime_version = self.runtime_version().await?;
 				SimpleRuntimeVersion::from_runtime_version(&runtime_version)
 			},
-			ChainRuntimeVersion::Custom(ref version) => *version,
 		})
 	}
 
 	fn can_start_version_guard(&self) -> bool {
-		!matches!(self.params.chain_runtime_version, ChainRuntimeVersion::Auto)
+		// the refreshed runtime version is used after the spec version is changed
+		!matches!(self.params.chain_runtime_version, ChainRuntimeVersion::Auto) &&
+			self.params.transaction_version_bump != TransactionVersionBump::Refresh
 	}
 
 	async fn raw_storage_value(
@@ -489,18 +825,7 @@ impl<C: Chain> Client<C> for RpcClient<C> {
 			.map_err(|e| Error::failed_to_submit_transaction::<C>(e))?
 			.map_err(|e| Error::failed_to_submit_transaction::<C>(Error::TransactionInvalid(e)))?;
 
-		self.jsonrpsee_execute(move |client| async move {
-			let tx_hash = SubstrateAuthorClient::<C>::submit_extrinsic(&*client, transaction)
-				.await
-				.map_err(|e| {
-					log::error!(target: "bridge", "Failed to send transaction to {} node: {:?}", C::NAME, e);
-					e
-				})?;
-			log::trace!(target: "bridge", "Sent transaction to {} node: {:?}", C::NAME, tx_hash);
-			Ok(tx_hash)
-		})
-		.await
-		.map_err(|e| Error::failed_to_submit_transaction::<C>(e))
+		self.send_extrinsic(transaction).await
 	}
 
 	async fn submit_signed_extrinsic(
@@ -515,8 +840,9 @@ impl<C: Chain> Client<C> for RpcClient<C> {
 		AccountIdOf<C>: From<<AccountKeyPairOf<C> as Pair>::Public>,
 	{
 		let _guard = self.submit_signed_extrinsic_lock.lock().await;
-		let transaction_nonce = self.next_account_index(signer.public().into()).await?;
+		let transaction_nonce = self.next_transaction_nonce(signer).await?;
 		let best_header = self.best_header().await?;
+		self.note_best_header_for_signing(&best_header);
 		let signing_data = self.build_sign_params(signer.clone()).await?;
 
 		// By using parent of best block here, we are protecting again best-block reorganizations.
@@ -527,8 +853,26 @@ impl<C: Chain> Client<C> for RpcClient<C> {
 		let best_header_id = best_header.parent_id().unwrap_or_else(|| best_header.id());
 
 		let extrinsic = prepare_extrinsic(best_header_id, transaction_nonce)?;
-		let signed_extrinsic = C::sign_transaction(signing_data, extrinsic)?.encode();
-		self.submit_unsigned_extrinsic(Bytes(signed_extrinsic)).await
+		// one last check that the transaction is valid. Most of checks happen in the relay loop and
+		// it is the "final" check before submission.
+		let (_, signed_extrinsic) = sign_and_validate_transaction(
+			extrinsic,
+			best_header.hash(),
+			|extrinsic| Ok(C::sign_transaction(signing_data.clone(), extrinsic)?.encode()),
+			|at, signed_extrinsic| self.validate_transaction(at, PreEncoded(signed_extrinsic)),
+			|| async {
+				let best_header = self.best_header().await?;
+				Ok((
+					best_header.hash(),
+					best_header.parent_id().unwrap_or_else(|| best_header.id()),
+				))
+			},
+		)
+		.await
+		.map_err(|e| self.signing_error(e))?;
+		let tx_hash = self.send_extrinsic(Bytes(signed_extrinsic)).await?;
+		self.note_submitted_nonce(signer, transaction_nonce);
+		Ok(tx_hash)
 	}
 
 	async fn submit_and_watch_signed_extrinsic(
@@ -542,11 +886,14 @@ impl<C: Chain> Client<C> for RpcClient<C> {
 		C: ChainWithTransactions,
 		AccountIdOf<C>: From<<AccountKeyPairOf<C> as Pair>::Public>,
 	{
+		// the slot is occupied until the tracker is dropped
+		let in_flight_slot = self.in_flight.acquire::<C>().await;
 		let self_clone = self.clone();
-		let signing_data = self.build_sign_params(signer.clone()).await?;
 		let _guard = self.submit_signed_extrinsic_lock.lock().await;
-		let transaction_nonce = self.next_account_index(signer.public().into()).await?;
+		let transaction_nonce = self.next_transaction_nonce(signer).await?;
 		let best_header = self.best_header().await?;
+		self.note_best_header_for_signing(&best_header);
+		let signing_data = self.build_sign_params(signer.clone()).await?;
 		let best_header_id = best_header.id();
 
 		let extrinsic = prepare_extrinsic(best_header_id, transaction_nonce)?;
@@ -555,42 +902,65 @@ impl<C: Chain> Client<C> for RpcClient<C> {
 			C::AVERAGE_BLOCK_INTERVAL,
 			STALL_TIMEOUT,
 		);
-		let signed_extrinsic = C::sign_transaction(signing_data, extrinsic)?.encode();
 
 		// one last check that the transaction is valid. Most of checks happen in the relay loop and
 		// it is the "final" check before submission.
-		self.validate_transaction(best_header_id.hash(), PreEncoded(signed_extrinsic.clone()))
-			.await
-			.map_err(|e| Error::failed_to_submit_transaction::<C>(e))?
-			.map_err(|e| Error::failed_to_submit_transaction::<C>(Error::TransactionInvalid(e)))?;
-
-		self.jsonrpsee_execute(move |client| async move {
-			let tx_hash = C::Hasher::hash(&signed_extrinsic);
-			let subscription: jsonrpsee::core::client::Subscription<_> =
-				SubstrateAuthorClient::<C>::submit_and_watch_extrinsic(
-					&*client,
-					Bytes(signed_extrinsic),
-				)
-				.await
-				.map_err(|e| {
-					log::error!(target: "bridge", "Failed to send transaction to {} node: {:?}", C::NAME, e);
-					e
-				})?;
-			log::trace!(target: "bridge", "Sent transaction to {} node: {:?}", C::NAME, tx_hash);
-			Ok(TransactionTracker::new(
-				self_clone,
-				stall_timeout,
-				tx_hash,
-				Subscription::new_forwarded(
-					StreamDescription::new("transaction events".into(), C::NAME.into()),
-					subscription,
-				),
-			))
+		let (_, signed_extrinsic) = sign_and_validate_transaction(
+			extrinsic,
+			best_header_id.hash(),
+			|extrinsic| Ok(C::sign_transaction(signing_data.clone(), extrinsic)?.encode()),
+			|at, signed_extrinsic| self.validate_transaction(at, PreEncoded(signed_extrinsic)),
+			|| async {
+				let best_header_id = self.best_header().await?.id();
+				Ok((best_header_id.hash(), best_header_id))
+			},
+		)
+		.await
+		.map_err(|e| self.signing_error(e))?;
+
+		self.jsonrpsee_submit(move |client| {
+			let self_clone = self_clone.clone();
+			let signed_extrinsic = signed_extrinsic.clone();
+			async move {
+				let tx_hash = C::Hasher::hash(&signed_extrinsic);
+				let subscription: jsonrpsee::core::client::Subscription<_> =
+					SubstrateAuthorClient::<C>::submit_and_watch_extrinsic(
+						&*client,
+						Bytes(signed_extrinsic),
+					)
+					.await
+					.map_err(|e| {
+						log::error!(target: "bridge", "Failed to send transaction to {} node: {:?}", C::NAME, e);
+						e
+					})?;
+				log::trace!(target: "bridge", "Sent transaction to {} node: {:?}", C::NAME, tx_hash);
+				Ok(TransactionTracker::new(
+					self_clone,
+					stall_timeout,
+					tx_hash,
+					Subscription::new_forwarded(
+						StreamDescription::new("transaction events".into(), C::NAME.into()),
+						subscription,
+					),
+				))
+			}
 		})
 		.await
+		.map(|tx_tracker| {
+			self.note_submitted_nonce(signer, transaction_nonce);
+			tx_tracker.with_in_flight_slot(in_flight_slot)
+		})
 		.map_err(|e| Error::failed_to_submit_transaction::<C>(e))
 	}
 
+	fn in_flight_transactions(&self) -> InFlightTransactions {
+		self.in_flight.clone()
+	}
+
+	fn endpoints(&self) -> Endpoints<C> {
+		self.endpoints.clone()
+	}
+
 	async fn validate_transaction<SignedTransaction: Encode + Send + 'static>(
 		&self,
 		at: HashOf<C>,
@@ -617,6 +987,19 @@ impl<C: Chain> Client<C> for RpcClient<C> {
 		Ok(dispatch_info.weight)
 	}
 
+	async fn estimate_extrinsic_fee<SignedTransaction: Encode + Send + 'static>(
+		&self,
+		at: HashOf<C>,
+		transaction: SignedTransaction,
+	) -> Result<BalanceOf<C>> {
+		let transaction_len = transaction.encoded_size() as u32;
+		let dispatch_info: RuntimeDispatchInfo<BalanceOf<C>> = self
+			.state_call(at, SUB_API_TX_PAYMENT_QUERY_INFO.into(), (transaction, transaction_len))
+			.await?;
+
+		Ok(dispatch_info.partial_fee)
+	}
+
 	async fn raw_state_call<Args: Encode + Send>(
 		&self,
 		at: HashOf<C>,
@@ -648,15 +1031,106 @@ impl<C: Chain> Client<C> for RpcClient<C> {
 	}
 }
 
+/// Call `ping` with given interval, until it returns `false`.
+async fn keepalive<F, Fut>(interval: Duration, mut ping: F)
+where
+	F: FnMut() -> Fut,
+	Fut: Future<Output = bool>,
+{
+	loop {
+		async_std::task::sleep(interval).await;
+		if !ping().await {
+			return
+		}
+	}
+}
+
+/// Call the lightweight `system_health` RPC method, exercising the connection to the node.
+async fn ping_node<C: Chain>(data: &RwLock<ClientData>) -> Result<()> {
+	let (tokio, client) = {
+		let data = data.read().await;
+		(data.tokio.clone(), data.client.clone())
+	};
+	tokio
+		.spawn(async move { SubstrateSystemClient::<C>::health(&*client).await })
+		.await??;
+	Ok(())
+}
+
+/// Read account nonce using one of given futures, selected by the `nonce_source`. Only the
+/// selected future is polled.
+async fn read_account_index<Nonce>(
+	nonce_source: NonceSource,
+	best_account_index: impl Future<Output = Result<Nonce>>,
+	finalized_account_index: impl Future<Output = Result<Nonce>>,
+) -> Result<Nonce> {
+	match nonce_source {
+		NonceSource::Best => best_account_index.await,
+		NonceSource::Finalized => finalized_account_index.await,
+	}
+}
+
+/// Submit transaction to the direct inclusion endpoint, if it is available. If the endpoint
+/// rejects the transaction or fails, the transaction is submitted to the node.
+async fn submit_with_fallback<C: Chain, Endpoint, MF, F, T>(
+	direct_inclusion: Option<Endpoint>,
+	node: Endpoint,
+	submit: MF,
+) -> Result<T>
+where
+	MF: Fn(Endpoint) -> F,
+	F: Future<Output = Result<T>>,
+{
+	if let Some(direct_inclusion) = direct_inclusion {
+		match submit(direct_inclusion).await {
+			Ok(result) => return Ok(result),
+			Err(e) => log::warn!(
+				target: "bridge",
+				"{} direct inclusion endpoint has failed to accept transaction: {:?}. Submitting \
+				it to the node",
+				C::NAME,
+				e,
+			),
+		}
+	}
+
+	submit(node).await
+}
+
+/// Returns URIs of all endpoints from the connection params. The primary endpoint comes first.
+fn endpoint_uris(params: &ConnectionParams) -> Vec<String> {
+	let primary = match params.uri {
+		Some(ref uri) => uri.clone(),
+		None => format!(
+			"{}://{}:{}{}",
+			if params.secure { "wss" } else { "ws" },
+			params.host,
+			params.port,
+			match params.path {
+				Some(ref path) => format!("/{}", path),
+				None => String::new(),
+			},
+		),
+	};
+	std::iter::once(primary).chain(params.fallback_uris.iter().cloned()).collect()
+}
+
+/// Storage key of the `System::Account` entry of given account.
+fn account_info_key<AccountId: Encode>(account: &AccountId) -> StorageKey {
+	storage_map_final_key::<Blake2_128Concat>("System", "Account", &account.encode())
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use crate::{guard::tests::TestEnvironment, test_chain::TestChain};
+	use crate::{guard::tests::TestEnvironment, test_chain::TestChain, EncodedOrDecodedCall};
+	use bp_runtime::HeaderId;
 	use futures::{channel::mpsc::unbounded, FutureExt, SinkExt, StreamExt};
 
 	async fn run_ensure_correct_runtime_version(
 		expected: ChainRuntimeVersion,
 		actual: RuntimeVersion,
+		endpoints: Endpoints<TestChain>,
 	) -> Result<()> {
 		let (
 			(mut runtime_version_tx, runtime_version_rx),
@@ -667,7 +1141,8 @@ mod tests {
 		let mut env = TestEnvironment { runtime_version_rx, slept_tx, aborted_tx };
 
 		let ensure_correct_runtime_version =
-			RpcClient::<TestChain>::ensure_correct_runtime_version(&mut env, expected).boxed();
+			RpcClient::<TestChain>::ensure_correct_runtime_version(&mut env, expected, &endpoints)
+				.boxed();
 		let aborted = aborted_rx.next().map(|_| Err(Error::Custom("".into()))).boxed();
 		futures::pin_mut!(ensure_correct_runtime_version, aborted);
 		futures::future::select(ensure_correct_runtime_version, aborted)
@@ -687,6 +1162,7 @@ mod tests {
 					transaction_version: 100,
 					..Default::default()
 				},
+				Endpoints::new(vec!["ws://node".into()]),
 			)
 			.await,
 			Ok(()),
@@ -703,6 +1179,7 @@ mod tests {
 					transaction_version: 100,
 					..Default::default()
 				},
+				Endpoints::new(vec!["ws://node".into()]),
 			)
 			.await,
 			Ok(()),
@@ -715,6 +1192,7 @@ mod tests {
 					transaction_version: 100
 				}),
 				RuntimeVersion { spec_version: 99, transaction_version: 100, ..Default::default() },
+				Endpoints::new(vec!["ws://node".into()]),
 			)
 			.await,
 			Err(Error::WaitingForRuntimeUpgrade {
@@ -735,9 +1213,184 @@ mod tests {
 					transaction_version: 100,
 					..Default::default()
 				},
+				Endpoints::new(vec!["ws://node".into()]),
 			)
 			.await,
 			Err(Error::Custom(_)),
 		));
+		// when actual spec version > expected spec version and there are other endpoints
+		let endpoints = Endpoints::new(vec!["ws://good".into(), "ws://upgraded".into()]);
+		endpoints.switch();
+		assert!(matches!(
+			run_ensure_correct_runtime_version(
+				ChainRuntimeVersion::Custom(SimpleRuntimeVersion {
+					spec_version: 100,
+					transaction_version: 100
+				}),
+				RuntimeVersion {
+					spec_version: 101,
+					transaction_version: 100,
+					..Default::default()
+				},
+				endpoints.clone(),
+			)
+			.await,
+			Err(Error::IncompatibleEndpoint { ref uri, .. }) if uri == "ws://upgraded",
+		));
+		assert_eq!(endpoints.current().unwrap(), "ws://good");
+		assert_eq!(endpoints.bad_endpoints(), 1);
+	}
+
+	fn reference_block(number: u32) -> HeaderIdOf<TestChain> {
+		HeaderId(number, sp_core::H256::repeat_byte(number as u8))
+	}
+
+	async fn run_sign_and_validate_transaction(
+		era: TransactionEra<u32, sp_core::H256>,
+		error: TransactionValidityError,
+	) -> Result<(UnsignedTransaction<TestChain>, Vec<u8>)> {
+		// the transaction is only valid if it uses the block 20 as the reference block
+		let valid_signed_extrinsic = reference_block(20).1.encode();
+		sign_and_validate_transaction(
+			UnsignedTransaction::new(EncodedOrDecodedCall::Encoded(vec![]), 0).era(era),
+			reference_block(10).1,
+			|extrinsic| Ok(extrinsic.era.signed_payload(Default::default()).encode()),
+			|_, signed_extrinsic| {
+				futures::future::ready(Ok(if signed_extrinsic == valid_signed_extrinsic {
+					Ok(Default::default())
+				} else {
+					Err(error)
+				}))
+			},
+			|| futures::future::ready(Ok((reference_block(20).1, reference_block(20)))),
+		)
+		.await
+	}
+
+	#[async_std::test]
+	async fn transaction_rejected_because_of_mortality_is_retried_with_refreshed_era() {
+		let (extrinsic, signed_extrinsic) = run_sign_and_validate_transaction(
+			TransactionEra::new(reference_block(10), Some(64)),
+			TransactionValidityError::Invalid(InvalidTransaction::AncientBirthBlock),
+		)
+		.await
+		.unwrap();
+		assert_eq!(extrinsic.era, TransactionEra::new(reference_block(20), Some(64)));
+		assert_eq!(signed_extrinsic, reference_block(20).1.encode());
+	}
+
+	#[async_std::test]
+	async fn transaction_rejected_for_other_reason_is_not_retried() {
+		assert!(matches!(
+			run_sign_and_validate_transaction(
+				TransactionEra::new(reference_block(10), Some(64)),
+				TransactionValidityError::Invalid(InvalidTransaction::Stale),
+			)
+			.await,
+			Err(Error::TransactionInvalid(TransactionValidityError::Invalid(
+				InvalidTransaction::Stale
+			))),
+		));
+		assert!(matches!(
+			run_sign_and_validate_transaction(
+				TransactionEra::Immortal,
+				TransactionValidityError::Invalid(InvalidTransaction::BadProof),
+			)
+			.await,
+			Err(Error::TransactionInvalid(TransactionValidityError::Invalid(
+				InvalidTransaction::BadProof
+			))),
+		));
+	}
+
+	#[async_std::test]
+	async fn account_index_is_read_from_selected_state() {
+		let read = |nonce_source| {
+			read_account_index(
+				nonce_source,
+				futures::future::ready(Ok(10u32)),
+				futures::future::ready(Ok(8u32)),
+			)
+		};
+		assert_eq!(read(NonceSource::Best).await.unwrap(), 10);
+		assert_eq!(read(NonceSource::Finalized).await.unwrap(), 8);
+	}
+
+	#[test]
+	fn nonce_source_is_parsed() {
+		assert_eq!("best".parse::<NonceSource>(), Ok(NonceSource::Best));
+		assert_eq!("finalized".parse::<NonceSource>(), Ok(NonceSource::Finalized));
+		assert!("latest".parse::<NonceSource>().is_err());
+	}
+
+	/// Mock of the endpoint, accepting transactions.
+	#[derive(Clone, Copy, Debug, PartialEq)]
+	struct MockEndpoint {
+		name: &'static str,
+		accepts: bool,
+	}
+
+	impl MockEndpoint {
+		async fn submit(self, submitted: Arc<Mutex<Vec<&'static str>>>) -> Result<&'static str> {
+			submitted.lock().await.push(self.name);
+			if self.accepts {
+				Ok(self.name)
+			} else {
+				Err(Error::Custom(format!("{} has rejected the transaction", self.name)))
+			}
+		}
+	}
+
+	#[async_std::test]
+	async fn transaction_is_submitted_to_node_if_direct_inclusion_endpoint_fails() {
+		let node = MockEndpoint { name: "node", accepts: true };
+		let submit = |direct_inclusion: Option<MockEndpoint>| async move {
+			let submitted = Arc::new(Mutex::new(Vec::new()));
+			let result =
+				submit_with_fallback::<TestChain, _, _, _, _>(direct_inclusion, node, |e| {
+					e.submit(submitted.clone())
+				})
+				.await;
+			let submitted = submitted.lock().await.clone();
+			(result.ok(), submitted)
+		};
+
+		// when direct inclusion endpoint accepts the transaction, node is not used
+		assert_eq!(
+			submit(Some(MockEndpoint { name: "direct", accepts: true })).await,
+			(Some("direct"), vec!["direct"]),
+		);
+		// when direct inclusion endpoint rejects the transaction, it is submitted to the node
+		assert_eq!(
+			submit(Some(MockEndpoint { name: "direct", accepts: false })).await,
+			(Some("node"), vec!["direct", "node"]),
+		);
+		// when direct inclusion endpoint is not available, transaction is submitted to the node
+		assert_eq!(submit(None).await, (Some("node"), vec!["node"]));
+	}
+
+	#[async_std::test]
+	async fn keepalive_fires_at_configured_interval() {
+		let interval = Duration::from_millis(20);
+		let pings = Arc::new(Mutex::new(Vec::new()));
+		let start = std::time::Instant::now();
+		keepalive(interval, || {
+			let pings = pings.clone();
+			async move {
+				let mut pings = pings.lock().await;
+				pings.push(start.elapsed());
+				pings.len() < 3
+			}
+		})
+		.await;
+
+		// every ping is made after the interval since the previous one
+		let pings = pings.lock().await.clone();
+		assert_eq!(pings.len(), 3);
+		let mut previous = Duration::ZERO;
+		for ping in pings {
+			assert!(ping - previous >= interval);
+			previous = ping;
+		}
 	}
 }
@@ -0,0 +1,287 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tracing of the raw JSON-RPC traffic between the client and the node.
+//!
+//! The traffic is huge, so it is only traced if it is enabled in the connection params. Even
+//! then, requests and responses are logged with the `trace` level under the `bridge-rpc`
+//! target, which must be enabled explicitly (e.g. `RUST_LOG=bridge-rpc=trace`).
+//!
+//! Subscription notifications are not traced - only the subscription requests are.
+
+use async_trait::async_trait;
+use jsonrpsee::{
+	core::{
+		client::{BatchResponse, ClientT, Subscription, SubscriptionClientT},
+		params::BatchRequestBuilder,
+		traits::ToRpcParams,
+		ClientError, DeserializeOwned, JsonRawValue, JsonValue,
+	},
+	ws_client::WsClient,
+};
+use std::borrow::Cow;
+
+/// Log target of the RPC traffic.
+const LOG_TARGET: &str = "bridge-rpc";
+/// Maximal length of the traced request params or response, in bytes. Longer payloads are
+/// truncated.
+const DEFAULT_MAX_TRACED_PAYLOAD_LEN: usize = 4096;
+/// RPC methods with signed transactions in their params.
+const SUBMIT_TRANSACTION_METHODS: [&str; 2] =
+	["author_submitExtrinsic", "author_submitAndWatchExtrinsic"];
+
+/// RPC traffic tracing parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RpcTraceParams {
+	/// Maximal length of the traced request params or response, in bytes.
+	pub max_payload_len: usize,
+	/// If true, signed transactions are not logged.
+	pub redact_transactions: bool,
+}
+
+impl Default for RpcTraceParams {
+	fn default() -> Self {
+		RpcTraceParams {
+			max_payload_len: DEFAULT_MAX_TRACED_PAYLOAD_LEN,
+			redact_transactions: false,
+		}
+	}
+}
+
+/// Websocket client that is optionally tracing RPC traffic.
+#[derive(Debug)]
+pub struct TracedWsClient {
+	/// Name of the chain, the client is connected to.
+	chain: &'static str,
+	/// Underlying client.
+	client: WsClient,
+	/// Tracing parameters. If `None`, the traffic is not traced.
+	params: Option<RpcTraceParams>,
+}
+
+impl TracedWsClient {
+	/// Wrap the websocket client.
+	pub fn new(chain: &'static str, client: WsClient, params: Option<RpcTraceParams>) -> Self {
+		TracedWsClient { chain, client, params }
+	}
+
+	/// Trace RPC request.
+	fn trace_request(&self, params: &RpcTraceParams, method: &str, payload: Option<&str>) {
+		log::trace!(
+			target: LOG_TARGET,
+			"{} RPC request {}: {}",
+			self.chain,
+			method,
+			traced_request_payload(params, method, payload),
+		);
+	}
+
+	/// Trace RPC response.
+	fn trace_response(
+		&self,
+		params: &RpcTraceParams,
+		method: &str,
+		response: Result<&dyn std::fmt::Display, &ClientError>,
+	) {
+		match response {
+			Ok(response) => log::trace!(
+				target: LOG_TARGET,
+				"{} RPC response {}: {}",
+				self.chain,
+				method,
+				truncate(&response.to_string(), params.max_payload_len),
+			),
+			Err(e) => log::trace!(
+				target: LOG_TARGET,
+				"{} RPC error {}: {:?}",
+				self.chain,
+				method,
+				e,
+			),
+		}
+	}
+}
+
+#[async_trait]
+impl ClientT for TracedWsClient {
+	async fn notification<Params>(&self, method: &str, params: Params) -> Result<(), ClientError>
+	where
+		Params: ToRpcParams + Send,
+	{
+		let trace_params = match self.params {
+			Some(ref trace_params) => trace_params,
+			None => return self.client.notification(method, params).await,
+		};
+
+		let params = params.to_rpc_params()?;
+		self.trace_request(trace_params, method, params.as_deref().map(JsonRawValue::get));
+		self.client.notification(method, RawParams(params)).await
+	}
+
+	async fn request<R, Params>(&self, method: &str, params: Params) -> Result<R, ClientError>
+	where
+		R: DeserializeOwned,
+		Params: ToRpcParams + Send,
+	{
+		let trace_params = match self.params {
+			Some(ref trace_params) => trace_params,
+			None => return self.client.request(method, params).await,
+		};
+
+		let params = params.to_rpc_params()?;
+		self.trace_request(trace_params, method, params.as_deref().map(JsonRawValue::get));
+		let response: Result<JsonValue, _> = self.client.request(method, RawParams(params)).await;
+		self.trace_response(
+			trace_params,
+			method,
+			response.as_ref().map(|response| response as &dyn std::fmt::Display),
+		);
+		Ok(serde_json::from_value(response?)?)
+	}
+
+	async fn batch_request<'a, R>(
+		&self,
+		batch: BatchRequestBuilder<'a>,
+	) -> Result<BatchResponse<'a, R>, ClientError>
+	where
+		R: DeserializeOwned + std::fmt::Debug + 'a,
+	{
+		self.client.batch_request(batch).await
+	}
+}
+
+#[async_trait]
+impl SubscriptionClientT for TracedWsClient {
+	async fn subscribe<'a, Notif, Params>(
+		&self,
+		subscribe_method: &'a str,
+		params: Params,
+		unsubscribe_method: &'a str,
+	) -> Result<Subscription<Notif>, ClientError>
+	where
+		Params: ToRpcParams + Send,
+		Notif: DeserializeOwned,
+	{
+		let trace_params = match self.params {
+			Some(ref trace_params) => trace_params,
+			None =>
+				return self.client.subscribe(subscribe_method, params, unsubscribe_method).await,
+		};
+
+		let params = params.to_rpc_params()?;
+		self.trace_request(
+			trace_params,
+			subscribe_method,
+			params.as_deref().map(JsonRawValue::get),
+		);
+		let subscription = self
+			.client
+			.subscribe(subscribe_method, RawParams(params), unsubscribe_method)
+			.await;
+		self.trace_response(
+			trace_params,
+			subscribe_method,
+			subscription.as_ref().map(|_| &"subscribed" as &dyn std::fmt::Display),
+		);
+		subscription
+	}
+
+	async fn subscribe_to_method<'a, Notif>(
+		&self,
+		method: &'a str,
+	) -> Result<Subscription<Notif>, ClientError>
+	where
+		Notif: DeserializeOwned,
+	{
+		if let Some(ref trace_params) = self.params {
+			self.trace_request(trace_params, method, None);
+		}
+		self.client.subscribe_to_method(method).await
+	}
+}
+
+/// Params that have already been serialized.
+struct RawParams(Option<Box<JsonRawValue>>);
+
+impl ToRpcParams for RawParams {
+	fn to_rpc_params(self) -> Result<Option<Box<JsonRawValue>>, serde_json::Error> {
+		Ok(self.0)
+	}
+}
+
+/// Returns traced params of the RPC request.
+fn traced_request_payload<'a>(
+	params: &RpcTraceParams,
+	method: &str,
+	payload: Option<&'a str>,
+) -> Cow<'a, str> {
+	let payload = match payload {
+		Some(payload) => payload,
+		None => return Cow::Borrowed("[]"),
+	};
+	if params.redact_transactions && SUBMIT_TRANSACTION_METHODS.contains(&method) {
+		return Cow::Owned(format!("<redacted {} bytes>", payload.len()))
+	}
+
+	truncate(payload, params.max_payload_len)
+}
+
+/// Truncate the payload to at most `max_len` bytes.
+fn truncate(payload: &str, max_len: usize) -> Cow<str> {
+	if payload.len() <= max_len {
+		return Cow::Borrowed(payload)
+	}
+
+	let mut end = max_len;
+	while !payload.is_char_boundary(end) {
+		end -= 1;
+	}
+	Cow::Owned(format!("{}... <truncated, {} bytes total>", &payload[..end], payload.len()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn traced_payload_is_truncated() {
+		assert_eq!(truncate("[1,2,3]", 7), "[1,2,3]");
+		assert_eq!(truncate("[1,2,3]", 4), "[1,2... <truncated, 7 bytes total>");
+		// truncation doesn't split characters
+		assert_eq!(truncate("[\"ü\"]", 3), "[\"... <truncated, 6 bytes total>");
+	}
+
+	#[test]
+	fn signed_transactions_are_redacted() {
+		let params = RpcTraceParams { max_payload_len: 4, redact_transactions: true };
+		assert_eq!(
+			traced_request_payload(&params, "author_submitExtrinsic", Some("[\"0x0102\"]")),
+			"<redacted 10 bytes>",
+		);
+		assert_eq!(
+			traced_request_payload(&params, "chain_getHeader", Some("[\"0x0102\"]")),
+			"[\"0x... <truncated, 10 bytes total>",
+		);
+		assert_eq!(traced_request_payload(&params, "chain_getHeader", None), "[]");
+
+		// transactions are logged if redaction is disabled
+		let params = RpcTraceParams { redact_transactions: false, ..params };
+		assert_eq!(
+			traced_request_payload(&params, "author_submitExtrinsic", Some("[\"0x0102\"]")),
+			"[\"0x... <truncated, 10 bytes total>",
+		);
+	}
+}
@@ -0,0 +1,348 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Client implementation that is sending read requests to the (optional) read replica
+//! and transactions to the primary node.
+
+use crate::{
+	client::Client, endpoints::Endpoints, error::Result, AccountIdOf, AccountKeyPairOf, BalanceOf,
+	BlockNumberOf, Chain, ChainWithGrandpa, ChainWithTransactions, HashOf, HeaderIdOf, HeaderOf,
+	InFlightTransactions, NonceOf, SignedBlockOf, SimpleRuntimeVersion, Subscription,
+	TransactionTracker, UnsignedTransaction,
+};
+
+use async_trait::async_trait;
+use codec::{Encode, Output};
+use frame_support::weights::Weight;
+use sp_consensus_grandpa::{AuthorityId, OpaqueKeyOwnershipProof, SetId};
+use sp_core::{
+	storage::{StorageData, StorageKey},
+	Bytes, Pair,
+};
+use sp_runtime::transaction_validity::TransactionValidity;
+use sp_trie::StorageProof;
+use sp_version::RuntimeVersion;
+use std::{future::Future, marker::PhantomData};
+
+/// Client implementation that is sending read requests to the read replica and
+/// transactions to the primary node.
+///
+/// If the read request to the replica fails, it is retried using the primary node. If
+/// there's no replica, all requests are sent to the primary node.
+#[derive(Clone)]
+pub struct SplitClient<C: Chain, B: Client<C>> {
+	replica: Option<B>,
+	primary: B,
+	_phantom: PhantomData<C>,
+}
+
+impl<C: Chain, B: Client<C>> SplitClient<C, B> {
+	/// Creates new `SplitClient` on top of given primary node and read replica clients.
+	pub fn new(primary: B, replica: Option<B>) -> Self {
+		SplitClient { replica, primary, _phantom: Default::default() }
+	}
+
+	/// Perform read request, using the replica first and falling back to the primary node.
+	async fn read<T, F, Fut>(&self, method: &str, request: F) -> Result<T>
+	where
+		F: Fn(B) -> Fut + Send + Sync,
+		Fut: Future<Output = Result<T>> + Send,
+		T: Send,
+	{
+		if let Some(ref replica) = self.replica {
+			match request(replica.clone()).await {
+				Ok(result) => return Ok(result),
+				Err(e) => log::debug!(
+					target: "bridge",
+					"Call to {} replica {} has failed: {:?}. Retrying with the primary node",
+					C::NAME,
+					method,
+					e,
+				),
+			}
+		}
+
+		request(self.primary.clone()).await
+	}
+}
+
+impl<C: Chain, B: Client<C>> std::fmt::Debug for SplitClient<C, B> {
+	fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+		fmt.write_fmt(format_args!("SplitClient<{:?}, {:?}>", self.primary, self.replica))
+	}
+}
+
+#[async_trait]
+impl<C: Chain, B: Client<C>> Client<C> for SplitClient<C, B> {
+	async fn ensure_synced(&self) -> Result<()> {
+		self.primary.ensure_synced().await
+	}
+
+	async fn reconnect(&self) -> Result<()> {
+		if let Some(ref replica) = self.replica {
+			replica.reconnect().await?;
+		}
+		self.primary.reconnect().await
+	}
+
+	fn genesis_hash(&self) -> HashOf<C> {
+		self.primary.genesis_hash()
+	}
+
+	async fn header_hash_by_number(&self, number: BlockNumberOf<C>) -> Result<HashOf<C>> {
+		self.read("header_hash_by_number", |client| async move {
+			client.header_hash_by_number(number).await
+		})
+		.await
+	}
+
+	async fn header_by_hash(&self, hash: HashOf<C>) -> Result<HeaderOf<C>> {
+		self.read("header_by_hash", |client| async move { client.header_by_hash(hash).await })
+			.await
+	}
+
+	async fn block_by_hash(&self, hash: HashOf<C>) -> Result<SignedBlockOf<C>> {
+		self.read("block_by_hash", |client| async move { client.block_by_hash(hash).await })
+			.await
+	}
+
+	async fn best_finalized_header_hash(&self) -> Result<HashOf<C>> {
+		self.read("best_finalized_header_hash", |client| async move {
+			client.best_finalized_header_hash().await
+		})
+		.await
+	}
+
+	async fn best_header(&self) -> Result<HeaderOf<C>> {
+		self.read("best_header", |client| async move { client.best_header().await })
+			.await
+	}
+
+	async fn subscribe_best_headers(&self) -> Result<Subscription<HeaderOf<C>>> {
+		self.read("subscribe_best_headers", |client| async move {
+			client.subscribe_best_headers().await
+		})
+		.await
+	}
+
+	async fn subscribe_finalized_headers(&self) -> Result<Subscription<HeaderOf<C>>> {
+		self.read("subscribe_finalized_headers", |client| async move {
+			client.subscribe_finalized_headers().await
+		})
+		.await
+	}
+
+	async fn subscribe_grandpa_finality_justifications(&self) -> Result<Subscription<Bytes>>
+	where
+		C: ChainWithGrandpa,
+	{
+		self.read("subscribe_grandpa_finality_justifications", |client| async move {
+			client.subscribe_grandpa_finality_justifications().await
+		})
+		.await
+	}
+
+	async fn generate_grandpa_key_ownership_proof(
+		&self,
+		at: HashOf<C>,
+		set_id: SetId,
+		authority_id: AuthorityId,
+	) -> Result<Option<OpaqueKeyOwnershipProof>> {
+		self.read("generate_grandpa_key_ownership_proof", |client| {
+			let authority_id = authority_id.clone();
+			async move { client.generate_grandpa_key_ownership_proof(at, set_id, authority_id).await }
+		})
+		.await
+	}
+
+	async fn subscribe_beefy_finality_justifications(&self) -> Result<Subscription<Bytes>> {
+		self.read("subscribe_beefy_finality_justifications", |client| async move {
+			client.subscribe_beefy_finality_justifications().await
+		})
+		.await
+	}
+
+	async fn token_decimals(&self) -> Result<Option<u64>> {
+		self.read("token_decimals", |client| async move { client.token_decimals().await })
+			.await
+	}
+
+	async fn runtime_version(&self) -> Result<RuntimeVersion> {
+		self.read("runtime_version", |client| async move { client.runtime_version().await })
+			.await
+	}
+
+	async fn simple_runtime_version(&self) -> Result<SimpleRuntimeVersion> {
+		self.read("simple_runtime_version", |client| async move {
+			client.simple_runtime_version().await
+		})
+		.await
+	}
+
+	fn can_start_version_guard(&self) -> bool {
+		self.primary.can_start_version_guard()
+	}
+
+	async fn raw_storage_value(
+		&self,
+		at: HashOf<C>,
+		storage_key: StorageKey,
+	) -> Result<Option<StorageData>> {
+		self.read("raw_storage_value", |client| {
+			let storage_key = storage_key.clone();
+			async move { client.raw_storage_value(at, storage_key).await }
+		})
+		.await
+	}
+
+	async fn pending_extrinsics(&self) -> Result<Vec<Bytes>> {
+		// transactions are submitted to the primary node, so we are interested in its pool
+		self.primary.pending_extrinsics().await
+	}
+
+	async fn submit_unsigned_extrinsic(&self, transaction: Bytes) -> Result<HashOf<C>> {
+		self.primary.submit_unsigned_extrinsic(transaction).await
+	}
+
+	async fn submit_signed_extrinsic(
+		&self,
+		signer: &AccountKeyPairOf<C>,
+		prepare_extrinsic: impl FnOnce(HeaderIdOf<C>, NonceOf<C>) -> Result<UnsignedTransaction<C>>
+			+ Send
+			+ 'static,
+	) -> Result<HashOf<C>>
+	where
+		C: ChainWithTransactions,
+		AccountIdOf<C>: From<<AccountKeyPairOf<C> as Pair>::Public>,
+	{
+		self.primary.submit_signed_extrinsic(signer, prepare_extrinsic).await
+	}
+
+	async fn submit_and_watch_signed_extrinsic(
+		&self,
+		signer: &AccountKeyPairOf<C>,
+		prepare_extrinsic: impl FnOnce(HeaderIdOf<C>, NonceOf<C>) -> Result<UnsignedTransaction<C>>
+			+ Send
+			+ 'static,
+	) -> Result<TransactionTracker<C, Self>>
+	where
+		C: ChainWithTransactions,
+		AccountIdOf<C>: From<<AccountKeyPairOf<C> as Pair>::Public>,
+	{
+		// transaction status subscription is opened at the primary node. The replica may
+		// be lagging, so headers of blocks, including the transaction, may be missing there.
+		// This is fine, because we'll fall back to the primary node then
+		self.primary
+			.submit_and_watch_signed_extrinsic(signer, prepare_extrinsic)
+			.await
+			.map(|t| t.switch_environment(self.clone()))
+	}
+
+	fn in_flight_transactions(&self) -> InFlightTransactions {
+		self.primary.in_flight_transactions()
+	}
+
+	fn endpoints(&self) -> Endpoints<C> {
+		self.primary.endpoints()
+	}
+
+	async fn validate_transaction<SignedTransaction: Encode + Send + 'static>(
+		&self,
+		at: HashOf<C>,
+		transaction: SignedTransaction,
+	) -> Result<TransactionValidity> {
+		let transaction = PreEncoded(transaction.encode());
+		self.read("validate_transaction", |client| {
+			let transaction = transaction.clone();
+			async move { client.validate_transaction(at, transaction).await }
+		})
+		.await
+	}
+
+	async fn estimate_extrinsic_weight<SignedTransaction: Encode + Send + 'static>(
+		&self,
+		at: HashOf<C>,
+		transaction: SignedTransaction,
+	) -> Result<Weight> {
+		let transaction = PreEncoded(transaction.encode());
+		self.read("estimate_extrinsic_weight", |client| {
+			let transaction = transaction.clone();
+			async move { client.estimate_extrinsic_weight(at, transaction).await }
+		})
+		.await
+	}
+
+	async fn estimate_extrinsic_fee<SignedTransaction: Encode + Send + 'static>(
+		&self,
+		at: HashOf<C>,
+		transaction: SignedTransaction,
+	) -> Result<BalanceOf<C>> {
+		let transaction = PreEncoded(transaction.encode());
+		self.read("estimate_extrinsic_fee", |client| {
+			let transaction = transaction.clone();
+			async move { client.estimate_extrinsic_fee(at, transaction).await }
+		})
+		.await
+	}
+
+	async fn raw_state_call<Args: Encode + Send>(
+		&self,
+		at: HashOf<C>,
+		method: String,
+		arguments: Args,
+	) -> Result<Bytes> {
+		let arguments = PreEncoded(arguments.encode());
+		self.read("raw_state_call", |client| {
+			let method = method.clone();
+			let arguments = arguments.clone();
+			async move { client.raw_state_call(at, method, arguments).await }
+		})
+		.await
+	}
+
+	async fn prove_storage(&self, at: HashOf<C>, keys: Vec<StorageKey>) -> Result<StorageProof> {
+		self.read("prove_storage", |client| {
+			let keys = keys.clone();
+			async move { client.prove_storage(at, keys).await }
+		})
+		.await
+	}
+}
+
+/// Already encoded value, which may be passed to both replica and primary clients.
+#[derive(Clone)]
+struct PreEncoded(Vec<u8>);
+
+impl Encode for PreEncoded {
+	fn size_hint(&self) -> usize {
+		self.0.len()
+	}
+
+	fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+		dest.write(&self.0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn pre_encoded_value_is_not_encoded_again() {
+		let value = (42u32, vec![1u8, 2, 3]);
+		assert_eq!(PreEncoded(value.encode()).encode(), value.encode());
+	}
+}
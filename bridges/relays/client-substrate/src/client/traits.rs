@@ -15,10 +15,11 @@
 // along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{
+	endpoints::Endpoints,
 	error::{Error, Result},
-	AccountIdOf, AccountKeyPairOf, BlockNumberOf, Chain, ChainWithGrandpa, ChainWithTransactions,
-	HashOf, HeaderIdOf, HeaderOf, NonceOf, SignedBlockOf, SimpleRuntimeVersion, Subscription,
-	TransactionTracker, UnsignedTransaction,
+	AccountIdOf, AccountKeyPairOf, BalanceOf, BlockNumberOf, Chain, ChainWithGrandpa,
+	ChainWithTransactions, HashOf, HeaderIdOf, HeaderOf, InFlightTransactions, NonceOf,
+	SignedBlockOf, SimpleRuntimeVersion, Subscription, TransactionTracker, UnsignedTransaction,
 };
 
 use async_trait::async_trait;
@@ -190,6 +191,11 @@ pub trait Client<C: Chain>: 'static + Send + Sync + Clone + Debug {
 	where
 		C: ChainWithTransactions,
 		AccountIdOf<C>: From<<AccountKeyPairOf<C> as Pair>::Public>;
+	/// Returns transactions that have been submitted using `submit_and_watch_signed_extrinsic`
+	/// and are not yet finalized or lost.
+	fn in_flight_transactions(&self) -> InFlightTransactions;
+	/// Returns endpoints of the chain nodes, that the client is switching between.
+	fn endpoints(&self) -> Endpoints<C>;
 	/// Validate transaction at given block.
 	async fn validate_transaction<SignedTransaction: Encode + Send + 'static>(
 		&self,
@@ -202,6 +208,12 @@ pub trait Client<C: Chain>: 'static + Send + Sync + Clone + Debug {
 		at: HashOf<C>,
 		transaction: SignedTransaction,
 	) -> Result<Weight>;
+	/// Returns estimated fee of the given transaction.
+	async fn estimate_extrinsic_fee<SignedTransaction: Encode + Send + 'static>(
+		&self,
+		at: HashOf<C>,
+		transaction: SignedTransaction,
+	) -> Result<BalanceOf<C>>;
 
 	/// Execute runtime call at given block.
 	async fn raw_state_call<Args: Encode + Send>(
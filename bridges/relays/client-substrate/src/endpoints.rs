@@ -0,0 +1,194 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Failover between endpoints of the same chain.
+//!
+//! The client is connected to a single endpoint at a time. When the connection to the node
+//! fails, the client switches to the next configured endpoint. All nodes must serve the chain
+//! with the same genesis hash as the node that the client has connected to first. Endpoints of
+//! nodes that serve other chain (e.g. other fork) or incompatible runtime are marked bad and
+//! are never used again, so a misconfigured endpoint can't poison the relay.
+
+use crate::{Chain, Error, HashOf, Result};
+
+use parking_lot::Mutex;
+use std::{collections::BTreeSet, sync::Arc};
+
+/// Endpoints of the same chain, shared by all clones of the client.
+pub struct Endpoints<C: Chain> {
+	uris: Arc<Vec<String>>,
+	state: Arc<Mutex<EndpointsState<C>>>,
+}
+
+struct EndpointsState<C: Chain> {
+	/// Index of the current endpoint.
+	current: usize,
+	/// Indices of endpoints that have been marked bad.
+	bad: BTreeSet<usize>,
+	/// Genesis hash of the chain, served by the first node that the client has connected to.
+	genesis_hash: Option<HashOf<C>>,
+}
+
+impl<C: Chain> Endpoints<C> {
+	/// Create endpoints set. The client starts with the first endpoint.
+	pub fn new(uris: Vec<String>) -> Self {
+		Endpoints {
+			uris: Arc::new(uris),
+			state: Arc::new(Mutex::new(EndpointsState {
+				current: 0,
+				bad: BTreeSet::new(),
+				genesis_hash: None,
+			})),
+		}
+	}
+
+	/// Returns URI of the endpoint that the client must use.
+	pub fn current(&self) -> Result<String> {
+		let state = self.state.lock();
+		if state.bad.contains(&state.current) {
+			return Err(Error::Custom(format!("All {} endpoints have been marked bad", C::NAME)))
+		}
+		Ok(self.uris[state.current].clone())
+	}
+
+	/// Returns true if there are endpoints, other than the current one, that are not marked bad.
+	pub fn has_alternatives(&self) -> bool {
+		let state = self.state.lock();
+		(0..self.uris.len()).any(|index| index != state.current && !state.bad.contains(&index))
+	}
+
+	/// Returns number of endpoints that have been marked bad.
+	pub fn bad_endpoints(&self) -> usize {
+		self.state.lock().bad.len()
+	}
+
+	/// Switch to the next endpoint that is not marked bad.
+	pub fn switch(&self) {
+		let mut state = self.state.lock();
+		self.switch_locked(&mut state);
+	}
+
+	/// Ensure that the node at the current endpoint serves the chain with given genesis hash.
+	/// Otherwise the endpoint is marked bad.
+	///
+	/// The genesis hash of the first node is remembered and other nodes are checked against it.
+	pub fn ensure_same_chain(&self, genesis_hash: HashOf<C>) -> Result<()> {
+		let expected = *self.state.lock().genesis_hash.get_or_insert(genesis_hash);
+		if genesis_hash != expected {
+			return Err(self.mark_bad(format!(
+				"node serves chain with genesis hash {genesis_hash:?}, while {expected:?} is \
+				expected"
+			)))
+		}
+		Ok(())
+	}
+
+	/// Mark the current endpoint bad and switch to the next endpoint. Returns error that
+	/// explains why the endpoint has been marked bad.
+	pub fn mark_bad(&self, reason: String) -> Error {
+		let mut state = self.state.lock();
+		let uri = self.uris[state.current].clone();
+		log::error!(
+			target: "bridge",
+			"{} endpoint {} is incompatible: {}. It is marked bad and won't be used anymore",
+			C::NAME,
+			uri,
+			reason,
+		);
+		let current = state.current;
+		state.bad.insert(current);
+		self.switch_locked(&mut state);
+
+		Error::IncompatibleEndpoint { chain: C::NAME.into(), uri, reason }
+	}
+
+	fn switch_locked(&self, state: &mut EndpointsState<C>) {
+		let next = (1..=self.uris.len())
+			.map(|offset| (state.current + offset) % self.uris.len())
+			.find(|index| !state.bad.contains(index));
+		match next {
+			Some(next) if next != state.current => {
+				log::info!(
+					target: "bridge",
+					"Switching {} client from {} to {}",
+					C::NAME,
+					self.uris[state.current],
+					self.uris[next],
+				);
+				state.current = next;
+			},
+			_ => (),
+		}
+	}
+}
+
+impl<C: Chain> Clone for Endpoints<C> {
+	fn clone(&self) -> Self {
+		Endpoints { uris: self.uris.clone(), state: self.state.clone() }
+	}
+}
+
+impl<C: Chain> std::fmt::Debug for Endpoints<C> {
+	fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+		fmt.debug_struct("Endpoints")
+			.field("uris", &self.uris)
+			.field("bad", &self.state.lock().bad)
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_chain::TestChain;
+	use sp_core::H256;
+
+	#[test]
+	fn wrong_fork_endpoint_is_marked_bad() {
+		let endpoints =
+			Endpoints::<TestChain>::new(vec!["ws://good".into(), "ws://wrong-fork".into()]);
+		let (good_genesis, wrong_genesis) = (H256::repeat_byte(1), H256::repeat_byte(2));
+		assert_eq!(endpoints.current().unwrap(), "ws://good");
+		endpoints.ensure_same_chain(good_genesis).unwrap();
+		assert!(endpoints.has_alternatives());
+
+		// connection to the good node is lost and the client fails over to the wrong fork
+		endpoints.switch();
+		assert_eq!(endpoints.current().unwrap(), "ws://wrong-fork");
+		assert!(matches!(
+			endpoints.ensure_same_chain(wrong_genesis),
+			Err(Error::IncompatibleEndpoint { ref uri, .. }) if uri == "ws://wrong-fork",
+		));
+		assert_eq!(endpoints.bad_endpoints(), 1);
+
+		// the client returns to the good node and never switches to the wrong fork again
+		assert_eq!(endpoints.current().unwrap(), "ws://good");
+		assert!(!endpoints.has_alternatives());
+		endpoints.switch();
+		assert_eq!(endpoints.current().unwrap(), "ws://good");
+		endpoints.ensure_same_chain(good_genesis).unwrap();
+	}
+
+	#[test]
+	fn client_fails_if_all_endpoints_are_bad() {
+		let endpoints = Endpoints::<TestChain>::new(vec!["ws://a".into(), "ws://b".into()]);
+		endpoints.mark_bad("incompatible runtime".into());
+		assert_eq!(endpoints.current().unwrap(), "ws://b");
+		endpoints.mark_bad("incompatible runtime".into());
+		assert!(endpoints.current().is_err());
+		assert_eq!(endpoints.bad_endpoints(), 2);
+	}
+}
@@ -20,10 +20,11 @@ use crate::{BlockNumberOf, Chain, HashOf, SimpleRuntimeVersion};
 use bp_header_chain::SubmitFinalityProofCallExtras;
 use bp_polkadot_core::parachains::ParaId;
 use jsonrpsee::core::ClientError as RpcError;
-use relay_utils::MaybeConnectionError;
+use relay_utils::{FailureKind, MaybeConnectionError};
 use sc_rpc_api::system::Health;
 use sp_core::{storage::StorageKey, Bytes};
-use sp_runtime::transaction_validity::TransactionValidityError;
+use sp_runtime::transaction_validity::{InvalidTransaction, TransactionValidityError};
+use std::time::Duration;
 use thiserror::Error;
 
 /// Result type used by Substrate client.
@@ -172,6 +173,16 @@ pub enum Error {
 		/// Underlying error.
 		error: Box<Error>,
 	},
+	/// Storage proof generation has taken longer than allowed.
+	#[error("Generating storage proof of {chain} at {hash} has taken longer than {timeout:?}.")]
+	ProofGenerationTimeout {
+		/// Name of the chain where the error has happened.
+		chain: String,
+		/// Hash of the block we've tried to prove keys at.
+		hash: String,
+		/// Maximal proof generation time.
+		timeout: Duration,
+	},
 	/// Failed to subscribe to GRANDPA justifications stream.
 	#[error("Failed to subscribe to {chain} best headers: {error:?}.")]
 	FailedToSubscribeBestHeaders {
@@ -236,6 +247,26 @@ pub enum Error {
 		/// Finality proof submission extras.
 		extras: SubmitFinalityProofCallExtras,
 	},
+	/// The signer balance is not enough to pay the transaction fee and keep the buffer.
+	#[error("Balance {balance} of {chain} account is not enough for the transaction: {required} required")]
+	InsufficientBalance {
+		/// Name of the chain where the error has happened.
+		chain: String,
+		/// Free balance of the account.
+		balance: String,
+		/// Estimated transaction fee plus the buffer.
+		required: String,
+	},
+	/// The node at the endpoint serves other chain or incompatible runtime.
+	#[error("{chain} endpoint {uri} is incompatible: {reason}")]
+	IncompatibleEndpoint {
+		/// Name of the chain where the error has happened.
+		chain: String,
+		/// URI of the incompatible endpoint.
+		uri: String,
+		/// Why the endpoint is incompatible.
+		reason: String,
+	},
 	/// Custom logic error.
 	#[error("{0}")]
 	Custom(String),
@@ -386,6 +417,11 @@ impl Error {
 		}
 	}
 
+	/// Constructs `ProofGenerationTimeout` variant.
+	pub fn proof_generation_timeout<C: Chain>(at: HashOf<C>, timeout: Duration) -> Self {
+		Error::ProofGenerationTimeout { chain: C::NAME.into(), hash: format!("{at}"), timeout }
+	}
+
 	/// Constructs `FailedToGetSystemHealth` variant.
 	pub fn failed_to_get_system_health<C: Chain>(e: Error) -> Self {
 		Error::FailedToGetSystemHealth { chain: C::NAME.into(), error: e.boxed() }
@@ -427,7 +463,122 @@ impl MaybeConnectionError for Error {
 				matches!(*e, RpcError::Transport(_) | RpcError::RestartNeeded(_),),
 			Error::ClientNotSynced(_) => true,
 			Error::UnorderedFinalizedHeaders { .. } => true,
+			Error::IncompatibleEndpoint { .. } => true,
 			_ => self.nested().map(|e| e.is_connection_error()).unwrap_or(false),
 		}
 	}
+
+	fn failure_kind(&self) -> Option<FailureKind> {
+		match *self {
+			Error::TransactionInvalid(TransactionValidityError::Invalid(_)) =>
+				Some(FailureKind::Deterministic),
+			Error::TransactionInvalid(TransactionValidityError::Unknown(_)) =>
+				Some(FailureKind::Transient),
+			Error::RpcError(RpcError::Call(ref e)) => match e.code() {
+				// `author_submitExtrinsic` error codes: invalid transaction
+				AUTHOR_INVALID_TRANSACTION => Some(FailureKind::Deterministic),
+				// unknown validity, temporarily banned, already imported, priority too low,
+				// cycle in dependencies and immediately dropped (transaction pool is full)
+				AUTHOR_UNKNOWN_VALIDITY..=AUTHOR_IMMEDIATELY_DROPPED =>
+					Some(FailureKind::Transient),
+				_ => None,
+			},
+			_ => self.nested().and_then(|e| e.failure_kind()),
+		}
+	}
+
+	fn exhausts_resources(&self) -> bool {
+		match *self {
+			Error::TransactionInvalid(TransactionValidityError::Invalid(
+				InvalidTransaction::ExhaustsResources,
+			)) => true,
+			Error::RpcError(RpcError::Call(ref e)) =>
+				e.code() == AUTHOR_INVALID_TRANSACTION &&
+					e.data().map(|data| data.get().contains(EXHAUSTS_RESOURCES)).unwrap_or(false),
+			_ => self.nested().map(|e| e.exhausts_resources()).unwrap_or(false),
+		}
+	}
+
+	fn is_proof_generation_timeout(&self) -> bool {
+		match *self {
+			Error::ProofGenerationTimeout { .. } => true,
+			_ => self.nested().map(|e| e.is_proof_generation_timeout()).unwrap_or(false),
+		}
+	}
+}
+
+/// `author_submitExtrinsic` error code of invalid transaction.
+const AUTHOR_INVALID_TRANSACTION: i32 = 1010;
+/// `author_submitExtrinsic` error code of transaction with unknown validity.
+const AUTHOR_UNKNOWN_VALIDITY: i32 = 1011;
+/// `author_submitExtrinsic` error code of transaction that has been dropped by the pool.
+const AUTHOR_IMMEDIATELY_DROPPED: i32 = 1016;
+/// Description of the `InvalidTransaction::ExhaustsResources` error, returned by the node.
+const EXHAUSTS_RESOURCES: &str = "Transaction would exhaust the block limits";
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jsonrpsee::types::ErrorObject;
+	use sp_runtime::transaction_validity::UnknownTransaction;
+
+	fn submission_error(code: i32) -> Error {
+		submission_error_with_data(code, None)
+	}
+
+	fn submission_error_with_data(code: i32, data: Option<&str>) -> Error {
+		Error::FailedToSubmitTransaction {
+			chain: "TestChain".into(),
+			error: Box::new(Error::RpcError(RpcError::Call(ErrorObject::owned(
+				code, "error", data,
+			)))),
+		}
+	}
+
+	#[test]
+	fn rejected_transaction_is_deterministic_failure() {
+		assert_eq!(
+			Error::TransactionInvalid(InvalidTransaction::BadProof.into()).failure_kind(),
+			Some(FailureKind::Deterministic),
+		);
+		assert_eq!(
+			submission_error(AUTHOR_INVALID_TRANSACTION).failure_kind(),
+			Some(FailureKind::Deterministic),
+		);
+	}
+
+	#[test]
+	fn dropped_transaction_is_transient_failure() {
+		assert_eq!(
+			Error::TransactionInvalid(UnknownTransaction::CannotLookup.into()).failure_kind(),
+			Some(FailureKind::Transient),
+		);
+		assert_eq!(
+			submission_error(AUTHOR_IMMEDIATELY_DROPPED).failure_kind(),
+			Some(FailureKind::Transient),
+		);
+	}
+
+	#[test]
+	fn other_errors_are_not_transaction_failures() {
+		assert_eq!(submission_error(-32000).failure_kind(), None);
+		assert_eq!(Error::Custom("delivery is paused".into()).failure_kind(), None);
+		assert_eq!(Error::BridgePalletIsHalted.failure_kind(), None);
+	}
+
+	#[test]
+	fn resources_exhaustion_is_detected() {
+		assert!(Error::TransactionInvalid(InvalidTransaction::ExhaustsResources.into())
+			.exhausts_resources());
+		assert!(submission_error_with_data(
+			AUTHOR_INVALID_TRANSACTION,
+			Some("Custom error: Transaction would exhaust the block limits"),
+		)
+		.exhausts_resources());
+
+		assert!(
+			!Error::TransactionInvalid(InvalidTransaction::BadProof.into()).exhausts_resources()
+		);
+		assert!(!submission_error(AUTHOR_INVALID_TRANSACTION).exhausts_resources());
+	}
 }
@@ -0,0 +1,571 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Discovery of messages from runtime events.
+//!
+//! The relay is generic over chains, so it doesn't know the runtime event type. Instead, the
+//! `System::Events` value is walked using the type registry from the runtime metadata: events
+//! of other pallets are skipped and only `MessageAccepted` and `MessagesReceived` events of the
+//! bridge messages pallet are decoded.
+
+use crate::{metadata::METADATA_METHOD, Chain, Client, Error, HashOf, Result};
+
+use bp_messages::{LaneId, MessageNonce, Weight};
+use bp_runtime::storage_value_key;
+use codec::{Compact, Decode};
+use frame_metadata::{
+	v14::StorageEntryType as StorageEntryTypeV14, v15::StorageEntryType as StorageEntryTypeV15,
+	RuntimeMetadata, RuntimeMetadataPrefixed,
+};
+use scale_info::{form::PortableForm, PortableRegistry, TypeDef, TypeDefPrimitive};
+use sp_core::storage::StorageKey;
+use std::ops::RangeInclusive;
+
+/// Name of the messages pallet event, emitted when the message is accepted.
+const MESSAGE_ACCEPTED_EVENT: &str = "MessageAccepted";
+/// Name of the messages pallet event, emitted when messages are received from the bridged chain.
+const MESSAGES_RECEIVED_EVENT: &str = "MessagesReceived";
+/// Name of the message reception result variant, used for dispatched messages.
+const DISPATCHED_RECEPTION_RESULT: &str = "Dispatched";
+
+/// Types, required to decode runtime events.
+#[derive(Clone, Debug)]
+pub struct EventsMetadata {
+	registry: PortableRegistry,
+	events_type: u32,
+}
+
+impl EventsMetadata {
+	/// Create events metadata from the type registry and identifier of the `System::Events`
+	/// value type in this registry.
+	pub fn new(registry: PortableRegistry, events_type: u32) -> Self {
+		EventsMetadata { registry, events_type }
+	}
+
+	/// Returns nonces of messages, accepted by the messages pallet with given name at given
+	/// lane, from the encoded `System::Events` value.
+	pub fn accepted_messages(
+		&self,
+		pallet_name: &str,
+		lane: LaneId,
+		encoded_events: &[u8],
+	) -> Result<Vec<MessageNonce>> {
+		self.decode_pallet_events(pallet_name, encoded_events, |event_variant, input| {
+			let is_accepted = event_variant.name == MESSAGE_ACCEPTED_EVENT;
+			let (mut event_lane, mut event_nonce) = (None, None);
+			for field in &event_variant.fields {
+				match field.name.as_deref() {
+					Some("lane_id") if is_accepted => event_lane = Some(LaneId::decode(input)?),
+					Some("nonce") if is_accepted =>
+						event_nonce = Some(MessageNonce::decode(input)?),
+					_ => self.skip(field.ty.id, input)?,
+				}
+			}
+			Ok(match (event_lane, event_nonce) {
+				(Some(event_lane), Some(event_nonce)) if event_lane == lane => Some(event_nonce),
+				_ => None,
+			})
+		})
+	}
+
+	/// Returns total unspent dispatch weight of messages with given nonces, received by the
+	/// messages pallet with given name at given lane, from the encoded `System::Events` value.
+	pub fn unspent_dispatch_weight(
+		&self,
+		pallet_name: &str,
+		lane: LaneId,
+		nonces: &RangeInclusive<MessageNonce>,
+		encoded_events: &[u8],
+	) -> Result<Weight> {
+		let weights =
+			self.decode_pallet_events(pallet_name, encoded_events, |event_variant, input| {
+				if event_variant.name != MESSAGES_RECEIVED_EVENT {
+					for field in &event_variant.fields {
+						self.skip(field.ty.id, input)?;
+					}
+					return Ok(None)
+				}
+				let received_messages_type = match event_variant.fields.first() {
+					Some(field) if event_variant.fields.len() == 1 => field.ty.id,
+					_ => return Err(unexpected_type("received messages event")),
+				};
+				self.decode_received_messages(received_messages_type, lane, nonces, input)
+					.map(Some)
+			})?;
+		Ok(weights.into_iter().fold(Weight::zero(), Weight::saturating_add))
+	}
+
+	/// Walk the encoded `System::Events` value and call `decode_event` for every event of the
+	/// pallet with given name. The callback must consume all fields of the event variant.
+	fn decode_pallet_events<T>(
+		&self,
+		pallet_name: &str,
+		mut encoded_events: &[u8],
+		mut decode_event: impl FnMut(
+			&scale_info::Variant<PortableForm>,
+			&mut &[u8],
+		) -> Result<Option<T>>,
+	) -> Result<Vec<T>> {
+		let input = &mut encoded_events;
+		let record_type = match self.resolve(self.events_type)? {
+			TypeDef::Sequence(sequence) => sequence.type_param.id,
+			_ => return Err(unexpected_type("System::Events")),
+		};
+		let record_fields = match self.resolve(record_type)? {
+			TypeDef::Composite(composite) => &composite.fields,
+			_ => return Err(unexpected_type("event record")),
+		};
+
+		let mut decoded = Vec::new();
+		let records_count = Compact::<u32>::decode(input)?.0;
+		for _ in 0..records_count {
+			for field in record_fields {
+				if field.name.as_deref() == Some("event") {
+					if let Some(event_variant) =
+						self.decode_pallet_event(field.ty.id, pallet_name, input)?
+					{
+						decoded.extend(decode_event(event_variant, input)?);
+					}
+				} else {
+					self.skip(field.ty.id, input)?;
+				}
+			}
+		}
+		Ok(decoded)
+	}
+
+	/// Decode the runtime event and return variant of the pallet event if it is the event of
+	/// given pallet. Events of other pallets are skipped.
+	fn decode_pallet_event(
+		&self,
+		runtime_event_type: u32,
+		pallet_name: &str,
+		input: &mut &[u8],
+	) -> Result<Option<&scale_info::Variant<PortableForm>>> {
+		// variants of the runtime event are named after pallets
+		let pallet_variant = self.decode_variant(runtime_event_type, input)?;
+		if pallet_variant.name != pallet_name {
+			for field in &pallet_variant.fields {
+				self.skip(field.ty.id, input)?;
+			}
+			return Ok(None)
+		}
+		let pallet_event_type = match pallet_variant.fields.first() {
+			Some(field) if pallet_variant.fields.len() == 1 => field.ty.id,
+			_ => return Err(unexpected_type("pallet event")),
+		};
+
+		self.decode_variant(pallet_event_type, input).map(Some)
+	}
+
+	/// Decode the `MessagesReceived` event payload and return unspent dispatch weight of
+	/// messages with given nonces, received at given lane.
+	fn decode_received_messages(
+		&self,
+		ty: u32,
+		lane: LaneId,
+		nonces: &RangeInclusive<MessageNonce>,
+		input: &mut &[u8],
+	) -> Result<Weight> {
+		let received_messages_type = match self.resolve(ty)? {
+			TypeDef::Sequence(sequence) => sequence.type_param.id,
+			_ => return Err(unexpected_type("received messages")),
+		};
+		let received_messages_fields = match self.resolve(received_messages_type)? {
+			TypeDef::Composite(composite) => &composite.fields,
+			_ => return Err(unexpected_type("received messages")),
+		};
+
+		let mut unspent_weight = Weight::zero();
+		for _ in 0..Compact::<u32>::decode(input)?.0 {
+			let (mut received_lane, mut lane_unspent_weight) = (None, Weight::zero());
+			for field in received_messages_fields {
+				match field.name.as_deref() {
+					Some("lane") => received_lane = Some(LaneId::decode(input)?),
+					Some("receive_results") =>
+						lane_unspent_weight =
+							self.decode_receive_results(field.ty.id, nonces, input)?,
+					_ => self.skip(field.ty.id, input)?,
+				}
+			}
+			if received_lane == Some(lane) {
+				unspent_weight = unspent_weight.saturating_add(lane_unspent_weight);
+			}
+		}
+		Ok(unspent_weight)
+	}
+
+	/// Decode reception results of messages and return unspent dispatch weight of dispatched
+	/// messages with given nonces.
+	fn decode_receive_results(
+		&self,
+		ty: u32,
+		nonces: &RangeInclusive<MessageNonce>,
+		input: &mut &[u8],
+	) -> Result<Weight> {
+		let receive_result_type = match self.resolve(ty)? {
+			TypeDef::Sequence(sequence) => sequence.type_param.id,
+			_ => return Err(unexpected_type("message reception results")),
+		};
+		let reception_result_type = match self.resolve(receive_result_type)? {
+			TypeDef::Tuple(tuple) if tuple.fields.len() == 2 => tuple.fields[1].id,
+			_ => return Err(unexpected_type("message reception result")),
+		};
+
+		let mut unspent_weight = Weight::zero();
+		for _ in 0..Compact::<u32>::decode(input)?.0 {
+			let nonce = MessageNonce::decode(input)?;
+			let reception_variant = self.decode_variant(reception_result_type, input)?;
+			// only dispatched messages have the dispatch result (with unspent weight)
+			match reception_variant.fields.first() {
+				Some(field)
+					if reception_variant.name == DISPATCHED_RECEPTION_RESULT &&
+						reception_variant.fields.len() == 1 =>
+				{
+					let message_unspent_weight = self.decode_unspent_weight(field.ty.id, input)?;
+					if nonces.contains(&nonce) {
+						unspent_weight = unspent_weight.saturating_add(message_unspent_weight);
+					}
+				},
+				_ =>
+					for field in &reception_variant.fields {
+						self.skip(field.ty.id, input)?;
+					},
+			}
+		}
+		Ok(unspent_weight)
+	}
+
+	/// Decode the message dispatch result and return its unspent weight. The runtime-specific
+	/// dispatch level result is skipped.
+	fn decode_unspent_weight(&self, ty: u32, input: &mut &[u8]) -> Result<Weight> {
+		let dispatch_result_fields = match self.resolve(ty)? {
+			TypeDef::Composite(composite) => &composite.fields,
+			_ => return Err(unexpected_type("message dispatch result")),
+		};
+
+		let mut unspent_weight = None;
+		for field in dispatch_result_fields {
+			match field.name.as_deref() {
+				Some("unspent_weight") => unspent_weight = Some(Weight::decode(input)?),
+				_ => self.skip(field.ty.id, input)?,
+			}
+		}
+		unspent_weight.ok_or_else(|| unexpected_type("message dispatch result"))
+	}
+
+	/// Decode variant index of the enum with given type and return the variant.
+	fn decode_variant(
+		&self,
+		ty: u32,
+		input: &mut &[u8],
+	) -> Result<&scale_info::Variant<PortableForm>> {
+		let variants = match self.resolve(ty)? {
+			TypeDef::Variant(variant) => &variant.variants,
+			_ => return Err(unexpected_type("event")),
+		};
+		let index = u8::decode(input)?;
+		variants
+			.iter()
+			.find(|variant| variant.index == index)
+			.ok_or_else(|| Error::Custom(format!("Unknown variant {index} of type {ty}")))
+	}
+
+	/// Skip encoded value of given type.
+	fn skip(&self, ty: u32, input: &mut &[u8]) -> Result<()> {
+		match self.resolve(ty)? {
+			TypeDef::Composite(composite) =>
+				for field in &composite.fields {
+					self.skip(field.ty.id, input)?;
+				},
+			TypeDef::Variant(_) => {
+				let variant = self.decode_variant(ty, input)?;
+				for field in &variant.fields {
+					self.skip(field.ty.id, input)?;
+				}
+			},
+			TypeDef::Sequence(sequence) =>
+				for _ in 0..Compact::<u32>::decode(input)?.0 {
+					self.skip(sequence.type_param.id, input)?;
+				},
+			TypeDef::Array(array) =>
+				for _ in 0..array.len {
+					self.skip(array.type_param.id, input)?;
+				},
+			TypeDef::Tuple(tuple) =>
+				for field in &tuple.fields {
+					self.skip(field.id, input)?;
+				},
+			TypeDef::Primitive(TypeDefPrimitive::Str) => {
+				Vec::<u8>::decode(input)?;
+			},
+			TypeDef::Primitive(primitive) => skip_bytes(input, primitive_size(primitive))?,
+			TypeDef::Compact(_) => {
+				Compact::<u128>::decode(input)?;
+			},
+			TypeDef::BitSequence(bit_sequence) => {
+				let bits = Compact::<u32>::decode(input)?.0 as usize;
+				let store_size = match self.resolve(bit_sequence.bit_store_type.id)? {
+					TypeDef::Primitive(primitive) => primitive_size(primitive),
+					_ => return Err(unexpected_type("bit sequence store")),
+				};
+				let store_bits = store_size * 8;
+				skip_bytes(input, (bits + store_bits - 1) / store_bits * store_size)?;
+			},
+		}
+		Ok(())
+	}
+
+	fn resolve(&self, ty: u32) -> Result<&TypeDef<PortableForm>> {
+		self.registry
+			.resolve(ty)
+			.map(|ty| &ty.type_def)
+			.ok_or_else(|| Error::Custom(format!("Type {ty} is missing from the metadata")))
+	}
+}
+
+/// Storage key of the `System::Events` value.
+pub fn system_events_key() -> StorageKey {
+	storage_value_key("System", "Events")
+}
+
+/// Read types, required to decode runtime events, at given block.
+pub async fn read_events_metadata<C: Chain>(
+	client: &impl Client<C>,
+	at: HashOf<C>,
+) -> Result<EventsMetadata> {
+	let encoded_metadata: Vec<u8> = client.state_call(at, METADATA_METHOD.into(), ()).await?;
+	let metadata = RuntimeMetadataPrefixed::decode(&mut &encoded_metadata[..])?;
+
+	// the `System::Events` type is found the same way in all supported metadata versions
+	macro_rules! events_metadata {
+		($metadata:expr, $storage_entry_type:ident) => {{
+			let events_type = $metadata
+				.pallets
+				.iter()
+				.filter(|pallet| pallet.name == "System")
+				.filter_map(|pallet| pallet.storage.as_ref())
+				.flat_map(|storage| storage.entries.iter())
+				.find(|entry| entry.name == "Events")
+				.and_then(|entry| match &entry.ty {
+					$storage_entry_type::Plain(ty) => Some(ty.id),
+					_ => None,
+				})
+				.ok_or_else(|| {
+					Error::Custom(format!("{} runtime has no System::Events value", C::NAME))
+				})?;
+			EventsMetadata::new($metadata.types, events_type)
+		}};
+	}
+	Ok(match metadata.1 {
+		RuntimeMetadata::V14(metadata) => events_metadata!(metadata, StorageEntryTypeV14),
+		RuntimeMetadata::V15(metadata) => events_metadata!(metadata, StorageEntryTypeV15),
+		_ => return Err(Error::Custom(format!("Unsupported {} runtime metadata version", C::NAME))),
+	})
+}
+
+fn primitive_size(primitive: &TypeDefPrimitive) -> usize {
+	match primitive {
+		TypeDefPrimitive::Bool | TypeDefPrimitive::U8 | TypeDefPrimitive::I8 => 1,
+		TypeDefPrimitive::U16 | TypeDefPrimitive::I16 => 2,
+		TypeDefPrimitive::Char | TypeDefPrimitive::U32 | TypeDefPrimitive::I32 => 4,
+		TypeDefPrimitive::U64 | TypeDefPrimitive::I64 => 8,
+		TypeDefPrimitive::U128 | TypeDefPrimitive::I128 => 16,
+		TypeDefPrimitive::U256 | TypeDefPrimitive::I256 => 32,
+		// strings have variable length and are skipped separately
+		TypeDefPrimitive::Str => 0,
+	}
+}
+
+fn skip_bytes(input: &mut &[u8], count: usize) -> Result<()> {
+	if input.len() < count {
+		return Err(codec::Error::from("Not enough data to skip").into())
+	}
+	*input = &input[count..];
+	Ok(())
+}
+
+fn unexpected_type(what: &str) -> Error {
+	Error::Custom(format!("Unexpected type of the {what} in the runtime metadata"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use bp_messages::{ReceivedMessages, ReceptionResult};
+	use bp_runtime::messages::MessageDispatchResult;
+	use codec::Encode;
+	use scale_info::{meta_type, Registry, TypeInfo};
+	use sp_core::H256;
+
+	#[derive(Encode, TypeInfo)]
+	enum TestPhase {
+		ApplyExtrinsic(u32),
+		#[allow(dead_code)]
+		Finalization,
+	}
+
+	#[derive(Encode, TypeInfo)]
+	enum TestSystemEvent {
+		ExtrinsicSuccess { weight: u64, class: u8 },
+		Remarked { sender: [u8; 32], hash: H256, note: String },
+	}
+
+	#[derive(Encode, TypeInfo)]
+	enum TestMessagesEvent {
+		MessageAccepted { lane_id: LaneId, nonce: MessageNonce },
+		MessagesDelivered { lane_id: LaneId, count: Compact<u64> },
+		MessagesReceived(Vec<ReceivedMessages<TestDispatchLevelResult>>),
+	}
+
+	#[derive(Encode, TypeInfo)]
+	enum TestDispatchLevelResult {
+		Dispatched,
+		NotDispatched(String),
+	}
+
+	#[derive(Encode, TypeInfo)]
+	enum TestRuntimeEvent {
+		System(TestSystemEvent),
+		BridgeRococoMessages(TestMessagesEvent),
+		BridgeWestendMessages(TestMessagesEvent),
+	}
+
+	#[derive(Encode, TypeInfo)]
+	struct TestEventRecord {
+		phase: TestPhase,
+		event: TestRuntimeEvent,
+		topics: Vec<H256>,
+	}
+
+	fn events_metadata() -> EventsMetadata {
+		let mut registry = Registry::new();
+		let events_type = registry.register_type(&meta_type::<Vec<TestEventRecord>>()).id;
+		EventsMetadata::new(registry.into(), events_type)
+	}
+
+	fn record(event: TestRuntimeEvent) -> TestEventRecord {
+		TestEventRecord {
+			phase: TestPhase::ApplyExtrinsic(1),
+			event,
+			topics: vec![H256::repeat_byte(1)],
+		}
+	}
+
+	fn accepted(lane: LaneId, nonce: MessageNonce) -> TestMessagesEvent {
+		TestMessagesEvent::MessageAccepted { lane_id: lane, nonce }
+	}
+
+	#[test]
+	fn accepted_messages_are_found_in_events() {
+		let (lane, other_lane) = (LaneId([0, 0, 0, 1]), LaneId([0, 0, 0, 2]));
+		let events = vec![
+			record(TestRuntimeEvent::System(TestSystemEvent::ExtrinsicSuccess {
+				weight: 100,
+				class: 0,
+			})),
+			record(TestRuntimeEvent::BridgeRococoMessages(accepted(lane, 11))),
+			record(TestRuntimeEvent::System(TestSystemEvent::Remarked {
+				sender: [2; 32],
+				hash: H256::repeat_byte(3),
+				note: "remark".into(),
+			})),
+			record(TestRuntimeEvent::BridgeRococoMessages(TestMessagesEvent::MessagesDelivered {
+				lane_id: lane,
+				count: Compact(2),
+			})),
+			record(TestRuntimeEvent::BridgeRococoMessages(accepted(other_lane, 5))),
+			record(TestRuntimeEvent::BridgeWestendMessages(accepted(lane, 20))),
+			record(TestRuntimeEvent::BridgeRococoMessages(accepted(lane, 12))),
+		];
+
+		assert_eq!(
+			events_metadata()
+				.accepted_messages("BridgeRococoMessages", lane, &events.encode())
+				.unwrap(),
+			vec![11, 12],
+		);
+	}
+
+	fn dispatched(
+		nonce: MessageNonce,
+		unspent_weight: u64,
+		dispatch_level_result: TestDispatchLevelResult,
+	) -> (MessageNonce, ReceptionResult<TestDispatchLevelResult>) {
+		(
+			nonce,
+			ReceptionResult::Dispatched(MessageDispatchResult {
+				unspent_weight: Weight::from_parts(unspent_weight, unspent_weight * 10),
+				dispatch_level_result,
+			}),
+		)
+	}
+
+	#[test]
+	fn unspent_dispatch_weight_is_found_in_events() {
+		let (lane, other_lane) = (LaneId([0, 0, 0, 1]), LaneId([0, 0, 0, 2]));
+		let events = vec![
+			record(TestRuntimeEvent::BridgeRococoMessages(accepted(lane, 11))),
+			record(TestRuntimeEvent::BridgeRococoMessages(TestMessagesEvent::MessagesReceived(
+				vec![
+					ReceivedMessages::new(
+						other_lane,
+						vec![dispatched(20, 1_000, TestDispatchLevelResult::Dispatched)],
+					),
+					ReceivedMessages::new(
+						lane,
+						vec![
+							dispatched(20, 100, TestDispatchLevelResult::Dispatched),
+							(21, ReceptionResult::InvalidNonce),
+							dispatched(
+								22,
+								200,
+								TestDispatchLevelResult::NotDispatched("failed".into()),
+							),
+							dispatched(23, 400, TestDispatchLevelResult::Dispatched),
+						],
+					),
+				],
+			))),
+			record(TestRuntimeEvent::BridgeWestendMessages(TestMessagesEvent::MessagesReceived(
+				vec![ReceivedMessages::new(
+					lane,
+					vec![dispatched(21, 2_000, TestDispatchLevelResult::Dispatched)],
+				)],
+			))),
+		];
+
+		assert_eq!(
+			events_metadata()
+				.unspent_dispatch_weight("BridgeRococoMessages", lane, &(20..=22), &events.encode())
+				.unwrap(),
+			Weight::from_parts(300, 3_000),
+		);
+	}
+
+	#[test]
+	fn truncated_events_are_rejected() {
+		let events = vec![record(TestRuntimeEvent::BridgeRococoMessages(accepted(
+			LaneId([0, 0, 0, 1]),
+			11,
+		)))]
+		.encode();
+
+		assert!(events_metadata()
+			.accepted_messages("BridgeRococoMessages", LaneId([0, 0, 0, 1]), &events[..20])
+			.is_err());
+	}
+}
@@ -0,0 +1,123 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Reading the global pause state of the bridge.
+//!
+//! Apart from operating modes of bridge pallets, some chains have a single switch that halts
+//! the whole bridge in emergencies. The switch is chain-specific, so its location in the
+//! runtime storage is configured by the user. The storage value is expected to be either `bool`
+//! or `BasicOperatingMode`: both are encoded as `0` when the bridge is operational and as `1`
+//! when it is paused. Missing value means that the bridge isn't paused.
+
+use crate::{Chain, Client, Error, HashOf};
+
+use bp_runtime::storage_value_key;
+use sp_core::storage::StorageKey;
+use std::{fmt, str::FromStr};
+
+/// Location of the global pause switch of the bridge in the runtime storage.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlobalPauseStorage {
+	/// Name of the pallet that owns the switch.
+	pub pallet: String,
+	/// Name of the switch storage value.
+	pub item: String,
+}
+
+impl GlobalPauseStorage {
+	/// Returns storage key of the switch.
+	pub fn storage_key(&self) -> StorageKey {
+		storage_value_key(&self.pallet, &self.item)
+	}
+
+	/// Read the switch at given block. Returns `true` if the bridge is paused.
+	pub async fn read_is_paused<C: Chain>(
+		&self,
+		client: &impl Client<C>,
+		at: HashOf<C>,
+	) -> Result<bool, Error> {
+		let is_paused = match client.raw_storage_value(at, self.storage_key()).await? {
+			Some(is_paused) => is_paused,
+			None => return Ok(false),
+		};
+		decode_is_paused(&is_paused.0).ok_or_else(|| {
+			Error::Custom(format!(
+				"{} global pause switch at {} has unexpected value: {:?}",
+				C::NAME,
+				self,
+				is_paused.0,
+			))
+		})
+	}
+}
+
+impl FromStr for GlobalPauseStorage {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.split_once('.') {
+			Some((pallet, item))
+				if !pallet.is_empty() && !item.is_empty() && !item.contains('.') =>
+				Ok(GlobalPauseStorage { pallet: pallet.into(), item: item.into() }),
+			_ => Err(format!(
+				"Invalid global pause storage: {s:?}. Expected `<pallet>.<storage value>`"
+			)),
+		}
+	}
+}
+
+impl fmt::Display for GlobalPauseStorage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}.{}", self.pallet, self.item)
+	}
+}
+
+/// Decode the switch value, encoded as `bool` or `BasicOperatingMode`.
+fn decode_is_paused(encoded: &[u8]) -> Option<bool> {
+	match encoded {
+		[0] => Some(false),
+		[1] => Some(true),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use bp_runtime::BasicOperatingMode;
+	use codec::Encode;
+
+	#[test]
+	fn pause_storage_is_parsed() {
+		assert_eq!(
+			GlobalPauseStorage::from_str("BridgeGuard.IsPaused"),
+			Ok(GlobalPauseStorage { pallet: "BridgeGuard".into(), item: "IsPaused".into() }),
+		);
+		assert!(GlobalPauseStorage::from_str("BridgeGuard").is_err());
+		assert!(GlobalPauseStorage::from_str(".IsPaused").is_err());
+		assert!(GlobalPauseStorage::from_str("BridgeGuard.IsPaused.Extra").is_err());
+	}
+
+	#[test]
+	fn pause_state_is_decoded() {
+		assert_eq!(decode_is_paused(&false.encode()), Some(false));
+		assert_eq!(decode_is_paused(&true.encode()), Some(true));
+		assert_eq!(decode_is_paused(&BasicOperatingMode::Normal.encode()), Some(false));
+		assert_eq!(decode_is_paused(&BasicOperatingMode::Halted.encode()), Some(true));
+		assert_eq!(decode_is_paused(&[2]), None);
+		assert_eq!(decode_is_paused(&[0, 1]), None);
+	}
+}
@@ -17,15 +17,21 @@
 //! Pallet provides a set of guard functions that are running in background threads
 //! and are aborting process if some condition fails.
 
-use crate::{error::Error, Chain, Client};
+use crate::{error::Error, Chain, Client, SimpleRuntimeVersion, TransactionVersionBump};
 
 use async_trait::async_trait;
+use parking_lot::Mutex;
 use sp_version::RuntimeVersion;
 use std::{
 	fmt::Display,
+	sync::Arc,
 	time::{Duration, Instant},
 };
 
+/// Runtime version that is used to sign transactions. It is shared by the client and the guard,
+/// refreshing it.
+pub type SharedRuntimeVersion = Arc<Mutex<SimpleRuntimeVersion>>;
+
 /// Guards environment.
 #[async_trait]
 pub trait Environment<C>: Send + Sync + 'static {
@@ -92,6 +98,100 @@ pub fn abort_on_spec_version_change<C: Chain>(
 	});
 }
 
+/// Handle changes of the runtime transaction version, when transactions are signed using the
+/// custom runtime version.
+///
+/// With the `Refresh` action, the runtime version that is used to sign transactions is replaced
+/// with the actual runtime version. The spec version is refreshed too, because it is also a part
+/// of the signed transaction payload.
+pub fn on_transaction_version_change<C: Chain>(
+	mut env: impl Environment<C>,
+	action: TransactionVersionBump,
+	runtime_version: SharedRuntimeVersion,
+) {
+	if action == TransactionVersionBump::Keep {
+		return
+	}
+
+	async_std::task::spawn(async move {
+		log::info!(
+			target: "bridge-guard",
+			"Starting transaction_version guard for {}. Transaction version bump action: {:?}",
+			C::NAME,
+			action,
+		);
+
+		loop {
+			match env.runtime_version().await {
+				Ok(actual) =>
+					if !handle_runtime_version::<C>(&mut env, action, &runtime_version, &actual)
+						.await
+					{
+						return
+					},
+				Err(error) => log::warn!(
+					target: "bridge-guard",
+					"Failed to read {} runtime version: {}. Transaction version bump may be missed",
+					C::NAME,
+					error,
+				),
+			}
+
+			env.sleep(conditions_check_delay::<C>()).await;
+		}
+	});
+}
+
+/// Compare the actual runtime version with the version that is used to sign transactions.
+/// Returns false if the guard has aborted the process.
+async fn handle_runtime_version<C: Chain>(
+	env: &mut impl Environment<C>,
+	action: TransactionVersionBump,
+	runtime_version: &SharedRuntimeVersion,
+	actual: &RuntimeVersion,
+) -> bool {
+	let actual = SimpleRuntimeVersion::from_runtime_version(actual);
+	let used = *runtime_version.lock();
+	let is_transaction_version_bumped = actual.transaction_version != used.transaction_version;
+	match action {
+		TransactionVersionBump::Keep => true,
+		TransactionVersionBump::Refresh => {
+			if actual.spec_version != used.spec_version || is_transaction_version_bumped {
+				log::warn!(
+					target: "bridge-guard",
+					"{} runtime version has changed from {:?} to {:?}{}. Refreshing transaction \
+					construction parameters",
+					C::NAME,
+					used,
+					actual,
+					if is_transaction_version_bumped {
+						" (transaction version bump)"
+					} else {
+						""
+					},
+				);
+				*runtime_version.lock() = actual;
+			}
+			true
+		},
+		TransactionVersionBump::Abort => {
+			if !is_transaction_version_bumped {
+				return true
+			}
+
+			log::error!(
+				target: "bridge-guard",
+				"{} runtime transaction version has changed from {} to {}. Aborting relay",
+				C::NAME,
+				used.transaction_version,
+				actual.transaction_version,
+			);
+			env.abort().await;
+			false
+		},
+	}
+}
+
 /// Delay between conditions check.
 fn conditions_check_delay<C: Chain>() -> Duration {
 	C::AVERAGE_BLOCK_INTERVAL * (10 + rand::random::<u32>() % 10)
@@ -168,6 +268,101 @@ pub(crate) mod tests {
 		});
 	}
 
+	fn run_transaction_version_guard(
+		action: TransactionVersionBump,
+	) -> (
+		UnboundedSender<RuntimeVersion>,
+		UnboundedReceiver<()>,
+		UnboundedReceiver<()>,
+		SharedRuntimeVersion,
+	) {
+		let (
+			(runtime_version_tx, runtime_version_rx),
+			(slept_tx, slept_rx),
+			(aborted_tx, aborted_rx),
+		) = (unbounded(), unbounded(), unbounded());
+		let runtime_version =
+			Arc::new(Mutex::new(SimpleRuntimeVersion { spec_version: 1, transaction_version: 1 }));
+		on_transaction_version_change(
+			TestEnvironment { runtime_version_rx, slept_tx, aborted_tx },
+			action,
+			runtime_version.clone(),
+		);
+		(runtime_version_tx, slept_rx, aborted_rx, runtime_version)
+	}
+
+	#[test]
+	fn refreshes_runtime_version_when_transaction_version_is_changed() {
+		async_std::task::block_on(async {
+			let (mut runtime_version_tx, mut slept_rx, mut aborted_rx, runtime_version) =
+				run_transaction_version_guard(TransactionVersionBump::Refresh);
+			// the upgraded runtime only accepts transactions, signed using its version
+			let is_accepted_by_runtime = |runtime: &RuntimeVersion| {
+				let signed_with = *runtime_version.lock();
+				signed_with.spec_version == runtime.spec_version &&
+					signed_with.transaction_version == runtime.transaction_version
+			};
+
+			// client responds with the same version
+			let initial =
+				RuntimeVersion { spec_version: 1, transaction_version: 1, ..Default::default() };
+			runtime_version_tx.send(initial.clone()).await.unwrap();
+			slept_rx.next().await;
+			assert!(is_accepted_by_runtime(&initial));
+
+			// client responds with the bumped transaction version
+			let upgraded =
+				RuntimeVersion { spec_version: 2, transaction_version: 2, ..Default::default() };
+			assert!(!is_accepted_by_runtime(&upgraded));
+			runtime_version_tx.send(upgraded.clone()).await.unwrap();
+			slept_rx.next().await;
+
+			// then transactions are signed using the refreshed version and the relay is not
+			// aborted
+			assert_eq!(
+				*runtime_version.lock(),
+				SimpleRuntimeVersion { spec_version: 2, transaction_version: 2 },
+			);
+			assert!(is_accepted_by_runtime(&upgraded));
+			assert!(aborted_rx.next().now_or_never().is_none());
+		});
+	}
+
+	#[test]
+	fn aborts_when_transaction_version_is_changed() {
+		async_std::task::block_on(async {
+			let (mut runtime_version_tx, mut slept_rx, mut aborted_rx, runtime_version) =
+				run_transaction_version_guard(TransactionVersionBump::Abort);
+
+			// spec version change alone is ignored
+			runtime_version_tx
+				.send(RuntimeVersion {
+					spec_version: 2,
+					transaction_version: 1,
+					..Default::default()
+				})
+				.await
+				.unwrap();
+			slept_rx.next().await;
+
+			// and the transaction version change aborts the relay
+			runtime_version_tx
+				.send(RuntimeVersion {
+					spec_version: 3,
+					transaction_version: 2,
+					..Default::default()
+				})
+				.await
+				.unwrap();
+			aborted_rx.next().await;
+			assert!(slept_rx.next().now_or_never().is_none());
+			assert_eq!(
+				*runtime_version.lock(),
+				SimpleRuntimeVersion { spec_version: 1, transaction_version: 1 },
+			);
+		});
+	}
+
 	#[test]
 	fn does_not_aborts_when_spec_version_is_unchanged() {
 		async_std::task::block_on(async {
@@ -25,28 +25,38 @@ mod sync_header;
 mod transaction_tracker;
 
 pub mod calls;
+pub mod endpoints;
+pub mod events;
+pub mod global_pause;
 pub mod guard;
+pub mod metadata;
 pub mod metrics;
+pub mod nonce_gaps;
+pub mod rate_limit;
+pub mod signing_cache;
+pub mod storage_proof;
 pub mod test_chain;
+pub mod xcm_filter;
 
 use std::time::Duration;
 
 pub use crate::{
 	chain::{
-		AccountKeyPairOf, BlockWithJustification, CallOf, Chain, ChainWithBalances,
-		ChainWithGrandpa, ChainWithMessages, ChainWithRuntimeVersion, ChainWithTransactions,
-		ChainWithUtilityPallet, FullRuntimeUtilityPallet, MockedRuntimeUtilityPallet, Parachain,
-		RelayChain, SignParam, SignedBlockOf, TransactionStatusOf, UnsignedTransaction,
-		UtilityPallet,
+		AccountKeyPairOf, BatchCallKind, BlockWithJustification, CallOf, Chain, ChainWithBalances,
+		ChainWithGrandpa, ChainWithMessages, ChainWithProxyPallet, ChainWithRuntimeVersion,
+		ChainWithTransactions, ChainWithUtilityPallet, FullRuntimeUtilityPallet,
+		MockedRuntimeUtilityPallet, Parachain, RelayChain, SignParam, SignedBlockOf,
+		TransactionStatusOf, UnsignedTransaction, UtilityPallet,
 	},
 	client::{
-		is_ancient_block, rpc_with_caching as new, ChainRuntimeVersion, Client,
-		OpaqueGrandpaAuthoritiesSet, RpcWithCachingClient, SimpleRuntimeVersion, StreamDescription,
-		Subscription, ANCIENT_BLOCK_THRESHOLD,
+		is_ancient_block, rpc_with_caching as new, split_rpc_with_caching as new_split,
+		ChainRuntimeVersion, Client, NonceSource, OpaqueGrandpaAuthoritiesSet, RpcTraceParams,
+		RpcWithCachingClient, RpcWithCachingSplitClient, SimpleRuntimeVersion, StreamDescription,
+		Subscription, TransactionVersionBump, ANCIENT_BLOCK_THRESHOLD,
 	},
 	error::{Error, Result},
 	sync_header::SyncHeader,
-	transaction_tracker::TransactionTracker,
+	transaction_tracker::{InFlightSlot, InFlightTransactions, TransactionTracker},
 };
 pub use bp_runtime::{
 	AccountIdOf, AccountPublicOf, BalanceOf, BlockNumberOf, Chain as ChainBase, HashOf, HeaderIdOf,
@@ -70,6 +80,35 @@ pub struct ConnectionParams {
 	pub secure: bool,
 	/// Defined chain runtime version
 	pub chain_runtime_version: ChainRuntimeVersion,
+	/// Maximal number of submitted transactions that are not yet finalized or lost. When the
+	/// limit is reached, new transactions are deferred. If `None`, there's no limit.
+	pub max_in_flight_transactions: Option<usize>,
+	/// If set, gaps in nonces of submitted transactions are filled with no-op transactions.
+	/// The value is the maximal number of such transactions. If `None`, gaps are not filled.
+	pub max_nonce_gap_fillers: Option<u32>,
+	/// State, used to read nonces of transaction signers.
+	pub nonce_source: NonceSource,
+	/// Websocket URL of the endpoint that submits transactions directly to block authors,
+	/// bypassing the transaction pool. If the endpoint is unavailable or rejects the
+	/// transaction, it is submitted to the node.
+	pub direct_inclusion_uri: Option<String>,
+	/// If set, the client calls a lightweight RPC method with this interval, so that idle
+	/// connections aren't dropped by proxies and dead connections are detected faster.
+	pub keepalive_interval: Option<Duration>,
+	/// If set, raw JSON-RPC requests and responses are logged with the `trace` level under the
+	/// `bridge-rpc` target.
+	pub rpc_trace: Option<RpcTraceParams>,
+	/// What to do when the transaction version of the chain runtime is changed, while the
+	/// custom runtime version is used.
+	pub transaction_version_bump: TransactionVersionBump,
+	/// Websocket URLs of other nodes of the same chain. When the connection to the node fails,
+	/// the client switches to the next endpoint. Endpoints of nodes that serve other chain or
+	/// incompatible runtime are marked bad and never used again.
+	pub fallback_uris: Vec<String>,
+	/// If set and the runtime version is read from the node, the version that is used to sign
+	/// transactions is cached until the runtime is upgraded, instead of reading it before
+	/// signing every transaction.
+	pub signing_cache: bool,
 }
 
 impl Default for ConnectionParams {
@@ -81,6 +120,15 @@ impl Default for ConnectionParams {
 			path: None,
 			secure: false,
 			chain_runtime_version: ChainRuntimeVersion::Auto,
+			max_in_flight_transactions: None,
+			max_nonce_gap_fillers: None,
+			nonce_source: NonceSource::Best,
+			direct_inclusion_uri: None,
+			keepalive_interval: None,
+			rpc_trace: None,
+			transaction_version_bump: TransactionVersionBump::Keep,
+			fallback_uris: Vec::new(),
+			signing_cache: true,
 		}
 	}
 }
@@ -0,0 +1,222 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Introspection of the runtime metadata.
+//!
+//! The relay is built for the concrete runtime, so it only uses the metadata (and declared
+//! versions of pallets) to detect mismatches between the runtime and the relay configuration.
+//! E.g. reading the storage item of the misnamed pallet returns nothing, which looks exactly
+//! like the missing value, so storage items that the relay is going to read are checked
+//! against the metadata before the relay is started.
+
+use crate::{Chain, Client, Error, HashOf, Result};
+
+use bp_runtime::storage_value_key;
+use codec::Decode;
+use frame_metadata::{RuntimeMetadata, RuntimeMetadataPrefixed};
+
+/// Name of the runtime API method, returning the runtime metadata.
+pub(crate) const METADATA_METHOD: &str = "Metadata_metadata";
+
+/// Name of the storage item, where FRAME pallets keep their declared storage version.
+const STORAGE_VERSION_ITEM: &str = ":__STORAGE_VERSION__:";
+
+/// Storage items of the bridge messages pallet, used to find its instances.
+pub const MESSAGES_PALLET_STORAGE_ITEMS: [&str; 3] =
+	["InboundLanes", "OutboundLanes", "OutboundMessages"];
+
+/// Pallets of the runtime and their storage items.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PalletsMetadata(Vec<(String, Vec<String>)>);
+
+impl PalletsMetadata {
+	/// Create pallets metadata from the list of pallet names and their storage items.
+	pub fn new(pallets: Vec<(String, Vec<String>)>) -> Self {
+		PalletsMetadata(pallets)
+	}
+
+	/// Returns names of pallets that have all storage items of the bridge messages pallet.
+	pub fn messages_pallets(&self) -> Vec<String> {
+		self.0
+			.iter()
+			.filter(|(_, storage_items)| {
+				MESSAGES_PALLET_STORAGE_ITEMS
+					.iter()
+					.all(|item| storage_items.iter().any(|storage_item| storage_item == item))
+			})
+			.map(|(name, _)| name.clone())
+			.collect()
+	}
+
+	/// Returns `Pallet::Item` names of given storage items that are missing from the metadata.
+	pub fn missing_storage_items(&self, items: &[(&str, &str)]) -> Vec<String> {
+		items
+			.iter()
+			.filter(|(pallet_name, item)| {
+				!self.0.iter().any(|(name, storage_items)| {
+					name == pallet_name &&
+						storage_items.iter().any(|storage_item| storage_item == item)
+				})
+			})
+			.map(|(pallet_name, item)| format!("{pallet_name}::{item}"))
+			.collect()
+	}
+}
+
+/// Read names of runtime pallets and their storage items at given block.
+pub async fn read_pallets_metadata<C: Chain>(
+	client: &impl Client<C>,
+	at: HashOf<C>,
+) -> Result<PalletsMetadata> {
+	let encoded_metadata: Vec<u8> = client.state_call(at, METADATA_METHOD.into(), ()).await?;
+	let metadata = RuntimeMetadataPrefixed::decode(&mut &encoded_metadata[..])?;
+
+	// storage items of all supported metadata versions are the same
+	macro_rules! pallets {
+		($metadata:expr) => {
+			$metadata
+				.pallets
+				.into_iter()
+				.map(|pallet| {
+					let storage_items = pallet
+						.storage
+						.map(|storage| {
+							storage.entries.into_iter().map(|entry| entry.name).collect()
+						})
+						.unwrap_or_default();
+					(pallet.name, storage_items)
+				})
+				.collect()
+		};
+	}
+	let pallets = match metadata.1 {
+		RuntimeMetadata::V14(metadata) => pallets!(metadata),
+		RuntimeMetadata::V15(metadata) => pallets!(metadata),
+		_ => return Err(Error::Custom(format!("Unsupported {} runtime metadata version", C::NAME))),
+	};
+	Ok(PalletsMetadata(pallets))
+}
+
+/// Read the constant of the pallet with given name at given block.
+///
+/// Returns `None` if the runtime doesn't have the pallet or the pallet doesn't have the
+/// constant.
+pub async fn read_pallet_constant<C: Chain, T: Decode>(
+	client: &impl Client<C>,
+	at: HashOf<C>,
+	pallet_name: &str,
+	constant_name: &str,
+) -> Result<Option<T>> {
+	let encoded_metadata: Vec<u8> = client.state_call(at, METADATA_METHOD.into(), ()).await?;
+	let metadata = RuntimeMetadataPrefixed::decode(&mut &encoded_metadata[..])?;
+
+	// constants of all supported metadata versions are the same
+	macro_rules! constant_value {
+		($metadata:expr) => {
+			$metadata
+				.pallets
+				.into_iter()
+				.find(|pallet| pallet.name == pallet_name)
+				.and_then(|pallet| {
+					pallet.constants.into_iter().find(|constant| constant.name == constant_name)
+				})
+				.map(|constant| constant.value)
+		};
+	}
+	let encoded_value = match metadata.1 {
+		RuntimeMetadata::V14(metadata) => constant_value!(metadata),
+		RuntimeMetadata::V15(metadata) => constant_value!(metadata),
+		_ => return Err(Error::Custom(format!("Unsupported {} runtime metadata version", C::NAME))),
+	};
+	encoded_value
+		.map(|value| T::decode(&mut &value[..]).map_err(Into::into))
+		.transpose()
+}
+
+/// Read declared storage version of the pallet with given name at given block.
+///
+/// Pallets that don't declare the version have version zero, so the missing value is read
+/// as zero too.
+pub async fn read_pallet_storage_version<C: Chain>(
+	client: &impl Client<C>,
+	at: HashOf<C>,
+	pallet_name: &str,
+) -> Result<u16> {
+	Ok(client
+		.storage_value(at, storage_value_key(pallet_name, STORAGE_VERSION_ITEM))
+		.await?
+		.unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn messages_pallets_are_found() {
+		let pallets = |items: &[&str]| items.iter().map(|item| item.to_string()).collect();
+		let metadata = PalletsMetadata::new(vec![
+			("System".into(), pallets(&["Account", "Number"])),
+			(
+				"BridgeRococoMessages".into(),
+				pallets(&["PalletOwner", "InboundLanes", "OutboundLanes", "OutboundMessages"]),
+			),
+			("BridgeRococoGrandpa".into(), pallets(&["BestFinalized"])),
+			(
+				"BridgeWestendMessages".into(),
+				pallets(&["InboundLanes", "OutboundLanes", "OutboundMessages"]),
+			),
+		]);
+		assert_eq!(
+			metadata.messages_pallets(),
+			vec!["BridgeRococoMessages".to_string(), "BridgeWestendMessages".to_string()],
+		);
+	}
+
+	#[test]
+	fn missing_storage_items_are_found() {
+		let metadata = PalletsMetadata::new(vec![
+			("System".into(), vec!["Account".into()]),
+			("BridgeRococoMessages".into(), vec!["InboundLanes".into(), "OutboundLanes".into()]),
+		]);
+
+		// correct pallet name
+		assert!(metadata
+			.missing_storage_items(&[
+				("System", "Account"),
+				("BridgeRococoMessages", "InboundLanes"),
+				("BridgeRococoMessages", "OutboundLanes"),
+			])
+			.is_empty());
+		// incorrect pallet name
+		assert_eq!(
+			metadata.missing_storage_items(&[
+				("System", "Account"),
+				("BridgeRococoMesages", "InboundLanes"),
+				("BridgeRococoMesages", "OutboundLanes"),
+			]),
+			vec![
+				"BridgeRococoMesages::InboundLanes".to_string(),
+				"BridgeRococoMesages::OutboundLanes".to_string(),
+			],
+		);
+		// missing storage item of the correct pallet
+		assert_eq!(
+			metadata.missing_storage_items(&[("BridgeRococoMessages", "OutboundMessages")]),
+			vec!["BridgeRococoMessages::OutboundMessages".to_string()],
+		);
+	}
+}
@@ -0,0 +1,67 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{endpoints::Endpoints, Chain};
+
+use async_trait::async_trait;
+use relay_utils::metrics::{
+	metric_name, register, Gauge, Metric, PrometheusError, Registry, StandaloneMetric, U64,
+};
+use std::time::Duration;
+
+/// Metric update interval.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Metric that represents the number of client endpoints that have been marked bad.
+#[derive(Clone, Debug)]
+pub struct BadEndpointsMetric<C: Chain> {
+	endpoints: Endpoints<C>,
+	metric: Gauge<U64>,
+}
+
+impl<C: Chain> BadEndpointsMetric<C> {
+	/// Create new metric.
+	pub fn new(endpoints: Endpoints<C>) -> Result<Self, PrometheusError> {
+		Ok(BadEndpointsMetric {
+			endpoints,
+			metric: Gauge::new(
+				metric_name(None, &format!("at_{}_bad_endpoints", C::NAME)),
+				format!(
+					"Number of {} endpoints that have been marked bad, because they serve other \
+					chain or incompatible runtime",
+					C::NAME,
+				),
+			)?,
+		})
+	}
+}
+
+impl<C: Chain> Metric for BadEndpointsMetric<C> {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		register(self.metric.clone(), registry).map(drop)
+	}
+}
+
+#[async_trait]
+impl<C: Chain> StandaloneMetric for BadEndpointsMetric<C> {
+	fn update_interval(&self) -> Duration {
+		UPDATE_INTERVAL
+	}
+
+	async fn update(&self) {
+		self.metric.set(self.endpoints.bad_endpoints() as u64);
+	}
+}
@@ -0,0 +1,65 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{Chain, InFlightTransactions};
+
+use async_trait::async_trait;
+use relay_utils::metrics::{
+	metric_name, register, Gauge, Metric, PrometheusError, Registry, StandaloneMetric, U64,
+};
+use std::{marker::PhantomData, time::Duration};
+
+/// Metric update interval.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Metric that represents the number of in-flight transactions of the client.
+#[derive(Clone, Debug)]
+pub struct InFlightTransactionsMetric<C> {
+	in_flight: InFlightTransactions,
+	metric: Gauge<U64>,
+	_phantom: PhantomData<C>,
+}
+
+impl<C: Chain> InFlightTransactionsMetric<C> {
+	/// Create new metric.
+	pub fn new(in_flight: InFlightTransactions) -> Result<Self, PrometheusError> {
+		Ok(InFlightTransactionsMetric {
+			in_flight,
+			metric: Gauge::new(
+				metric_name(None, &format!("at_{}_in_flight_transactions", C::NAME)),
+				format!("Number of {} transactions that are not yet finalized or lost", C::NAME),
+			)?,
+			_phantom: Default::default(),
+		})
+	}
+}
+
+impl<C: Chain> Metric for InFlightTransactionsMetric<C> {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		register(self.metric.clone(), registry).map(drop)
+	}
+}
+
+#[async_trait]
+impl<C: Chain> StandaloneMetric for InFlightTransactionsMetric<C> {
+	fn update_interval(&self) -> Duration {
+		UPDATE_INTERVAL
+	}
+
+	async fn update(&self) {
+		self.metric.set(self.in_flight.count() as u64);
+	}
+}
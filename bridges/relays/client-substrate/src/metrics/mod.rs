@@ -16,6 +16,10 @@
 
 //! Contains several Substrate-specific metrics that may be exposed by relay.
 
+pub use bad_endpoints::BadEndpointsMetric;
 pub use float_storage_value::{FixedU128OrOne, FloatStorageValue, FloatStorageValueMetric};
+pub use in_flight_transactions::InFlightTransactionsMetric;
 
+mod bad_endpoints;
 mod float_storage_value;
+mod in_flight_transactions;
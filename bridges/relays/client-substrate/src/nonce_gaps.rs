@@ -0,0 +1,139 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Filling gaps in transaction nonces of the relay accounts.
+//!
+//! If the relay transaction is dropped from the pool, transactions with greater nonces,
+//! submitted by the same account, are stuck in the pool until the missing nonce is used by
+//! some other transaction. The client may fill such gaps with no-op `system.remark`
+//! transactions.
+
+use crate::{AccountIdOf, Chain, NonceOf};
+
+use bp_runtime::EncodedOrDecodedCall;
+use codec::Encode;
+use parking_lot::Mutex;
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Index of the `frame_system` pallet. It is the first pallet in all known runtimes.
+const SYSTEM_PALLET_INDEX: u8 = 0;
+
+/// Returns no-op call that is used to fill nonce gaps.
+pub fn remark_call<C: Chain>() -> EncodedOrDecodedCall<C::Call> {
+	EncodedOrDecodedCall::Encoded(
+		(SYSTEM_PALLET_INDEX, crate::calls::SystemCall::remark(Vec::new())).encode(),
+	)
+}
+
+/// Tracker of nonce gaps, shared by all clones.
+pub struct NonceGaps<C: Chain> {
+	data: Arc<Mutex<NonceGapsData<C>>>,
+}
+
+struct NonceGapsData<C: Chain> {
+	/// Number of gap-filling transactions that may still be submitted.
+	remaining_fillers: u32,
+	/// Latest nonce of the transaction, submitted by every signer.
+	latest_submitted: BTreeMap<AccountIdOf<C>, NonceOf<C>>,
+}
+
+impl<C: Chain> Clone for NonceGaps<C> {
+	fn clone(&self) -> Self {
+		NonceGaps { data: self.data.clone() }
+	}
+}
+
+impl<C: Chain> NonceGaps<C> {
+	/// Create new tracker that allows submitting at most `max_fillers` gap-filling transactions.
+	pub fn new(max_fillers: u32) -> Self {
+		NonceGaps {
+			data: Arc::new(Mutex::new(NonceGapsData {
+				remaining_fillers: max_fillers,
+				latest_submitted: BTreeMap::new(),
+			})),
+		}
+	}
+
+	/// Given the next nonce of the signer (as reported by the node), returns the nonce that is
+	/// missing and needs to be filled before submitting new transaction.
+	///
+	/// The node only counts transactions that are ready for inclusion. So if the next nonce is
+	/// less than the nonce of the transaction that we have submitted earlier, that transaction
+	/// is stuck in the pool, waiting for the missing nonce.
+	pub fn missing_nonce(
+		&self,
+		signer: &AccountIdOf<C>,
+		next_nonce: NonceOf<C>,
+	) -> Option<NonceOf<C>> {
+		let mut data = self.data.lock();
+		let latest_submitted = *data.latest_submitted.get(signer)?;
+		if next_nonce >= latest_submitted {
+			return None
+		}
+
+		if data.remaining_fillers == 0 {
+			log::warn!(
+				target: "bridge",
+				"{} transaction with nonce {} of {:?} is missing, but the limit of gap-filling \
+				transactions is reached",
+				C::NAME,
+				next_nonce,
+				signer,
+			);
+			return None
+		}
+
+		data.remaining_fillers -= 1;
+		Some(next_nonce)
+	}
+
+	/// Remember that the transaction with given nonce has been submitted.
+	pub fn note_submitted(&self, signer: AccountIdOf<C>, nonce: NonceOf<C>) {
+		let mut data = self.data.lock();
+		let latest_submitted = data.latest_submitted.entry(signer).or_insert(nonce);
+		*latest_submitted = std::cmp::max(*latest_submitted, nonce);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_chain::TestChain;
+
+	#[test]
+	fn nonce_gaps_are_detected() {
+		let gaps = NonceGaps::<TestChain>::new(2);
+
+		// nothing has been submitted yet
+		assert_eq!(gaps.missing_nonce(&1, 5), None);
+
+		// transactions 5 and 6 are submitted and transaction 5 is dropped
+		gaps.note_submitted(1, 5);
+		gaps.note_submitted(1, 6);
+		assert_eq!(gaps.missing_nonce(&1, 7), None);
+		assert_eq!(gaps.missing_nonce(&2, 5), None);
+		assert_eq!(gaps.missing_nonce(&1, 5), Some(5));
+
+		// the number of fillers is limited
+		assert_eq!(gaps.missing_nonce(&1, 5), Some(5));
+		assert_eq!(gaps.missing_nonce(&1, 5), None);
+	}
+
+	#[test]
+	fn remark_call_is_encoded() {
+		assert_eq!(remark_call::<TestChain>().into_encoded(), vec![0, 1, 0]);
+	}
+}
@@ -0,0 +1,172 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Reading the per-account rate limit quota.
+//!
+//! Some chains limit the number of transactions that an account may submit within some
+//! period (e.g. using a custom signed extension). Such limits are not a part of the bridge,
+//! so the storage map with the remaining quota of accounts is chain-specific. The map is
+//! expected to be keyed by the account id and its value is the remaining number of
+//! transactions, encoded as unsigned integer of any size. Accounts without the map entry
+//! are not limited.
+
+use crate::{AccountIdOf, Chain, Client, Error, HashOf};
+
+use bp_runtime::storage_map_final_key;
+use codec::Encode;
+use frame_support::{Blake2_128Concat, Identity, Twox64Concat};
+use sp_core::storage::StorageKey;
+use std::{fmt, str::FromStr};
+
+/// Hasher of the quota storage map key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuotaKeyHasher {
+	/// `Blake2_128Concat` hasher.
+	Blake2_128Concat,
+	/// `Twox64Concat` hasher.
+	Twox64Concat,
+	/// `Identity` hasher.
+	Identity,
+}
+
+/// Location of the remaining rate limit quota of accounts in the runtime storage.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RateLimitQuotaStorage {
+	/// Name of the pallet that owns the quota map.
+	pub pallet: String,
+	/// Name of the quota map.
+	pub item: String,
+	/// Hasher of the map key.
+	pub hasher: QuotaKeyHasher,
+}
+
+impl RateLimitQuotaStorage {
+	/// Returns storage key of the quota of given account.
+	pub fn storage_key<AccountId: Encode>(&self, account: &AccountId) -> StorageKey {
+		let account = account.encode();
+		match self.hasher {
+			QuotaKeyHasher::Blake2_128Concat =>
+				storage_map_final_key::<Blake2_128Concat>(&self.pallet, &self.item, &account),
+			QuotaKeyHasher::Twox64Concat =>
+				storage_map_final_key::<Twox64Concat>(&self.pallet, &self.item, &account),
+			QuotaKeyHasher::Identity =>
+				storage_map_final_key::<Identity>(&self.pallet, &self.item, &account),
+		}
+	}
+
+	/// Read the remaining quota of given account at given block. Returns `None` if the account
+	/// is not limited.
+	pub async fn read_remaining_quota<C: Chain>(
+		&self,
+		client: &impl Client<C>,
+		at: HashOf<C>,
+		account: &AccountIdOf<C>,
+	) -> Result<Option<u128>, Error> {
+		let quota = match client.raw_storage_value(at, self.storage_key(account)).await? {
+			Some(quota) => quota,
+			None => return Ok(None),
+		};
+		decode_quota(&quota.0).map(Some).ok_or_else(|| {
+			Error::Custom(format!(
+				"{} rate limit quota at {} has unexpected size of {} bytes",
+				C::NAME,
+				self,
+				quota.0.len(),
+			))
+		})
+	}
+}
+
+impl FromStr for RateLimitQuotaStorage {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || {
+			format!(
+				"Invalid rate limit quota storage: {s:?}. Expected `<pallet>.<map>` or \
+				`<pallet>.<map>:<hasher>`, where hasher is one of `blake2-128-concat` (default), \
+				`twox-64-concat` or `identity`"
+			)
+		};
+
+		let (location, hasher) = match s.split_once(':') {
+			Some((location, "blake2-128-concat")) => (location, QuotaKeyHasher::Blake2_128Concat),
+			Some((location, "twox-64-concat")) => (location, QuotaKeyHasher::Twox64Concat),
+			Some((location, "identity")) => (location, QuotaKeyHasher::Identity),
+			Some(_) => return Err(invalid()),
+			None => (s, QuotaKeyHasher::Blake2_128Concat),
+		};
+		match location.split_once('.') {
+			Some((pallet, item))
+				if !pallet.is_empty() && !item.is_empty() && !item.contains('.') =>
+				Ok(RateLimitQuotaStorage { pallet: pallet.into(), item: item.into(), hasher }),
+			_ => Err(invalid()),
+		}
+	}
+}
+
+impl fmt::Display for RateLimitQuotaStorage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}.{}", self.pallet, self.item)
+	}
+}
+
+/// Decode quota, encoded as unsigned integer of any size.
+fn decode_quota(encoded: &[u8]) -> Option<u128> {
+	if !matches!(encoded.len(), 1 | 2 | 4 | 8 | 16) {
+		return None
+	}
+
+	let mut quota = [0u8; 16];
+	quota[..encoded.len()].copy_from_slice(encoded);
+	Some(u128::from_le_bytes(quota))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn quota_storage_is_parsed() {
+		assert_eq!(
+			RateLimitQuotaStorage::from_str("RateLimiter.Quota"),
+			Ok(RateLimitQuotaStorage {
+				pallet: "RateLimiter".into(),
+				item: "Quota".into(),
+				hasher: QuotaKeyHasher::Blake2_128Concat,
+			}),
+		);
+		assert_eq!(
+			RateLimitQuotaStorage::from_str("RateLimiter.Quota:twox-64-concat")
+				.map(|storage| storage.hasher),
+			Ok(QuotaKeyHasher::Twox64Concat),
+		);
+		assert!(RateLimitQuotaStorage::from_str("RateLimiter").is_err());
+		assert!(RateLimitQuotaStorage::from_str("RateLimiter.Quota.Extra").is_err());
+		assert!(RateLimitQuotaStorage::from_str("RateLimiter.Quota:sha256").is_err());
+	}
+
+	#[test]
+	fn quota_of_any_size_is_decoded() {
+		assert_eq!(decode_quota(&5u8.encode()), Some(5));
+		assert_eq!(decode_quota(&500u16.encode()), Some(500));
+		assert_eq!(decode_quota(&500_000u32.encode()), Some(500_000));
+		assert_eq!(decode_quota(&u64::MAX.encode()), Some(u64::MAX as u128));
+		assert_eq!(decode_quota(&u128::MAX.encode()), Some(u128::MAX));
+		assert_eq!(decode_quota(&[0, 1, 2]), None);
+		assert_eq!(decode_quota(&[]), None);
+	}
+}
@@ -0,0 +1,182 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Caching invariant parts of signed transactions.
+//!
+//! The genesis hash and the runtime version are parts of the signature payload of every
+//! transaction. The genesis hash never changes and is read once, when the client connects to
+//! the node. But when the runtime version isn't configured, it is read from the node before
+//! signing every transaction, which costs an RPC request on every iteration of busy lanes. The
+//! cache keeps the version until the runtime may have been upgraded: the best block announces
+//! the runtime environment update, the node rejects the transaction signature or the client
+//! reconnects to the node.
+
+use crate::{Chain, Error, HeaderOf, Result, SimpleRuntimeVersion};
+
+use parking_lot::Mutex;
+use sp_runtime::{
+	generic::DigestItem,
+	traits::Header as HeaderT,
+	transaction_validity::{InvalidTransaction, TransactionValidityError},
+};
+use std::{future::Future, marker::PhantomData, sync::Arc};
+
+/// Cache of the runtime version, used to sign transactions, shared by all clones.
+pub struct SigningCache<C: Chain> {
+	runtime_version: Arc<Mutex<Option<SimpleRuntimeVersion>>>,
+	_phantom: PhantomData<C>,
+}
+
+impl<C: Chain> Clone for SigningCache<C> {
+	fn clone(&self) -> Self {
+		SigningCache { runtime_version: self.runtime_version.clone(), _phantom: PhantomData }
+	}
+}
+
+impl<C: Chain> Default for SigningCache<C> {
+	fn default() -> Self {
+		SigningCache { runtime_version: Arc::new(Mutex::new(None)), _phantom: PhantomData }
+	}
+}
+
+impl<C: Chain> SigningCache<C> {
+	/// Returns cached runtime version. If there's no cached version, it is read using given
+	/// function and cached.
+	pub async fn runtime_version<F>(
+		&self,
+		read_runtime_version: impl FnOnce() -> F,
+	) -> Result<SimpleRuntimeVersion>
+	where
+		F: Future<Output = Result<SimpleRuntimeVersion>>,
+	{
+		let cached = *self.runtime_version.lock();
+		if let Some(runtime_version) = cached {
+			return Ok(runtime_version)
+		}
+
+		let runtime_version = read_runtime_version().await?;
+		*self.runtime_version.lock() = Some(runtime_version);
+		Ok(runtime_version)
+	}
+
+	/// Invalidate the cache if the runtime has been upgraded at given best block.
+	pub fn note_best_header(&self, header: &HeaderOf<C>) {
+		let is_runtime_updated = header
+			.digest()
+			.logs()
+			.iter()
+			.any(|item| matches!(item, DigestItem::RuntimeEnvironmentUpdated));
+		if is_runtime_updated {
+			self.invalidate("runtime environment has been updated at the best block");
+		}
+	}
+
+	/// Invalidate the cache if the transaction has been rejected because of the bad signature.
+	/// The runtime version is a part of the signature payload, so it may be outdated.
+	pub fn note_transaction_error(&self, error: &Error) {
+		if matches!(
+			error,
+			Error::TransactionInvalid(TransactionValidityError::Invalid(
+				InvalidTransaction::BadProof
+			))
+		) {
+			self.invalidate("transaction signature has been rejected");
+		}
+	}
+
+	/// Forget the cached runtime version.
+	pub fn invalidate(&self, reason: &str) {
+		let runtime_version = self.runtime_version.lock().take();
+		if let Some(runtime_version) = runtime_version {
+			log::debug!(
+				target: "bridge",
+				"Forgetting cached {} runtime version {:?}: {}",
+				C::NAME,
+				runtime_version,
+				reason,
+			);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_chain::TestChain;
+	use sp_runtime::Digest;
+
+	const VERSION_1: SimpleRuntimeVersion =
+		SimpleRuntimeVersion { spec_version: 1, transaction_version: 1 };
+	const VERSION_2: SimpleRuntimeVersion =
+		SimpleRuntimeVersion { spec_version: 2, transaction_version: 2 };
+
+	fn header(logs: Vec<DigestItem>) -> HeaderOf<TestChain> {
+		HeaderOf::<TestChain>::new(
+			1,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Digest { logs },
+		)
+	}
+
+	#[async_std::test]
+	async fn runtime_version_is_cached_until_runtime_is_upgraded() {
+		let cache = SigningCache::<TestChain>::default();
+		let (node_version, reads) = (&Mutex::new(VERSION_1), &Mutex::new(0));
+		let read = move || async move {
+			*reads.lock() += 1;
+			Ok(*node_version.lock())
+		};
+
+		// the version is read once
+		assert_eq!(cache.runtime_version(read).await.unwrap(), VERSION_1);
+		assert_eq!(cache.runtime_version(read).await.unwrap(), VERSION_1);
+		assert_eq!(*reads.lock(), 1);
+
+		// the runtime is upgraded, but the best block without digest item doesn't invalidate
+		// the cache
+		*node_version.lock() = VERSION_2;
+		cache.note_best_header(&header(vec![DigestItem::Other(vec![42])]));
+		assert_eq!(cache.runtime_version(read).await.unwrap(), VERSION_1);
+
+		// the upgrade is announced by the best block
+		cache.note_best_header(&header(vec![DigestItem::RuntimeEnvironmentUpdated]));
+		assert_eq!(cache.runtime_version(read).await.unwrap(), VERSION_2);
+		assert_eq!(cache.runtime_version(read).await.unwrap(), VERSION_2);
+		assert_eq!(*reads.lock(), 2);
+	}
+
+	#[async_std::test]
+	async fn runtime_version_is_refreshed_when_signature_is_rejected() {
+		let cache = SigningCache::<TestChain>::default();
+		let node_version = &Mutex::new(VERSION_1);
+		let read = move || async move { Ok(*node_version.lock()) };
+		assert_eq!(cache.runtime_version(read).await.unwrap(), VERSION_1);
+
+		// we have missed the block that has upgraded the runtime
+		*node_version.lock() = VERSION_2;
+		cache.note_transaction_error(&Error::TransactionInvalid(
+			TransactionValidityError::Invalid(InvalidTransaction::Stale),
+		));
+		assert_eq!(cache.runtime_version(read).await.unwrap(), VERSION_1);
+
+		cache.note_transaction_error(&Error::TransactionInvalid(
+			TransactionValidityError::Invalid(InvalidTransaction::BadProof),
+		));
+		assert_eq!(cache.runtime_version(read).await.unwrap(), VERSION_2);
+	}
+}
@@ -0,0 +1,276 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Generation of storage proofs by the node and their local verification.
+//!
+//! Storage proofs are submitted to the bridged chain either as a plain set of trie nodes
+//! (the legacy format, accepted by all bridge pallets), or as a compact trie proof, where
+//! hashes of nodes that are included into the proof are omitted. The compact proof is smaller,
+//! but only runtimes that declare the [`COMPACT_STORAGE_PROOF_API_ID`] API accept it.
+
+use crate::{error::Result, Chain, Client, Error, HashOf};
+
+use bp_runtime::{HasherOf, StorageProofChecker};
+use futures::Future;
+use sp_core::storage::StorageKey;
+use sp_runtime::traits::Header as _;
+use sp_trie::StorageProof;
+use sp_version::{ApiId, RuntimeVersion};
+use std::time::Duration;
+
+/// Identifier of the runtime API, declared by runtimes that accept compact storage proofs in
+/// bridge calls. It is the `blake2_64` hash of the `BridgeCompactStorageProofApi` name.
+pub const COMPACT_STORAGE_PROOF_API_ID: ApiId = [0x5a, 0x36, 0x0b, 0x02, 0x6f, 0xdf, 0x7f, 0x72];
+
+/// Format of storage proofs, submitted to the bridged chain.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StorageProofFormat {
+	/// Set of trie nodes.
+	#[default]
+	Legacy,
+	/// Compact trie proof.
+	Compact,
+}
+
+impl StorageProofFormat {
+	/// Returns format, accepted by the runtime of given version.
+	pub fn detect(runtime_version: &RuntimeVersion) -> Self {
+		if runtime_version.has_api_with(&COMPACT_STORAGE_PROOF_API_ID, |version| version >= 1) {
+			StorageProofFormat::Compact
+		} else {
+			StorageProofFormat::Legacy
+		}
+	}
+
+	/// Encode the proof, generated at the block with given state root.
+	pub fn encode<C: Chain>(
+		&self,
+		proof: StorageProof,
+		state_root: HashOf<C>,
+	) -> Result<Vec<Vec<u8>>> {
+		match self {
+			StorageProofFormat::Legacy => Ok(proof.into_iter_nodes().collect()),
+			StorageProofFormat::Compact => proof
+				.into_compact_proof::<HasherOf<C>>(state_root)
+				.map(|proof| proof.encoded_nodes)
+				.map_err(|e| {
+					Error::Custom(format!(
+						"Failed to encode {} compact storage proof: {e:?}",
+						C::NAME
+					))
+				}),
+		}
+	}
+}
+
+/// Detect format of storage proofs, accepted by the chain that the `client` is connected to.
+///
+/// If the runtime version can't be read, the legacy format is used.
+pub async fn detect_storage_proof_format<C: Chain>(client: &impl Client<C>) -> StorageProofFormat {
+	let format = match client.runtime_version().await {
+		Ok(runtime_version) => StorageProofFormat::detect(&runtime_version),
+		Err(e) => {
+			log::warn!(
+				target: "bridge",
+				"Failed to read {} runtime version to detect accepted storage proof format: {:?}. \
+				Falling back to the legacy format",
+				C::NAME,
+				e,
+			);
+			StorageProofFormat::Legacy
+		},
+	};
+	log::info!(target: "bridge", "Using {:?} format of storage proofs, submitted to {}", format, C::NAME);
+	format
+}
+
+/// Generate storage proof of given keys at given block and encode it in given format.
+///
+/// See [`prove_storage_within`] for the `max_time` meaning.
+pub async fn prove_storage_in_format<C: Chain>(
+	client: &impl Client<C>,
+	at: HashOf<C>,
+	keys: Vec<StorageKey>,
+	max_time: Option<Duration>,
+	format: StorageProofFormat,
+) -> Result<Vec<Vec<u8>>> {
+	let proof = prove_storage_within(client, at, keys, max_time).await?;
+	let state_root = match format {
+		StorageProofFormat::Legacy => Default::default(),
+		StorageProofFormat::Compact => *client.header_by_hash(at).await?.state_root(),
+	};
+	format.encode::<C>(proof, state_root)
+}
+
+/// Generate storage proof of given keys at given block.
+///
+/// If `max_time` is set and the node doesn't generate the proof in time, the generation is
+/// abandoned with the `ProofGenerationTimeout` error. The proof, generated by the slow node,
+/// may be stale by the time it is submitted.
+pub async fn prove_storage_within<C: Chain>(
+	client: &impl Client<C>,
+	at: HashOf<C>,
+	keys: Vec<StorageKey>,
+	max_time: Option<Duration>,
+) -> Result<StorageProof> {
+	within::<C, _>(at, max_time, client.prove_storage(at, keys)).await
+}
+
+/// Wait for the proof generation future to complete in `max_time`.
+async fn within<C: Chain, T>(
+	at: HashOf<C>,
+	max_time: Option<Duration>,
+	generate_proof: impl Future<Output = Result<T>>,
+) -> Result<T> {
+	match max_time {
+		Some(max_time) => async_std::future::timeout(max_time, generate_proof)
+			.await
+			.map_err(|_| Error::proof_generation_timeout::<C>(at, max_time))?,
+		None => generate_proof.await,
+	}
+}
+
+/// Verify storage proof against the given state root and read values of given keys from it.
+///
+/// The verification is the same that the bridged chain runtime does: it fails if the proof
+/// doesn't match the state root, if some value can't be read from the proof or if the proof
+/// has nodes that aren't required to read these values.
+pub fn verify_storage_proof<C: Chain>(
+	state_root: HashOf<C>,
+	proof: StorageProof,
+	keys: &[StorageKey],
+) -> Result<Vec<Option<Vec<u8>>>> {
+	let mut checker =
+		StorageProofChecker::<HasherOf<C>>::new(state_root, proof.into_iter_nodes().collect())
+			.map_err(Error::StorageProofError)?;
+	let values = keys
+		.iter()
+		.map(|key| checker.read_value(&key.0))
+		.collect::<std::result::Result<Vec<_>, _>>()
+		.map_err(Error::StorageProofError)?;
+	checker.ensure_no_unused_nodes().map_err(Error::StorageProofError)?;
+	Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_chain::TestChain;
+	use relay_utils::MaybeConnectionError;
+
+	fn valid_proof() -> (HashOf<TestChain>, StorageProof, Vec<StorageKey>) {
+		let (root, proof) = bp_runtime::craft_valid_storage_proof();
+		let keys = [&b"key1"[..], &b"key2"[..], &b"key4"[..], &b"key22"[..]]
+			.iter()
+			.map(|key| StorageKey(key.to_vec()))
+			.collect();
+		(root, StorageProof::new(proof), keys)
+	}
+
+	#[test]
+	fn valid_storage_proof_is_verified() {
+		let (root, proof, keys) = valid_proof();
+		let values = verify_storage_proof::<TestChain>(root, proof, &keys).unwrap();
+		assert_eq!(values[0], Some(b"value1".to_vec()));
+		assert_eq!(values[1], Some(b"value2".to_vec()));
+		assert_eq!(values[3], None);
+	}
+
+	#[test]
+	fn storage_proof_is_encoded_in_given_format() {
+		let (root, proof, keys) = valid_proof();
+		let legacy = StorageProofFormat::Legacy.encode::<TestChain>(proof.clone(), root).unwrap();
+		assert_eq!(legacy, proof.clone().into_iter_nodes().collect::<Vec<_>>());
+
+		let compact = StorageProofFormat::Compact.encode::<TestChain>(proof.clone(), root).unwrap();
+		// hashes of nodes, included into the proof, are omitted
+		assert!(
+			compact.iter().map(Vec::len).sum::<usize>() <
+				legacy.iter().map(Vec::len).sum::<usize>()
+		);
+		let (decoded, decoded_root) = sp_trie::CompactProof { encoded_nodes: compact }
+			.to_storage_proof::<HasherOf<TestChain>>(Some(&root))
+			.unwrap();
+		assert_eq!(decoded_root, root);
+		assert_eq!(verify_storage_proof::<TestChain>(root, decoded, &keys).unwrap().len(), 4);
+	}
+
+	#[test]
+	fn storage_proof_format_is_detected_from_runtime_version() {
+		assert_eq!(
+			COMPACT_STORAGE_PROOF_API_ID,
+			sp_core::hashing::blake2_64(b"BridgeCompactStorageProofApi"),
+		);
+
+		let runtime_version =
+			|apis: Vec<(ApiId, u32)>| RuntimeVersion { apis: apis.into(), ..Default::default() };
+		assert_eq!(
+			StorageProofFormat::detect(&runtime_version(vec![])),
+			StorageProofFormat::Legacy,
+		);
+		assert_eq!(
+			StorageProofFormat::detect(&runtime_version(vec![([1; 8], 1)])),
+			StorageProofFormat::Legacy,
+		);
+		assert_eq!(
+			StorageProofFormat::detect(&runtime_version(vec![(COMPACT_STORAGE_PROOF_API_ID, 1)])),
+			StorageProofFormat::Compact,
+		);
+	}
+
+	#[test]
+	fn storage_proof_with_wrong_state_root_is_rejected() {
+		let (_, proof, keys) = valid_proof();
+		assert!(matches!(
+			verify_storage_proof::<TestChain>(Default::default(), proof, &keys),
+			Err(Error::StorageProofError(bp_runtime::StorageProofError::StorageRootMismatch)),
+		));
+	}
+
+	#[async_std::test]
+	async fn slow_proof_generation_is_abandoned() {
+		let (root, proof, _) = valid_proof();
+		let slow_proof = |delay| {
+			let proof = proof.clone();
+			async move {
+				async_std::task::sleep(delay).await;
+				Ok(proof)
+			}
+		};
+
+		// the proof is generated in time
+		let max_time = Some(Duration::from_millis(500));
+		assert_eq!(
+			within::<TestChain, _>(root, max_time, slow_proof(Duration::from_millis(10)))
+				.await
+				.ok(),
+			Some(proof.clone()),
+		);
+		assert_eq!(
+			within::<TestChain, _>(root, None, slow_proof(Duration::from_millis(10)))
+				.await
+				.ok(),
+			Some(proof.clone()),
+		);
+
+		// the proof generation takes too long
+		let max_time = Some(Duration::from_millis(10));
+		let result =
+			within::<TestChain, _>(root, max_time, slow_proof(Duration::from_secs(5))).await;
+		assert!(matches!(result, Err(Error::ProofGenerationTimeout { .. })));
+		assert!(result.unwrap_err().is_proof_generation_timeout());
+	}
+}
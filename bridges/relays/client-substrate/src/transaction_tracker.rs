@@ -16,13 +16,25 @@
 
 //! Helper for tracking transaction invalidation events.
 
-use crate::{Chain, Error, HashOf, HeaderIdOf, Subscription, TransactionStatusOf};
+use crate::{
+	client::SubscriptionBroadcaster, Chain, Error, HashOf, HeaderIdOf, Subscription,
+	TransactionStatusOf,
+};
 
 use async_trait::async_trait;
 use futures::{future::Either, Future, FutureExt, Stream, StreamExt};
 use relay_utils::{HeaderId, TrackedTransactionStatus};
 use sp_runtime::traits::Header as _;
-use std::time::Duration;
+use std::{
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+	time::Duration,
+};
+
+/// Interval at which we check if there's a free slot for the new in-flight transaction.
+const IN_FLIGHT_SLOT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 /// Transaction tracker environment.
 #[async_trait]
@@ -65,6 +77,7 @@ pub struct TransactionTracker<C: Chain, E> {
 	transaction_hash: HashOf<C>,
 	stall_timeout: Duration,
 	subscription: Subscription<TransactionStatusOf<C>>,
+	in_flight_slot: Option<InFlightSlot>,
 }
 
 impl<C: Chain, E: Environment<C>> TransactionTracker<C, E> {
@@ -75,7 +88,14 @@ impl<C: Chain, E: Environment<C>> TransactionTracker<C, E> {
 		transaction_hash: HashOf<C>,
 		subscription: Subscription<TransactionStatusOf<C>>,
 	) -> Self {
-		Self { environment, stall_timeout, transaction_hash, subscription }
+		Self { environment, stall_timeout, transaction_hash, subscription, in_flight_slot: None }
+	}
+
+	/// Associate in-flight transaction slot with the tracker. The slot is freed when
+	/// the tracker is dropped - i.e. when the transaction is finalized or lost.
+	pub fn with_in_flight_slot(mut self, in_flight_slot: InFlightSlot) -> Self {
+		self.in_flight_slot = Some(in_flight_slot);
+		self
 	}
 
 	// TODO (https://github.com/paritytech/parity-bridges-common/issues/2133): remove me after
@@ -90,7 +110,39 @@ impl<C: Chain, E: Environment<C>> TransactionTracker<C, E> {
 			stall_timeout: self.stall_timeout,
 			transaction_hash: self.transaction_hash,
 			subscription: self.subscription,
+			in_flight_slot: self.in_flight_slot,
+		}
+	}
+
+	/// Split the tracker into given number of trackers of the same transaction.
+	///
+	/// This is used when single transaction is submitted on behalf of several relay loops, e.g.
+	/// when it is a batch of their calls. The in-flight slot, if any, is kept by the first
+	/// returned tracker.
+	pub async fn split(self, count: usize) -> Result<Vec<Self>, Error>
+	where
+		E: Clone,
+	{
+		let broadcaster = SubscriptionBroadcaster::new(self.subscription).map_err(|_| {
+			Error::Custom(format!(
+				"{} transaction {:?} status subscription is already broadcasted",
+				C::NAME,
+				self.transaction_hash,
+			))
+		})?;
+
+		let mut in_flight_slot = self.in_flight_slot;
+		let mut trackers = Vec::with_capacity(count);
+		for _ in 0..count {
+			trackers.push(TransactionTracker {
+				environment: self.environment.clone(),
+				stall_timeout: self.stall_timeout,
+				transaction_hash: self.transaction_hash,
+				subscription: broadcaster.subscribe().await?,
+				in_flight_slot: in_flight_slot.take(),
+			});
 		}
+		Ok(trackers)
 	}
 
 	/// Wait for final transaction status and return it along with last known internal invalidation
@@ -156,6 +208,73 @@ impl<C: Chain, E: Environment<C>> relay_utils::TransactionTracker for Transactio
 	}
 }
 
+/// Transactions, submitted by the client, which are not yet finalized or lost.
+///
+/// All clones are sharing the same set of slots.
+#[derive(Clone, Debug, Default)]
+pub struct InFlightTransactions {
+	max: Option<usize>,
+	count: Arc<AtomicUsize>,
+}
+
+impl InFlightTransactions {
+	/// Create new in-flight transactions set. If `max` is `None`, the number of in-flight
+	/// transactions is not limited.
+	pub fn new(max: Option<usize>) -> Self {
+		InFlightTransactions { max, count: Default::default() }
+	}
+
+	/// Returns current number of in-flight transactions.
+	pub fn count(&self) -> usize {
+		self.count.load(Ordering::SeqCst)
+	}
+
+	/// Try to occupy the slot for the new in-flight transaction. Returns `None` if the limit
+	/// is reached.
+	pub fn try_acquire(&self) -> Option<InFlightSlot> {
+		self.count
+			.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| match self.max {
+				Some(max) if count >= max => None,
+				_ => Some(count + 1),
+			})
+			.ok()
+			.map(|_| InFlightSlot { count: self.count.clone() })
+	}
+
+	/// Wait until there's a free slot for the new in-flight transaction and occupy it.
+	pub async fn acquire<C: Chain>(&self) -> InFlightSlot {
+		let mut is_deferred = false;
+		loop {
+			if let Some(slot) = self.try_acquire() {
+				return slot
+			}
+
+			if !is_deferred {
+				log::debug!(
+					target: "bridge",
+					"There are already {} in-flight {} transactions. Deferring new transaction",
+					self.count(),
+					C::NAME,
+				);
+				is_deferred = true;
+			}
+			async_std::task::sleep(IN_FLIGHT_SLOT_CHECK_INTERVAL).await;
+		}
+	}
+}
+
+/// Occupied in-flight transaction slot. The slot is freed when dropped.
+#[derive(Debug)]
+pub struct InFlightSlot {
+	count: Arc<AtomicUsize>,
+}
+
+impl Drop for InFlightSlot {
+	fn drop(&mut self) {
+		self.count.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
 /// Transaction invalidation status.
 ///
 /// Note that in places where the `TransactionTracker` is used, the finalization event will be
@@ -346,6 +465,43 @@ mod tests {
 		is.map(|is| (ts, is))
 	}
 
+	#[test]
+	fn in_flight_transactions_are_limited() {
+		let in_flight = InFlightTransactions::new(Some(2));
+		let slot1 = in_flight.try_acquire().unwrap();
+		let slot2 = in_flight.clone().try_acquire().unwrap();
+		assert_eq!(in_flight.count(), 2);
+		assert!(in_flight.try_acquire().is_none());
+
+		drop(slot1);
+		assert_eq!(in_flight.count(), 1);
+		let _slot3 = in_flight.try_acquire().unwrap();
+		assert!(in_flight.try_acquire().is_none());
+
+		drop(slot2);
+		assert_eq!(in_flight.count(), 1);
+	}
+
+	#[test]
+	fn in_flight_slot_is_freed_when_tracker_is_dropped() {
+		let in_flight = InFlightTransactions::new(Some(1));
+		let (_sender, receiver) = futures::channel::mpsc::channel(1);
+		let tx_tracker = TransactionTracker::<TestChain, TestEnvironment>::new(
+			TestEnvironment(Ok(HeaderId(0, Default::default()))),
+			Duration::from_secs(0),
+			Default::default(),
+			Subscription::new_forwarded(
+				StreamDescription::new("test".into(), "test".into()),
+				receiver,
+			),
+		)
+		.with_in_flight_slot(in_flight.try_acquire().unwrap());
+		assert!(in_flight.try_acquire().is_none());
+
+		drop(tx_tracker);
+		assert_eq!(in_flight.count(), 0);
+	}
+
 	#[async_std::test]
 	async fn returns_finalized_on_finalized() {
 		assert_eq!(
@@ -0,0 +1,125 @@
+// Copyright 2019-2023 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Matching XCM messages against instruction filter.
+//!
+//! The bridge messages pallet treats message payload as an opaque blob. But for bridges that
+//! are used to send XCM messages, the payload is the encoded `VersionedXcm`. The filter
+//! decodes payload and checks whether the message has the top-level instruction with given
+//! name. Instructions of nested programs (e.g. `SetAppendix`) are not inspected.
+
+use codec::DecodeLimit;
+use std::{fmt, str::FromStr};
+use xcm::{latest::Xcm, VersionedXcm, MAX_XCM_DECODE_DEPTH};
+
+/// XCM instruction filter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XcmInstructionFilter(String);
+
+impl XcmInstructionFilter {
+	/// Returns `Some(true)` if the payload is the XCM message with the matching instruction,
+	/// `Some(false)` if it is the XCM message without such instruction and `None` if the
+	/// payload can't be decoded as the XCM message.
+	pub fn matches(&self, payload: &[u8]) -> Option<bool> {
+		let xcm = VersionedXcm::<()>::decode_all_with_depth_limit(
+			MAX_XCM_DECODE_DEPTH,
+			&mut &payload[..],
+		)
+		.ok()?;
+		let xcm = Xcm::<()>::try_from(xcm).ok()?;
+		Some(xcm.0.iter().any(|instruction| instruction_name(instruction) == self.0))
+	}
+}
+
+impl FromStr for XcmInstructionFilter {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let is_identifier = s.chars().next().map_or(false, |c| c.is_ascii_uppercase()) &&
+			s.chars().all(|c| c.is_ascii_alphanumeric());
+		if !is_identifier {
+			return Err(format!(
+				"Invalid XCM instruction name: {s:?}. Expected name of the instruction, e.g. `Transact`"
+			))
+		}
+
+		Ok(XcmInstructionFilter(s.into()))
+	}
+}
+
+impl fmt::Display for XcmInstructionFilter {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+/// Returns name of the instruction variant.
+fn instruction_name<T: fmt::Debug>(instruction: &T) -> String {
+	format!("{instruction:?}")
+		.chars()
+		.take_while(|c| c.is_ascii_alphanumeric())
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+	use xcm::latest::prelude::*;
+
+	fn payload(xcm: Xcm<()>) -> Vec<u8> {
+		VersionedXcm::<()>::from(xcm).encode()
+	}
+
+	#[test]
+	fn filter_matches_top_level_instruction() {
+		let filter = XcmInstructionFilter::from_str("Transact").unwrap();
+		assert_eq!(
+			filter.matches(&payload(Xcm(vec![
+				ClearOrigin,
+				Transact {
+					origin_kind: OriginKind::Xcm,
+					require_weight_at_most: Weight::zero(),
+					call: vec![].into(),
+				},
+			]))),
+			Some(true),
+		);
+		assert_eq!(filter.matches(&payload(Xcm(vec![ClearOrigin]))), Some(false));
+		assert_eq!(
+			XcmInstructionFilter::from_str("ClearOrigin")
+				.unwrap()
+				.matches(&payload(Xcm(vec![ClearOrigin]))),
+			Some(true),
+		);
+	}
+
+	#[test]
+	fn undecodable_payload_is_reported() {
+		let filter = XcmInstructionFilter::from_str("Transact").unwrap();
+		assert_eq!(filter.matches(&[0xFF, 0x01]), None);
+		let mut payload = payload(Xcm(vec![ClearOrigin]));
+		payload.push(0);
+		assert_eq!(filter.matches(&payload), None);
+	}
+
+	#[test]
+	fn invalid_filter_is_rejected() {
+		assert!(XcmInstructionFilter::from_str("").is_err());
+		assert!(XcmInstructionFilter::from_str("transact").is_err());
+		assert!(XcmInstructionFilter::from_str("Transact { .. }").is_err());
+	}
+}
@@ -0,0 +1,429 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Messages relay throughput benchmark.
+//!
+//! The benchmark floods the lane with messages at the source chain and runs the messages relay
+//! until all of them are delivered to the target chain. It only makes sense against development
+//! chains, so it is only available with the `benchmark` feature. Same as the `relay-messages`
+//! subcommand, it requires source chain headers to be relayed to the target chain by some other
+//! relay.
+
+use crate::{
+	cli::{bridge::*, chain_schema::*, relay_messages::MessagesRelayer, HexLaneId},
+	messages_discovery::MessageDiscovery,
+	messages_lane::{MessagesRelayParams, SubstrateMessageLane},
+	messages_pallet::PalletVersionMismatchAction,
+	messages_target::FeeEstimationFallback,
+	TransactionParams,
+};
+
+use async_trait::async_trait;
+use futures::{future::Either, StreamExt, TryStreamExt};
+use sp_core::Pair;
+use std::{
+	collections::BTreeMap,
+	time::{Duration, Instant},
+};
+use structopt::StructOpt;
+
+use bp_messages::{
+	storage_keys::{inbound_lane_data_key, outbound_lane_data_key},
+	ChainWithMessages as _, InboundLaneData, LaneId, MessageNonce, OutboundLaneData,
+};
+use messages_relay::message_lane_loop::OversizedWeightPolicy;
+use relay_substrate_client::{
+	storage_proof::StorageProofFormat, AccountIdOf, AccountKeyPairOf, BalanceOf, CallOf, Chain,
+	ChainWithBalances, ChainWithRuntimeVersion, ChainWithTransactions, Client, TransactionEra,
+	UnsignedTransaction,
+};
+use relay_utils::metrics::MetricsParams;
+
+/// Messages benchmark params.
+#[derive(StructOpt)]
+pub struct BenchmarkMessagesParams {
+	/// Hex-encoded lane id that should be flooded with messages. Defaults to `00000000`.
+	#[structopt(long, default_value = "00000000")]
+	lane: HexLaneId,
+	/// Number of messages to send.
+	#[structopt(long, default_value = "100")]
+	messages: MessageNonce,
+	/// Maximal number of message sending transactions that are submitted concurrently.
+	#[structopt(long, default_value = "4")]
+	concurrency: usize,
+	/// Size of every message payload in bytes.
+	#[structopt(long, default_value = "128")]
+	payload_size: usize,
+	#[structopt(flatten)]
+	source: SourceConnectionParams,
+	#[structopt(flatten)]
+	source_sign: SourceSigningParams,
+	#[structopt(flatten)]
+	target: TargetConnectionParams,
+	#[structopt(flatten)]
+	target_sign: TargetSigningParams,
+}
+
+/// Bridge representation that can be used from the CLI for benchmarking messages relay.
+pub trait MessagesBenchmarkCliBridge: MessagesCliBridge
+where
+	Self::Source: ChainWithTransactions,
+{
+	/// Build source chain call that sends message with given payload over given lane.
+	fn send_message_call(lane: LaneId, payload: Vec<u8>) -> anyhow::Result<CallOf<Self::Source>>;
+}
+
+/// Trait used for benchmarking messages relay between 2 chains.
+#[async_trait]
+pub trait MessagesBenchmarker: MessagesBenchmarkCliBridge + MessagesRelayer
+where
+	Self::Source: ChainWithTransactions + ChainWithRuntimeVersion,
+	Self::Target: ChainWithBalances,
+	AccountIdOf<Self::Source>: From<<AccountKeyPairOf<Self::Source> as Pair>::Public>,
+	AccountIdOf<Self::Target>: From<<AccountKeyPairOf<Self::Target> as Pair>::Public>,
+	BalanceOf<Self::Source>: TryFrom<BalanceOf<Self::Target>>,
+{
+	/// Flood the lane with messages and relay them, printing throughput summary.
+	async fn benchmark_messages(data: BenchmarkMessagesParams) -> anyhow::Result<()> {
+		let source_client = data.source.into_client::<Self::Source>().await?;
+		let source_sign = data.source_sign.to_keypair::<Self::Source>().await?;
+		let source_transactions_mortality = data.source_sign.transactions_mortality()?;
+		let target_client = data.target.into_client::<Self::Target>().await?;
+		let target_sign = data.target_sign.to_keypair::<Self::Target>().await?;
+		let target_transactions_mortality = data.target_sign.transactions_mortality()?;
+		let lane = data.lane.into();
+		let source_transaction_params = TransactionParams {
+			signer: source_sign,
+			mortality: source_transactions_mortality,
+			priority: None,
+		};
+
+		let relay = crate::messages_lane::run::<Self::MessagesLane, _, _>(MessagesRelayParams {
+			source_client: source_client.clone(),
+			source_transaction_params: source_transaction_params.clone(),
+			source_additional_signers: Vec::new(),
+			source_proxy: None,
+			target_client: target_client.clone(),
+			target_transaction_params: TransactionParams {
+				signer: target_sign,
+				mortality: target_transactions_mortality,
+				priority: None,
+			},
+			target_proxy: None,
+			source_to_target_headers_relay: None,
+			target_to_source_headers_relay: None,
+			lane_id: lane,
+			limits: Self::maybe_messages_limits(),
+			metrics_params: MetricsParams::disabled(),
+			control: Default::default(),
+			sole_relayer: None,
+			max_fees_per_hour: None,
+			fee_safety_margin_percent: 0,
+			fee_estimation_fallback: FeeEstimationFallback::Skip,
+			pallet_version_mismatch: PalletVersionMismatchAction::Warn,
+			backfill: None,
+			max_nonces_ahead: None,
+			log_backpressure: false,
+			target_sync_confirmations: 0,
+			reorg_alert_depth: None,
+			competition_analytics_window: None,
+			xcm_instruction_filter: None,
+			warm_up_blocks: 0,
+			first_submit_delay: Duration::ZERO,
+			inactivity_log_threshold: None,
+			correlate_messages: false,
+			oversized_weight_policy: OversizedWeightPolicy::WarnOnly,
+			max_message_dispatch_weight: None,
+			companion_lane: None,
+			target_balance_guard: None,
+			source_confirmation_guard: None,
+			source_reward_eligibility_guard: None,
+			source_rate_limit_quota_guard: None,
+			source_confirmations_batcher: None,
+			target_fee_multiplier_guard: None,
+			target_runtime_upgrade_guard: None,
+			target_synced_header_age_guard: None,
+			target_header_progress_guard: None,
+			target_block_weight_headroom: None,
+			target_rate_limit_quota_guard: None,
+			messages_proof_format: StorageProofFormat::Legacy,
+			delivery_proof_format: StorageProofFormat::Legacy,
+			confirmation_lag: None,
+			max_confirmation_latency: None,
+			source_message_confirmations: 0,
+			deterministic: false,
+			throughput_budget: Default::default(),
+			global_pause_guard: None,
+			min_batch: None,
+			pending_messages_gate: None,
+			serialize_races: false,
+			log_level: None,
+			lane_error_state_metric: false,
+			track_dispatch_refunds: false,
+			paused_lane_poll_interval: None,
+			max_proof_generation_time: None,
+			message_discovery: MessageDiscovery::Storage,
+			message_grace_period_blocks: 0,
+			confirmations_priority_threshold: None,
+			retry_budget: None,
+			batch_tuner: None,
+			max_runtime: None,
+			scale_event_sink: None,
+		});
+		let benchmark = run_benchmark::<Self::MessagesLane>(
+			source_client,
+			target_client,
+			source_transaction_params,
+			lane,
+			data.messages,
+			data.concurrency,
+			move || Self::send_message_call(lane, vec![0u8; data.payload_size]),
+		);
+
+		futures::pin_mut!(relay, benchmark);
+		match futures::future::select(relay, benchmark).await {
+			Either::Left((relay_result, _)) => relay_result
+				.and_then(|_| Err(anyhow::format_err!("Messages relay has stopped unexpectedly"))),
+			Either::Right((summary, _)) => {
+				println!("{}", summary?);
+				Ok(())
+			},
+		}
+	}
+}
+
+/// Send messages and wait until all of them are delivered.
+async fn run_benchmark<P: SubstrateMessageLane>(
+	source_client: impl Client<P::SourceChain>,
+	target_client: impl Client<P::TargetChain>,
+	source_transaction_params: TransactionParams<AccountKeyPairOf<P::SourceChain>>,
+	lane: LaneId,
+	messages: MessageNonce,
+	concurrency: usize,
+	send_message_call: impl Fn() -> anyhow::Result<CallOf<P::SourceChain>>,
+) -> anyhow::Result<BenchmarkSummary> {
+	let start_nonce = latest_generated_nonce::<P>(&source_client, lane).await?;
+	let mut stats = BenchmarkStats::new(Instant::now(), start_nonce);
+	log::info!(
+		target: "bridge",
+		"Sending {} messages starting from nonce {} over {} -> {} lane {:?}",
+		messages,
+		start_nonce + 1,
+		P::SourceChain::NAME,
+		P::TargetChain::NAME,
+		lane,
+	);
+
+	let send_messages = futures::stream::iter(0..messages)
+		.map(|_| {
+			let source_client = source_client.clone();
+			let transaction_params = source_transaction_params.clone();
+			let call = send_message_call();
+			async move {
+				let call = call?;
+				source_client
+					.submit_signed_extrinsic(
+						&transaction_params.signer,
+						move |best_block_id, transaction_nonce| {
+							Ok(UnsignedTransaction::new(call.into(), transaction_nonce).era(
+								TransactionEra::new(best_block_id, transaction_params.mortality),
+							))
+						},
+					)
+					.await
+					.map_err(|e| anyhow::format_err!("Failed to send message: {e:?}"))
+			}
+		})
+		.buffer_unordered(concurrency.max(1))
+		.try_for_each(|_| futures::future::ready(Ok(())));
+
+	let poll_interval = std::cmp::min(
+		P::SourceChain::AVERAGE_BLOCK_INTERVAL,
+		P::TargetChain::AVERAGE_BLOCK_INTERVAL,
+	);
+	let end_nonce = start_nonce + messages;
+	let track_messages = async {
+		while stats.latest_delivered_nonce < end_nonce {
+			async_std::task::sleep(poll_interval).await;
+
+			let latest_generated_nonce = latest_generated_nonce::<P>(&source_client, lane).await?;
+			let latest_delivered_nonce = latest_delivered_nonce::<P>(&target_client, lane).await?;
+			let now = Instant::now();
+			stats.on_generated(latest_generated_nonce, now);
+			stats.on_delivered(std::cmp::min(latest_delivered_nonce, end_nonce), now);
+		}
+		Ok::<_, anyhow::Error>(())
+	};
+
+	futures::try_join!(send_messages, track_messages)?;
+	Ok(stats.summary())
+}
+
+/// Read latest generated nonce of the outbound lane at the best source chain block.
+async fn latest_generated_nonce<P: SubstrateMessageLane>(
+	source_client: &impl Client<P::SourceChain>,
+	lane: LaneId,
+) -> anyhow::Result<MessageNonce> {
+	let best_hash = source_client.best_header_hash().await?;
+	let lane_data: Option<OutboundLaneData> = source_client
+		.storage_value(
+			best_hash,
+			outbound_lane_data_key(P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME, &lane),
+		)
+		.await?;
+	Ok(lane_data.map(|data| data.latest_generated_nonce).unwrap_or(0))
+}
+
+/// Read latest delivered nonce of the inbound lane at the best target chain block.
+async fn latest_delivered_nonce<P: SubstrateMessageLane>(
+	target_client: &impl Client<P::TargetChain>,
+	lane: LaneId,
+) -> anyhow::Result<MessageNonce> {
+	let best_hash = target_client.best_header_hash().await?;
+	let lane_data: Option<InboundLaneData<AccountIdOf<P::SourceChain>>> = target_client
+		.storage_value(
+			best_hash,
+			inbound_lane_data_key(P::SourceChain::WITH_CHAIN_MESSAGES_PALLET_NAME, &lane),
+		)
+		.await?;
+	Ok(lane_data.map(|data| data.last_delivered_nonce()).unwrap_or(0))
+}
+
+/// Benchmark statistics.
+struct BenchmarkStats {
+	/// When the benchmark has been started.
+	started_at: Instant,
+	/// When the last message has been delivered.
+	finished_at: Instant,
+	/// Latest generated nonce that we have seen at the source chain.
+	latest_generated_nonce: MessageNonce,
+	/// Latest delivered nonce that we have seen at the target chain.
+	latest_delivered_nonce: MessageNonce,
+	/// Moments when generated and not yet delivered messages have been seen at the source chain.
+	generated_at: BTreeMap<MessageNonce, Instant>,
+	/// End-to-end latencies of delivered messages.
+	latencies: Vec<Duration>,
+}
+
+impl BenchmarkStats {
+	fn new(started_at: Instant, start_nonce: MessageNonce) -> Self {
+		BenchmarkStats {
+			started_at,
+			finished_at: started_at,
+			latest_generated_nonce: start_nonce,
+			latest_delivered_nonce: start_nonce,
+			generated_at: BTreeMap::new(),
+			latencies: Vec::new(),
+		}
+	}
+
+	fn on_generated(&mut self, latest_generated_nonce: MessageNonce, now: Instant) {
+		for nonce in self.latest_generated_nonce + 1..=latest_generated_nonce {
+			self.generated_at.insert(nonce, now);
+		}
+		self.latest_generated_nonce =
+			std::cmp::max(self.latest_generated_nonce, latest_generated_nonce);
+	}
+
+	fn on_delivered(&mut self, latest_delivered_nonce: MessageNonce, now: Instant) {
+		for nonce in self.latest_delivered_nonce + 1..=latest_delivered_nonce {
+			let generated_at = self.generated_at.remove(&nonce).unwrap_or(now);
+			self.latencies.push(now.saturating_duration_since(generated_at));
+			self.finished_at = now;
+		}
+		self.latest_delivered_nonce =
+			std::cmp::max(self.latest_delivered_nonce, latest_delivered_nonce);
+	}
+
+	fn summary(&self) -> BenchmarkSummary {
+		BenchmarkSummary {
+			messages: self.latencies.len(),
+			duration: self.finished_at.saturating_duration_since(self.started_at),
+			min_latency: self.latencies.iter().min().cloned().unwrap_or_default(),
+			avg_latency: self
+				.latencies
+				.iter()
+				.sum::<Duration>()
+				.checked_div(self.latencies.len() as u32)
+				.unwrap_or_default(),
+			max_latency: self.latencies.iter().max().cloned().unwrap_or_default(),
+		}
+	}
+}
+
+/// Benchmark results.
+#[derive(Debug, PartialEq)]
+struct BenchmarkSummary {
+	/// Number of delivered messages.
+	messages: usize,
+	/// Time spent on delivering all messages.
+	duration: Duration,
+	/// Minimal end-to-end message latency.
+	min_latency: Duration,
+	/// Average end-to-end message latency.
+	avg_latency: Duration,
+	/// Maximal end-to-end message latency.
+	max_latency: Duration,
+}
+
+impl std::fmt::Display for BenchmarkSummary {
+	fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+		let throughput = match self.duration.as_secs_f64() {
+			secs if secs > 0.0 => self.messages as f64 / secs,
+			_ => 0.0,
+		};
+		write!(
+			fmt,
+			"Delivered {} messages in {:?} ({:.2} messages/s). End-to-end latency: min {:?}, \
+			avg {:?}, max {:?}",
+			self.messages,
+			self.duration,
+			throughput,
+			self.min_latency,
+			self.avg_latency,
+			self.max_latency,
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn benchmark_stats_are_computed() {
+		let started_at = Instant::now();
+		let at = |secs| started_at + Duration::from_secs(secs);
+		let mut stats = BenchmarkStats::new(started_at, 10);
+
+		stats.on_generated(12, at(1));
+		stats.on_generated(14, at(2));
+		stats.on_delivered(11, at(4));
+		stats.on_generated(14, at(5));
+		stats.on_delivered(14, at(6));
+
+		assert_eq!(
+			stats.summary(),
+			BenchmarkSummary {
+				messages: 4,
+				duration: Duration::from_secs(6),
+				min_latency: Duration::from_secs(3),
+				avg_latency: Duration::from_secs(4),
+				max_latency: Duration::from_secs(5),
+			},
+		);
+	}
+}
@@ -17,14 +17,18 @@
 //! Basic traits for exposing bridges in the CLI.
 
 use crate::{
+	cli::{chain_schema::ProxyCliParams, DefaultClient},
 	equivocation::SubstrateEquivocationDetectionPipeline,
 	finality::SubstrateFinalitySyncPipeline,
+	messages_header_progress::StartHeadersRelay,
 	messages_lane::{MessagesRelayLimits, SubstrateMessageLane},
 	parachains::SubstrateParachainsPipeline,
+	ProxyParams, TransactionParams,
 };
 use pallet_bridge_parachains::{RelayBlockHash, RelayBlockHasher, RelayBlockNumber};
 use relay_substrate_client::{
-	Chain, ChainWithRuntimeVersion, ChainWithTransactions, Parachain, RelayChain,
+	AccountKeyPairOf, BlockNumberOf, CallOf, Chain, ChainWithRuntimeVersion, ChainWithTransactions,
+	Parachain, RelayChain,
 };
 
 /// Minimal bridge representation that can be used from the CLI.
@@ -107,4 +111,36 @@ pub trait MessagesCliBridge: CliBridgeBase {
 	fn maybe_messages_limits() -> Option<MessagesRelayLimits> {
 		None
 	}
+
+	/// Proxy that is used to dispatch delivery confirmation calls at the source chain.
+	/// Bridges with source chain that implements `ChainWithProxyPallet` should override
+	/// it with `params.into_proxy_params()`.
+	fn source_proxy(params: ProxyCliParams) -> anyhow::Result<Option<ProxyParams<Self::Source>>> {
+		params.ensure_unsupported()
+	}
+
+	/// Proxy that is used to dispatch delivery calls at the target chain.
+	fn target_proxy(params: ProxyCliParams) -> anyhow::Result<Option<ProxyParams<Self::Target>>> {
+		params.ensure_unsupported()
+	}
+
+	/// Call that registers the relayer at the bridge relayers pallet of the target chain until
+	/// given block. Bridges with target chain that has the bridge relayers pallet should
+	/// override it to support `--auto-register`.
+	fn encode_register_relayer(
+		_valid_till: BlockNumberOf<Self::Target>,
+	) -> Option<CallOf<Self::Target>> {
+		None
+	}
+
+	/// Function that starts the on-demand relay of source headers to the target chain. Bridges
+	/// should override it with the `OnDemandHeadersRelay` (or `OnDemandParachainsRelay`) of
+	/// their headers pipeline to support `--no-header-relay-policy auto-relay`.
+	fn start_source_to_target_headers_relay(
+		_source_client: DefaultClient<Self::Source>,
+		_target_client: DefaultClient<Self::Target>,
+		_target_transaction_params: TransactionParams<AccountKeyPairOf<Self::Target>>,
+	) -> Option<StartHeadersRelay<Self::Source, Self::Target>> {
+		None
+	}
 }
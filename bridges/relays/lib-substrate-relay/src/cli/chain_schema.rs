@@ -16,13 +16,16 @@
 
 //! Primitives related to chain CLI options.
 
-use relay_substrate_client::{AccountKeyPairOf, ChainWithTransactions};
+use codec::Decode;
+use relay_substrate_client::{
+	AccountIdOf, AccountKeyPairOf, Chain, ChainWithProxyPallet, ChainWithTransactions,
+};
 use structopt::StructOpt;
 use strum::{EnumString, VariantNames};
 
 use relay_substrate_client::{ChainRuntimeVersion, ChainWithRuntimeVersion, SimpleRuntimeVersion};
 
-use crate::TransactionParams;
+use crate::{ProxyParams, TransactionParams};
 
 #[doc = "Runtime version params."]
 #[derive(StructOpt, Debug, PartialEq, Eq, Clone, Copy, EnumString, VariantNames)]
@@ -98,7 +101,7 @@ macro_rules! declare_chain_connection_params_cli_schema {
 			#[doc = $chain " connection params."]
 			#[derive(StructOpt, Debug, PartialEq, Eq, Clone)]
 			pub struct [<$chain ConnectionParams>] {
-				#[doc = "WS endpoint of " $chain ": full URI. Overrides all other connection string components (host, port, path, secure)."]
+				#[doc = "WS endpoint of " $chain ": full URI. Overrides all other connection string components (host, port, path, secure). May be a secret reference (`file://`, `env://` or `vault://`)."]
 				#[structopt(long)]
 				pub [<$chain_prefix _uri>]: Option<String>,
 				#[doc = "WS endpoint of " $chain ": host component."]
@@ -116,6 +119,42 @@ macro_rules! declare_chain_connection_params_cli_schema {
 				#[doc = "Custom runtime version"]
 				#[structopt(flatten)]
 				pub [<$chain_prefix _runtime_version>]: [<$chain RuntimeVersionParams>],
+				#[doc = "WS endpoint of " $chain " read replica: full URI. If specified, storage and header reads are sent to this node, falling back to the primary node if the replica fails. May be a secret reference (`file://`, `env://` or `vault://`)."]
+				#[structopt(long)]
+				pub [<$chain_prefix _read_endpoint>]: Option<String>,
+				#[doc = "WS endpoint of " $chain " primary node: full URI. Transactions are always submitted to this node. Overrides all other connection string components (URI, host, port, path, secure). May be a secret reference (`file://`, `env://` or `vault://`)."]
+				#[structopt(long)]
+				pub [<$chain_prefix _write_endpoint>]: Option<String>,
+				#[doc = "Maximal number of transactions, submitted to " $chain " node, that are not yet finalized or lost. New transactions are deferred until older transactions are finalized or lost. There's no limit by default."]
+				#[structopt(long)]
+				pub [<$chain_prefix _max_in_flight>]: Option<usize>,
+				#[doc = "If some transaction, submitted to " $chain " node, is dropped, later transactions of the same signer are stuck in the pool. If set, the relay fills such nonce gaps with no-op `system.remark` transactions, submitting at most given number of them. Gaps are not filled by default."]
+				#[structopt(long)]
+				pub [<$chain_prefix _fill_nonce_gaps>]: Option<u32>,
+				#[doc = "State of " $chain " node, used to read nonces of transaction signers: `best` (with the transaction pool) or `finalized`. The best nonce may be reused if the best block is reorganized. The finalized nonce is never reused, but the next transaction of the same signer is only submitted correctly after the previous one is finalized."]
+				#[structopt(long, default_value = "best")]
+				pub [<$chain_prefix _nonce_source>]: relay_substrate_client::NonceSource,
+				#[doc = "What to do when the transaction version of " $chain " runtime is changed while the relay is running, if transactions are signed using the custom or bundled runtime version: `keep` using the configured version (transactions are rejected by the upgraded runtime), `refresh` the version that is used to sign transactions or `abort` the relay. With `refresh`, the relay polls the runtime version and isn't aborted when the spec version is changed."]
+				#[structopt(long, default_value = "keep")]
+				pub [<$chain_prefix _transaction_version_bump>]: relay_substrate_client::TransactionVersionBump,
+				#[doc = "WS endpoint of " $chain " block authors, accepting transactions directly, bypassing the transaction pool: full URI. If specified, transactions are submitted to this endpoint, falling back to the node if the endpoint is unavailable or rejects the transaction. May be a secret reference (`file://`, `env://` or `vault://`)."]
+				#[structopt(long)]
+				pub [<$chain_prefix _direct_inclusion_endpoint>]: Option<String>,
+				#[doc = "Call a lightweight RPC method of " $chain " node every given number of seconds, so that idle connections aren't dropped and dead connections are detected faster. There's no keepalive by default."]
+				#[structopt(long)]
+				pub [<$chain_prefix _rpc_keepalive_secs>]: Option<u64>,
+				#[doc = "Log every raw JSON-RPC request to and response from " $chain " node, truncating large payloads. The traffic is logged with the `trace` level under the `bridge-rpc` target, so it also needs `RUST_LOG=bridge-rpc=trace`. It is very verbose and meant for debugging only."]
+				#[structopt(long)]
+				pub [<$chain_prefix _trace_rpc>]: bool,
+				#[doc = "Don't log signed transactions, submitted to " $chain " node, when tracing RPC traffic. Only used with " $chain_prefix "_trace_rpc option."]
+				#[structopt(long)]
+				pub [<$chain_prefix _trace_rpc_redact_transactions>]: bool,
+				#[doc = "WS endpoint of other " $chain " node: full URI. May be specified multiple times. When the connection to the node fails, the relay switches to the next endpoint. Endpoints of nodes that serve other chain (e.g. other fork) or incompatible runtime are marked bad and are never used again. May be a secret reference (`file://`, `env://` or `vault://`)."]
+				#[structopt(long)]
+				pub [<$chain_prefix _fallback_endpoint>]: Vec<String>,
+				#[doc = "Read the runtime version of " $chain " node before signing every transaction. By default, the version is only read again after the runtime upgrade. Has no effect if the runtime version is configured."]
+				#[structopt(long)]
+				pub [<$chain_prefix _disable_signing_cache>]: bool,
 			}
 
 			impl [<$chain ConnectionParams>] {
@@ -127,14 +166,65 @@ macro_rules! declare_chain_connection_params_cli_schema {
 					let chain_runtime_version = self
 						.[<$chain_prefix _runtime_version>]
 						.into_runtime_version(Chain::RUNTIME_VERSION)?;
-					Ok(relay_substrate_client::new(relay_substrate_client::ConnectionParams {
-						uri: self.[<$chain_prefix _uri>],
-						host: self.[<$chain_prefix _host>],
-						port: self.[<$chain_prefix _port>],
-						path: self.[<$chain_prefix _path>],
-						secure: self.[<$chain_prefix _secure>],
+					let resolve_uri = |uri: Option<String>| async move {
+						match uri {
+							Some(uri) => relay_utils::secrets::resolve_secret(&uri, &["ws", "wss"])
+								.await
+								.map(Some),
+							None => Ok(None),
+						}
+					};
+					let uri = resolve_uri(
+						self.[<$chain_prefix _write_endpoint>].or(self.[<$chain_prefix _uri>]),
+					)
+					.await?;
+					let replica_uri = resolve_uri(self.[<$chain_prefix _read_endpoint>]).await?;
+					let direct_inclusion_uri =
+						resolve_uri(self.[<$chain_prefix _direct_inclusion_endpoint>]).await?;
+					let keepalive_interval = self
+						.[<$chain_prefix _rpc_keepalive_secs>]
+						.map(std::time::Duration::from_secs);
+					let transaction_version_bump = self.[<$chain_prefix _transaction_version_bump>];
+					let mut fallback_uris =
+						Vec::with_capacity(self.[<$chain_prefix _fallback_endpoint>].len());
+					for uri in &self.[<$chain_prefix _fallback_endpoint>] {
+						fallback_uris
+							.push(relay_utils::secrets::resolve_secret(uri, &["ws", "wss"]).await?);
+					}
+					let rpc_trace = self.[<$chain_prefix _trace_rpc>].then(|| {
+						relay_substrate_client::RpcTraceParams {
+							redact_transactions: self.[<$chain_prefix _trace_rpc_redact_transactions>],
+							..Default::default()
+						}
+					});
+					let replica = replica_uri.map(|uri| relay_substrate_client::ConnectionParams {
+						uri: Some(uri),
 						chain_runtime_version,
-					})
+						keepalive_interval,
+						rpc_trace,
+						transaction_version_bump,
+						..Default::default()
+					});
+					Ok(relay_substrate_client::new_split(
+						relay_substrate_client::ConnectionParams {
+							uri,
+							host: self.[<$chain_prefix _host>],
+							port: self.[<$chain_prefix _port>],
+							path: self.[<$chain_prefix _path>],
+							secure: self.[<$chain_prefix _secure>],
+							chain_runtime_version,
+							max_in_flight_transactions: self.[<$chain_prefix _max_in_flight>],
+							max_nonce_gap_fillers: self.[<$chain_prefix _fill_nonce_gaps>],
+							nonce_source: self.[<$chain_prefix _nonce_source>],
+							direct_inclusion_uri,
+							keepalive_interval,
+							rpc_trace,
+							transaction_version_bump,
+							fallback_uris,
+							signing_cache: !self.[<$chain_prefix _disable_signing_cache>],
+						},
+						replica,
+					)
 					.await
 					)
 				}
@@ -143,18 +233,64 @@ macro_rules! declare_chain_connection_params_cli_schema {
 	};
 }
 
+/// Proxy that is used to dispatch relay calls on behalf of other account.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct ProxyCliParams {
+	/// Hex-encoded id of the proxied account.
+	pub real: Option<String>,
+	/// Type of the proxy.
+	pub proxy_type: Option<String>,
+}
+
+impl ProxyCliParams {
+	/// Parse proxy parameters for the chain with proxy pallet.
+	pub fn into_proxy_params<C: ChainWithProxyPallet>(
+		self,
+	) -> anyhow::Result<Option<ProxyParams<C>>>
+	where
+		C::ProxyType: Send + Sync + 'static,
+	{
+		let (real, proxy_type) = match (self.real, self.proxy_type) {
+			(Some(real), Some(proxy_type)) => (real, proxy_type),
+			(None, None) => return Ok(None),
+			_ =>
+				return Err(anyhow::format_err!(
+					"Both proxied account and proxy type must be specified for {} proxy",
+					C::NAME,
+				)),
+		};
+
+		let real = hex::decode(real.trim_start_matches("0x"))?;
+		let real = AccountIdOf::<C>::decode(&mut &real[..])
+			.map_err(|e| anyhow::format_err!("Failed to decode {} account id: {:?}", C::NAME, e))?;
+		let proxy_type = proxy_type
+			.parse::<C::ProxyType>()
+			.map_err(|_| anyhow::format_err!("Unknown {} proxy type: {}", C::NAME, proxy_type))?;
+		Ok(Some(ProxyParams::new(real, proxy_type)))
+	}
+
+	/// Ensure that proxy is not specified for the chain without proxy pallet.
+	pub fn ensure_unsupported<C: Chain>(&self) -> anyhow::Result<Option<ProxyParams<C>>> {
+		if self.real.is_some() || self.proxy_type.is_some() {
+			return Err(anyhow::format_err!("{} relay doesn't support proxies", C::NAME))
+		}
+
+		Ok(None)
+	}
+}
+
 /// Create chain-specific set of signing parameters.
 #[macro_export]
 macro_rules! declare_chain_signing_params_cli_schema {
 	($chain:ident, $chain_prefix:ident) => {
 		bp_runtime::paste::item! {
 			#[doc = $chain " signing params."]
-			#[derive(StructOpt, Debug, PartialEq, Eq, Clone)]
+			#[derive(StructOpt, PartialEq, Eq, Clone)]
 			pub struct [<$chain SigningParams>] {
-				#[doc = "The SURI of secret key to use when transactions are submitted to the " $chain " node."]
+				#[doc = "The SURI of secret key to use when transactions are submitted to the " $chain " node. May be a secret reference (`file://`, `env://` or `vault://`). May be repeated - then messages relay signs consecutive transactions with given keys in turns."]
 				#[structopt(long)]
-				pub [<$chain_prefix _signer>]: Option<String>,
-				#[doc = "The password for the SURI of secret key to use when transactions are submitted to the " $chain " node."]
+				pub [<$chain_prefix _signer>]: Vec<String>,
+				#[doc = "The password for the SURI of secret key to use when transactions are submitted to the " $chain " node. May be a secret reference (`file://`, `env://` or `vault://`)."]
 				#[structopt(long)]
 				pub [<$chain_prefix _signer_password>]: Option<String>,
 
@@ -168,6 +304,41 @@ macro_rules! declare_chain_signing_params_cli_schema {
 				#[doc = "Transactions mortality period, in blocks. MUST be a power of two in [4; 65536] range. MAY NOT be larger than `BlockHashCount` parameter of the chain system module."]
 				#[structopt(long)]
 				pub [<$chain_prefix _transactions_mortality>]: Option<u32>,
+
+				#[doc = "Hex-encoded id of the " $chain " account, on behalf of which relay calls are dispatched. The signer must be registered as a proxy of this account. Requires " $chain_prefix "_proxy_type option."]
+				#[structopt(long)]
+				pub [<$chain_prefix _proxy_real>]: Option<String>,
+				#[doc = "Type of the proxy that the signer has been registered with at the " $chain " chain."]
+				#[structopt(long)]
+				pub [<$chain_prefix _proxy_type>]: Option<String>,
+			}
+
+			impl std::fmt::Debug for [<$chain SigningParams>] {
+				fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+					// literal SURIs and passwords must never be printed
+					let redact = |value: &String| relay_utils::secrets::redact_secret(value);
+					f.debug_struct(stringify!([<$chain SigningParams>]))
+						.field(
+							stringify!([<$chain_prefix _signer>]),
+							&self.[<$chain_prefix _signer>].iter().map(redact).collect::<Vec<_>>(),
+						)
+						.field(
+							stringify!([<$chain_prefix _signer_password>]),
+							&self.[<$chain_prefix _signer_password>].as_ref().map(redact),
+						)
+						.field(stringify!([<$chain_prefix _signer_file>]), &self.[<$chain_prefix _signer_file>])
+						.field(
+							stringify!([<$chain_prefix _signer_password_file>]),
+							&self.[<$chain_prefix _signer_password_file>],
+						)
+						.field(
+							stringify!([<$chain_prefix _transactions_mortality>]),
+							&self.[<$chain_prefix _transactions_mortality>],
+						)
+						.field(stringify!([<$chain_prefix _proxy_real>]), &self.[<$chain_prefix _proxy_real>])
+						.field(stringify!([<$chain_prefix _proxy_type>]), &self.[<$chain_prefix _proxy_type>])
+						.finish()
+				}
 			}
 
 			impl [<$chain SigningParams>] {
@@ -192,9 +363,9 @@ macro_rules! declare_chain_signing_params_cli_schema {
 
 				/// Parse signing params into chain-specific KeyPair.
 				#[allow(dead_code)]
-				pub fn to_keypair<Chain: ChainWithTransactions>(&self) -> anyhow::Result<AccountKeyPairOf<Chain>> {
-					let suri = match (self.[<$chain_prefix _signer>].as_ref(), self.[<$chain_prefix _signer_file>].as_ref()) {
-						(Some(suri), _) => suri.to_owned(),
+				pub async fn to_keypair<Chain: ChainWithTransactions>(&self) -> anyhow::Result<AccountKeyPairOf<Chain>> {
+					let suri = match (self.[<$chain_prefix _signer>].first(), self.[<$chain_prefix _signer_file>].as_ref()) {
+						(Some(suri), _) => relay_utils::secrets::resolve_secret(suri, &[]).await?,
 						(None, Some(suri_file)) => std::fs::read_to_string(suri_file)
 							.map_err(|err| anyhow::format_err!(
 								"Failed to read SURI from file {:?}: {}",
@@ -208,11 +379,28 @@ macro_rules! declare_chain_signing_params_cli_schema {
 						)),
 					};
 
+					self.keypair_from_suri::<Chain>(&suri).await
+				}
+
+				/// Parse all signers, except the first one, into chain-specific KeyPairs.
+				#[allow(dead_code)]
+				pub async fn additional_keypairs<Chain: ChainWithTransactions>(&self) -> anyhow::Result<Vec<AccountKeyPairOf<Chain>>> {
+					let mut keypairs = Vec::new();
+					for suri in self.[<$chain_prefix _signer>].iter().skip(1) {
+						let suri = relay_utils::secrets::resolve_secret(suri, &[]).await?;
+						keypairs.push(self.keypair_from_suri::<Chain>(&suri).await?);
+					}
+					Ok(keypairs)
+				}
+
+				/// Parse given SURI into chain-specific KeyPair, using the signer password.
+				#[allow(dead_code)]
+				async fn keypair_from_suri<Chain: ChainWithTransactions>(&self, suri: &str) -> anyhow::Result<AccountKeyPairOf<Chain>> {
 					let suri_password = match (
 						self.[<$chain_prefix _signer_password>].as_ref(),
 						self.[<$chain_prefix _signer_password_file>].as_ref(),
 					) {
-						(Some(suri_password), _) => Some(suri_password.to_owned()),
+						(Some(suri_password), _) => Some(relay_utils::secrets::resolve_secret(suri_password, &[]).await?),
 						(None, Some(suri_password_file)) => std::fs::read_to_string(suri_password_file)
 							.map(Some)
 							.map_err(|err| anyhow::format_err!(
@@ -226,19 +414,29 @@ macro_rules! declare_chain_signing_params_cli_schema {
 					use sp_core::crypto::Pair;
 
 					AccountKeyPairOf::<Chain>::from_string(
-						&suri,
+						suri,
 						suri_password.as_deref()
 					).map_err(|e| anyhow::format_err!("{:?}", e))
 				}
 
+				/// Return proxy parameters.
+				#[allow(dead_code)]
+				pub fn proxy(&self) -> $crate::cli::chain_schema::ProxyCliParams {
+					$crate::cli::chain_schema::ProxyCliParams {
+						real: self.[<$chain_prefix _proxy_real>].clone(),
+						proxy_type: self.[<$chain_prefix _proxy_type>].clone(),
+					}
+				}
+
 				/// Return transaction parameters.
 				#[allow(dead_code)]
-				pub fn transaction_params<Chain: ChainWithTransactions>(
+				pub async fn transaction_params<Chain: ChainWithTransactions>(
 					&self,
 				) -> anyhow::Result<TransactionParams<AccountKeyPairOf<Chain>>> {
 					Ok(TransactionParams {
 						mortality: self.transactions_mortality()?,
-						signer: self.to_keypair::<Chain>()?,
+						signer: self.to_keypair::<Chain>().await?,
+						priority: None,
 					})
 				}
 			}
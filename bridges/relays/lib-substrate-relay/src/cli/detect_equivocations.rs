@@ -57,7 +57,7 @@ where
 		equivocation::run::<Self::Equivocation>(
 			source_client,
 			data.target.into_client::<Self::Target>().await?,
-			data.source_sign.transaction_params::<Self::Source>()?,
+			data.source_sign.transaction_params::<Self::Source>().await?,
 			data.prometheus_params.into_metrics_params()?,
 		)
 		.await
@@ -60,7 +60,7 @@ where
 	async fn init_bridge(data: InitBridgeParams) -> anyhow::Result<()> {
 		let source_client = data.source.into_client::<Self::Source>().await?;
 		let target_client = data.target.into_client::<Self::Target>().await?;
-		let target_sign = data.target_sign.to_keypair::<Self::Target>()?;
+		let target_sign = data.target_sign.to_keypair::<Self::Target>().await?;
 		let dry_run = data.dry_run;
 
 		crate::finality::initialize::initialize::<Self::Engine, _, _, _>(
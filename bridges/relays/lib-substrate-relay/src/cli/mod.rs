@@ -21,8 +21,16 @@ use rbtag::BuildInfo;
 use structopt::StructOpt;
 use strum::{EnumString, VariantNames};
 
-use bp_messages::LaneId;
-
+use crate::{
+	messages_sole_relayer::{SoleRelayerAction, SoleRelayerParams},
+	scale_events::ScaleEventSink,
+};
+use bp_messages::{LaneId, MessageNonce};
+use relay_utils::stats_history::{spawn_history_tracker, StatsHistory};
+use std::{path::PathBuf, time::Duration};
+
+#[cfg(feature = "benchmark")]
+pub mod benchmark_messages;
 pub mod bridge;
 pub mod chain_schema;
 pub mod detect_equivocations;
@@ -35,10 +43,15 @@ pub mod relay_parachains;
 /// The target that will be used when publishing logs related to this pallet.
 pub const LOG_TARGET: &str = "bridge";
 
+/// Interval of checking whether the relay is ready to create the `--ready-file`.
+const READY_FILE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Name of the file in the `--state-dir`, where the activity history is persisted.
+const STATS_HISTORY_FILE_NAME: &str = "stats-history.json";
+
 /// Default Substrate client type that we are using. We'll use it all over the glue CLI code
 /// to avoid multiple level generic arguments and constraints. We still allow usage of other
 /// clients in the **core logic code**.
-pub type DefaultClient<C> = relay_substrate_client::RpcWithCachingClient<C>;
+pub type DefaultClient<C> = relay_substrate_client::RpcWithCachingSplitClient<C>;
 
 /// Lane id.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -60,6 +73,162 @@ impl std::str::FromStr for HexLaneId {
 	}
 }
 
+/// Lane id with optional designated signer, in the `<hex-lane-id>[@<signer-index>]` form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaneConfig {
+	/// Lane id.
+	pub lane: HexLaneId,
+	/// Index of the signer that signs all transactions of this lane. The main signer has index
+	/// `0` and additional signers are numbered from `1`. If `None`, the lane is served by all
+	/// signers that aren't designated to other lanes.
+	pub signer: Option<usize>,
+}
+
+impl std::str::FromStr for LaneConfig {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (s, signer) = match s.rsplit_once('@') {
+			Some((s, signer)) => (
+				s,
+				Some(
+					signer
+						.parse()
+						.map_err(|e| format!("Failed to parse signer index '{signer}': {e:?}"))?,
+				),
+			),
+			None => (s, None),
+		};
+		let lane = s
+			.parse::<HexLaneId>()
+			.map_err(|e| format!("Failed to parse lane id '{s}': {e:?}"))?;
+		Ok(LaneConfig { lane, signer })
+	}
+}
+
+/// Log level override of the lane in the `<hex-lane-id>=<level>` form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaneLogConfig {
+	/// Lane id.
+	pub lane: HexLaneId,
+	/// Level of relay logs of this lane.
+	pub level: log::LevelFilter,
+}
+
+impl std::str::FromStr for LaneLogConfig {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (lane, level) = s
+			.split_once('=')
+			.ok_or_else(|| format!("Invalid lane log level '{s}'. Expected '<lane>=<level>'"))?;
+		let lane = lane
+			.parse::<HexLaneId>()
+			.map_err(|e| format!("Failed to parse lane id '{lane}': {e:?}"))?;
+		let level = level.parse::<log::LevelFilter>().map_err(|_| {
+			format!(
+				"Invalid log level '{level}'. Expected one of 'off', 'error', 'warn', 'info', \
+				'debug' or 'trace'"
+			)
+		})?;
+		Ok(LaneLogConfig { lane, level })
+	}
+}
+
+/// Parse percent in the `1..=100` range.
+pub fn parse_percent(s: &str) -> Result<u8, String> {
+	match s.parse::<u8>() {
+		Ok(percent) if (1..=100).contains(&percent) => Ok(percent),
+		_ => Err(format!("Invalid percent '{s}'. Expected number in 1..=100 range")),
+	}
+}
+
+/// How confident the relayer must be in the source chain header, before it delivers messages,
+/// sent at this header, in the `best|<n>-deep|finalized` form.
+///
+/// Message proofs are verified against source headers that are finalized and synced to the
+/// target chain, so messages are never delivered from non-finalized source headers:
+///
+/// - `best` and `finalized` deliver messages from finalized source headers;
+///
+/// - `<n>-deep` delivers messages from finalized source headers that have at least `n` descendants.
+///   It protects from reorgs of chains with unsafe finality.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceConfidence {
+	/// Best source header.
+	Best,
+	/// Source header with at least given number of descendants.
+	Deep(u32),
+	/// Finalized source header.
+	Finalized,
+}
+
+impl SourceConfidence {
+	/// Returns number of source blocks that must be built on top of the block, where message
+	/// has been sent, before the relay delivers it.
+	pub fn source_message_confirmations(&self) -> u32 {
+		match *self {
+			SourceConfidence::Best | SourceConfidence::Finalized => 0,
+			SourceConfidence::Deep(depth) => depth,
+		}
+	}
+}
+
+impl std::str::FromStr for SourceConfidence {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"best" => Ok(SourceConfidence::Best),
+			"finalized" => Ok(SourceConfidence::Finalized),
+			_ => s
+				.strip_suffix("-deep")
+				.and_then(|depth| depth.parse().ok())
+				.map(SourceConfidence::Deep)
+				.ok_or_else(|| {
+					format!(
+						"Invalid source confidence '{s}'. Expected 'best', '<n>-deep' or \
+						'finalized'"
+					)
+				}),
+		}
+	}
+}
+
+/// Returns true if the string may be used as Prometheus metric name prefix or label name.
+fn is_valid_prometheus_name(s: &str) -> bool {
+	let mut chars = s.chars();
+	chars.next().map_or(false, |c| c.is_ascii_alphabetic() || c == '_') &&
+		chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parse prefix of Prometheus metric names.
+pub fn parse_metrics_prefix(s: &str) -> Result<String, String> {
+	if !is_valid_prometheus_name(s) {
+		return Err(format!(
+			"Invalid metrics prefix '{s}'. It must match the '[a-zA-Z_][a-zA-Z0-9_]*' pattern"
+		))
+	}
+	Ok(s.into())
+}
+
+/// Parse Prometheus metric label in the `key=value` form.
+pub fn parse_metrics_label(s: &str) -> Result<(String, String), String> {
+	let (key, value) = s
+		.split_once('=')
+		.ok_or_else(|| format!("Invalid metrics label '{s}'. Expected 'key=value'"))?;
+	if !is_valid_prometheus_name(key) || key.starts_with("__") {
+		return Err(format!(
+			"Invalid metrics label name '{key}'. It must match the '[a-zA-Z_][a-zA-Z0-9_]*' \
+			pattern and must not start with '__'"
+		))
+	}
+	if value.is_empty() {
+		return Err(format!("Value of the metrics label '{key}' must not be empty"))
+	}
+	Ok((key.into(), value.into()))
+}
+
 /// Nicer formatting for raw bytes vectors.
 #[derive(Default, Encode, Decode, PartialEq, Eq)]
 pub struct HexBytes(pub Vec<u8>);
@@ -96,6 +265,53 @@ pub struct PrometheusParams {
 	/// Expose Prometheus endpoint at given port.
 	#[structopt(long, default_value = "9616")]
 	pub prometheus_port: u16,
+	/// Prefix that is added to names of all exposed metrics. E.g. with `relay_1` prefix, the
+	/// `substrate_relay_build_info` metric is exposed as `relay_1_substrate_relay_build_info`.
+	#[structopt(long, parse(try_from_str = parse_metrics_prefix))]
+	pub metrics_prefix: Option<String>,
+	/// Label in the `key=value` form that is added to all exposed metrics. May be repeated.
+	#[structopt(long = "metrics-label", parse(try_from_str = parse_metrics_label))]
+	pub metrics_labels: Vec<(String, String)>,
+}
+
+/// Relay control params.
+#[derive(Clone, Debug, PartialEq, StructOpt)]
+pub struct ControlParams {
+	/// Path of the Unix socket that accepts `pause`, `resume` and `status` commands. While the
+	/// relay is paused, it keeps connections and tracked state, but doesn't submit transactions.
+	#[structopt(long)]
+	pub control_socket: Option<std::path::PathBuf>,
+	/// Write JSON summary of the run (delivered and confirmed messages, spent fees, final
+	/// nonces of every lane) to given file when the relay exits.
+	#[structopt(long)]
+	pub exit_summary: Option<std::path::PathBuf>,
+	/// Stop the relay after given number of seconds. Messages that are held back and
+	/// transactions that are being submitted at this moment are handled before the relay
+	/// exits.
+	#[structopt(long)]
+	pub max_runtime_secs: Option<u64>,
+	/// Write SCALE-encoded records of the relay activity (submitted deliveries and
+	/// confirmations, skipped deliveries) to given file or Unix socket.
+	#[structopt(long)]
+	pub scale_event_sink: Option<std::path::PathBuf>,
+	/// Create given file once the relay has connected to both chains and all its races have
+	/// warmed up. The file is removed when the relay exits.
+	#[structopt(long)]
+	pub ready_file: Option<std::path::PathBuf>,
+	/// Directory, where the relay keeps the state that survives restarts. The rolling 24h
+	/// history of delivered and confirmed messages and spent fees is persisted there and it is
+	/// returned by the `stats` control command and written to the exit summary.
+	#[structopt(long)]
+	pub state_dir: Option<std::path::PathBuf>,
+	/// Duration of single bucket of the activity history, in seconds. Only used with
+	/// `--state-dir`.
+	#[structopt(long, default_value = "3600")]
+	pub stats_bucket_secs: u64,
+	/// Serve human-readable relay status page at `http://<address>/dashboard`. The page shows
+	/// the state of lanes and races, relay account balances and recent throughput, that the
+	/// relay already knows, so it never makes calls to nodes.
+	#[structopt(long)]
+	pub dashboard_address: Option<std::net::SocketAddr>,
 }
 
 /// Struct to get git commit info and build time.
@@ -135,8 +351,172 @@ impl PrometheusParams {
 			.clone()
 			.unwrap_or_else(|| "unknown".to_string());
 		let relay_commit = SubstrateRelayBuildInfo::get_git_commit();
-		relay_utils::metrics::MetricsParams::new(metrics_address, relay_version, relay_commit)
-			.map_err(|e| anyhow::format_err!("{:?}", e))
+
+		let mut labels = std::collections::HashMap::new();
+		for (key, value) in self.metrics_labels {
+			if labels.insert(key.clone(), value).is_some() {
+				return Err(anyhow::format_err!("Metrics label '{}' is specified twice", key))
+			}
+		}
+
+		relay_utils::metrics::MetricsParams::new(
+			metrics_address,
+			relay_version,
+			relay_commit,
+			self.metrics_prefix,
+			labels,
+		)
+		.map_err(|e| anyhow::format_err!("{:?}", e))
+	}
+}
+
+impl ControlParams {
+	/// Create relay control and start serving control socket, if it is enabled.
+	pub fn into_relay_control(self) -> anyhow::Result<relay_utils::control::RelayControl> {
+		let control = relay_utils::control::RelayControl::default();
+		if let Some(ref control_socket) = self.control_socket {
+			control.serve(control_socket)?;
+		}
+		if let Some(ready_file) = self.ready_file {
+			spawn_ready_file_writer(control.clone(), ready_file);
+		}
+		if let Some(state_dir) = self.state_dir {
+			std::fs::create_dir_all(&state_dir).map_err(|e| {
+				anyhow::format_err!("Failed to create state directory {:?}: {}", state_dir, e)
+			})?;
+			let history_path = state_dir.join(STATS_HISTORY_FILE_NAME);
+			let history =
+				StatsHistory::load(&history_path, Duration::from_secs(self.stats_bucket_secs))?;
+			control.summary().track_history(history);
+			spawn_history_tracker(control.summary().clone(), Some(history_path));
+		}
+		if let Some(dashboard_address) = self.dashboard_address {
+			relay_utils::dashboard::serve(control.clone(), dashboard_address)?;
+		}
+		Ok(control)
+	}
+
+	/// Open the `--scale-event-sink`, if it is set.
+	pub fn scale_event_sink(&self) -> anyhow::Result<Option<ScaleEventSink>> {
+		self.scale_event_sink.as_deref().map(ScaleEventSink::open).transpose()
+	}
+
+	/// Returns maximal duration of the relay run, if it is limited.
+	pub fn max_runtime(&self) -> Option<Duration> {
+		self.max_runtime_secs.map(Duration::from_secs)
+	}
+
+	/// Write summary of the relay run to the `--exit-summary` file, if it is set.
+	///
+	/// Failure to write the summary is only logged, so that the relay `result` is
+	/// propagated to the caller.
+	pub fn write_exit_summary(
+		&self,
+		control: &relay_utils::control::RelayControl,
+		result: &anyhow::Result<()>,
+	) {
+		let exit_summary = match self.exit_summary {
+			Some(ref exit_summary) => exit_summary,
+			None => return,
+		};
+		if let Err(e) = control.summary().write(exit_summary, &self.exit_reason(result)) {
+			log::error!(target: "bridge", "{:?}", e);
+		}
+	}
+
+	/// Remove the `--ready-file`, if it is set and has been created.
+	pub fn remove_ready_file(&self) {
+		let ready_file = match self.ready_file {
+			Some(ref ready_file) => ready_file,
+			None => return,
+		};
+		match std::fs::remove_file(ready_file) {
+			Ok(()) => (),
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+			Err(e) => log::error!(
+				target: "bridge",
+				"Failed to remove ready file {:?}: {}",
+				ready_file,
+				e,
+			),
+		}
+	}
+
+	/// Returns exit reason of the relay run with given `result`.
+	fn exit_reason(&self, result: &anyhow::Result<()>) -> String {
+		match (result, self.max_runtime_secs) {
+			// messages relay only exits successfully when its exit signal fires
+			(Ok(()), Some(max_runtime_secs)) =>
+				format!("max runtime of {max_runtime_secs}s elapsed"),
+			(Ok(()), None) => "finished".to_string(),
+			(Err(ref e), _) => format!("{e:?}"),
+		}
+	}
+}
+
+/// Start background task that creates the `ready_file` once all relay races are ready.
+///
+/// The file isn't created if the relay is halted before that.
+fn spawn_ready_file_writer(control: relay_utils::control::RelayControl, ready_file: PathBuf) {
+	async_std::task::spawn(async move {
+		loop {
+			if control.halt_reason().is_some() {
+				return
+			}
+			if control.is_ready() {
+				break
+			}
+			async_std::task::sleep(READY_FILE_POLL_INTERVAL).await;
+		}
+
+		match std::fs::write(&ready_file, format!("{}\n", std::process::id())) {
+			Ok(()) => log::info!(target: "bridge", "Relay is ready. Created {:?}", ready_file),
+			Err(e) =>
+				log::error!(target: "bridge", "Failed to create ready file {:?}: {}", ready_file, e),
+		}
+	});
+}
+
+/// OpenTelemetry traces export params.
+#[derive(Clone, Debug, PartialEq, StructOpt)]
+pub struct TracingParams {
+	/// Export traces to the OTLP collector at given endpoint (e.g. `http://localhost:4318`)
+	/// using the OTLP/HTTP JSON protocol. Traces are not collected if the endpoint is not set.
+	#[structopt(long)]
+	pub otlp_endpoint: Option<String>,
+}
+
+impl TracingParams {
+	/// Start exporting traces, if it is enabled.
+	pub fn start_exporter(&self) -> anyhow::Result<()> {
+		match self.otlp_endpoint {
+			Some(ref otlp_endpoint) =>
+				relay_utils::otlp::start_exporter(otlp_endpoint, "substrate-relay"),
+			None => Ok(()),
+		}
+	}
+}
+
+/// Params of detecting that the relayer is the only active relayer of the lane.
+#[derive(Clone, Debug, PartialEq, StructOpt)]
+pub struct SoleRelayerDetectionParams {
+	/// Alert if the given number of latest delivered messages have all been delivered by this
+	/// relayer. Detection is disabled if not set.
+	#[structopt(long)]
+	pub sole_relayer_lookback: Option<MessageNonce>,
+	/// What to do when the relayer is the only active relayer of the lane: `alert` or `pause`.
+	/// Paused relay may be resumed using the control socket.
+	#[structopt(long, default_value = "alert", possible_values = SoleRelayerAction::VARIANTS)]
+	pub sole_relayer_action: SoleRelayerAction,
+}
+
+impl SoleRelayerDetectionParams {
+	/// Returns sole relayer detection params, if detection is enabled.
+	pub fn into_params(&self) -> Option<SoleRelayerParams> {
+		self.sole_relayer_lookback.map(|lookback_messages| SoleRelayerParams {
+			lookback_messages,
+			action: self.sole_relayer_action,
+		})
 	}
 }
 
@@ -193,4 +573,107 @@ mod tests {
 		// then
 		assert_eq!(hex.0, hex2.0);
 	}
+
+	#[test]
+	fn lane_config_is_parsed() {
+		assert_eq!(
+			"00000000".parse::<LaneConfig>(),
+			Ok(LaneConfig { lane: HexLaneId([0, 0, 0, 0]), signer: None }),
+		);
+		assert_eq!(
+			"00000004@2".parse::<LaneConfig>(),
+			Ok(LaneConfig { lane: HexLaneId([0, 0, 0, 4]), signer: Some(2) }),
+		);
+
+		assert!("0000000".parse::<LaneConfig>().is_err());
+		assert!("00000000:".parse::<LaneConfig>().is_err());
+		assert!("00000000:altruistic".parse::<LaneConfig>().is_err());
+		assert!("00000000@".parse::<LaneConfig>().is_err());
+		assert!("00000000@alice".parse::<LaneConfig>().is_err());
+		assert!("00000000@-1".parse::<LaneConfig>().is_err());
+	}
+
+	#[test]
+	fn lane_log_config_is_parsed() {
+		assert_eq!(
+			"00000001=debug".parse::<LaneLogConfig>(),
+			Ok(LaneLogConfig { lane: HexLaneId([0, 0, 0, 1]), level: log::LevelFilter::Debug }),
+		);
+		assert_eq!(
+			"00000000=WARN".parse::<LaneLogConfig>(),
+			Ok(LaneLogConfig { lane: HexLaneId([0, 0, 0, 0]), level: log::LevelFilter::Warn }),
+		);
+		assert_eq!(
+			"00000002=off".parse::<LaneLogConfig>(),
+			Ok(LaneLogConfig { lane: HexLaneId([0, 0, 0, 2]), level: log::LevelFilter::Off }),
+		);
+
+		assert!("00000000".parse::<LaneLogConfig>().is_err());
+		assert!("00000000=".parse::<LaneLogConfig>().is_err());
+		assert!("00000000=verbose".parse::<LaneLogConfig>().is_err());
+		assert!("0000000=debug".parse::<LaneLogConfig>().is_err());
+		assert!("00000000:altruistic=debug".parse::<LaneLogConfig>().is_err());
+	}
+
+	#[test]
+	fn metrics_prefix_and_labels_are_parsed() {
+		assert_eq!(parse_metrics_prefix("relay_1"), Ok("relay_1".into()));
+		assert!(parse_metrics_prefix("").is_err());
+		assert!(parse_metrics_prefix("1relay").is_err());
+		assert!(parse_metrics_prefix("relay-1").is_err());
+
+		assert_eq!(
+			parse_metrics_label("instance=relay-1:9616"),
+			Ok(("instance".into(), "relay-1:9616".into())),
+		);
+		assert_eq!(parse_metrics_label("a=b=c"), Ok(("a".into(), "b=c".into())));
+		assert!(parse_metrics_label("instance").is_err());
+		assert!(parse_metrics_label("instance=").is_err());
+		assert!(parse_metrics_label("=relay").is_err());
+		assert!(parse_metrics_label("__name__=relay").is_err());
+		assert!(parse_metrics_label("relay.instance=1").is_err());
+	}
+
+	#[test]
+	fn percent_is_parsed() {
+		assert_eq!(parse_percent("1"), Ok(1));
+		assert_eq!(parse_percent("100"), Ok(100));
+		assert!(parse_percent("0").is_err());
+		assert!(parse_percent("101").is_err());
+		assert!(parse_percent("50%").is_err());
+	}
+
+	#[test]
+	fn source_confidence_is_mapped_to_confirmations() {
+		assert_eq!("best".parse(), Ok(SourceConfidence::Best));
+		assert_eq!(SourceConfidence::Best.source_message_confirmations(), 0);
+		assert_eq!("12-deep".parse(), Ok(SourceConfidence::Deep(12)));
+		assert_eq!(SourceConfidence::Deep(12).source_message_confirmations(), 12);
+		assert_eq!("finalized".parse(), Ok(SourceConfidence::Finalized));
+		assert_eq!(SourceConfidence::Finalized.source_message_confirmations(), 0);
+
+		assert!("deep".parse::<SourceConfidence>().is_err());
+		assert!("-deep".parse::<SourceConfidence>().is_err());
+		assert!("-1-deep".parse::<SourceConfidence>().is_err());
+		assert!("12".parse::<SourceConfidence>().is_err());
+	}
+
+	#[test]
+	fn exit_reason_reflects_time_boxed_run() {
+		let mut control_params = ControlParams {
+			control_socket: None,
+			exit_summary: None,
+			max_runtime_secs: Some(60),
+			scale_event_sink: None,
+			ready_file: None,
+			state_dir: None,
+			stats_bucket_secs: 3600,
+			dashboard_address: None,
+		};
+		assert_eq!(control_params.exit_reason(&Ok(())), "max runtime of 60s elapsed");
+		assert_eq!(control_params.exit_reason(&Err(anyhow::format_err!("failed"))), "failed",);
+
+		control_params.max_runtime_secs = None;
+		assert_eq!(control_params.exit_reason(&Ok(())), "finished");
+	}
 }
@@ -86,7 +86,7 @@ pub trait HeadersRelayer: RelayToRelayHeadersCliBridge {
 		let source_client = data.source.into_client::<Self::Source>().await?;
 		let target_client = data.target.into_client::<Self::Target>().await?;
 		let target_transactions_mortality = data.target_sign.target_transactions_mortality;
-		let target_sign = data.target_sign.to_keypair::<Self::Target>()?;
+		let target_sign = data.target_sign.to_keypair::<Self::Target>().await?;
 
 		let metrics_params: relay_utils::metrics::MetricsParams =
 			data.prometheus_params.into_metrics_params()?;
@@ -95,6 +95,7 @@ pub trait HeadersRelayer: RelayToRelayHeadersCliBridge {
 		let target_transactions_params = crate::TransactionParams {
 			signer: target_sign,
 			mortality: target_transactions_mortality,
+			priority: None,
 		};
 		Self::Finality::start_relay_guards(&target_client, target_client.can_start_version_guard())
 			.await?;
@@ -114,7 +115,7 @@ pub trait HeadersRelayer: RelayToRelayHeadersCliBridge {
 		let source_client = data.source.into_client::<Self::Source>().await?;
 		let target_client = data.target.into_client::<Self::Target>().await?;
 		let target_transactions_mortality = data.target_sign.target_transactions_mortality;
-		let target_sign = data.target_sign.to_keypair::<Self::Target>()?;
+		let target_sign = data.target_sign.to_keypair::<Self::Target>().await?;
 
 		crate::finality::relay_single_header::<Self::Finality>(
 			source_client,
@@ -122,6 +123,7 @@ pub trait HeadersRelayer: RelayToRelayHeadersCliBridge {
 			crate::TransactionParams {
 				signer: target_sign,
 				mortality: target_transactions_mortality,
+				priority: None,
 			},
 			data.number.unique_saturated_into(),
 		)
@@ -31,32 +31,59 @@ pub mod relay_to_relay;
 pub mod relay_to_parachain;
 
 use async_trait::async_trait;
-use std::{marker::PhantomData, sync::Arc};
+use std::{collections::BTreeMap, marker::PhantomData, sync::Arc, time::Duration};
 use structopt::StructOpt;
+use strum::{EnumString, VariantNames};
 
 use futures::{FutureExt, TryFutureExt};
 
 use crate::{
-	cli::{bridge::MessagesCliBridge, DefaultClient, HexLaneId, PrometheusParams},
-	messages_lane::{MessagesRelayLimits, MessagesRelayParams},
+	cli::{
+		bridge::MessagesCliBridge, relay_messages::ensure_different_chains, ControlParams,
+		DefaultClient, HexLaneId, LaneConfig, LaneLogConfig, PrometheusParams,
+		SoleRelayerDetectionParams, TracingParams,
+	},
+	messages_confirmations_batch::ConfirmationsBatcher,
+	messages_discovery::MessageDiscovery,
+	messages_global_pause::GlobalPauseGuard,
+	messages_lane::{MessageLaneAdapter, MessagesRelayLimits, MessagesRelayParams},
+	messages_pallet::PalletVersionMismatchAction,
+	messages_sole_relayer::SoleRelayerParams,
+	messages_target::FeeEstimationFallback,
 	on_demand::OnDemandRelay,
+	scale_events::ScaleEventSink,
 	HeadersToRelay, TaggedAccount, TransactionParams,
 };
 use bp_messages::LaneId;
 use bp_runtime::BalanceOf;
+use log::LevelFilter;
+use messages_relay::message_lane_loop::{metrics_prefix, CompanionLane, OversizedWeightPolicy};
 use relay_substrate_client::{
-	AccountIdOf, AccountKeyPairOf, Chain, ChainWithBalances, ChainWithMessages,
-	ChainWithRuntimeVersion, ChainWithTransactions,
+	global_pause::GlobalPauseStorage, storage_proof::StorageProofFormat, AccountIdOf,
+	AccountKeyPairOf, BatchCallKind, Chain, ChainWithBalances, ChainWithMessages,
+	ChainWithRuntimeVersion, ChainWithTransactions, Client,
+};
+use relay_utils::{
+	control::RelayControl,
+	metrics::{Metric, MetricsParams},
+	throughput_budget::ThroughputBudget,
 };
-use relay_utils::metrics::MetricsParams;
 use sp_core::Pair;
 
 /// Parameters that have the same names across all bridges.
 #[derive(Debug, PartialEq, StructOpt)]
 pub struct HeadersAndMessagesSharedParams {
-	/// Hex-encoded lane identifiers that should be served by the complex relay.
+	/// Hex-encoded lane identifiers that should be served by the complex relay. Every lane may
+	/// be served by the designated signer at both chains, e.g. `00000002@1`: `0` is the main
+	/// signer and additional signers are numbered from `1`. The designated signer isn't used by
+	/// other lanes.
 	#[structopt(long, default_value = "00000000")]
-	pub lane: Vec<HexLaneId>,
+	pub lane: Vec<LaneConfig>,
+	/// Override level of relay logs of the lane, e.g. `00000001=debug` to debug the troubled
+	/// lane or `00000000=warn` to quiet down the healthy one. The override applies to both
+	/// directions of the lane. Other lanes use levels, configured with `RUST_LOG`.
+	#[structopt(long)]
+	pub lane_log: Vec<LaneLogConfig>,
 	/// If passed, only mandatory headers (headers that are changing the GRANDPA authorities set)
 	/// are relayed.
 	#[structopt(long)]
@@ -65,9 +92,67 @@ pub struct HeadersAndMessagesSharedParams {
 	/// are relayed. Overrides `only_mandatory_headers`.
 	#[structopt(long)]
 	pub only_free_headers: bool,
+	/// Defer delivery of messages at the lane until its companion lane is making progress, e.g.
+	/// `00000000:left-to-right=00000000:right-to-left`. The companion lane is stalled if it has
+	/// unconfirmed messages and its confirmed nonce hasn't advanced within the staleness window.
+	#[structopt(long)]
+	pub companion_lane: Vec<CompanionLaneConfig>,
+	/// Companion lane staleness window in seconds.
+	#[structopt(long, default_value = "600")]
+	pub companion_lane_staleness_window: u64,
+	/// Maximal number of messages that all lanes may deliver within a rolling minute. Every
+	/// lane that has messages to deliver gets an equal share of this budget. Unlimited if not
+	/// set.
+	#[structopt(long)]
+	pub global_messages_per_minute: Option<u64>,
+	/// Submit delivery confirmations of lanes that are served by the same signers in a single
+	/// `pallet-utility` batch transaction. Confirmations that are batched with headers are
+	/// submitted separately. Batch transactions are not refunded by the bridge signed
+	/// extensions.
+	#[structopt(long)]
+	pub batch_confirmations_across_lanes: bool,
+	/// Call that is used to batch delivery confirmations of multiple lanes: `batch` stops at the
+	/// first failed confirmation, `batch-all` reverts all confirmations if any of them fails and
+	/// `force-batch` skips failed confirmations. Lanes resubmit confirmations that haven't been
+	/// dispatched.
+	#[structopt(long, default_value = "force-batch", possible_values = ConfirmationsBatchCall::VARIANTS)]
+	pub confirmations_batch_call: ConfirmationsBatchCall,
+	/// Global pause switch of the bridge at the left chain, e.g. `BridgeGuard.IsPaused`. The
+	/// storage value must be `bool` or `BasicOperatingMode`. While the bridge is paused, all
+	/// lanes hold back their delivery and confirmation transactions.
+	#[structopt(long)]
+	pub left_global_pause_storage: Option<GlobalPauseStorage>,
+	/// The same as `--left-global-pause-storage`, but at the right chain.
+	#[structopt(long)]
+	pub right_global_pause_storage: Option<GlobalPauseStorage>,
+	/// Interval in seconds between two consecutive reads of the global pause switch.
+	#[structopt(long, default_value = "12")]
+	pub global_pause_poll_interval_secs: u64,
+	/// Expose the category of the most recent error of every lane (`connection`, `rejected`
+	/// or `stalled`) as the `lane_error_state` metric. The category is reset to `ok` after the
+	/// successful delivery or confirmation transaction.
+	#[structopt(long)]
+	pub lane_error_state_metric: bool,
+	/// Read `MessagesReceived` events of blocks with delivery transactions and report the
+	/// dispatch weight, that has not been spent by delivered messages of every lane.
+	#[structopt(long)]
+	pub track_dispatch_refunds: bool,
+	/// Allow relaying if left and right clients are connected to the same chain. This only
+	/// makes sense for testing.
+	#[structopt(long)]
+	pub allow_same_chain: bool,
 	#[structopt(flatten)]
 	/// Prometheus metrics params.
 	pub prometheus_params: PrometheusParams,
+	#[structopt(flatten)]
+	/// Relay control params.
+	pub control_params: ControlParams,
+	#[structopt(flatten)]
+	/// OpenTelemetry traces export params.
+	pub tracing_params: TracingParams,
+	#[structopt(flatten)]
+	/// Sole relayer detection params.
+	pub sole_relayer_params: SoleRelayerDetectionParams,
 }
 
 impl HeadersAndMessagesSharedParams {
@@ -78,6 +163,216 @@ impl HeadersAndMessagesSharedParams {
 			_ => HeadersToRelay::All,
 		}
 	}
+
+	/// Returns companion lanes of gated lanes. Fails if some lane is not served by the relay,
+	/// if lane has multiple companions or if lanes are gating each other.
+	fn companion_lanes(
+		&self,
+		lanes: &[LaneId],
+	) -> anyhow::Result<BTreeMap<DirectedLane, DirectedLane>> {
+		let mut companion_lanes = BTreeMap::new();
+		for config in &self.companion_lane {
+			for lane in [config.gated, config.companion] {
+				if !lanes.contains(&lane.lane) {
+					anyhow::bail!("Companion lane config refers to unknown lane {}", lane)
+				}
+			}
+			if companion_lanes.insert(config.gated, config.companion).is_some() {
+				anyhow::bail!("Lane {} has multiple companion lanes", config.gated)
+			}
+		}
+
+		// every lane has at most one companion, so following companions from any lane either
+		// ends at the lane without companion, or loops
+		for gated in companion_lanes.keys() {
+			let mut path = vec![*gated];
+			let mut current = *gated;
+			while let Some(companion) = companion_lanes.get(&current) {
+				if *companion == *gated {
+					path.push(*companion);
+					anyhow::bail!(
+						"Cyclic companion lanes dependency: {}",
+						path.iter().map(|lane| lane.to_string()).collect::<Vec<_>>().join(" -> "),
+					)
+				}
+				if path.contains(companion) {
+					// loop that doesn't include `gated` lane, it'll be reported when we start
+					// from one of its lanes
+					break
+				}
+				path.push(*companion);
+				current = *companion;
+			}
+		}
+
+		Ok(companion_lanes)
+	}
+
+	/// Returns log level overrides of lanes. Fails if some lane is not served by the relay or
+	/// if lane has multiple overrides.
+	fn lane_log_levels(&self, lanes: &[LaneId]) -> anyhow::Result<BTreeMap<LaneId, LevelFilter>> {
+		let mut lane_log_levels = BTreeMap::new();
+		for config in &self.lane_log {
+			let lane = LaneId::from(config.lane.clone());
+			if !lanes.contains(&lane) {
+				anyhow::bail!(
+					"Lane log level refers to unknown lane {}",
+					hex::encode(config.lane.0)
+				)
+			}
+			if lane_log_levels.insert(lane, config.level).is_some() {
+				anyhow::bail!("Lane {} has multiple log levels", hex::encode(config.lane.0))
+			}
+		}
+		Ok(lane_log_levels)
+	}
+
+	/// Returns indices of left and right signers of every lane. Fails if the lane refers to an
+	/// unknown signer, if the signer is designated to multiple lanes or if there are no signers
+	/// left for lanes without designated signers.
+	///
+	/// Every signer is used by a single lane or by lanes without designated signers, so nonces
+	/// of signers, designated to different lanes, are never shared.
+	fn lane_signers(
+		&self,
+		left_signers: usize,
+		right_signers: usize,
+	) -> anyhow::Result<Vec<(Vec<usize>, Vec<usize>)>> {
+		let mut designated = BTreeMap::new();
+		for config in &self.lane {
+			if let Some(signer) = config.signer {
+				if signer >= left_signers || signer >= right_signers {
+					anyhow::bail!(
+						"Lane {} refers to unknown signer {}. There are {} left and {} right signers",
+						hex::encode(config.lane.0),
+						signer,
+						left_signers,
+						right_signers,
+					)
+				}
+				if let Some(other) = designated.insert(signer, config.lane.clone()) {
+					anyhow::bail!(
+						"Signer {} is designated to lanes {} and {}",
+						signer,
+						hex::encode(other.0),
+						hex::encode(config.lane.0),
+					)
+				}
+			}
+		}
+
+		let shared_signers = |signers: usize| {
+			(0..signers)
+				.filter(|signer| !designated.contains_key(signer))
+				.collect::<Vec<_>>()
+		};
+		let (left_shared_signers, right_shared_signers) =
+			(shared_signers(left_signers), shared_signers(right_signers));
+		self.lane
+			.iter()
+			.map(|config| match config.signer {
+				Some(signer) => Ok((vec![signer], vec![signer])),
+				None if left_shared_signers.is_empty() || right_shared_signers.is_empty() =>
+					Err(anyhow::format_err!(
+						"Lane {} has no designated signer and all signers are designated to other lanes",
+						hex::encode(config.lane.0),
+					)),
+				None => Ok((left_shared_signers.clone(), right_shared_signers.clone())),
+			})
+			.collect()
+	}
+}
+
+/// Call that is used to batch delivery confirmations of multiple lanes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, VariantNames)]
+#[strum(serialize_all = "kebab_case")]
+pub enum ConfirmationsBatchCall {
+	/// `pallet-utility::Call::batch`.
+	Batch,
+	/// `pallet-utility::Call::batch_all`.
+	BatchAll,
+	/// `pallet-utility::Call::force_batch`.
+	ForceBatch,
+}
+
+impl From<ConfirmationsBatchCall> for BatchCallKind {
+	fn from(call: ConfirmationsBatchCall) -> Self {
+		match call {
+			ConfirmationsBatchCall::Batch => BatchCallKind::Batch,
+			ConfirmationsBatchCall::BatchAll => BatchCallKind::BatchAll,
+			ConfirmationsBatchCall::ForceBatch => BatchCallKind::ForceBatch,
+		}
+	}
+}
+
+/// Direction of messages at the lane, served by the complex relay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, EnumString, VariantNames)]
+#[strum(serialize_all = "kebab_case")]
+pub enum LaneDirection {
+	/// Messages are delivered from the left chain to the right chain.
+	LeftToRight,
+	/// Messages are delivered from the right chain to the left chain.
+	RightToLeft,
+}
+
+/// Lane and direction of messages at this lane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DirectedLane {
+	/// Lane id.
+	pub lane: LaneId,
+	/// Direction of messages.
+	pub direction: LaneDirection,
+}
+
+impl std::fmt::Display for DirectedLane {
+	fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+		let direction = match self.direction {
+			LaneDirection::LeftToRight => "left-to-right",
+			LaneDirection::RightToLeft => "right-to-left",
+		};
+		write!(fmt, "{}:{}", hex::encode(self.lane), direction)
+	}
+}
+
+impl std::str::FromStr for DirectedLane {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (lane, direction) = s
+			.split_once(':')
+			.ok_or_else(|| format!("Invalid lane '{s}'. Expected '<lane>:<direction>'"))?;
+		let lane = lane
+			.parse::<HexLaneId>()
+			.map_err(|e| format!("Failed to parse lane id '{lane}': {e:?}"))?;
+		let direction = direction.parse::<LaneDirection>().map_err(|_| {
+			format!(
+				"Invalid lane direction '{direction}'. Expected one of {:?}",
+				LaneDirection::VARIANTS
+			)
+		})?;
+		Ok(DirectedLane { lane: lane.into(), direction })
+	}
+}
+
+/// Lane, which delivery is gated on progress of its companion lane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompanionLaneConfig {
+	/// Lane, which delivery is gated.
+	pub gated: DirectedLane,
+	/// Lane that must not be stalled for the gated lane delivery to proceed.
+	pub companion: DirectedLane,
+}
+
+impl std::str::FromStr for CompanionLaneConfig {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (gated, companion) =
+			s.split_once('=').ok_or_else(|| {
+				format!("Invalid companion lane '{s}'. Expected '<lane>:<direction>=<lane>:<direction>'")
+			})?;
+		Ok(CompanionLaneConfig { gated: gated.parse()?, companion: companion.parse()? })
+	}
 }
 
 /// Bridge parameters, shared by all bridge types.
@@ -94,6 +389,14 @@ pub struct Full2WayBridgeCommonParams<
 
 	/// Common metric parameters.
 	pub metrics_params: MetricsParams,
+	/// Runtime control, shared by all relays.
+	pub control: RelayControl,
+	/// Messages throughput budget, shared by all lanes.
+	pub throughput_budget: ThroughputBudget,
+	/// Sink of the relay activity events, shared by all lanes.
+	pub scale_event_sink: Option<ScaleEventSink>,
+	/// Guard of the global pause switch, shared by all lanes.
+	pub global_pause_guard: Option<GlobalPauseGuard>,
 }
 
 impl<
@@ -107,11 +410,55 @@ impl<
 		left: BridgeEndCommonParams<Left>,
 		right: BridgeEndCommonParams<Right>,
 	) -> anyhow::Result<Self> {
+		// Refuse to relay between two connections to the same chain.
+		ensure_different_chains::<Left, Right>(
+			left.client.genesis_hash().as_ref(),
+			right.client.genesis_hash().as_ref(),
+			shared.allow_same_chain,
+		)?;
 		// Create metrics registry.
 		let metrics_params = shared.prometheus_params.clone().into_metrics_params()?;
 		let metrics_params = relay_utils::relay_metrics(metrics_params).into_params();
+		// Start serving control commands.
+		let control = shared.control_params.clone().into_relay_control()?;
+		// Start exporting traces.
+		shared.tracing_params.start_exporter()?;
+		// Create messages throughput budget.
+		let throughput_budget = match shared.global_messages_per_minute {
+			Some(messages_per_minute) => {
+				let throughput_budget = ThroughputBudget::new(messages_per_minute, None)?;
+				throughput_budget.register(&metrics_params.registry)?;
+				throughput_budget
+			},
+			None => ThroughputBudget::default(),
+		};
+
+		// Open the sink of relay activity events.
+		let scale_event_sink = shared.control_params.scale_event_sink()?;
+		// Create guard of the global pause switch. It is started by the relay.
+		let global_pause_guard = if shared.left_global_pause_storage.is_some() ||
+			shared.right_global_pause_storage.is_some()
+		{
+			let global_pause_guard = GlobalPauseGuard::new(
+				Duration::from_secs(shared.global_pause_poll_interval_secs),
+				None,
+			)?;
+			global_pause_guard.register(&metrics_params.registry)?;
+			Some(global_pause_guard)
+		} else {
+			None
+		};
 
-		Ok(Self { shared, left, right, metrics_params })
+		Ok(Self {
+			shared,
+			left,
+			right,
+			metrics_params,
+			control,
+			throughput_budget,
+			scale_event_sink,
+			global_pause_guard,
+		})
 	}
 }
 
@@ -121,10 +468,37 @@ pub struct BridgeEndCommonParams<Chain: ChainWithTransactions + ChainWithRuntime
 	pub client: DefaultClient<Chain>,
 	/// Params used for sending transactions to the chain.
 	pub tx_params: TransactionParams<AccountKeyPairOf<Chain>>,
+	/// Additional signers, used to sign messages relay transactions in turns.
+	pub additional_signers: Vec<AccountKeyPairOf<Chain>>,
 	/// Accounts, which balances are exposed as metrics by the relay process.
 	pub accounts: Vec<TaggedAccount<AccountIdOf<Chain>>>,
 }
 
+impl<Chain: ChainWithTransactions + ChainWithRuntimeVersion> BridgeEndCommonParams<Chain> {
+	/// Returns number of signers: the main signer and additional signers.
+	fn signers_count(&self) -> usize {
+		1 + self.additional_signers.len()
+	}
+
+	/// Returns transaction params and additional signers that sign transactions with given
+	/// signers in turns. The main signer has index `0`.
+	fn with_signers(
+		&self,
+		signers: &[usize],
+	) -> (TransactionParams<AccountKeyPairOf<Chain>>, Vec<AccountKeyPairOf<Chain>>) {
+		let signer = |index: usize| match index {
+			0 => self.tx_params.signer.clone(),
+			_ => self.additional_signers[index - 1].clone(),
+		};
+		let (main_signer, additional_signers) =
+			signers.split_first().expect("lane signers are validated to be non-empty; qed");
+		(
+			TransactionParams { signer: signer(*main_signer), ..self.tx_params.clone() },
+			additional_signers.iter().map(|index| signer(*index)).collect(),
+		)
+	}
+}
+
 /// All data of the bidirectional complex relay.
 pub struct FullBridge<
 	'a,
@@ -135,6 +509,14 @@ pub struct FullBridge<
 	source: &'a mut BridgeEndCommonParams<Source>,
 	target: &'a mut BridgeEndCommonParams<Target>,
 	metrics_params: &'a MetricsParams,
+	control: &'a RelayControl,
+	throughput_budget: &'a ThroughputBudget,
+	sole_relayer: Option<SoleRelayerParams>,
+	max_runtime: Option<Duration>,
+	scale_event_sink: Option<ScaleEventSink>,
+	global_pause_guard: Option<GlobalPauseGuard>,
+	lane_error_state_metric: bool,
+	track_dispatch_refunds: bool,
 	_phantom_data: PhantomData<Bridge>,
 }
 
@@ -154,8 +536,61 @@ where
 		source: &'a mut BridgeEndCommonParams<Source>,
 		target: &'a mut BridgeEndCommonParams<Target>,
 		metrics_params: &'a MetricsParams,
+		control: &'a RelayControl,
+		throughput_budget: &'a ThroughputBudget,
+		sole_relayer: Option<SoleRelayerParams>,
+		max_runtime: Option<Duration>,
+		scale_event_sink: Option<ScaleEventSink>,
+		global_pause_guard: Option<GlobalPauseGuard>,
+		lane_error_state_metric: bool,
+		track_dispatch_refunds: bool,
 	) -> Self {
-		Self { source, target, metrics_params, _phantom_data: Default::default() }
+		Self {
+			source,
+			target,
+			metrics_params,
+			control,
+			throughput_budget,
+			sole_relayer,
+			max_runtime,
+			scale_event_sink,
+			global_pause_guard,
+			lane_error_state_metric,
+			track_dispatch_refunds,
+			_phantom_data: Default::default(),
+		}
+	}
+
+	/// Returns batcher of delivery confirmations of lanes with given source signers. Batchers
+	/// are shared using `batchers`.
+	fn confirmations_batcher(
+		&self,
+		batchers: &mut BTreeMap<
+			Vec<usize>,
+			Option<ConfirmationsBatcher<Bridge::MessagesLane, DefaultClient<Source>>>,
+		>,
+		source_signers: &[usize],
+		kind: BatchCallKind,
+	) -> Option<ConfirmationsBatcher<Bridge::MessagesLane, DefaultClient<Source>>> {
+		batchers
+			.entry(source_signers.to_vec())
+			.or_insert_with(|| {
+				let (transaction_params, _) = self.source.with_signers(source_signers);
+				let batcher =
+					ConfirmationsBatcher::new(self.source.client.clone(), transaction_params, kind);
+				if batcher.is_none() {
+					log::warn!(
+						target: "bridge",
+						"{} runtime doesn't support batch calls. Delivery confirmations of {} -> {} \
+						lanes are not batched",
+						Source::NAME,
+						Source::NAME,
+						Target::NAME,
+					);
+				}
+				batcher
+			})
+			.clone()
 	}
 
 	/// Returns message relay parameters.
@@ -164,18 +599,85 @@ where
 		source_to_target_headers_relay: Arc<dyn OnDemandRelay<Source, Target>>,
 		target_to_source_headers_relay: Arc<dyn OnDemandRelay<Target, Source>>,
 		lane_id: LaneId,
+		(source_signers, target_signers): &(Vec<usize>, Vec<usize>),
 		maybe_limits: Option<MessagesRelayLimits>,
+		companion_lane: Option<CompanionLane>,
+		log_level: Option<LevelFilter>,
+		confirmations_batcher: Option<
+			ConfirmationsBatcher<Bridge::MessagesLane, DefaultClient<Source>>,
+		>,
 	) -> MessagesRelayParams<Bridge::MessagesLane, DefaultClient<Source>, DefaultClient<Target>> {
+		let (source_transaction_params, source_additional_signers) =
+			self.source.with_signers(source_signers);
+		// delivery transactions are always signed by a single signer
+		let (target_transaction_params, _) = self.target.with_signers(target_signers);
 		MessagesRelayParams {
 			source_client: self.source.client.clone(),
-			source_transaction_params: self.source.tx_params.clone(),
+			source_transaction_params,
+			source_additional_signers,
+			source_proxy: None,
 			target_client: self.target.client.clone(),
-			target_transaction_params: self.target.tx_params.clone(),
+			target_transaction_params,
+			target_proxy: None,
 			source_to_target_headers_relay: Some(source_to_target_headers_relay),
 			target_to_source_headers_relay: Some(target_to_source_headers_relay),
 			lane_id,
 			limits: maybe_limits,
 			metrics_params: self.metrics_params.clone().disable(),
+			control: self.control.clone(),
+			sole_relayer: self.sole_relayer.clone(),
+			max_fees_per_hour: None,
+			fee_safety_margin_percent: 0,
+			fee_estimation_fallback: FeeEstimationFallback::Skip,
+			pallet_version_mismatch: PalletVersionMismatchAction::Warn,
+			backfill: None,
+			max_nonces_ahead: None,
+			log_backpressure: false,
+			target_sync_confirmations: 0,
+			reorg_alert_depth: None,
+			competition_analytics_window: None,
+			xcm_instruction_filter: None,
+			warm_up_blocks: 0,
+			first_submit_delay: Duration::ZERO,
+			inactivity_log_threshold: None,
+			correlate_messages: false,
+			oversized_weight_policy: OversizedWeightPolicy::WarnOnly,
+			max_message_dispatch_weight: None,
+			companion_lane,
+			target_balance_guard: None,
+			source_confirmation_guard: None,
+			source_reward_eligibility_guard: None,
+			source_rate_limit_quota_guard: None,
+			source_confirmations_batcher: confirmations_batcher,
+			target_fee_multiplier_guard: None,
+			target_runtime_upgrade_guard: None,
+			target_synced_header_age_guard: None,
+			target_header_progress_guard: None,
+			target_block_weight_headroom: None,
+			target_rate_limit_quota_guard: None,
+			messages_proof_format: StorageProofFormat::Legacy,
+			delivery_proof_format: StorageProofFormat::Legacy,
+			confirmation_lag: None,
+			max_confirmation_latency: None,
+			source_message_confirmations: 0,
+			deterministic: false,
+			throughput_budget: self.throughput_budget.clone(),
+			global_pause_guard: self.global_pause_guard.clone(),
+			min_batch: None,
+			pending_messages_gate: None,
+			serialize_races: false,
+			log_level,
+			lane_error_state_metric: self.lane_error_state_metric,
+			track_dispatch_refunds: self.track_dispatch_refunds,
+			paused_lane_poll_interval: None,
+			max_proof_generation_time: None,
+			message_discovery: MessageDiscovery::Storage,
+			message_grace_period_blocks: 0,
+			confirmations_priority_threshold: None,
+			retry_budget: None,
+			batch_tuner: None,
+			max_runtime: self.max_runtime,
+			scale_event_sink: self.scale_event_sink.clone(),
 		}
 	}
 }
@@ -254,6 +756,14 @@ where
 			&mut common.left,
 			&mut common.right,
 			&common.metrics_params,
+			&common.control,
+			&common.throughput_budget,
+			common.shared.sole_relayer_params.into_params(),
+			common.shared.control_params.max_runtime(),
+			common.scale_event_sink.clone(),
+			common.global_pause_guard.clone(),
+			common.shared.lane_error_state_metric,
+			common.shared.track_dispatch_refunds,
 		)
 	}
 
@@ -264,9 +774,45 @@ where
 			&mut common.right,
 			&mut common.left,
 			&common.metrics_params,
+			&common.control,
+			&common.throughput_budget,
+			common.shared.sole_relayer_params.into_params(),
+			common.shared.control_params.max_runtime(),
+			common.scale_event_sink.clone(),
+			common.global_pause_guard.clone(),
+			common.shared.lane_error_state_metric,
+			common.shared.track_dispatch_refunds,
 		)
 	}
 
+	/// Returns name of the given lane in the run summary.
+	fn lane_name(lane: DirectedLane) -> String {
+		match lane.direction {
+			LaneDirection::LeftToRight => metrics_prefix::<
+				MessageLaneAdapter<<Self::L2R as MessagesCliBridge>::MessagesLane>,
+			>(&lane.lane),
+			LaneDirection::RightToLeft => metrics_prefix::<
+				MessageLaneAdapter<<Self::R2L as MessagesCliBridge>::MessagesLane>,
+			>(&lane.lane),
+		}
+	}
+
+	/// Returns companion lane of the given lane.
+	fn companion_lane(
+		&self,
+		companion_lanes: &BTreeMap<DirectedLane, DirectedLane>,
+		lane: DirectedLane,
+	) -> Option<CompanionLane> {
+		let companion = companion_lanes.get(&lane)?;
+		let common = self.base().common();
+		let name = Self::lane_name(*companion);
+		Some(CompanionLane {
+			summary: common.control.summary().lane(name.clone()),
+			name,
+			staleness_window: Duration::from_secs(common.shared.companion_lane_staleness_window),
+		})
+	}
+
 	/// Start complex relay.
 	async fn run(&mut self) -> anyhow::Result<()> {
 		// Register standalone metrics.
@@ -286,21 +832,42 @@ where
 		let (left_to_right_on_demand_headers, right_to_left_on_demand_headers) =
 			self.mut_base().start_on_demand_headers_relayers().await?;
 
+		// start observing the global pause switch of the bridge
+		{
+			let common = self.base().common();
+			if let Some(ref global_pause_guard) = common.global_pause_guard {
+				if let Some(ref storage) = common.shared.left_global_pause_storage {
+					global_pause_guard.observe(common.left.client.clone(), storage.clone()).await?;
+				}
+				if let Some(ref storage) = common.shared.right_global_pause_storage {
+					global_pause_guard
+						.observe(common.right.client.clone(), storage.clone())
+						.await?;
+				}
+			}
+		}
+
 		// add balance-related metrics
-		let lanes = self
-			.base()
-			.common()
-			.shared
+		let shared = &self.base().common().shared;
+		let lanes = shared
 			.lane
 			.iter()
-			.cloned()
-			.map(Into::into)
+			.map(|lane| LaneId::from(lane.lane.clone()))
 			.collect::<Vec<_>>();
+		let companion_lanes = shared.companion_lanes(&lanes)?;
+		let lane_log_levels = shared.lane_log_levels(&lanes)?;
+		let common = self.base().common();
+		let lane_signers =
+			shared.lane_signers(common.left.signers_count(), common.right.signers_count())?;
+		let confirmations_batch_call = shared
+			.batch_confirmations_across_lanes
+			.then_some(BatchCallKind::from(shared.confirmations_batch_call));
 		{
 			let common = self.mut_base().mut_common();
 			crate::messages_metrics::add_relay_balances_metrics::<_, Self::Right>(
 				common.left.client.clone(),
 				&common.metrics_params,
+				common.control.summary(),
 				&common.left.accounts,
 				&lanes,
 			)
@@ -308,6 +875,7 @@ where
 			crate::messages_metrics::add_relay_balances_metrics::<_, Self::Left>(
 				common.right.client.clone(),
 				&common.metrics_params,
+				common.control.summary(),
 				&common.right.accounts,
 				&lanes,
 			)
@@ -316,27 +884,60 @@ where
 
 		// Need 2x capacity since we consider both directions for each lane
 		let mut message_relays = Vec::with_capacity(lanes.len() * 2);
-		for lane in lanes {
+		let mut left_to_right_confirmations_batchers = BTreeMap::new();
+		let mut right_to_left_confirmations_batchers = BTreeMap::new();
+		for (lane, (left_signers, right_signers)) in lanes.iter().copied().zip(lane_signers) {
+			let left_to_right_companion_lane = self.companion_lane(
+				&companion_lanes,
+				DirectedLane { lane, direction: LaneDirection::LeftToRight },
+			);
+			let left_to_right_confirmations_batcher = confirmations_batch_call.and_then(|kind| {
+				self.left_to_right().confirmations_batcher(
+					&mut left_to_right_confirmations_batchers,
+					&left_signers,
+					kind,
+				)
+			});
 			let left_to_right_messages =
 				crate::messages_lane::run::<<Self::L2R as MessagesCliBridge>::MessagesLane, _, _>(
 					self.left_to_right().messages_relay_params(
 						left_to_right_on_demand_headers.clone(),
 						right_to_left_on_demand_headers.clone(),
 						lane,
+						&(left_signers.clone(), right_signers.clone()),
 						Self::L2R::maybe_messages_limits(),
+						left_to_right_companion_lane,
+						lane_log_levels.get(&lane).copied(),
+						left_to_right_confirmations_batcher,
 					),
 				)
 				.map_err(|e| anyhow::format_err!("{}", e))
 				.boxed();
 			message_relays.push(left_to_right_messages);
 
+			let right_to_left_companion_lane = self.companion_lane(
+				&companion_lanes,
+				DirectedLane { lane, direction: LaneDirection::RightToLeft },
+			);
+			let right_to_left_confirmations_batcher = confirmations_batch_call.and_then(|kind| {
+				self.right_to_left().confirmations_batcher(
+					&mut right_to_left_confirmations_batchers,
+					&right_signers,
+					kind,
+				)
+			});
+
 			let right_to_left_messages =
 				crate::messages_lane::run::<<Self::R2L as MessagesCliBridge>::MessagesLane, _, _>(
 					self.right_to_left().messages_relay_params(
 						right_to_left_on_demand_headers.clone(),
 						left_to_right_on_demand_headers.clone(),
 						lane,
+						&(right_signers, left_signers),
 						Self::R2L::maybe_messages_limits(),
+						right_to_left_companion_lane,
+						lane_log_levels.get(&lane).copied(),
+						right_to_left_confirmations_batcher,
 					),
 				)
 				.map_err(|e| anyhow::format_err!("{}", e))
@@ -349,16 +950,28 @@ where
 			.await
 			.map_err(|e| anyhow::format_err!("{}", e))?;
 
-		futures::future::select_all(message_relays).await.0
+		let result = futures::future::select_all(message_relays).await.0;
+		let common = self.base().common();
+		if let Some(ref global_pause_guard) = common.global_pause_guard {
+			global_pause_guard.stop().await;
+		}
+		common.shared.control_params.write_exit_summary(&common.control, &result);
+		common.shared.control_params.remove_ready_file();
+		result
 	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use crate::{cli::chain_schema::RuntimeVersionType, declare_chain_cli_schema};
+	use crate::{
+		cli::chain_schema::RuntimeVersionType, declare_chain_cli_schema,
+		messages_sole_relayer::SoleRelayerAction,
+	};
 
-	use relay_substrate_client::{ChainRuntimeVersion, Parachain, SimpleRuntimeVersion};
+	use relay_substrate_client::{
+		ChainRuntimeVersion, NonceSource, Parachain, SimpleRuntimeVersion, TransactionVersionBump,
+	};
 
 	#[test]
 	// We need `#[allow(dead_code)]` because some of the methods generated by the macros
@@ -432,13 +1045,45 @@ mod tests {
 			res,
 			BridgeHubKusamaBridgeHubPolkadotHeadersAndMessages {
 				shared: HeadersAndMessagesSharedParams {
-					lane: vec![HexLaneId([0x00, 0x00, 0x00, 0x00])],
+					lane: vec![LaneConfig {
+						lane: HexLaneId([0x00, 0x00, 0x00, 0x00]),
+						signer: None,
+					}],
+					lane_log: vec![],
 					only_mandatory_headers: false,
 					only_free_headers: false,
+					companion_lane: vec![],
+					companion_lane_staleness_window: 600,
+					global_messages_per_minute: None,
+					batch_confirmations_across_lanes: false,
+					confirmations_batch_call: ConfirmationsBatchCall::ForceBatch,
+					left_global_pause_storage: None,
+					right_global_pause_storage: None,
+					global_pause_poll_interval_secs: 12,
+					lane_error_state_metric: false,
+					track_dispatch_refunds: false,
+					allow_same_chain: false,
 					prometheus_params: PrometheusParams {
 						no_prometheus: false,
 						prometheus_host: "0.0.0.0".into(),
 						prometheus_port: 9616,
+						metrics_prefix: None,
+						metrics_labels: vec![],
+					},
+					control_params: ControlParams {
+						control_socket: None,
+						exit_summary: None,
+						max_runtime_secs: None,
+						scale_event_sink: None,
+						ready_file: None,
+						state_dir: None,
+						stats_bucket_secs: 3600,
+						dashboard_address: None,
+					},
+					tracing_params: TracingParams { otlp_endpoint: None },
+					sole_relayer_params: SoleRelayerDetectionParams {
+						sole_relayer_lookback: None,
+						sole_relayer_action: SoleRelayerAction::Alert,
 					},
 				},
 				left: BridgeHubKusamaConnectionParams {
@@ -452,9 +1097,21 @@ mod tests {
 						bridge_hub_kusama_spec_version: None,
 						bridge_hub_kusama_transaction_version: None,
 					},
+					bridge_hub_kusama_read_endpoint: None,
+					bridge_hub_kusama_write_endpoint: None,
+					bridge_hub_kusama_max_in_flight: None,
+					bridge_hub_kusama_fill_nonce_gaps: None,
+					bridge_hub_kusama_nonce_source: NonceSource::Best,
+					bridge_hub_kusama_transaction_version_bump: TransactionVersionBump::Keep,
+					bridge_hub_kusama_direct_inclusion_endpoint: None,
+					bridge_hub_kusama_rpc_keepalive_secs: None,
+					bridge_hub_kusama_trace_rpc: false,
+					bridge_hub_kusama_trace_rpc_redact_transactions: false,
+					bridge_hub_kusama_fallback_endpoint: vec![],
+					bridge_hub_kusama_disable_signing_cache: false,
 				},
 				left_sign: BridgeHubKusamaSigningParams {
-					bridge_hub_kusama_signer: Some("//Iden".into()),
+					bridge_hub_kusama_signer: vec!["//Iden".into()],
 					bridge_hub_kusama_signer_password: None,
 					bridge_hub_kusama_signer_file: None,
 					bridge_hub_kusama_signer_password_file: None,
@@ -471,6 +1128,18 @@ mod tests {
 						kusama_spec_version: None,
 						kusama_transaction_version: None,
 					},
+					kusama_read_endpoint: None,
+					kusama_write_endpoint: None,
+					kusama_max_in_flight: None,
+					kusama_fill_nonce_gaps: None,
+					kusama_nonce_source: NonceSource::Best,
+					kusama_transaction_version_bump: TransactionVersionBump::Keep,
+					kusama_direct_inclusion_endpoint: None,
+					kusama_rpc_keepalive_secs: None,
+					kusama_trace_rpc: false,
+					kusama_trace_rpc_redact_transactions: false,
+					kusama_fallback_endpoint: vec![],
+					kusama_disable_signing_cache: false,
 				},
 				right: BridgeHubPolkadotConnectionParams {
 					bridge_hub_polkadot_uri: None,
@@ -483,9 +1152,21 @@ mod tests {
 						bridge_hub_polkadot_spec_version: None,
 						bridge_hub_polkadot_transaction_version: None,
 					},
+					bridge_hub_polkadot_read_endpoint: None,
+					bridge_hub_polkadot_write_endpoint: None,
+					bridge_hub_polkadot_max_in_flight: None,
+					bridge_hub_polkadot_fill_nonce_gaps: None,
+					bridge_hub_polkadot_nonce_source: NonceSource::Best,
+					bridge_hub_polkadot_transaction_version_bump: TransactionVersionBump::Keep,
+					bridge_hub_polkadot_direct_inclusion_endpoint: None,
+					bridge_hub_polkadot_rpc_keepalive_secs: None,
+					bridge_hub_polkadot_trace_rpc: false,
+					bridge_hub_polkadot_trace_rpc_redact_transactions: false,
+					bridge_hub_polkadot_fallback_endpoint: vec![],
+					bridge_hub_polkadot_disable_signing_cache: false,
 				},
 				right_sign: BridgeHubPolkadotSigningParams {
-					bridge_hub_polkadot_signer: Some("//George".into()),
+					bridge_hub_polkadot_signer: vec!["//George".into()],
 					bridge_hub_polkadot_signer_password: None,
 					bridge_hub_polkadot_signer_file: None,
 					bridge_hub_polkadot_signer_password_file: None,
@@ -502,8 +1183,173 @@ mod tests {
 						polkadot_spec_version: None,
 						polkadot_transaction_version: None,
 					},
+					polkadot_read_endpoint: None,
+					polkadot_write_endpoint: None,
+					polkadot_max_in_flight: None,
+					polkadot_fill_nonce_gaps: None,
+					polkadot_nonce_source: NonceSource::Best,
+					polkadot_transaction_version_bump: TransactionVersionBump::Keep,
+					polkadot_direct_inclusion_endpoint: None,
+					polkadot_rpc_keepalive_secs: None,
+					polkadot_trace_rpc: false,
+					polkadot_trace_rpc_redact_transactions: false,
+					polkadot_fallback_endpoint: vec![],
+					polkadot_disable_signing_cache: false,
 				},
 			}
 		);
 	}
+
+	fn shared_params(companion_lanes: &[&str]) -> HeadersAndMessagesSharedParams {
+		let mut args = vec!["test", "--lane", "00000000", "--lane", "00000001"];
+		for companion_lane in companion_lanes {
+			args.extend(["--companion-lane", companion_lane]);
+		}
+		HeadersAndMessagesSharedParams::from_iter(args)
+	}
+
+	#[test]
+	fn companion_lane_config_is_parsed() {
+		assert_eq!(
+			"00000000:left-to-right=00000001:right-to-left".parse::<CompanionLaneConfig>(),
+			Ok(CompanionLaneConfig {
+				gated: DirectedLane {
+					lane: LaneId([0, 0, 0, 0]),
+					direction: LaneDirection::LeftToRight
+				},
+				companion: DirectedLane {
+					lane: LaneId([0, 0, 0, 1]),
+					direction: LaneDirection::RightToLeft
+				},
+			}),
+		);
+		assert!("00000000:left-to-right".parse::<CompanionLaneConfig>().is_err());
+		assert!("00000000:up=00000001:right-to-left".parse::<CompanionLaneConfig>().is_err());
+	}
+
+	#[test]
+	fn companion_lanes_are_validated() {
+		let lanes = [LaneId([0, 0, 0, 0]), LaneId([0, 0, 0, 1])];
+
+		// one lane is gated on the reverse direction of the same lane
+		let companion_lanes = shared_params(&["00000000:left-to-right=00000000:right-to-left"])
+			.companion_lanes(&lanes)
+			.unwrap();
+		assert_eq!(companion_lanes.len(), 1);
+
+		// chain of dependencies is fine
+		assert!(shared_params(&[
+			"00000000:left-to-right=00000000:right-to-left",
+			"00000000:right-to-left=00000001:left-to-right",
+		])
+		.companion_lanes(&lanes)
+		.is_ok());
+
+		// unknown lane
+		assert!(shared_params(&["00000000:left-to-right=00000002:right-to-left"])
+			.companion_lanes(&lanes)
+			.is_err());
+
+		// multiple companions
+		assert!(shared_params(&[
+			"00000000:left-to-right=00000000:right-to-left",
+			"00000000:left-to-right=00000001:right-to-left",
+		])
+		.companion_lanes(&lanes)
+		.is_err());
+
+		// lanes are gating each other
+		let error = shared_params(&[
+			"00000000:left-to-right=00000000:right-to-left",
+			"00000000:right-to-left=00000001:left-to-right",
+			"00000001:left-to-right=00000000:left-to-right",
+		])
+		.companion_lanes(&lanes)
+		.unwrap_err();
+		assert!(error.to_string().contains("Cyclic companion lanes dependency"));
+
+		// lane is gating itself
+		assert!(shared_params(&["00000001:left-to-right=00000001:left-to-right"])
+			.companion_lanes(&lanes)
+			.is_err());
+	}
+
+	fn lanes_params(lanes: &[&str]) -> HeadersAndMessagesSharedParams {
+		let mut args = vec!["test"];
+		for lane in lanes {
+			args.extend(["--lane", *lane]);
+		}
+		HeadersAndMessagesSharedParams::from_iter(args)
+	}
+
+	#[test]
+	fn lane_signers_are_isolated() {
+		let lane_signers = lanes_params(&["00000000", "00000001@1", "00000002@2"])
+			.lane_signers(3, 4)
+			.unwrap();
+		assert_eq!(
+			lane_signers,
+			vec![(vec![0], vec![0, 3]), (vec![1], vec![1]), (vec![2], vec![2])],
+		);
+
+		// signers of different lanes never share nonces
+		for (i, (left_i, right_i)) in lane_signers.iter().enumerate() {
+			for (left_j, right_j) in lane_signers.iter().skip(i + 1) {
+				assert!(left_i.iter().all(|signer| !left_j.contains(signer)));
+				assert!(right_i.iter().all(|signer| !right_j.contains(signer)));
+			}
+		}
+
+		// lanes without designated signers share all signers
+		assert_eq!(
+			lanes_params(&["00000000", "00000001"]).lane_signers(2, 1).unwrap(),
+			vec![(vec![0, 1], vec![0]), (vec![0, 1], vec![0])],
+		);
+	}
+
+	#[test]
+	fn lane_signers_are_validated() {
+		// unknown signer at one of chains
+		assert!(lanes_params(&["00000000@1"]).lane_signers(2, 1).is_err());
+		assert!(lanes_params(&["00000000@2"]).lane_signers(2, 3).is_err());
+
+		// signer is designated to multiple lanes
+		let error = lanes_params(&["00000000@1", "00000001@1"]).lane_signers(2, 2).unwrap_err();
+		assert!(error.to_string().contains("is designated to lanes"));
+
+		// no signers left for lanes without designated signers
+		assert!(lanes_params(&["00000000@0", "00000001"]).lane_signers(1, 2).is_err());
+		assert!(lanes_params(&["00000000@0", "00000001@1"]).lane_signers(2, 2).is_ok());
+	}
+
+	#[test]
+	fn lane_log_levels_are_parsed_and_validated() {
+		let lanes = [LaneId([0, 0, 0, 0]), LaneId([0, 0, 0, 1])];
+		let lane_log_params = |lane_logs: &[&str]| {
+			let mut args = vec!["test", "--lane", "00000000", "--lane", "00000001"];
+			for lane_log in lane_logs {
+				args.extend(["--lane-log", lane_log]);
+			}
+			HeadersAndMessagesSharedParams::from_iter(args)
+		};
+
+		assert_eq!(
+			lane_log_params(&["00000001=debug", "00000000=warn"])
+				.lane_log_levels(&lanes)
+				.unwrap(),
+			vec![(lanes[0], LevelFilter::Warn), (lanes[1], LevelFilter::Debug)]
+				.into_iter()
+				.collect(),
+		);
+		assert!(lane_log_params(&[]).lane_log_levels(&lanes).unwrap().is_empty());
+
+		// unknown lane
+		assert!(lane_log_params(&["00000002=debug"]).lane_log_levels(&lanes).is_err());
+
+		// multiple levels of the same lane
+		let error = lane_log_params(&["00000001=debug", "00000001=trace"])
+			.lane_log_levels(&lanes)
+			.unwrap_err();
+		assert!(error.to_string().contains("has multiple log levels"));
+	}
 }
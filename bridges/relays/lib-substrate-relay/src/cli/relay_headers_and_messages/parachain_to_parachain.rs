@@ -110,12 +110,14 @@ macro_rules! declare_parachain_to_parachain_bridge_schema {
 							self.shared,
 							BridgeEndCommonParams {
 								client: self.left.into_client::<Left>().await?,
-								tx_params: self.left_sign.transaction_params::<Left>()?,
+								tx_params: self.left_sign.transaction_params::<Left>().await?,
+								additional_signers: self.left_sign.additional_keypairs::<Left>().await?,
 								accounts: vec![],
 							},
 							BridgeEndCommonParams {
 								client: self.right.into_client::<Right>().await?,
-								tx_params: self.right_sign.transaction_params::<Right>()?,
+								tx_params: self.right_sign.transaction_params::<Right>().await?,
+								additional_signers: self.right_sign.additional_keypairs::<Right>().await?,
 								accounts: vec![],
 							},
 						)?,
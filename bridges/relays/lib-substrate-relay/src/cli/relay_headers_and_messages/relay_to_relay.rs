@@ -85,17 +85,19 @@ macro_rules! declare_relay_to_relay_bridge_schema {
 							self.shared,
 							BridgeEndCommonParams {
 								client: self.left.into_client::<Left>().await?,
-								tx_params: self.left_sign.transaction_params::<Left>()?,
+								tx_params: self.left_sign.transaction_params::<Left>().await?,
+								additional_signers: self.left_sign.additional_keypairs::<Left>().await?,
 								accounts: vec![],
 							},
 							BridgeEndCommonParams {
 								client: self.right.into_client::<Right>().await?,
-								tx_params: self.right_sign.transaction_params::<Right>()?,
+								tx_params: self.right_sign.transaction_params::<Right>().await?,
+								additional_signers: self.right_sign.additional_keypairs::<Right>().await?,
 								accounts: vec![],
 							},
 						)?,
-						right_to_left_transaction_params: self.left_sign.transaction_params::<Left>(),
-						left_to_right_transaction_params: self.right_sign.transaction_params::<Right>(),
+						right_to_left_transaction_params: self.left_sign.transaction_params::<Left>().await,
+						left_to_right_transaction_params: self.right_sign.transaction_params::<Right>().await,
 					})
 				}
 			}
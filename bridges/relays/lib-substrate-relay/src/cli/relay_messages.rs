@@ -17,29 +17,505 @@
 //! Primitives for exposing the messages relaying functionality in the CLI.
 
 use crate::{
-	cli::{bridge::*, chain_schema::*, HexLaneId, PrometheusParams},
-	messages_lane::MessagesRelayParams,
+	cli::{
+		bridge::*, chain_schema::*, parse_percent, ControlParams, HexLaneId, PrometheusParams,
+		SoleRelayerDetectionParams, SourceConfidence, TracingParams,
+	},
+	messages_backfill::{BackfillParams, BackfillPolicy},
+	messages_decode::{read_outbound_message, read_pending_messages},
+	messages_discovery::MessageDiscovery,
+	messages_global_pause::GlobalPauseGuard,
+	messages_header_progress::{HeaderProgressGuard, NoHeaderRelayPolicy},
+	messages_lane::{MessageLaneAdapter, MessagesRelayParams},
+	messages_pallet::PalletVersionMismatchAction,
+	messages_replay::{replay_delivery_decision, ReplayConfig},
+	messages_source::{ConfirmationBalanceGuard, RewardEligibilityGuard},
+	messages_target::{
+		read_existential_deposit, BalanceGuard, BlockWeightHeadroom, FeeEstimationFallback,
+		FeeMultiplierGuard, RateLimitQuotaGuard, RuntimeUpgradeGuard, SyncedHeaderAgeGuard,
+	},
+	messages_time_to_confirm::{estimate_time_to_confirm, read_lane_nonces, RelayTimings},
+	messages_verify_proof::verify_messages_proof,
+	relayer_registration::ensure_relayer_registered,
 	TransactionParams,
 };
 
 use async_trait::async_trait;
+use codec::Decode;
 use sp_core::Pair;
 use structopt::StructOpt;
+use strum::{EnumString, VariantNames};
 
-use bp_messages::MessageNonce;
+use bp_messages::{ChainWithMessages as _, LaneId, MessageNonce};
 use bp_runtime::HeaderIdProvider;
+use frame_support::weights::Weight;
+use messages_relay::message_lane_loop::{
+	metrics_prefix, BatchAutoTuner, MinBatch, PendingMessagesGate, RetryBudget,
+};
+use pallet_transaction_payment::Multiplier;
 use relay_substrate_client::{
-	AccountIdOf, AccountKeyPairOf, BalanceOf, Chain, ChainWithRuntimeVersion,
-	ChainWithTransactions, Client,
+	global_pause::GlobalPauseStorage,
+	rate_limit::RateLimitQuotaStorage,
+	storage_proof::{detect_storage_proof_format, StorageProofFormat},
+	xcm_filter::XcmInstructionFilter,
+	AccountIdOf, AccountKeyPairOf, BalanceOf, Chain, ChainWithBalances, ChainWithMessages as _,
+	ChainWithRuntimeVersion, ChainWithTransactions, Client,
 };
-use relay_utils::UniqueSaturatedInto;
+use relay_utils::{control::SpecVersions, metrics::Metric, UniqueSaturatedInto};
+use sp_runtime::transaction_validity::TransactionPriority;
+use std::time::Duration;
+
+/// What the relay does when the next message can't fit into the delivery transaction.
+///
+/// There's no `skip` policy: the messages pallet only accepts messages in nonce order, so the
+/// oversized message can't be skipped.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, EnumString, VariantNames)]
+#[strum(serialize_all = "kebab_case")]
+pub enum OversizedWeightPolicy {
+	/// Stop the relay with an error.
+	Halt,
+	/// Keep trying to deliver the message and log an error.
+	WarnOnly,
+	/// Hold back the lane and check the message again every `--stall-reprobe-interval-secs`.
+	Reprobe,
+}
+
+impl OversizedWeightPolicy {
+	/// Convert into the policy of the messages relay.
+	pub fn into_policy(
+		self,
+		reprobe_interval: Duration,
+	) -> messages_relay::message_lane_loop::OversizedWeightPolicy {
+		match self {
+			OversizedWeightPolicy::Halt =>
+				messages_relay::message_lane_loop::OversizedWeightPolicy::Halt,
+			OversizedWeightPolicy::WarnOnly =>
+				messages_relay::message_lane_loop::OversizedWeightPolicy::WarnOnly,
+			OversizedWeightPolicy::Reprobe =>
+				messages_relay::message_lane_loop::OversizedWeightPolicy::Reprobe(reprobe_interval),
+		}
+	}
+}
+
+/// Format of storage proofs, submitted to the chain.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, EnumString, VariantNames)]
+#[strum(serialize_all = "kebab_case")]
+pub enum ProofFormat {
+	/// Select format, supported by the chain runtime.
+	Auto,
+	/// Proof with all trie nodes.
+	Legacy,
+	/// Compact proof without hashes of included trie nodes.
+	Compact,
+}
+
+impl ProofFormat {
+	/// Returns format of storage proofs that are verified by the runtime of the given chain.
+	pub async fn select<C: Chain>(self, client: &impl Client<C>) -> StorageProofFormat {
+		match self {
+			ProofFormat::Auto => detect_storage_proof_format(client).await,
+			ProofFormat::Legacy => StorageProofFormat::Legacy,
+			ProofFormat::Compact => StorageProofFormat::Compact,
+		}
+	}
+}
+
+/// Returns explicit priority of transactions, submitted to the given chain.
+///
+/// Most runtimes compute the transaction priority themselves (e.g. from the tip). For such
+/// runtimes the explicit priority would be ignored, so it is dropped here and we warn the user.
+fn transaction_priority<C: ChainWithTransactions>(
+	priority: Option<TransactionPriority>,
+) -> Option<TransactionPriority> {
+	match priority {
+		Some(priority) if !C::HAS_PRIORITY_EXTENSION => {
+			log::warn!(
+				target: "bridge",
+				"{} runtime has no signed extension with explicit transaction priority. Ignoring \
+				`--transaction-priority {}`",
+				C::NAME,
+				priority,
+			);
+			None
+		},
+		priority => priority,
+	}
+}
+
+/// Ensure that source and target clients are connected to different chains.
+///
+/// Genesis hashes of chains are compared, so it also detects the case when both source and
+/// target connection params are pointing to the same node.
+pub(crate) fn ensure_different_chains<Source: Chain, Target: Chain>(
+	source_genesis_hash: &[u8],
+	target_genesis_hash: &[u8],
+	allow_same_chain: bool,
+) -> anyhow::Result<()> {
+	if source_genesis_hash != target_genesis_hash {
+		return Ok(())
+	}
+
+	if allow_same_chain {
+		log::warn!(
+			target: "bridge",
+			"Source ({}) and target ({}) clients are connected to the same chain with genesis {}. \
+			Relaying anyway, because `--allow-same-chain` is set",
+			Source::NAME,
+			Target::NAME,
+			hex::encode(source_genesis_hash),
+		);
+		return Ok(())
+	}
+
+	Err(anyhow::format_err!(
+		"Source ({}) and target ({}) clients are connected to the same chain with genesis {}. \
+		Check connection params or use `--allow-same-chain` if this is intended",
+		Source::NAME,
+		Target::NAME,
+		hex::encode(source_genesis_hash),
+	))
+}
 
 /// Messages relaying params.
-#[derive(StructOpt)]
+#[derive(Debug, StructOpt)]
 pub struct RelayMessagesParams {
 	/// Hex-encoded lane id that should be served by the relay. Defaults to `00000000`.
 	#[structopt(long, default_value = "00000000")]
 	lane: HexLaneId,
+	/// Maximal fees (in target chain tokens) that may be spent on delivery transactions within
+	/// a rolling hour. Delivery is deferred once the limit is reached. Unlimited if not set.
+	#[structopt(long)]
+	max_fees_per_hour: Option<u128>,
+	/// Increase the estimated fee of delivery transaction by this percent before checking it
+	/// against `--max-fees-per-hour` and `--balance-buffer`. The fee is estimated from the
+	/// actual weight of the delivery call, so the margin only covers the fee changes between
+	/// estimation and inclusion.
+	#[structopt(long, default_value = "0")]
+	fee_safety_margin_percent: u32,
+	/// What to do when the fee of delivery transaction can't be estimated: `skip` defers the
+	/// delivery, `altruistic` delivers messages without checking the fee and `last-known` uses
+	/// the latest successfully estimated fee (or defers the delivery if there's none).
+	#[structopt(long, default_value = "skip", possible_values = FeeEstimationFallback::VARIANTS)]
+	fee_estimation_fallback: FeeEstimationFallback,
+	/// What to do when messages pallet instances at the source and target chains have
+	/// different declared versions: `refuse` to start the relay or `warn` and start it.
+	#[structopt(
+		long,
+		default_value = "refuse",
+		possible_values = PalletVersionMismatchAction::VARIANTS
+	)]
+	pallet_version_mismatch: PalletVersionMismatchAction,
+	/// Check the number of undelivered messages at startup. If there are more messages than
+	/// that, the relay follows the `--backfill-policy`.
+	#[structopt(long)]
+	max_backfill_nonces: Option<MessageNonce>,
+	/// What to do when there are more undelivered messages than `--max-backfill-nonces` at
+	/// startup: `refuse` to start the relay or `cap` the backfill. The capped backfill delivers
+	/// the allowed number of oldest messages and then pauses the relay, which may be resumed
+	/// using the control socket.
+	#[structopt(long, default_value = "refuse", possible_values = BackfillPolicy::VARIANTS)]
+	backfill_policy: BackfillPolicy,
+	/// Deliver all undelivered messages, even if there are more than `--max-backfill-nonces`.
+	#[structopt(long, requires = "max-backfill-nonces")]
+	acknowledge_backfill: bool,
+	/// Only submit delivery transaction if the signer free balance covers its estimated fee
+	/// plus this buffer (in target chain tokens). The buffer should be at least the existential
+	/// deposit, unless `--preserve-existential-deposit` is used. Delivery is deferred with an
+	/// error otherwise. The balance is not checked if
+	/// not set.
+	#[structopt(long)]
+	balance_buffer: Option<u128>,
+	/// Only submit delivery confirmation transaction if the signer free balance covers its
+	/// estimated fee plus this buffer (in source chain tokens). Confirmation is deferred with an
+	/// error otherwise. Relayer rewards are only registered when deliveries are confirmed, so
+	/// the error means that rewards are piling up unclaimed. The balance is not checked if not
+	/// set.
+	#[structopt(long)]
+	confirmation_balance_buffer: Option<u128>,
+	/// Pause delivery while the relayer can't pay for the delivery confirmation transaction.
+	/// Requires `--confirmation-balance-buffer`.
+	#[structopt(long, requires = "confirmation-balance-buffer")]
+	pause_delivery_on_unaffordable_confirmation: bool,
+	/// Reserve the existential deposit, when checking the signer balance against
+	/// `--balance-buffer` and `--confirmation-balance-buffer`. Transactions that would leave
+	/// the account with less than the buffer plus the existential deposit are deferred, so
+	/// the account is never reaped. The existential deposit is read from the `Balances`
+	/// pallet constants at startup.
+	#[structopt(long)]
+	preserve_existential_deposit: bool,
+	/// Check that the relayer is eligible for rewards at the source chain bridge relayers
+	/// pallet before submitting delivery confirmations. The ineligibility reason is logged.
+	#[structopt(long)]
+	check_reward_eligibility: bool,
+	/// Skip delivery confirmations while the relayer is not eligible for rewards. Requires
+	/// `--check-reward-eligibility`.
+	#[structopt(long, requires = "check-reward-eligibility")]
+	skip_ineligible_confirmations: bool,
+	/// Defer delivery while the target chain `NextFeeMultiplier` is above this ceiling (e.g.
+	/// `1.5`). Delivery is resumed once the multiplier drops. There's no ceiling if not set.
+	#[structopt(long)]
+	max_fee_multiplier: Option<f64>,
+	/// Storage map with the remaining rate limit quota of target chain accounts, e.g.
+	/// `RateLimiter.RemainingQuota`. The map key hasher may be appended, e.g.
+	/// `RateLimiter.RemainingQuota:twox-64-concat` (`blake2-128-concat` is used by default).
+	/// Delivery is deferred while the relayer account has no quota left. The quota isn't
+	/// checked if not set.
+	#[structopt(long)]
+	target_rate_limit_quota_storage: Option<RateLimitQuotaStorage>,
+	/// The same as `--target-rate-limit-quota-storage`, but for delivery confirmations at the
+	/// source chain.
+	#[structopt(long)]
+	source_rate_limit_quota_storage: Option<RateLimitQuotaStorage>,
+	/// Format of messages proofs and messages delivery proofs. With `auto`, the format is
+	/// selected using the runtime version of the chain that verifies the proof, falling back to
+	/// `legacy` if the format can't be detected.
+	#[structopt(long, possible_values = ProofFormat::VARIANTS, default_value = "auto")]
+	proof_format: ProofFormat,
+	/// Global pause switch of the bridge at the source chain, e.g. `BridgeGuard.IsPaused`. The
+	/// storage value must be `bool` or `BasicOperatingMode`. While the bridge is paused, both
+	/// delivery and confirmation transactions are held back.
+	#[structopt(long)]
+	source_global_pause_storage: Option<GlobalPauseStorage>,
+	/// The same as `--source-global-pause-storage`, but at the target chain.
+	#[structopt(long)]
+	target_global_pause_storage: Option<GlobalPauseStorage>,
+	/// Interval in seconds between two consecutive reads of the global pause switch.
+	#[structopt(long, default_value = "12")]
+	global_pause_poll_interval_secs: u64,
+	/// Fit delivery batches into the weight that the recent target chain blocks have left for
+	/// normal transactions. Delivery is deferred while even one message doesn't fit.
+	#[structopt(long)]
+	respect_block_weight_headroom: bool,
+	/// Defer delivery for this number of seconds after the target chain runtime upgrade has
+	/// been detected, so that transactions aren't built against the previous runtime version.
+	/// Upgrades are detected by a change of the `LastRuntimeUpgrade` spec version. Delivery
+	/// isn't deferred if not set.
+	#[structopt(long)]
+	runtime_upgrade_settle_period: Option<u64>,
+	/// Report the source header, last synced to the target chain, as stale if it is older than
+	/// this number of seconds. Proofs of new messages can't be verified at the target chain
+	/// while source headers aren't synced. The age is exposed as a metric anyway.
+	#[structopt(long)]
+	max_synced_header_age: Option<u64>,
+	/// Pause delivery while the source header, last synced to the target chain, is stale.
+	/// Requires `--max-synced-header-age`.
+	#[structopt(long, requires = "max-synced-header-age")]
+	pause_delivery_on_stale_synced_header: bool,
+	/// What to do when the source header, required by delivery, hasn't been synced to the
+	/// target chain for `--no-header-relay-timeout` seconds, while the last synced header
+	/// hasn't advanced: `warn` about the missing headers relay, `pause` delivery until headers
+	/// are synced or start the on-demand headers relay (`auto-relay`). This command doesn't
+	/// relay headers itself, so some headers relay must be running otherwise.
+	#[structopt(long, default_value = "warn", possible_values = NoHeaderRelayPolicy::VARIANTS)]
+	no_header_relay_policy: NoHeaderRelayPolicy,
+	/// Number of seconds that the required source header may stay unsynced before the
+	/// `--no-header-relay-policy` is applied.
+	#[structopt(long, default_value = "600")]
+	no_header_relay_timeout: u64,
+	/// Register the relayer at the bridge relayers pallet of the target chain on startup, if
+	/// it isn't registered yet. The stake is defined by the target chain runtime. The relay
+	/// exits if the registration fails. Requires `--registration-lease`.
+	#[structopt(long, requires = "registration-lease")]
+	auto_register: bool,
+	/// Number of target chain blocks, starting from the best block, for which the relayer is
+	/// registered by `--auto-register`.
+	#[structopt(long)]
+	registration_lease: Option<u32>,
+	/// Maximal number of messages ahead of the latest confirmed message that the relay
+	/// tracks. Later messages are not skipped - they are tracked when earlier messages are
+	/// confirmed. Unlimited if not set.
+	#[structopt(long)]
+	max_nonces_ahead: Option<MessageNonce>,
+	/// Log when the relay defers tracking of messages because of `--max-nonces-ahead`.
+	#[structopt(long)]
+	log_backpressure: bool,
+	/// Minimal number of messages in the delivery transaction. The relay holds back messages
+	/// until there are enough of them, or until `--min-batch-timeout` elapses. Held back
+	/// messages are delivered on exit. Messages are delivered as soon as possible if not set.
+	#[structopt(long)]
+	min_batch_size: Option<MessageNonce>,
+	/// Maximal time (in seconds) to hold back messages because of `--min-batch-size`.
+	#[structopt(long, default_value = "60")]
+	min_batch_timeout: u64,
+	/// Stay idle until at least given number of messages are waiting for delivery. Unlike the
+	/// `--min-batch-size`, the relay doesn't even prepare deliveries while it is idle. The relay
+	/// is engaged until all pending messages are delivered. Disabled if not set.
+	#[structopt(long)]
+	min_pending_messages: Option<MessageNonce>,
+	/// Also stop idling when messages have been waiting for delivery for given number of
+	/// seconds, even if there are less than `--min-pending-messages` of them. If not set,
+	/// messages may wait indefinitely.
+	#[structopt(long)]
+	min_pending_messages_timeout: Option<u64>,
+	/// Don't let the delivery and confirmation races talk to nodes concurrently. Races take
+	/// turns reading nonces, generating proofs and submitting transactions instead. That lowers
+	/// the load of constrained nodes, but every race waits for the other one, so messages are
+	/// delivered and confirmed later. By default, races run concurrently.
+	#[structopt(long)]
+	serialize_races: bool,
+	/// When the messages pallet at either side is halted, idle the lane instead of failing,
+	/// checking the pallet operating mode every given number of seconds. The lane is resumed as
+	/// soon as the pallet is operational again. If not set, the halted pallet is an error and
+	/// the relay keeps retrying.
+	#[structopt(long)]
+	paused_lane_poll_interval: Option<u64>,
+	/// Abandon the messages proof if the source node hasn't generated it in this number of
+	/// seconds. The proof is generated again later. With `--auto-tune-batch-size`, the batch is
+	/// shrunk after repeated timeouts. There's no limit if not set.
+	#[structopt(long)]
+	max_proof_gen_secs: Option<u64>,
+	/// How new messages are found at the source chain: `storage` reads the latest generated
+	/// nonce from the outbound lane storage at every block, `events` finds `MessageAccepted`
+	/// events of blocks, built since the previous read. Reading events may be cheaper on
+	/// archive nodes. The storage is authoritative and is still read after reorgs and gaps.
+	#[structopt(long, default_value = "storage", possible_values = MessageDiscovery::VARIANTS)]
+	message_discovery: MessageDiscovery,
+	/// Number of source chain blocks that must be built on top of the block where the message
+	/// has been generated, before the relay considers it for delivery. Messages, generated
+	/// within the grace period, are delivered together with later messages.
+	#[structopt(long, default_value = "0")]
+	message_grace_period_blocks: u32,
+	/// Throttle deliveries while the unrewarded relayers set at the target chain is filled by
+	/// at least given percent (1-100), so that delivery confirmations may drain it. Rewards
+	/// can't be claimed while the set is full. Deliveries are not throttled if not set.
+	#[structopt(long, parse(try_from_str = parse_percent))]
+	confirmations_priority_threshold: Option<u8>,
+	/// Halt the relay when the delivery of the next message has failed with transient errors
+	/// (e.g. when the transaction pool is full or the transaction is lost) more than given
+	/// number of times. Unlimited if not set, unless `--max-deterministic-retries` is set.
+	#[structopt(long)]
+	max_transient_retries: Option<u32>,
+	/// Halt the relay when the delivery of the next message has failed with deterministic
+	/// errors (e.g. when the transaction is rejected by the runtime or it is included, but the
+	/// message isn't delivered) more than given number of times. Unlimited if not set, unless
+	/// `--max-transient-retries` is set.
+	#[structopt(long)]
+	max_deterministic_retries: Option<u32>,
+	/// Tune the number of messages in the delivery transaction. The batch is halved when the
+	/// delivery transaction exhausts resources of the target block and grows by one message
+	/// after every successful delivery.
+	#[structopt(long)]
+	auto_tune_batch_size: bool,
+	/// Minimal number of messages in the delivery transaction, selected by
+	/// `--auto-tune-batch-size`.
+	#[structopt(long, default_value = "1")]
+	auto_tune_min_batch_size: MessageNonce,
+	/// Maximal number of messages in the delivery transaction, selected by
+	/// `--auto-tune-batch-size`. Defaults to the number of messages that fit the delivery
+	/// transaction.
+	#[structopt(long)]
+	auto_tune_max_batch_size: Option<MessageNonce>,
+	/// Number of target chain blocks that must be built on top of the block where the source
+	/// header has been synced, before the relay builds message proofs against this header.
+	#[structopt(long, default_value = "0")]
+	target_sync_confirmations: u32,
+	/// Only build delivery confirmations against target headers that have been synced to the
+	/// source chain at least this number of seconds ago. The lag is converted to the number of
+	/// source blocks using the observed source block time. The target header must also be
+	/// finalized, as it is without this option, so the stricter of both constraints applies.
+	#[structopt(long)]
+	confirmation_lag_secs: Option<u64>,
+	/// Maximal number of seconds between the message delivery and its delivery confirmation.
+	/// When delivered messages are staying unconfirmed for almost that long, confirmations are
+	/// relayed without waiting for the confirmation lag.
+	#[structopt(long)]
+	max_confirmation_latency_secs: Option<u64>,
+	/// Number of source chain blocks that must be built on top of the block, where message has
+	/// been sent, before the relay delivers it. Messages are always read from finalized source
+	/// blocks, so this only protects from reorgs of chains with unsafe finality.
+	#[structopt(long, default_value = "0")]
+	source_message_confirmations: u32,
+	/// How confident the relayer must be in the source chain header, before it delivers
+	/// messages, sent at this header: `best`, `<n>-deep` or `finalized`. Messages can only be
+	/// proven using finalized source headers, so `best` is the same as `finalized`, and
+	/// `<n>-deep` is the same as `--source-message-confirmations <n>`.
+	#[structopt(long, conflicts_with = "source-message-confirmations")]
+	source_confidence: Option<SourceConfidence>,
+	/// Monitor reorgs of both chains and report them using metrics. Reorgs that are deeper
+	/// than given number of blocks are logged as errors.
+	#[structopt(long)]
+	reorg_alert_depth: Option<u32>,
+	/// Report what fraction of given number of latest delivered messages has been delivered
+	/// by this relayer, using the `relayer_win_rate` metric and periodic log summaries.
+	#[structopt(long)]
+	competition_analytics_window: Option<MessageNonce>,
+	/// Explicit priority of relay transactions at both chains. Only applied if the chain
+	/// runtime has the signed extension with explicit priority. Ignored otherwise.
+	#[structopt(long)]
+	transaction_priority: Option<TransactionPriority>,
+	/// Only deliver XCM messages that have the top-level instruction with given name (e.g.
+	/// `Transact`). Messages are delivered in order, so the relay stops at the first message
+	/// without this instruction and waits until it is delivered by other relayers.
+	#[structopt(long)]
+	xcm_instruction_filter: Option<XcmInstructionFilter>,
+	/// Number of new source and target blocks that the relay observes after connecting,
+	/// before it starts submitting transactions. This gives the relay time to build the
+	/// complete view of both chains.
+	#[structopt(long, default_value = "0")]
+	warm_up_blocks: u32,
+	/// Number of seconds that the relay waits after (re)connecting to nodes, before it submits
+	/// the first transaction. The relay keeps reading the state of both chains meanwhile.
+	/// Some nodes don't reliably accept transactions right after the connection is opened.
+	#[structopt(long, default_value = "0")]
+	first_submit_delay_secs: u64,
+	/// Number of consecutive relay loop iterations without anything to deliver, after which
+	/// the relay logs its state at info level (or at warn level if there are messages or
+	/// confirmations that it doesn't deliver). Such iterations are only logged at debug level
+	/// if not set.
+	#[structopt(long)]
+	inactivity_log_threshold: Option<u32>,
+	/// Log correlation ids (`<source>-><target>/<lane>/<nonce>`) of messages, delivered and
+	/// confirmed by the relay transactions, and attach them to exported spans. The delivery
+	/// transaction itself has no place for the ids, so they never leave the relay.
+	#[structopt(long)]
+	correlate_messages: bool,
+	/// Expose the category of the most recent error of the lane (`connection`, `rejected` or
+	/// `stalled`) as the `lane_error_state` metric, with one gauge per category.
+	/// The category is reset to `ok` after the successful delivery or confirmation transaction.
+	#[structopt(long)]
+	lane_error_state_metric: bool,
+	/// Read `MessagesReceived` events of blocks with delivery transactions and report the
+	/// dispatch weight, that has not been spent by delivered messages, as the
+	/// `unspent_dispatch_weight` metric and in the run summary.
+	#[structopt(long)]
+	track_dispatch_refunds: bool,
+	/// Only deliver messages while the target runtime spec version belongs to given list of
+	/// versions and ranges (e.g. `1000,1002-1005`). Delivery is deferred with an error
+	/// otherwise, until the version is allowed again or the list is updated using the
+	/// `allow-target-spec-versions` control command. Any version is allowed if not set.
+	#[structopt(long)]
+	allowed_target_spec_versions: Option<SpecVersions>,
+	/// What to do when the dispatch weight of the next message exceeds maximal weight of the
+	/// delivery transaction: `halt`, `warn-only` or `reprobe`. Such message can never be
+	/// delivered and blocks the lane. It can't be skipped, because messages are delivered in
+	/// nonce order. With `reprobe`, the stalled lane is held back instead of halting the relay,
+	/// also when the message exceeds `--max-message-dispatch-weight`.
+	#[structopt(long, default_value = "warn-only", possible_values = OversizedWeightPolicy::VARIANTS)]
+	oversized_weight_policy: OversizedWeightPolicy,
+	/// Interval between two consecutive checks of the message that stalls the lane, with the
+	/// `reprobe` oversized weight policy.
+	#[structopt(long, default_value = "300")]
+	stall_reprobe_interval_secs: u64,
+	/// Maximal acceptable declared dispatch weight (`ref_time` component) of the single
+	/// message. Messages of the lane are delivered in order, so the heavier message can't be
+	/// skipped: messages that precede it are delivered and then the relay is halted.
+	#[structopt(long)]
+	max_message_dispatch_weight: Option<u64>,
+	/// Allow relaying messages if source and target clients are connected to the same chain.
+	/// This only makes sense for testing.
+	#[structopt(long)]
+	allow_same_chain: bool,
+	/// Deliver messages one by one and wait until every message is confirmed before delivering
+	/// the next message. Messages are still delivered and confirmed by regular transactions,
+	/// but the order of relay transactions is reproducible. Only makes sense for testing.
+	#[cfg(feature = "deterministic")]
+	#[structopt(long)]
+	deterministic: bool,
+	/// Print the relay configuration, resolved from the command line arguments and defaults,
+	/// and exit. Signer SURIs and passwords are redacted, unless they are secret references.
+	/// The output is stable, so configurations of two relays may be diffed.
+	#[structopt(long)]
+	print_config_and_exit: bool,
 	#[structopt(flatten)]
 	source: SourceConnectionParams,
 	#[structopt(flatten)]
@@ -50,6 +526,12 @@ pub struct RelayMessagesParams {
 	target_sign: TargetSigningParams,
 	#[structopt(flatten)]
 	prometheus_params: PrometheusParams,
+	#[structopt(flatten)]
+	control_params: ControlParams,
+	#[structopt(flatten)]
+	tracing_params: TracingParams,
+	#[structopt(flatten)]
+	sole_relayer_params: SoleRelayerDetectionParams,
 }
 
 /// Messages range relaying params.
@@ -99,52 +581,429 @@ pub struct RelayMessagesDeliveryConfirmationParams {
 	target: TargetConnectionParams,
 }
 
+/// Time to confirmation estimation params.
+#[derive(StructOpt)]
+pub struct TimeToConfirmParams {
+	/// Nonce of the message.
+	nonce: MessageNonce,
+	/// Hex-encoded lane id of the message. Defaults to `00000000`.
+	#[structopt(long, default_value = "00000000")]
+	lane: HexLaneId,
+	/// Maximal observed time (in seconds) between two consecutive delivery transactions.
+	#[structopt(long, default_value = "60")]
+	max_delivery_interval: u64,
+	/// Maximal observed time (in seconds) since message is delivered and until its delivery
+	/// is confirmed at the source chain.
+	#[structopt(long, default_value = "120")]
+	max_confirmation_delay: u64,
+	#[structopt(flatten)]
+	source: SourceConnectionParams,
+	#[structopt(flatten)]
+	target: TargetConnectionParams,
+}
+
+/// Outbound message decoding params.
+#[derive(StructOpt)]
+pub struct DecodeMessageParams {
+	/// Nonce of the message.
+	nonce: MessageNonce,
+	/// Hex-encoded lane id of the message. Defaults to `00000000`.
+	#[structopt(long, default_value = "00000000")]
+	lane: HexLaneId,
+	#[structopt(flatten)]
+	source: SourceConnectionParams,
+}
+
+/// Pending messages listing params.
+#[derive(StructOpt)]
+pub struct ListPendingParams {
+	/// Hex-encoded lane id of messages. Defaults to `00000000`.
+	#[structopt(long, default_value = "00000000")]
+	lane: HexLaneId,
+	/// Maximal number of messages to list. All pending messages are listed if not set.
+	#[structopt(long)]
+	limit: Option<MessageNonce>,
+	/// Print messages as JSON instead of table.
+	#[structopt(long)]
+	json: bool,
+	#[structopt(flatten)]
+	source: SourceConnectionParams,
+	#[structopt(flatten)]
+	target: TargetConnectionParams,
+}
+
+/// Messages proof verification params.
+#[derive(StructOpt)]
+pub struct VerifyProofParams {
+	/// Number of the source chain header that we will use to prepare a messages proof.
+	#[structopt(long)]
+	at_source_block: u128,
+	/// Hex-encoded lane id of messages. Defaults to `00000000`.
+	#[structopt(long, default_value = "00000000")]
+	lane: HexLaneId,
+	/// Nonce (inclusive) of the first message to prove.
+	#[structopt(long)]
+	messages_start: MessageNonce,
+	/// Nonce (inclusive) of the last message to prove.
+	#[structopt(long)]
+	messages_end: MessageNonce,
+	/// Whether the outbound lane state proof should be included into proof.
+	#[structopt(long)]
+	outbound_state_proof_required: bool,
+	#[structopt(flatten)]
+	source: SourceConnectionParams,
+}
+
+/// Delivery decision replay params. Both chains state is read at past blocks, so both nodes
+/// must be archive nodes.
+#[derive(StructOpt)]
+pub struct ReplayDecisionParams {
+	/// Number of the source chain header, at which the decision is replayed.
+	#[structopt(long)]
+	at_source_block: u128,
+	/// Hex-encoded lane id of messages. Defaults to `00000000`.
+	#[structopt(long, default_value = "00000000")]
+	lane: HexLaneId,
+	/// Hex-encoded id of the relayer account at the target chain. Its balance is only checked
+	/// if set.
+	#[structopt(long)]
+	relayer: Option<String>,
+	/// The same as the `relay-messages` option.
+	#[structopt(long)]
+	balance_buffer: Option<u128>,
+	/// The same as the `relay-messages` option.
+	#[structopt(long)]
+	max_fee_multiplier: Option<f64>,
+	#[structopt(flatten)]
+	source: SourceConnectionParams,
+	#[structopt(flatten)]
+	target: TargetConnectionParams,
+}
+
 /// Trait used for relaying messages between 2 chains.
 #[async_trait]
 pub trait MessagesRelayer: MessagesCliBridge
 where
-	Self::Source: ChainWithTransactions + ChainWithRuntimeVersion,
+	Self::Source: ChainWithTransactions + ChainWithRuntimeVersion + ChainWithBalances,
+	Self::Target: ChainWithBalances,
 	AccountIdOf<Self::Source>: From<<AccountKeyPairOf<Self::Source> as Pair>::Public>,
 	AccountIdOf<Self::Target>: From<<AccountKeyPairOf<Self::Target> as Pair>::Public>,
 	BalanceOf<Self::Source>: TryFrom<BalanceOf<Self::Target>>,
 {
 	/// Start relaying messages.
 	async fn relay_messages(data: RelayMessagesParams) -> anyhow::Result<()> {
+		if data.print_config_and_exit {
+			println!("{data:#?}");
+			return Ok(())
+		}
+		let oversized_weight_policy = data
+			.oversized_weight_policy
+			.into_policy(Duration::from_secs(data.stall_reprobe_interval_secs));
+		data.tracing_params.start_exporter()?;
+		if data.source_confidence == Some(SourceConfidence::Best) {
+			log::warn!(
+				target: "bridge",
+				"{} messages can only be proven using finalized headers. Ignoring \
+				`--source-confidence best` and delivering messages from finalized headers",
+				Self::Source::NAME,
+			);
+		}
+		let source_message_confirmations = data
+			.source_confidence
+			.map_or(data.source_message_confirmations, |source_confidence| {
+				source_confidence.source_message_confirmations()
+			});
+
 		let source_client = data.source.into_client::<Self::Source>().await?;
-		let source_sign = data.source_sign.to_keypair::<Self::Source>()?;
+		let source_sign = data.source_sign.to_keypair::<Self::Source>().await?;
+		let source_additional_signers =
+			data.source_sign.additional_keypairs::<Self::Source>().await?;
 		let source_transactions_mortality = data.source_sign.transactions_mortality()?;
 		let target_client = data.target.into_client::<Self::Target>().await?;
-		let target_sign = data.target_sign.to_keypair::<Self::Target>()?;
+		ensure_different_chains::<Self::Source, Self::Target>(
+			source_client.genesis_hash().as_ref(),
+			target_client.genesis_hash().as_ref(),
+			data.allow_same_chain,
+		)?;
+		let target_sign = data.target_sign.to_keypair::<Self::Target>().await?;
 		let target_transactions_mortality = data.target_sign.transactions_mortality()?;
+		let mut target_balance_guard = data
+			.balance_buffer
+			.map(|buffer| BalanceGuard::new::<Self::Target>(&target_sign.public().into(), buffer));
+		let lane_id: LaneId = data.lane.into();
+		let mut source_confirmation_guard = data
+			.confirmation_balance_buffer
+			.map(|buffer| {
+				let signers = std::iter::once(source_sign.clone())
+					.chain(source_additional_signers.iter().cloned())
+					.collect::<Vec<_>>();
+				ConfirmationBalanceGuard::new::<Self::Source>(
+					&signers,
+					buffer,
+					data.pause_delivery_on_unaffordable_confirmation,
+					Some(&metrics_prefix::<MessageLaneAdapter<Self::MessagesLane>>(&lane_id)),
+				)
+			})
+			.transpose()?;
+		if data.preserve_existential_deposit {
+			if let Some(balance_guard) = target_balance_guard.take() {
+				let existential_deposit =
+					read_existential_deposit::<Self::Target>(&target_client).await?;
+				target_balance_guard =
+					Some(balance_guard.with_existential_deposit(existential_deposit));
+			}
+			if let Some(confirmation_guard) = source_confirmation_guard.take() {
+				let existential_deposit =
+					read_existential_deposit::<Self::Source>(&source_client).await?;
+				source_confirmation_guard =
+					Some(confirmation_guard.with_existential_deposit(existential_deposit));
+			}
+		}
+		let source_reward_eligibility_guard = if data.check_reward_eligibility {
+			let relayers_pallet_name =
+				Self::Target::WITH_CHAIN_RELAYERS_PALLET_NAME.ok_or_else(|| {
+					anyhow::format_err!(
+						"{} has no bridge relayers pallet to check reward eligibility at",
+						Self::Source::NAME,
+					)
+				})?;
+			Some(RewardEligibilityGuard::new(
+				relayers_pallet_name,
+				data.skip_ineligible_confirmations,
+			))
+		} else {
+			None
+		};
+		let target_fee_multiplier_guard = data
+			.max_fee_multiplier
+			.map(|max_fee_multiplier| {
+				FeeMultiplierGuard::new(
+					Multiplier::from_float(max_fee_multiplier),
+					Some(&metrics_prefix::<MessageLaneAdapter<Self::MessagesLane>>(&lane_id)),
+				)
+			})
+			.transpose()?;
+		// both guards are serving the same lane, so their metrics are prefixed with the chain side
+		let rate_limit_quota_guard = |storage, side: &str| {
+			RateLimitQuotaGuard::new(
+				storage,
+				Some(&format!(
+					"{}_{}",
+					metrics_prefix::<MessageLaneAdapter<Self::MessagesLane>>(&lane_id),
+					side,
+				)),
+			)
+		};
+		let source_rate_limit_quota_guard = data
+			.source_rate_limit_quota_storage
+			.map(|storage| rate_limit_quota_guard(storage, "source"))
+			.transpose()?;
+		let target_rate_limit_quota_guard = data
+			.target_rate_limit_quota_storage
+			.map(|storage| rate_limit_quota_guard(storage, "target"))
+			.transpose()?;
+		let messages_proof_format = data.proof_format.select(&target_client).await;
+		let delivery_proof_format = data.proof_format.select(&source_client).await;
+		let metrics_params = data.prometheus_params.into_metrics_params()?;
+		let global_pause_guard = if data.source_global_pause_storage.is_some() ||
+			data.target_global_pause_storage.is_some()
+		{
+			let global_pause_guard = GlobalPauseGuard::new(
+				Duration::from_secs(data.global_pause_poll_interval_secs),
+				Some(&metrics_prefix::<MessageLaneAdapter<Self::MessagesLane>>(&lane_id)),
+			)?;
+			global_pause_guard.register(&metrics_params.registry)?;
+			if let Some(storage) = data.source_global_pause_storage {
+				global_pause_guard.observe(source_client.clone(), storage).await?;
+			}
+			if let Some(storage) = data.target_global_pause_storage {
+				global_pause_guard.observe(target_client.clone(), storage).await?;
+			}
+			Some(global_pause_guard)
+		} else {
+			None
+		};
+		let target_synced_header_age_guard = SyncedHeaderAgeGuard::new(
+			data.max_synced_header_age.map(Duration::from_secs),
+			data.pause_delivery_on_stale_synced_header,
+			Some(&metrics_prefix::<MessageLaneAdapter<Self::MessagesLane>>(&lane_id)),
+		)?;
+		let target_header_progress_guard = HeaderProgressGuard::new(
+			data.no_header_relay_policy,
+			Duration::from_secs(data.no_header_relay_timeout),
+			if data.no_header_relay_policy == NoHeaderRelayPolicy::AutoRelay {
+				Self::start_source_to_target_headers_relay(
+					source_client.clone(),
+					target_client.clone(),
+					TransactionParams {
+						signer: target_sign.clone(),
+						mortality: target_transactions_mortality,
+						priority: transaction_priority::<Self::Target>(data.transaction_priority),
+					},
+				)
+			} else {
+				None
+			},
+		)?;
+		let target_runtime_upgrade_guard = data
+			.runtime_upgrade_settle_period
+			.map(|settle_period| RuntimeUpgradeGuard::new(Duration::from_secs(settle_period)));
+		let target_block_weight_headroom = if data.respect_block_weight_headroom {
+			Some(BlockWeightHeadroom::new(&target_client).await?)
+		} else {
+			None
+		};
+		if let (true, Some(registration_lease)) = (data.auto_register, data.registration_lease) {
+			let relayers_pallet_name =
+				Self::Source::WITH_CHAIN_RELAYERS_PALLET_NAME.ok_or_else(|| {
+					anyhow::format_err!(
+						"{} has no bridge relayers pallet to register at",
+						Self::Target::NAME,
+					)
+				})?;
+			ensure_relayer_registered::<Self::Target>(
+				&target_client,
+				relayers_pallet_name,
+				TransactionParams {
+					signer: target_sign.clone(),
+					mortality: target_transactions_mortality,
+					priority: transaction_priority::<Self::Target>(data.transaction_priority),
+				},
+				registration_lease,
+				Self::encode_register_relayer,
+			)
+			.await?;
+		}
+		let min_batch = data
+			.min_batch_size
+			.map(|size| MinBatch::new(size, Duration::from_secs(data.min_batch_timeout)));
+		let pending_messages_gate = data.min_pending_messages.map(|min_pending| {
+			PendingMessagesGate::new(
+				min_pending,
+				data.min_pending_messages_timeout.map(Duration::from_secs),
+			)
+		});
+		let retry_budget = match (data.max_transient_retries, data.max_deterministic_retries) {
+			(None, None) => None,
+			(max_transient_retries, max_deterministic_retries) => Some(RetryBudget::new(
+				max_transient_retries.unwrap_or(u32::MAX),
+				max_deterministic_retries.unwrap_or(u32::MAX),
+			)),
+		};
+		let batch_tuner = data.auto_tune_batch_size.then(|| {
+			BatchAutoTuner::new(
+				data.auto_tune_min_batch_size,
+				data.auto_tune_max_batch_size.unwrap_or(MessageNonce::MAX),
+			)
+		});
+
+		#[cfg(feature = "deterministic")]
+		let deterministic = data.deterministic;
+		#[cfg(not(feature = "deterministic"))]
+		let deterministic = false;
 
-		crate::messages_lane::run::<Self::MessagesLane, _, _>(MessagesRelayParams {
+		let control = data.control_params.clone().into_relay_control()?;
+		control.set_allowed_target_spec_versions(data.allowed_target_spec_versions);
+		let result = crate::messages_lane::run::<Self::MessagesLane, _, _>(MessagesRelayParams {
 			source_client,
 			source_transaction_params: TransactionParams {
 				signer: source_sign,
 				mortality: source_transactions_mortality,
+				priority: transaction_priority::<Self::Source>(data.transaction_priority),
 			},
+			source_additional_signers,
+			source_proxy: Self::source_proxy(data.source_sign.proxy())?,
 			target_client,
 			target_transaction_params: TransactionParams {
 				signer: target_sign,
 				mortality: target_transactions_mortality,
+				priority: transaction_priority::<Self::Target>(data.transaction_priority),
 			},
+			target_proxy: Self::target_proxy(data.target_sign.proxy())?,
 			source_to_target_headers_relay: None,
 			target_to_source_headers_relay: None,
-			lane_id: data.lane.into(),
+			lane_id,
 			limits: Self::maybe_messages_limits(),
-			metrics_params: data.prometheus_params.into_metrics_params()?,
+			metrics_params,
+			control: control.clone(),
+			sole_relayer: data.sole_relayer_params.into_params(),
+			max_fees_per_hour: data.max_fees_per_hour,
+			fee_safety_margin_percent: data.fee_safety_margin_percent,
+			fee_estimation_fallback: data.fee_estimation_fallback,
+			pallet_version_mismatch: data.pallet_version_mismatch,
+			backfill: data.max_backfill_nonces.map(|max_nonces| BackfillParams {
+				max_nonces,
+				policy: data.backfill_policy,
+				acknowledged: data.acknowledge_backfill,
+			}),
+			max_nonces_ahead: data.max_nonces_ahead,
+			log_backpressure: data.log_backpressure,
+			target_sync_confirmations: data.target_sync_confirmations,
+			confirmation_lag: data.confirmation_lag_secs.map(Duration::from_secs),
+			max_confirmation_latency: data.max_confirmation_latency_secs.map(Duration::from_secs),
+			source_message_confirmations,
+			reorg_alert_depth: data.reorg_alert_depth,
+			competition_analytics_window: data.competition_analytics_window,
+			xcm_instruction_filter: data.xcm_instruction_filter,
+			warm_up_blocks: data.warm_up_blocks,
+			first_submit_delay: Duration::from_secs(data.first_submit_delay_secs),
+			inactivity_log_threshold: data.inactivity_log_threshold,
+			correlate_messages: data.correlate_messages,
+			oversized_weight_policy,
+			max_message_dispatch_weight: data
+				.max_message_dispatch_weight
+				.map(|ref_time| Weight::from_parts(ref_time, u64::MAX)),
+			companion_lane: None,
+			target_balance_guard,
+			source_confirmation_guard,
+			source_reward_eligibility_guard,
+			source_confirmations_batcher: None,
+			source_rate_limit_quota_guard,
+			target_fee_multiplier_guard,
+			target_runtime_upgrade_guard,
+			target_synced_header_age_guard: Some(target_synced_header_age_guard),
+			target_header_progress_guard: Some(target_header_progress_guard),
+			target_block_weight_headroom,
+			target_rate_limit_quota_guard,
+			messages_proof_format,
+			delivery_proof_format,
+			throughput_budget: Default::default(),
+			global_pause_guard: global_pause_guard.clone(),
+			min_batch,
+			pending_messages_gate,
+			serialize_races: data.serialize_races,
+			log_level: None,
+			lane_error_state_metric: data.lane_error_state_metric,
+			track_dispatch_refunds: data.track_dispatch_refunds,
+			paused_lane_poll_interval: data.paused_lane_poll_interval.map(Duration::from_secs),
+			max_proof_generation_time: data.max_proof_gen_secs.map(Duration::from_secs),
+			message_discovery: data.message_discovery,
+			message_grace_period_blocks: data.message_grace_period_blocks,
+			confirmations_priority_threshold: data.confirmations_priority_threshold,
+			retry_budget,
+			batch_tuner,
+			deterministic,
+			max_runtime: data.control_params.max_runtime(),
+			scale_event_sink: data.control_params.scale_event_sink()?,
 		})
 		.await
-		.map_err(|e| anyhow::format_err!("{}", e))
+		.map_err(|e| anyhow::format_err!("{}", e));
+
+		if let Some(global_pause_guard) = global_pause_guard {
+			global_pause_guard.stop().await;
+		}
+		data.control_params.write_exit_summary(&control, &result);
+		data.control_params.remove_ready_file();
+		result
 	}
 
 	/// Relay a consequitive range of messages.
 	async fn relay_messages_range(data: RelayMessagesRangeParams) -> anyhow::Result<()> {
 		let source_client = data.source.into_client::<Self::Source>().await?;
 		let target_client = data.target.into_client::<Self::Target>().await?;
-		let source_sign = data.source_sign.to_keypair::<Self::Source>()?;
+		let source_sign = data.source_sign.to_keypair::<Self::Source>().await?;
 		let source_transactions_mortality = data.source_sign.transactions_mortality()?;
-		let target_sign = data.target_sign.to_keypair::<Self::Target>()?;
+		let target_sign = data.target_sign.to_keypair::<Self::Target>().await?;
 		let target_transactions_mortality = data.target_sign.transactions_mortality()?;
 
 		let at_source_block = source_client
@@ -164,8 +1023,16 @@ where
 		crate::messages_lane::relay_messages_range::<Self::MessagesLane>(
 			source_client,
 			target_client,
-			TransactionParams { signer: source_sign, mortality: source_transactions_mortality },
-			TransactionParams { signer: target_sign, mortality: target_transactions_mortality },
+			TransactionParams {
+				signer: source_sign,
+				mortality: source_transactions_mortality,
+				priority: None,
+			},
+			TransactionParams {
+				signer: target_sign,
+				mortality: target_transactions_mortality,
+				priority: None,
+			},
 			at_source_block,
 			data.lane.into(),
 			data.messages_start..=data.messages_end,
@@ -180,7 +1047,7 @@ where
 	) -> anyhow::Result<()> {
 		let source_client = data.source.into_client::<Self::Source>().await?;
 		let target_client = data.target.into_client::<Self::Target>().await?;
-		let source_sign = data.source_sign.to_keypair::<Self::Source>()?;
+		let source_sign = data.source_sign.to_keypair::<Self::Source>().await?;
 		let source_transactions_mortality = data.source_sign.transactions_mortality()?;
 
 		let at_target_block = target_client
@@ -200,10 +1067,179 @@ where
 		crate::messages_lane::relay_messages_delivery_confirmation::<Self::MessagesLane>(
 			source_client,
 			target_client,
-			TransactionParams { signer: source_sign, mortality: source_transactions_mortality },
+			TransactionParams {
+				signer: source_sign,
+				mortality: source_transactions_mortality,
+				priority: None,
+			},
 			at_target_block,
 			data.lane.into(),
 		)
 		.await
 	}
+
+	/// Estimate time until given message is delivered and confirmed.
+	async fn time_to_confirm(data: TimeToConfirmParams) -> anyhow::Result<()> {
+		let source_client = data.source.into_client::<Self::Source>().await?;
+		let target_client = data.target.into_client::<Self::Target>().await?;
+		let lane_nonces = read_lane_nonces::<Self::MessagesLane>(
+			&source_client,
+			&target_client,
+			data.lane.into(),
+		)
+		.await?;
+
+		// at most one delivery transaction per target block and at least one block at the
+		// source chain to confirm delivery
+		let timings = RelayTimings {
+			max_messages_in_delivery_transaction: Self::maybe_messages_limits()
+				.map(|limits| limits.max_messages_in_single_batch)
+				.unwrap_or(Self::Source::MAX_UNCONFIRMED_MESSAGES_IN_CONFIRMATION_TX),
+			delivery_interval: Self::Target::AVERAGE_BLOCK_INTERVAL..=
+				Duration::from_secs(data.max_delivery_interval),
+			confirmation_delay: Self::Source::AVERAGE_BLOCK_INTERVAL..=
+				Duration::from_secs(data.max_confirmation_delay),
+		};
+
+		let time_to_confirm = estimate_time_to_confirm(data.nonce, lane_nonces, &timings)?;
+		println!("{time_to_confirm}");
+		Ok(())
+	}
+
+	/// Read outbound message from the source chain storage and display it.
+	async fn decode_message(data: DecodeMessageParams) -> anyhow::Result<()> {
+		let source_client = data.source.into_client::<Self::Source>().await?;
+		let message = read_outbound_message::<Self::MessagesLane>(
+			&source_client,
+			data.lane.into(),
+			data.nonce,
+		)
+		.await?;
+
+		// the messages pallet doesn't define any payload format, so we can't decode it
+		log::warn!(
+			target: "bridge",
+			"Format of {} -> {} message payload is unknown. Displaying raw payload bytes",
+			Self::Source::NAME,
+			Self::Target::NAME,
+		);
+		println!("{message}");
+		Ok(())
+	}
+
+	/// List messages that are generated at the source chain, but not yet delivered to the
+	/// target chain.
+	async fn list_pending(data: ListPendingParams) -> anyhow::Result<()> {
+		let source_client = data.source.into_client::<Self::Source>().await?;
+		let target_client = data.target.into_client::<Self::Target>().await?;
+		let pending = read_pending_messages::<Self::MessagesLane>(
+			&source_client,
+			&target_client,
+			data.lane.into(),
+			data.limit,
+		)
+		.await?;
+
+		if data.json {
+			println!("{}", serde_json::to_string_pretty(&pending.to_json())?);
+		} else {
+			println!("{pending}");
+		}
+		Ok(())
+	}
+
+	/// Generate messages proof and verify it against the source header state root.
+	async fn verify_proof(data: VerifyProofParams) -> anyhow::Result<()> {
+		let source_client = data.source.into_client::<Self::Source>().await?;
+		let at_source_block = source_client
+			.header_by_number(data.at_source_block.unique_saturated_into())
+			.await
+			.map_err(|e| {
+				anyhow::format_err!(
+					"Failed to read {} header with number {}: {e:?}",
+					Self::Source::NAME,
+					data.at_source_block,
+				)
+			})?;
+
+		let verification = verify_messages_proof::<Self::MessagesLane>(
+			&source_client,
+			at_source_block,
+			data.lane.into(),
+			data.messages_start..=data.messages_end,
+			data.outbound_state_proof_required,
+		)
+		.await?;
+		println!("{verification}");
+		Ok(())
+	}
+
+	/// Replay the delivery decision at given source block and display why messages would or
+	/// wouldn't have been delivered.
+	async fn replay_decision(data: ReplayDecisionParams) -> anyhow::Result<()> {
+		let source_client = data.source.into_client::<Self::Source>().await?;
+		let target_client = data.target.into_client::<Self::Target>().await?;
+		let relayer = data
+			.relayer
+			.map(|relayer| {
+				let encoded = hex::decode(relayer.trim_start_matches("0x"))?;
+				AccountIdOf::<Self::Target>::decode(&mut &encoded[..]).map_err(|e| {
+					anyhow::format_err!("Invalid {} relayer account id: {e:?}", Self::Target::NAME)
+				})
+			})
+			.transpose()?;
+		let config = ReplayConfig {
+			max_unrewarded_relayer_entries_at_target:
+				Self::Source::MAX_UNREWARDED_RELAYERS_IN_CONFIRMATION_TX,
+			max_unconfirmed_nonces_at_target:
+				Self::Source::MAX_UNCONFIRMED_MESSAGES_IN_CONFIRMATION_TX,
+			// the relay is using half of the limits, selected by the runtime
+			max_messages_in_single_batch: Self::maybe_messages_limits()
+				.map(|limits| limits.max_messages_in_single_batch / 2)
+				.unwrap_or(Self::Source::MAX_UNCONFIRMED_MESSAGES_IN_CONFIRMATION_TX),
+			max_fee_multiplier: data.max_fee_multiplier.map(Multiplier::from_float),
+			balance_buffer: data.balance_buffer,
+		};
+
+		let replay = replay_delivery_decision::<Self::MessagesLane>(
+			&source_client,
+			&target_client,
+			data.lane.into(),
+			data.at_source_block.unique_saturated_into(),
+			relayer,
+			&config,
+		)
+		.await?;
+		println!("{replay}");
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use relay_substrate_client::test_chain::{TestChain, TestParachain};
+
+	#[test]
+	fn relay_refuses_to_start_if_chains_are_the_same() {
+		let genesis_hash = [42u8; 32];
+		assert!(ensure_different_chains::<TestChain, TestParachain>(
+			&genesis_hash,
+			&genesis_hash,
+			false
+		)
+		.is_err());
+		assert!(ensure_different_chains::<TestChain, TestParachain>(
+			&genesis_hash,
+			&genesis_hash,
+			true
+		)
+		.is_ok());
+		assert!(ensure_different_chains::<TestChain, TestParachain>(
+			&genesis_hash,
+			&[43u8; 32],
+			false
+		)
+		.is_ok());
+	}
 }
@@ -90,8 +90,9 @@ where
 		);
 
 		let target_transaction_params = TransactionParams {
-			signer: data.target_sign.to_keypair::<Self::Target>()?,
+			signer: data.target_sign.to_keypair::<Self::Target>().await?,
 			mortality: data.target_sign.target_transactions_mortality,
+			priority: None,
 		};
 		let target_chain_client = data.target.into_client::<Self::Target>().await?;
 		let target_client = ParachainsTarget::<Self::ParachainFinality, _, _>::new(
@@ -130,8 +131,9 @@ where
 		);
 
 		let target_transaction_params = TransactionParams {
-			signer: data.target_sign.to_keypair::<Self::Target>()?,
+			signer: data.target_sign.to_keypair::<Self::Target>().await?,
 			mortality: data.target_sign.target_transactions_mortality,
+			priority: None,
 		};
 		let target_chain_client = data.target.into_client::<Self::Target>().await?;
 		let target_client = ParachainsTarget::<Self::ParachainFinality, _, _>::new(
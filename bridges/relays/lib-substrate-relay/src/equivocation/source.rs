@@ -100,6 +100,7 @@ impl<P: SubstrateEquivocationDetectionPipeline, SourceClnt: Client<P::SourceChai
 				.await?;
 
 		let mortality = self.transaction_params.mortality;
+		let priority = self.transaction_params.priority;
 		let call = P::ReportEquivocationCallBuilder::build_report_equivocation_call(
 			equivocation,
 			key_owner_proof,
@@ -109,7 +110,8 @@ impl<P: SubstrateEquivocationDetectionPipeline, SourceClnt: Client<P::SourceChai
 				&self.transaction_params.signer,
 				move |best_block_id, transaction_nonce| {
 					Ok(UnsignedTransaction::new(call.into(), transaction_nonce)
-						.era(TransactionEra::new(best_block_id, mortality)))
+						.era(TransactionEra::new(best_block_id, mortality))
+						.priority(priority))
 				},
 			)
 			.await
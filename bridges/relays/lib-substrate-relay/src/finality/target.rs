@@ -158,6 +158,7 @@ where
 
 		// now we may submit optimized finality proof
 		let mortality = self.transaction_params.mortality;
+		let priority = self.transaction_params.priority;
 		let call = P::SubmitFinalityProofCallBuilder::build_submit_finality_proof_call(
 			header,
 			proof,
@@ -169,7 +170,8 @@ where
 				&self.transaction_params.signer,
 				move |best_block_id, transaction_nonce| {
 					Ok(UnsignedTransaction::new(call.into(), transaction_nonce)
-						.era(TransactionEra::new(best_block_id, mortality)))
+						.era(TransactionEra::new(best_block_id, mortality))
+						.priority(priority))
 				},
 			)
 			.await
@@ -18,9 +18,20 @@
 
 #![warn(missing_docs)]
 
-use relay_substrate_client::{Chain, ChainWithUtilityPallet, UtilityPallet};
+use frame_support::traits::InstanceFilter;
+use relay_substrate_client::{
+	AccountIdOf, BatchCallKind, CallOf, Chain, ChainWithProxyPallet, ChainWithUtilityPallet,
+	Error as SubstrateError, UtilityPallet,
+};
+use sp_runtime::transaction_validity::TransactionPriority;
 
-use std::marker::PhantomData;
+use std::{
+	marker::PhantomData,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+};
 
 // to avoid `finality_relay` dependency in other crates
 pub use finality_relay::HeadersToRelay;
@@ -30,12 +41,29 @@ pub mod equivocation;
 pub mod error;
 pub mod finality;
 pub mod finality_base;
+pub mod messages_backfill;
+pub mod messages_competition;
+pub mod messages_confirmations_batch;
+pub mod messages_decode;
+pub mod messages_discovery;
+pub mod messages_dispatch_refunds;
+pub mod messages_global_pause;
+pub mod messages_header_progress;
 pub mod messages_lane;
+pub mod messages_latency;
 pub mod messages_metrics;
+pub mod messages_pallet;
+pub mod messages_replay;
+pub mod messages_sole_relayer;
 pub mod messages_source;
 pub mod messages_target;
+pub mod messages_time_to_confirm;
+pub mod messages_verify_proof;
 pub mod on_demand;
 pub mod parachains;
+pub mod relayer_registration;
+pub mod reorg_monitor;
+pub mod scale_events;
 
 /// Transaction creation parameters.
 #[derive(Clone, Debug)]
@@ -44,6 +72,89 @@ pub struct TransactionParams<TS> {
 	pub signer: TS,
 	/// Transactions mortality.
 	pub mortality: Option<u32>,
+	/// Explicit transactions priority. Ignored by chains without the priority signed extension.
+	pub priority: Option<TransactionPriority>,
+}
+
+/// Proxy, used to dispatch relay calls on behalf of the proxied account.
+#[derive(Clone)]
+pub struct ProxyParams<C: Chain> {
+	/// The proxied account. Relay calls are dispatched on behalf of this account.
+	pub real: AccountIdOf<C>,
+	/// Description of the proxy.
+	description: String,
+	/// Wraps relay call into the `proxy.proxy` call.
+	#[allow(clippy::type_complexity)]
+	wrap: Arc<dyn Fn(CallOf<C>) -> Result<CallOf<C>, SubstrateError> + Send + Sync>,
+}
+
+impl<C: Chain> ProxyParams<C> {
+	/// Create proxy params for the chain with proxy pallet.
+	pub fn new(real: AccountIdOf<C>, proxy_type: C::ProxyType) -> Self
+	where
+		C: ChainWithProxyPallet,
+		C::ProxyType: Send + Sync + 'static,
+	{
+		let description = format!("{:?} proxy of {:?}", proxy_type, real);
+		let real_clone = real.clone();
+		let wrap = move |call: CallOf<C>| {
+			if !proxy_type.filter(&call) {
+				return Err(SubstrateError::Custom(format!(
+					"{} proxy type {:?} doesn't permit dispatching {:?}",
+					C::NAME,
+					proxy_type,
+					call,
+				)))
+			}
+			Ok(C::build_proxy_call(real_clone.clone(), proxy_type.clone(), call))
+		};
+		ProxyParams { real, description, wrap: Arc::new(wrap) }
+	}
+
+	/// Wrap relay call into the `proxy.proxy` call. Fails if proxy type doesn't permit the call.
+	pub fn wrap(&self, call: CallOf<C>) -> Result<CallOf<C>, SubstrateError> {
+		(self.wrap)(call)
+	}
+}
+
+impl<C: Chain> std::fmt::Debug for ProxyParams<C> {
+	fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+		fmt.write_str(&self.description)
+	}
+}
+
+/// Set of items (e.g. transaction signers), that are selected in turns. All clones are
+/// sharing the same selection state.
+#[derive(Clone, Debug)]
+pub struct RoundRobin<T> {
+	items: Arc<Vec<T>>,
+	next: Arc<AtomicUsize>,
+}
+
+impl<T: Clone> RoundRobin<T> {
+	/// Create new set, starting with the `first` item.
+	pub fn new(first: T, others: Vec<T>) -> Self {
+		let mut items = Vec::with_capacity(1 + others.len());
+		items.push(first);
+		items.extend(others);
+		RoundRobin { items: Arc::new(items), next: Arc::new(AtomicUsize::new(0)) }
+	}
+
+	/// Returns the first item of the set.
+	pub fn first(&self) -> &T {
+		&self.items[0]
+	}
+
+	/// Returns all items of the set.
+	pub fn items(&self) -> &[T] {
+		&self.items
+	}
+
+	/// Select next item.
+	pub fn select(&self) -> T {
+		let index = self.next.fetch_add(1, Ordering::Relaxed) % self.items.len();
+		self.items[index].clone()
+	}
 }
 
 /// Tagged relay account, which balance may be exposed as metrics by the relay.
@@ -80,7 +191,12 @@ impl<AccountId> TaggedAccount<AccountId> {
 /// Batch call builder.
 pub trait BatchCallBuilder<Call>: Clone + Send + Sync {
 	/// Create batch call from given calls vector.
-	fn build_batch_call(&self, _calls: Vec<Call>) -> Call;
+	fn build_batch_call(&self, calls: Vec<Call>) -> Call {
+		self.build_batch_call_of_kind(BatchCallKind::BatchAll, calls)
+	}
+
+	/// Create batch call of given kind from given calls vector.
+	fn build_batch_call_of_kind(&self, _kind: BatchCallKind, _calls: Vec<Call>) -> Call;
 }
 
 /// Batch call builder constructor.
@@ -99,8 +215,8 @@ impl<C: Chain> BatchCallBuilder<C::Call> for UtilityPalletBatchCallBuilder<C>
 where
 	C: ChainWithUtilityPallet,
 {
-	fn build_batch_call(&self, calls: Vec<C::Call>) -> C::Call {
-		C::UtilityPallet::build_batch_call(calls)
+	fn build_batch_call_of_kind(&self, kind: BatchCallKind, calls: Vec<C::Call>) -> C::Call {
+		C::UtilityPallet::build_batch_call_of_kind(kind, calls)
 	}
 }
 
@@ -126,7 +242,26 @@ impl<Call> BatchCallBuilderConstructor<Call> for () {
 // Dummy `BatchCallBuilder` implementation that must never be used outside
 // of the `impl BatchCallBuilderConstructor for ()` code.
 impl<Call> BatchCallBuilder<Call> for () {
-	fn build_batch_call(&self, _calls: Vec<Call>) -> Call {
+	fn build_batch_call_of_kind(&self, _kind: BatchCallKind, _calls: Vec<Call>) -> Call {
 		unreachable!("never called, because ()::new_builder() returns None; qed")
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn round_robin_selects_items_in_turns() {
+		let items = RoundRobin::new(1, vec![2, 3]);
+		let clone = items.clone();
+		assert_eq!(items.select(), 1);
+		assert_eq!(clone.select(), 2);
+		assert_eq!(items.select(), 3);
+		assert_eq!(clone.select(), 1);
+
+		let single = RoundRobin::new(1, vec![]);
+		assert_eq!(single.select(), 1);
+		assert_eq!(single.select(), 1);
+	}
+}
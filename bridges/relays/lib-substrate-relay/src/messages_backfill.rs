@@ -0,0 +1,209 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Guard against backfilling too many messages at startup.
+//!
+//! If the relay has been stopped for a long time, it starts by delivering all messages that
+//! have been sent in the meantime, paying fees for all of them. The relay compares the backlog
+//! of the lane with the configured cap at startup and refuses to start, unless the operator
+//! has acknowledged the backfill.
+//!
+//! Messages are delivered in order, so the relay can't skip old messages to start from some
+//! recent point. Instead, the capped backfill delivers at most the cap of oldest messages and
+//! then pauses the relay.
+
+use crate::{messages_lane::SubstrateMessageLane, messages_sole_relayer::read_inbound_lane_data};
+
+use bp_messages::{
+	storage_keys::outbound_lane_data_key, ChainWithMessages as _, LaneId, MessageNonce,
+	OutboundLaneData,
+};
+use relay_substrate_client::{Chain, Client};
+use relay_utils::control::RelayControl;
+use std::time::Duration;
+use strum::{EnumString, VariantNames};
+
+/// Interval between two consecutive checks of the capped backfill progress.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What relayer does when the backlog of the lane exceeds the backfill cap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, VariantNames)]
+#[strum(serialize_all = "kebab_case")]
+pub enum BackfillPolicy {
+	/// Refuse to start the relay, unless the backfill is acknowledged.
+	Refuse,
+	/// Deliver at most the cap of messages and pause the relay. It shall be resumed using the
+	/// control socket.
+	Cap,
+}
+
+/// Backfill guard parameters.
+#[derive(Debug, Clone, Copy)]
+pub struct BackfillParams {
+	/// Maximal number of undelivered messages that the relay backfills without confirmation.
+	pub max_nonces: MessageNonce,
+	/// What to do when the backlog exceeds `max_nonces`.
+	pub policy: BackfillPolicy,
+	/// If true, the operator has acknowledged that the whole backlog is delivered.
+	pub acknowledged: bool,
+}
+
+/// Ensure that the backlog of the lane doesn't exceed the backfill cap.
+///
+/// If the backlog exceeds the cap and the capped backfill is selected, starts background task
+/// that pauses the relay once the cap of messages is delivered.
+pub async fn ensure_backfill_depth<P: SubstrateMessageLane>(
+	source_client: &impl Client<P::SourceChain>,
+	target_client: impl Client<P::TargetChain>,
+	lane_id: LaneId,
+	params: BackfillParams,
+	control: RelayControl,
+) -> anyhow::Result<()> {
+	let source_at = source_client.best_finalized_header_hash().await?;
+	let outbound_lane_data: OutboundLaneData = source_client
+		.storage_value(
+			source_at,
+			outbound_lane_data_key(P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME, &lane_id),
+		)
+		.await?
+		.unwrap_or_default();
+	let latest_delivered_nonce = read_inbound_lane_data::<P>(&target_client, lane_id)
+		.await?
+		.last_delivered_nonce();
+
+	let pause_at = match check_backlog(
+		P::SourceChain::NAME,
+		P::TargetChain::NAME,
+		outbound_lane_data.latest_generated_nonce,
+		latest_delivered_nonce,
+		params,
+	)? {
+		Some(pause_at) => pause_at,
+		None => return Ok(()),
+	};
+
+	async_std::task::spawn(async move {
+		loop {
+			async_std::task::sleep(CHECK_INTERVAL).await;
+
+			match read_inbound_lane_data::<P>(&target_client, lane_id).await {
+				Ok(lane_data) if lane_data.last_delivered_nonce() >= pause_at => {
+					log::error!(
+						target: "bridge",
+						"Capped backfill of {} -> {} messages at lane {} is complete. Pausing the \
+						relay. Resume it using the control socket",
+						P::SourceChain::NAME,
+						P::TargetChain::NAME,
+						hex::encode(lane_id),
+					);
+					control.pause();
+					return
+				},
+				Ok(_) => {},
+				Err(e) => log::debug!(
+					target: "bridge",
+					"Failed to read {} -> {} inbound lane {} state: {:?}",
+					P::SourceChain::NAME,
+					P::TargetChain::NAME,
+					hex::encode(lane_id),
+					e,
+				),
+			}
+		}
+	});
+
+	Ok(())
+}
+
+/// Compare the backlog of the lane with the backfill cap and act accordingly.
+///
+/// Returns the nonce, after which the relay must be paused, if backfill is capped.
+fn check_backlog(
+	source_chain: &str,
+	target_chain: &str,
+	latest_generated_nonce: MessageNonce,
+	latest_delivered_nonce: MessageNonce,
+	params: BackfillParams,
+) -> anyhow::Result<Option<MessageNonce>> {
+	let backlog = latest_generated_nonce.saturating_sub(latest_delivered_nonce);
+	if backlog <= params.max_nonces {
+		return Ok(None)
+	}
+
+	let alert = format!(
+		"There are {backlog} undelivered {source_chain} -> {target_chain} messages, which is \
+		more than the backfill cap of {} messages",
+		params.max_nonces,
+	);
+	match (params.policy, params.acknowledged) {
+		(_, true) => {
+			log::warn!(target: "bridge", "{alert}. Backfill is acknowledged, delivering all of them");
+			Ok(None)
+		},
+		(BackfillPolicy::Refuse, false) => Err(anyhow::format_err!(
+			"{alert}. Refusing to start the relay. Use `--acknowledge-backfill` to deliver all \
+			of them"
+		)),
+		(BackfillPolicy::Cap, false) => {
+			let pause_at = latest_delivered_nonce + params.max_nonces;
+			log::error!(
+				target: "bridge",
+				"{alert}. Only delivering messages up to nonce {pause_at}",
+			);
+			Ok(Some(pause_at))
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn params(policy: BackfillPolicy, acknowledged: bool) -> BackfillParams {
+		BackfillParams { max_nonces: 100, policy, acknowledged }
+	}
+
+	#[test]
+	fn backlog_is_checked_against_backfill_cap() {
+		// backlog is within the cap
+		assert_eq!(
+			check_backlog("S", "T", 150, 50, params(BackfillPolicy::Refuse, false)).unwrap(),
+			None
+		);
+		// backlog exceeds the cap
+		assert_eq!(
+			check_backlog("S", "T", 151, 50, params(BackfillPolicy::Refuse, false))
+				.unwrap_err()
+				.to_string(),
+			"There are 101 undelivered S -> T messages, which is more than the backfill cap of \
+			100 messages. Refusing to start the relay. Use `--acknowledge-backfill` to deliver \
+			all of them",
+		);
+		assert_eq!(
+			check_backlog("S", "T", 151, 50, params(BackfillPolicy::Cap, false)).unwrap(),
+			Some(150),
+		);
+		// backfill is acknowledged
+		assert_eq!(
+			check_backlog("S", "T", 151, 50, params(BackfillPolicy::Refuse, true)).unwrap(),
+			None
+		);
+		assert_eq!(
+			check_backlog("S", "T", 151, 50, params(BackfillPolicy::Cap, true)).unwrap(),
+			None
+		);
+	}
+}
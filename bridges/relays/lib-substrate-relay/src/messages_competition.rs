@@ -0,0 +1,225 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Analytics of the competition between relayers of the lane.
+//!
+//! Deliverers of messages are only remembered by the inbound lane until deliveries are
+//! confirmed at the source chain. So the relay keeps its own bounded history of recent
+//! deliveries and reports the fraction of messages, delivered by our relayer.
+
+use crate::{
+	messages_lane::{MessageLaneAdapter, SubstrateMessageLane},
+	messages_sole_relayer::read_inbound_lane_data,
+};
+
+use bp_messages::{InboundLaneData, LaneId, MessageNonce};
+use messages_relay::message_lane_loop::metrics_prefix;
+use relay_substrate_client::{AccountIdOf, Chain, Client};
+use relay_utils::metrics::{metric_name, register, Gauge, Registry, F64};
+use std::{
+	collections::VecDeque,
+	time::{Duration, Instant},
+};
+
+/// Interval between two consecutive reads of the inbound lane state.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Interval between two consecutive competition summaries in the log.
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Messages that have been delivered by the same relayer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Delivery {
+	/// Number of delivered messages.
+	messages: MessageNonce,
+	/// True if messages have been delivered by our relayer.
+	is_ours: bool,
+}
+
+/// Bounded history of recent message deliveries at the lane.
+#[derive(Debug)]
+pub struct DeliveryHistory {
+	/// Maximal number of messages in the history.
+	window: MessageNonce,
+	/// Latest nonce, known to the history.
+	latest_nonce: MessageNonce,
+	/// Recent deliveries, oldest first.
+	deliveries: VecDeque<Delivery>,
+}
+
+impl DeliveryHistory {
+	/// Create empty history of at most `window` latest delivered messages.
+	pub fn new(window: MessageNonce) -> Self {
+		DeliveryHistory { window, latest_nonce: 0, deliveries: VecDeque::new() }
+	}
+
+	/// Add deliveries that are recorded in the inbound lane state and are not yet known to
+	/// the history.
+	pub fn update<AccountId: PartialEq>(
+		&mut self,
+		lane_data: &InboundLaneData<AccountId>,
+		relayer: &AccountId,
+	) {
+		for entry in &lane_data.relayers {
+			if entry.messages.end <= self.latest_nonce {
+				continue
+			}
+
+			let begin = std::cmp::max(entry.messages.begin, self.latest_nonce + 1);
+			self.deliveries.push_back(Delivery {
+				messages: entry.messages.end - begin + 1,
+				is_ours: entry.relayer == *relayer,
+			});
+			self.latest_nonce = entry.messages.end;
+		}
+
+		let mut total_messages = self.total_messages();
+		while total_messages > self.window {
+			let excess = total_messages - self.window;
+			let oldest = match self.deliveries.front_mut() {
+				Some(oldest) => oldest,
+				None => break,
+			};
+			if oldest.messages <= excess {
+				total_messages -= oldest.messages;
+				self.deliveries.pop_front();
+			} else {
+				oldest.messages -= excess;
+				total_messages = self.window;
+			}
+		}
+	}
+
+	/// Returns number of messages in the history.
+	pub fn total_messages(&self) -> MessageNonce {
+		self.deliveries.iter().map(|delivery| delivery.messages).sum()
+	}
+
+	/// Returns number of messages in the history, delivered by our relayer.
+	pub fn our_messages(&self) -> MessageNonce {
+		self.deliveries
+			.iter()
+			.filter(|delivery| delivery.is_ours)
+			.map(|delivery| delivery.messages)
+			.sum()
+	}
+
+	/// Returns fraction of messages in the history, delivered by our relayer.
+	///
+	/// Returns `None` if the history is empty.
+	pub fn win_rate(&self) -> Option<f64> {
+		match self.total_messages() {
+			0 => None,
+			total_messages => Some(self.our_messages() as f64 / total_messages as f64),
+		}
+	}
+}
+
+/// Start background task that tracks what fraction of recent deliveries at the lane have been
+/// made by our relayer.
+pub fn spawn<P: SubstrateMessageLane>(
+	target_client: impl Client<P::TargetChain>,
+	lane_id: LaneId,
+	relayer_id_at_source: AccountIdOf<P::SourceChain>,
+	window: MessageNonce,
+	registry: &Registry,
+) -> anyhow::Result<()> {
+	let metric = Gauge::<F64>::new(
+		metric_name(Some(&metrics_prefix::<MessageLaneAdapter<P>>(&lane_id)), "relayer_win_rate"),
+		"Fraction of recent message deliveries that have been made by this relayer",
+	)?;
+	register(metric.clone(), registry)?;
+
+	async_std::task::spawn(async move {
+		let mut history = DeliveryHistory::new(window);
+		let mut last_summary = Instant::now();
+		loop {
+			match read_inbound_lane_data::<P>(&target_client, lane_id).await {
+				Ok(lane_data) => {
+					history.update(&lane_data, &relayer_id_at_source);
+					if let Some(win_rate) = history.win_rate() {
+						metric.set(win_rate);
+					}
+				},
+				Err(e) => log::debug!(
+					target: "bridge",
+					"Failed to read {} -> {} inbound lane {} state: {:?}",
+					P::SourceChain::NAME,
+					P::TargetChain::NAME,
+					hex::encode(lane_id),
+					e,
+				),
+			}
+
+			if last_summary.elapsed() >= SUMMARY_INTERVAL {
+				log::info!(
+					target: "bridge",
+					"Our relayer {:?} has delivered {} of {} recent {} -> {} messages at lane {}",
+					relayer_id_at_source,
+					history.our_messages(),
+					history.total_messages(),
+					P::SourceChain::NAME,
+					P::TargetChain::NAME,
+					hex::encode(lane_id),
+				);
+				last_summary = Instant::now();
+			}
+
+			async_std::task::sleep(CHECK_INTERVAL).await;
+		}
+	});
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use bp_messages::{DeliveredMessages, UnrewardedRelayer};
+
+	fn lane_data(relayers: Vec<(u64, MessageNonce, MessageNonce)>) -> InboundLaneData<u64> {
+		InboundLaneData {
+			relayers: relayers
+				.into_iter()
+				.map(|(relayer, begin, end)| UnrewardedRelayer {
+					relayer,
+					messages: DeliveredMessages { begin, end },
+				})
+				.collect(),
+			last_confirmed_nonce: 0,
+		}
+	}
+
+	#[test]
+	fn win_rate_is_computed_over_bounded_history() {
+		let mut history = DeliveryHistory::new(10);
+		assert_eq!(history.win_rate(), None);
+
+		// we have delivered 4 of 8 messages
+		history.update(&lane_data(vec![(1, 1, 4), (2, 5, 8)]), &1);
+		assert_eq!(history.win_rate(), Some(0.5));
+
+		// earlier deliveries are confirmed and only new messages of the entry are counted
+		history.update(&lane_data(vec![(2, 7, 10)]), &1);
+		assert_eq!((history.our_messages(), history.total_messages()), (4, 10));
+
+		// oldest deliveries are leaving the history
+		history.update(&lane_data(vec![(2, 7, 10), (1, 11, 14)]), &1);
+		assert_eq!((history.our_messages(), history.total_messages()), (4, 10));
+		history.update(&lane_data(vec![(1, 15, 16)]), &1);
+		assert_eq!((history.our_messages(), history.total_messages()), (6, 10));
+		assert_eq!(history.win_rate(), Some(0.6));
+	}
+}
@@ -0,0 +1,201 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Delivery confirmations of several lanes in a single transaction.
+//!
+//! Every lane normally confirms message delivery with its own transaction. When multiple lanes
+//! are served by the same signer, their confirmations may be collected for a short period and
+//! submitted as a single `pallet-utility` batch transaction instead.
+//!
+//! The batch transaction may be included even if some of batched confirmations have failed:
+//! `batch` stops at the first failed confirmation and `force_batch` skips failed confirmations.
+//! With `batch_all`, a single failed confirmation reverts all confirmations of the batch. In any
+//! case, every lane gets its own tracker of the batch transaction and the lane re-reads its state
+//! once the transaction is finalized, so confirmations that haven't been dispatched are
+//! submitted again.
+//!
+//! Batch transactions are not recognized by the bridge signed extensions, so relayers are not
+//! refunded for them.
+
+use crate::{
+	messages_lane::SubstrateMessageLane, BatchCallBuilder, BatchCallBuilderConstructor,
+	TransactionParams,
+};
+
+use bp_messages::LaneId;
+use futures::channel::oneshot;
+use parking_lot::Mutex;
+use relay_substrate_client::{
+	AccountKeyPairOf, BatchCallKind, CallOf, Chain, Client, Error as SubstrateError,
+	TransactionEra, TransactionTracker, UnsignedTransaction,
+};
+use std::{sync::Arc, time::Duration};
+
+/// Confirmations that are submitted within this period after the first confirmation of the
+/// batch, are submitted in the same batch.
+pub const CONFIRMATIONS_BATCH_WINDOW: Duration = Duration::from_secs(6);
+
+/// Tracker of the batch transaction or the reason why it hasn't been submitted.
+type BatchResult<P, Clnt> =
+	Result<TransactionTracker<<P as SubstrateMessageLane>::SourceChain, Clnt>, String>;
+
+/// Calls of the batch that hasn't been submitted yet.
+struct PendingBatch<P: SubstrateMessageLane, Clnt> {
+	/// Lanes that have added their calls to the batch.
+	lanes: Vec<LaneId>,
+	/// Confirmation calls of `lanes`.
+	calls: Vec<CallOf<P::SourceChain>>,
+	/// Senders of batch transaction trackers to `lanes`.
+	senders: Vec<oneshot::Sender<BatchResult<P, Clnt>>>,
+}
+
+/// Batcher of delivery confirmations of lanes, served by the same signer.
+#[derive(Clone)]
+pub struct ConfirmationsBatcher<P: SubstrateMessageLane, Clnt> {
+	client: Clnt,
+	transaction_params: TransactionParams<AccountKeyPairOf<P::SourceChain>>,
+	kind: BatchCallKind,
+	builder: <P::SourceBatchCallBuilder as BatchCallBuilderConstructor<
+		CallOf<P::SourceChain>,
+	>>::CallBuilder,
+	pending: Arc<Mutex<Option<PendingBatch<P, Clnt>>>>,
+}
+
+impl<P: SubstrateMessageLane, Clnt: Client<P::SourceChain>> ConfirmationsBatcher<P, Clnt> {
+	/// Create batcher that submits `kind` batch transactions, signed by the `transaction_params`
+	/// signer. Returns `None` if the source chain runtime doesn't support batch calls.
+	pub fn new(
+		client: Clnt,
+		transaction_params: TransactionParams<AccountKeyPairOf<P::SourceChain>>,
+		kind: BatchCallKind,
+	) -> Option<Self> {
+		Some(ConfirmationsBatcher {
+			client,
+			transaction_params,
+			kind,
+			builder: P::SourceBatchCallBuilder::new_builder()?,
+			pending: Default::default(),
+		})
+	}
+
+	/// Returns signer of batch transactions.
+	pub fn signer(&self) -> &AccountKeyPairOf<P::SourceChain> {
+		&self.transaction_params.signer
+	}
+
+	/// Add confirmation call of given lane to the batch and wait until the batch transaction is
+	/// submitted.
+	pub async fn submit(
+		&self,
+		lane: LaneId,
+		call: CallOf<P::SourceChain>,
+	) -> Result<TransactionTracker<P::SourceChain, Clnt>, SubstrateError> {
+		let (sender, receiver) = oneshot::channel();
+		let is_first_call = {
+			let mut pending = self.pending.lock();
+			let batch = pending.get_or_insert_with(|| PendingBatch {
+				lanes: Vec::new(),
+				calls: Vec::new(),
+				senders: Vec::new(),
+			});
+			batch.lanes.push(lane);
+			batch.calls.push(call);
+			batch.senders.push(sender);
+			batch.calls.len() == 1
+		};
+
+		// the batch is submitted by the background task, so that it isn't lost if the lane that
+		// has started it is restarted
+		if is_first_call {
+			let batcher = self.clone();
+			async_std::task::spawn(async move {
+				async_std::task::sleep(CONFIRMATIONS_BATCH_WINDOW).await;
+				batcher.submit_pending().await
+			});
+		}
+
+		receiver
+			.await
+			.map_err(|_| SubstrateError::Custom("Confirmations batch has been dropped".into()))?
+			.map_err(SubstrateError::Custom)
+	}
+
+	/// Submit pending batch and send its transaction trackers to batched lanes.
+	async fn submit_pending(&self) {
+		let batch = match self.pending.lock().take() {
+			Some(batch) => batch,
+			None => return,
+		};
+
+		log::info!(
+			target: "bridge",
+			"Submitting {} batch transaction with delivery confirmations of {} lanes: {:?}",
+			P::SourceChain::NAME,
+			batch.lanes.len(),
+			batch.lanes,
+		);
+
+		let trackers_count = batch.senders.len();
+		let trackers = match self.submit_batch(batch.calls).await {
+			Ok(tracker) => tracker.split(trackers_count).await,
+			Err(e) => Err(e),
+		};
+		match trackers {
+			Ok(trackers) =>
+				for (sender, tracker) in batch.senders.into_iter().zip(trackers) {
+					let _ = sender.send(Ok(tracker));
+				},
+			Err(e) => {
+				log::warn!(
+					target: "bridge",
+					"Failed to submit {} batch transaction with delivery confirmations of \
+					lanes {:?}: {:?}",
+					P::SourceChain::NAME,
+					batch.lanes,
+					e,
+				);
+
+				let e = format!("Failed to submit confirmations batch: {e:?}");
+				for sender in batch.senders {
+					let _ = sender.send(Err(e.clone()));
+				}
+			},
+		}
+	}
+
+	/// Submit transaction with given calls.
+	async fn submit_batch(
+		&self,
+		mut calls: Vec<CallOf<P::SourceChain>>,
+	) -> Result<TransactionTracker<P::SourceChain, Clnt>, SubstrateError> {
+		// there's no need to wrap single call
+		let call = match calls.len() {
+			1 => calls.remove(0),
+			_ => self.builder.build_batch_call_of_kind(self.kind, calls),
+		};
+		let transaction_params = self.transaction_params.clone();
+		self.client
+			.submit_and_watch_signed_extrinsic(
+				&self.transaction_params.signer,
+				move |best_block_id, transaction_nonce| {
+					Ok(UnsignedTransaction::new(call.into(), transaction_nonce)
+						.era(TransactionEra::new(best_block_id, transaction_params.mortality))
+						.priority(transaction_params.priority))
+				},
+			)
+			.await
+	}
+}
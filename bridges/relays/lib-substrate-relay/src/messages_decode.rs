@@ -0,0 +1,264 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Reading and displaying outbound messages, stored at the source chain.
+//!
+//! The messages pallet treats the message payload as an opaque blob. The only message
+//! fields, known to the relay, are those that are returned by the outbound lane runtime API.
+
+use crate::{messages_lane::SubstrateMessageLane, messages_time_to_confirm::read_lane_nonces};
+
+use bp_messages::{
+	storage_keys::message_key, ChainWithMessages as _, LaneId, MessageNonce, MessagePayload,
+	OutboundMessageDetails,
+};
+use relay_substrate_client::{Chain, ChainWithMessages, Client};
+use serde_json::{json, Value};
+use std::fmt;
+
+/// Maximal number of messages, which details are read using single runtime API call.
+const MAX_MESSAGES_IN_DETAILS_CALL: MessageNonce = 1024;
+
+/// Outbound message, read from the source chain storage.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutboundMessage {
+	/// Lane of the message.
+	pub lane: LaneId,
+	/// Nonce of the message.
+	pub nonce: MessageNonce,
+	/// Message details, returned by the runtime API. May be `None` if the runtime API
+	/// has returned nothing for the message.
+	pub details: Option<OutboundMessageDetails>,
+	/// Raw message payload.
+	pub payload: MessagePayload,
+}
+
+impl fmt::Display for OutboundMessage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Lane: {}", hex::encode(self.lane))?;
+		writeln!(f, "Nonce: {}", self.nonce)?;
+		match self.details {
+			Some(ref details) => {
+				writeln!(
+					f,
+					"Dispatch weight: ref_time={}, proof_size={}",
+					details.dispatch_weight.ref_time(),
+					details.dispatch_weight.proof_size(),
+				)?;
+				writeln!(f, "Size: {} bytes", details.size)?;
+			},
+			None => writeln!(f, "Details: unknown")?,
+		}
+		write!(f, "Payload: 0x{}", hex::encode(&self.payload))
+	}
+}
+
+/// Read outbound message with given nonce at the best finalized source chain block.
+pub async fn read_outbound_message<P: SubstrateMessageLane>(
+	source_client: &impl Client<P::SourceChain>,
+	lane: LaneId,
+	nonce: MessageNonce,
+) -> anyhow::Result<OutboundMessage> {
+	let at = source_client.best_finalized_header_hash().await?;
+	let payload: MessagePayload = source_client
+		.storage_value(
+			at,
+			message_key(P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME, &lane, nonce),
+		)
+		.await?
+		.ok_or_else(|| {
+			anyhow::format_err!(
+				"Message to {} {:?}/{} is missing from the storage of {} at {:?}",
+				P::TargetChain::NAME,
+				lane,
+				nonce,
+				P::SourceChain::NAME,
+				at,
+			)
+		})?;
+
+	let details: Vec<OutboundMessageDetails> = source_client
+		.state_call(
+			at,
+			P::TargetChain::TO_CHAIN_MESSAGE_DETAILS_METHOD.into(),
+			(lane, nonce, nonce),
+		)
+		.await?;
+
+	Ok(OutboundMessage {
+		lane,
+		nonce,
+		details: details.into_iter().find(|details| details.nonce == nonce),
+		payload,
+	})
+}
+
+/// Messages, generated at the source chain, but not yet delivered to the target chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingMessages {
+	/// Lane of messages.
+	pub lane: LaneId,
+	/// Number of all pending messages, including those that are not listed.
+	pub total: MessageNonce,
+	/// Details of listed messages, ordered by nonce.
+	pub messages: Vec<OutboundMessageDetails>,
+}
+
+impl PendingMessages {
+	/// Returns JSON representation of pending messages.
+	pub fn to_json(&self) -> Value {
+		json!({
+			"lane": hex::encode(self.lane),
+			"total": self.total,
+			"messages": self.messages.iter().map(|details| json!({
+				"nonce": details.nonce,
+				"size": details.size,
+				"dispatch_weight": {
+					"ref_time": details.dispatch_weight.ref_time(),
+					"proof_size": details.dispatch_weight.proof_size(),
+				},
+			})).collect::<Vec<_>>(),
+		})
+	}
+}
+
+impl fmt::Display for PendingMessages {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Lane: {}", hex::encode(self.lane))?;
+		write!(f, "Pending messages: {}", self.total)?;
+		if (self.messages.len() as MessageNonce) < self.total {
+			write!(f, " (listing first {})", self.messages.len())?;
+		}
+		if self.messages.is_empty() {
+			return Ok(())
+		}
+
+		write!(f, "\n{:>10} {:>10} {:>20} {:>20}", "Nonce", "Size", "Ref time", "Proof size")?;
+		for details in &self.messages {
+			write!(
+				f,
+				"\n{:>10} {:>10} {:>20} {:>20}",
+				details.nonce,
+				details.size,
+				details.dispatch_weight.ref_time(),
+				details.dispatch_weight.proof_size(),
+			)?;
+		}
+		Ok(())
+	}
+}
+
+/// Read details of messages that are generated at the best finalized source chain block, but
+/// are not delivered at the best finalized target chain block. At most `limit` messages are
+/// listed, if it is set.
+pub async fn read_pending_messages<P: SubstrateMessageLane>(
+	source_client: &impl Client<P::SourceChain>,
+	target_client: &impl Client<P::TargetChain>,
+	lane: LaneId,
+	limit: Option<MessageNonce>,
+) -> anyhow::Result<PendingMessages> {
+	let lane_nonces = read_lane_nonces::<P>(source_client, target_client, lane).await?;
+	let total = lane_nonces
+		.latest_generated_nonce
+		.saturating_sub(lane_nonces.latest_delivered_nonce);
+	let listed = limit.map(|limit| std::cmp::min(limit, total)).unwrap_or(total);
+
+	let at = source_client.best_finalized_header_hash().await?;
+	let mut messages = Vec::new();
+	let mut begin = lane_nonces.latest_delivered_nonce + 1;
+	let end = lane_nonces.latest_delivered_nonce + listed;
+	while begin <= end {
+		let chunk_end = std::cmp::min(end, begin + MAX_MESSAGES_IN_DETAILS_CALL - 1);
+		let details: Vec<OutboundMessageDetails> = source_client
+			.state_call(
+				at,
+				P::TargetChain::TO_CHAIN_MESSAGE_DETAILS_METHOD.into(),
+				(lane, begin, chunk_end),
+			)
+			.await?;
+		messages.extend(details);
+		begin = chunk_end + 1;
+	}
+
+	Ok(PendingMessages { lane, total, messages })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_support::weights::Weight;
+
+	#[test]
+	fn outbound_message_is_displayed() {
+		let message = OutboundMessage {
+			lane: LaneId([0, 0, 0, 1]),
+			nonce: 42,
+			details: Some(OutboundMessageDetails {
+				nonce: 42,
+				dispatch_weight: Weight::from_parts(100, 200),
+				size: 3,
+			}),
+			payload: vec![1, 2, 3],
+		};
+		assert_eq!(
+			message.to_string(),
+			"Lane: 00000001\n\
+			Nonce: 42\n\
+			Dispatch weight: ref_time=100, proof_size=200\n\
+			Size: 3 bytes\n\
+			Payload: 0x010203",
+		);
+	}
+
+	#[test]
+	fn pending_messages_are_displayed() {
+		let details = |nonce| OutboundMessageDetails {
+			nonce,
+			dispatch_weight: Weight::from_parts(100, 200),
+			size: 3,
+		};
+		let pending = PendingMessages {
+			lane: LaneId([0, 0, 0, 1]),
+			total: 3,
+			messages: vec![details(5), details(6)],
+		};
+		assert_eq!(
+			pending.to_string(),
+			format!(
+				"Lane: 00000001\n\
+				Pending messages: 3 (listing first 2)\n\
+				{:>10} {:>10} {:>20} {:>20}\n\
+				{:>10} {:>10} {:>20} {:>20}\n\
+				{:>10} {:>10} {:>20} {:>20}",
+				"Nonce", "Size", "Ref time", "Proof size", 5, 3, 100, 200, 6, 3, 100, 200,
+			),
+		);
+		assert_eq!(
+			pending.to_json(),
+			json!({
+				"lane": "00000001",
+				"total": 3,
+				"messages": [
+					{ "nonce": 5, "size": 3, "dispatch_weight": { "ref_time": 100, "proof_size": 200 } },
+					{ "nonce": 6, "size": 3, "dispatch_weight": { "ref_time": 100, "proof_size": 200 } },
+				],
+			}),
+		);
+
+		let empty = PendingMessages { lane: LaneId([0, 0, 0, 1]), total: 0, messages: vec![] };
+		assert_eq!(empty.to_string(), "Lane: 00000001\nPending messages: 0");
+	}
+}
@@ -0,0 +1,222 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Discovery of messages, generated at the source chain.
+//!
+//! By default, the latest generated nonce is read from the outbound lane storage at every
+//! source block. Alternatively, new messages may be discovered from `MessageAccepted` events
+//! of blocks, built since the previous read. Events are only used to advance the nonce that
+//! has been read from the storage: the storage is read again after reorgs, large gaps and
+//! failures to decode events.
+
+use async_std::sync::{Arc, Mutex};
+use bp_messages::{LaneId, MessageNonce};
+use bp_runtime::HeaderIdProvider;
+use relay_substrate_client::{
+	events::{read_events_metadata, system_events_key, EventsMetadata},
+	Chain, Client, Error as SubstrateError, HeaderIdOf,
+};
+use relay_utils::UniqueSaturatedInto;
+use sp_runtime::traits::{Header as _, One};
+use strum::{EnumString, VariantNames};
+
+/// Maximal number of blocks, which events are read to find new messages. If more blocks have
+/// been built since the previous read, the latest generated nonce is read from the storage.
+const MAX_SCANNED_BLOCKS: u64 = 64;
+
+/// Method that is used to discover messages at the source chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, VariantNames)]
+#[strum(serialize_all = "kebab_case")]
+pub enum MessageDiscovery {
+	/// Read the latest generated nonce from the outbound lane storage.
+	Storage,
+	/// Find new messages in events of blocks, built since the previous read.
+	Events,
+}
+
+/// Discovery of messages from events, shared by all clones.
+pub struct EventsDiscovery<C: Chain> {
+	pallet_name: &'static str,
+	lane: LaneId,
+	state: Arc<Mutex<EventsDiscoveryState<C>>>,
+}
+
+impl<C: Chain> Clone for EventsDiscovery<C> {
+	fn clone(&self) -> Self {
+		EventsDiscovery {
+			pallet_name: self.pallet_name,
+			lane: self.lane,
+			state: self.state.clone(),
+		}
+	}
+}
+
+struct EventsDiscoveryState<C: Chain> {
+	metadata: Option<EventsMetadata>,
+	latest: Option<(HeaderIdOf<C>, MessageNonce)>,
+}
+
+impl<C: Chain> EventsDiscovery<C> {
+	/// Create discovery of messages, sent by the messages pallet with given name at given lane.
+	pub fn new(pallet_name: &'static str, lane: LaneId) -> Self {
+		EventsDiscovery {
+			pallet_name,
+			lane,
+			state: Arc::new(Mutex::new(EventsDiscoveryState { metadata: None, latest: None })),
+		}
+	}
+
+	/// Returns latest generated nonce at given block. If events can't be used, the nonce is
+	/// read using `read_from_storage`.
+	pub async fn latest_generated_nonce<F>(
+		&self,
+		client: &impl Client<C>,
+		id: HeaderIdOf<C>,
+		read_from_storage: impl FnOnce() -> F,
+	) -> Result<MessageNonce, SubstrateError>
+	where
+		F: std::future::Future<Output = Result<MessageNonce, SubstrateError>>,
+	{
+		let mut state = self.state.lock().await;
+		let latest = state.latest;
+		let scanned = match latest {
+			Some((latest_id, latest_nonce)) if latest_id == id => Some(Ok(latest_nonce)),
+			Some((latest_id, latest_nonce)) if is_within_scan_range::<C>(latest_id, id) =>
+				Some(self.scan(&mut state, client, latest_id, latest_nonce, id).await),
+			_ => None,
+		};
+		let latest_nonce = match scanned {
+			Some(Ok(latest_nonce)) => latest_nonce,
+			Some(Err(e)) => {
+				log::debug!(
+					target: "bridge",
+					"Failed to discover {} messages at lane {} from events: {:?}. Reading them \
+					from the storage",
+					C::NAME,
+					hex::encode(self.lane),
+					e,
+				);
+				// the runtime may have been upgraded
+				state.metadata = None;
+				read_from_storage().await?
+			},
+			None => read_from_storage().await?,
+		};
+		state.latest = Some((id, latest_nonce));
+		Ok(latest_nonce)
+	}
+
+	/// Read events of blocks, following the `latest_id` up to the `id` and return the latest
+	/// generated nonce at the `id`.
+	async fn scan(
+		&self,
+		state: &mut EventsDiscoveryState<C>,
+		client: &impl Client<C>,
+		latest_id: HeaderIdOf<C>,
+		latest_nonce: MessageNonce,
+		id: HeaderIdOf<C>,
+	) -> Result<MessageNonce, SubstrateError> {
+		if state.metadata.is_none() {
+			state.metadata = Some(read_events_metadata(client, id.hash()).await?);
+		}
+		let metadata = state.metadata.as_ref().expect("initialized above; qed");
+
+		let mut parent_hash = latest_id.hash();
+		let mut number = latest_id.number();
+		let mut nonce = latest_nonce;
+		while number < id.number() {
+			number = number + One::one();
+			let header = match number == id.number() {
+				true => client.header_by_hash(id.hash()).await?,
+				false => client.header_by_number(number).await?,
+			};
+			if *header.parent_hash() != parent_hash {
+				return Err(SubstrateError::Custom(format!(
+					"{} block {:?} is not a descendant of the previously scanned block",
+					C::NAME,
+					header.id(),
+				)))
+			}
+			parent_hash = header.hash();
+
+			let events = client.raw_storage_value(parent_hash, system_events_key()).await?;
+			let accepted = match events {
+				Some(events) =>
+					metadata.accepted_messages(self.pallet_name, self.lane, &events.0)?,
+				None => Vec::new(),
+			};
+			nonce = next_latest_generated_nonce(nonce, &accepted);
+		}
+		Ok(nonce)
+	}
+}
+
+/// Returns true if blocks between `latest_id` and `id` may be scanned.
+fn is_within_scan_range<C: Chain>(latest_id: HeaderIdOf<C>, id: HeaderIdOf<C>) -> bool {
+	let (latest_number, number): (u64, u64) =
+		(latest_id.number().unique_saturated_into(), id.number().unique_saturated_into());
+	number > latest_number && number - latest_number <= MAX_SCANNED_BLOCKS
+}
+
+/// Returns latest generated nonce after messages with given nonces have been accepted.
+fn next_latest_generated_nonce(
+	latest_nonce: MessageNonce,
+	accepted: &[MessageNonce],
+) -> MessageNonce {
+	accepted
+		.iter()
+		.fold(latest_nonce, |latest, nonce| std::cmp::max(latest, *nonce))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use relay_substrate_client::test_chain::TestChain;
+	use sp_core::H256;
+	use std::ops::RangeInclusive;
+
+	#[test]
+	fn events_and_storage_discover_the_same_messages() {
+		// latest generated nonces from the storage and accepted nonces from events of every
+		// block, starting with block 1
+		let blocks: Vec<(MessageNonce, Vec<MessageNonce>)> =
+			vec![(10, vec![]), (12, vec![11, 12]), (12, vec![]), (15, vec![13, 14, 15])];
+
+		let mut storage_nonces = Vec::new();
+		let mut events_nonces = Vec::new();
+		let (mut storage_latest, mut events_latest) = (blocks[0].0, blocks[0].0);
+		for (latest_generated_nonce, accepted) in &blocks[1..] {
+			storage_nonces.extend(storage_latest + 1..=*latest_generated_nonce);
+			storage_latest = *latest_generated_nonce;
+
+			events_nonces.extend(accepted.iter().copied());
+			events_latest = next_latest_generated_nonce(events_latest, accepted);
+			assert_eq!(events_latest, storage_latest);
+		}
+		assert_eq!(events_nonces, storage_nonces);
+		assert_eq!(events_nonces, RangeInclusive::new(11, 15).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn only_close_descendants_are_scanned() {
+		let id = |number| bp_runtime::HeaderId(number, H256::repeat_byte(number as u8));
+		assert!(is_within_scan_range::<TestChain>(id(10), id(11)));
+		assert!(is_within_scan_range::<TestChain>(id(10), id(10 + MAX_SCANNED_BLOCKS as u32)));
+		assert!(!is_within_scan_range::<TestChain>(id(10), id(11 + MAX_SCANNED_BLOCKS as u32)));
+		assert!(!is_within_scan_range::<TestChain>(id(10), id(10)));
+		assert!(!is_within_scan_range::<TestChain>(id(10), id(9)));
+	}
+}
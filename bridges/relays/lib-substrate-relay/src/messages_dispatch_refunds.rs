@@ -0,0 +1,132 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tracking of the dispatch weight, which has not been spent by delivered messages.
+//!
+//! When the delivery transaction is finalized, `MessagesReceived` events of the block with
+//! this transaction are read and the unspent dispatch weight of delivered messages is reported.
+//! Failures to read events are only logged: messages have been delivered anyway.
+
+use async_std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use bp_messages::{LaneId, MessageNonce, Weight};
+use relay_substrate_client::{
+	events::{read_events_metadata, system_events_key, EventsMetadata},
+	Chain, Client, Error as SubstrateError, HashOf, HeaderIdOf, TransactionTracker,
+};
+use relay_utils::{dispatch_refunds::DispatchRefunds, TrackedTransactionStatus};
+use std::ops::RangeInclusive;
+
+/// Reader of the unspent dispatch weight from events of the target chain, shared by all clones.
+#[derive(Clone)]
+pub struct DispatchRefundsReader {
+	pallet_name: &'static str,
+	lane: LaneId,
+	refunds: DispatchRefunds,
+	metadata: Arc<Mutex<Option<EventsMetadata>>>,
+}
+
+impl DispatchRefundsReader {
+	/// Create reader of the weight, unspent by messages that are received by the messages
+	/// pallet with given name at given lane.
+	pub fn new(pallet_name: &'static str, lane: LaneId, refunds: DispatchRefunds) -> Self {
+		DispatchRefundsReader { pallet_name, lane, refunds, metadata: Default::default() }
+	}
+
+	/// Read events of given block and report the unspent dispatch weight of messages with
+	/// given nonces.
+	pub async fn note_delivered<C: Chain>(
+		&self,
+		client: &impl Client<C>,
+		at: HashOf<C>,
+		nonces: &RangeInclusive<MessageNonce>,
+	) {
+		match self.read_unspent_weight(client, at, nonces).await {
+			Ok(unspent_weight) =>
+				self.refunds.note(unspent_weight.ref_time(), unspent_weight.proof_size()),
+			Err(e) => {
+				log::debug!(
+					target: "bridge",
+					"Failed to read unspent dispatch weight of {} messages {:?} at lane {}: {:?}",
+					C::NAME,
+					nonces,
+					hex::encode(self.lane),
+					e,
+				);
+				// the runtime may have been upgraded
+				*self.metadata.lock().await = None;
+			},
+		}
+	}
+
+	async fn read_unspent_weight<C: Chain>(
+		&self,
+		client: &impl Client<C>,
+		at: HashOf<C>,
+		nonces: &RangeInclusive<MessageNonce>,
+	) -> Result<Weight, SubstrateError> {
+		let mut metadata = self.metadata.lock().await;
+		if metadata.is_none() {
+			*metadata = Some(read_events_metadata(client, at).await?);
+		}
+		let metadata = metadata.as_ref().expect("initialized above; qed");
+
+		match client.raw_storage_value(at, system_events_key()).await? {
+			Some(events) =>
+				metadata.unspent_dispatch_weight(self.pallet_name, self.lane, nonces, &events.0),
+			None => Ok(Weight::zero()),
+		}
+	}
+}
+
+/// Tracker of the messages delivery transaction. If the reader is set, the unspent dispatch
+/// weight of delivered messages is reported when the transaction is finalized.
+pub struct DeliveryTransactionTracker<C: Chain, Clnt> {
+	tracker: TransactionTracker<C, Clnt>,
+	refunds: Option<(DispatchRefundsReader, Clnt, RangeInclusive<MessageNonce>)>,
+}
+
+impl<C: Chain, Clnt> DeliveryTransactionTracker<C, Clnt> {
+	/// Create tracker of the transaction, delivering messages with given nonces.
+	pub fn new(
+		tracker: TransactionTracker<C, Clnt>,
+		reader: Option<DispatchRefundsReader>,
+		client: Clnt,
+		nonces: RangeInclusive<MessageNonce>,
+	) -> Self {
+		DeliveryTransactionTracker {
+			tracker,
+			refunds: reader.map(|reader| (reader, client, nonces)),
+		}
+	}
+}
+
+#[async_trait]
+impl<C: Chain, Clnt: Client<C>> relay_utils::TransactionTracker
+	for DeliveryTransactionTracker<C, Clnt>
+{
+	type HeaderId = HeaderIdOf<C>;
+
+	async fn wait(self) -> TrackedTransactionStatus<HeaderIdOf<C>> {
+		let status = self.tracker.wait().await;
+		if let (TrackedTransactionStatus::Finalized(ref id), Some((reader, client, nonces))) =
+			(&status, self.refunds)
+		{
+			reader.note_delivered(&client, id.hash(), &nonces).await;
+		}
+		status
+	}
+}
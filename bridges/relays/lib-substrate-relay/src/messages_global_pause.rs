@@ -0,0 +1,245 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Observing the global pause switch of the bridge.
+//!
+//! The switch is polled by background tasks, one for every observed chain, and the guard is
+//! shared by all lanes of the relay process. While the bridge is paused at any of observed
+//! chains, lanes refuse to submit delivery and confirmation transactions and retry them later.
+//! Submission is resumed once the bridge is unpaused at all observed chains. Background tasks
+//! are stopped when the relay is finished.
+
+use async_std::task::JoinHandle;
+use bp_messages::LaneId;
+use parking_lot::Mutex;
+use relay_substrate_client::{global_pause::GlobalPauseStorage, Chain, Client, Error};
+use relay_utils::metrics::{
+	metric_name, register, Counter, Gauge, Metric, PrometheusError, Registry, F64, U64,
+};
+use std::{
+	collections::BTreeSet,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+/// Guard that holds back submissions while the bridge is paused.
+#[derive(Clone, Debug)]
+pub struct GlobalPauseGuard {
+	poll_interval: Duration,
+	state: Arc<Mutex<PauseState>>,
+	/// Background tasks that poll the pause switch.
+	observers: Arc<Mutex<Vec<JoinHandle<()>>>>,
+	is_paused: Gauge<U64>,
+	held_back_seconds: Counter<F64>,
+}
+
+#[derive(Debug, Default)]
+struct PauseState {
+	/// Names of chains, where the bridge is currently paused.
+	paused_at: BTreeSet<&'static str>,
+	/// Time since when the bridge is paused at any of observed chains.
+	paused_since: Option<Instant>,
+}
+
+impl GlobalPauseGuard {
+	/// Create guard that polls the pause switch with given interval.
+	pub fn new(poll_interval: Duration, prefix: Option<&str>) -> Result<Self, PrometheusError> {
+		Ok(GlobalPauseGuard {
+			poll_interval,
+			state: Default::default(),
+			observers: Default::default(),
+			is_paused: Gauge::new(
+				metric_name(prefix, "bridge_is_globally_paused"),
+				"Whether the bridge is paused by its global pause switch",
+			)?,
+			held_back_seconds: Counter::new(
+				metric_name(prefix, "global_pause_held_back_seconds"),
+				"Total time that transactions have been held back, because the bridge has been \
+				paused",
+			)?,
+		})
+	}
+
+	/// Start observing the pause switch at given chain.
+	///
+	/// The switch is read once before returning, so that nothing is submitted if the bridge
+	/// is already paused. Later read failures are logged and the last known state is kept.
+	pub async fn observe<C: Chain>(
+		&self,
+		client: impl Client<C>,
+		storage: GlobalPauseStorage,
+	) -> Result<(), Error> {
+		let is_paused = storage.read_is_paused(&client, client.best_header_hash().await?).await?;
+		self.update(C::NAME, &storage, is_paused, Instant::now());
+
+		let guard = self.clone();
+		let observer = async_std::task::spawn(async move {
+			loop {
+				async_std::task::sleep(guard.poll_interval).await;
+
+				let is_paused = match client.best_header_hash().await {
+					Ok(at) => storage.read_is_paused(&client, at).await,
+					Err(e) => Err(e),
+				};
+				match is_paused {
+					Ok(is_paused) => guard.update(C::NAME, &storage, is_paused, Instant::now()),
+					Err(e) => log::warn!(
+						target: "bridge",
+						"Failed to read the global pause switch {} at {}: {:?}",
+						storage,
+						C::NAME,
+						e,
+					),
+				}
+			}
+		});
+		self.observers.lock().push(observer);
+
+		Ok(())
+	}
+
+	/// Stop observing the pause switch at all chains.
+	pub async fn stop(&self) {
+		let observers = std::mem::take(&mut *self.observers.lock());
+		for observer in observers {
+			observer.cancel().await;
+		}
+	}
+
+	/// Returns true if the bridge is paused at any of observed chains.
+	pub fn is_paused(&self) -> bool {
+		!self.state.lock().paused_at.is_empty()
+	}
+
+	/// Ensure that the bridge is not paused at any of observed chains.
+	///
+	/// It is checked before the transaction is submitted. The error makes the race retry the
+	/// submission later, so the lane is never stuck waiting inside a submission and can exit
+	/// while the bridge is paused.
+	pub(crate) fn ensure_unpaused(&self, lane: LaneId) -> Result<(), Error> {
+		let state = self.state.lock();
+		if state.paused_at.is_empty() {
+			return Ok(())
+		}
+
+		log::debug!(
+			target: "bridge",
+			"Holding back transactions of lane {} until the bridge is unpaused",
+			hex::encode(lane),
+		);
+		Err(Error::Custom(format!(
+			"The bridge is paused at {}",
+			state.paused_at.iter().cloned().collect::<Vec<_>>().join(", "),
+		)))
+	}
+
+	/// Update the pause state of the bridge at given chain.
+	fn update(
+		&self,
+		chain: &'static str,
+		storage: &GlobalPauseStorage,
+		is_paused: bool,
+		now: Instant,
+	) {
+		let mut state = self.state.lock();
+		let paused_at = &mut state.paused_at;
+		match (is_paused, paused_at.contains(chain)) {
+			(true, false) => log::error!(
+				target: "bridge",
+				"The bridge has been PAUSED by the global pause switch {} at {}. All delivery and \
+				confirmation transactions are held back until it is unpaused",
+				storage,
+				chain,
+			),
+			(false, true) => log::warn!(
+				target: "bridge",
+				"The bridge has been unpaused by the global pause switch {} at {}{}",
+				storage,
+				chain,
+				if paused_at.len() > 1 { "" } else { ". Resuming transactions" },
+			),
+			_ => (),
+		}
+
+		if is_paused {
+			paused_at.insert(chain);
+		} else {
+			paused_at.remove(chain);
+		}
+		let is_paused = !paused_at.is_empty();
+		match (is_paused, state.paused_since) {
+			(true, None) => state.paused_since = Some(now),
+			(false, Some(paused_since)) => {
+				state.paused_since = None;
+				self.held_back_seconds
+					.inc_by(now.saturating_duration_since(paused_since).as_secs_f64());
+			},
+			_ => (),
+		}
+		self.is_paused.set(is_paused as u64);
+	}
+}
+
+impl Metric for GlobalPauseGuard {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		register(self.is_paused.clone(), registry)?;
+		register(self.held_back_seconds.clone(), registry)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn transactions_are_held_back_while_bridge_is_paused() {
+		let guard = GlobalPauseGuard::new(Duration::from_millis(1), None).unwrap();
+		let storage = GlobalPauseStorage { pallet: "Guard".into(), item: "IsPaused".into() };
+		let lane = LaneId([0, 0, 0, 1]);
+		let start = Instant::now();
+
+		// nothing is held back while the bridge is operational
+		guard.update("A", &storage, false, start);
+		assert!(guard.ensure_unpaused(lane).is_ok());
+
+		// transactions are held back while the bridge is paused at any chain
+		guard.update("A", &storage, true, start);
+		guard.update("B", &storage, true, start + Duration::from_secs(10));
+		assert!(guard.ensure_unpaused(lane).is_err());
+		guard.update("A", &storage, false, start + Duration::from_secs(20));
+		assert!(guard.ensure_unpaused(lane).is_err());
+		assert_eq!(guard.is_paused.get(), 1);
+		assert_eq!(guard.held_back_seconds.get(), 0.0);
+
+		// and are resumed once it is unpaused everywhere
+		guard.update("B", &storage, false, start + Duration::from_secs(30));
+		assert!(guard.ensure_unpaused(lane).is_ok());
+		assert!(!guard.is_paused());
+		assert_eq!(guard.is_paused.get(), 0);
+		assert_eq!(guard.held_back_seconds.get(), 30.0);
+	}
+
+	#[async_std::test]
+	async fn observers_are_stopped() {
+		let guard = GlobalPauseGuard::new(Duration::from_millis(1), None).unwrap();
+		let observer = async_std::task::spawn(futures::future::pending::<()>());
+		guard.observers.lock().push(observer);
+
+		async_std::future::timeout(Duration::from_secs(10), guard.stop()).await.unwrap();
+		assert!(guard.observers.lock().is_empty());
+	}
+}
@@ -0,0 +1,310 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Detection of the missing source headers relay.
+//!
+//! Messages proof can only be verified at the target chain after the source header, where it
+//! has been generated, is synced to the target chain. The standalone messages relay doesn't
+//! sync headers and assumes that some other relay does that. If there's no such relay, the
+//! source header, synced to the target chain, never reaches the required header and the relay
+//! keeps waiting forever. The guard notices that the required header hasn't been synced for
+//! a while and warns about it, pauses delivery or starts its own on-demand headers relay.
+
+use crate::on_demand::OnDemandRelay;
+
+use parking_lot::Mutex;
+use relay_substrate_client::{BlockNumberOf, Chain};
+use std::{
+	sync::Arc,
+	time::{Duration, Instant},
+};
+use strum::{EnumString, VariantNames};
+
+/// What the relay does when source headers aren't synced to the target chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, VariantNames)]
+#[strum(serialize_all = "kebab_case")]
+pub enum NoHeaderRelayPolicy {
+	/// Log the error and keep waiting for headers.
+	Warn,
+	/// Log the error and pause delivery until headers are synced again.
+	Pause,
+	/// Log the error and start the on-demand headers relay.
+	AutoRelay,
+}
+
+/// Function that starts the on-demand source headers relay.
+pub type StartHeadersRelay<SourceChain, TargetChain> =
+	Arc<dyn Fn() -> Arc<dyn OnDemandRelay<SourceChain, TargetChain>> + Send + Sync>;
+
+/// Guard that watches progress of source headers, synced to the target chain.
+pub struct HeaderProgressGuard<SourceChain: Chain, TargetChain: Chain> {
+	policy: NoHeaderRelayPolicy,
+	/// Time that the required header may stay unsynced, while the synced header doesn't
+	/// advance.
+	timeout: Duration,
+	start_headers_relay: Option<StartHeadersRelay<SourceChain, TargetChain>>,
+	state: Arc<Mutex<HeaderProgressState<SourceChain, TargetChain>>>,
+}
+
+struct HeaderProgressState<SourceChain: Chain, TargetChain: Chain> {
+	/// Number of the source header, last synced to the target chain.
+	synced: Option<BlockNumberOf<SourceChain>>,
+	/// Number of the source header, required by delivery, and time since when the synced
+	/// header hasn't advanced while it has been required.
+	required: Option<(BlockNumberOf<SourceChain>, Instant)>,
+	/// True if headers have stopped being synced.
+	is_stalled: bool,
+	/// Headers relay, started by the `AutoRelay` policy.
+	headers_relay: Option<Arc<dyn OnDemandRelay<SourceChain, TargetChain>>>,
+}
+
+impl<SourceChain: Chain, TargetChain: Chain> Clone
+	for HeaderProgressGuard<SourceChain, TargetChain>
+{
+	fn clone(&self) -> Self {
+		HeaderProgressGuard {
+			policy: self.policy,
+			timeout: self.timeout,
+			start_headers_relay: self.start_headers_relay.clone(),
+			state: self.state.clone(),
+		}
+	}
+}
+
+impl<SourceChain: Chain, TargetChain: Chain> HeaderProgressGuard<SourceChain, TargetChain> {
+	/// Create guard with given policy. The `start_headers_relay` must be provided if the
+	/// policy is `AutoRelay`.
+	pub fn new(
+		policy: NoHeaderRelayPolicy,
+		timeout: Duration,
+		start_headers_relay: Option<StartHeadersRelay<SourceChain, TargetChain>>,
+	) -> anyhow::Result<Self> {
+		if policy == NoHeaderRelayPolicy::AutoRelay && start_headers_relay.is_none() {
+			anyhow::bail!(
+				"Relaying {} headers to {} on demand is not supported by the bridge",
+				SourceChain::NAME,
+				TargetChain::NAME,
+			);
+		}
+
+		Ok(HeaderProgressGuard {
+			policy,
+			timeout,
+			start_headers_relay,
+			state: Arc::new(Mutex::new(HeaderProgressState {
+				synced: None,
+				required: None,
+				is_stalled: false,
+				headers_relay: None,
+			})),
+		})
+	}
+
+	/// Returns true if delivery is paused, because headers aren't synced.
+	pub fn is_delivery_paused(&self) -> bool {
+		self.policy == NoHeaderRelayPolicy::Pause && self.state.lock().is_stalled
+	}
+
+	/// Remember the number of the source header, synced to the target chain.
+	pub(crate) fn note_synced_header(&self, synced: BlockNumberOf<SourceChain>, now: Instant) {
+		let mut state = self.state.lock();
+		let has_advanced = state.synced.map(|prev_synced| synced > prev_synced).unwrap_or(true);
+		if !has_advanced {
+			return
+		}
+
+		state.synced = Some(synced);
+		state.required = match state.required {
+			Some((required, _)) if required <= synced => None,
+			Some((required, _)) => Some((required, now)),
+			None => None,
+		};
+		if state.is_stalled {
+			state.is_stalled = false;
+			log::info!(
+				target: "bridge",
+				"{} headers are synced to {} again. The last synced header is {}{}",
+				SourceChain::NAME,
+				TargetChain::NAME,
+				synced,
+				if self.policy == NoHeaderRelayPolicy::Pause { ". Delivery is resumed" } else { "" },
+			);
+		}
+	}
+
+	/// Remember that delivery requires the source header with given number at the target chain.
+	/// Returns the headers relay, started by the `AutoRelay` policy, if any.
+	pub(crate) fn note_required_header(
+		&self,
+		required: BlockNumberOf<SourceChain>,
+		now: Instant,
+	) -> Option<Arc<dyn OnDemandRelay<SourceChain, TargetChain>>> {
+		let mut state = self.state.lock();
+		if state.synced.map(|synced| synced >= required).unwrap_or(false) {
+			return state.headers_relay.clone()
+		}
+
+		let since = match state.required {
+			Some((prev_required, since)) => {
+				state.required = Some((prev_required.max(required), since));
+				since
+			},
+			None => {
+				state.required = Some((required, now));
+				now
+			},
+		};
+		if state.is_stalled || now.saturating_duration_since(since) < self.timeout {
+			return state.headers_relay.clone()
+		}
+
+		state.is_stalled = true;
+		log::error!(
+			target: "bridge",
+			"{} header {} is required at {}, but the last synced header {:?} hasn't advanced for \
+			{:?}. Is the headers relay running? {}",
+			SourceChain::NAME,
+			required,
+			TargetChain::NAME,
+			state.synced,
+			self.timeout,
+			match self.policy {
+				NoHeaderRelayPolicy::Warn => "Waiting for headers",
+				NoHeaderRelayPolicy::Pause => "Delivery is paused",
+				NoHeaderRelayPolicy::AutoRelay if state.headers_relay.is_some() =>
+					"Waiting for the on-demand headers relay",
+				NoHeaderRelayPolicy::AutoRelay => "Starting the on-demand headers relay",
+			},
+		);
+		if let (NoHeaderRelayPolicy::AutoRelay, true, Some(ref start_headers_relay)) =
+			(self.policy, state.headers_relay.is_none(), &self.start_headers_relay)
+		{
+			state.headers_relay = Some(start_headers_relay());
+		}
+
+		state.headers_relay.clone()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use relay_substrate_client::{
+		test_chain::{TestChain, TestParachain},
+		CallOf, Error as SubstrateError, HeaderIdOf,
+	};
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	const TIMEOUT: Duration = Duration::from_secs(60);
+
+	struct TestHeadersRelay;
+
+	#[async_trait::async_trait]
+	impl OnDemandRelay<TestChain, TestParachain> for TestHeadersRelay {
+		async fn reconnect(&self) -> Result<(), SubstrateError> {
+			Ok(())
+		}
+
+		async fn require_more_headers(&self, _required_header: BlockNumberOf<TestChain>) {}
+
+		async fn prove_header(
+			&self,
+			_required_header: BlockNumberOf<TestChain>,
+		) -> Result<(HeaderIdOf<TestChain>, Vec<CallOf<TestParachain>>), SubstrateError> {
+			unimplemented!()
+		}
+	}
+
+	fn guard(
+		policy: NoHeaderRelayPolicy,
+	) -> (HeaderProgressGuard<TestChain, TestParachain>, Arc<AtomicUsize>) {
+		let started = Arc::new(AtomicUsize::new(0));
+		let started_clone = started.clone();
+		let start_headers_relay: StartHeadersRelay<TestChain, TestParachain> =
+			Arc::new(move || {
+				started_clone.fetch_add(1, Ordering::SeqCst);
+				Arc::new(TestHeadersRelay)
+			});
+		(HeaderProgressGuard::new(policy, TIMEOUT, Some(start_headers_relay)).unwrap(), started)
+	}
+
+	#[test]
+	fn non_advancing_synced_header_pauses_delivery() {
+		let (guard, _) = guard(NoHeaderRelayPolicy::Pause);
+		let start = Instant::now();
+
+		// header 10 is required, but the synced header stays at 5
+		guard.note_synced_header(5, start);
+		assert!(guard.note_required_header(10, start).is_none());
+		guard.note_synced_header(5, start + TIMEOUT / 2);
+		guard.note_required_header(10, start + TIMEOUT / 2);
+		assert!(!guard.is_delivery_paused());
+		guard.note_synced_header(5, start + TIMEOUT);
+		guard.note_required_header(10, start + TIMEOUT);
+		assert!(guard.is_delivery_paused());
+
+		// the synced header advances, but hasn't reached the required header yet
+		guard.note_synced_header(7, start + TIMEOUT * 2);
+		assert!(!guard.is_delivery_paused());
+		guard.note_required_header(10, start + TIMEOUT * 2);
+		assert!(!guard.is_delivery_paused());
+
+		// the required header is synced
+		guard.note_synced_header(10, start + TIMEOUT * 3);
+		guard.note_required_header(12, start + TIMEOUT * 3);
+		guard.note_synced_header(12, start + TIMEOUT * 4);
+		guard.note_required_header(12, start + TIMEOUT * 5);
+		assert!(!guard.is_delivery_paused());
+	}
+
+	#[test]
+	fn warn_policy_never_pauses_delivery() {
+		let (guard, started) = guard(NoHeaderRelayPolicy::Warn);
+		let start = Instant::now();
+		guard.note_synced_header(5, start);
+		guard.note_required_header(10, start);
+		assert!(guard.note_required_header(10, start + TIMEOUT).is_none());
+		assert!(!guard.is_delivery_paused());
+		assert_eq!(started.load(Ordering::SeqCst), 0);
+	}
+
+	#[test]
+	fn auto_relay_policy_starts_headers_relay_once() {
+		let (guard, started) = guard(NoHeaderRelayPolicy::AutoRelay);
+		let start = Instant::now();
+		guard.note_synced_header(5, start);
+		assert!(guard.note_required_header(10, start).is_none());
+		assert!(guard.note_required_header(10, start + TIMEOUT).is_some());
+		assert!(guard.note_required_header(11, start + TIMEOUT * 2).is_some());
+		assert!(!guard.is_delivery_paused());
+		assert_eq!(started.load(Ordering::SeqCst), 1);
+
+		// the started relay keeps being used after headers are synced
+		guard.note_synced_header(11, start + TIMEOUT * 3);
+		assert!(guard.note_required_header(11, start + TIMEOUT * 3).is_some());
+		assert_eq!(started.load(Ordering::SeqCst), 1);
+	}
+
+	#[test]
+	fn auto_relay_policy_requires_headers_relay() {
+		assert!(HeaderProgressGuard::<TestChain, TestParachain>::new(
+			NoHeaderRelayPolicy::AutoRelay,
+			TIMEOUT,
+			None,
+		)
+		.is_err());
+	}
+}
@@ -17,10 +17,28 @@
 //! Tools for supporting message lanes between two Substrate-based chains.
 
 use crate::{
-	messages_source::{SubstrateMessagesProof, SubstrateMessagesSource},
-	messages_target::{SubstrateMessagesDeliveryProof, SubstrateMessagesTarget},
+	messages_backfill::BackfillParams,
+	messages_confirmations_batch::ConfirmationsBatcher,
+	messages_discovery::MessageDiscovery,
+	messages_dispatch_refunds::DispatchRefundsReader,
+	messages_global_pause::GlobalPauseGuard,
+	messages_header_progress::HeaderProgressGuard,
+	messages_pallet::PalletVersionMismatchAction,
+	messages_sole_relayer::SoleRelayerParams,
+	messages_source::{
+		ConfirmationBalanceGuard, PausedLaneIdler, ProofGenerationLimit, RewardEligibilityGuard,
+		SubstrateMessagesProof, SubstrateMessagesSource, SubstrateMessagesSourceParams,
+		XcmMessagesFilter,
+	},
+	messages_target::{
+		BalanceGuard, BlockWeightHeadroom, FeeEstimation, FeeEstimationFallback,
+		FeeMultiplierGuard, RateLimitQuotaGuard, RuntimeUpgradeGuard,
+		SubstrateMessagesDeliveryProof, SubstrateMessagesTarget, SubstrateMessagesTargetParams,
+		SyncedHeaderAgeGuard,
+	},
 	on_demand::OnDemandRelay,
-	BatchCallBuilder, BatchCallBuilderConstructor, TransactionParams,
+	scale_events::ScaleEventSink,
+	BatchCallBuilder, BatchCallBuilderConstructor, ProxyParams, RoundRobin, TransactionParams,
 };
 
 use async_std::sync::Arc;
@@ -33,20 +51,37 @@ use bridge_runtime_common::messages::{
 };
 use codec::Encode;
 use frame_support::{dispatch::GetDispatchInfo, weights::Weight};
-use messages_relay::{message_lane::MessageLane, message_lane_loop::BatchTransaction};
+use futures::FutureExt;
+use messages_relay::{
+	message_lane::MessageLane,
+	message_lane_loop::{
+		BatchAutoTuner, BatchTransaction, CompanionLane, ConfirmationDeadline, LaneErrorState,
+		MinBatch, OversizedWeightPolicy, PendingMessagesGate, RetryBudget,
+	},
+};
 use pallet_bridge_messages::{Call as BridgeMessagesCall, Config as BridgeMessagesConfig};
 use relay_substrate_client::{
-	transaction_stall_timeout, AccountKeyPairOf, BalanceOf, BlockNumberOf, CallOf, Chain,
-	ChainWithMessages, ChainWithTransactions, Client, Error as SubstrateError, HashOf, SignParam,
-	UnsignedTransaction,
+	metrics::{BadEndpointsMetric, InFlightTransactionsMetric},
+	storage_proof::StorageProofFormat,
+	transaction_stall_timeout,
+	xcm_filter::XcmInstructionFilter,
+	AccountKeyPairOf, BalanceOf, BlockNumberOf, CallOf, Chain, ChainWithMessages,
+	ChainWithTransactions, Client, Error as SubstrateError, HashOf, SignParam, UnsignedTransaction,
 };
 use relay_utils::{
-	metrics::{GlobalMetrics, MetricsParams, StandaloneMetric},
+	control::RelayControl,
+	dispatch_refunds::DispatchRefunds,
+	fee_accounting::FeeAccounting,
+	fee_budget::FeeBudget,
+	metrics::{
+		metric_name, register, Counter, GlobalMetrics, Metric, MetricsParams, StandaloneMetric,
+	},
+	throughput_budget::ThroughputBudget,
 	STALL_TIMEOUT,
 };
 use sp_core::Pair;
 use sp_runtime::traits::Zero;
-use std::{fmt::Debug, marker::PhantomData, ops::RangeInclusive};
+use std::{fmt::Debug, marker::PhantomData, ops::RangeInclusive, time::Duration};
 
 /// Substrate -> Substrate messages synchronization pipeline.
 pub trait SubstrateMessageLane: 'static + Clone + Debug + Send + Sync {
@@ -93,10 +128,17 @@ pub struct MessagesRelayParams<P: SubstrateMessageLane, SourceClnt, TargetClnt>
 	pub source_client: SourceClnt,
 	/// Source transaction params.
 	pub source_transaction_params: TransactionParams<AccountKeyPairOf<P::SourceChain>>,
+	/// Additional source chain signers. Source transactions are signed by all signers in turns
+	/// and rewards for delivered messages are distributed between their accounts.
+	pub source_additional_signers: Vec<AccountKeyPairOf<P::SourceChain>>,
+	/// If set, delivery confirmation calls are dispatched using this proxy.
+	pub source_proxy: Option<ProxyParams<P::SourceChain>>,
 	/// Messages target client.
 	pub target_client: TargetClnt,
 	/// Target transaction params.
 	pub target_transaction_params: TransactionParams<AccountKeyPairOf<P::TargetChain>>,
+	/// If set, delivery calls are dispatched using this proxy.
+	pub target_proxy: Option<ProxyParams<P::TargetChain>>,
 	/// Optional on-demand source to target headers relay.
 	pub source_to_target_headers_relay:
 		Option<Arc<dyn OnDemandRelay<P::SourceChain, P::TargetChain>>>,
@@ -110,6 +152,142 @@ pub struct MessagesRelayParams<P: SubstrateMessageLane, SourceClnt, TargetClnt>
 	pub limits: Option<MessagesRelayLimits>,
 	/// Metrics parameters.
 	pub metrics_params: MetricsParams,
+	/// Runtime control of the relay.
+	pub control: RelayControl,
+	/// If set, the relay checks whether it is the only active relayer of the lane.
+	pub sole_relayer: Option<SoleRelayerParams>,
+	/// If set, the relay reports what fraction of given number of latest delivered messages
+	/// has been delivered by its own account.
+	pub competition_analytics_window: Option<MessageNonce>,
+	/// Maximal fees (in target chain tokens) that may be spent on delivery transactions within
+	/// a rolling hour. Unlimited if not set.
+	pub max_fees_per_hour: Option<u128>,
+	/// Percent that is added to the estimated fee of delivery transaction before checking it
+	/// against the fee budget and the signer balance.
+	pub fee_safety_margin_percent: u32,
+	/// What to do when the delivery transaction fee can't be estimated.
+	pub fee_estimation_fallback: FeeEstimationFallback,
+	/// What to do when messages pallet instances at both sides of the lane have different
+	/// declared versions.
+	pub pallet_version_mismatch: PalletVersionMismatchAction,
+	/// If set, the relay checks the backlog of the lane against the backfill cap at startup.
+	pub backfill: Option<BackfillParams>,
+	/// Maximal number of nonces ahead of the latest confirmed nonce, tracked by the relay.
+	pub max_nonces_ahead: Option<MessageNonce>,
+	/// Log when the relay defers tracking of messages because of `max_nonces_ahead`.
+	pub log_backpressure: bool,
+	/// Number of target chain blocks, that must be built on top of the block, where the source
+	/// header has been synced, before the relay is building proofs against this header.
+	pub target_sync_confirmations: u32,
+	/// If set, delivery confirmations are only built against target headers that have been
+	/// synced to the source chain at least that long ago.
+	pub confirmation_lag: Option<Duration>,
+	/// If set, the relay ignores the confirmation lag when delivered messages are staying
+	/// unconfirmed for almost that long.
+	pub max_confirmation_latency: Option<Duration>,
+	/// Number of source chain blocks, that must be built on top of the block, before the relay
+	/// delivers messages from this block.
+	pub source_message_confirmations: u32,
+	/// If set, the relay is monitoring reorgs of both chains and alerts when reorg is deeper
+	/// than this number of blocks.
+	pub reorg_alert_depth: Option<u32>,
+	/// If set, the relay only delivers XCM messages with given instruction.
+	pub xcm_instruction_filter: Option<XcmInstructionFilter>,
+	/// Number of new source and target blocks that the relay must observe after connecting,
+	/// before it starts submitting transactions.
+	pub warm_up_blocks: u32,
+	/// Time that the relay waits after (re)connecting, before it starts submitting
+	/// transactions.
+	pub first_submit_delay: Duration,
+	/// If set, the relay escalates logs of races that have nothing to deliver for given number
+	/// of consecutive iterations.
+	pub inactivity_log_threshold: Option<u32>,
+	/// If true, correlation ids of relayed messages are attached to relay logs and spans.
+	pub correlate_messages: bool,
+	/// What to do with message that can't fit into the delivery transaction.
+	pub oversized_weight_policy: OversizedWeightPolicy,
+	/// If set, messages with larger declared dispatch weight are never delivered.
+	pub max_message_dispatch_weight: Option<Weight>,
+	/// If set, delivery is deferred while the companion lane, served by the same relay process,
+	/// is stalled.
+	pub companion_lane: Option<CompanionLane>,
+	/// If set, delivery is deferred while the target signer balance doesn't cover the delivery
+	/// transaction fee.
+	pub target_balance_guard: Option<BalanceGuard>,
+	/// If set, delivery confirmations are deferred while the source signer balance doesn't
+	/// cover the confirmation transaction fee.
+	pub source_confirmation_guard: Option<ConfirmationBalanceGuard>,
+	/// If set, the relay checks that the confirmation signer is eligible for rewards before
+	/// submitting confirmations.
+	pub source_reward_eligibility_guard: Option<RewardEligibilityGuard>,
+	/// If set, delivery confirmations are batched with confirmations of other lanes, served by
+	/// the same signer.
+	pub source_confirmations_batcher: Option<ConfirmationsBatcher<P, SourceClnt>>,
+	/// If set, delivery confirmations are deferred while the source signer has exhausted its
+	/// rate limit quota.
+	pub source_rate_limit_quota_guard: Option<RateLimitQuotaGuard>,
+	/// If set, delivery is deferred while the target chain fee multiplier is above the ceiling.
+	pub target_fee_multiplier_guard: Option<FeeMultiplierGuard>,
+	/// If set, delivery is deferred while the target chain runtime upgrade is settling.
+	pub target_runtime_upgrade_guard: Option<RuntimeUpgradeGuard>,
+	/// If set, the age of the source header, last synced to the target chain, is tracked.
+	pub target_synced_header_age_guard: Option<SyncedHeaderAgeGuard>,
+	/// If set and there's no source headers relay, the guard detects that source headers,
+	/// required by delivery, aren't synced to the target chain.
+	pub target_header_progress_guard: Option<HeaderProgressGuard<P::SourceChain, P::TargetChain>>,
+	/// If set, delivery batches are fitted into the weight headroom of target chain blocks.
+	pub target_block_weight_headroom: Option<BlockWeightHeadroom>,
+	/// If set, delivery is deferred while the target signer has exhausted its rate limit quota.
+	pub target_rate_limit_quota_guard: Option<RateLimitQuotaGuard>,
+	/// Format of messages proofs, accepted by the target chain.
+	pub messages_proof_format: StorageProofFormat,
+	/// Format of messages delivery proofs, accepted by the source chain.
+	pub delivery_proof_format: StorageProofFormat,
+	/// Messages throughput budget, shared by all lanes of the relay process.
+	pub throughput_budget: ThroughputBudget,
+	/// If set, delivery and confirmation transactions are held back while the bridge is paused.
+	pub global_pause_guard: Option<GlobalPauseGuard>,
+	/// If set, the relay holds back messages until there are enough of them to fill the
+	/// delivery transaction.
+	pub min_batch: Option<MinBatch>,
+	/// If set, the relay stays idle until there are enough pending messages.
+	pub pending_messages_gate: Option<PendingMessagesGate>,
+	/// If true, the delivery and confirmation races of the lane take turns talking to nodes.
+	pub serialize_races: bool,
+	/// If set, overrides level of relay logs of the lane.
+	pub log_level: Option<log::LevelFilter>,
+	/// If true, the category of the most recent error of the lane is exposed as a metric.
+	pub lane_error_state_metric: bool,
+	/// If true, dispatch weight, that has not been spent by delivered messages, is read from
+	/// target chain events and reported.
+	pub track_dispatch_refunds: bool,
+	/// Method that is used to find new messages at the source chain.
+	pub message_discovery: MessageDiscovery,
+	/// If set, the lane is idle while the messages pallet at either side is halted. The value
+	/// is the interval of checking whether the pallet is operational again.
+	pub paused_lane_poll_interval: Option<Duration>,
+	/// If set, messages proofs that aren't generated in this time are abandoned and generated
+	/// again later.
+	pub max_proof_generation_time: Option<Duration>,
+	/// Number of source blocks that must be built after the message is generated, before the
+	/// relay delivers it.
+	pub message_grace_period_blocks: u32,
+	/// If set, deliveries are throttled while the unrewarded relayers set at the target chain
+	/// is filled by at least given percent.
+	pub confirmations_priority_threshold: Option<u8>,
+	/// If set, the relay is halted when the next message can't be delivered after given number
+	/// of retries.
+	pub retry_budget: Option<RetryBudget>,
+	/// If set, the number of messages in the delivery transaction is tuned by results of
+	/// previous deliveries.
+	pub batch_tuner: Option<BatchAutoTuner>,
+	/// If set, messages are delivered one by one and every message is confirmed before the
+	/// next message is delivered.
+	pub deterministic: bool,
+	/// If set, the relay exits after given duration.
+	pub max_runtime: Option<Duration>,
+	/// If set, the relay activity is written to this sink.
+	pub scale_event_sink: Option<ScaleEventSink>,
 }
 
 /// Delivery transaction limits.
@@ -204,6 +382,11 @@ where
 	};
 	let (max_messages_in_single_batch, max_messages_weight_in_single_batch) =
 		(limits.max_messages_in_single_batch / 2, limits.max_messages_weight_in_single_batch / 2);
+	let (max_messages_in_single_batch, max_unconfirmed_nonces_at_target) = if params.deterministic {
+		(1, 1)
+	} else {
+		(max_messages_in_single_batch, P::SourceChain::MAX_UNCONFIRMED_MESSAGES_IN_CONFIRMATION_TX)
+	};
 
 	let source_client = params.source_client;
 	let target_client = params.target_client;
@@ -239,6 +422,264 @@ where
 		).as_secs_f64() / 60.0f64,
 	);
 
+	// messages are sent by the source instance and received by the target instance
+	crate::messages_pallet::ensure_messages_pallet_instance(
+		&source_client,
+		P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
+		params.lane_id,
+	)
+	.await?;
+	crate::messages_pallet::ensure_messages_pallet_instance(
+		&target_client,
+		P::SourceChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
+		params.lane_id,
+	)
+	.await?;
+	crate::messages_pallet::ensure_relay_storage_items(
+		&source_client,
+		P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
+	)
+	.await?;
+	crate::messages_pallet::ensure_relay_storage_items(
+		&target_client,
+		P::SourceChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
+	)
+	.await?;
+	crate::messages_pallet::ensure_compatible_messages_pallet_versions(
+		&source_client,
+		P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
+		&target_client,
+		P::SourceChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
+		params.pallet_version_mismatch,
+	)
+	.await?;
+	if let Some(backfill) = params.backfill {
+		crate::messages_backfill::ensure_backfill_depth::<P>(
+			&source_client,
+			target_client.clone(),
+			params.lane_id,
+			backfill,
+			params.control.clone(),
+		)
+		.await?;
+	}
+
+	if let Some(sole_relayer) = params.sole_relayer {
+		crate::messages_sole_relayer::spawn::<P>(
+			target_client.clone(),
+			params.lane_id,
+			relayer_id_at_source.clone(),
+			sole_relayer,
+			params.control.clone(),
+			&params.metrics_params.registry,
+		)?;
+	}
+
+	if let Some(competition_analytics_window) = params.competition_analytics_window {
+		crate::messages_competition::spawn::<P>(
+			target_client.clone(),
+			params.lane_id,
+			relayer_id_at_source.clone(),
+			competition_analytics_window,
+			&params.metrics_params.registry,
+		)?;
+	}
+
+	if let Some(reorg_alert_depth) = params.reorg_alert_depth {
+		let prefix = messages_relay::message_lane_loop::metrics_prefix::<MessageLaneAdapter<P>>(
+			&params.lane_id,
+		);
+		crate::reorg_monitor::spawn::<P::SourceChain>(
+			source_client.clone(),
+			format!("{prefix}_source"),
+			reorg_alert_depth,
+			&params.metrics_params.registry,
+		)?;
+		crate::reorg_monitor::spawn::<P::TargetChain>(
+			target_client.clone(),
+			format!("{prefix}_target"),
+			reorg_alert_depth,
+			&params.metrics_params.registry,
+		)?;
+	}
+
+	crate::messages_latency::spawn::<P>(
+		source_client.clone(),
+		target_client.clone(),
+		params.lane_id,
+		&params.metrics_params.registry,
+	)?;
+
+	// the same client may be used by several relays, so the metric is only spawned once
+	InFlightTransactionsMetric::<P::SourceChain>::new(source_client.in_flight_transactions())?
+		.register_and_spawn(&params.metrics_params.registry)?;
+	InFlightTransactionsMetric::<P::TargetChain>::new(target_client.in_flight_transactions())?
+		.register_and_spawn(&params.metrics_params.registry)?;
+	BadEndpointsMetric::<P::SourceChain>::new(source_client.endpoints())?
+		.register_and_spawn(&params.metrics_params.registry)?;
+	BadEndpointsMetric::<P::TargetChain>::new(target_client.endpoints())?
+		.register_and_spawn(&params.metrics_params.registry)?;
+
+	let relayer_ids_at_source = RoundRobin::new(
+		relayer_id_at_source,
+		params
+			.source_additional_signers
+			.iter()
+			.map(|signer| signer.public().into())
+			.collect(),
+	);
+
+	let confirmation_deadline = params.max_confirmation_latency.map(ConfirmationDeadline::new);
+
+	let fee_budget = match params.max_fees_per_hour {
+		Some(max_fees_per_hour) => {
+			let fee_budget = FeeBudget::new(
+				max_fees_per_hour,
+				Some(&messages_relay::message_lane_loop::metrics_prefix::<MessageLaneAdapter<P>>(
+					&params.lane_id,
+				)),
+			)?;
+			fee_budget.register(&params.metrics_params.registry)?;
+			fee_budget
+		},
+		None => FeeBudget::default(),
+	};
+	params
+		.control
+		.summary()
+		.lane(messages_relay::message_lane_loop::metrics_prefix::<MessageLaneAdapter<P>>(
+			&params.lane_id,
+		))
+		.track_fees(fee_budget.clone());
+	let fee_accounting =
+		FeeAccounting::new(Some(&messages_relay::message_lane_loop::metrics_prefix::<
+			MessageLaneAdapter<P>,
+		>(&params.lane_id)))?;
+	fee_accounting.register(&params.metrics_params.registry)?;
+	params
+		.control
+		.summary()
+		.lane(messages_relay::message_lane_loop::metrics_prefix::<MessageLaneAdapter<P>>(
+			&params.lane_id,
+		))
+		.track_fees_by_transaction_type(fee_accounting.clone());
+
+	let xcm_filter = params
+		.xcm_instruction_filter
+		.map(|filter| {
+			XcmMessagesFilter::new(
+				filter,
+				Some(&messages_relay::message_lane_loop::metrics_prefix::<MessageLaneAdapter<P>>(
+					&params.lane_id,
+				)),
+				&params.metrics_params.registry,
+			)
+		})
+		.transpose()?;
+	if let Some(ref confirmation_guard) = params.source_confirmation_guard {
+		confirmation_guard.register(&params.metrics_params.registry)?;
+	}
+	if let Some(ref fee_multiplier_guard) = params.target_fee_multiplier_guard {
+		fee_multiplier_guard.register(&params.metrics_params.registry)?;
+	}
+	if let Some(ref rate_limit_quota_guard) = params.source_rate_limit_quota_guard {
+		rate_limit_quota_guard.register(&params.metrics_params.registry)?;
+	}
+	if let Some(ref rate_limit_quota_guard) = params.target_rate_limit_quota_guard {
+		rate_limit_quota_guard.register(&params.metrics_params.registry)?;
+	}
+	if let Some(ref synced_header_age_guard) = params.target_synced_header_age_guard {
+		synced_header_age_guard.register(&params.metrics_params.registry)?;
+	}
+	let preempted_messages = Counter::new(
+		metric_name(
+			Some(&messages_relay::message_lane_loop::metrics_prefix::<MessageLaneAdapter<P>>(
+				&params.lane_id,
+			)),
+			"messages_preempted_by_other_relayers",
+		),
+		"Number of messages that have been delivered by other relayers after the relay has \
+		selected them for delivery, but before it has submitted the delivery transaction",
+	)?;
+	register(preempted_messages.clone(), &params.metrics_params.registry)?;
+	let preempted_confirmations = Counter::new(
+		metric_name(
+			Some(&messages_relay::message_lane_loop::metrics_prefix::<MessageLaneAdapter<P>>(
+				&params.lane_id,
+			)),
+			"confirmations_preempted_by_other_relayers",
+		),
+		"Number of delivery confirmations that have been dropped, because other relayers have \
+		confirmed the same deliveries before the relay has submitted the confirmation transaction",
+	)?;
+	register(preempted_confirmations.clone(), &params.metrics_params.registry)?;
+	let fee_estimation = FeeEstimation::new(
+		params.fee_estimation_fallback,
+		Some(&messages_relay::message_lane_loop::metrics_prefix::<MessageLaneAdapter<P>>(
+			&params.lane_id,
+		)),
+	)?;
+	fee_estimation.register(&params.metrics_params.registry)?;
+	let paused_lane_idler = params
+		.paused_lane_poll_interval
+		.map(|poll_interval| {
+			PausedLaneIdler::new(
+				poll_interval,
+				Some(&messages_relay::message_lane_loop::metrics_prefix::<MessageLaneAdapter<P>>(
+					&params.lane_id,
+				)),
+			)
+		})
+		.transpose()?;
+	if let Some(ref paused_lane_idler) = paused_lane_idler {
+		paused_lane_idler.register(&params.metrics_params.registry)?;
+	}
+	let proof_generation_limit = params
+		.max_proof_generation_time
+		.map(|max_time| {
+			ProofGenerationLimit::new(
+				max_time,
+				Some(&messages_relay::message_lane_loop::metrics_prefix::<MessageLaneAdapter<P>>(
+					&params.lane_id,
+				)),
+			)
+		})
+		.transpose()?;
+	if let Some(ref proof_generation_limit) = proof_generation_limit {
+		proof_generation_limit.register(&params.metrics_params.registry)?;
+	}
+	let error_state = if params.lane_error_state_metric {
+		let error_state =
+			LaneErrorState::new(Some(&messages_relay::message_lane_loop::metrics_prefix::<
+				MessageLaneAdapter<P>,
+			>(&params.lane_id)))?;
+		error_state.register(&params.metrics_params.registry)?;
+		Some(error_state)
+	} else {
+		None
+	};
+	let dispatch_refunds = if params.track_dispatch_refunds {
+		let dispatch_refunds =
+			DispatchRefunds::new(Some(&messages_relay::message_lane_loop::metrics_prefix::<
+				MessageLaneAdapter<P>,
+			>(&params.lane_id)))?;
+		dispatch_refunds.register(&params.metrics_params.registry)?;
+		params
+			.control
+			.summary()
+			.lane(messages_relay::message_lane_loop::metrics_prefix::<MessageLaneAdapter<P>>(
+				&params.lane_id,
+			))
+			.track_dispatch_refunds(dispatch_refunds.clone());
+		Some(DispatchRefundsReader::new(
+			P::SourceChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
+			params.lane_id,
+			dispatch_refunds,
+		))
+	} else {
+		None
+	};
+
 	messages_relay::message_lane_loop::run(
 		messages_relay::message_lane_loop::Params {
 			lane: params.lane_id,
@@ -248,36 +689,125 @@ where
 			delivery_params: messages_relay::message_lane_loop::MessageDeliveryParams {
 				max_unrewarded_relayer_entries_at_target:
 					P::SourceChain::MAX_UNREWARDED_RELAYERS_IN_CONFIRMATION_TX,
-				max_unconfirmed_nonces_at_target:
-					P::SourceChain::MAX_UNCONFIRMED_MESSAGES_IN_CONFIRMATION_TX,
+				max_unconfirmed_nonces_at_target,
 				max_messages_in_single_batch,
 				max_messages_weight_in_single_batch,
 				max_messages_size_in_single_batch,
+				fee_budget: fee_budget.clone(),
+				max_nonces_ahead: params.max_nonces_ahead,
+				log_backpressure: params.log_backpressure,
+				max_extrinsic_weight_at_target: P::TargetChain::max_extrinsic_weight(),
+				oversized_weight_policy: params.oversized_weight_policy,
+				max_message_dispatch_weight: params.max_message_dispatch_weight,
+				companion_lane: params.companion_lane,
+				throughput_budget: params.throughput_budget.lane(
+					messages_relay::message_lane_loop::metrics_prefix::<MessageLaneAdapter<P>>(
+						&params.lane_id,
+					),
+				),
+				// the batch can't be larger than the delivery transaction allows
+				min_batch: params.min_batch.map(|mut min_batch| {
+					min_batch.size = std::cmp::min(min_batch.size, max_messages_in_single_batch);
+					min_batch
+				}),
+				message_grace_period_blocks: params.message_grace_period_blocks,
+				confirmations_priority_threshold: params.confirmations_priority_threshold,
+				retry_budget: params.retry_budget,
+				// the tuner can't select more messages than the delivery transaction allows
+				batch_tuner: params.batch_tuner.map(|batch_tuner| {
+					let max_size =
+						std::cmp::min(batch_tuner.max_size, max_messages_in_single_batch);
+					BatchAutoTuner::new(std::cmp::min(batch_tuner.min_size, max_size), max_size)
+				}),
+				weight_headroom: params
+					.target_block_weight_headroom
+					.as_ref()
+					.map(BlockWeightHeadroom::headroom),
 			},
+			control: params.control.clone(),
+			warm_up_blocks: params.warm_up_blocks,
+			first_submit_delay: params.first_submit_delay,
+			inactivity_log_threshold: params.inactivity_log_threshold,
+			correlate_messages: params.correlate_messages,
+			confirmation_deadline: confirmation_deadline.clone(),
+			pending_messages_gate: params.pending_messages_gate,
+			serialize_races: params.serialize_races,
+			log_level: params.log_level,
+			error_state,
 		},
 		SubstrateMessagesSource::<P, _, _>::new(
 			source_client.clone(),
 			target_client.clone(),
 			params.lane_id,
 			params.source_transaction_params,
-			params.target_to_source_headers_relay,
+			params.source_additional_signers,
+			SubstrateMessagesSourceParams {
+				proxy: params.source_proxy,
+				target_to_source_headers_relay: params.target_to_source_headers_relay,
+				xcm_filter,
+				confirmation_guard: params.source_confirmation_guard.clone(),
+				confirmation_lag: params.confirmation_lag,
+				confirmation_deadline,
+				message_confirmations: params.source_message_confirmations,
+				event_sink: params.scale_event_sink.clone(),
+				paused_lane_idler: paused_lane_idler.clone(),
+				fee_accounting: Some(fee_accounting.clone()),
+				message_discovery: params.message_discovery,
+				proof_generation_limit,
+				preempted_confirmations: Some(preempted_confirmations),
+				reward_eligibility_guard: params.source_reward_eligibility_guard,
+				confirmations_batcher: params.source_confirmations_batcher,
+				rate_limit_quota_guard: params.source_rate_limit_quota_guard,
+				proof_format: params.messages_proof_format,
+				global_pause_guard: params.global_pause_guard.clone(),
+			},
 		),
 		SubstrateMessagesTarget::<P, _, _>::new(
 			target_client,
 			source_client,
 			params.lane_id,
-			relayer_id_at_source,
+			relayer_ids_at_source,
 			Some(params.target_transaction_params),
-			params.source_to_target_headers_relay,
+			SubstrateMessagesTargetParams {
+				proxy: params.target_proxy,
+				source_to_target_headers_relay: params.source_to_target_headers_relay,
+				fee_budget,
+				fee_safety_margin_percent: params.fee_safety_margin_percent,
+				balance_guard: params.target_balance_guard,
+				confirmation_guard: params.source_confirmation_guard,
+				fee_multiplier_guard: params.target_fee_multiplier_guard,
+				fee_estimation: Some(fee_estimation),
+				sync_confirmations: params.target_sync_confirmations,
+				preempted_messages: Some(preempted_messages),
+				control: params.control.clone(),
+				event_sink: params.scale_event_sink,
+				paused_lane_idler,
+				fee_accounting: Some(fee_accounting),
+				runtime_upgrade_guard: params.target_runtime_upgrade_guard,
+				synced_header_age_guard: params.target_synced_header_age_guard,
+				block_weight_headroom: params.target_block_weight_headroom,
+				rate_limit_quota_guard: params.target_rate_limit_quota_guard,
+				proof_format: params.delivery_proof_format,
+				global_pause_guard: params.global_pause_guard,
+				header_progress_guard: params.target_header_progress_guard,
+				dispatch_refunds,
+			},
 		),
 		{
 			GlobalMetrics::new()?.register_and_spawn(&params.metrics_params.registry)?;
 			params.metrics_params
 		},
-		futures::future::pending(),
+		match params.max_runtime {
+			Some(max_runtime) => async_std::task::sleep(max_runtime).boxed(),
+			None => futures::future::pending().boxed(),
+		},
 	)
-	.await
-	.map_err(Into::into)
+	.await?;
+
+	match params.control.halt_reason() {
+		Some(reason) => Err(anyhow::format_err!("{}", reason)),
+		None => Ok(()),
+	}
 }
 
 /// Deliver range of Substrate-to-Substrate messages. No checks are made to ensure that transaction
@@ -305,15 +835,16 @@ where
 			target_client.clone(),
 			lane_id,
 			source_transaction_params,
-			None,
+			Vec::new(),
+			Default::default(),
 		),
 		SubstrateMessagesTarget::<P, _, _>::new(
 			target_client,
 			source_client,
 			lane_id,
-			relayer_id_at_source,
+			RoundRobin::new(relayer_id_at_source, Vec::new()),
 			Some(target_transaction_params),
-			None,
+			Default::default(),
 		),
 		at_source_block,
 		range,
@@ -345,15 +876,16 @@ where
 			target_client.clone(),
 			lane_id,
 			source_transaction_params,
-			None,
+			Vec::new(),
+			Default::default(),
 		),
 		SubstrateMessagesTarget::<P, _, _>::new(
 			target_client,
 			source_client,
 			lane_id,
-			relayer_id_at_source,
-			None,
+			RoundRobin::new(relayer_id_at_source, Vec::new()),
 			None,
+			Default::default(),
 		),
 		at_target_block,
 	)
@@ -671,6 +1203,7 @@ where
 			nonce: Zero::zero(),
 			tip: Zero::zero(),
 			era: TransactionEra::Immortal,
+			priority: None,
 		},
 	)
 	.map_err(Into::into)
@@ -0,0 +1,287 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Message delivery latency monitoring.
+//!
+//! Source and target chains may have very different block times, so the latency in blocks
+//! of one chain can't be compared across bridges. Instead, the wall-clock latency is computed
+//! from block timestamps: it is the time between the timestamp of the source block where the
+//! message has been seen for the first time and the timestamp of the target block where it
+//! has been delivered. The number of target blocks between these two moments is exposed
+//! separately.
+
+use crate::messages_lane::{MessageLaneAdapter, SubstrateMessageLane};
+
+use bp_messages::{
+	storage_keys::{inbound_lane_data_key, outbound_lane_data_key},
+	ChainWithMessages as _, InboundLaneData, LaneId, MessageNonce, OutboundLaneData,
+};
+use bp_runtime::storage_value_key;
+use messages_relay::message_lane_loop::metrics_prefix;
+use relay_substrate_client::{AccountIdOf, Chain, Client, Error as SubstrateError, HashOf};
+use relay_utils::{
+	metrics::{metric_name, register, Gauge, Registry, F64, U64},
+	UniqueSaturatedInto,
+};
+use sp_runtime::traits::Header as _;
+use std::{collections::BTreeMap, time::Duration};
+
+/// Latency of the single message delivery.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageLatency {
+	/// Time between the source block timestamp, where the message has been seen, and the
+	/// target block timestamp, where it has been delivered.
+	pub wall_clock: Duration,
+	/// Number of target blocks between these two moments.
+	pub target_blocks: u64,
+}
+
+/// Moment when message has been seen at the source chain.
+#[derive(Clone, Copy, Debug)]
+struct GeneratedAt {
+	/// Timestamp of the source block (in milliseconds).
+	source_timestamp: u64,
+	/// Number of the best target block at the same moment.
+	target_block: u64,
+}
+
+/// Tracker of message delivery latencies.
+#[derive(Debug)]
+pub struct LatencyTracker {
+	latest_generated_nonce: MessageNonce,
+	latest_delivered_nonce: MessageNonce,
+	generated_at: BTreeMap<MessageNonce, GeneratedAt>,
+}
+
+impl LatencyTracker {
+	/// Create new tracker. Latencies of messages that are already generated are not tracked.
+	pub fn new(latest_generated_nonce: MessageNonce, latest_delivered_nonce: MessageNonce) -> Self {
+		LatencyTracker {
+			latest_generated_nonce,
+			latest_delivered_nonce,
+			generated_at: BTreeMap::new(),
+		}
+	}
+
+	/// Remember when new messages have been seen at the source chain.
+	pub fn on_generated(
+		&mut self,
+		latest_generated_nonce: MessageNonce,
+		source_timestamp: u64,
+		target_block: u64,
+	) {
+		for nonce in self.latest_generated_nonce + 1..=latest_generated_nonce {
+			self.generated_at.insert(nonce, GeneratedAt { source_timestamp, target_block });
+		}
+		self.latest_generated_nonce =
+			std::cmp::max(self.latest_generated_nonce, latest_generated_nonce);
+	}
+
+	/// Compute latencies of messages that have been delivered to the target chain and return
+	/// the maximal one.
+	pub fn on_delivered(
+		&mut self,
+		latest_delivered_nonce: MessageNonce,
+		target_timestamp: u64,
+		target_block: u64,
+	) -> Option<MessageLatency> {
+		let mut max_latency: Option<MessageLatency> = None;
+		for nonce in self.latest_delivered_nonce + 1..=latest_delivered_nonce {
+			let generated_at = match self.generated_at.remove(&nonce) {
+				Some(generated_at) => generated_at,
+				None => continue,
+			};
+			let latency = MessageLatency {
+				wall_clock: Duration::from_millis(
+					target_timestamp.saturating_sub(generated_at.source_timestamp),
+				),
+				target_blocks: target_block.saturating_sub(generated_at.target_block),
+			};
+			if max_latency.map(|max| latency.wall_clock > max.wall_clock).unwrap_or(true) {
+				max_latency = Some(latency);
+			}
+		}
+		self.latest_delivered_nonce =
+			std::cmp::max(self.latest_delivered_nonce, latest_delivered_nonce);
+		max_latency
+	}
+}
+
+/// State of the chain at its best block.
+struct BestBlockState {
+	number: u64,
+	timestamp: u64,
+	nonce: MessageNonce,
+}
+
+/// Start background task that is tracking message delivery latencies and exposes them
+/// as metrics.
+pub fn spawn<P: SubstrateMessageLane>(
+	source_client: impl Client<P::SourceChain>,
+	target_client: impl Client<P::TargetChain>,
+	lane_id: LaneId,
+	registry: &Registry,
+) -> anyhow::Result<()> {
+	let prefix = metrics_prefix::<MessageLaneAdapter<P>>(&lane_id);
+	let wall_clock_metric = Gauge::<F64>::new(
+		metric_name(Some(&prefix), "message_delivery_latency_seconds"),
+		"Time between timestamps of the source block, where the message has been seen, and the \
+		target block, where it has been delivered. Maximal value among the recently delivered \
+		messages",
+	)?;
+	register(wall_clock_metric.clone(), registry)?;
+	let blocks_metric = Gauge::<U64>::new(
+		metric_name(Some(&prefix), "message_delivery_latency_blocks"),
+		"Number of target blocks, built between the moment when message has been seen at the \
+		source chain and its delivery. Reported for the same message as the wall-clock latency",
+	)?;
+	register(blocks_metric.clone(), registry)?;
+
+	let poll_interval = std::cmp::min(
+		P::SourceChain::AVERAGE_BLOCK_INTERVAL,
+		P::TargetChain::AVERAGE_BLOCK_INTERVAL,
+	);
+	async_std::task::spawn(async move {
+		let mut tracker: Option<LatencyTracker> = None;
+		loop {
+			async_std::task::sleep(poll_interval).await;
+
+			let states = futures::try_join!(
+				read_source_state::<P>(&source_client, lane_id),
+				read_target_state::<P>(&target_client, lane_id),
+			);
+			let (source, target) = match states {
+				Ok(states) => states,
+				Err(e) => {
+					log::debug!(
+						target: "bridge",
+						"Failed to read {} -> {} lane {} state: {:?}",
+						P::SourceChain::NAME,
+						P::TargetChain::NAME,
+						hex::encode(lane_id),
+						e,
+					);
+					continue
+				},
+			};
+
+			let tracker =
+				tracker.get_or_insert_with(|| LatencyTracker::new(source.nonce, target.nonce));
+			tracker.on_generated(source.nonce, source.timestamp, target.number);
+			if let Some(latency) =
+				tracker.on_delivered(target.nonce, target.timestamp, target.number)
+			{
+				wall_clock_metric.set(latency.wall_clock.as_secs_f64());
+				blocks_metric.set(latency.target_blocks);
+			}
+		}
+	});
+
+	Ok(())
+}
+
+/// Read latest generated nonce and timestamp at the best source block.
+async fn read_source_state<P: SubstrateMessageLane>(
+	source_client: &impl Client<P::SourceChain>,
+	lane_id: LaneId,
+) -> Result<BestBlockState, SubstrateError> {
+	let best_header = source_client.best_header().await?;
+	let best_hash = best_header.hash();
+	let lane_data: Option<OutboundLaneData> = source_client
+		.storage_value(
+			best_hash,
+			outbound_lane_data_key(P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME, &lane_id),
+		)
+		.await?;
+	Ok(BestBlockState {
+		number: (*best_header.number()).unique_saturated_into(),
+		timestamp: read_timestamp(source_client, best_hash).await?,
+		nonce: lane_data.map(|data| data.latest_generated_nonce).unwrap_or(0),
+	})
+}
+
+/// Read latest delivered nonce and timestamp at the best target block.
+async fn read_target_state<P: SubstrateMessageLane>(
+	target_client: &impl Client<P::TargetChain>,
+	lane_id: LaneId,
+) -> Result<BestBlockState, SubstrateError> {
+	let best_header = target_client.best_header().await?;
+	let best_hash = best_header.hash();
+	let lane_data: Option<InboundLaneData<AccountIdOf<P::SourceChain>>> = target_client
+		.storage_value(
+			best_hash,
+			inbound_lane_data_key(P::SourceChain::WITH_CHAIN_MESSAGES_PALLET_NAME, &lane_id),
+		)
+		.await?;
+	Ok(BestBlockState {
+		number: (*best_header.number()).unique_saturated_into(),
+		timestamp: read_timestamp(target_client, best_hash).await?,
+		nonce: lane_data.map(|data| data.last_delivered_nonce()).unwrap_or(0),
+	})
+}
+
+/// Read timestamp (in milliseconds) of given block.
+pub(crate) async fn read_timestamp<C: Chain>(
+	client: &impl Client<C>,
+	at: HashOf<C>,
+) -> Result<u64, SubstrateError> {
+	Ok(client
+		.storage_value(at, storage_value_key("Timestamp", "Now"))
+		.await?
+		.unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn wall_clock_latency_is_computed_from_timestamps_with_asymmetric_block_times() {
+		// source chain has 12s blocks and target chain has 2s blocks
+		let source_timestamp = |block: u64| block * 12_000;
+		let target_timestamp = |block: u64| block * 2_000;
+		let mut tracker = LatencyTracker::new(10, 10);
+
+		// messages 11 and 12 are seen at source block 10, when target best block is 60
+		tracker.on_generated(12, source_timestamp(10), 60);
+		// message 13 is seen at source block 11, when target best block is 66
+		tracker.on_generated(13, source_timestamp(11), 66);
+
+		// message 11 is delivered at target block 63
+		assert_eq!(
+			tracker.on_delivered(11, target_timestamp(63), 63),
+			Some(MessageLatency { wall_clock: Duration::from_secs(6), target_blocks: 3 }),
+		);
+		// messages 12 and 13 are delivered at target block 69. The maximal latency is the
+		// latency of message 12
+		assert_eq!(
+			tracker.on_delivered(13, target_timestamp(69), 69),
+			Some(MessageLatency { wall_clock: Duration::from_secs(18), target_blocks: 9 }),
+		);
+		// nothing new is delivered
+		assert_eq!(tracker.on_delivered(13, target_timestamp(70), 70), None);
+	}
+
+	#[test]
+	fn messages_generated_before_tracker_is_started_are_ignored() {
+		let mut tracker = LatencyTracker::new(10, 5);
+		tracker.on_generated(11, 12_000, 6);
+		assert_eq!(
+			tracker.on_delivered(11, 14_000, 7),
+			Some(MessageLatency { wall_clock: Duration::from_secs(2), target_blocks: 1 }),
+		);
+	}
+}
@@ -29,15 +29,19 @@ use relay_substrate_client::{
 	AccountIdOf, BalanceOf, Chain, ChainWithBalances, ChainWithMessages, Client,
 	Error as SubstrateError, NonceOf,
 };
-use relay_utils::metrics::{MetricsParams, StandaloneMetric};
+use relay_utils::{
+	metrics::{MetricsParams, StandaloneMetric},
+	run_summary::RunSummary,
+};
 use sp_core::storage::StorageData;
 use sp_runtime::{FixedPointNumber, FixedU128};
 use std::{fmt::Debug, marker::PhantomData};
 
-/// Add relay accounts balance metrics.
+/// Add relay accounts balance metrics. Balances are also reported to the run `summary`.
 pub async fn add_relay_balances_metrics<C: ChainWithBalances, BC: ChainWithMessages>(
 	client: impl Client<C>,
 	metrics: &MetricsParams,
+	summary: &RunSummary,
 	relay_accounts: &Vec<TaggedAccount<AccountIdOf<C>>>,
 	lanes: &[LaneId],
 ) -> anyhow::Result<()>
@@ -79,6 +83,10 @@ where
 			format!("at_{}_relay_{}_balance", C::NAME, account.tag()),
 			format!("Balance of the {} relay account at the {}", account.tag(), C::NAME),
 		)?;
+		summary.track_balance(
+			format!("{} relay account at {}", account.tag(), C::NAME),
+			relay_account_balance_metric.shared_value_ref(),
+		);
 		relay_account_balance_metric.register_and_spawn(&metrics.registry)?;
 
 		if let Some(relayers_pallet_name) = BC::WITH_CHAIN_RELAYERS_PALLET_NAME {
@@ -0,0 +1,288 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Check of the bridge messages pallet instance, that the relay is built for.
+//!
+//! Runtimes, bridged with several chains, have several instances of the messages pallet. The
+//! relay is built for the concrete instance, so a wrong instance name in the chain definition
+//! is only noticed when the relay fails to read the lane state. Instead, the relay finds all
+//! messages pallet instances in the runtime metadata at startup and refuses to start if the
+//! runtime doesn't have the expected instance.
+//!
+//! Instances at both sides of the lane must also be compatible: e.g. the proof format may
+//! change between pallet versions. The relay compares declared (storage) versions of both
+//! instances at startup, so that the incompatibility is reported before the relay fails to
+//! deliver anything.
+//!
+//! Finally, reading a storage item that the runtime doesn't have returns nothing, which the relay
+//! can't tell from "no messages". So all storage items that the relay reads are looked up in the
+//! metadata too, and the relay refuses to start if any of them is missing.
+
+use bp_messages::{
+	storage_keys::{inbound_lane_data_key, outbound_lane_data_key},
+	LaneId,
+};
+use relay_substrate_client::{
+	metadata::{read_pallet_storage_version, read_pallets_metadata, MESSAGES_PALLET_STORAGE_ITEMS},
+	Chain, Client,
+};
+use strum::{EnumString, VariantNames};
+
+/// What relayer does when messages pallet instances at both sides of the lane have different
+/// declared versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, VariantNames)]
+#[strum(serialize_all = "kebab_case")]
+pub enum PalletVersionMismatchAction {
+	/// Refuse to start the relay.
+	Refuse,
+	/// Log warning and start the relay.
+	Warn,
+}
+
+/// Declared version of the messages pallet instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DeclaredVersion<'a> {
+	chain: &'a str,
+	pallet_name: &'a str,
+	version: u16,
+}
+
+/// Ensure that the runtime has the messages pallet instance with given name.
+///
+/// If it doesn't, the error lists instances that are serving the `lane`. If none of instances
+/// is serving it, all messages pallet instances of the runtime are listed.
+pub async fn ensure_messages_pallet_instance<C: Chain>(
+	client: &impl Client<C>,
+	pallet_name: &str,
+	lane: LaneId,
+) -> anyhow::Result<()> {
+	let at = client.best_finalized_header_hash().await?;
+	let metadata = match read_pallets_metadata(client, at).await {
+		Ok(metadata) => metadata,
+		Err(e) => {
+			log::warn!(
+				target: "bridge",
+				"Failed to read {} runtime metadata: {:?}. Messages pallet instance {} is not \
+				checked",
+				C::NAME,
+				e,
+				pallet_name,
+			);
+			return Ok(())
+		},
+	};
+
+	let instances = metadata.messages_pallets();
+	if instances.iter().any(|instance| instance == pallet_name) {
+		return Ok(())
+	}
+
+	let mut serving_instances = Vec::new();
+	for instance in &instances {
+		let inbound_lane = client.raw_storage_value(at, inbound_lane_data_key(instance, &lane));
+		let outbound_lane = client.raw_storage_value(at, outbound_lane_data_key(instance, &lane));
+		if inbound_lane.await?.is_some() || outbound_lane.await?.is_some() {
+			serving_instances.push(instance.clone());
+		}
+	}
+
+	Err(anyhow::format_err!(
+		"{}",
+		missing_instance_error(C::NAME, pallet_name, lane, &instances, &serving_instances)
+	))
+}
+
+/// Ensure that the runtime has all storage items that the messages relay reads: storage items of
+/// the messages pallet instance with given name and accounts of transaction signers.
+pub async fn ensure_relay_storage_items<C: Chain>(
+	client: &impl Client<C>,
+	pallet_name: &str,
+) -> anyhow::Result<()> {
+	let at = client.best_finalized_header_hash().await?;
+	let metadata = match read_pallets_metadata(client, at).await {
+		Ok(metadata) => metadata,
+		Err(e) => {
+			log::warn!(
+				target: "bridge",
+				"Failed to read {} runtime metadata: {:?}. Storage items are not checked",
+				C::NAME,
+				e,
+			);
+			return Ok(())
+		},
+	};
+
+	let missing_items = metadata.missing_storage_items(&relay_storage_items(pallet_name));
+	if missing_items.is_empty() {
+		return Ok(())
+	}
+
+	Err(anyhow::format_err!(
+		"{} runtime has no storage items that the relay reads: {}",
+		C::NAME,
+		missing_items.join(", "),
+	))
+}
+
+/// (pallet name, storage item) pairs that the messages relay reads.
+fn relay_storage_items(pallet_name: &str) -> Vec<(&str, &str)> {
+	MESSAGES_PALLET_STORAGE_ITEMS
+		.iter()
+		.map(|item| (pallet_name, *item))
+		.chain(std::iter::once(("System", "Account")))
+		.collect()
+}
+
+/// Ensure that the messages pallet instance at the source chain and the instance at the target
+/// chain have the same declared version.
+pub async fn ensure_compatible_messages_pallet_versions<SC: Chain, TC: Chain>(
+	source_client: &impl Client<SC>,
+	source_pallet_name: &str,
+	target_client: &impl Client<TC>,
+	target_pallet_name: &str,
+	action: PalletVersionMismatchAction,
+) -> anyhow::Result<()> {
+	let source_at = source_client.best_finalized_header_hash().await?;
+	let target_at = target_client.best_finalized_header_hash().await?;
+	let source_version =
+		read_pallet_storage_version(source_client, source_at, source_pallet_name).await?;
+	let target_version =
+		read_pallet_storage_version(target_client, target_at, target_pallet_name).await?;
+
+	check_pallet_versions(
+		DeclaredVersion {
+			chain: SC::NAME,
+			pallet_name: source_pallet_name,
+			version: source_version,
+		},
+		DeclaredVersion {
+			chain: TC::NAME,
+			pallet_name: target_pallet_name,
+			version: target_version,
+		},
+		action,
+	)
+}
+
+/// Compare declared versions of messages pallet instances and act accordingly.
+fn check_pallet_versions(
+	source: DeclaredVersion,
+	target: DeclaredVersion,
+	action: PalletVersionMismatchAction,
+) -> anyhow::Result<()> {
+	if source.version == target.version {
+		return Ok(())
+	}
+
+	let error = format!(
+		"{} messages pallet {} has version {}, but {} messages pallet {} has version {}",
+		source.chain,
+		source.pallet_name,
+		source.version,
+		target.chain,
+		target.pallet_name,
+		target.version,
+	);
+	match action {
+		PalletVersionMismatchAction::Refuse =>
+			Err(anyhow::format_err!("{error}. Refusing to start the relay")),
+		PalletVersionMismatchAction::Warn => {
+			log::warn!(target: "bridge", "{error}. The relay may fail to deliver messages");
+			Ok(())
+		},
+	}
+}
+
+/// Error message, explaining that the runtime doesn't have the messages pallet instance.
+fn missing_instance_error(
+	chain: &str,
+	pallet_name: &str,
+	lane: LaneId,
+	instances: &[String],
+	serving_instances: &[String],
+) -> String {
+	let prefix = format!("{chain} runtime has no messages pallet instance {pallet_name}");
+	match (instances.len(), serving_instances.len()) {
+		(0, _) => format!("{prefix}. The runtime has no messages pallet instances at all"),
+		(_, 0) => format!(
+			"{prefix}. None of its instances is serving lane {}. Candidates: {}",
+			hex::encode(lane),
+			instances.join(", "),
+		),
+		_ => format!(
+			"{prefix}. Lane {} is served by: {}",
+			hex::encode(lane),
+			serving_instances.join(", "),
+		),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn missing_instance_error_lists_candidates() {
+		let lane = LaneId([0, 0, 0, 1]);
+		let instances = vec!["BridgeRococoMessages".to_string(), "BridgeWestendMessages".into()];
+
+		assert_eq!(
+			missing_instance_error("Test", "BridgeKusamaMessages", lane, &[], &[]),
+			"Test runtime has no messages pallet instance BridgeKusamaMessages. The runtime has \
+			no messages pallet instances at all",
+		);
+		assert_eq!(
+			missing_instance_error("Test", "BridgeKusamaMessages", lane, &instances, &[]),
+			"Test runtime has no messages pallet instance BridgeKusamaMessages. None of its \
+			instances is serving lane 00000001. Candidates: BridgeRococoMessages, \
+			BridgeWestendMessages",
+		);
+		assert_eq!(
+			missing_instance_error(
+				"Test",
+				"BridgeKusamaMessages",
+				lane,
+				&instances,
+				&instances[1..]
+			),
+			"Test runtime has no messages pallet instance BridgeKusamaMessages. Lane 00000001 is \
+			served by: BridgeWestendMessages",
+		);
+	}
+
+	#[test]
+	fn mismatched_pallet_versions_are_refused() {
+		let source =
+			DeclaredVersion { chain: "Source", pallet_name: "BridgeTargetMessages", version: 1 };
+		let target =
+			DeclaredVersion { chain: "Target", pallet_name: "BridgeSourceMessages", version: 2 };
+
+		assert_eq!(
+			check_pallet_versions(source, target, PalletVersionMismatchAction::Refuse)
+				.unwrap_err()
+				.to_string(),
+			"Source messages pallet BridgeTargetMessages has version 1, but Target messages \
+			pallet BridgeSourceMessages has version 2. Refusing to start the relay",
+		);
+		assert!(check_pallet_versions(source, target, PalletVersionMismatchAction::Warn).is_ok());
+		assert!(check_pallet_versions(
+			source,
+			DeclaredVersion { version: 1, ..target },
+			PalletVersionMismatchAction::Refuse
+		)
+		.is_ok());
+	}
+}
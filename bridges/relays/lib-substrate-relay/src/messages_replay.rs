@@ -0,0 +1,469 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Replay of the messages delivery decision at given source block.
+//!
+//! The relay only knows the current state of both chains, so it can't tell why some message
+//! hasn't been delivered in the past. The replay reads the state, that the relay would have
+//! seen at given source block, from archive nodes and repeats checks of the delivery strategy
+//! against it.
+
+use crate::{
+	finality_base::best_synced_header_id,
+	messages_lane::SubstrateMessageLane,
+	messages_latency::read_timestamp,
+	messages_target::{read_fee_multiplier, BalanceGuard},
+};
+
+use bp_messages::{
+	storage_keys::{inbound_lane_data_key, outbound_lane_data_key},
+	ChainWithMessages as _, InboundLaneData, LaneId, MessageNonce, OutboundLaneData,
+	UnrewardedRelayersState,
+};
+use pallet_transaction_payment::Multiplier;
+use relay_substrate_client::{
+	AccountIdOf, BlockNumberOf, Chain, Client, Error as SubstrateError, HeaderOf,
+};
+use relay_utils::UniqueSaturatedInto;
+use sp_runtime::traits::Header as _;
+use std::{fmt, ops::RangeInclusive};
+
+/// Delivery strategy configuration, used to replay the decision.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayConfig {
+	/// Maximal number of unrewarded relayer entries at the target inbound lane.
+	pub max_unrewarded_relayer_entries_at_target: MessageNonce,
+	/// Maximal number of unconfirmed messages at the target inbound lane.
+	pub max_unconfirmed_nonces_at_target: MessageNonce,
+	/// Maximal number of messages in the delivery transaction.
+	pub max_messages_in_single_batch: MessageNonce,
+	/// Delivery is deferred while the target fee multiplier is above this ceiling.
+	pub max_fee_multiplier: Option<Multiplier>,
+	/// Delivery is deferred while the relayer balance at the target chain is below this buffer.
+	pub balance_buffer: Option<u128>,
+}
+
+/// State of both chains, that the relay would have seen at the replayed source block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayedState {
+	/// Number of the replayed source block.
+	pub source_block: u64,
+	/// Latest nonce, generated at the replayed source block.
+	pub latest_generated_nonce: MessageNonce,
+	/// Number of the target block, that has been the best finalized block at that moment.
+	pub target_block: u64,
+	/// Number of the best finalized source block, known to the target chain.
+	pub best_finalized_source_block_at_target: Option<u64>,
+	/// Latest nonce, generated at the best finalized source block, known to the target chain.
+	/// Only these messages may be proved to the target chain.
+	pub latest_provable_nonce: MessageNonce,
+	/// Latest nonce, which delivery is confirmed at the best finalized source block, known to
+	/// the target chain.
+	pub latest_confirmed_nonce_at_source: MessageNonce,
+	/// Latest nonce, which delivery confirmation is known to the target chain.
+	pub latest_confirmed_nonce_at_target: MessageNonce,
+	/// State of the unrewarded relayers set at the target inbound lane.
+	pub unrewarded_relayers: UnrewardedRelayersState,
+	/// Fee multiplier of the target chain.
+	pub fee_multiplier: Multiplier,
+	/// Free balance of the relayer at the target chain, if the relayer is known.
+	pub relayer_balance: Option<u128>,
+}
+
+/// Replayed delivery decision.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReplayedDecision {
+	/// Messages would have been delivered.
+	Deliver {
+		/// Nonces of messages in the delivery transaction.
+		nonces: RangeInclusive<MessageNonce>,
+		/// Whether the outbound lane state proof would have been included into the transaction.
+		outbound_state_proof_required: bool,
+	},
+	/// Messages wouldn't have been delivered.
+	Defer(DeferReason),
+}
+
+/// The reason why messages wouldn't have been delivered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeferReason {
+	/// All generated messages have already been delivered.
+	NothingToDeliver,
+	/// Messages are generated at source blocks, that the target chain doesn't know yet. The
+	/// source headers relay needs to bring them first.
+	SourceHeaderMissingAtTarget,
+	/// The unrewarded relayers set at the target chain is full and the relay can't prove
+	/// enough delivery confirmations to free it. Confirmations race needs to do its job first.
+	UnrewardedRelayersLimitReached,
+	/// There are too many unconfirmed messages at the target chain.
+	UnconfirmedMessagesLimitReached,
+	/// The target fee multiplier has been above the ceiling.
+	FeeMultiplierAboveCeiling,
+	/// The relayer balance at the target chain hasn't covered the buffer.
+	InsufficientBalance,
+}
+
+impl fmt::Display for DeferReason {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			DeferReason::NothingToDeliver =>
+				write!(f, "all generated messages have already been delivered"),
+			DeferReason::SourceHeaderMissingAtTarget => write!(
+				f,
+				"messages are generated at source headers that the target chain doesn't know yet. \
+				Source headers need to be relayed first"
+			),
+			DeferReason::UnrewardedRelayersLimitReached => write!(
+				f,
+				"the unrewarded relayers set at the target chain is full and there are not enough \
+				delivery confirmations to prove. Delivery confirmations need to be relayed first"
+			),
+			DeferReason::UnconfirmedMessagesLimitReached => write!(
+				f,
+				"there are too many unconfirmed messages at the target chain. Delivery \
+				confirmations need to be relayed first"
+			),
+			DeferReason::FeeMultiplierAboveCeiling =>
+				write!(f, "the target chain fee multiplier is above the ceiling"),
+			DeferReason::InsufficientBalance =>
+				write!(f, "the relayer balance at the target chain doesn't cover the buffer"),
+		}
+	}
+}
+
+/// Replayed state and the delivery decision.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecisionReplay {
+	/// State of both chains.
+	pub state: ReplayedState,
+	/// Decision, made by the delivery strategy.
+	pub decision: ReplayedDecision,
+}
+
+impl fmt::Display for DecisionReplay {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let state = &self.state;
+		writeln!(f, "Source block: {}", state.source_block)?;
+		writeln!(f, "Latest generated nonce: {}", state.latest_generated_nonce)?;
+		writeln!(f, "Target block: {}", state.target_block)?;
+		match state.best_finalized_source_block_at_target {
+			Some(number) => writeln!(f, "Best finalized source block at target: {number}")?,
+			None => writeln!(f, "Best finalized source block at target: unknown")?,
+		}
+		writeln!(f, "Latest provable nonce: {}", state.latest_provable_nonce)?;
+		writeln!(f, "Latest delivered nonce: {}", state.unrewarded_relayers.last_delivered_nonce)?;
+		writeln!(
+			f,
+			"Latest confirmed nonce: {} at source, {} at target",
+			state.latest_confirmed_nonce_at_source, state.latest_confirmed_nonce_at_target,
+		)?;
+		writeln!(
+			f,
+			"Unrewarded relayers: {} entries, {} messages",
+			state.unrewarded_relayers.unrewarded_relayer_entries,
+			state.unrewarded_relayers.total_messages,
+		)?;
+		writeln!(f, "Fee multiplier: {}", state.fee_multiplier)?;
+		if let Some(relayer_balance) = state.relayer_balance {
+			writeln!(f, "Relayer balance: {relayer_balance}")?;
+		}
+		match self.decision {
+			ReplayedDecision::Deliver { ref nonces, outbound_state_proof_required } => write!(
+				f,
+				"Decision: deliver messages {:?}{}",
+				nonces,
+				if outbound_state_proof_required { " with outbound lane state proof" } else { "" },
+			),
+			ReplayedDecision::Defer(ref reason) => write!(f, "Decision: defer delivery, {reason}"),
+		}
+	}
+}
+
+/// Repeat checks of the delivery strategy against the replayed state.
+pub fn replay_decision(state: &ReplayedState, config: &ReplayConfig) -> ReplayedDecision {
+	let latest_delivered_nonce = state.unrewarded_relayers.last_delivered_nonce;
+	if state.latest_generated_nonce <= latest_delivered_nonce {
+		return ReplayedDecision::Defer(DeferReason::NothingToDeliver)
+	}
+	if state.latest_provable_nonce <= latest_delivered_nonce {
+		return ReplayedDecision::Defer(DeferReason::SourceHeaderMissingAtTarget)
+	}
+
+	// the same checks as in the delivery strategy: the target chain rejects messages if the
+	// unrewarded relayers set is full, unless we prove that some entries may be pruned
+	let unrewarded_relayers = &state.unrewarded_relayers;
+	let unrewarded_limit_reached = unrewarded_relayers.unrewarded_relayer_entries >=
+		config.max_unrewarded_relayer_entries_at_target ||
+		unrewarded_relayers.total_messages >= config.max_unconfirmed_nonces_at_target;
+	if unrewarded_limit_reached {
+		let number_of_rewards_being_proved = state
+			.latest_confirmed_nonce_at_source
+			.saturating_sub(state.latest_confirmed_nonce_at_target);
+		if number_of_rewards_being_proved < unrewarded_relayers.messages_in_oldest_entry {
+			return ReplayedDecision::Defer(DeferReason::UnrewardedRelayersLimitReached)
+		}
+	}
+
+	let outbound_state_proof_required =
+		state.latest_confirmed_nonce_at_target < state.latest_confirmed_nonce_at_source;
+	let future_confirmed_nonce_at_target = if outbound_state_proof_required {
+		state.latest_confirmed_nonce_at_source
+	} else {
+		state.latest_confirmed_nonce_at_target
+	};
+	let max_nonces = latest_delivered_nonce
+		.checked_sub(future_confirmed_nonce_at_target)
+		.and_then(|diff| config.max_unconfirmed_nonces_at_target.checked_sub(diff))
+		.unwrap_or_default();
+	let max_nonces = std::cmp::min(max_nonces, config.max_messages_in_single_batch);
+	if max_nonces == 0 {
+		return ReplayedDecision::Defer(DeferReason::UnconfirmedMessagesLimitReached)
+	}
+
+	// these checks are performed right before submitting the delivery transaction
+	if config
+		.max_fee_multiplier
+		.map(|max_fee_multiplier| state.fee_multiplier > max_fee_multiplier)
+		.unwrap_or(false)
+	{
+		return ReplayedDecision::Defer(DeferReason::FeeMultiplierAboveCeiling)
+	}
+	if let (Some(buffer), Some(relayer_balance)) = (config.balance_buffer, state.relayer_balance) {
+		if relayer_balance < buffer {
+			return ReplayedDecision::Defer(DeferReason::InsufficientBalance)
+		}
+	}
+
+	let begin = latest_delivered_nonce + 1;
+	let end = std::cmp::min(state.latest_provable_nonce, begin + max_nonces - 1);
+	ReplayedDecision::Deliver { nonces: begin..=end, outbound_state_proof_required }
+}
+
+/// Read the state, that the relay would have seen at given source block, and replay the
+/// delivery decision. Both clients must be connected to archive nodes.
+pub async fn replay_delivery_decision<P: SubstrateMessageLane>(
+	source_client: &impl Client<P::SourceChain>,
+	target_client: &impl Client<P::TargetChain>,
+	lane: LaneId,
+	source_block: BlockNumberOf<P::SourceChain>,
+	relayer: Option<AccountIdOf<P::TargetChain>>,
+	config: &ReplayConfig,
+) -> anyhow::Result<DecisionReplay> {
+	let source_header = source_client
+		.header_by_number(source_block)
+		.await
+		.map_err(|e| historical_state_error::<P::SourceChain>(source_block, e))?;
+	let source_outbound_lane = read_outbound_lane::<P>(source_client, &source_header, lane).await?;
+
+	// the relay reads the target state at the best finalized target block. We are looking for
+	// the latest target block that has been produced before the source block
+	let source_timestamp = read_timestamp(source_client, source_header.hash())
+		.await
+		.map_err(|e| historical_state_error::<P::SourceChain>(source_block, e))?;
+	let target_header = target_header_at_timestamp(target_client, source_timestamp).await?;
+	let target_block = *target_header.number();
+	let target_hash = target_header.hash();
+	let inbound_lane: InboundLaneData<AccountIdOf<P::SourceChain>> = target_client
+		.storage_value(
+			target_hash,
+			inbound_lane_data_key(P::SourceChain::WITH_CHAIN_MESSAGES_PALLET_NAME, &lane),
+		)
+		.await
+		.map_err(|e| historical_state_error::<P::TargetChain>(target_block, e))?
+		.unwrap_or_default();
+	let best_finalized_source_at_target =
+		best_synced_header_id::<P::SourceChain, P::TargetChain>(target_client, target_hash)
+			.await
+			.map_err(|e| historical_state_error::<P::TargetChain>(target_block, e))?;
+	let fee_multiplier = read_fee_multiplier(target_client, target_hash)
+		.await
+		.map_err(|e| historical_state_error::<P::TargetChain>(target_block, e))?;
+	let relayer_balance = match relayer {
+		Some(relayer) => Some(
+			BalanceGuard::new::<P::TargetChain>(&relayer, 0)
+				.free_balance_at(target_client, target_hash)
+				.await
+				.map_err(|e| historical_state_error::<P::TargetChain>(target_block, e))?,
+		),
+		None => None,
+	};
+
+	// only messages, generated at source headers that are known to the target chain, may be
+	// proved. Messages, generated after the replayed block, are not interesting
+	let provable_outbound_lane = match best_finalized_source_at_target {
+		Some(ref id) if id.0 < source_block => {
+			let provable_header = source_client
+				.header_by_hash(id.1)
+				.await
+				.map_err(|e| historical_state_error::<P::SourceChain>(id.0, e))?;
+			read_outbound_lane::<P>(source_client, &provable_header, lane).await?
+		},
+		Some(_) => source_outbound_lane.clone(),
+		None => OutboundLaneData::default(),
+	};
+
+	let state = ReplayedState {
+		source_block: source_block.unique_saturated_into(),
+		latest_generated_nonce: source_outbound_lane.latest_generated_nonce,
+		target_block: target_block.unique_saturated_into(),
+		best_finalized_source_block_at_target: best_finalized_source_at_target
+			.map(|id| id.0.unique_saturated_into()),
+		latest_provable_nonce: provable_outbound_lane.latest_generated_nonce,
+		latest_confirmed_nonce_at_source: provable_outbound_lane.latest_received_nonce,
+		latest_confirmed_nonce_at_target: inbound_lane.last_confirmed_nonce,
+		unrewarded_relayers: (&inbound_lane).into(),
+		fee_multiplier,
+		relayer_balance,
+	};
+	let decision = replay_decision(&state, config);
+	Ok(DecisionReplay { state, decision })
+}
+
+/// Read the outbound lane state at given source header.
+async fn read_outbound_lane<P: SubstrateMessageLane>(
+	source_client: &impl Client<P::SourceChain>,
+	at: &HeaderOf<P::SourceChain>,
+	lane: LaneId,
+) -> anyhow::Result<OutboundLaneData> {
+	Ok(source_client
+		.storage_value(
+			at.hash(),
+			outbound_lane_data_key(P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME, &lane),
+		)
+		.await
+		.map_err(|e| historical_state_error::<P::SourceChain>(*at.number(), e))?
+		.unwrap_or_default())
+}
+
+/// Find the latest finalized header with timestamp that is not after given timestamp.
+async fn target_header_at_timestamp<C: Chain>(
+	client: &impl Client<C>,
+	timestamp: u64,
+) -> anyhow::Result<HeaderOf<C>> {
+	let timestamp_at = |number: BlockNumberOf<C>| async move {
+		let hash = client
+			.header_hash_by_number(number)
+			.await
+			.map_err(|e| historical_state_error::<C>(number, e))?;
+		read_timestamp(client, hash)
+			.await
+			.map_err(|e| historical_state_error::<C>(number, e))
+	};
+
+	// the genesis block has zero timestamp, so it is always a valid candidate
+	let mut low: u64 = 0;
+	let mut high: u64 = client.best_finalized_header_number().await?.unique_saturated_into();
+	while low < high {
+		let middle = low + (high - low + 1) / 2;
+		if timestamp_at(middle.unique_saturated_into()).await? <= timestamp {
+			low = middle;
+		} else {
+			high = middle - 1;
+		}
+	}
+
+	let number: BlockNumberOf<C> = low.unique_saturated_into();
+	client
+		.header_by_number(number)
+		.await
+		.map_err(|e| historical_state_error::<C>(number, e))
+}
+
+/// Error, returned when historical state of the chain can't be read.
+fn historical_state_error<C: Chain>(
+	number: BlockNumberOf<C>,
+	error: SubstrateError,
+) -> anyhow::Error {
+	anyhow::format_err!(
+		"Failed to read {} state at block {:?}: {:?}. Historical state is only available at \
+		archive nodes. Make sure that the {} node runs with `--state-pruning archive`",
+		C::NAME,
+		number,
+		error,
+		C::NAME,
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn config() -> ReplayConfig {
+		ReplayConfig {
+			max_unrewarded_relayer_entries_at_target: 4,
+			max_unconfirmed_nonces_at_target: 16,
+			max_messages_in_single_batch: 4,
+			max_fee_multiplier: Some(Multiplier::from_u32(2)),
+			balance_buffer: Some(100),
+		}
+	}
+
+	fn state() -> ReplayedState {
+		ReplayedState {
+			source_block: 100,
+			latest_generated_nonce: 20,
+			target_block: 200,
+			best_finalized_source_block_at_target: Some(90),
+			latest_provable_nonce: 18,
+			latest_confirmed_nonce_at_source: 8,
+			latest_confirmed_nonce_at_target: 8,
+			unrewarded_relayers: UnrewardedRelayersState {
+				unrewarded_relayer_entries: 2,
+				messages_in_oldest_entry: 1,
+				total_messages: 2,
+				last_delivered_nonce: 10,
+			},
+			fee_multiplier: Multiplier::from_u32(1),
+			relayer_balance: Some(1_000),
+		}
+	}
+
+	#[test]
+	fn replayed_relay_delivers_provable_messages() {
+		assert_eq!(
+			replay_decision(&state(), &config()),
+			ReplayedDecision::Deliver { nonces: 11..=14, outbound_state_proof_required: false },
+		);
+	}
+
+	#[test]
+	fn replayed_relay_explains_why_messages_are_not_delivered() {
+		let defer = |state: ReplayedState| match replay_decision(&state, &config()) {
+			ReplayedDecision::Defer(reason) => reason,
+			decision => panic!("Unexpected decision: {decision:?}"),
+		};
+
+		let mut delivered = state();
+		delivered.unrewarded_relayers.last_delivered_nonce = 20;
+		assert_eq!(defer(delivered), DeferReason::NothingToDeliver);
+
+		let mut unprovable = state();
+		unprovable.latest_provable_nonce = 10;
+		assert_eq!(defer(unprovable), DeferReason::SourceHeaderMissingAtTarget);
+
+		let mut full_unrewarded_set = state();
+		full_unrewarded_set.unrewarded_relayers.unrewarded_relayer_entries = 4;
+		assert_eq!(defer(full_unrewarded_set), DeferReason::UnrewardedRelayersLimitReached);
+
+		let mut congested = state();
+		congested.fee_multiplier = Multiplier::from_u32(3);
+		assert_eq!(defer(congested), DeferReason::FeeMultiplierAboveCeiling);
+
+		let mut poor = state();
+		poor.relayer_balance = Some(50);
+		assert_eq!(defer(poor), DeferReason::InsufficientBalance);
+	}
+}
@@ -0,0 +1,202 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Detection of the case when our relayer is the only active relayer of the lane.
+//!
+//! The target chain remembers who has delivered messages, until deliveries are confirmed at
+//! the source chain. So if all recent deliveries, recorded in the inbound lane state, have
+//! been made by our relayer account, the lane has a single point of failure.
+
+use crate::messages_lane::{MessageLaneAdapter, SubstrateMessageLane};
+
+use bp_messages::{
+	storage_keys::inbound_lane_data_key, ChainWithMessages as _, InboundLaneData, LaneId,
+	MessageNonce,
+};
+use messages_relay::message_lane_loop::metrics_prefix;
+use relay_substrate_client::{AccountIdOf, Chain, Client, Error as SubstrateError};
+use relay_utils::{
+	control::RelayControl,
+	metrics::{metric_name, register, Gauge, Registry, U64},
+};
+use std::time::Duration;
+use strum::{EnumString, VariantNames};
+
+/// Interval between two consecutive checks.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What relayer does when it detects that it is the only active relayer of the lane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, VariantNames)]
+#[strum(serialize_all = "kebab_case")]
+pub enum SoleRelayerAction {
+	/// Log error and set the `is_sole_relayer` metric.
+	Alert,
+	/// Alert and pause the relay. It shall be resumed using the control socket.
+	Pause,
+}
+
+/// Sole relayer detection parameters.
+#[derive(Debug, Clone)]
+pub struct SoleRelayerParams {
+	/// Number of latest delivered messages that are checked.
+	pub lookback_messages: MessageNonce,
+	/// What to do when relayer detects that it is the only active relayer.
+	pub action: SoleRelayerAction,
+}
+
+/// Returns true if latest `lookback_messages` messages, known to the inbound lane, have all
+/// been delivered by the given relayer.
+///
+/// Returns false if there are no unconfirmed deliveries at the lane.
+pub fn is_sole_relayer<AccountId: PartialEq>(
+	lane_data: &InboundLaneData<AccountId>,
+	relayer: &AccountId,
+	lookback_messages: MessageNonce,
+) -> bool {
+	let mut checked_messages: MessageNonce = 0;
+	for entry in lane_data.relayers.iter().rev() {
+		if checked_messages >= lookback_messages {
+			break
+		}
+		if entry.relayer != *relayer {
+			return false
+		}
+		checked_messages = checked_messages.saturating_add(entry.messages.total_messages());
+	}
+
+	checked_messages != 0
+}
+
+/// Start background task that periodically checks whether our relayer is the only active
+/// relayer of the lane.
+pub fn spawn<P: SubstrateMessageLane>(
+	target_client: impl Client<P::TargetChain>,
+	lane_id: LaneId,
+	relayer_id_at_source: AccountIdOf<P::SourceChain>,
+	params: SoleRelayerParams,
+	control: RelayControl,
+	registry: &Registry,
+) -> anyhow::Result<()> {
+	let metric = Gauge::<U64>::new(
+		metric_name(Some(&metrics_prefix::<MessageLaneAdapter<P>>(&lane_id)), "is_sole_relayer"),
+		"Whether all recent message deliveries have been made by this relayer",
+	)?;
+	register(metric.clone(), registry)?;
+
+	async_std::task::spawn(async move {
+		let mut was_sole_relayer = false;
+		loop {
+			match read_inbound_lane_data::<P>(&target_client, lane_id).await {
+				Ok(lane_data) => {
+					let is_sole_relayer = is_sole_relayer(
+						&lane_data,
+						&relayer_id_at_source,
+						params.lookback_messages,
+					);
+					metric.set(is_sole_relayer as u64);
+
+					if is_sole_relayer && !was_sole_relayer {
+						log::error!(
+							target: "bridge",
+							"All recent {} -> {} message deliveries at lane {} have been made \
+							by our relayer {:?}. The lane has no redundancy",
+							P::SourceChain::NAME,
+							P::TargetChain::NAME,
+							hex::encode(lane_id),
+							relayer_id_at_source,
+						);
+						if params.action == SoleRelayerAction::Pause {
+							log::error!(
+								target: "bridge",
+								"Pausing {} -> {} messages relay. Resume it using the control socket",
+								P::SourceChain::NAME,
+								P::TargetChain::NAME,
+							);
+							control.pause();
+						}
+					} else if !is_sole_relayer && was_sole_relayer {
+						log::info!(
+							target: "bridge",
+							"Other relayers are delivering {} -> {} messages at lane {} again",
+							P::SourceChain::NAME,
+							P::TargetChain::NAME,
+							hex::encode(lane_id),
+						);
+					}
+					was_sole_relayer = is_sole_relayer;
+				},
+				Err(e) => log::debug!(
+					target: "bridge",
+					"Failed to read {} -> {} inbound lane {} state: {:?}",
+					P::SourceChain::NAME,
+					P::TargetChain::NAME,
+					hex::encode(lane_id),
+					e,
+				),
+			}
+
+			async_std::task::sleep(CHECK_INTERVAL).await;
+		}
+	});
+
+	Ok(())
+}
+
+/// Read inbound lane state at the best finalized target header.
+pub(crate) async fn read_inbound_lane_data<P: SubstrateMessageLane>(
+	target_client: &impl Client<P::TargetChain>,
+	lane_id: LaneId,
+) -> Result<InboundLaneData<AccountIdOf<P::SourceChain>>, SubstrateError> {
+	let best_finalized_hash = target_client.best_finalized_header_hash().await?;
+	Ok(target_client
+		.storage_value(
+			best_finalized_hash,
+			inbound_lane_data_key(P::SourceChain::WITH_CHAIN_MESSAGES_PALLET_NAME, &lane_id),
+		)
+		.await?
+		.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use bp_messages::{DeliveredMessages, UnrewardedRelayer};
+
+	fn lane_data(relayers: Vec<(u64, MessageNonce, MessageNonce)>) -> InboundLaneData<u64> {
+		InboundLaneData {
+			relayers: relayers
+				.into_iter()
+				.map(|(relayer, begin, end)| UnrewardedRelayer {
+					relayer,
+					messages: DeliveredMessages { begin, end },
+				})
+				.collect(),
+			last_confirmed_nonce: 0,
+		}
+	}
+
+	#[test]
+	fn sole_relayer_is_detected() {
+		// no deliveries => not a sole relayer
+		assert!(!is_sole_relayer(&lane_data(vec![]), &1, 10));
+		// all deliveries are ours
+		assert!(is_sole_relayer(&lane_data(vec![(1, 1, 5), (1, 6, 10)]), &1, 10));
+		// other relayer has delivered messages within the window
+		assert!(!is_sole_relayer(&lane_data(vec![(2, 1, 5), (1, 6, 10)]), &1, 10));
+		// other relayer has delivered messages outside of the window
+		assert!(is_sole_relayer(&lane_data(vec![(2, 1, 5), (1, 6, 10)]), &1, 5));
+	}
+}
@@ -20,12 +20,19 @@
 
 use crate::{
 	finality_base::best_synced_header_id,
+	messages_confirmations_batch::ConfirmationsBatcher,
+	messages_discovery::{EventsDiscovery, MessageDiscovery},
+	messages_global_pause::GlobalPauseGuard,
 	messages_lane::{
 		BatchProofTransaction, MessageLaneAdapter, ReceiveMessagesDeliveryProofCallBuilder,
 		SubstrateMessageLane,
 	},
+	messages_latency::read_timestamp,
+	messages_target::{estimate_transaction_fee, BalanceGuard, RateLimitQuotaGuard},
 	on_demand::OnDemandRelay,
-	TransactionParams,
+	relayer_registration::{read_registration, reward_ineligibility_reason},
+	scale_events::{RelayEvent, ScaleEventSink},
+	ProxyParams, RoundRobin, TransactionParams,
 };
 
 use async_std::sync::Arc;
@@ -35,26 +42,43 @@ use bp_messages::{
 	ChainWithMessages as _, InboundMessageDetails, LaneId, MessageNonce, MessagePayload,
 	MessagesOperatingMode, OutboundLaneData, OutboundMessageDetails,
 };
-use bp_runtime::{BasicOperatingMode, HeaderIdProvider};
+use bp_runtime::{BasicOperatingMode, HeaderId, HeaderIdProvider};
 use bridge_runtime_common::messages::target::FromBridgedChainMessagesProof;
 use codec::Encode;
 use frame_support::weights::Weight;
 use messages_relay::{
 	message_lane::{MessageLane, SourceHeaderIdOf, TargetHeaderIdOf},
 	message_lane_loop::{
-		ClientState, MessageDetails, MessageDetailsMap, MessageProofParameters, SourceClient,
-		SourceClientState,
+		ClientState, ConfirmationDeadline, MessageDetails, MessageDetailsMap,
+		MessageProofParameters, SourceClient, SourceClientState,
 	},
 };
 use num_traits::Zero;
 use relay_substrate_client::{
-	AccountIdOf, AccountKeyPairOf, BalanceOf, Chain, ChainWithMessages, Client,
-	Error as SubstrateError, HashOf, HeaderIdOf, TransactionEra, TransactionTracker,
-	UnsignedTransaction,
+	storage_proof::{prove_storage_in_format, StorageProofFormat},
+	xcm_filter::XcmInstructionFilter,
+	AccountIdOf, AccountKeyPairOf, BalanceOf, CallOf, Chain, ChainWithBalances, ChainWithMessages,
+	ChainWithTransactions, Client, Error as SubstrateError, HashOf, HeaderIdOf, TransactionEra,
+	TransactionTracker, UnsignedTransaction,
+};
+use relay_utils::{
+	fee_accounting::{FeeAccounting, TransactionType},
+	metrics::{metric_name, register, Counter, Metric, PrometheusError, Registry, F64, U64},
+	relay_loop::Client as RelayClient,
+	MaybeConnectionError, UniqueSaturatedInto,
+};
+use sp_core::{storage::StorageKey, ByteArray, Pair};
+use sp_runtime::traits::AtLeast32BitUnsigned;
+use std::{
+	collections::BTreeMap,
+	future::Future,
+	ops::RangeInclusive,
+	sync::atomic::{AtomicBool, AtomicU64, Ordering},
+	time::Duration,
 };
-use relay_utils::relay_loop::Client as RelayClient;
-use sp_core::Pair;
-use std::ops::RangeInclusive;
+
+/// Number of recent source blocks, used to compute the observed source block time.
+const OBSERVED_BLOCK_TIME_WINDOW: u32 = 64;
 
 /// Intermediate message proof returned by the source Substrate node. Includes everything
 /// required to submit to the target node: cumulative dispatch weight of bundled messages and
@@ -62,37 +86,538 @@ use std::ops::RangeInclusive;
 pub type SubstrateMessagesProof<C> = (Weight, FromBridgedChainMessagesProof<HashOf<C>>);
 type MessagesToRefine<'a> = Vec<(MessagePayload, &'a mut OutboundMessageDetails)>;
 
+/// Filter of messages, delivered by the relay.
+///
+/// The messages pallet only accepts messages in nonce order, so the relay can't skip messages
+/// that don't match the filter. Instead, it stops at the first non-matching message and waits
+/// until the message is delivered by some other relayer. If there are no other relayers, the
+/// lane is stalled.
+#[derive(Clone, Debug)]
+pub struct XcmMessagesFilter {
+	filter: XcmInstructionFilter,
+	undecodable_messages: Counter<U64>,
+	/// The greatest nonce of undecodable message that has been counted. The same message may
+	/// be checked several times, but it is only counted once.
+	counted_undecodable_nonce: Arc<AtomicU64>,
+}
+
+impl XcmMessagesFilter {
+	/// Create new filter and register its metric.
+	pub fn new(
+		filter: XcmInstructionFilter,
+		prefix: Option<&str>,
+		registry: &Registry,
+	) -> Result<Self, PrometheusError> {
+		let undecodable_messages = Counter::new(
+			metric_name(prefix, "undecodable_xcm_messages"),
+			"Number of messages with payload that can't be decoded as XCM message",
+		)?;
+		register(undecodable_messages.clone(), registry)?;
+		Ok(XcmMessagesFilter {
+			filter,
+			undecodable_messages,
+			counted_undecodable_nonce: Default::default(),
+		})
+	}
+
+	/// Returns true if the message with given payload matches the filter. Messages that can't
+	/// be decoded as XCM messages never match.
+	fn matches(&self, lane: LaneId, nonce: MessageNonce, payload: &[u8]) -> bool {
+		match self.filter.matches(payload) {
+			Some(matches) => matches,
+			None => {
+				if self.counted_undecodable_nonce.fetch_max(nonce, Ordering::SeqCst) < nonce {
+					self.undecodable_messages.inc();
+					log::warn!(
+						target: "bridge",
+						"Payload of message {:?}/{} can't be decoded as XCM message. Treating it \
+						as not matching the {} instruction filter",
+						lane,
+						nonce,
+						self.filter,
+					);
+				}
+				false
+			},
+		}
+	}
+}
+
+/// Detector of the situation when the relayer can't pay for the delivery confirmation
+/// transaction at the source chain.
+///
+/// Relayer rewards for delivered messages are only registered when the delivery is confirmed
+/// at the source chain. While the relayer can't afford the confirmation transaction, rewards
+/// pile up unclaimed, so the guard may also pause delivery until the signer is topped up.
+#[derive(Clone, Debug)]
+pub struct ConfirmationBalanceGuard {
+	/// Balance guards of confirmation transaction signers, keyed by their public keys.
+	balance_guards: Arc<BTreeMap<Vec<u8>, BalanceGuard>>,
+	/// Whether delivery is paused while the confirmation transaction is unaffordable.
+	pause_delivery: bool,
+	/// True if the latest confirmation transaction has been unaffordable.
+	is_unaffordable: Arc<AtomicBool>,
+	unaffordable_confirmations: Counter<U64>,
+}
+
+impl ConfirmationBalanceGuard {
+	/// Create guard for given signers of confirmation transactions. The `buffer` is the
+	/// balance that must be left at the signer account after paying the transaction fee.
+	pub fn new<C: ChainWithBalances>(
+		signers: &[AccountKeyPairOf<C>],
+		buffer: u128,
+		pause_delivery: bool,
+		prefix: Option<&str>,
+	) -> Result<Self, PrometheusError>
+	where
+		AccountIdOf<C>: From<<AccountKeyPairOf<C> as Pair>::Public>,
+	{
+		let unaffordable_confirmations = Counter::new(
+			metric_name(prefix, "unaffordable_delivery_confirmations"),
+			"Number of delivery confirmation transactions that have been deferred, because the \
+			relayer couldn't pay for them",
+		)?;
+		let balance_guards = signers
+			.iter()
+			.map(|signer| {
+				let public = signer.public();
+				(public.to_raw_vec(), BalanceGuard::new::<C>(&public.into(), buffer))
+			})
+			.collect();
+		Ok(ConfirmationBalanceGuard {
+			balance_guards: Arc::new(balance_guards),
+			pause_delivery,
+			is_unaffordable: Default::default(),
+			unaffordable_confirmations,
+		})
+	}
+
+	/// Reserve the existential deposit at all signer accounts, so that paying for the
+	/// confirmation transaction never leads to the account being reaped.
+	pub fn with_existential_deposit(mut self, existential_deposit: u128) -> Self {
+		self.balance_guards = Arc::new(
+			self.balance_guards
+				.iter()
+				.map(|(public, balance_guard)| {
+					(
+						public.clone(),
+						balance_guard.clone().with_existential_deposit(existential_deposit),
+					)
+				})
+				.collect(),
+		);
+		self
+	}
+
+	/// Returns true if delivery is paused, because the latest delivery confirmation
+	/// transaction has been unaffordable.
+	pub fn is_delivery_paused(&self) -> bool {
+		self.pause_delivery && self.is_unaffordable.load(Ordering::SeqCst)
+	}
+
+	/// Ensure that the signer can pay for the confirmation transaction with given call.
+	async fn ensure_affordable<C: ChainWithTransactions>(
+		&self,
+		client: &impl Client<C>,
+		signer: &AccountKeyPairOf<C>,
+		call: CallOf<C>,
+	) -> Result<(), SubstrateError> {
+		let balance_guard = match self.balance_guards.get(&signer.public().to_raw_vec()) {
+			Some(balance_guard) => balance_guard,
+			None => return Ok(()),
+		};
+		let fee: u128 = estimate_transaction_fee::<C>(client, signer, call)
+			.await?
+			.unique_saturated_into();
+		let free_balance = balance_guard.free_balance(client).await?;
+		let result = balance_guard.ensure_sufficient::<C>(free_balance, fee);
+		if result.is_err() {
+			self.unaffordable_confirmations.inc();
+		}
+		self.is_unaffordable.store(result.is_err(), Ordering::SeqCst);
+		result
+	}
+}
+
+impl Metric for ConfirmationBalanceGuard {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		register(self.unaffordable_confirmations.clone(), registry)?;
+		Ok(())
+	}
+}
+
+/// Check of the relayer reward-claim eligibility, performed before submitting the delivery
+/// confirmation transaction.
+///
+/// The bridge relayers pallet at the source chain only pays rewards to relayers that are
+/// eligible, so the guard warns when the confirmation signer isn't. It may also skip the
+/// confirmation, since it wouldn't pay out.
+#[derive(Clone, Debug)]
+pub struct RewardEligibilityGuard {
+	/// Name of the bridge relayers pallet at the source chain.
+	relayers_pallet_name: &'static str,
+	/// Whether confirmations are skipped while the signer is not eligible for rewards.
+	skip_ineligible: bool,
+}
+
+impl RewardEligibilityGuard {
+	/// Create guard that reads relayer registrations from the given bridge relayers pallet.
+	pub fn new(relayers_pallet_name: &'static str, skip_ineligible: bool) -> Self {
+		RewardEligibilityGuard { relayers_pallet_name, skip_ineligible }
+	}
+
+	/// Ensure that the relayer is eligible for rewards at the best block of the chain.
+	///
+	/// Ineligible relayer is only an error if confirmations of ineligible relayers are skipped.
+	async fn ensure_eligible<C: Chain>(
+		&self,
+		client: &impl Client<C>,
+		relayer: &AccountIdOf<C>,
+	) -> Result<(), SubstrateError> {
+		let best_id = client.best_header().await?.id();
+		let registration =
+			read_registration::<C>(client, self.relayers_pallet_name, relayer, best_id.hash())
+				.await?;
+		let reason = match reward_ineligibility_reason(registration.as_ref(), best_id.number()) {
+			Some(reason) => reason,
+			None => return Ok(()),
+		};
+
+		log::warn!(
+			target: "bridge",
+			"Relayer {:?} is not eligible for rewards at {}: {}. {}",
+			relayer,
+			C::NAME,
+			reason,
+			if self.skip_ineligible {
+				"Skipping delivery confirmation"
+			} else {
+				"Delivery confirmation won't pay out"
+			},
+		);
+		if self.skip_ineligible {
+			return Err(SubstrateError::Custom(format!(
+				"Relayer {:?} is not eligible for rewards at {}: {}",
+				relayer,
+				C::NAME,
+				reason,
+			)))
+		}
+		Ok(())
+	}
+}
+
+/// Idler of the lane, served by the halted messages pallet.
+///
+/// Without the idler, the halted pallet is an error and the relay keeps retrying its state
+/// reads with growing delays. With the idler, the lane is idle while the pallet (at either side)
+/// is halted: its operating mode is polled with given interval and the lane is resumed as
+/// soon as the pallet is operational again.
+#[derive(Clone, Debug)]
+pub struct PausedLaneIdler {
+	poll_interval: Duration,
+	paused_seconds: Counter<F64>,
+}
+
+impl PausedLaneIdler {
+	/// Create idler, polling the pallet operating mode with given interval.
+	pub fn new(poll_interval: Duration, prefix: Option<&str>) -> Result<Self, PrometheusError> {
+		Ok(PausedLaneIdler {
+			poll_interval,
+			paused_seconds: Counter::new(
+				metric_name(prefix, "lane_paused_seconds"),
+				"Total time that the lane has been idle, because the messages pallet has been \
+				halted",
+			)?,
+		})
+	}
+
+	/// Wait until the messages pallet at `AtChain`, serving the lane with `WithChain`, is active.
+	pub(crate) async fn wait_until_pallet_active<AtChain, WithChain, AtChainClient>(
+		&self,
+		client: &AtChainClient,
+		lane: LaneId,
+	) -> Result<(), SubstrateError>
+	where
+		AtChain: Chain,
+		WithChain: ChainWithMessages,
+		AtChainClient: Client<AtChain>,
+	{
+		self.wait_until_active(AtChain::NAME, lane, || {
+			ensure_messages_pallet_active::<AtChain, WithChain, _>(client)
+		})
+		.await
+	}
+
+	/// Poll `ensure_active` until it reports that the pallet isn't halted.
+	async fn wait_until_active<F: Future<Output = Result<(), SubstrateError>>>(
+		&self,
+		chain: &str,
+		lane: LaneId,
+		ensure_active: impl Fn() -> F,
+	) -> Result<(), SubstrateError> {
+		let mut is_paused = false;
+		loop {
+			match ensure_active().await {
+				Ok(()) => {
+					if is_paused {
+						log::info!(
+							target: "bridge",
+							"Messages pallet at {}, serving lane {}, is operational again. \
+							Resuming the lane",
+							chain,
+							hex::encode(lane),
+						);
+					}
+					return Ok(())
+				},
+				Err(SubstrateError::BridgePalletIsHalted) => {
+					if !is_paused {
+						log::info!(
+							target: "bridge",
+							"Messages pallet at {}, serving lane {}, is halted. Idling the lane \
+							until it is resumed",
+							chain,
+							hex::encode(lane),
+						);
+						is_paused = true;
+					}
+					async_std::task::sleep(self.poll_interval).await;
+					self.paused_seconds.inc_by(self.poll_interval.as_secs_f64());
+				},
+				Err(e) => return Err(e),
+			}
+		}
+	}
+}
+
+impl Metric for PausedLaneIdler {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		register(self.paused_seconds.clone(), registry)?;
+		Ok(())
+	}
+}
+
+/// Limit of the messages proof generation time.
+///
+/// The proof, generated by the slow or overloaded source node, may be stale by the time it is
+/// submitted. If the generation takes longer than allowed, the batch is abandoned and the proof
+/// is generated again later.
+#[derive(Clone, Debug)]
+pub struct ProofGenerationLimit {
+	max_time: Duration,
+	timeouts: Counter<U64>,
+}
+
+impl ProofGenerationLimit {
+	/// Create limit with given maximal proof generation time.
+	pub fn new(max_time: Duration, prefix: Option<&str>) -> Result<Self, PrometheusError> {
+		Ok(ProofGenerationLimit {
+			max_time,
+			timeouts: Counter::new(
+				metric_name(prefix, "messages_proof_generation_timeouts"),
+				"Number of messages proofs, abandoned because their generation has taken too long",
+			)?,
+		})
+	}
+}
+
+impl Metric for ProofGenerationLimit {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		register(self.timeouts.clone(), registry)?;
+		Ok(())
+	}
+}
+
+/// Optional parameters of the Substrate messages source.
+///
+/// The default value has no guards, discovers messages using storage reads and uses the legacy
+/// proof format.
+pub struct SubstrateMessagesSourceParams<P: SubstrateMessageLane, SourceClnt> {
+	/// If set, confirmation calls are dispatched using the proxy.
+	pub proxy: Option<ProxyParams<P::SourceChain>>,
+	/// On-demand relay of target headers to the source chain.
+	pub target_to_source_headers_relay:
+		Option<Arc<dyn OnDemandRelay<P::TargetChain, P::SourceChain>>>,
+	/// If set, only messages that match the filter are delivered.
+	pub xcm_filter: Option<XcmMessagesFilter>,
+	/// If set, confirmations are deferred while the signer can't pay for them.
+	pub confirmation_guard: Option<ConfirmationBalanceGuard>,
+	/// If set, confirmations are only built against target headers that have been synced to
+	/// the source chain at least that long ago, unless the `confirmation_deadline` is
+	/// approaching.
+	pub confirmation_lag: Option<Duration>,
+	/// Deadline of delivery confirmations.
+	pub confirmation_deadline: Option<ConfirmationDeadline>,
+	/// Messages are only read from source blocks that have at least that many descendants.
+	pub message_confirmations: u32,
+	/// Sink of submitted confirmation events.
+	pub event_sink: Option<ScaleEventSink>,
+	/// If set, the lane is idle while the messages pallet is halted.
+	pub paused_lane_idler: Option<PausedLaneIdler>,
+	/// Estimated fees of confirmations are reported there.
+	pub fee_accounting: Option<FeeAccounting>,
+	/// Method that is used to find new messages.
+	pub message_discovery: MessageDiscovery,
+	/// If set, messages proofs that aren't generated in time are abandoned.
+	pub proof_generation_limit: Option<ProofGenerationLimit>,
+	/// Counter of confirmations that have been submitted by other relayers in the meantime.
+	pub preempted_confirmations: Option<Counter<U64>>,
+	/// If set, the signer eligibility for rewards is checked before submitting confirmations.
+	pub reward_eligibility_guard: Option<RewardEligibilityGuard>,
+	/// If set, confirmations are batched with confirmations of other lanes.
+	pub confirmations_batcher: Option<ConfirmationsBatcher<P, SourceClnt>>,
+	/// If set, confirmations are deferred while the signer has exhausted its quota.
+	pub rate_limit_quota_guard: Option<RateLimitQuotaGuard>,
+	/// Format of messages proofs.
+	pub proof_format: StorageProofFormat,
+	/// If set, confirmations are held back while the bridge is paused.
+	pub global_pause_guard: Option<GlobalPauseGuard>,
+}
+
+impl<P: SubstrateMessageLane, SourceClnt> Default for SubstrateMessagesSourceParams<P, SourceClnt> {
+	fn default() -> Self {
+		SubstrateMessagesSourceParams {
+			proxy: None,
+			target_to_source_headers_relay: None,
+			xcm_filter: None,
+			confirmation_guard: None,
+			confirmation_lag: None,
+			confirmation_deadline: None,
+			message_confirmations: 0,
+			event_sink: None,
+			paused_lane_idler: None,
+			fee_accounting: None,
+			message_discovery: MessageDiscovery::Storage,
+			proof_generation_limit: None,
+			preempted_confirmations: None,
+			reward_eligibility_guard: None,
+			confirmations_batcher: None,
+			rate_limit_quota_guard: None,
+			proof_format: StorageProofFormat::Legacy,
+			global_pause_guard: None,
+		}
+	}
+}
+
 /// Substrate client as Substrate messages source.
 pub struct SubstrateMessagesSource<P: SubstrateMessageLane, SourceClnt, TargetClnt> {
 	source_client: SourceClnt,
 	target_client: TargetClnt,
 	lane_id: LaneId,
 	transaction_params: TransactionParams<AccountKeyPairOf<P::SourceChain>>,
+	signers: RoundRobin<AccountKeyPairOf<P::SourceChain>>,
+	proxy: Option<ProxyParams<P::SourceChain>>,
 	target_to_source_headers_relay: Option<Arc<dyn OnDemandRelay<P::TargetChain, P::SourceChain>>>,
+	xcm_filter: Option<XcmMessagesFilter>,
+	confirmation_guard: Option<ConfirmationBalanceGuard>,
+	confirmation_lag: Option<Duration>,
+	confirmation_deadline: Option<ConfirmationDeadline>,
+	message_confirmations: u32,
+	event_sink: Option<ScaleEventSink>,
+	paused_lane_idler: Option<PausedLaneIdler>,
+	fee_accounting: Option<FeeAccounting>,
+	events_discovery: Option<EventsDiscovery<P::SourceChain>>,
+	proof_generation_limit: Option<ProofGenerationLimit>,
+	preempted_confirmations: Option<Counter<U64>>,
+	reward_eligibility_guard: Option<RewardEligibilityGuard>,
+	confirmations_batcher: Option<ConfirmationsBatcher<P, SourceClnt>>,
+	rate_limit_quota_guard: Option<RateLimitQuotaGuard>,
+	proof_format: StorageProofFormat,
+	global_pause_guard: Option<GlobalPauseGuard>,
 }
 
 impl<P: SubstrateMessageLane, SourceClnt: Client<P::SourceChain>, TargetClnt>
 	SubstrateMessagesSource<P, SourceClnt, TargetClnt>
 {
-	/// Create new Substrate headers source.
+	/// Create new Substrate headers source. Transactions are signed by the `transaction_params`
+	/// signer and `additional_signers` in turns. Optional guards and knobs of the source are
+	/// configured by the `params`.
 	pub fn new(
 		source_client: SourceClnt,
 		target_client: TargetClnt,
 		lane_id: LaneId,
 		transaction_params: TransactionParams<AccountKeyPairOf<P::SourceChain>>,
-		target_to_source_headers_relay: Option<
-			Arc<dyn OnDemandRelay<P::TargetChain, P::SourceChain>>,
-		>,
+		additional_signers: Vec<AccountKeyPairOf<P::SourceChain>>,
+		params: SubstrateMessagesSourceParams<P, SourceClnt>,
 	) -> Self {
+		let signers = RoundRobin::new(transaction_params.signer.clone(), additional_signers);
+		let events_discovery = match params.message_discovery {
+			MessageDiscovery::Storage => None,
+			MessageDiscovery::Events =>
+				Some(EventsDiscovery::new(P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME, lane_id)),
+		};
 		SubstrateMessagesSource {
 			source_client,
 			target_client,
 			lane_id,
 			transaction_params,
-			target_to_source_headers_relay,
+			signers,
+			proxy: params.proxy,
+			target_to_source_headers_relay: params.target_to_source_headers_relay,
+			xcm_filter: params.xcm_filter,
+			confirmation_guard: params.confirmation_guard,
+			confirmation_lag: params.confirmation_lag,
+			confirmation_deadline: params.confirmation_deadline,
+			message_confirmations: params.message_confirmations,
+			event_sink: params.event_sink,
+			paused_lane_idler: params.paused_lane_idler,
+			fee_accounting: params.fee_accounting,
+			events_discovery,
+			proof_generation_limit: params.proof_generation_limit,
+			preempted_confirmations: params.preempted_confirmations,
+			reward_eligibility_guard: params.reward_eligibility_guard,
+			confirmations_batcher: params.confirmations_batcher,
+			rate_limit_quota_guard: params.rate_limit_quota_guard,
+			proof_format: params.proof_format,
+			global_pause_guard: params.global_pause_guard,
 		}
 	}
 
+	/// Ensure that the delivery of messages up to given nonce hasn't been confirmed by other
+	/// relayer.
+	async fn ensure_delivery_not_confirmed(
+		&self,
+		latest_confirmed_nonce: MessageNonce,
+	) -> Result<(), SubstrateError> {
+		let best_block_hash = self.source_client.best_header_hash().await?;
+		let latest_received_nonce = self
+			.source_client
+			.storage_value::<OutboundLaneData>(
+				best_block_hash,
+				outbound_lane_data_key(
+					P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
+					&self.lane_id,
+				),
+			)
+			.await?
+			.map(|data| data.latest_received_nonce)
+			.unwrap_or(0);
+		if !is_delivery_confirmed(latest_confirmed_nonce, latest_received_nonce) {
+			return Ok(())
+		}
+
+		// the race selects the new proof when it reads the new source state
+		if let Some(ref preempted_confirmations) = self.preempted_confirmations {
+			preempted_confirmations.inc();
+		}
+		log::info!(
+			target: "bridge",
+			"Dropping delivery confirmation of {} -> {} messages up to {}: messages up to {} have \
+			been confirmed by other relayer",
+			P::SourceChain::NAME,
+			P::TargetChain::NAME,
+			latest_confirmed_nonce,
+			latest_received_nonce,
+		);
+		Err(SubstrateError::Custom(format!(
+			"Delivery of {} -> {} messages up to {} is already confirmed",
+			P::SourceChain::NAME,
+			P::TargetChain::NAME,
+			latest_confirmed_nonce,
+		)))
+	}
+
 	/// Read outbound lane state from the on-chain storage at given block.
 	async fn outbound_lane_data(
 		&self,
@@ -111,8 +636,20 @@ impl<P: SubstrateMessageLane, SourceClnt: Client<P::SourceChain>, TargetClnt>
 
 	/// Ensure that the messages pallet at source chain is active.
 	async fn ensure_pallet_active(&self) -> Result<(), SubstrateError> {
-		ensure_messages_pallet_active::<P::SourceChain, P::TargetChain, _>(&self.source_client)
-			.await
+		match self.paused_lane_idler {
+			Some(ref paused_lane_idler) =>
+				paused_lane_idler
+					.wait_until_pallet_active::<P::SourceChain, P::TargetChain, _>(
+						&self.source_client,
+						self.lane_id,
+					)
+					.await,
+			None =>
+				ensure_messages_pallet_active::<P::SourceChain, P::TargetChain, _>(
+					&self.source_client,
+				)
+				.await,
+		}
 	}
 }
 
@@ -125,7 +662,25 @@ impl<P: SubstrateMessageLane, SourceClnt: Clone, TargetClnt: Clone> Clone
 			target_client: self.target_client.clone(),
 			lane_id: self.lane_id,
 			transaction_params: self.transaction_params.clone(),
+			signers: self.signers.clone(),
+			proxy: self.proxy.clone(),
 			target_to_source_headers_relay: self.target_to_source_headers_relay.clone(),
+			xcm_filter: self.xcm_filter.clone(),
+			confirmation_guard: self.confirmation_guard.clone(),
+			confirmation_lag: self.confirmation_lag,
+			confirmation_deadline: self.confirmation_deadline.clone(),
+			message_confirmations: self.message_confirmations,
+			event_sink: self.event_sink.clone(),
+			paused_lane_idler: self.paused_lane_idler.clone(),
+			fee_accounting: self.fee_accounting.clone(),
+			events_discovery: self.events_discovery.clone(),
+			proof_generation_limit: self.proof_generation_limit.clone(),
+			preempted_confirmations: self.preempted_confirmations.clone(),
+			reward_eligibility_guard: self.reward_eligibility_guard.clone(),
+			confirmations_batcher: self.confirmations_batcher.clone(),
+			rate_limit_quota_guard: self.rate_limit_quota_guard.clone(),
+			proof_format: self.proof_format,
+			global_pause_guard: self.global_pause_guard.clone(),
 		}
 	}
 }
@@ -183,7 +738,44 @@ where
 		// we can't relay confirmations if messages pallet at source chain is halted
 		self.ensure_pallet_active().await?;
 
-		read_client_state_from_both_chains(&self.source_client, &self.target_client).await
+		let is_deadline_approaching = self
+			.confirmation_deadline
+			.as_ref()
+			.map(|confirmation_deadline| confirmation_deadline.is_approaching())
+			.unwrap_or(false);
+		let peer_sync_confirmations = match self.confirmation_lag {
+			Some(_) if is_deadline_approaching => {
+				log::info!(
+					target: "bridge",
+					"Delivery confirmations of {} -> {} lane {} are approaching the maximal \
+					latency. Ignoring the confirmation lag",
+					P::SourceChain::NAME,
+					P::TargetChain::NAME,
+					hex::encode(self.lane_id),
+				);
+				0
+			},
+			Some(confirmation_lag) =>
+				confirmation_lag_in_blocks(&self.source_client, confirmation_lag).await?,
+			None => 0,
+		};
+		let mut client_state = read_client_state_from_both_chains(
+			&self.source_client,
+			&self.target_client,
+			peer_sync_confirmations,
+		)
+		.await?;
+		// messages are read at the best finalized source block, so if it isn't deep enough, we
+		// need to use its ancestor
+		if let Some(confirmed_number) = confirmed_finalized_block_number(
+			client_state.best_self.number(),
+			client_state.best_finalized_self.number(),
+			self.message_confirmations,
+		) {
+			let confirmed_hash = self.source_client.header_hash_by_number(confirmed_number).await?;
+			client_state.best_finalized_self = HeaderId(confirmed_number, confirmed_hash);
+		}
+		Ok(client_state)
 	}
 
 	async fn latest_generated_nonce(
@@ -191,11 +783,20 @@ where
 		id: SourceHeaderIdOf<MessageLaneAdapter<P>>,
 	) -> Result<(SourceHeaderIdOf<MessageLaneAdapter<P>>, MessageNonce), SubstrateError> {
 		// lane data missing from the storage is fine until first message is sent
-		let latest_generated_nonce = self
-			.outbound_lane_data(id)
-			.await?
-			.map(|data| data.latest_generated_nonce)
-			.unwrap_or(0);
+		let read_from_storage = || async {
+			Ok(self
+				.outbound_lane_data(id)
+				.await?
+				.map(|data| data.latest_generated_nonce)
+				.unwrap_or(0))
+		};
+		let latest_generated_nonce = match self.events_discovery {
+			Some(ref events_discovery) =>
+				events_discovery
+					.latest_generated_nonce(&self.source_client, id, read_from_storage)
+					.await?,
+			None => read_from_storage().await?,
+		};
 		Ok((id, latest_generated_nonce))
 	}
 
@@ -229,6 +830,7 @@ where
 
 		// prepare arguments of the inbound message details call (if we need it)
 		let mut msgs_to_refine = vec![];
+		let mut first_filtered_nonce = None;
 		for out_msg_details in out_msgs_details.iter_mut() {
 			// in our current strategy all messages are supposed to be paid at the target chain
 
@@ -251,6 +853,24 @@ where
 					))
 				})?;
 
+			// messages are delivered in order, so we can't deliver messages after the first
+			// message that doesn't match the filter
+			if let Some(ref xcm_filter) = self.xcm_filter {
+				if !xcm_filter.matches(self.lane_id, out_msg_details.nonce, &msg_payload) {
+					log::trace!(
+						target: "bridge",
+						"{}->{} message {:?}/{} doesn't match the XCM filter. Not delivering it \
+						and later messages",
+						P::SourceChain::NAME,
+						P::TargetChain::NAME,
+						self.lane_id,
+						out_msg_details.nonce,
+					);
+					first_filtered_nonce = Some(out_msg_details.nonce);
+					break
+				}
+			}
+
 			msgs_to_refine.push((msg_payload, out_msg_details));
 		}
 
@@ -293,7 +913,8 @@ where
 		}
 
 		let mut msgs_details_map = MessageDetailsMap::new();
-		for out_msg_details in out_msgs_details {
+		let is_selected = |nonce| first_filtered_nonce.map_or(true, |filtered| nonce < filtered);
+		for out_msg_details in out_msgs_details.into_iter().filter(|d| is_selected(d.nonce)) {
 			msgs_details_map.insert(
 				out_msg_details.nonce,
 				MessageDetails {
@@ -320,31 +941,39 @@ where
 		),
 		SubstrateError,
 	> {
-		let mut storage_keys =
-			Vec::with_capacity(nonces.end().saturating_sub(*nonces.start()) as usize + 1);
-		let mut message_nonce = *nonces.start();
-		while message_nonce <= *nonces.end() {
-			let message_key = bp_messages::storage_keys::message_key(
-				P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
-				&self.lane_id,
-				message_nonce,
-			);
-			storage_keys.push(message_key);
-			message_nonce += 1;
-		}
-		if proof_parameters.outbound_state_proof_required {
-			storage_keys.push(bp_messages::storage_keys::outbound_lane_data_key(
-				P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
-				&self.lane_id,
-			));
-		}
-
-		let proof = self
-			.source_client
-			.prove_storage(id.1, storage_keys)
-			.await?
-			.into_iter_nodes()
-			.collect();
+		let storage_keys = messages_proof_storage_keys::<P>(
+			self.lane_id,
+			nonces.clone(),
+			proof_parameters.outbound_state_proof_required,
+		);
+		let max_time = self.proof_generation_limit.as_ref().map(|limit| limit.max_time);
+		let proof = match prove_storage_in_format(
+			&self.source_client,
+			id.1,
+			storage_keys,
+			max_time,
+			self.proof_format,
+		)
+		.await
+		{
+			Ok(proof) => proof,
+			Err(e) => {
+				if let (true, Some(ref limit)) =
+					(e.is_proof_generation_timeout(), &self.proof_generation_limit)
+				{
+					log::warn!(
+						target: "bridge",
+						"Abandoning proof of {} -> {} messages {:?}: {}",
+						P::SourceChain::NAME,
+						P::TargetChain::NAME,
+						nonces,
+						e,
+					);
+					limit.timeouts.inc();
+				}
+				return Err(e)
+			},
+		};
 		let proof = FromBridgedChainMessagesProof {
 			bridged_header_hash: id.1,
 			storage_proof: proof,
@@ -361,26 +990,124 @@ where
 		_generated_at_block: TargetHeaderIdOf<MessageLaneAdapter<P>>,
 		proof: <MessageLaneAdapter<P> as MessageLane>::MessagesReceivingProof,
 	) -> Result<Self::TransactionTracker, SubstrateError> {
+		if let Some(ref global_pause_guard) = self.global_pause_guard {
+			global_pause_guard.ensure_unpaused(self.lane_id)?;
+		}
+
+		let latest_confirmed_nonce = proof.0.last_delivered_nonce;
+		self.ensure_delivery_not_confirmed(latest_confirmed_nonce).await?;
+
+		// confirmations that are batched with headers can't be batched with other lanes
+		let confirmations_batcher =
+			self.confirmations_batcher.as_ref().filter(|_| maybe_batch_tx.is_none());
+
 		let messages_proof_call =
 			P::ReceiveMessagesDeliveryProofCallBuilder::build_receive_messages_delivery_proof_call(
 				proof,
 				maybe_batch_tx.is_none(),
 			);
+		let messages_proof_call = match self.proxy {
+			Some(ref proxy) => proxy.wrap(messages_proof_call)?,
+			None => messages_proof_call,
+		};
 		let final_call = match maybe_batch_tx {
 			Some(batch_tx) => batch_tx.append_call_and_build(messages_proof_call),
 			None => messages_proof_call,
 		};
 
-		let transaction_params = self.transaction_params.clone();
-		self.source_client
-			.submit_and_watch_signed_extrinsic(
-				&self.transaction_params.signer,
-				move |best_block_id, transaction_nonce| {
-					Ok(UnsignedTransaction::new(final_call.into(), transaction_nonce)
-						.era(TransactionEra::new(best_block_id, transaction_params.mortality)))
-				},
+		// every signer has its own account nonce, which is read by the client right before
+		// transaction is signed
+		let signer = match confirmations_batcher {
+			Some(confirmations_batcher) => confirmations_batcher.signer().clone(),
+			None => self.signers.select(),
+		};
+		if let Some(ref rate_limit_quota_guard) = self.rate_limit_quota_guard {
+			if let Err(e) = rate_limit_quota_guard
+				.ensure_quota_left::<P::SourceChain>(&self.source_client, &signer.public().into())
+				.await
+			{
+				log::debug!(
+					target: "bridge",
+					"Deferring delivery confirmation of {} -> {} messages: {}",
+					P::SourceChain::NAME,
+					P::TargetChain::NAME,
+					e,
+				);
+				return Err(e)
+			}
+		}
+		if let Some(ref reward_eligibility_guard) = self.reward_eligibility_guard {
+			reward_eligibility_guard
+				.ensure_eligible::<P::SourceChain>(&self.source_client, &signer.public().into())
+				.await?;
+		}
+		if let Some(ref confirmation_guard) = self.confirmation_guard {
+			let result = confirmation_guard
+				.ensure_affordable::<P::SourceChain>(
+					&self.source_client,
+					&signer,
+					final_call.clone(),
+				)
+				.await;
+			if let Err(e) = result {
+				if matches!(e, SubstrateError::InsufficientBalance { .. }) {
+					log::error!(
+						target: "bridge",
+						"Deferring delivery confirmation of {} -> {} messages: {}. Relayer rewards \
+						are not registered until the relayer account is topped up{}!",
+						P::SourceChain::NAME,
+						P::TargetChain::NAME,
+						e,
+						if confirmation_guard.is_delivery_paused() {
+							". Delivery is paused"
+						} else {
+							""
+						},
+					);
+				}
+				return Err(e)
+			}
+		}
+		// the fee is only used for accounting, so the transaction is submitted even if the fee
+		// can't be estimated
+		let fee = match self.fee_accounting {
+			Some(_) => estimate_transaction_fee::<P::SourceChain>(
+				&self.source_client,
+				&signer,
+				final_call.clone(),
 			)
 			.await
+			.ok(),
+			None => None,
+		};
+		let tx_tracker = match confirmations_batcher {
+			Some(confirmations_batcher) =>
+				confirmations_batcher.submit(self.lane_id, final_call).await?,
+			None => {
+				let transaction_params = self.transaction_params.clone();
+				self.source_client
+					.submit_and_watch_signed_extrinsic(
+						&signer,
+						move |best_block_id, transaction_nonce| {
+							Ok(UnsignedTransaction::new(final_call.into(), transaction_nonce)
+								.era(TransactionEra::new(
+									best_block_id,
+									transaction_params.mortality,
+								))
+								.priority(transaction_params.priority))
+						},
+					)
+					.await?
+			},
+		};
+		if let (Some(ref fee_accounting), Some(fee)) = (&self.fee_accounting, fee) {
+			fee_accounting.note(TransactionType::DeliveryConfirmation, fee.unique_saturated_into());
+		}
+		if let Some(ref event_sink) = self.event_sink {
+			event_sink
+				.emit(RelayEvent::DeliveryConfirmed { lane: self.lane_id, latest_confirmed_nonce });
+		}
+		Ok(tx_tracker)
 	}
 
 	async fn require_target_header_on_source(
@@ -401,6 +1128,30 @@ where
 	}
 }
 
+/// Returns storage keys that are proved by the messages proof.
+pub(crate) fn messages_proof_storage_keys<P: SubstrateMessageLane>(
+	lane_id: LaneId,
+	nonces: RangeInclusive<MessageNonce>,
+	outbound_state_proof_required: bool,
+) -> Vec<StorageKey> {
+	let mut storage_keys =
+		Vec::with_capacity(nonces.end().saturating_sub(*nonces.start()) as usize + 1);
+	for message_nonce in nonces {
+		storage_keys.push(bp_messages::storage_keys::message_key(
+			P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
+			&lane_id,
+			message_nonce,
+		));
+	}
+	if outbound_state_proof_required {
+		storage_keys.push(outbound_lane_data_key(
+			P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
+			&lane_id,
+		));
+	}
+	storage_keys
+}
+
 /// Ensure that the messages pallet at source chain is active.
 pub(crate) async fn ensure_messages_pallet_active<AtChain, WithChain, AtChainClient>(
 	client: &AtChainClient,
@@ -459,15 +1210,31 @@ where
 
 /// Does the same stuff as `read_client_state`, but properly fills the
 /// `actual_best_finalized_peer_at_best_self` field of the result.
+///
+/// If `peer_sync_confirmations` is non-zero, the best finalized peer header is read at
+/// the ancestor of the best `SelfChain` block. So the peer header that has been synced
+/// recently (within last `peer_sync_confirmations` blocks) is not used until it gets deep
+/// enough and can't be lost in the reorg.
 pub async fn read_client_state_from_both_chains<SelfChain, PeerChain>(
 	self_client: &impl Client<SelfChain>,
 	peer_client: &impl Client<PeerChain>,
+	peer_sync_confirmations: u32,
 ) -> Result<ClientState<HeaderIdOf<SelfChain>, HeaderIdOf<PeerChain>>, SubstrateError>
 where
 	SelfChain: Chain,
 	PeerChain: Chain,
 {
 	let mut client_state = read_client_state::<SelfChain, PeerChain>(self_client).await?;
+	if peer_sync_confirmations != 0 {
+		let confirmed_self_hash = self_client
+			.header_hash_by_number(confirmed_block_number(
+				client_state.best_self.number(),
+				peer_sync_confirmations,
+			))
+			.await?;
+		client_state.best_finalized_peer_at_best_self =
+			best_synced_header_id::<PeerChain, SelfChain>(self_client, confirmed_self_hash).await?;
+	}
 	client_state.actual_best_finalized_peer_at_best_self =
 		match client_state.best_finalized_peer_at_best_self.as_ref() {
 			Some(peer_on_self_best_finalized_id) => {
@@ -480,6 +1247,67 @@ where
 	Ok(client_state)
 }
 
+/// Returns true if the delivery of messages up to `latest_confirmed_nonce` has already been
+/// confirmed at the source chain, which has received confirmations up to
+/// `latest_received_nonce`.
+///
+/// The source runtime rejects confirmations that don't confirm any new messages, so such
+/// confirmation transaction would only waste the fee.
+fn is_delivery_confirmed(
+	latest_confirmed_nonce: MessageNonce,
+	latest_received_nonce: MessageNonce,
+) -> bool {
+	latest_confirmed_nonce <= latest_received_nonce
+}
+
+/// Returns number of the block that has at least `confirmations` descendants, given number
+/// of the best block.
+fn confirmed_block_number<N: AtLeast32BitUnsigned>(best: N, confirmations: u32) -> N {
+	best.saturating_sub(confirmations.into())
+}
+
+/// Returns number of the finalized block that has at least `confirmations` descendants, if the
+/// best finalized block itself doesn't have that many descendants.
+fn confirmed_finalized_block_number<N: AtLeast32BitUnsigned + Copy>(
+	best: N,
+	best_finalized: N,
+	confirmations: u32,
+) -> Option<N> {
+	if confirmations == 0 {
+		return None
+	}
+
+	let confirmed = confirmed_block_number(best, confirmations);
+	(confirmed < best_finalized).then_some(confirmed)
+}
+
+/// Returns number of blocks that are built within the `lag`, given the observed block time of
+/// the chain.
+async fn confirmation_lag_in_blocks<C: Chain>(
+	client: &impl Client<C>,
+	lag: Duration,
+) -> Result<u32, SubstrateError> {
+	let best_id = client.best_header().await?.id();
+	let window_start_number = confirmed_block_number(best_id.number(), OBSERVED_BLOCK_TIME_WINDOW);
+	let window_start_hash = client.header_hash_by_number(window_start_number).await?;
+	let window_blocks: u64 = (best_id.number() - window_start_number).unique_saturated_into();
+	let window_duration = read_timestamp(client, best_id.hash())
+		.await?
+		.saturating_sub(read_timestamp(client, window_start_hash).await?);
+	let block_time = match window_duration.checked_div(window_blocks) {
+		Some(block_time) if block_time != 0 => Duration::from_millis(block_time),
+		_ => C::AVERAGE_BLOCK_INTERVAL,
+	};
+	Ok(lag_in_blocks(lag, block_time))
+}
+
+/// Returns minimal number of blocks with given block time that cover the `lag`.
+fn lag_in_blocks(lag: Duration, block_time: Duration) -> u32 {
+	let block_time = block_time.as_millis().max(1);
+	let blocks = (lag.as_millis() + block_time - 1) / block_time;
+	blocks.try_into().unwrap_or(u32::MAX)
+}
+
 /// Reads best `PeerChain` header known to the `SelfChain` using provided runtime API method.
 ///
 /// Method is supposed to be the `<PeerChain>FinalityApi::best_finalized()` method.
@@ -599,6 +1427,43 @@ mod tests {
 			.collect()
 	}
 
+	#[test]
+	fn already_confirmed_delivery_is_detected() {
+		assert!(!is_delivery_confirmed(20, 10));
+		assert!(!is_delivery_confirmed(20, 19));
+		assert!(is_delivery_confirmed(20, 20));
+		assert!(is_delivery_confirmed(20, 30));
+	}
+
+	#[test]
+	fn confirmed_block_number_works() {
+		// when confirmations are not required, peer header, synced at the best block is used
+		assert_eq!(confirmed_block_number(100u32, 0), 100);
+		// otherwise peer headers, synced at last 5 blocks are not used
+		assert_eq!(confirmed_block_number(100u32, 5), 95);
+		assert_eq!(confirmed_block_number(3u32, 5), 0);
+	}
+
+	#[test]
+	fn lag_in_blocks_works() {
+		assert_eq!(lag_in_blocks(Duration::from_secs(0), Duration::from_secs(6)), 0);
+		assert_eq!(lag_in_blocks(Duration::from_secs(60), Duration::from_secs(6)), 10);
+		assert_eq!(lag_in_blocks(Duration::from_secs(61), Duration::from_secs(6)), 11);
+		assert_eq!(lag_in_blocks(Duration::from_secs(60), Duration::from_millis(0)), 60_000);
+	}
+
+	#[test]
+	fn messages_from_shallow_source_blocks_are_not_read() {
+		// without confirmations, messages are read at the best finalized block
+		assert_eq!(confirmed_finalized_block_number(100u32, 100, 0), None);
+		// when the best finalized block is deep enough, it is used
+		assert_eq!(confirmed_finalized_block_number(100u32, 90, 10), None);
+		// message, generated at the block 98, is not read until it is 3 blocks deep, so if the
+		// block 98 is retracted, the message is never relayed
+		assert_eq!(confirmed_finalized_block_number(100u32, 100, 3), Some(97));
+		assert_eq!(confirmed_finalized_block_number(2u32, 2, 3), Some(0));
+	}
+
 	#[test]
 	fn validate_out_msgs_details_succeeds_if_no_messages_are_missing() {
 		assert!(validate_out_msgs_details::<TestChain>(&message_details_from_rpc(1..=3), 1..=3,)
@@ -740,4 +1605,33 @@ mod tests {
 			Ok(vec![2, 4, 3]),
 		);
 	}
+
+	#[async_std::test]
+	async fn paused_lane_is_resumed_when_pallet_is_operational() {
+		let idler = PausedLaneIdler::new(Duration::from_millis(1), None).unwrap();
+		let operating_modes = parking_lot::Mutex::new(vec![
+			Ok(()),
+			Err(SubstrateError::BridgePalletIsHalted),
+			Err(SubstrateError::BridgePalletIsHalted),
+		]);
+		let ensure_active = || futures::future::ready(operating_modes.lock().pop().unwrap());
+
+		// the lane is idle until the pallet is operational again
+		assert!(idler
+			.wait_until_active("Test", LaneId([0, 0, 0, 1]), ensure_active)
+			.await
+			.is_ok());
+		assert!(operating_modes.lock().is_empty());
+		assert!(idler.paused_seconds.get() > 0.0);
+
+		// other errors are returned immediately
+		let operating_modes =
+			parking_lot::Mutex::new(vec![Ok(()), Err(SubstrateError::Custom("test".into()))]);
+		let ensure_active = || futures::future::ready(operating_modes.lock().pop().unwrap());
+		assert!(matches!(
+			idler.wait_until_active("Test", LaneId([0, 0, 0, 1]), ensure_active).await,
+			Err(SubstrateError::Custom(_)),
+		));
+		assert_eq!(operating_modes.lock().len(), 1);
+	}
 }
@@ -19,15 +19,21 @@
 //! `<BridgedName>` chain.
 
 use crate::{
+	messages_dispatch_refunds::{DeliveryTransactionTracker, DispatchRefundsReader},
+	messages_global_pause::GlobalPauseGuard,
+	messages_header_progress::HeaderProgressGuard,
 	messages_lane::{
 		BatchProofTransaction, MessageLaneAdapter, ReceiveMessagesProofCallBuilder,
 		SubstrateMessageLane,
 	},
+	messages_latency::read_timestamp,
 	messages_source::{
-		ensure_messages_pallet_active, read_client_state_from_both_chains, SubstrateMessagesProof,
+		ensure_messages_pallet_active, read_client_state_from_both_chains,
+		ConfirmationBalanceGuard, PausedLaneIdler, SubstrateMessagesProof,
 	},
 	on_demand::OnDemandRelay,
-	TransactionParams,
+	scale_events::{RelayEvent, ScaleEventSink},
+	ProxyParams, RoundRobin, TransactionParams,
 };
 
 use async_std::sync::Arc;
@@ -36,31 +42,785 @@ use bp_messages::{
 	storage_keys::inbound_lane_data_key, ChainWithMessages as _, InboundLaneData, LaneId,
 	MessageNonce, UnrewardedRelayersState,
 };
+use bp_runtime::storage_value_key;
 use bridge_runtime_common::messages::source::FromBridgedChainMessagesDeliveryProof;
+use codec::Encode;
+use frame_support::{dispatch::DispatchClass, weights::Weight};
+use frame_system::{limits::BlockWeights, AccountInfo, ConsumedWeight, LastRuntimeUpgradeInfo};
 use messages_relay::{
 	message_lane::{MessageLane, SourceHeaderIdOf, TargetHeaderIdOf},
-	message_lane_loop::{NoncesSubmitArtifacts, TargetClient, TargetClientState},
+	message_lane_loop::{NoncesSubmitArtifacts, TargetClient, TargetClientState, WeightHeadroom},
 };
+use pallet_balances::AccountData;
+use pallet_transaction_payment::Multiplier;
+use parking_lot::Mutex;
 use relay_substrate_client::{
-	AccountIdOf, AccountKeyPairOf, BalanceOf, CallOf, Chain, Client, Error as SubstrateError,
-	HashOf, TransactionEra, TransactionTracker, UnsignedTransaction,
+	metadata::read_pallet_constant,
+	rate_limit::RateLimitQuotaStorage,
+	storage_proof::{prove_storage_in_format, StorageProofFormat},
+	AccountIdOf, AccountKeyPairOf, BalanceOf, CallOf, Chain, ChainWithBalances,
+	ChainWithTransactions, Client, Error as SubstrateError, HashOf, NonceOf, SignParam,
+	TransactionEra, UnsignedTransaction,
 };
-use relay_utils::relay_loop::Client as RelayClient;
-use sp_core::Pair;
-use std::ops::RangeInclusive;
+use relay_utils::{
+	control::RelayControl,
+	fee_accounting::{FeeAccounting, TransactionType},
+	fee_budget::FeeBudget,
+	metrics::{metric_name, register, Counter, Gauge, Metric, PrometheusError, Registry, F64, U64},
+	relay_loop::Client as RelayClient,
+	UniqueSaturatedInto,
+};
+use sp_core::{hashing::blake2_256, storage::StorageKey, Pair};
+use sp_runtime::{traits::Zero, FixedPointNumber};
+use std::{
+	ops::RangeInclusive,
+	sync::atomic::{AtomicBool, Ordering},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use strum::{EnumString, VariantNames};
 
 /// Message receiving proof returned by the target Substrate node.
 pub type SubstrateMessagesDeliveryProof<C> =
 	(UnrewardedRelayersState, FromBridgedChainMessagesDeliveryProof<HashOf<C>>);
 
+/// Signer balance check, performed before submitting the delivery transaction.
+///
+/// If the signer can't pay for the transaction, it won't be included anyway, so the delivery
+/// is deferred until the account is topped up.
+#[derive(Clone, Debug)]
+pub struct BalanceGuard {
+	/// Storage key of the signer `frame_system::AccountInfo`.
+	account_info_key: StorageKey,
+	/// Balance that must be left at the signer account after paying the transaction fee.
+	buffer: u128,
+	/// Existential deposit, reserved at the signer account on top of the `buffer`. Zero if the
+	/// existential deposit is not preserved.
+	existential_deposit: u128,
+}
+
+impl BalanceGuard {
+	/// Create balance guard for the given signer account. The `buffer` should cover the
+	/// transaction tip, if it is used, and the existential deposit, unless it is preserved
+	/// using [`Self::with_existential_deposit`].
+	pub fn new<C: ChainWithBalances>(signer: &AccountIdOf<C>, buffer: u128) -> Self {
+		BalanceGuard {
+			account_info_key: C::account_info_storage_key(signer),
+			buffer,
+			existential_deposit: 0,
+		}
+	}
+
+	/// Reserve the existential deposit at the signer account, so that paying the transaction
+	/// fee never leads to the account being reaped.
+	pub fn with_existential_deposit(mut self, existential_deposit: u128) -> Self {
+		self.existential_deposit = existential_deposit;
+		self
+	}
+
+	/// Read free balance of the signer at the best block.
+	pub(crate) async fn free_balance<C: Chain>(
+		&self,
+		client: &impl Client<C>,
+	) -> Result<u128, SubstrateError> {
+		self.free_balance_at(client, client.best_header_hash().await?).await
+	}
+
+	/// Read free balance of the signer at given block.
+	pub(crate) async fn free_balance_at<C: Chain>(
+		&self,
+		client: &impl Client<C>,
+		at: HashOf<C>,
+	) -> Result<u128, SubstrateError> {
+		let account_info: Option<AccountInfo<NonceOf<C>, AccountData<BalanceOf<C>>>> =
+			client.storage_value(at, self.account_info_key.clone()).await?;
+		Ok(account_info.map(|info| info.data.free.unique_saturated_into()).unwrap_or(0))
+	}
+
+	/// Ensure that the free balance covers the transaction fee and the buffer.
+	pub(crate) fn ensure_sufficient<C: Chain>(
+		&self,
+		free_balance: u128,
+		fee: u128,
+	) -> Result<(), SubstrateError> {
+		let required = fee.saturating_add(self.buffer).saturating_add(self.existential_deposit);
+		if free_balance < required {
+			return Err(SubstrateError::InsufficientBalance {
+				chain: C::NAME.into(),
+				balance: free_balance.to_string(),
+				required: required.to_string(),
+			})
+		}
+		Ok(())
+	}
+}
+
+/// Read the existential deposit of the chain from the `Balances` pallet constants.
+pub async fn read_existential_deposit<C: ChainWithBalances>(
+	client: &impl Client<C>,
+) -> Result<u128, SubstrateError> {
+	let at = client.best_finalized_header_hash().await?;
+	let existential_deposit: BalanceOf<C> =
+		read_pallet_constant(client, at, "Balances", "ExistentialDeposit")
+			.await?
+			.ok_or_else(|| {
+				SubstrateError::Custom(format!(
+					"{} runtime has no Balances::ExistentialDeposit constant",
+					C::NAME
+				))
+			})?;
+	Ok(existential_deposit.unique_saturated_into())
+}
+
+/// Ceiling of the target chain fee multiplier.
+///
+/// The fee multiplier grows while the target chain blocks are congested. The guard defers
+/// delivery while the `NextFeeMultiplier` of the target chain is above the ceiling, instead
+/// of paying the increased fee.
+#[derive(Clone, Debug)]
+pub struct FeeMultiplierGuard {
+	/// Maximal fee multiplier, at which delivery transactions are still submitted.
+	max_fee_multiplier: Multiplier,
+	/// True if the latest seen fee multiplier has been above the ceiling.
+	is_above_ceiling: Arc<AtomicBool>,
+	fee_multiplier: Gauge<F64>,
+}
+
+impl FeeMultiplierGuard {
+	/// Create guard with given fee multiplier ceiling.
+	pub fn new(
+		max_fee_multiplier: Multiplier,
+		prefix: Option<&str>,
+	) -> Result<Self, PrometheusError> {
+		Ok(FeeMultiplierGuard {
+			max_fee_multiplier,
+			is_above_ceiling: Default::default(),
+			fee_multiplier: Gauge::new(
+				metric_name(prefix, "target_fee_multiplier"),
+				"Fee multiplier of the target chain, read before submitting the latest delivery transaction",
+			)?,
+		})
+	}
+
+	/// Read the fee multiplier at the best block and ensure that it isn't above the ceiling.
+	pub(crate) async fn ensure_below_ceiling<C: Chain>(
+		&self,
+		client: &impl Client<C>,
+	) -> Result<(), SubstrateError> {
+		let fee_multiplier = read_fee_multiplier(client, client.best_header_hash().await?).await?;
+		self.update::<C>(fee_multiplier)
+	}
+
+	/// Remember the fee multiplier and ensure that it isn't above the ceiling.
+	fn update<C: Chain>(&self, fee_multiplier: Multiplier) -> Result<(), SubstrateError> {
+		self.fee_multiplier.set(fee_multiplier.to_float());
+
+		let is_above_ceiling = fee_multiplier > self.max_fee_multiplier;
+		let was_above_ceiling = self.is_above_ceiling.swap(is_above_ceiling, Ordering::SeqCst);
+		match (was_above_ceiling, is_above_ceiling) {
+			(false, true) => log::warn!(
+				target: "bridge",
+				"{} fee multiplier {} is above the ceiling {}. Delivery is paused",
+				C::NAME,
+				fee_multiplier,
+				self.max_fee_multiplier,
+			),
+			(true, false) => log::info!(
+				target: "bridge",
+				"{} fee multiplier {} has dropped to the ceiling {}. Delivery is resumed",
+				C::NAME,
+				fee_multiplier,
+				self.max_fee_multiplier,
+			),
+			_ => (),
+		}
+
+		if is_above_ceiling {
+			return Err(SubstrateError::Custom(format!(
+				"{} fee multiplier {} is above the ceiling {}",
+				C::NAME,
+				fee_multiplier,
+				self.max_fee_multiplier,
+			)))
+		}
+		Ok(())
+	}
+}
+
+/// Read the `NextFeeMultiplier` of the chain at given block.
+pub(crate) async fn read_fee_multiplier<C: Chain>(
+	client: &impl Client<C>,
+	at: HashOf<C>,
+) -> Result<Multiplier, SubstrateError> {
+	Ok(client
+		.storage_value(at, storage_value_key("TransactionPayment", "NextFeeMultiplier"))
+		.await?
+		.unwrap_or_else(Multiplier::one))
+}
+
+impl Metric for FeeMultiplierGuard {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		register(self.fee_multiplier.clone(), registry)?;
+		Ok(())
+	}
+}
+
+/// Remaining rate limit quota check, performed before submitting the transaction.
+///
+/// Chains that limit the number of transactions of the account reject transactions once the
+/// account has exhausted its quota. The guard defers submission until the quota is replenished.
+#[derive(Clone, Debug)]
+pub struct RateLimitQuotaGuard {
+	/// Location of the remaining quota of accounts.
+	storage: RateLimitQuotaStorage,
+	/// True if the quota has been exhausted when it has been checked last time.
+	is_exhausted: Arc<AtomicBool>,
+	throttled_transactions: Counter<U64>,
+}
+
+impl RateLimitQuotaGuard {
+	/// Create guard that reads the remaining quota from given storage.
+	pub fn new(
+		storage: RateLimitQuotaStorage,
+		prefix: Option<&str>,
+	) -> Result<Self, PrometheusError> {
+		Ok(RateLimitQuotaGuard {
+			storage,
+			is_exhausted: Default::default(),
+			throttled_transactions: Counter::new(
+				metric_name(prefix, "rate_limit_quota_throttled_transactions"),
+				"Number of transactions, deferred because the relayer account has exhausted its rate limit quota",
+			)?,
+		})
+	}
+
+	/// Read the remaining quota of the signer at the best block and ensure that it isn't
+	/// exhausted.
+	pub(crate) async fn ensure_quota_left<C: Chain>(
+		&self,
+		client: &impl Client<C>,
+		signer: &AccountIdOf<C>,
+	) -> Result<(), SubstrateError> {
+		let quota = self
+			.storage
+			.read_remaining_quota(client, client.best_header_hash().await?, signer)
+			.await?;
+		self.update::<C>(quota)
+	}
+
+	/// Remember whether the quota is exhausted and ensure that it isn't.
+	fn update<C: Chain>(&self, quota: Option<u128>) -> Result<(), SubstrateError> {
+		let is_exhausted = quota == Some(0);
+		let was_exhausted = self.is_exhausted.swap(is_exhausted, Ordering::SeqCst);
+		match (was_exhausted, is_exhausted) {
+			(false, true) => log::warn!(
+				target: "bridge",
+				"Relayer account has exhausted its {} rate limit quota, stored at {}. \
+				Transactions are throttled until the quota is replenished",
+				C::NAME,
+				self.storage,
+			),
+			(true, false) => log::info!(
+				target: "bridge",
+				"{} rate limit quota of the relayer account has been replenished. Transactions \
+				are resumed",
+				C::NAME,
+			),
+			_ => (),
+		}
+
+		if is_exhausted {
+			self.throttled_transactions.inc();
+			return Err(SubstrateError::Custom(format!(
+				"Relayer account has exhausted its {} rate limit quota",
+				C::NAME,
+			)))
+		}
+		Ok(())
+	}
+}
+
+impl Metric for RateLimitQuotaGuard {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		register(self.throttled_transactions.clone(), registry)?;
+		Ok(())
+	}
+}
+
+/// Weight headroom of the target chain blocks.
+///
+/// The headroom is the weight of normal transactions that the best target block still has
+/// room for. It is read along with the target chain state, so it follows the recent block
+/// usage. The delivery race fits the batch into the headroom, so that the delivery
+/// transaction isn't wasted on busy chains.
+#[derive(Clone, Debug)]
+pub struct BlockWeightHeadroom {
+	/// Maximal total weight of normal transactions in the target block.
+	max_normal_weight: Weight,
+	/// Headroom, shared with the delivery race.
+	headroom: WeightHeadroom,
+}
+
+impl BlockWeightHeadroom {
+	/// Read block weight limits of the chain from the `System` pallet constants.
+	pub async fn new<C: Chain>(client: &impl Client<C>) -> Result<Self, SubstrateError> {
+		let at = client.best_finalized_header_hash().await?;
+		let block_weights: BlockWeights =
+			read_pallet_constant(client, at, "System", "BlockWeights")
+				.await?
+				.ok_or_else(|| {
+					SubstrateError::Custom(format!(
+						"{} runtime has no System::BlockWeights constant",
+						C::NAME
+					))
+				})?;
+		Ok(BlockWeightHeadroom {
+			max_normal_weight: block_weights
+				.get(DispatchClass::Normal)
+				.max_total
+				.unwrap_or(block_weights.max_block),
+			headroom: WeightHeadroom::default(),
+		})
+	}
+
+	/// Returns headroom, shared with the delivery race.
+	pub fn headroom(&self) -> WeightHeadroom {
+		self.headroom.clone()
+	}
+
+	/// Read the weight, consumed by given block, and update the headroom.
+	pub(crate) async fn update<C: Chain>(&self, client: &impl Client<C>, at: HashOf<C>) {
+		match client
+			.storage_value::<ConsumedWeight>(at, storage_value_key("System", "BlockWeight"))
+			.await
+		{
+			Ok(consumed_weight) =>
+				self.note_consumed_weight::<C>(&consumed_weight.unwrap_or_default()),
+			// the previous headroom is kept
+			Err(e) => log::warn!(
+				target: "bridge",
+				"Failed to read weight of {} block {:?}: {:?}",
+				C::NAME,
+				at,
+				e,
+			),
+		}
+	}
+
+	/// Update the headroom, given the weight, consumed by the block.
+	fn note_consumed_weight<C: Chain>(&self, consumed_weight: &ConsumedWeight) {
+		let headroom = self
+			.max_normal_weight
+			.saturating_sub(*consumed_weight.get(DispatchClass::Normal));
+		log::trace!(target: "bridge", "Weight headroom of {} block: {}", C::NAME, headroom);
+		self.headroom.set(headroom);
+	}
+}
+
+/// Age of the source header, last synced to the target chain.
+///
+/// If source headers are no longer synced to the target chain (e.g. because the headers relay
+/// has died), proofs of new messages can't be verified at the target chain. The guard exposes
+/// the age of the last synced header, read along with the target chain state, and reports
+/// when it exceeds the threshold. Delivery may also be paused while the header is stale.
+#[derive(Clone, Debug)]
+pub struct SyncedHeaderAgeGuard {
+	/// Age of the synced header, after which it is considered stale.
+	max_age: Option<Duration>,
+	/// If true, delivery is paused while the synced header is stale.
+	pause_delivery: bool,
+	/// True if the latest seen synced header has been stale.
+	is_stale: Arc<AtomicBool>,
+	age: Gauge<F64>,
+}
+
+impl SyncedHeaderAgeGuard {
+	/// Create guard with given staleness threshold.
+	pub fn new(
+		max_age: Option<Duration>,
+		pause_delivery: bool,
+		prefix: Option<&str>,
+	) -> Result<Self, PrometheusError> {
+		Ok(SyncedHeaderAgeGuard {
+			max_age,
+			pause_delivery,
+			is_stale: Default::default(),
+			age: Gauge::new(
+				metric_name(prefix, "target_last_synced_source_header_age_secs"),
+				"Time since the timestamp of the source header, last synced to the target chain",
+			)?,
+		})
+	}
+
+	/// Returns true if delivery is paused, because the last synced header is stale.
+	pub fn is_delivery_paused(&self) -> bool {
+		self.pause_delivery && self.is_stale.load(Ordering::SeqCst)
+	}
+
+	/// Read timestamp of the synced source header and remember its age.
+	pub(crate) async fn update_synced_header<SourceChain: Chain, TargetChain: Chain>(
+		&self,
+		source_client: &impl Client<SourceChain>,
+		synced_header_hash: HashOf<SourceChain>,
+	) {
+		let timestamp = match read_timestamp(source_client, synced_header_hash).await {
+			Ok(timestamp) => timestamp,
+			Err(e) => {
+				log::debug!(
+					target: "bridge",
+					"Failed to read timestamp of {} header {}, synced to {}: {:?}",
+					SourceChain::NAME,
+					synced_header_hash,
+					TargetChain::NAME,
+					e,
+				);
+				return
+			},
+		};
+		let now = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|now| now.as_millis() as u64)
+			.unwrap_or(0);
+		self.update::<SourceChain, TargetChain>(Duration::from_millis(
+			now.saturating_sub(timestamp),
+		));
+	}
+
+	/// Remember the age of the synced header.
+	fn update<SourceChain: Chain, TargetChain: Chain>(&self, age: Duration) {
+		self.age.set(age.as_secs_f64());
+
+		let max_age = match self.max_age {
+			Some(max_age) => max_age,
+			None => return,
+		};
+		let is_stale = age > max_age;
+		let was_stale = self.is_stale.swap(is_stale, Ordering::SeqCst);
+		match (was_stale, is_stale) {
+			(false, true) => log::error!(
+				target: "bridge",
+				"{} header, last synced to {}, is {:?} old, which exceeds {:?}. Is the headers \
+				relay running?{}",
+				SourceChain::NAME,
+				TargetChain::NAME,
+				age,
+				max_age,
+				if self.pause_delivery { " Delivery is paused" } else { "" },
+			),
+			(true, false) => log::info!(
+				target: "bridge",
+				"{} headers are synced to {} again. The last synced header is {:?} old{}",
+				SourceChain::NAME,
+				TargetChain::NAME,
+				age,
+				if self.pause_delivery { ". Delivery is resumed" } else { "" },
+			),
+			_ => (),
+		}
+	}
+}
+
+impl Metric for SyncedHeaderAgeGuard {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		register(self.age.clone(), registry)?;
+		Ok(())
+	}
+}
+
+/// Guard that defers delivery while the target chain runtime upgrade settles.
+///
+/// Transactions, built right before the runtime upgrade is enacted, may be built against the
+/// previous runtime version. The guard watches the spec version from the `LastRuntimeUpgrade`
+/// of the target chain and defers delivery for the settle period after it has changed.
+#[derive(Clone, Debug)]
+pub struct RuntimeUpgradeGuard {
+	/// Period after the runtime upgrade, during which delivery is deferred.
+	settle_period: Duration,
+	state: Arc<Mutex<RuntimeUpgradeState>>,
+}
+
+#[derive(Debug, Default)]
+struct RuntimeUpgradeState {
+	/// The latest seen spec version.
+	spec_version: Option<u32>,
+	/// Time when the latest runtime upgrade has been detected, if it hasn't settled yet.
+	upgraded_at: Option<Instant>,
+}
+
+impl RuntimeUpgradeGuard {
+	/// Create guard with given settle period.
+	pub fn new(settle_period: Duration) -> Self {
+		RuntimeUpgradeGuard { settle_period, state: Default::default() }
+	}
+
+	/// Read the spec version at the best block and ensure that the runtime upgrade has settled.
+	pub(crate) async fn ensure_settled<C: Chain>(
+		&self,
+		client: &impl Client<C>,
+	) -> Result<(), SubstrateError> {
+		let last_runtime_upgrade: Option<LastRuntimeUpgradeInfo> = client
+			.storage_value(
+				client.best_header_hash().await?,
+				storage_value_key("System", "LastRuntimeUpgrade"),
+			)
+			.await?;
+		let spec_version = match last_runtime_upgrade {
+			Some(last_runtime_upgrade) => last_runtime_upgrade.spec_version.0,
+			None => client.simple_runtime_version().await?.spec_version,
+		};
+		self.update::<C>(spec_version, Instant::now())
+	}
+
+	/// Remember the spec version and ensure that the runtime upgrade has settled.
+	fn update<C: Chain>(&self, spec_version: u32, now: Instant) -> Result<(), SubstrateError> {
+		let mut state = self.state.lock();
+		match state.spec_version {
+			Some(previous_spec_version) if previous_spec_version != spec_version => {
+				log::warn!(
+					target: "bridge",
+					"{} runtime has been upgraded from spec version {} to {}. Delivery is paused \
+					for {:?}",
+					C::NAME,
+					previous_spec_version,
+					spec_version,
+					self.settle_period,
+				);
+				state.upgraded_at = Some(now);
+			},
+			_ => (),
+		}
+		state.spec_version = Some(spec_version);
+
+		if let Some(upgraded_at) = state.upgraded_at {
+			let elapsed = now.saturating_duration_since(upgraded_at);
+			if elapsed < self.settle_period {
+				return Err(SubstrateError::Custom(format!(
+					"{} runtime upgrade to spec version {} is settling for another {:?}",
+					C::NAME,
+					spec_version,
+					self.settle_period - elapsed,
+				)))
+			}
+
+			log::info!(
+				target: "bridge",
+				"{} runtime spec version {} has settled. Delivery is resumed",
+				C::NAME,
+				spec_version,
+			);
+			state.upgraded_at = None;
+		}
+		Ok(())
+	}
+}
+
+/// What the relay does when the delivery transaction fee can't be estimated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, VariantNames)]
+#[strum(serialize_all = "kebab_case")]
+pub enum FeeEstimationFallback {
+	/// Defer delivery until the fee can be estimated.
+	Skip,
+	/// Deliver messages without checking the fee against the signer balance and the fee budget.
+	Altruistic,
+	/// Use the latest successfully estimated fee. Delivery is deferred if there's no such fee.
+	LastKnown,
+}
+
+/// Delivery transaction fee estimation with the fallback, used when the estimation fails.
+#[derive(Clone, Debug)]
+pub struct FeeEstimation {
+	/// What to do when the fee can't be estimated.
+	fallback: FeeEstimationFallback,
+	/// The latest successfully estimated fee.
+	last_known_fee: Arc<Mutex<Option<u128>>>,
+	fallbacks: Counter<U64>,
+}
+
+impl FeeEstimation {
+	/// Create fee estimation with given fallback.
+	pub fn new(
+		fallback: FeeEstimationFallback,
+		prefix: Option<&str>,
+	) -> Result<Self, PrometheusError> {
+		Ok(FeeEstimation {
+			fallback,
+			last_known_fee: Default::default(),
+			fallbacks: Counter::new(
+				metric_name(prefix, "delivery_fee_estimation_fallbacks"),
+				"Number of delivery transactions, which fee couldn't be estimated",
+			)?,
+		})
+	}
+
+	/// Returns the estimated fee or the fee, selected by the fallback if the estimation has
+	/// failed. `None` means that the fee must not be checked.
+	pub(crate) fn fee_or_fallback<C: Chain>(
+		&self,
+		estimated_fee: Result<u128, SubstrateError>,
+	) -> Result<Option<u128>, SubstrateError> {
+		let mut last_known_fee = self.last_known_fee.lock();
+		let error = match estimated_fee {
+			Ok(fee) => {
+				*last_known_fee = Some(fee);
+				return Ok(Some(fee))
+			},
+			Err(error) => error,
+		};
+
+		self.fallbacks.inc();
+		let fee = match (self.fallback, *last_known_fee) {
+			(FeeEstimationFallback::Skip, _) | (FeeEstimationFallback::LastKnown, None) => {
+				log::warn!(
+					target: "bridge",
+					"Failed to estimate {} delivery transaction fee: {:?}. Deferring delivery",
+					C::NAME,
+					error,
+				);
+				return Err(error)
+			},
+			(FeeEstimationFallback::Altruistic, _) => {
+				log::warn!(
+					target: "bridge",
+					"Failed to estimate {} delivery transaction fee: {:?}. Delivering without \
+					fee checks",
+					C::NAME,
+					error,
+				);
+				None
+			},
+			(FeeEstimationFallback::LastKnown, Some(fee)) => {
+				log::warn!(
+					target: "bridge",
+					"Failed to estimate {} delivery transaction fee: {:?}. Using the last known \
+					fee {}",
+					C::NAME,
+					error,
+					fee,
+				);
+				Some(fee)
+			},
+		};
+		Ok(fee)
+	}
+}
+
+impl Metric for FeeEstimation {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		register(self.fallbacks.clone(), registry)?;
+		Ok(())
+	}
+}
+
+/// Optional parameters of the Substrate messages target.
+///
+/// The default value has no guards, unlimited fee budget and the legacy proof format.
+pub struct SubstrateMessagesTargetParams<P: SubstrateMessageLane> {
+	/// If set, delivery calls are dispatched using the proxy.
+	pub proxy: Option<ProxyParams<P::TargetChain>>,
+	/// On-demand relay of source headers to the target chain.
+	pub source_to_target_headers_relay:
+		Option<Arc<dyn OnDemandRelay<P::SourceChain, P::TargetChain>>>,
+	/// Budget that delivery transaction fees are spent from.
+	pub fee_budget: FeeBudget,
+	/// Percent that is added to the estimated delivery transaction fee before it is checked.
+	pub fee_safety_margin_percent: u32,
+	/// If set, delivery is deferred while the signer can't pay for the transaction.
+	pub balance_guard: Option<BalanceGuard>,
+	/// If set, delivery is deferred while the guard pauses it.
+	pub confirmation_guard: Option<ConfirmationBalanceGuard>,
+	/// If set, delivery is deferred while the target fee multiplier is above its ceiling.
+	pub fee_multiplier_guard: Option<FeeMultiplierGuard>,
+	/// Fallback that is used if the fee can't be estimated. Without it, delivery is deferred.
+	pub fee_estimation: Option<FeeEstimation>,
+	/// Number of target blocks that must be built on top of the block, where the source header
+	/// has been synced, before proofs are built against this header.
+	pub sync_confirmations: u32,
+	/// Counter of messages that have been delivered by other relayers right before
+	/// submitting the delivery transaction.
+	pub preempted_messages: Option<Counter<U64>>,
+	/// Relay control. Delivery is deferred while the target runtime spec version isn't allowed.
+	pub control: RelayControl,
+	/// Sink of submitted and skipped delivery events.
+	pub event_sink: Option<ScaleEventSink>,
+	/// If set, the lane is idle while the messages pallet is halted.
+	pub paused_lane_idler: Option<PausedLaneIdler>,
+	/// Estimated fees of deliveries are reported there.
+	pub fee_accounting: Option<FeeAccounting>,
+	/// If set, delivery is deferred while the target runtime upgrade is settling.
+	pub runtime_upgrade_guard: Option<RuntimeUpgradeGuard>,
+	/// The age of the synced source header is reported there. It may also pause delivery.
+	pub synced_header_age_guard: Option<SyncedHeaderAgeGuard>,
+	/// Weight headroom of the target block, updated with the target chain state.
+	pub block_weight_headroom: Option<BlockWeightHeadroom>,
+	/// If set, delivery is deferred while the signer has exhausted its quota.
+	pub rate_limit_quota_guard: Option<RateLimitQuotaGuard>,
+	/// Format of messages delivery proofs.
+	pub proof_format: StorageProofFormat,
+	/// If set, delivery is held back while the bridge is paused.
+	pub global_pause_guard: Option<GlobalPauseGuard>,
+	/// If set, detects that required source headers aren't synced by any headers relay.
+	pub header_progress_guard: Option<HeaderProgressGuard<P::SourceChain, P::TargetChain>>,
+	/// If set, unspent dispatch weight of delivered messages is reported there.
+	pub dispatch_refunds: Option<DispatchRefundsReader>,
+}
+
+impl<P: SubstrateMessageLane> Default for SubstrateMessagesTargetParams<P> {
+	fn default() -> Self {
+		SubstrateMessagesTargetParams {
+			proxy: None,
+			source_to_target_headers_relay: None,
+			fee_budget: FeeBudget::default(),
+			fee_safety_margin_percent: 0,
+			balance_guard: None,
+			confirmation_guard: None,
+			fee_multiplier_guard: None,
+			fee_estimation: None,
+			sync_confirmations: 0,
+			preempted_messages: None,
+			control: RelayControl::default(),
+			event_sink: None,
+			paused_lane_idler: None,
+			fee_accounting: None,
+			runtime_upgrade_guard: None,
+			synced_header_age_guard: None,
+			block_weight_headroom: None,
+			rate_limit_quota_guard: None,
+			proof_format: StorageProofFormat::Legacy,
+			global_pause_guard: None,
+			header_progress_guard: None,
+			dispatch_refunds: None,
+		}
+	}
+}
+
 /// Substrate client as Substrate messages target.
 pub struct SubstrateMessagesTarget<P: SubstrateMessageLane, SourceClnt, TargetClnt> {
 	target_client: TargetClnt,
 	source_client: SourceClnt,
 	lane_id: LaneId,
-	relayer_id_at_source: AccountIdOf<P::SourceChain>,
+	relayer_ids_at_source: RoundRobin<AccountIdOf<P::SourceChain>>,
 	transaction_params: Option<TransactionParams<AccountKeyPairOf<P::TargetChain>>>,
+	proxy: Option<ProxyParams<P::TargetChain>>,
 	source_to_target_headers_relay: Option<Arc<dyn OnDemandRelay<P::SourceChain, P::TargetChain>>>,
+	fee_budget: FeeBudget,
+	fee_safety_margin_percent: u32,
+	balance_guard: Option<BalanceGuard>,
+	confirmation_guard: Option<ConfirmationBalanceGuard>,
+	fee_multiplier_guard: Option<FeeMultiplierGuard>,
+	fee_estimation: Option<FeeEstimation>,
+	sync_confirmations: u32,
+	preempted_messages: Option<Counter<U64>>,
+	control: RelayControl,
+	event_sink: Option<ScaleEventSink>,
+	paused_lane_idler: Option<PausedLaneIdler>,
+	fee_accounting: Option<FeeAccounting>,
+	runtime_upgrade_guard: Option<RuntimeUpgradeGuard>,
+	synced_header_age_guard: Option<SyncedHeaderAgeGuard>,
+	block_weight_headroom: Option<BlockWeightHeadroom>,
+	rate_limit_quota_guard: Option<RateLimitQuotaGuard>,
+	proof_format: StorageProofFormat,
+	global_pause_guard: Option<GlobalPauseGuard>,
+	header_progress_guard: Option<HeaderProgressGuard<P::SourceChain, P::TargetChain>>,
+	dispatch_refunds: Option<DispatchRefundsReader>,
 }
 
 impl<P, SourceClnt, TargetClnt> SubstrateMessagesTarget<P, SourceClnt, TargetClnt>
@@ -68,27 +828,98 @@ where
 	P: SubstrateMessageLane,
 	TargetClnt: Client<P::TargetChain>,
 {
-	/// Create new Substrate headers target.
+	/// Create new Substrate headers target. Optional guards and knobs of the target are
+	/// configured by the `params`.
 	pub fn new(
 		target_client: TargetClnt,
 		source_client: SourceClnt,
 		lane_id: LaneId,
-		relayer_id_at_source: AccountIdOf<P::SourceChain>,
+		relayer_ids_at_source: RoundRobin<AccountIdOf<P::SourceChain>>,
 		transaction_params: Option<TransactionParams<AccountKeyPairOf<P::TargetChain>>>,
-		source_to_target_headers_relay: Option<
-			Arc<dyn OnDemandRelay<P::SourceChain, P::TargetChain>>,
-		>,
+		params: SubstrateMessagesTargetParams<P>,
 	) -> Self {
 		SubstrateMessagesTarget {
 			target_client,
 			source_client,
 			lane_id,
-			relayer_id_at_source,
+			relayer_ids_at_source,
 			transaction_params,
-			source_to_target_headers_relay,
+			proxy: params.proxy,
+			source_to_target_headers_relay: params.source_to_target_headers_relay,
+			fee_budget: params.fee_budget,
+			fee_safety_margin_percent: params.fee_safety_margin_percent,
+			balance_guard: params.balance_guard,
+			confirmation_guard: params.confirmation_guard,
+			fee_multiplier_guard: params.fee_multiplier_guard,
+			fee_estimation: params.fee_estimation,
+			sync_confirmations: params.sync_confirmations,
+			preempted_messages: params.preempted_messages,
+			control: params.control,
+			event_sink: params.event_sink,
+			paused_lane_idler: params.paused_lane_idler,
+			fee_accounting: params.fee_accounting,
+			runtime_upgrade_guard: params.runtime_upgrade_guard,
+			synced_header_age_guard: params.synced_header_age_guard,
+			block_weight_headroom: params.block_weight_headroom,
+			rate_limit_quota_guard: params.rate_limit_quota_guard,
+			proof_format: params.proof_format,
+			global_pause_guard: params.global_pause_guard,
+			header_progress_guard: params.header_progress_guard,
+			dispatch_refunds: params.dispatch_refunds,
 		}
 	}
 
+	/// Ensure that none of messages of the batch has been delivered by other relayer.
+	///
+	/// The same batch may also be delivered by another relay process, serving the same lane
+	/// with the same signer, or by this process before restart.
+	async fn ensure_batch_not_delivered(
+		&self,
+		generated_at_header: &SourceHeaderIdOf<MessageLaneAdapter<P>>,
+		nonces: &RangeInclusive<MessageNonce>,
+	) -> Result<(), SubstrateError> {
+		let best_block_hash = self.target_client.best_header_hash().await?;
+		let last_delivered_nonce = self
+			.target_client
+			.storage_value::<InboundLaneData<AccountIdOf<P::SourceChain>>>(
+				best_block_hash,
+				inbound_lane_data_key(
+					P::SourceChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
+					&self.lane_id,
+				),
+			)
+			.await?
+			.map(|data| data.last_delivered_nonce())
+			.unwrap_or(0);
+		let delivered_nonces = delivered_nonces_count(nonces, last_delivered_nonce);
+		if delivered_nonces == 0 {
+			return Ok(())
+		}
+
+		// the race selects remaining messages again when it reads the new target state
+		if let Some(ref preempted_messages) = self.preempted_messages {
+			preempted_messages.inc_by(delivered_nonces);
+		}
+		let batch_key = batch_idempotency_key(self.lane_id, nonces, generated_at_header);
+		log::info!(
+			target: "bridge",
+			"Dropping delivery of {} -> {} messages {:?} (batch {}): messages up to {} have been \
+			delivered by other relayer",
+			P::SourceChain::NAME,
+			P::TargetChain::NAME,
+			nonces,
+			hex::encode(batch_key),
+			last_delivered_nonce,
+		);
+		Err(SubstrateError::Custom(format!(
+			"Batch {} of {} -> {} messages {:?} is already delivered",
+			hex::encode(batch_key),
+			P::SourceChain::NAME,
+			P::TargetChain::NAME,
+			nonces,
+		)))
+	}
+
 	/// Read inbound lane state from the on-chain storage at given block.
 	async fn inbound_lane_data(
 		&self,
@@ -107,8 +938,20 @@ where
 
 	/// Ensure that the messages pallet at target chain is active.
 	async fn ensure_pallet_active(&self) -> Result<(), SubstrateError> {
-		ensure_messages_pallet_active::<P::TargetChain, P::SourceChain, _>(&self.target_client)
-			.await
+		match self.paused_lane_idler {
+			Some(ref paused_lane_idler) =>
+				paused_lane_idler
+					.wait_until_pallet_active::<P::TargetChain, P::SourceChain, _>(
+						&self.target_client,
+						self.lane_id,
+					)
+					.await,
+			None =>
+				ensure_messages_pallet_active::<P::TargetChain, P::SourceChain, _>(
+					&self.target_client,
+				)
+				.await,
+		}
 	}
 }
 
@@ -120,9 +963,30 @@ impl<P: SubstrateMessageLane, SourceClnt: Clone, TargetClnt: Clone> Clone
 			target_client: self.target_client.clone(),
 			source_client: self.source_client.clone(),
 			lane_id: self.lane_id,
-			relayer_id_at_source: self.relayer_id_at_source.clone(),
+			relayer_ids_at_source: self.relayer_ids_at_source.clone(),
 			transaction_params: self.transaction_params.clone(),
+			proxy: self.proxy.clone(),
 			source_to_target_headers_relay: self.source_to_target_headers_relay.clone(),
+			fee_budget: self.fee_budget.clone(),
+			fee_safety_margin_percent: self.fee_safety_margin_percent,
+			balance_guard: self.balance_guard.clone(),
+			confirmation_guard: self.confirmation_guard.clone(),
+			fee_multiplier_guard: self.fee_multiplier_guard.clone(),
+			fee_estimation: self.fee_estimation.clone(),
+			sync_confirmations: self.sync_confirmations,
+			preempted_messages: self.preempted_messages.clone(),
+			control: self.control.clone(),
+			event_sink: self.event_sink.clone(),
+			paused_lane_idler: self.paused_lane_idler.clone(),
+			fee_accounting: self.fee_accounting.clone(),
+			runtime_upgrade_guard: self.runtime_upgrade_guard.clone(),
+			synced_header_age_guard: self.synced_header_age_guard.clone(),
+			block_weight_headroom: self.block_weight_headroom.clone(),
+			rate_limit_quota_guard: self.rate_limit_quota_guard.clone(),
+			proof_format: self.proof_format,
+			global_pause_guard: self.global_pause_guard.clone(),
+			header_progress_guard: self.header_progress_guard.clone(),
+			dispatch_refunds: self.dispatch_refunds.clone(),
 		}
 	}
 }
@@ -156,6 +1020,256 @@ impl<
 	}
 }
 
+impl<
+		P: SubstrateMessageLane,
+		SourceClnt: Client<P::SourceChain>,
+		TargetClnt: Client<P::TargetChain>,
+	> SubstrateMessagesTarget<P, SourceClnt, TargetClnt>
+where
+	AccountIdOf<P::TargetChain>: From<<AccountKeyPairOf<P::TargetChain> as Pair>::Public>,
+	BalanceOf<P::SourceChain>: TryFrom<BalanceOf<P::TargetChain>>,
+{
+	/// Check the delivery conditions and submit the messages delivery transaction.
+	async fn try_submit_messages_proof(
+		&self,
+		maybe_batch_tx: Option<
+			BatchProofTransaction<P::TargetChain, P::SourceChain, P::TargetBatchCallBuilder>,
+		>,
+		generated_at_header: SourceHeaderIdOf<MessageLaneAdapter<P>>,
+		nonces: RangeInclusive<MessageNonce>,
+		proof: <MessageLaneAdapter<P> as MessageLane>::MessagesProof,
+	) -> Result<
+		NoncesSubmitArtifacts<DeliveryTransactionTracker<P::TargetChain, TargetClnt>>,
+		SubstrateError,
+	> {
+		if let Some(ref global_pause_guard) = self.global_pause_guard {
+			global_pause_guard.ensure_unpaused(self.lane_id)?;
+		}
+
+		let spec_version = self.target_client.simple_runtime_version().await?.spec_version;
+		if !self.control.is_target_spec_version_allowed(spec_version) {
+			log::error!(
+				target: "bridge",
+				"Deferring delivery of {} -> {} messages {:?}: {} runtime spec version {} is not \
+				allowed. Check the new runtime and update the allowed versions!",
+				P::SourceChain::NAME,
+				P::TargetChain::NAME,
+				nonces,
+				P::TargetChain::NAME,
+				spec_version,
+			);
+			return Err(SubstrateError::Custom(format!(
+				"{} runtime spec version {} is not allowed",
+				P::TargetChain::NAME,
+				spec_version,
+			)))
+		}
+		if let Some(ref runtime_upgrade_guard) = self.runtime_upgrade_guard {
+			if let Err(e) = runtime_upgrade_guard.ensure_settled(&self.target_client).await {
+				log::debug!(
+					target: "bridge",
+					"Deferring delivery of {} -> {} messages {:?}: {}",
+					P::SourceChain::NAME,
+					P::TargetChain::NAME,
+					nonces,
+					e,
+				);
+				return Err(e)
+			}
+		}
+		if self
+			.synced_header_age_guard
+			.as_ref()
+			.map(|guard| guard.is_delivery_paused())
+			.unwrap_or(false)
+		{
+			log::error!(
+				target: "bridge",
+				"Deferring delivery of {} -> {} messages {:?}: the {} header, last synced to {}, \
+				is stale",
+				P::SourceChain::NAME,
+				P::TargetChain::NAME,
+				nonces,
+				P::SourceChain::NAME,
+				P::TargetChain::NAME,
+			);
+			return Err(SubstrateError::Custom(format!(
+				"Delivery is paused until new {} headers are synced to {}",
+				P::SourceChain::NAME,
+				P::TargetChain::NAME,
+			)))
+		}
+		if self
+			.header_progress_guard
+			.as_ref()
+			.map(|guard| guard.is_delivery_paused())
+			.unwrap_or(false)
+		{
+			log::error!(
+				target: "bridge",
+				"Deferring delivery of {} -> {} messages {:?}: required {} headers aren't synced \
+				to {}",
+				P::SourceChain::NAME,
+				P::TargetChain::NAME,
+				nonces,
+				P::SourceChain::NAME,
+				P::TargetChain::NAME,
+			);
+			return Err(SubstrateError::Custom(format!(
+				"Delivery is paused until required {} headers are synced to {}",
+				P::SourceChain::NAME,
+				P::TargetChain::NAME,
+			)))
+		}
+		if self
+			.confirmation_guard
+			.as_ref()
+			.map(|guard| guard.is_delivery_paused())
+			.unwrap_or(false)
+		{
+			log::error!(
+				target: "bridge",
+				"Deferring delivery of {} -> {} messages {:?}: the relayer can't pay for delivery \
+				confirmations at {}",
+				P::SourceChain::NAME,
+				P::TargetChain::NAME,
+				nonces,
+				P::SourceChain::NAME,
+			);
+			return Err(SubstrateError::Custom(format!(
+				"Delivery is paused until the relayer can pay for delivery confirmations at {}",
+				P::SourceChain::NAME,
+			)))
+		}
+		if let Some(ref fee_multiplier_guard) = self.fee_multiplier_guard {
+			if let Err(e) = fee_multiplier_guard.ensure_below_ceiling(&self.target_client).await {
+				log::debug!(
+					target: "bridge",
+					"Deferring delivery of {} -> {} messages {:?}: {}",
+					P::SourceChain::NAME,
+					P::TargetChain::NAME,
+					nonces,
+					e,
+				);
+				return Err(e)
+			}
+		}
+
+		// rewards for delivered messages are distributed between all relayer accounts
+		let messages_proof_call = make_messages_delivery_call::<P>(
+			self.relayer_ids_at_source.select(),
+			proof.1.nonces_start..=proof.1.nonces_end,
+			proof,
+			maybe_batch_tx.is_none(),
+		);
+		let messages_proof_call = match self.proxy {
+			Some(ref proxy) => proxy.wrap(messages_proof_call)?,
+			None => messages_proof_call,
+		};
+		let final_call = match maybe_batch_tx {
+			Some(batch_tx) => batch_tx.append_call_and_build(messages_proof_call),
+			None => messages_proof_call,
+		};
+
+		let transaction_params = self.transaction_params.clone().map(Ok).unwrap_or_else(|| {
+			// this error shall never happen in practice, so it not deserves
+			// a separate error variant
+			Err(SubstrateError::Custom(format!(
+				"Cannot sign transaction of {} chain",
+				P::TargetChain::NAME,
+			)))
+		})?;
+		if let Some(ref rate_limit_quota_guard) = self.rate_limit_quota_guard {
+			let signer: AccountIdOf<P::TargetChain> = transaction_params.signer.public().into();
+			if let Err(e) =
+				rate_limit_quota_guard.ensure_quota_left(&self.target_client, &signer).await
+			{
+				log::debug!(
+					target: "bridge",
+					"Deferring delivery of {} -> {} messages {:?}: {}",
+					P::SourceChain::NAME,
+					P::TargetChain::NAME,
+					nonces,
+					e,
+				);
+				return Err(e)
+			}
+		}
+		let fee = if !self.fee_budget.is_unlimited() || self.balance_guard.is_some() {
+			let estimated_fee = estimate_transaction_fee::<P::TargetChain>(
+				&self.target_client,
+				&transaction_params.signer,
+				final_call.clone(),
+			)
+			.await
+			.map(|fee| fee.unique_saturated_into());
+			let fee = match self.fee_estimation {
+				Some(ref fee_estimation) =>
+					fee_estimation.fee_or_fallback::<P::TargetChain>(estimated_fee)?,
+				None => Some(estimated_fee?),
+			};
+			let fee = fee.map(|fee| with_fee_safety_margin(fee, self.fee_safety_margin_percent));
+			if let (Some(fee), Some(ref balance_guard)) = (fee, &self.balance_guard) {
+				let free_balance = balance_guard.free_balance(&self.target_client).await?;
+				if let Err(e) = balance_guard.ensure_sufficient::<P::TargetChain>(free_balance, fee)
+				{
+					log::error!(
+						target: "bridge",
+						"Deferring delivery of {} -> {} messages {:?}: {}. Top up the relayer account!",
+						P::SourceChain::NAME,
+						P::TargetChain::NAME,
+						nonces,
+						e,
+					);
+					return Err(e)
+				}
+			}
+			fee
+		} else if self.fee_accounting.is_some() {
+			// the fee is only used for accounting, so the transaction is submitted even if the
+			// fee can't be estimated
+			estimate_transaction_fee::<P::TargetChain>(
+				&self.target_client,
+				&transaction_params.signer,
+				final_call.clone(),
+			)
+			.await
+			.ok()
+			.map(|fee| fee.unique_saturated_into())
+		} else {
+			None
+		};
+
+		// other relayers may have delivered messages since they have been selected for delivery,
+		// so the target chain state is checked right before submitting the transaction
+		self.ensure_batch_not_delivered(&generated_at_header, &nonces).await?;
+		if let Some(fee) = fee {
+			self.fee_budget.spend(fee);
+		}
+		let tx_tracker = self
+			.target_client
+			.submit_and_watch_signed_extrinsic(
+				&transaction_params.signer,
+				move |best_block_id, transaction_nonce| {
+					Ok(UnsignedTransaction::new(final_call.into(), transaction_nonce)
+						.era(TransactionEra::new(best_block_id, transaction_params.mortality))
+						.priority(transaction_params.priority))
+				},
+			)
+			.await?;
+		if let (Some(ref fee_accounting), Some(fee)) = (&self.fee_accounting, fee) {
+			fee_accounting.note(TransactionType::MessagesDelivery, fee);
+		}
+		let tx_tracker = DeliveryTransactionTracker::new(
+			tx_tracker,
+			self.dispatch_refunds.clone(),
+			self.target_client.clone(),
+			nonces.clone(),
+		);
+		Ok(NoncesSubmitArtifacts { nonces, tx_tracker })
+	}
+}
+
 #[async_trait]
 impl<
 		P: SubstrateMessageLane,
@@ -168,7 +1282,7 @@ where
 {
 	type BatchTransaction =
 		BatchProofTransaction<P::TargetChain, P::SourceChain, P::TargetBatchCallBuilder>;
-	type TransactionTracker = TransactionTracker<P::TargetChain, TargetClnt>;
+	type TransactionTracker = DeliveryTransactionTracker<P::TargetChain, TargetClnt>;
 
 	async fn state(&self) -> Result<TargetClientState<MessageLaneAdapter<P>>, SubstrateError> {
 		// we can't continue to deliver confirmations if source node is out of sync, because
@@ -181,7 +1295,33 @@ where
 		// we can't relay messages if messages pallet at target chain is halted
 		self.ensure_pallet_active().await?;
 
-		read_client_state_from_both_chains(&self.target_client, &self.source_client).await
+		let state = read_client_state_from_both_chains(
+			&self.target_client,
+			&self.source_client,
+			self.sync_confirmations,
+		)
+		.await?;
+		if let (Some(ref guard), Some(synced_header_id)) =
+			(&self.synced_header_age_guard, state.actual_best_finalized_peer_at_best_self)
+		{
+			guard
+				.update_synced_header::<P::SourceChain, P::TargetChain>(
+					&self.source_client,
+					synced_header_id.hash(),
+				)
+				.await;
+		}
+		if let (Some(ref guard), Some(synced_header_id)) =
+			(&self.header_progress_guard, state.actual_best_finalized_peer_at_best_self)
+		{
+			guard.note_synced_header(synced_header_id.number(), Instant::now());
+		}
+		if let Some(ref block_weight_headroom) = self.block_weight_headroom {
+			block_weight_headroom
+				.update::<P::TargetChain>(&self.target_client, state.best_self.hash())
+				.await;
+		}
+		Ok(state)
 	}
 
 	async fn latest_received_nonce(
@@ -235,12 +1375,14 @@ where
 			P::SourceChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
 			&self.lane_id,
 		);
-		let proof = self
-			.target_client
-			.prove_storage(id.hash(), vec![inbound_data_key])
-			.await?
-			.into_iter_nodes()
-			.collect();
+		let proof = prove_storage_in_format(
+			&self.target_client,
+			id.hash(),
+			vec![inbound_data_key],
+			None,
+			self.proof_format,
+		)
+		.await?;
 		let proof = FromBridgedChainMessagesDeliveryProof {
 			bridged_header_hash: id.1,
 			storage_proof: proof,
@@ -252,47 +1394,45 @@ where
 	async fn submit_messages_proof(
 		&self,
 		maybe_batch_tx: Option<Self::BatchTransaction>,
-		_generated_at_header: SourceHeaderIdOf<MessageLaneAdapter<P>>,
+		generated_at_header: SourceHeaderIdOf<MessageLaneAdapter<P>>,
 		nonces: RangeInclusive<MessageNonce>,
 		proof: <MessageLaneAdapter<P> as MessageLane>::MessagesProof,
 	) -> Result<NoncesSubmitArtifacts<Self::TransactionTracker>, SubstrateError> {
-		let messages_proof_call = make_messages_delivery_call::<P>(
-			self.relayer_id_at_source.clone(),
-			proof.1.nonces_start..=proof.1.nonces_end,
-			proof,
-			maybe_batch_tx.is_none(),
-		);
-		let final_call = match maybe_batch_tx {
-			Some(batch_tx) => batch_tx.append_call_and_build(messages_proof_call),
-			None => messages_proof_call,
-		};
-
-		let transaction_params = self.transaction_params.clone().map(Ok).unwrap_or_else(|| {
-			// this error shall never happen in practice, so it not deserves
-			// a separate error variant
-			Err(SubstrateError::Custom(format!(
-				"Cannot sign transaction of {} chain",
-				P::TargetChain::NAME,
-			)))
-		})?;
-		let tx_tracker = self
-			.target_client
-			.submit_and_watch_signed_extrinsic(
-				&transaction_params.signer,
-				move |best_block_id, transaction_nonce| {
-					Ok(UnsignedTransaction::new(final_call.into(), transaction_nonce)
-						.era(TransactionEra::new(best_block_id, transaction_params.mortality)))
+		let result = self
+			.try_submit_messages_proof(maybe_batch_tx, generated_at_header, nonces.clone(), proof)
+			.await;
+		if let Some(ref event_sink) = self.event_sink {
+			event_sink.emit(match result {
+				Ok(_) => RelayEvent::MessagesDelivered {
+					lane: self.lane_id,
+					nonces_begin: *nonces.start(),
+					nonces_end: *nonces.end(),
 				},
-			)
-			.await?;
-		Ok(NoncesSubmitArtifacts { nonces, tx_tracker })
+				Err(ref e) => RelayEvent::MessagesDeliverySkipped {
+					lane: self.lane_id,
+					nonces_begin: *nonces.start(),
+					nonces_end: *nonces.end(),
+					reason: e.to_string(),
+				},
+			});
+		}
+		result
 	}
 
 	async fn require_source_header_on_target(
 		&self,
 		id: SourceHeaderIdOf<MessageLaneAdapter<P>>,
 	) -> Result<Option<Self::BatchTransaction>, SubstrateError> {
-		if let Some(ref source_to_target_headers_relay) = self.source_to_target_headers_relay {
+		// without the headers relay, the guard may start the on-demand relay
+		let source_to_target_headers_relay = match self.source_to_target_headers_relay {
+			Some(ref source_to_target_headers_relay) =>
+				Some(source_to_target_headers_relay.clone()),
+			None => self
+				.header_progress_guard
+				.as_ref()
+				.and_then(|guard| guard.note_required_header(id.0, Instant::now())),
+		};
+		if let Some(source_to_target_headers_relay) = source_to_target_headers_relay {
 			if let Some(batch_tx) =
 				BatchProofTransaction::new(source_to_target_headers_relay.clone(), id.0).await?
 			{
@@ -306,6 +1446,58 @@ where
 	}
 }
 
+/// Deterministic key of the messages delivery batch.
+///
+/// Two relays, delivering the same batch, compute the same key, so it may be used to find
+/// duplicate deliveries in logs of different relay processes.
+pub fn batch_idempotency_key<SourceHeaderId: Encode>(
+	lane: LaneId,
+	nonces: &RangeInclusive<MessageNonce>,
+	generated_at_header: &SourceHeaderId,
+) -> [u8; 32] {
+	blake2_256(&(lane, nonces.start(), nonces.end(), generated_at_header).encode())
+}
+
+/// Returns number of messages of the batch with given nonces that have already been delivered.
+///
+/// The target runtime doesn't accept transactions that deliver already delivered messages, so
+/// there's no point in submitting the batch if it is delivered, even partially.
+fn delivered_nonces_count(
+	nonces: &RangeInclusive<MessageNonce>,
+	last_delivered_nonce: MessageNonce,
+) -> MessageNonce {
+	std::cmp::min(*nonces.end(), last_delivered_nonce)
+		.saturating_sub(nonces.start().saturating_sub(1))
+}
+
+/// Increase the estimated transaction fee by given percent.
+///
+/// The fee budget tracks estimated fees, so it tracks the increased fee too.
+fn with_fee_safety_margin(fee: u128, margin_percent: u32) -> u128 {
+	fee.saturating_add(fee.saturating_mul(margin_percent.into()) / 100)
+}
+
+/// Estimate fee of the transaction with given call.
+pub(crate) async fn estimate_transaction_fee<C: ChainWithTransactions>(
+	client: &impl Client<C>,
+	signer: &AccountKeyPairOf<C>,
+	call: CallOf<C>,
+) -> Result<BalanceOf<C>, SubstrateError> {
+	// the fee only depends on the call weight and the transaction size, so we may use dummy
+	// values for everything else
+	let transaction = C::sign_transaction(
+		SignParam {
+			spec_version: 0,
+			transaction_version: 0,
+			genesis_hash: Default::default(),
+			signer: signer.clone(),
+		},
+		UnsignedTransaction::new(call.into(), Zero::zero()),
+	)?;
+	let best_block_hash = client.best_header_hash().await?;
+	client.estimate_extrinsic_fee(best_block_hash, transaction).await
+}
+
 /// Make messages delivery call from given proof.
 fn make_messages_delivery_call<P: SubstrateMessageLane>(
 	relayer_id_at_source: AccountIdOf<P::SourceChain>,
@@ -323,3 +1515,205 @@ fn make_messages_delivery_call<P: SubstrateMessageLane>(
 		trace_call,
 	)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use relay_substrate_client::test_chain::TestChain;
+
+	#[test]
+	fn delivery_is_deferred_if_balance_is_not_enough() {
+		let balance_guard = BalanceGuard {
+			account_info_key: StorageKey(vec![]),
+			buffer: 10,
+			existential_deposit: 0,
+		};
+
+		// balance covers fee and buffer
+		assert!(balance_guard.ensure_sufficient::<TestChain>(110, 100).is_ok());
+		// balance covers fee, but not the buffer
+		assert!(matches!(
+			balance_guard.ensure_sufficient::<TestChain>(105, 100),
+			Err(SubstrateError::InsufficientBalance { .. }),
+		));
+		// balance doesn't cover fee
+		assert!(matches!(
+			balance_guard.ensure_sufficient::<TestChain>(50, 100),
+			Err(SubstrateError::InsufficientBalance { .. }),
+		));
+	}
+
+	#[test]
+	fn delivery_is_deferred_if_existential_deposit_is_not_preserved() {
+		let balance_guard = BalanceGuard {
+			account_info_key: StorageKey(vec![]),
+			buffer: 10,
+			existential_deposit: 0,
+		};
+		let preserving_guard = balance_guard.clone().with_existential_deposit(50);
+
+		// balance covers fee, buffer and the existential deposit
+		assert!(preserving_guard.ensure_sufficient::<TestChain>(160, 100).is_ok());
+		// paying the fee would leave the account one unit below the existential deposit
+		assert!(balance_guard.ensure_sufficient::<TestChain>(159, 100).is_ok());
+		assert!(matches!(
+			preserving_guard.ensure_sufficient::<TestChain>(159, 100),
+			Err(SubstrateError::InsufficientBalance { .. }),
+		));
+	}
+
+	#[test]
+	fn fee_safety_margin_is_added_to_estimated_fee() {
+		assert_eq!(with_fee_safety_margin(1_000, 0), 1_000);
+		assert_eq!(with_fee_safety_margin(1_000, 15), 1_150);
+		assert_eq!(with_fee_safety_margin(u128::MAX, 15), u128::MAX);
+
+		// the margin may make the relayer balance insufficient
+		let balance_guard = BalanceGuard {
+			account_info_key: StorageKey(vec![]),
+			buffer: 10,
+			existential_deposit: 0,
+		};
+		assert!(balance_guard.ensure_sufficient::<TestChain>(1_100, 1_000).is_ok());
+		assert!(balance_guard
+			.ensure_sufficient::<TestChain>(1_100, with_fee_safety_margin(1_000, 10))
+			.is_err());
+	}
+
+	#[test]
+	fn fee_estimation_fallback_is_used_when_estimation_fails() {
+		let failed = || Err(SubstrateError::Custom("estimation has failed".into()));
+		let fee_estimation =
+			|fallback| FeeEstimation::new(fallback, None).expect("valid metric name");
+
+		let skip = fee_estimation(FeeEstimationFallback::Skip);
+		assert_eq!(skip.fee_or_fallback::<TestChain>(Ok(100)).unwrap(), Some(100));
+		assert!(skip.fee_or_fallback::<TestChain>(failed()).is_err());
+		assert_eq!(skip.fallbacks.get(), 1);
+
+		let altruistic = fee_estimation(FeeEstimationFallback::Altruistic);
+		assert_eq!(altruistic.fee_or_fallback::<TestChain>(failed()).unwrap(), None);
+		assert_eq!(altruistic.fallbacks.get(), 1);
+
+		// without successful estimation, `last-known` defers delivery
+		let last_known = fee_estimation(FeeEstimationFallback::LastKnown);
+		assert!(last_known.fee_or_fallback::<TestChain>(failed()).is_err());
+		assert_eq!(last_known.fee_or_fallback::<TestChain>(Ok(100)).unwrap(), Some(100));
+		assert_eq!(last_known.fee_or_fallback::<TestChain>(Ok(200)).unwrap(), Some(200));
+		assert_eq!(last_known.fee_or_fallback::<TestChain>(failed()).unwrap(), Some(200));
+		assert_eq!(last_known.fallbacks.get(), 2);
+	}
+
+	#[test]
+	fn duplicate_batch_is_detected() {
+		let lane = LaneId([0, 0, 0, 1]);
+		let header = bp_runtime::HeaderId(100u64, sp_core::H256::repeat_byte(1));
+
+		// the same batch has the same key
+		let key = batch_idempotency_key(lane, &(11..=20), &header);
+		assert_eq!(key, batch_idempotency_key(lane, &(11..=20), &header));
+		assert_ne!(key, batch_idempotency_key(lane, &(11..=21), &header));
+		assert_ne!(key, batch_idempotency_key(LaneId([0, 0, 0, 2]), &(11..=20), &header));
+		assert_ne!(
+			key,
+			batch_idempotency_key(lane, &(11..=20), &bp_runtime::HeaderId(101u64, header.1)),
+		);
+
+		// another relay has delivered the batch
+		assert_eq!(delivered_nonces_count(&(11..=20), 10), 0);
+		assert_eq!(delivered_nonces_count(&(11..=20), 15), 5);
+		assert_eq!(delivered_nonces_count(&(11..=20), 20), 10);
+		assert_eq!(delivered_nonces_count(&(11..=20), 30), 10);
+	}
+
+	#[test]
+	fn delivery_is_deferred_while_fee_multiplier_is_above_ceiling() {
+		let guard = FeeMultiplierGuard::new(Multiplier::from_u32(2), None).unwrap();
+
+		assert!(guard.update::<TestChain>(Multiplier::from_u32(2)).is_ok());
+		assert!(guard.update::<TestChain>(Multiplier::from_rational(5, 2)).is_err());
+		assert!(guard.update::<TestChain>(Multiplier::from_u32(3)).is_err());
+		assert_eq!(guard.fee_multiplier.get(), 3.0);
+		assert!(guard.update::<TestChain>(Multiplier::from_rational(3, 2)).is_ok());
+		assert!(!guard.is_above_ceiling.load(Ordering::SeqCst));
+	}
+
+	#[test]
+	fn transactions_are_throttled_while_rate_limit_quota_is_exhausted() {
+		let guard = RateLimitQuotaGuard::new("RateLimiter.Quota".parse().unwrap(), None).unwrap();
+
+		// accounts without quota are not limited
+		assert!(guard.update::<TestChain>(None).is_ok());
+		assert!(guard.update::<TestChain>(Some(1)).is_ok());
+		assert!(guard.update::<TestChain>(Some(0)).is_err());
+		assert!(guard.update::<TestChain>(Some(0)).is_err());
+		assert_eq!(guard.throttled_transactions.get(), 2);
+		assert!(guard.update::<TestChain>(Some(10)).is_ok());
+		assert!(!guard.is_exhausted.load(Ordering::SeqCst));
+	}
+
+	#[test]
+	fn block_weight_headroom_is_updated() {
+		let block_weight_headroom = BlockWeightHeadroom {
+			max_normal_weight: Weight::from_parts(100, 10),
+			headroom: WeightHeadroom::default(),
+		};
+		assert_eq!(block_weight_headroom.headroom().get(), None);
+
+		let mut consumed_weight = ConsumedWeight::default();
+		consumed_weight.set(Weight::from_parts(60, 2), DispatchClass::Normal);
+		// weight of mandatory transactions doesn't count
+		consumed_weight.set(Weight::from_parts(1000, 1000), DispatchClass::Mandatory);
+		block_weight_headroom.note_consumed_weight::<TestChain>(&consumed_weight);
+		assert_eq!(block_weight_headroom.headroom().get(), Some(Weight::from_parts(40, 8)));
+
+		consumed_weight.set(Weight::from_parts(120, 2), DispatchClass::Normal);
+		block_weight_headroom.note_consumed_weight::<TestChain>(&consumed_weight);
+		assert_eq!(block_weight_headroom.headroom().get(), Some(Weight::from_parts(0, 8)));
+	}
+
+	#[test]
+	fn delivery_is_paused_while_synced_header_is_stale() {
+		let guard = SyncedHeaderAgeGuard::new(Some(Duration::from_secs(600)), true, None).unwrap();
+
+		guard.update::<TestChain, TestChain>(Duration::from_secs(30));
+		assert_eq!(guard.age.get(), 30.0);
+		assert!(!guard.is_delivery_paused());
+		guard.update::<TestChain, TestChain>(Duration::from_secs(601));
+		assert!(guard.is_delivery_paused());
+		guard.update::<TestChain, TestChain>(Duration::from_secs(12));
+		assert!(!guard.is_delivery_paused());
+
+		// without pausing, the stale header is only reported
+		let guard = SyncedHeaderAgeGuard::new(Some(Duration::from_secs(600)), false, None).unwrap();
+		guard.update::<TestChain, TestChain>(Duration::from_secs(601));
+		assert!(guard.is_stale.load(Ordering::SeqCst));
+		assert!(!guard.is_delivery_paused());
+
+		// without threshold, the age is only exposed
+		let guard = SyncedHeaderAgeGuard::new(None, true, None).unwrap();
+		guard.update::<TestChain, TestChain>(Duration::from_secs(6000));
+		assert_eq!(guard.age.get(), 6000.0);
+		assert!(!guard.is_delivery_paused());
+	}
+
+	#[test]
+	fn delivery_is_deferred_while_runtime_upgrade_settles() {
+		let guard = RuntimeUpgradeGuard::new(Duration::from_secs(60));
+		let start = Instant::now();
+
+		// the first seen version is not an upgrade
+		assert!(guard.update::<TestChain>(1000, start).is_ok());
+		assert!(guard.update::<TestChain>(1000, start + Duration::from_secs(1)).is_ok());
+
+		// the upgrade is detected and delivery is paused for the settle period
+		assert!(guard.update::<TestChain>(1001, start + Duration::from_secs(10)).is_err());
+		assert!(guard.update::<TestChain>(1001, start + Duration::from_secs(69)).is_err());
+		assert!(guard.update::<TestChain>(1001, start + Duration::from_secs(70)).is_ok());
+		assert!(guard.update::<TestChain>(1001, start + Duration::from_secs(71)).is_ok());
+
+		// another upgrade restarts the settle period
+		assert!(guard.update::<TestChain>(1002, start + Duration::from_secs(100)).is_err());
+		assert!(guard.update::<TestChain>(1002, start + Duration::from_secs(160)).is_ok());
+	}
+}
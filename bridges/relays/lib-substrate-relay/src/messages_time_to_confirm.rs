@@ -0,0 +1,207 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Estimation of time, required to deliver and confirm given message.
+//!
+//! The estimation is based on the lane state at both chains: messages that are queued before
+//! the message need to be delivered first. Every delivery transaction takes some time, which
+//! is bounded by the given relay timings.
+
+use crate::messages_lane::SubstrateMessageLane;
+
+use bp_messages::{
+	storage_keys::{inbound_lane_data_key, outbound_lane_data_key},
+	ChainWithMessages as _, InboundLaneData, LaneId, MessageNonce, OutboundLaneData,
+};
+use relay_substrate_client::{AccountIdOf, Client};
+use std::{fmt, ops::RangeInclusive, time::Duration};
+
+/// Nonces of the lane, used to estimate time to confirmation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LaneNonces {
+	/// Latest nonce, generated at the source chain.
+	pub latest_generated_nonce: MessageNonce,
+	/// Latest nonce, delivered to the target chain.
+	pub latest_delivered_nonce: MessageNonce,
+	/// Latest nonce, which delivery is confirmed at the source chain.
+	pub latest_confirmed_nonce: MessageNonce,
+}
+
+/// Relay timings, used to estimate time to confirmation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelayTimings {
+	/// Maximal number of messages in single delivery transaction.
+	pub max_messages_in_delivery_transaction: MessageNonce,
+	/// Time between two consecutive delivery transactions.
+	pub delivery_interval: RangeInclusive<Duration>,
+	/// Time since message is delivered and until its delivery is confirmed at the source chain.
+	pub confirmation_delay: RangeInclusive<Duration>,
+}
+
+/// Estimated time to confirmation of the message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TimeToConfirm {
+	/// Message delivery is already confirmed.
+	Confirmed,
+	/// Message is delivered and waits for delivery confirmation.
+	Delivered {
+		/// Estimated time until delivery is confirmed.
+		confirmed_in: RangeInclusive<Duration>,
+	},
+	/// Message is waiting for delivery.
+	Queued {
+		/// Number of undelivered messages, including this message, that need to be delivered.
+		messages_to_deliver: MessageNonce,
+		/// Estimated time until message is delivered.
+		delivered_in: RangeInclusive<Duration>,
+		/// Estimated time until delivery is confirmed.
+		confirmed_in: RangeInclusive<Duration>,
+	},
+}
+
+impl fmt::Display for TimeToConfirm {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			TimeToConfirm::Confirmed => write!(f, "already delivered and confirmed"),
+			TimeToConfirm::Delivered { ref confirmed_in } =>
+				write!(f, "already delivered, confirmed in {}", format_duration_range(confirmed_in)),
+			TimeToConfirm::Queued { messages_to_deliver, ref delivered_in, ref confirmed_in } =>
+				write!(
+					f,
+					"{} messages to deliver, delivered in {}, confirmed in {}",
+					messages_to_deliver,
+					format_duration_range(delivered_in),
+					format_duration_range(confirmed_in),
+				),
+		}
+	}
+}
+
+/// Estimate time to confirmation of the message with given nonce.
+pub fn estimate_time_to_confirm(
+	nonce: MessageNonce,
+	lane_nonces: LaneNonces,
+	timings: &RelayTimings,
+) -> anyhow::Result<TimeToConfirm> {
+	if nonce == 0 || nonce > lane_nonces.latest_generated_nonce {
+		return Err(anyhow::format_err!(
+			"Message {} is not generated yet. Latest generated nonce is {}",
+			nonce,
+			lane_nonces.latest_generated_nonce,
+		))
+	}
+
+	if nonce <= lane_nonces.latest_confirmed_nonce {
+		return Ok(TimeToConfirm::Confirmed)
+	}
+
+	if nonce <= lane_nonces.latest_delivered_nonce {
+		return Ok(TimeToConfirm::Delivered { confirmed_in: timings.confirmation_delay.clone() })
+	}
+
+	let messages_to_deliver = nonce - lane_nonces.latest_delivered_nonce;
+	let transactions = messages_to_deliver
+		.saturating_add(timings.max_messages_in_delivery_transaction.saturating_sub(1))
+		.checked_div(timings.max_messages_in_delivery_transaction)
+		.unwrap_or(messages_to_deliver);
+	let transactions = u32::try_from(transactions).unwrap_or(u32::MAX);
+	let delivered_in = timings.delivery_interval.start().saturating_mul(transactions)..=
+		timings.delivery_interval.end().saturating_mul(transactions);
+	let confirmed_in = delivered_in.start().saturating_add(*timings.confirmation_delay.start())..=
+		delivered_in.end().saturating_add(*timings.confirmation_delay.end());
+
+	Ok(TimeToConfirm::Queued { messages_to_deliver, delivered_in, confirmed_in })
+}
+
+/// Read lane nonces at the best finalized blocks of both chains.
+pub async fn read_lane_nonces<P: SubstrateMessageLane>(
+	source_client: &impl Client<P::SourceChain>,
+	target_client: &impl Client<P::TargetChain>,
+	lane: LaneId,
+) -> anyhow::Result<LaneNonces> {
+	let source_hash = source_client.best_finalized_header_hash().await?;
+	let outbound_lane_data: OutboundLaneData = source_client
+		.storage_value(
+			source_hash,
+			outbound_lane_data_key(P::TargetChain::WITH_CHAIN_MESSAGES_PALLET_NAME, &lane),
+		)
+		.await?
+		.unwrap_or_default();
+
+	let target_hash = target_client.best_finalized_header_hash().await?;
+	let inbound_lane_data: InboundLaneData<AccountIdOf<P::SourceChain>> = target_client
+		.storage_value(
+			target_hash,
+			inbound_lane_data_key(P::SourceChain::WITH_CHAIN_MESSAGES_PALLET_NAME, &lane),
+		)
+		.await?
+		.unwrap_or_default();
+
+	Ok(LaneNonces {
+		latest_generated_nonce: outbound_lane_data.latest_generated_nonce,
+		latest_delivered_nonce: inbound_lane_data.last_delivered_nonce(),
+		latest_confirmed_nonce: outbound_lane_data.latest_received_nonce,
+	})
+}
+
+fn format_duration_range(range: &RangeInclusive<Duration>) -> String {
+	format!("{}s..{}s", range.start().as_secs(), range.end().as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn timings() -> RelayTimings {
+		RelayTimings {
+			max_messages_in_delivery_transaction: 10,
+			delivery_interval: Duration::from_secs(6)..=Duration::from_secs(60),
+			confirmation_delay: Duration::from_secs(12)..=Duration::from_secs(120),
+		}
+	}
+
+	fn lane_nonces() -> LaneNonces {
+		LaneNonces {
+			latest_generated_nonce: 100,
+			latest_delivered_nonce: 50,
+			latest_confirmed_nonce: 40,
+		}
+	}
+
+	#[test]
+	fn time_to_confirm_is_estimated() {
+		assert!(estimate_time_to_confirm(101, lane_nonces(), &timings()).is_err());
+		assert_eq!(
+			estimate_time_to_confirm(40, lane_nonces(), &timings()).unwrap(),
+			TimeToConfirm::Confirmed,
+		);
+		assert_eq!(
+			estimate_time_to_confirm(41, lane_nonces(), &timings()).unwrap(),
+			TimeToConfirm::Delivered {
+				confirmed_in: Duration::from_secs(12)..=Duration::from_secs(120)
+			},
+		);
+		// 25 messages need 3 delivery transactions
+		assert_eq!(
+			estimate_time_to_confirm(75, lane_nonces(), &timings()).unwrap(),
+			TimeToConfirm::Queued {
+				messages_to_deliver: 25,
+				delivered_in: Duration::from_secs(18)..=Duration::from_secs(180),
+				confirmed_in: Duration::from_secs(30)..=Duration::from_secs(300),
+			},
+		);
+	}
+}
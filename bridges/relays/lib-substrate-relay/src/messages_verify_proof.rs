@@ -0,0 +1,145 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Local verification of messages proofs.
+//!
+//! When the target chain rejects messages delivery transaction, it is either because of the
+//! invalid proof or because of the target chain state. Verifying the proof against the state
+//! root of the source header, without any interaction with the target chain, tells one from
+//! another.
+
+use crate::{messages_lane::SubstrateMessageLane, messages_source::messages_proof_storage_keys};
+
+use bp_messages::{LaneId, MessageNonce};
+use codec::Encode;
+use relay_substrate_client::{
+	storage_proof::verify_storage_proof, Chain, Client, Error as SubstrateError, HashOf, HeaderOf,
+};
+use sp_core::storage::StorageKey;
+use sp_runtime::traits::Header as _;
+use sp_trie::StorageProof;
+use std::{fmt, ops::RangeInclusive};
+
+/// Result of the local messages proof verification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessagesProofVerification {
+	/// Size of the encoded storage proof.
+	pub proof_size: usize,
+	/// Verification error, if the proof is invalid.
+	pub error: Option<String>,
+}
+
+impl fmt::Display for MessagesProofVerification {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Proof size: {} bytes", self.proof_size)?;
+		match self.error {
+			Some(ref error) => write!(f, "Verification: failed ({error})"),
+			None => write!(f, "Verification: succeeded"),
+		}
+	}
+}
+
+/// Generate proof of messages with given nonces at given source chain header and verify it
+/// against the header state root.
+pub async fn verify_messages_proof<P: SubstrateMessageLane>(
+	source_client: &impl Client<P::SourceChain>,
+	at: HeaderOf<P::SourceChain>,
+	lane: LaneId,
+	nonces: RangeInclusive<MessageNonce>,
+	outbound_state_proof_required: bool,
+) -> Result<MessagesProofVerification, SubstrateError> {
+	let storage_keys =
+		messages_proof_storage_keys::<P>(lane, nonces.clone(), outbound_state_proof_required);
+	let proof = source_client.prove_storage(at.hash(), storage_keys.clone()).await?;
+	Ok(MessagesProofVerification {
+		proof_size: proof.encoded_size(),
+		error: check_messages_proof::<P::SourceChain>(
+			*at.state_root(),
+			proof,
+			&storage_keys,
+			nonces,
+			outbound_state_proof_required,
+		)
+		.err(),
+	})
+}
+
+/// Check that the proof is valid and has all values that the target chain expects.
+fn check_messages_proof<C: Chain>(
+	state_root: HashOf<C>,
+	proof: StorageProof,
+	storage_keys: &[StorageKey],
+	nonces: RangeInclusive<MessageNonce>,
+	outbound_state_proof_required: bool,
+) -> Result<(), String> {
+	let values =
+		verify_storage_proof::<C>(state_root, proof, storage_keys).map_err(|e| format!("{e:?}"))?;
+	// all messages are required, even if the lane state is also in the proof
+	if let Some((nonce, _)) = nonces.zip(values.iter()).find(|(_, value)| value.is_none()) {
+		return Err(format!("message {nonce} is missing from the proof"))
+	}
+	if outbound_state_proof_required && values.last().map(Option::is_none).unwrap_or(true) {
+		return Err("outbound lane state is missing from the proof".into())
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use relay_substrate_client::test_chain::TestChain;
+
+	fn storage_keys(keys: &[&str]) -> Vec<StorageKey> {
+		keys.iter().map(|key| StorageKey(key.as_bytes().to_vec())).collect()
+	}
+
+	#[test]
+	fn messages_proof_is_checked() {
+		let (root, proof) = bp_runtime::craft_valid_storage_proof();
+		let check = |keys: &[&str], nonces, outbound_state_proof_required| {
+			check_messages_proof::<TestChain>(
+				root,
+				StorageProof::new(proof.clone()),
+				&storage_keys(keys),
+				nonces,
+				outbound_state_proof_required,
+			)
+		};
+
+		assert_eq!(
+			check(&["key1", "key2", "key4", "key22"], 1..=2, true),
+			Err("outbound lane state is missing from the proof".into()),
+		);
+		assert_eq!(
+			check(&["key1", "key22", "key2", "key4"], 1..=2, false),
+			Err("message 2 is missing from the proof".into()),
+		);
+		assert_eq!(check(&["key1", "key2", "key4", "key22"], 1..=3, false), Ok(()));
+	}
+
+	#[test]
+	fn messages_proof_verification_is_displayed() {
+		assert_eq!(
+			MessagesProofVerification { proof_size: 100, error: None }.to_string(),
+			"Proof size: 100 bytes\nVerification: succeeded",
+		);
+		assert_eq!(
+			MessagesProofVerification { proof_size: 100, error: Some("bad proof".into()) }
+				.to_string(),
+			"Proof size: 100 bytes\nVerification: failed (bad proof)",
+		);
+	}
+}
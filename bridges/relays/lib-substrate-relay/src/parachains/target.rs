@@ -225,7 +225,8 @@ where
 				&transaction_params.signer,
 				move |best_block_id, transaction_nonce| {
 					Ok(UnsignedTransaction::new(call.into(), transaction_nonce)
-						.era(TransactionEra::new(best_block_id, transaction_params.mortality)))
+						.era(TransactionEra::new(best_block_id, transaction_params.mortality))
+						.priority(transaction_params.priority))
 				},
 			)
 			.await
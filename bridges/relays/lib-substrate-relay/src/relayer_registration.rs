@@ -0,0 +1,187 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Registration of the relayer at the bridge relayers pallet.
+//!
+//! Transactions of registered relayers get the priority boost at the chain with the bridge
+//! relayers pallet. The registration is valid until the given block. The stake, that is
+//! reserved from the relayer account, is defined by the chain runtime.
+
+use crate::TransactionParams;
+
+use bp_relayers::Registration;
+use bp_runtime::{storage_map_final_key, HeaderIdProvider};
+use codec::Encode;
+use frame_support::Blake2_128Concat;
+use relay_substrate_client::{
+	AccountIdOf, AccountKeyPairOf, BalanceOf, BlockNumberOf, CallOf, Chain, ChainWithTransactions,
+	Client, Error as SubstrateError, HashOf, TransactionEra, UnsignedTransaction,
+};
+use relay_utils::{TrackedTransactionStatus, TransactionTracker};
+use sp_core::Pair;
+use sp_runtime::traits::Saturating;
+
+/// Name of the map with relayer registrations in the bridge relayers pallet.
+const REGISTERED_RELAYERS_MAP_NAME: &str = "RegisteredRelayers";
+
+/// Returns true if the relayer registration is still valid at given block.
+pub fn is_relayer_registered<BlockNumber: PartialOrd, Balance>(
+	registration: Option<&Registration<BlockNumber, Balance>>,
+	at_block: BlockNumber,
+) -> bool {
+	registration.map_or(false, |registration| registration.valid_till > at_block)
+}
+
+/// Returns the reason why the relayer with given registration isn't eligible for rewards at
+/// given block, or `None` if it is eligible.
+pub fn reward_ineligibility_reason<BlockNumber: PartialOrd + std::fmt::Debug, Balance>(
+	registration: Option<&Registration<BlockNumber, Balance>>,
+	at_block: BlockNumber,
+) -> Option<String> {
+	match registration {
+		None => Some("relayer is not registered".into()),
+		Some(registration) if registration.valid_till <= at_block =>
+			Some(format!("relayer registration has expired at block {:?}", registration.valid_till)),
+		Some(_) => None,
+	}
+}
+
+/// Register the relayer at the bridge relayers pallet of the chain, if it isn't registered yet.
+///
+/// The registration is valid for `lease` blocks after the best block. Returns an error if the
+/// registration call is not supported (`encode_register_relayer` returns `None`) or if the
+/// registration transaction has failed.
+pub async fn ensure_relayer_registered<C: ChainWithTransactions>(
+	client: &impl Client<C>,
+	relayers_pallet_name: &str,
+	transaction_params: TransactionParams<AccountKeyPairOf<C>>,
+	lease: u32,
+	encode_register_relayer: impl FnOnce(BlockNumberOf<C>) -> Option<CallOf<C>>,
+) -> anyhow::Result<()>
+where
+	AccountIdOf<C>: From<<AccountKeyPairOf<C> as Pair>::Public>,
+{
+	let relayer: AccountIdOf<C> = transaction_params.signer.public().into();
+	let best_id = client.best_header().await?.id();
+	let registration =
+		read_registration::<C>(client, relayers_pallet_name, &relayer, best_id.hash()).await?;
+	if is_relayer_registered(registration.as_ref(), best_id.number()) {
+		log::info!(
+			target: "bridge",
+			"Relayer {:?} is already registered at {}: {:?}",
+			relayer,
+			C::NAME,
+			registration,
+		);
+		return Ok(())
+	}
+
+	let valid_till = best_id.number().saturating_add(lease.into());
+	let call = encode_register_relayer(valid_till).ok_or_else(|| {
+		anyhow::format_err!("Registering relayers at {} is not supported", C::NAME)
+	})?;
+	log::info!(
+		target: "bridge",
+		"Registering relayer {:?} at {} until block {}",
+		relayer,
+		C::NAME,
+		valid_till,
+	);
+	let tx_tracker = client
+		.submit_and_watch_signed_extrinsic(
+			&transaction_params.signer,
+			move |best_block_id, transaction_nonce| {
+				Ok(UnsignedTransaction::new(call.into(), transaction_nonce)
+					.era(TransactionEra::new(best_block_id, transaction_params.mortality)))
+			},
+		)
+		.await?;
+	let finalized_at = match tx_tracker.wait().await {
+		TrackedTransactionStatus::Finalized(finalized_at) => finalized_at,
+		TrackedTransactionStatus::Lost =>
+			return Err(anyhow::format_err!(
+				"Transaction that registers relayer {:?} is considered lost at {}",
+				relayer,
+				C::NAME,
+			)),
+	};
+
+	// the transaction may be finalized, but the call may still fail
+	let registration =
+		read_registration::<C>(client, relayers_pallet_name, &relayer, finalized_at.hash()).await?;
+	if !is_relayer_registered(registration.as_ref(), finalized_at.number()) {
+		return Err(anyhow::format_err!(
+			"Failed to register relayer {:?} at {}. Registration: {:?}",
+			relayer,
+			C::NAME,
+			registration,
+		))
+	}
+
+	log::info!(target: "bridge", "Relayer {:?} is registered at {}", relayer, C::NAME);
+	Ok(())
+}
+
+/// Read relayer registration at given block.
+pub(crate) async fn read_registration<C: Chain>(
+	client: &impl Client<C>,
+	relayers_pallet_name: &str,
+	relayer: &AccountIdOf<C>,
+	at: HashOf<C>,
+) -> Result<Option<Registration<BlockNumberOf<C>, BalanceOf<C>>>, SubstrateError> {
+	client
+		.storage_value(
+			at,
+			storage_map_final_key::<Blake2_128Concat>(
+				relayers_pallet_name,
+				REGISTERED_RELAYERS_MAP_NAME,
+				&relayer.encode(),
+			),
+		)
+		.await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn already_registered_relayer_is_not_registered_again() {
+		let registration = Registration { valid_till: 100u32, stake: 10u64 };
+		assert!(is_relayer_registered(Some(&registration), 99));
+
+		// expired registration needs to be renewed
+		assert!(!is_relayer_registered(Some(&registration), 100));
+		assert!(!is_relayer_registered::<u32, u64>(None, 99));
+	}
+
+	#[test]
+	fn reward_eligibility_is_checked() {
+		let registration = Registration { valid_till: 100u32, stake: 10u64 };
+
+		// eligible relayer
+		assert_eq!(reward_ineligibility_reason(Some(&registration), 99), None);
+		// ineligible relayers
+		assert_eq!(
+			reward_ineligibility_reason(Some(&registration), 100),
+			Some("relayer registration has expired at block 100".into()),
+		);
+		assert_eq!(
+			reward_ineligibility_reason::<u32, u64>(None, 99),
+			Some("relayer is not registered".into()),
+		);
+	}
+}
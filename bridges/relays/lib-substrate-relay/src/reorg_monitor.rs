@@ -0,0 +1,255 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Monitoring of chain reorganizations.
+//!
+//! The relay follows the best headers of the chain and remembers recent best headers. When
+//! the new best header is not a descendant of the previous best header, we look for their
+//! common ancestor. The number of previous best chain headers above this ancestor is the
+//! reorg depth.
+
+use futures::StreamExt;
+use num_traits::{One, Zero};
+use relay_substrate_client::{
+	BlockNumberOf, Chain, Client, Error as SubstrateError, HashOf, HeaderOf,
+	ANCIENT_BLOCK_THRESHOLD,
+};
+use relay_utils::{
+	metrics::{metric_name, register, Gauge, Registry, U64},
+	relay_loop::RECONNECT_DELAY,
+	UniqueSaturatedInto,
+};
+use sp_runtime::traits::{AtLeast32BitUnsigned, Header as _};
+use std::collections::VecDeque;
+
+/// Recent best headers of the chain.
+///
+/// The history is bounded, so reorgs that are deeper than the history are reported with
+/// the depth of the history.
+#[derive(Debug)]
+pub struct BestHeadersHistory<Number, Hash> {
+	headers: VecDeque<(Number, Hash)>,
+}
+
+impl<Number: AtLeast32BitUnsigned + Copy, Hash: PartialEq> BestHeadersHistory<Number, Hash> {
+	/// Create empty history.
+	pub fn new() -> Self {
+		BestHeadersHistory { headers: VecDeque::new() }
+	}
+
+	/// Returns true if the header is in the history.
+	pub fn contains(&self, number: Number, hash: &Hash) -> bool {
+		self.headers.iter().any(|(n, h)| *n == number && h == hash)
+	}
+
+	/// Returns true if the header with given number is older than any header in the history.
+	pub fn is_older(&self, number: Number) -> bool {
+		self.headers.front().map(|(oldest, _)| number < *oldest).unwrap_or(true)
+	}
+
+	/// Import new best header, which is the descendant of the `ancestor_number` header from
+	/// the history (or the `ancestor_number` header itself).
+	///
+	/// Returns the reorg depth: number of previous best chain headers that are not ancestors
+	/// of the new best header.
+	pub fn import(&mut self, number: Number, hash: Hash, ancestor_number: Number) -> Number {
+		let depth = self
+			.headers
+			.back()
+			.map(|(best, _)| best.saturating_sub(ancestor_number))
+			.unwrap_or_else(Zero::zero);
+
+		while self.headers.back().map(|(n, _)| *n > ancestor_number).unwrap_or(false) {
+			self.headers.pop_back();
+		}
+		if !self.headers.back().map(|(n, h)| *n == number && *h == hash).unwrap_or(false) {
+			self.headers.push_back((number, hash));
+		}
+		while self.headers.len() > ANCIENT_BLOCK_THRESHOLD as usize {
+			self.headers.pop_front();
+		}
+
+		depth
+	}
+}
+
+impl<Number: AtLeast32BitUnsigned + Copy, Hash: PartialEq> Default
+	for BestHeadersHistory<Number, Hash>
+{
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Start background task that is following best headers of the chain and exposes reorg
+/// depth metrics.
+///
+/// Reorgs that are deeper than `alert_depth` are logged as errors.
+pub fn spawn<C: Chain>(
+	client: impl Client<C>,
+	prefix: String,
+	alert_depth: u32,
+	registry: &Registry,
+) -> anyhow::Result<()> {
+	let max_depth_metric = Gauge::<U64>::new(
+		metric_name(Some(&prefix), "max_reorg_depth"),
+		format!("Maximal depth of {} reorgs, seen by the relay", C::NAME),
+	)?;
+	register(max_depth_metric.clone(), registry)?;
+	let recent_depth_metric = Gauge::<U64>::new(
+		metric_name(Some(&prefix), "recent_reorg_depth"),
+		format!("Depth of the most recent {} reorg, seen by the relay", C::NAME),
+	)?;
+	register(recent_depth_metric.clone(), registry)?;
+
+	async_std::task::spawn(async move {
+		let mut history = BestHeadersHistory::new();
+		let mut max_depth = 0u64;
+		loop {
+			let mut best_headers = match client.subscribe_best_headers().await {
+				Ok(best_headers) => best_headers,
+				Err(e) => {
+					log::debug!(
+						target: "bridge",
+						"Failed to subscribe to {} best headers: {:?}",
+						C::NAME,
+						e,
+					);
+					async_std::task::sleep(RECONNECT_DELAY).await;
+					continue
+				},
+			};
+
+			while let Some(header) = best_headers.next().await {
+				let number = *header.number();
+				let hash = header.hash();
+				let ancestor_number = if history.contains(number, &hash) {
+					number
+				} else {
+					match find_ancestor::<C>(&client, &history, &header).await {
+						Ok(ancestor_number) => ancestor_number,
+						Err(e) => {
+							log::debug!(
+								target: "bridge",
+								"Failed to find {} header {:?} ancestor: {:?}",
+								C::NAME,
+								hash,
+								e,
+							);
+							continue
+						},
+					}
+				};
+
+				let depth: u64 =
+					history.import(number, hash, ancestor_number).unique_saturated_into();
+				if depth == 0 {
+					continue
+				}
+
+				max_depth = max_depth.max(depth);
+				max_depth_metric.set(max_depth);
+				recent_depth_metric.set(depth);
+				if depth > alert_depth as u64 {
+					log::error!(
+						target: "bridge",
+						"{} reorg of depth {} has been detected. New best header: {:?}. It is \
+						deeper than the alert depth {}",
+						C::NAME,
+						depth,
+						hash,
+						alert_depth,
+					);
+				} else {
+					log::info!(
+						target: "bridge",
+						"{} reorg of depth {} has been detected. New best header: {:?}",
+						C::NAME,
+						depth,
+						hash,
+					);
+				}
+			}
+
+			log::debug!(target: "bridge", "{} best headers subscription has finished", C::NAME);
+			async_std::task::sleep(RECONNECT_DELAY).await;
+		}
+	});
+
+	Ok(())
+}
+
+/// Find number of the header from the history, which is the ancestor of given header.
+///
+/// If there's no such header in the history, returns number of the oldest checked ancestor.
+async fn find_ancestor<C: Chain>(
+	client: &impl Client<C>,
+	history: &BestHeadersHistory<BlockNumberOf<C>, HashOf<C>>,
+	header: &HeaderOf<C>,
+) -> Result<BlockNumberOf<C>, SubstrateError> {
+	let mut number = *header.number();
+	let mut parent_hash = *header.parent_hash();
+	loop {
+		if number.is_zero() {
+			return Ok(number)
+		}
+
+		let parent_number = number - One::one();
+		if history.contains(parent_number, &parent_hash) || history.is_older(parent_number) {
+			return Ok(parent_number)
+		}
+
+		let parent = client.header_by_hash(parent_hash).await?;
+		number = parent_number;
+		parent_hash = *parent.parent_hash();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reorg_depth_is_computed() {
+		let mut history = BestHeadersHistory::<u32, u32>::new();
+		for number in 1..=5 {
+			assert_eq!(history.import(number, number, number - 1), 0);
+		}
+
+		// the same best header is reported again
+		assert_eq!(history.import(5, 5, 5), 0);
+		// headers 4 and 5 are replaced with 4' and 5'
+		assert!(history.contains(3, &3));
+		assert_eq!(history.import(5, 50, 3), 2);
+		assert!(!history.contains(4, &4));
+		assert!(!history.contains(5, &5));
+		assert!(history.contains(5, &50));
+		// some headers are missing from the subscription, but it is not a reorg
+		assert_eq!(history.import(8, 80, 5), 0);
+	}
+
+	#[test]
+	fn history_is_bounded() {
+		let mut history = BestHeadersHistory::<u32, u32>::new();
+		for number in 1..=ANCIENT_BLOCK_THRESHOLD * 2 {
+			history.import(number, number, number - 1);
+		}
+
+		assert_eq!(history.headers.len(), ANCIENT_BLOCK_THRESHOLD as usize);
+		assert!(history.is_older(ANCIENT_BLOCK_THRESHOLD));
+		assert!(!history.is_older(ANCIENT_BLOCK_THRESHOLD + 1));
+	}
+}
@@ -0,0 +1,167 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Export of the relay activity as SCALE-encoded event records.
+//!
+//! Every record is written as SCALE-encoded `Vec<u8>` (the compact length, followed by the
+//! encoded `RelayEventRecord`), so the stream may be decoded record by record. The encoding
+//! is stable: variants have explicit indices, new variants and fields are only appended.
+
+use bp_messages::{LaneId, MessageNonce};
+use codec::{Decode, Encode};
+use parking_lot::Mutex;
+use std::{
+	fs::OpenOptions,
+	io::Write,
+	os::unix::{fs::FileTypeExt, net::UnixStream},
+	path::Path,
+	sync::Arc,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Relay activity event.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub enum RelayEvent {
+	/// Messages delivery transaction has been submitted to the target chain.
+	#[codec(index = 0)]
+	MessagesDelivered {
+		/// Lane of delivered messages.
+		lane: LaneId,
+		/// Nonce of the first delivered message.
+		nonces_begin: MessageNonce,
+		/// Nonce of the last delivered message.
+		nonces_end: MessageNonce,
+	},
+	/// Messages delivery confirmation transaction has been submitted to the source chain.
+	#[codec(index = 1)]
+	DeliveryConfirmed {
+		/// Lane of confirmed messages.
+		lane: LaneId,
+		/// Nonce of the latest confirmed message.
+		latest_confirmed_nonce: MessageNonce,
+	},
+	/// Messages delivery transaction has not been submitted.
+	#[codec(index = 2)]
+	MessagesDeliverySkipped {
+		/// Lane of skipped messages.
+		lane: LaneId,
+		/// Nonce of the first skipped message.
+		nonces_begin: MessageNonce,
+		/// Nonce of the last skipped message.
+		nonces_end: MessageNonce,
+		/// Why the transaction has not been submitted.
+		reason: String,
+	},
+}
+
+/// Relay activity event with the moment when it has happened.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub struct RelayEventRecord {
+	/// Unix timestamp of the event (in milliseconds).
+	pub timestamp: u64,
+	/// The event itself.
+	pub event: RelayEvent,
+}
+
+impl RelayEventRecord {
+	/// Returns length-prefixed encoding of the record.
+	pub fn encode_framed(&self) -> Vec<u8> {
+		self.encode().encode()
+	}
+}
+
+/// Sink of relay events, shared by all clones.
+///
+/// The sink is a regular file, where records are appended, or the Unix socket, where records
+/// are sent to.
+#[derive(Clone)]
+pub struct ScaleEventSink {
+	writer: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl std::fmt::Debug for ScaleEventSink {
+	fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+		fmt.write_str("ScaleEventSink")
+	}
+}
+
+impl ScaleEventSink {
+	/// Open the sink at given path. If there's Unix socket at this path, the sink is connected
+	/// to the socket. Otherwise, records are appended to the file at this path.
+	pub fn open(path: &Path) -> anyhow::Result<Self> {
+		let is_socket = std::fs::metadata(path)
+			.map(|metadata| metadata.file_type().is_socket())
+			.unwrap_or(false);
+		let writer: Box<dyn Write + Send> = if is_socket {
+			Box::new(UnixStream::connect(path).map_err(|e| {
+				anyhow::format_err!("Failed to connect to event sink socket {:?}: {}", path, e)
+			})?)
+		} else {
+			Box::new(OpenOptions::new().create(true).append(true).open(path).map_err(|e| {
+				anyhow::format_err!("Failed to open event sink file {:?}: {}", path, e)
+			})?)
+		};
+		Ok(ScaleEventSink { writer: Arc::new(Mutex::new(writer)) })
+	}
+
+	/// Write the event to the sink. Failures are only logged, so that the relay isn't affected
+	/// by the sink.
+	pub fn emit(&self, event: RelayEvent) {
+		let timestamp = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|since_epoch| since_epoch.as_millis() as u64)
+			.unwrap_or(0);
+		let record = RelayEventRecord { timestamp, event };
+		let mut writer = self.writer.lock();
+		if let Err(e) = writer.write_all(&record.encode_framed()).and_then(|_| writer.flush()) {
+			log::warn!(target: "bridge", "Failed to write {:?} to the event sink: {}", record, e);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn event_record_encoding_is_stable() {
+		let record = RelayEventRecord {
+			timestamp: 1,
+			event: RelayEvent::MessagesDeliverySkipped {
+				lane: LaneId([1, 2, 3, 4]),
+				nonces_begin: 5,
+				nonces_end: 6,
+				reason: "x".into(),
+			},
+		};
+		// compact length of the record
+		let mut expected = vec![31 << 2];
+		// timestamp
+		expected.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+		// variant index and lane
+		expected.extend_from_slice(&[2, 1, 2, 3, 4]);
+		// nonces
+		expected.extend_from_slice(&[5, 0, 0, 0, 0, 0, 0, 0]);
+		expected.extend_from_slice(&[6, 0, 0, 0, 0, 0, 0, 0]);
+		// reason
+		expected.extend_from_slice(&[1 << 2, b'x']);
+		assert_eq!(record.encode_framed(), expected);
+
+		let mut encoded = &expected[..];
+		let decoded = Vec::<u8>::decode(&mut encoded).unwrap();
+		assert_eq!(RelayEventRecord::decode(&mut &decoded[..]).unwrap(), record);
+	}
+}
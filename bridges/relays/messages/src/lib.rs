@@ -34,6 +34,8 @@ mod message_race_delivery;
 mod message_race_limits;
 mod message_race_loop;
 mod message_race_receiving;
+mod message_race_serializer;
+mod message_race_stall;
 mod message_race_strategy;
 
 pub use message_race_delivery::relay_messages_range;
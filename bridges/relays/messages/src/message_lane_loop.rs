@@ -24,21 +24,47 @@
 //! finalized header. I.e. when talking about headers in lane context, we
 //! only care about finalized headers.
 
-use std::{collections::BTreeMap, fmt::Debug, future::Future, ops::RangeInclusive, time::Duration};
+use std::{
+	collections::{BTreeMap, VecDeque},
+	fmt::Debug,
+	future::Future,
+	ops::RangeInclusive,
+	sync::Arc,
+	time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
-use futures::{channel::mpsc::unbounded, future::FutureExt, stream::StreamExt};
+use futures::{
+	channel::mpsc::unbounded,
+	future::{FusedFuture, FutureExt},
+	stream::StreamExt,
+};
+use parking_lot::Mutex;
 
 use bp_messages::{LaneId, MessageNonce, UnrewardedRelayersState, Weight};
 use relay_utils::{
-	interval, metrics::MetricsParams, process_future_result, relay_loop::Client as RelayClient,
-	retry_backoff, FailedClient, TransactionTracker,
+	control::RelayControl,
+	fee_budget::FeeBudget,
+	interval,
+	metrics::{
+		metric_name, register, GaugeVec, Metric, MetricsParams, Opts, PrometheusError, Registry,
+		U64,
+	},
+	otlp::{Span, SpanContext},
+	process_future_result,
+	relay_loop::Client as RelayClient,
+	retry_backoff,
+	run_summary::LaneSummary,
+	throughput_budget::LaneThroughputBudget,
+	FailedClient, FailureKind, TransactionTracker,
 };
 
 use crate::{
 	message_lane::{MessageLane, SourceHeaderIdOf, TargetHeaderIdOf},
-	message_race_delivery::run as run_message_delivery_race,
-	message_race_receiving::run as run_message_receiving_race,
+	message_race_delivery::{delivery_race_name, run as run_message_delivery_race},
+	message_race_loop::RaceLoopParams,
+	message_race_receiving::{receiving_race_name, run as run_message_receiving_race},
+	message_race_serializer::RaceSerializer,
 	metrics::MessageLaneLoopMetrics,
 };
 
@@ -55,6 +81,30 @@ pub struct Params {
 	pub reconnect_delay: Duration,
 	/// Message delivery race parameters.
 	pub delivery_params: MessageDeliveryParams,
+	/// Runtime control of the loop. While the loop is paused, it doesn't submit any
+	/// transactions.
+	pub control: RelayControl,
+	/// Number of new source and target blocks that the loop must observe after connecting,
+	/// before it starts submitting transactions.
+	pub warm_up_blocks: u32,
+	/// Time that the loop waits after connecting, before it starts submitting transactions.
+	pub first_submit_delay: Duration,
+	/// If set, races escalate their logs after given number of consecutive iterations without
+	/// anything to deliver.
+	pub inactivity_log_threshold: Option<u32>,
+	/// If true, races attach correlation ids of relayed messages to their logs and spans.
+	pub correlate_messages: bool,
+	/// If set, the receiving race tracks how long delivered messages stay unconfirmed.
+	pub confirmation_deadline: Option<ConfirmationDeadline>,
+	/// If set, the delivery race is only engaged when enough messages are pending.
+	pub pending_messages_gate: Option<PendingMessagesGate>,
+	/// If true, the delivery and confirmation races don't talk to nodes concurrently. They take
+	/// turns instead, which lowers the nodes load at the cost of the relay latency.
+	pub serialize_races: bool,
+	/// If set, overrides level of `bridge` logs of the loop.
+	pub log_level: Option<log::LevelFilter>,
+	/// If set, the category of the most recent error of the lane is tracked.
+	pub error_state: Option<LaneErrorState>,
 }
 
 /// Message delivery race parameters.
@@ -75,6 +125,497 @@ pub struct MessageDeliveryParams {
 	pub max_messages_weight_in_single_batch: Weight,
 	/// Maximal cumulative size of relayed messages in single delivery transaction.
 	pub max_messages_size_in_single_batch: u32,
+	/// Budget of delivery transaction fees.
+	pub fee_budget: FeeBudget,
+	/// If set, the delivery race doesn't track messages that are more than given number of
+	/// nonces ahead of the latest confirmed nonce. Such messages are tracked later, when
+	/// earlier messages are confirmed.
+	pub max_nonces_ahead: Option<MessageNonce>,
+	/// Log when the delivery race defers tracking of messages because of `max_nonces_ahead`.
+	pub log_backpressure: bool,
+	/// Maximal weight of the single delivery transaction at the target chain. Message with larger
+	/// dispatch weight can never be delivered.
+	pub max_extrinsic_weight_at_target: Weight,
+	/// What to do when such oversized message is the next message to deliver.
+	pub oversized_weight_policy: OversizedWeightPolicy,
+	/// If set, messages with larger declared dispatch weight are never delivered. Messages of
+	/// the lane are delivered in order, so such message can't be skipped: the relay delivers
+	/// messages that precede it and then halts.
+	pub max_message_dispatch_weight: Option<Weight>,
+	/// If set, the delivery race defers delivery while the companion lane is stalled.
+	pub companion_lane: Option<CompanionLane>,
+	/// Share of the messages throughput budget, that is used by the lane.
+	pub throughput_budget: LaneThroughputBudget,
+	/// If set, the delivery race holds back messages until the batch is large enough.
+	pub min_batch: Option<MinBatch>,
+	/// Number of source blocks that must be built on top of the block, where the message has
+	/// been generated, before the delivery race considers it for delivery.
+	pub message_grace_period_blocks: u32,
+	/// If set, the delivery race throttles deliveries while the unrewarded relayers set at the
+	/// target chain is filled by at least given percent, so that delivery confirmations may
+	/// drain it. Deliveries that bring confirmations to the target chain are not throttled.
+	pub confirmations_priority_threshold: Option<u8>,
+	/// If set, the relay is halted when the next message can't be delivered after given number
+	/// of retries.
+	pub retry_budget: Option<RetryBudget>,
+	/// If set, the number of messages in the delivery transaction is tuned by results of
+	/// previous deliveries.
+	pub batch_tuner: Option<BatchAutoTuner>,
+	/// If set, the dispatch weight of the delivery transaction is limited by the weight
+	/// headroom of the target chain block.
+	pub weight_headroom: Option<WeightHeadroom>,
+}
+
+/// Other lane, served by the same relay process, which progress gates the message delivery.
+#[derive(Debug, Clone)]
+pub struct CompanionLane {
+	/// Name of the companion lane, used in logs.
+	pub name: String,
+	/// Summary of the companion lane, updated by its own lane loop.
+	pub summary: LaneSummary,
+	/// The companion lane is considered stalled if it has unconfirmed messages and its latest
+	/// confirmed nonce hasn't advanced within this window.
+	pub staleness_window: Duration,
+}
+
+/// Minimal number of messages in the delivery transaction.
+///
+/// Every delivery transaction has fixed costs, so delivering messages one by one may be
+/// unprofitable. The delivery race holds back messages until there are at least `size` of them,
+/// or until `timeout` elapses since they have been held back for the first time.
+#[derive(Debug, Clone)]
+pub struct MinBatch {
+	/// Minimal number of messages in the delivery transaction.
+	pub size: MessageNonce,
+	/// Maximal time to hold back messages.
+	pub timeout: Duration,
+	state: Arc<Mutex<MinBatchState>>,
+}
+
+#[derive(Debug, Default)]
+struct MinBatchState {
+	/// Moment when messages have been held back for the first time and the latest held back
+	/// nonce.
+	held_back: Option<(Instant, MessageNonce)>,
+	/// If true, messages are not held back anymore.
+	flushing: bool,
+}
+
+impl MinBatch {
+	/// Create new minimal batch parameters.
+	pub fn new(size: MessageNonce, timeout: Duration) -> Self {
+		MinBatch { size, timeout, state: Default::default() }
+	}
+
+	/// Stop holding back messages. Returns the latest held back nonce, if there are messages
+	/// that are still held back.
+	pub fn flush(&self) -> Option<MessageNonce> {
+		let mut state = self.state.lock();
+		state.flushing = true;
+		state.held_back.map(|(_, nonce)| nonce)
+	}
+
+	/// Returns true if messages with given nonces may be delivered right now. Otherwise they're
+	/// held back.
+	pub(crate) fn allows(&self, nonces: &RangeInclusive<MessageNonce>) -> bool {
+		self.allows_at(Instant::now(), nonces)
+	}
+
+	fn allows_at(&self, now: Instant, nonces: &RangeInclusive<MessageNonce>) -> bool {
+		let mut state = self.state.lock();
+		let held_back_since = state.held_back.map(|(since, _)| since).unwrap_or(now);
+		let batch_size = nonces.end().saturating_add(1).saturating_sub(*nonces.start());
+		if state.flushing ||
+			batch_size >= self.size ||
+			now.duration_since(held_back_since) >= self.timeout
+		{
+			state.held_back = None;
+			return true
+		}
+
+		state.held_back = Some((held_back_since, *nonces.end()));
+		false
+	}
+}
+
+/// Minimal number of pending messages that engages the delivery race.
+///
+/// Unlike the `MinBatch`, the gate is checked by the lane loop, before the delivery race is
+/// engaged. While the gate is closed, the delivery race doesn't see new source states, so it
+/// doesn't read the message nonces, doesn't prepare proofs and doesn't submit anything. The
+/// gate opens once there are at least `min_pending` undelivered messages, or once some messages
+/// have been pending for `timeout`. It is closed again when all messages are delivered.
+#[derive(Debug, Clone)]
+pub struct PendingMessagesGate {
+	/// Minimal number of pending messages that opens the gate.
+	pub min_pending: MessageNonce,
+	/// If set, the gate is also opened when messages have been pending for this time.
+	pub timeout: Option<Duration>,
+	state: Arc<Mutex<PendingMessagesGateState>>,
+}
+
+#[derive(Debug, Default)]
+struct PendingMessagesGateState {
+	/// True if the delivery race is engaged.
+	is_open: bool,
+	/// Moment when pending messages have been seen for the first time.
+	pending_since: Option<Instant>,
+}
+
+impl PendingMessagesGate {
+	/// Create new closed gate.
+	pub fn new(min_pending: MessageNonce, timeout: Option<Duration>) -> Self {
+		PendingMessagesGate { min_pending, timeout, state: Default::default() }
+	}
+
+	/// Returns true if the delivery race is engaged.
+	pub fn is_open(&self) -> bool {
+		self.state.lock().is_open
+	}
+
+	/// Update the gate with the number of pending messages. Returns true if the gate is open.
+	pub(crate) fn note_pending(&self, pending: MessageNonce) -> bool {
+		self.note_pending_at(Instant::now(), pending)
+	}
+
+	fn note_pending_at(&self, now: Instant, pending: MessageNonce) -> bool {
+		let mut state = self.state.lock();
+		if pending == 0 {
+			*state = PendingMessagesGateState::default();
+			return false
+		}
+
+		let pending_since = *state.pending_since.get_or_insert(now);
+		let is_timed_out = self.timeout.map(|timeout| now.duration_since(pending_since) >= timeout);
+		if pending >= self.min_pending || is_timed_out.unwrap_or(false) {
+			state.is_open = true;
+		}
+		state.is_open
+	}
+}
+
+/// Maximal time between the message delivery and the delivery confirmation.
+///
+/// Confirmations may be deferred (e.g. by the confirmation lag) to save on transaction costs.
+/// The receiving race remembers when every delivered nonce has been seen for the first time.
+/// Once the oldest unconfirmed delivery is approaching `max_latency`, confirmations must not
+/// be deferred anymore.
+#[derive(Debug, Clone)]
+pub struct ConfirmationDeadline {
+	/// Maximal acceptable confirmation latency.
+	pub max_latency: Duration,
+	state: Arc<Mutex<ConfirmationDeadlineState>>,
+}
+
+#[derive(Debug, Default)]
+struct ConfirmationDeadlineState {
+	/// Latest confirmed nonce.
+	confirmed: MessageNonce,
+	/// Delivered, but not yet confirmed nonces and moments when they have been seen delivered.
+	delivered: VecDeque<(MessageNonce, Instant)>,
+}
+
+impl ConfirmationDeadline {
+	/// Create new confirmation deadline.
+	pub fn new(max_latency: Duration) -> Self {
+		ConfirmationDeadline { max_latency, state: Default::default() }
+	}
+
+	/// Remember that messages up to given nonce have been delivered to the target chain.
+	pub(crate) fn note_delivered(&self, nonce: MessageNonce) {
+		self.note_delivered_at(Instant::now(), nonce)
+	}
+
+	fn note_delivered_at(&self, now: Instant, nonce: MessageNonce) {
+		let mut state = self.state.lock();
+		let latest_known =
+			state.delivered.back().map(|(nonce, _)| *nonce).unwrap_or(state.confirmed);
+		if nonce > latest_known {
+			state.delivered.push_back((nonce, now));
+		}
+	}
+
+	/// Remember that messages up to given nonce have been confirmed at the source chain.
+	pub(crate) fn note_confirmed(&self, nonce: MessageNonce) {
+		let mut state = self.state.lock();
+		state.confirmed = std::cmp::max(state.confirmed, nonce);
+		while state
+			.delivered
+			.front()
+			.map(|(delivered, _)| *delivered <= nonce)
+			.unwrap_or(false)
+		{
+			state.delivered.pop_front();
+		}
+	}
+
+	/// Returns true if the oldest unconfirmed delivery is approaching the deadline, so pending
+	/// confirmations must be delivered right now.
+	pub fn is_approaching(&self) -> bool {
+		self.is_approaching_at(Instant::now())
+	}
+
+	fn is_approaching_at(&self, now: Instant) -> bool {
+		// leave a quarter of the latency for the confirmation transaction itself
+		let flush_after = self.max_latency.saturating_sub(self.max_latency / 4);
+		self.state
+			.lock()
+			.delivered
+			.front()
+			.map(|(_, delivered_at)| now.saturating_duration_since(*delivered_at) >= flush_after)
+			.unwrap_or(false)
+	}
+}
+
+/// Budget of failed attempts to deliver the same message.
+///
+/// Transient failures (e.g. when the transaction pool is full) are likely to go away, while
+/// deterministic failures (e.g. when the delivery transaction is rejected by the runtime) will
+/// happen again. So they have separate budgets. Messages of the lane are delivered in order,
+/// so the message can't be skipped and the relay is halted once the budget of the next
+/// message is exhausted.
+#[derive(Debug, Clone)]
+pub struct RetryBudget {
+	/// Maximal number of retries after transient failures.
+	pub max_transient_retries: u32,
+	/// Maximal number of retries after deterministic failures.
+	pub max_deterministic_retries: u32,
+	state: Arc<Mutex<RetryBudgetState>>,
+}
+
+#[derive(Debug, Default)]
+struct RetryBudgetState {
+	/// Nonce of the message, which delivery has failed.
+	nonce: MessageNonce,
+	/// Number of transient failures.
+	transient_failures: u32,
+	/// Number of deterministic failures.
+	deterministic_failures: u32,
+}
+
+impl RetryBudget {
+	/// Create new retry budget.
+	pub fn new(max_transient_retries: u32, max_deterministic_retries: u32) -> Self {
+		RetryBudget { max_transient_retries, max_deterministic_retries, state: Default::default() }
+	}
+
+	/// Remember failed attempt to deliver the batch, starting with message with given nonce.
+	/// Returns error if the message has exhausted its budget.
+	pub(crate) fn note_failure(
+		&self,
+		nonce: MessageNonce,
+		kind: FailureKind,
+	) -> Result<(), String> {
+		let mut state = self.state.lock();
+		if state.nonce != nonce {
+			*state = RetryBudgetState { nonce, ..Default::default() };
+		}
+
+		let (failures, max_retries) = match kind {
+			FailureKind::Transient => (&mut state.transient_failures, self.max_transient_retries),
+			FailureKind::Deterministic =>
+				(&mut state.deterministic_failures, self.max_deterministic_retries),
+		};
+		*failures = failures.saturating_add(1);
+		if *failures > max_retries {
+			return Err(format!(
+				"Delivery of message {} has failed {} times with {:?} error",
+				nonce, failures, kind,
+			))
+		}
+		Ok(())
+	}
+}
+
+/// Number of messages in the delivery transaction, tuned by results of previous deliveries.
+///
+/// The delivery transaction that fits configured limits may still exhaust resources of the
+/// target block. The tuner halves the batch size after such failure and increases it by one
+/// message after every successful delivery. The size always stays within
+/// `min_size..=max_size`.
+#[derive(Debug, Clone)]
+pub struct BatchAutoTuner {
+	/// Minimal number of messages in the delivery transaction.
+	pub min_size: MessageNonce,
+	/// Maximal number of messages in the delivery transaction.
+	pub max_size: MessageNonce,
+	size: Arc<Mutex<MessageNonce>>,
+}
+
+impl BatchAutoTuner {
+	/// Create new tuner. The batch size starts at `max_size`.
+	pub fn new(min_size: MessageNonce, max_size: MessageNonce) -> Self {
+		let min_size = std::cmp::max(min_size, 1);
+		let max_size = std::cmp::max(max_size, min_size);
+		BatchAutoTuner { min_size, max_size, size: Arc::new(Mutex::new(max_size)) }
+	}
+
+	/// Returns current maximal number of messages in the delivery transaction.
+	pub fn size(&self) -> MessageNonce {
+		*self.size.lock()
+	}
+
+	/// Grow the batch after successful delivery. Returns the previous and the new sizes if the
+	/// size has changed.
+	pub(crate) fn note_delivered(&self) -> Option<(MessageNonce, MessageNonce)> {
+		self.update(|size| size.saturating_add(1))
+	}
+
+	/// Shrink the batch after the delivery transaction has exhausted block resources. Returns
+	/// the previous and the new sizes if the size has changed.
+	pub(crate) fn note_resources_exhausted(&self) -> Option<(MessageNonce, MessageNonce)> {
+		self.update(|size| size / 2)
+	}
+
+	fn update(
+		&self,
+		f: impl FnOnce(MessageNonce) -> MessageNonce,
+	) -> Option<(MessageNonce, MessageNonce)> {
+		let mut size = self.size.lock();
+		let prev_size = *size;
+		*size = f(prev_size).clamp(self.min_size, self.max_size);
+		if *size != prev_size {
+			Some((prev_size, *size))
+		} else {
+			None
+		}
+	}
+}
+
+/// Weight, that is still available to transactions in the target chain block.
+///
+/// If target chain blocks are nearly full of other transactions, the heavy delivery transaction
+/// may not fit into the block. The headroom is updated by the target client and the delivery
+/// race fits the batch into it, or defers delivery if even one message doesn't fit.
+#[derive(Debug, Clone, Default)]
+pub struct WeightHeadroom {
+	headroom: Arc<Mutex<Option<Weight>>>,
+}
+
+impl WeightHeadroom {
+	/// Returns the latest known headroom or `None` if it is not known yet.
+	pub fn get(&self) -> Option<Weight> {
+		*self.headroom.lock()
+	}
+
+	/// Update the headroom.
+	pub fn set(&self, headroom: Weight) {
+		*self.headroom.lock() = Some(headroom);
+	}
+}
+
+/// Category of the most recent error of the lane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaneErrorCategory {
+	/// There have been no errors since the latest successful transaction.
+	Ok,
+	/// Connection to the source or target node has been lost.
+	Connection,
+	/// The relay transaction has been rejected, lost or hasn't had any effect.
+	Rejected,
+	/// The lane is stalled by the message that can't be delivered.
+	Stalled,
+}
+
+impl LaneErrorCategory {
+	/// All error categories.
+	pub const ALL: [LaneErrorCategory; 4] = [
+		LaneErrorCategory::Ok,
+		LaneErrorCategory::Connection,
+		LaneErrorCategory::Rejected,
+		LaneErrorCategory::Stalled,
+	];
+
+	/// Returns metric label of the category.
+	pub fn label(&self) -> &'static str {
+		match *self {
+			LaneErrorCategory::Ok => "ok",
+			LaneErrorCategory::Connection => "connection",
+			LaneErrorCategory::Rejected => "rejected",
+			LaneErrorCategory::Stalled => "stalled",
+		}
+	}
+}
+
+/// Category of the most recent error of the lane, exposed as the state gauge.
+///
+/// The gauge of the current category is `1` and gauges of other categories are `0`. The
+/// category is reset to `ok` after the successful relay transaction of the lane. Categories
+/// that describe the ongoing condition (connection and stalled lane) are also
+/// reset once the condition is over.
+#[derive(Clone)]
+pub struct LaneErrorState {
+	category: Arc<Mutex<LaneErrorCategory>>,
+	state: GaugeVec<U64>,
+}
+
+impl LaneErrorState {
+	/// Create error state of the lane with metrics, prefixed with given prefix.
+	pub fn new(prefix: Option<&str>) -> Result<Self, PrometheusError> {
+		let error_state = LaneErrorState {
+			category: Arc::new(Mutex::new(LaneErrorCategory::Ok)),
+			state: GaugeVec::new(
+				Opts::new(
+					metric_name(prefix, "lane_error_state"),
+					"Category of the most recent error of the lane",
+				),
+				&["category"],
+			)?,
+		};
+		error_state.update(&mut error_state.category.lock(), LaneErrorCategory::Ok);
+		Ok(error_state)
+	}
+
+	/// Returns category of the most recent error.
+	pub fn category(&self) -> LaneErrorCategory {
+		*self.category.lock()
+	}
+
+	/// Note error of given category or success, if category is `Ok`.
+	pub(crate) fn note(&self, category: LaneErrorCategory) {
+		self.update(&mut self.category.lock(), category);
+	}
+
+	/// Reset the category to `ok` if the most recent error has given category.
+	pub(crate) fn clear(&self, category: LaneErrorCategory) {
+		let mut current = self.category.lock();
+		if *current == category {
+			self.update(&mut current, LaneErrorCategory::Ok);
+		}
+	}
+
+	fn update(&self, current: &mut LaneErrorCategory, category: LaneErrorCategory) {
+		*current = category;
+		for known in LaneErrorCategory::ALL {
+			self.state.with_label_values(&[known.label()]).set((known == category) as u64);
+		}
+	}
+}
+
+impl Debug for LaneErrorState {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_tuple("LaneErrorState").field(&self.category()).finish()
+	}
+}
+
+impl Metric for LaneErrorState {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		register(self.state.clone(), registry)?;
+		Ok(())
+	}
+}
+
+/// What the delivery race does with message that can never be delivered, because its dispatch
+/// weight exceeds the maximal delivery transaction weight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversizedWeightPolicy {
+	/// Halt the relay with an error.
+	Halt,
+	/// Keep trying to deliver the message, logging an error every time.
+	WarnOnly,
+	/// Hold back the lane and check the message again with given interval. Delivery is resumed
+	/// once the message may be delivered or is delivered by someone else.
+	Reprobe(Duration),
 }
 
 /// Message details.
@@ -273,6 +814,34 @@ pub struct ClientsState<P: MessageLane> {
 	pub target: Option<TargetClientState<P>>,
 }
 
+/// Correlation ids of messages, attached to logs and spans of relay transactions.
+///
+/// The id of the message is `<source>-><target>/<lane>/<nonce>`, so the same message may be
+/// found in logs of both races, different relayers and other tools without extra context.
+#[derive(Debug, Clone)]
+pub struct MessageCorrelation {
+	prefix: String,
+}
+
+impl MessageCorrelation {
+	/// Create correlation of messages at given lane.
+	pub fn new<P: MessageLane>(lane: &LaneId) -> Self {
+		MessageCorrelation {
+			prefix: format!("{}->{}/{}", P::SOURCE_NAME, P::TARGET_NAME, hex::encode(lane)),
+		}
+	}
+
+	/// Returns correlation id of the message with given nonce.
+	pub fn id(&self, nonce: MessageNonce) -> String {
+		format!("{}/{}", self.prefix, nonce)
+	}
+
+	/// Returns comma-separated correlation ids of messages with given nonces.
+	pub fn ids(&self, nonces: RangeInclusive<MessageNonce>) -> String {
+		nonces.map(|nonce| self.id(nonce)).collect::<Vec<_>>().join(",")
+	}
+}
+
 /// Return prefix that will be used by default to expose Prometheus metrics of the finality proofs
 /// sync loop.
 pub fn metrics_prefix<P: MessageLane>(lane: &LaneId) -> String {
@@ -290,18 +859,41 @@ pub async fn run<P: MessageLane>(
 	let exit_signal = exit_signal.shared();
 	relay_utils::relay_loop(source_client, target_client)
 		.reconnect_delay(params.reconnect_delay)
+		.log_level(params.log_level)
 		.with_metrics(metrics_params)
 		.loop_metric(MessageLaneLoopMetrics::new(Some(&metrics_prefix::<P>(&params.lane)))?)?
 		.expose()
 		.await?
 		.run(metrics_prefix::<P>(&params.lane), move |source_client, target_client, metrics| {
+			// every reconnect starts the new root span
+			let mut span = Span::root("message_lane_loop");
+			span.set_attribute("source", P::SOURCE_NAME);
+			span.set_attribute("target", P::TARGET_NAME);
+			span.set_attribute("lane", hex::encode(params.lane));
+			let span_context = span.context();
+			let error_state = params.error_state.clone();
+			let control = params.control.clone();
+			let lane_name = metrics_prefix::<P>(&params.lane);
+
 			run_until_connection_lost(
 				params.clone(),
 				source_client,
 				target_client,
 				metrics,
 				exit_signal.clone(),
+				span_context,
 			)
+			.map(move |result| {
+				if let Err(ref failed_client) = result {
+					let error = format!("connection to {failed_client:?} node has been lost");
+					span.set_error(&error);
+					control.set_last_error(lane_name, error);
+					if let Some(error_state) = error_state.as_ref() {
+						error_state.note(LaneErrorCategory::Connection);
+					}
+				}
+				result
+			})
 		})
 		.await
 }
@@ -314,6 +906,7 @@ async fn run_until_connection_lost<P: MessageLane, SC: SourceClient<P>, TC: Targ
 	target_client: TC,
 	metrics_msg: Option<MessageLaneLoopMetrics>,
 	exit_signal: impl Future<Output = ()>,
+	span_context: SpanContext,
 ) -> Result<(), FailedClient> {
 	let mut source_retry_backoff = retry_backoff();
 	let mut source_client_is_online = false;
@@ -329,17 +922,41 @@ async fn run_until_connection_lost<P: MessageLane, SC: SourceClient<P>, TC: Targ
 	let target_go_offline_future = futures::future::Fuse::terminated();
 	let target_tick_stream = interval(params.target_tick).fuse();
 
+	let correlation = params.correlate_messages.then(|| MessageCorrelation::new::<P>(&params.lane));
 	let (
 		(delivery_source_state_sender, delivery_source_state_receiver),
 		(delivery_target_state_sender, delivery_target_state_receiver),
 	) = (unbounded(), unbounded());
+	let control = params.control.clone();
+	let min_batch = params.delivery_params.min_batch.clone();
+	let pending_messages_gate = params.pending_messages_gate.clone();
+	let error_state = params.error_state.clone();
+	let mut latest_source_state = None;
+	let mut latest_target_state = None;
+	let mut pending_messages_check_required = false;
+	let pending_messages_check = futures::future::Fuse::terminated();
+	let lane_summary = params.control.summary().lane(metrics_prefix::<P>(&params.lane));
+	let race_params = RaceLoopParams {
+		control: params.control.clone(),
+		warm_up_blocks: params.warm_up_blocks,
+		first_submit_delay: params.first_submit_delay,
+		inactivity_log_threshold: params.inactivity_log_threshold,
+		retry_budget: None,
+		batch_tuner: None,
+		correlation,
+		race_serializer: params.serialize_races.then(RaceSerializer::default),
+		error_state: error_state.clone(),
+		span_context,
+	};
 	let delivery_race_loop = run_message_delivery_race(
 		source_client.clone(),
 		delivery_source_state_receiver,
 		target_client.clone(),
 		delivery_target_state_receiver,
 		metrics_msg.clone(),
+		lane_summary.clone(),
 		params.delivery_params,
+		race_params.clone(),
 	)
 	.fuse();
 
@@ -353,10 +970,16 @@ async fn run_until_connection_lost<P: MessageLane, SC: SourceClient<P>, TC: Targ
 		target_client.clone(),
 		receiving_target_state_receiver,
 		metrics_msg.clone(),
+		lane_summary,
+		params.confirmation_deadline,
+		race_params,
 	)
 	.fuse();
 
 	let exit_signal = exit_signal.fuse();
+	let mut is_exiting = false;
+	let mut flush_up_to = None;
+	let flush_deadline = futures::future::Fuse::terminated();
 
 	futures::pin_mut!(
 		source_state,
@@ -367,7 +990,9 @@ async fn run_until_connection_lost<P: MessageLane, SC: SourceClient<P>, TC: Targ
 		target_tick_stream,
 		delivery_race_loop,
 		receiving_race_loop,
-		exit_signal
+		exit_signal,
+		flush_deadline,
+		pending_messages_check
 	);
 
 	loop {
@@ -385,8 +1010,22 @@ async fn run_until_connection_lost<P: MessageLane, SC: SourceClient<P>, TC: Targ
 							P::SOURCE_NAME,
 							new_source_state,
 						);
-						let _ = delivery_source_state_sender.unbounded_send(new_source_state.clone());
+						// while the gate is closed, the delivery race doesn't see new source states
+						let is_gate_open = pending_messages_gate
+							.as_ref()
+							.map(|gate| gate.is_open())
+							.unwrap_or(true);
+						if is_gate_open {
+							let _ = delivery_source_state_sender.unbounded_send(new_source_state.clone());
+						}
 						let _ = receiving_source_state_sender.unbounded_send(new_source_state.clone());
+						pending_messages_check_required = pending_messages_gate.is_some();
+						latest_source_state = Some(new_source_state.clone());
+						if latest_target_state.is_some() {
+							if let Some(error_state) = error_state.as_ref() {
+								error_state.clear(LaneErrorCategory::Connection);
+							}
+						}
 
 						if let Some(metrics_msg) = metrics_msg.as_ref() {
 							metrics_msg.update_source_state::<P>(new_source_state);
@@ -418,6 +1057,12 @@ async fn run_until_connection_lost<P: MessageLane, SC: SourceClient<P>, TC: Targ
 						);
 						let _ = delivery_target_state_sender.unbounded_send(new_target_state.clone());
 						let _ = receiving_target_state_sender.unbounded_send(new_target_state.clone());
+						latest_target_state = Some(new_target_state.clone());
+						if latest_source_state.is_some() {
+							if let Some(error_state) = error_state.as_ref() {
+								error_state.clear(LaneErrorCategory::Connection);
+							}
+						}
 
 						if let Some(metrics_msg) = metrics_msg.as_ref() {
 							metrics_msg.update_target_state::<P>(new_target_state);
@@ -449,10 +1094,122 @@ async fn run_until_connection_lost<P: MessageLane, SC: SourceClient<P>, TC: Targ
 			},
 
 			() = exit_signal => {
+				is_exiting = true;
+
+				// deliver messages that are held back by the delivery race before exiting
+				let held_back = min_batch
+					.as_ref()
+					.and_then(|min_batch| Some((min_batch.timeout, min_batch.flush()?)));
+				if let Some((timeout, held_back_nonce)) = held_back {
+					log::info!(
+						target: "bridge",
+						"Delivering held back {} -> {} messages up to {} before exit",
+						P::SOURCE_NAME,
+						P::TARGET_NAME,
+						held_back_nonce,
+					);
+					flush_up_to = Some(held_back_nonce);
+					flush_deadline.set(async_std::task::sleep(timeout).fuse());
+				}
+			},
+			pending_messages = pending_messages_check => {
+				match (pending_messages, pending_messages_gate.as_ref()) {
+					(Ok(pending_messages), Some(gate)) => {
+						let was_open = gate.is_open();
+						let is_open = gate.note_pending(pending_messages);
+						if !was_open && is_open {
+							log::info!(
+								target: "bridge",
+								"Engaging {} -> {} delivery race: {} messages are pending",
+								P::SOURCE_NAME,
+								P::TARGET_NAME,
+								pending_messages,
+							);
+							if let Some(ref source_state) = latest_source_state {
+								let _ = delivery_source_state_sender.unbounded_send(source_state.clone());
+							}
+						} else if was_open && !is_open {
+							log::info!(
+								target: "bridge",
+								"All {} -> {} messages are delivered. Delivery race is idle until {} \
+								messages are pending",
+								P::SOURCE_NAME,
+								P::TARGET_NAME,
+								gate.min_pending,
+							);
+						}
+						if !is_open {
+							// the idle race doesn't see source states, but both nodes are
+							// connected
+							control.set_race_ready(delivery_race_name::<P>(), true);
+						}
+					},
+					(Err(e), _) => log::debug!(
+						target: "bridge",
+						"Failed to read number of pending {} -> {} messages: {}",
+						P::SOURCE_NAME,
+						P::TARGET_NAME,
+						e,
+					),
+					(Ok(_), None) => (),
+				}
+			},
+			() = flush_deadline => {
+				log::warn!(
+					target: "bridge",
+					"Failed to deliver held back {} -> {} messages before exit",
+					P::SOURCE_NAME,
+					P::TARGET_NAME,
+				);
 				return Ok(());
 			}
 		}
 
+		if control.halt_reason().is_some() {
+			return Ok(())
+		}
+		if is_exiting {
+			let is_flushed = flush_up_to
+				.map(|flush_up_to| {
+					control.last_submitted_nonce(&delivery_race_name::<P>()).unwrap_or(0) >=
+						flush_up_to
+				})
+				.unwrap_or(true);
+			// transactions, which are being submitted, are not abandoned halfway
+			let is_submitting = control.is_submission_in_progress(&delivery_race_name::<P>()) ||
+				control.is_submission_in_progress(&receiving_race_name::<P>());
+			if is_flushed && !is_submitting {
+				return Ok(())
+			}
+		}
+
+		if let (true, true, Some(source_state), Some(target_state)) = (
+			pending_messages_check_required,
+			pending_messages_check.is_terminated(),
+			latest_source_state.as_ref(),
+			latest_target_state.as_ref(),
+		) {
+			pending_messages_check_required = false;
+			let source_client = source_client.clone();
+			let target_client = target_client.clone();
+			let at_source = source_state.best_finalized_self.clone();
+			let at_target = target_state.best_self.clone();
+			pending_messages_check.set(
+				async move {
+					let (_, generated) = source_client
+						.latest_generated_nonce(at_source)
+						.await
+						.map_err(|e| format!("{e:?}"))?;
+					let (_, received) = target_client
+						.latest_received_nonce(at_target)
+						.await
+						.map_err(|e| format!("{e:?}"))?;
+					Ok::<_, String>(generated.saturating_sub(received))
+				}
+				.fuse(),
+			);
+		}
+
 		if source_client_is_online && source_state_required {
 			log::debug!(target: "bridge", "Asking {} node about its state", P::SOURCE_NAME);
 			source_state.set(source_client.state().fuse());
@@ -570,6 +1327,7 @@ pub(crate) mod tests {
 		is_source_reconnected: bool,
 		source_state: SourceClientState<TestMessageLane>,
 		source_latest_generated_nonce: MessageNonce,
+		source_unfinalized_generated_nonce: Option<(TestSourceHeaderId, MessageNonce)>,
 		source_latest_confirmed_received_nonce: MessageNonce,
 		source_tracked_transaction_status: TrackedTransactionStatus<TestTargetHeaderId>,
 		submitted_messages_receiving_proofs: Vec<TestMessagesReceivingProof>,
@@ -595,6 +1353,7 @@ pub(crate) mod tests {
 				is_source_reconnected: false,
 				source_state: Default::default(),
 				source_latest_generated_nonce: 0,
+				source_unfinalized_generated_nonce: None,
 				source_latest_confirmed_received_nonce: 0,
 				source_tracked_transaction_status: TrackedTransactionStatus::Finalized(HeaderId(
 					0,
@@ -657,8 +1416,25 @@ pub(crate) mod tests {
 			self.submitted_messages_receiving_proofs.push(proof);
 			self.source_latest_confirmed_received_nonce = proof;
 		}
+
+		/// Simulate target chain reorg.
+		///
+		/// The best target header is replaced with the fork header of the same height and all
+		/// messages, delivered after `received_nonce`, are reverted. The headers of the fork
+		/// have hashes that never match hashes of headers at the original chain.
+		fn reorg_target(&mut self, received_nonce: MessageNonce) {
+			let best_self = self.target_state.best_self;
+			self.target_state.best_self = HeaderId(best_self.0, best_self.1 + TARGET_FORK_OFFSET);
+			self.target_state.best_finalized_self = self.target_state.best_self;
+			self.target_latest_received_nonce = received_nonce;
+			self.target_latest_confirmed_received_nonce =
+				std::cmp::min(self.target_latest_confirmed_received_nonce, received_nonce);
+		}
 	}
 
+	/// Difference between hashes of target headers at the fork and at the original chain.
+	const TARGET_FORK_OFFSET: TestTargetHeaderHash = 1_000_000;
+
 	#[derive(Clone)]
 	pub struct TestSourceClient {
 		data: Arc<Mutex<TestClientData>>,
@@ -716,7 +1492,11 @@ pub(crate) mod tests {
 				return Err(TestError)
 			}
 			(self.post_tick)(&mut data);
-			Ok((id, data.source_latest_generated_nonce))
+			let latest_generated_nonce = match data.source_unfinalized_generated_nonce {
+				Some((unfinalized_id, nonce)) if unfinalized_id == id => nonce,
+				_ => data.source_latest_generated_nonce,
+			};
+			Ok((id, latest_generated_nonce))
 		}
 
 		async fn latest_confirmed_received_nonce(
@@ -944,6 +1724,27 @@ pub(crate) mod tests {
 		target_post_tick: Arc<dyn Fn(&mut TestClientData) + Send + Sync>,
 		exit_signal: impl Future<Output = ()> + 'static + Send,
 	) -> TestClientData {
+		run_loop_test_with_control(
+			data,
+			source_tick,
+			source_post_tick,
+			target_tick,
+			target_post_tick,
+			Default::default(),
+			exit_signal,
+		)
+		.1
+	}
+
+	fn run_loop_test_with_control(
+		data: Arc<Mutex<TestClientData>>,
+		source_tick: Arc<dyn Fn(&mut TestClientData) + Send + Sync>,
+		source_post_tick: Arc<dyn Fn(&mut TestClientData) + Send + Sync>,
+		target_tick: Arc<dyn Fn(&mut TestClientData) + Send + Sync>,
+		target_post_tick: Arc<dyn Fn(&mut TestClientData) + Send + Sync>,
+		control: RelayControl,
+		exit_signal: impl Future<Output = ()> + 'static + Send,
+	) -> (Result<(), relay_utils::Error>, TestClientData) {
 		async_std::task::block_on(async {
 			let source_client = TestSourceClient {
 				data: data.clone(),
@@ -955,7 +1756,7 @@ pub(crate) mod tests {
 				tick: target_tick,
 				post_tick: target_post_tick,
 			};
-			let _ = run(
+			let loop_result = run(
 				Params {
 					lane: LaneId([0, 0, 0, 0]),
 					source_tick: Duration::from_millis(100),
@@ -967,7 +1768,31 @@ pub(crate) mod tests {
 						max_messages_in_single_batch: 4,
 						max_messages_weight_in_single_batch: Weight::from_parts(4, 0),
 						max_messages_size_in_single_batch: 4,
+						fee_budget: FeeBudget::default(),
+						max_nonces_ahead: None,
+						log_backpressure: false,
+						max_extrinsic_weight_at_target: Weight::MAX,
+						oversized_weight_policy: OversizedWeightPolicy::WarnOnly,
+						max_message_dispatch_weight: None,
+						companion_lane: None,
+						throughput_budget: Default::default(),
+						min_batch: None,
+						message_grace_period_blocks: 0,
+						confirmations_priority_threshold: None,
+						retry_budget: None,
+						batch_tuner: None,
+						weight_headroom: None,
 					},
+					control,
+					warm_up_blocks: 0,
+					first_submit_delay: Duration::ZERO,
+					inactivity_log_threshold: None,
+					correlate_messages: false,
+					confirmation_deadline: None,
+					pending_messages_gate: None,
+					serialize_races: false,
+					log_level: None,
+					error_state: None,
 				},
 				source_client,
 				target_client,
@@ -976,7 +1801,7 @@ pub(crate) mod tests {
 			)
 			.await;
 			let result = data.lock().clone();
-			result
+			(loop_result, result)
 		})
 	}
 
@@ -1114,6 +1939,198 @@ pub(crate) mod tests {
 		assert_eq!(result.submitted_messages_receiving_proofs.len(), 2);
 	}
 
+	fn run_target_reorg_test(
+		target_post_tick: Arc<dyn Fn(&mut TestClientData) + Send + Sync>,
+	) -> TestClientData {
+		let (exit_sender, exit_receiver) = unbounded();
+		run_loop_test(
+			Arc::new(Mutex::new(TestClientData {
+				source_state: ClientState {
+					best_self: HeaderId(0, 0),
+					best_finalized_self: HeaderId(0, 0),
+					best_finalized_peer_at_best_self: Some(HeaderId(0, 0)),
+					actual_best_finalized_peer_at_best_self: Some(HeaderId(0, 0)),
+				},
+				source_latest_generated_nonce: 1,
+				target_state: ClientState {
+					best_self: HeaderId(0, 0),
+					best_finalized_self: HeaderId(0, 0),
+					best_finalized_peer_at_best_self: Some(HeaderId(0, 0)),
+					actual_best_finalized_peer_at_best_self: Some(HeaderId(0, 0)),
+				},
+				target_latest_received_nonce: 0,
+				..Default::default()
+			})),
+			Arc::new(move |data: &mut TestClientData| {
+				// source must never confirm messages that are not delivered to the target
+				assert!(
+					data.source_latest_confirmed_received_nonce <=
+						data.target_latest_received_nonce
+				);
+				// blocks are produced on every tick
+				data.source_state.best_self =
+					HeaderId(data.source_state.best_self.0 + 1, data.source_state.best_self.1 + 1);
+				data.source_state.best_finalized_self = data.source_state.best_self;
+				// syncing target headers -> source chain
+				if let Some(last_requirement) = data.target_to_source_header_requirements.last() {
+					if *last_requirement !=
+						data.source_state.best_finalized_peer_at_best_self.unwrap()
+					{
+						data.source_state.best_finalized_peer_at_best_self =
+							Some(*last_requirement);
+					}
+				}
+			}),
+			Arc::new(|_| {}),
+			Arc::new(move |data: &mut TestClientData| {
+				// blocks are produced on every tick
+				data.target_state.best_self =
+					HeaderId(data.target_state.best_self.0 + 1, data.target_state.best_self.1 + 1);
+				data.target_state.best_finalized_self = data.target_state.best_self;
+				// syncing source headers -> target chain
+				if let Some(last_requirement) = data.source_to_target_header_requirements.last() {
+					if *last_requirement !=
+						data.target_state.best_finalized_peer_at_best_self.unwrap()
+					{
+						data.target_state.best_finalized_peer_at_best_self =
+							Some(*last_requirement);
+					}
+				}
+				// if source has received all messages receiving confirmations => stop
+				if data.source_latest_confirmed_received_nonce == 1 {
+					exit_sender.unbounded_send(()).unwrap();
+				}
+			}),
+			target_post_tick,
+			exit_receiver.into_future().map(|(_, _)| ()),
+		)
+	}
+
+	#[test]
+	fn message_lane_loop_redelivers_messages_after_reorg_before_delivery_inclusion() {
+		// the block with first delivery transaction is retracted before transaction is
+		// finalized, so the transaction is lost
+		let result = run_target_reorg_test(Arc::new(|data: &mut TestClientData| {
+			if data.submitted_messages_proofs.len() == 1 {
+				if data.target_latest_received_nonce == 1 {
+					data.reorg_target(0);
+				}
+				data.target_tracked_transaction_status = TrackedTransactionStatus::Lost;
+			} else {
+				data.target_tracked_transaction_status =
+					TrackedTransactionStatus::Finalized(data.target_state.best_finalized_self);
+			}
+		}));
+
+		let delivered_nonces = result
+			.submitted_messages_proofs
+			.iter()
+			.map(|proof| proof.0.clone())
+			.collect::<Vec<_>>();
+		assert_eq!(delivered_nonces, vec![1..=1, 1..=1]);
+		assert_eq!(result.submitted_messages_receiving_proofs, vec![1]);
+	}
+
+	#[test]
+	fn message_lane_loop_redelivers_messages_after_reorg_before_confirmation() {
+		// the first delivery transaction is reported as finalized, but the block with this
+		// transaction is retracted before delivery is confirmed at the source
+		let result = run_target_reorg_test(Arc::new(|data: &mut TestClientData| {
+			if data.submitted_messages_proofs.len() == 1 && data.target_latest_received_nonce == 1 {
+				data.target_tracked_transaction_status =
+					TrackedTransactionStatus::Finalized(data.target_state.best_finalized_self);
+				data.reorg_target(0);
+			}
+		}));
+
+		let delivered_nonces = result
+			.submitted_messages_proofs
+			.iter()
+			.map(|proof| proof.0.clone())
+			.collect::<Vec<_>>();
+		assert_eq!(delivered_nonces, vec![1..=1, 1..=1]);
+		assert_eq!(result.submitted_messages_receiving_proofs, vec![1]);
+	}
+
+	#[test]
+	fn message_lane_loop_never_delivers_messages_from_non_finalized_source_blocks() {
+		let (exit_sender, exit_receiver) = unbounded();
+		let result = run_loop_test(
+			Arc::new(Mutex::new(TestClientData {
+				source_state: ClientState {
+					best_self: HeaderId(1, 1),
+					best_finalized_self: HeaderId(0, 0),
+					best_finalized_peer_at_best_self: Some(HeaderId(0, 0)),
+					actual_best_finalized_peer_at_best_self: Some(HeaderId(0, 0)),
+				},
+				// message 1 is finalized and message 2 is only sent at the best source block
+				source_latest_generated_nonce: 1,
+				source_unfinalized_generated_nonce: Some((HeaderId(1, 1), 2)),
+				target_state: ClientState {
+					best_self: HeaderId(0, 0),
+					best_finalized_self: HeaderId(0, 0),
+					best_finalized_peer_at_best_self: Some(HeaderId(0, 0)),
+					actual_best_finalized_peer_at_best_self: Some(HeaderId(0, 0)),
+				},
+				target_latest_received_nonce: 0,
+				..Default::default()
+			})),
+			Arc::new(|data: &mut TestClientData| {
+				// the best source block with message 2 is retracted after the first delivery
+				if data.source_unfinalized_generated_nonce.is_some() {
+					if !data.submitted_messages_proofs.is_empty() {
+						data.source_unfinalized_generated_nonce = None;
+						data.source_state.best_self = HeaderId(1, 1_000_001);
+					}
+					return
+				}
+				// blocks are produced on every tick
+				data.source_state.best_self =
+					HeaderId(data.source_state.best_self.0 + 1, data.source_state.best_self.1 + 1);
+				data.source_state.best_finalized_self = data.source_state.best_self;
+				// syncing target headers -> source chain
+				if let Some(last_requirement) = data.target_to_source_header_requirements.last() {
+					if *last_requirement !=
+						data.source_state.best_finalized_peer_at_best_self.unwrap()
+					{
+						data.source_state.best_finalized_peer_at_best_self =
+							Some(*last_requirement);
+					}
+				}
+			}),
+			Arc::new(|_| {}),
+			Arc::new(move |data: &mut TestClientData| {
+				// blocks are produced on every tick
+				data.target_state.best_self =
+					HeaderId(data.target_state.best_self.0 + 1, data.target_state.best_self.1 + 1);
+				data.target_state.best_finalized_self = data.target_state.best_self;
+				// syncing source headers -> target chain
+				if let Some(last_requirement) = data.source_to_target_header_requirements.last() {
+					if *last_requirement !=
+						data.target_state.best_finalized_peer_at_best_self.unwrap()
+					{
+						data.target_state.best_finalized_peer_at_best_self =
+							Some(*last_requirement);
+					}
+				}
+				// if source has received all messages receiving confirmations => stop
+				if data.source_latest_confirmed_received_nonce == 1 {
+					exit_sender.unbounded_send(()).unwrap();
+				}
+			}),
+			Arc::new(|_| {}),
+			exit_receiver.into_future().map(|(_, _)| ()),
+		);
+
+		let delivered_nonces = result
+			.submitted_messages_proofs
+			.iter()
+			.map(|proof| proof.0.clone())
+			.collect::<Vec<_>>();
+		assert_eq!(delivered_nonces, vec![1..=1]);
+		assert_eq!(result.target_latest_received_nonce, 1);
+	}
+
 	#[test]
 	fn message_lane_loop_works() {
 		let (exit_sender, exit_receiver) = unbounded();
@@ -1274,4 +2291,197 @@ pub(crate) mod tests {
 		assert!(!result.target_to_source_header_requirements.is_empty());
 		assert!(!result.source_to_target_header_requirements.is_empty());
 	}
+
+	#[test]
+	fn message_lane_loop_exits_cleanly_when_exit_timer_fires() {
+		// the loop keeps delivering messages until the timer fires, which happens once the
+		// first batch is delivered
+		let (exit_sender, exit_receiver) = unbounded();
+		let control = RelayControl::default();
+		let (loop_result, result) = run_loop_test_with_control(
+			Arc::new(Mutex::new(TestClientData {
+				source_state: ClientState {
+					best_self: HeaderId(10, 10),
+					best_finalized_self: HeaderId(10, 10),
+					best_finalized_peer_at_best_self: Some(HeaderId(0, 0)),
+					actual_best_finalized_peer_at_best_self: Some(HeaderId(0, 0)),
+				},
+				source_latest_generated_nonce: 100,
+				target_state: ClientState {
+					best_self: HeaderId(10, 10),
+					best_finalized_self: HeaderId(10, 10),
+					best_finalized_peer_at_best_self: Some(HeaderId(10, 10)),
+					actual_best_finalized_peer_at_best_self: Some(HeaderId(10, 10)),
+				},
+				target_latest_received_nonce: 0,
+				..Default::default()
+			})),
+			Arc::new(|_| {}),
+			Arc::new(|_| {}),
+			Arc::new(|_| {}),
+			Arc::new(move |data: &mut TestClientData| {
+				if data.target_latest_received_nonce >= 4 {
+					let _ = exit_sender.unbounded_send(());
+				}
+			}),
+			control.clone(),
+			exit_receiver.into_future().map(|(_, _)| ()),
+		);
+
+		// the loop has exited because the timer has fired, not because it has been halted or
+		// has lost connection to any client
+		assert!(loop_result.is_ok());
+		assert_eq!(control.halt_reason(), None);
+		assert!(!result.is_source_reconnected);
+		assert!(!result.is_target_reconnected);
+		assert!(result.target_latest_received_nonce >= 4);
+		assert!(result.target_latest_received_nonce < 100);
+	}
+
+	#[test]
+	fn message_correlation_ids_are_derived_from_lane_and_nonce() {
+		let correlation = MessageCorrelation::new::<TestMessageLane>(&LaneId([0, 0, 0, 1]));
+		assert_eq!(correlation.id(5), "TestSource->TestTarget/00000001/5");
+		assert_eq!(
+			correlation.ids(5..=6),
+			"TestSource->TestTarget/00000001/5,TestSource->TestTarget/00000001/6",
+		);
+	}
+
+	#[test]
+	fn min_batch_holds_back_messages_until_batch_is_large_enough_or_timeout_elapses() {
+		let min_batch = MinBatch::new(3, Duration::from_secs(60));
+		let start = Instant::now();
+
+		assert!(!min_batch.allows_at(start, &(1..=1)));
+		assert!(!min_batch.allows_at(start + Duration::from_secs(30), &(1..=2)));
+		assert!(min_batch.allows_at(start + Duration::from_secs(40), &(1..=3)));
+
+		// the timeout is counted from the moment when messages have been held back first time
+		assert!(!min_batch.allows_at(start + Duration::from_secs(50), &(4..=4)));
+		assert!(!min_batch.allows_at(start + Duration::from_secs(100), &(4..=5)));
+		assert!(min_batch.allows_at(start + Duration::from_secs(110), &(4..=5)));
+	}
+
+	#[test]
+	fn deterministic_failures_exhaust_retry_budget_quickly() {
+		let retry_budget = RetryBudget::new(5, 1);
+		assert!(retry_budget.note_failure(10, FailureKind::Deterministic).is_ok());
+		assert!(retry_budget.note_failure(10, FailureKind::Deterministic).is_err());
+	}
+
+	#[test]
+	fn transient_failures_exhaust_retry_budget_slowly() {
+		let retry_budget = RetryBudget::new(5, 1);
+		for _ in 0..5 {
+			assert!(retry_budget.note_failure(10, FailureKind::Transient).is_ok());
+		}
+		// transient failures don't count against the deterministic budget
+		assert!(retry_budget.note_failure(10, FailureKind::Deterministic).is_ok());
+		assert!(retry_budget.note_failure(10, FailureKind::Transient).is_err());
+	}
+
+	#[test]
+	fn retry_budget_is_reset_when_next_message_fails() {
+		let retry_budget = RetryBudget::new(1, 1);
+		assert!(retry_budget.note_failure(10, FailureKind::Transient).is_ok());
+		// message 10 has been delivered and now message 11 fails
+		assert!(retry_budget.note_failure(11, FailureKind::Transient).is_ok());
+		assert!(retry_budget.note_failure(11, FailureKind::Transient).is_err());
+	}
+
+	#[test]
+	fn pending_messages_gate_opens_when_enough_messages_are_pending_or_timeout_elapses() {
+		let gate = PendingMessagesGate::new(3, Some(Duration::from_secs(60)));
+		let start = Instant::now();
+		assert!(!gate.note_pending_at(start, 0));
+		assert!(!gate.note_pending_at(start, 1));
+		assert!(!gate.note_pending_at(start + Duration::from_secs(30), 2));
+		assert!(gate.note_pending_at(start + Duration::from_secs(40), 3));
+		// the gate stays open until all messages are delivered
+		assert!(gate.note_pending_at(start + Duration::from_secs(50), 1));
+		assert!(!gate.note_pending_at(start + Duration::from_secs(60), 0));
+
+		// long-waiting small set of messages opens the gate
+		assert!(!gate.note_pending_at(start + Duration::from_secs(70), 1));
+		assert!(!gate.note_pending_at(start + Duration::from_secs(120), 1));
+		assert!(gate.note_pending_at(start + Duration::from_secs(130), 1));
+
+		// without timeout, small set of messages never opens the gate
+		let gate = PendingMessagesGate::new(3, None);
+		assert!(!gate.note_pending_at(start, 1));
+		assert!(!gate.note_pending_at(start + Duration::from_secs(3600), 2));
+	}
+
+	#[test]
+	fn batch_is_shrunk_after_resources_exhaustion() {
+		let batch_tuner = BatchAutoTuner::new(2, 16);
+		assert_eq!(batch_tuner.size(), 16);
+		assert_eq!(batch_tuner.note_delivered(), None);
+
+		assert_eq!(batch_tuner.note_resources_exhausted(), Some((16, 8)));
+		assert_eq!(batch_tuner.note_resources_exhausted(), Some((8, 4)));
+		assert_eq!(batch_tuner.note_resources_exhausted(), Some((4, 2)));
+		assert_eq!(batch_tuner.note_resources_exhausted(), None);
+		assert_eq!(batch_tuner.size(), 2);
+
+		assert_eq!(batch_tuner.note_delivered(), Some((2, 3)));
+		assert_eq!(batch_tuner.size(), 3);
+	}
+
+	#[test]
+	fn confirmation_deadline_is_approaching_for_oldest_unconfirmed_delivery() {
+		let deadline = ConfirmationDeadline::new(Duration::from_secs(60));
+		let start = Instant::now();
+
+		// nothing is delivered => nothing to flush
+		assert!(!deadline.is_approaching_at(start));
+
+		// message 1 is delivered at `start` and message 2 is delivered 30s later
+		deadline.note_delivered_at(start, 1);
+		deadline.note_delivered_at(start + Duration::from_secs(30), 2);
+		// the same nonce, seen later, doesn't reset the delivery moment
+		deadline.note_delivered_at(start + Duration::from_secs(40), 2);
+		assert!(!deadline.is_approaching_at(start + Duration::from_secs(44)));
+		assert!(deadline.is_approaching_at(start + Duration::from_secs(45)));
+
+		// when message 1 is confirmed, the deadline is computed for message 2
+		deadline.note_confirmed(1);
+		assert!(!deadline.is_approaching_at(start + Duration::from_secs(74)));
+		assert!(deadline.is_approaching_at(start + Duration::from_secs(75)));
+
+		// when everything is confirmed, nothing to flush
+		deadline.note_confirmed(2);
+		assert!(!deadline.is_approaching_at(start + Duration::from_secs(1000)));
+		// and confirmed nonces are never tracked again
+		deadline.note_delivered_at(start + Duration::from_secs(1000), 2);
+		assert!(!deadline.is_approaching_at(start + Duration::from_secs(2000)));
+	}
+
+	#[test]
+	fn lane_error_state_exposes_most_recent_error_category() {
+		let error_state = LaneErrorState::new(None).unwrap();
+		let gauge = |category: LaneErrorCategory| {
+			error_state.state.with_label_values(&[category.label()]).get()
+		};
+		assert_eq!(gauge(LaneErrorCategory::Ok), 1);
+		assert_eq!(gauge(LaneErrorCategory::Connection), 0);
+
+		// only the most recent category is set
+		error_state.note(LaneErrorCategory::Connection);
+		error_state.note(LaneErrorCategory::Rejected);
+		assert_eq!(error_state.category(), LaneErrorCategory::Rejected);
+		assert_eq!(
+			LaneErrorCategory::ALL.iter().map(|c| gauge(*c)).collect::<Vec<_>>(),
+			vec![0, 0, 0, 1, 0],
+		);
+
+		// the category is only cleared when the matching condition is over
+		error_state.clear(LaneErrorCategory::Connection);
+		assert_eq!(error_state.category(), LaneErrorCategory::Rejected);
+		error_state.note(LaneErrorCategory::Ok);
+		assert_eq!(error_state.category(), LaneErrorCategory::Ok);
+		assert_eq!(gauge(LaneErrorCategory::Ok), 1);
+		assert_eq!(gauge(LaneErrorCategory::Rejected), 0);
+	}
 }
@@ -13,26 +13,39 @@
 
 //! Message delivery race delivers proof-of-messages from "lane.source" to "lane.target".
 
-use std::{collections::VecDeque, marker::PhantomData, ops::RangeInclusive};
+use std::{
+	collections::VecDeque,
+	marker::PhantomData,
+	ops::RangeInclusive,
+	sync::atomic::{AtomicBool, Ordering},
+	time::Duration,
+};
 
 use async_trait::async_trait;
 use futures::stream::FusedStream;
+use num_traits::Saturating;
 
 use bp_messages::{MessageNonce, UnrewardedRelayersState, Weight};
-use relay_utils::{FailedClient, TrackedTransactionStatus, TransactionTracker};
+use relay_utils::{
+	control::RelayControl, fee_budget::FeeBudget, run_summary::LaneSummary,
+	throughput_budget::LaneThroughputBudget, FailedClient, TrackedTransactionStatus,
+	TransactionTracker,
+};
 
 use crate::{
 	message_lane::{MessageLane, SourceHeaderIdOf, TargetHeaderIdOf},
 	message_lane_loop::{
-		MessageDeliveryParams, MessageDetailsMap, MessageProofParameters, NoncesSubmitArtifacts,
+		BatchAutoTuner, CompanionLane, LaneErrorState, MessageDeliveryParams, MessageDetailsMap,
+		MessageProofParameters, MinBatch, NoncesSubmitArtifacts, OversizedWeightPolicy,
 		SourceClient as MessageLaneSourceClient, SourceClientState,
-		TargetClient as MessageLaneTargetClient, TargetClientState,
+		TargetClient as MessageLaneTargetClient, TargetClientState, WeightHeadroom,
 	},
 	message_race_limits::{MessageRaceLimits, RelayMessagesBatchReference},
 	message_race_loop::{
-		MessageRace, NoncesRange, RaceState, RaceStrategy, SourceClient, SourceClientNonces,
-		TargetClient, TargetClientNonces,
+		MessageRace, NoncesRange, RaceLoopParams, RaceState, RaceStrategy, SourceClient,
+		SourceClientNonces, TargetClient, TargetClientNonces,
 	},
+	message_race_stall::StallTracker,
 	message_race_strategy::BasicStrategy,
 	metrics::MessageLaneLoopMetrics,
 };
@@ -44,18 +57,24 @@ pub async fn run<P: MessageLane>(
 	target_client: impl MessageLaneTargetClient<P>,
 	target_state_updates: impl FusedStream<Item = TargetClientState<P>>,
 	metrics_msg: Option<MessageLaneLoopMetrics>,
+	lane_summary: LaneSummary,
 	params: MessageDeliveryParams,
+	race_params: RaceLoopParams,
 ) -> Result<(), FailedClient> {
 	crate::message_race_loop::run(
 		MessageDeliveryRaceSource {
 			client: source_client.clone(),
 			metrics_msg: metrics_msg.clone(),
+			lane_summary: lane_summary.clone(),
+			max_nonces_ahead: params.max_nonces_ahead,
+			log_backpressure: params.log_backpressure,
 			_phantom: Default::default(),
 		},
 		source_state_updates,
 		MessageDeliveryRaceTarget {
 			client: target_client.clone(),
 			metrics_msg: metrics_msg.clone(),
+			lane_summary,
 			_phantom: Default::default(),
 		},
 		target_state_updates,
@@ -68,11 +87,35 @@ pub async fn run<P: MessageLane>(
 			max_messages_in_single_batch: params.max_messages_in_single_batch,
 			max_messages_weight_in_single_batch: params.max_messages_weight_in_single_batch,
 			max_messages_size_in_single_batch: params.max_messages_size_in_single_batch,
+			fee_budget: params.fee_budget,
+			max_extrinsic_weight_at_target: params.max_extrinsic_weight_at_target,
+			oversized_weight_policy: params.oversized_weight_policy,
+			max_message_dispatch_weight: params.max_message_dispatch_weight,
+			error_state: race_params.error_state.clone(),
+			control: race_params.control.clone(),
+			stall_tracker: match params.oversized_weight_policy {
+				OversizedWeightPolicy::Reprobe(reprobe_interval) =>
+					Some(StallTracker::new(reprobe_interval)),
+				OversizedWeightPolicy::Halt | OversizedWeightPolicy::WarnOnly => None,
+			},
+			companion_lane: params.companion_lane,
+			throughput_budget: params.throughput_budget,
+			min_batch: params.min_batch,
+			batch_tuner: params.batch_tuner.clone(),
+			weight_headroom: params.weight_headroom,
+			message_grace_period_blocks: params.message_grace_period_blocks,
+			confirmations_priority_threshold: params.confirmations_priority_threshold,
+			is_prioritizing_confirmations: AtomicBool::new(false),
 			latest_confirmed_nonces_at_source: VecDeque::new(),
 			target_nonces: None,
 			strategy: BasicStrategy::new(),
 			metrics_msg,
 		},
+		RaceLoopParams {
+			retry_budget: params.retry_budget,
+			batch_tuner: params.batch_tuner,
+			..race_params
+		},
 	)
 	.await
 }
@@ -140,6 +183,11 @@ pub async fn relay_messages_range<P: MessageLane>(
 	}
 }
 
+/// Name of the message delivery race, used to report its progress.
+pub(crate) fn delivery_race_name<P: MessageLane>() -> String {
+	crate::message_race_loop::race_name::<MessageDeliveryRace<P>>()
+}
+
 /// Message delivery race.
 struct MessageDeliveryRace<P>(std::marker::PhantomData<P>);
 
@@ -163,6 +211,9 @@ impl<P: MessageLane> MessageRace for MessageDeliveryRace<P> {
 struct MessageDeliveryRaceSource<P: MessageLane, C> {
 	client: C,
 	metrics_msg: Option<MessageLaneLoopMetrics>,
+	lane_summary: LaneSummary,
+	max_nonces_ahead: Option<MessageNonce>,
+	log_backpressure: bool,
 	_phantom: PhantomData<P>,
 }
 
@@ -190,16 +241,35 @@ where
 			metrics_msg.update_source_latest_generated_nonce(latest_generated_nonce);
 			metrics_msg.update_source_latest_confirmed_nonce(latest_confirmed_nonce);
 		}
+		self.lane_summary.update_source_latest_generated_nonce(latest_generated_nonce);
+		self.lane_summary.update_source_latest_confirmed_nonce(latest_confirmed_nonce);
+
+		let new_nonces_range = new_nonces_to_track(
+			prev_latest_nonce,
+			latest_generated_nonce,
+			latest_confirmed_nonce,
+			self.max_nonces_ahead,
+		);
+		let latest_tracked_nonce =
+			new_nonces_range.as_ref().map(|range| *range.end()).unwrap_or(prev_latest_nonce);
+		if self.log_backpressure && latest_tracked_nonce < latest_generated_nonce {
+			log::info!(
+				target: "bridge",
+				"Deferring {} -> {} messages {:?}: they are too far ahead of the latest confirmed \
+				nonce {}",
+				P::SOURCE_NAME,
+				P::TARGET_NAME,
+				std::cmp::max(prev_latest_nonce, latest_tracked_nonce) + 1..=latest_generated_nonce,
+				latest_confirmed_nonce,
+			);
+		}
 
-		let new_nonces = if latest_generated_nonce > prev_latest_nonce {
-			self.client
-				.generated_message_details(
-					at_block.clone(),
-					prev_latest_nonce + 1..=latest_generated_nonce,
-				)
-				.await?
-		} else {
-			MessageDetailsMap::new()
+		let new_nonces = match new_nonces_range {
+			Some(new_nonces_range) =>
+				self.client
+					.generated_message_details(at_block.clone(), new_nonces_range)
+					.await?,
+			None => MessageDetailsMap::new(),
 		};
 
 		Ok((
@@ -219,10 +289,36 @@ where
 	}
 }
 
+/// Returns range of new nonces that the delivery race needs to start tracking.
+///
+/// Nonces above `latest_confirmed_nonce + max_nonces_ahead` are not skipped. They are returned
+/// by later calls, once the confirmed nonce is increased.
+fn new_nonces_to_track(
+	prev_latest_nonce: MessageNonce,
+	latest_generated_nonce: MessageNonce,
+	latest_confirmed_nonce: MessageNonce,
+	max_nonces_ahead: Option<MessageNonce>,
+) -> Option<RangeInclusive<MessageNonce>> {
+	let latest_tracked_nonce = match max_nonces_ahead {
+		Some(max_nonces_ahead) => std::cmp::min(
+			latest_generated_nonce,
+			latest_confirmed_nonce.saturating_add(max_nonces_ahead),
+		),
+		None => latest_generated_nonce,
+	};
+
+	if latest_tracked_nonce > prev_latest_nonce {
+		Some(prev_latest_nonce + 1..=latest_tracked_nonce)
+	} else {
+		None
+	}
+}
+
 /// Message delivery race target, which is a target of the lane.
 struct MessageDeliveryRaceTarget<P: MessageLane, C> {
 	client: C,
 	metrics_msg: Option<MessageLaneLoopMetrics>,
+	lane_summary: LaneSummary,
 	_phantom: PhantomData<P>,
 }
 
@@ -261,6 +357,8 @@ where
 				metrics_msg.update_target_latest_received_nonce(latest_received_nonce);
 				metrics_msg.update_target_latest_confirmed_nonce(latest_confirmed_nonce);
 			}
+			self.lane_summary.update_target_latest_received_nonce(latest_received_nonce);
+			self.lane_summary.update_target_latest_confirmed_nonce(latest_confirmed_nonce);
 		}
 
 		Ok((
@@ -315,6 +413,38 @@ struct MessageDeliveryStrategy<P: MessageLane, SC, TC> {
 	max_messages_weight_in_single_batch: Weight,
 	/// Maximal messages size in the single delivery transaction.
 	max_messages_size_in_single_batch: u32,
+	/// Budget of delivery transaction fees.
+	fee_budget: FeeBudget,
+	/// Maximal weight of the delivery transaction at the target chain.
+	max_extrinsic_weight_at_target: Weight,
+	/// What to do with messages that are heavier than `max_extrinsic_weight_at_target`.
+	oversized_weight_policy: OversizedWeightPolicy,
+	/// Maximal acceptable declared dispatch weight of the single message.
+	max_message_dispatch_weight: Option<Weight>,
+	/// If set, stalled lane is reported there.
+	error_state: Option<LaneErrorState>,
+	/// Relay control, used to halt the relay.
+	control: RelayControl,
+	/// If set, messages that can't be delivered stall the lane instead of halting the relay.
+	stall_tracker: Option<StallTracker>,
+	/// Lane that must not be stalled for the delivery to proceed.
+	companion_lane: Option<CompanionLane>,
+	/// Share of the messages throughput budget, that is used by this lane.
+	throughput_budget: LaneThroughputBudget,
+	/// If set, delivery of small batches is deferred until more messages are generated.
+	min_batch: Option<MinBatch>,
+	/// If set, the number of messages in the delivery transaction is limited by the tuner.
+	batch_tuner: Option<BatchAutoTuner>,
+	/// If set, the dispatch weight of the batch is limited by the target block headroom.
+	weight_headroom: Option<WeightHeadroom>,
+	/// Number of source blocks, that must be built after the message is generated, before it
+	/// is considered for delivery.
+	message_grace_period_blocks: u32,
+	/// If set, deliveries are throttled while the unrewarded relayers set at the target chain
+	/// is filled by at least given percent.
+	confirmations_priority_threshold: Option<u8>,
+	/// True if deliveries are throttled because of `confirmations_priority_threshold`.
+	is_prioritizing_confirmations: AtomicBool,
 	/// Latest confirmed nonces at the source client + the header id where we have first met this
 	/// nonce.
 	latest_confirmed_nonces_at_source: VecDeque<(SourceHeaderIdOf<P>, MessageNonce)>,
@@ -346,6 +476,20 @@ impl<P: MessageLane, SC, TC> std::fmt::Debug for MessageDeliveryStrategy<P, SC,
 			.field("max_messages_in_single_batch", &self.max_messages_in_single_batch)
 			.field("max_messages_weight_in_single_batch", &self.max_messages_weight_in_single_batch)
 			.field("max_messages_size_in_single_batch", &self.max_messages_size_in_single_batch)
+			.field("fee_budget", &self.fee_budget)
+			.field("max_extrinsic_weight_at_target", &self.max_extrinsic_weight_at_target)
+			.field("oversized_weight_policy", &self.oversized_weight_policy)
+			.field("max_message_dispatch_weight", &self.max_message_dispatch_weight)
+			.field("error_state", &self.error_state)
+			.field("stall_tracker", &self.stall_tracker)
+			.field("companion_lane", &self.companion_lane)
+			.field("throughput_budget", &self.throughput_budget)
+			.field("min_batch", &self.min_batch)
+			.field("batch_tuner", &self.batch_tuner)
+			.field("weight_headroom", &self.weight_headroom)
+			.field("message_grace_period_blocks", &self.message_grace_period_blocks)
+			.field("confirmations_priority_threshold", &self.confirmations_priority_threshold)
+			.field("is_prioritizing_confirmations", &self.is_prioritizing_confirmations)
 			.field("latest_confirmed_nonces_at_source", &self.latest_confirmed_nonces_at_source)
 			.field("target_nonces", &self.target_nonces)
 			.field("strategy", &self.strategy)
@@ -440,6 +584,48 @@ where
 			}
 		}
 
+		// when the unrewarded relayers set is close to saturation, we let the confirmations race
+		// drain it. Otherwise we may end up with the full set and rewards that can't be claimed
+		// until confirmations are delivered. Delivery transaction that brings confirmations to
+		// the target chain drains the set too, so it isn't throttled
+		if let Some(confirmations_priority_threshold) = self.confirmations_priority_threshold {
+			let saturation = unrewarded_relayers_saturation(
+				&target_nonces.nonces_data.unrewarded_relayers,
+				self.max_unrewarded_relayer_entries_at_target,
+				self.max_unconfirmed_nonces_at_target,
+			);
+			let is_prioritizing_confirmations = saturation >= confirmations_priority_threshold;
+			if self
+				.is_prioritizing_confirmations
+				.swap(is_prioritizing_confirmations, Ordering::SeqCst) !=
+				is_prioritizing_confirmations
+			{
+				if is_prioritizing_confirmations {
+					log::info!(
+						target: "bridge",
+						"Unrewarded relayers set at {} is {}% full. Throttling {} -> {} \
+						deliveries until delivery confirmations drain it",
+						P::TARGET_NAME,
+						saturation,
+						P::SOURCE_NAME,
+						P::TARGET_NAME,
+					);
+				} else {
+					log::info!(
+						target: "bridge",
+						"Unrewarded relayers set at {} is {}% full. Resuming {} -> {} deliveries",
+						P::TARGET_NAME,
+						saturation,
+						P::SOURCE_NAME,
+						P::TARGET_NAME,
+					);
+				}
+			}
+			if is_prioritizing_confirmations && !outbound_state_proof_required {
+				return None
+			}
+		}
+
 		// If we're here, then the confirmations race did its job && sending side now knows that
 		// messages have been delivered. Now let's select nonces that we want to deliver.
 		//
@@ -463,14 +649,28 @@ where
 			.and_then(|diff| self.max_unconfirmed_nonces_at_target.checked_sub(diff))
 			.unwrap_or_default();
 		let max_nonces = std::cmp::min(max_nonces, self.max_messages_in_single_batch);
+		let max_nonces = match self.batch_tuner {
+			Some(ref batch_tuner) => std::cmp::min(max_nonces, batch_tuner.size()),
+			None => max_nonces,
+		};
 		let max_messages_weight_in_single_batch = self.max_messages_weight_in_single_batch;
 		let max_messages_size_in_single_batch = self.max_messages_size_in_single_batch;
 		let lane_source_client = self.lane_source_client.clone();
 		let lane_target_client = self.lane_target_client.clone();
 
 		// select nonces from nonces, available for delivery
-		let selected_nonces = match self.strategy.available_source_queue_indices(race_state) {
+		let best_finalized_source_header_id_at_source =
+			race_state.best_finalized_source_header_id_at_source();
+		let available_source_queue_indices =
+			self.strategy.available_source_queue_indices(race_state).and_then(|indices| {
+				self.exclude_messages_in_grace_period(
+					indices,
+					best_finalized_source_header_id_at_source,
+				)
+			});
+		let selected_nonces = match available_source_queue_indices {
 			Some(available_source_queue_indices) => {
+				let max_nonces = std::cmp::min(max_nonces, self.throughput_budget.available());
 				let source_queue = self.strategy.source_queue();
 				let reference = RelayMessagesBatchReference {
 					max_messages_in_this_batch: max_nonces,
@@ -482,6 +682,13 @@ where
 					nonces_queue: source_queue.clone(),
 					nonces_queue_range: available_source_queue_indices,
 					metrics: self.metrics_msg.clone(),
+					max_extrinsic_weight_at_target: self.max_extrinsic_weight_at_target,
+					oversized_weight_policy: self.oversized_weight_policy,
+					max_message_dispatch_weight: self.max_message_dispatch_weight,
+					control: self.control.clone(),
+					stall_tracker: self.stall_tracker.clone(),
+					weight_headroom: self.weight_headroom.as_ref().and_then(WeightHeadroom::get),
+					error_state: self.error_state.clone(),
 				};
 
 				MessageRaceLimits::decide(reference).await
@@ -509,6 +716,30 @@ where
 		))
 	}
 
+	/// Exclude source queue entries, that are generated less than `message_grace_period_blocks`
+	/// blocks ago, from the given range of entries.
+	fn exclude_messages_in_grace_period(
+		&self,
+		indices: RangeInclusive<usize>,
+		best_finalized_source_header_id_at_source: Option<SourceHeaderIdOf<P>>,
+	) -> Option<RangeInclusive<usize>> {
+		if self.message_grace_period_blocks == 0 {
+			return Some(indices)
+		}
+
+		let best_finalized_source_header_number = best_finalized_source_header_id_at_source?.0;
+		let grace_period: P::SourceHeaderNumber = self.message_grace_period_blocks.into();
+		let source_queue = self.strategy.source_queue();
+		let begin_index = *indices.start();
+		let end_index = indices
+			.take_while(|index| {
+				source_queue[*index].0 .0.saturating_add(grace_period) <=
+					best_finalized_source_header_number
+			})
+			.last()?;
+		Some(begin_index..=end_index)
+	}
+
 	/// Returns latest confirmed message at source chain, given source block.
 	fn latest_confirmed_nonce_at_source(&self, at: &SourceHeaderIdOf<P>) -> Option<MessageNonce> {
 		self.latest_confirmed_nonces_at_source
@@ -533,6 +764,24 @@ where
 	}
 }
 
+/// Returns how full (in percents) is the unrewarded relayers set at the target chain.
+fn unrewarded_relayers_saturation(
+	unrewarded_relayers: &UnrewardedRelayersState,
+	max_unrewarded_relayer_entries_at_target: MessageNonce,
+	max_unconfirmed_nonces_at_target: MessageNonce,
+) -> u8 {
+	let percent = |value: MessageNonce, max: MessageNonce| {
+		value.saturating_mul(100).checked_div(max).unwrap_or(100).min(100) as u8
+	};
+	std::cmp::max(
+		percent(
+			unrewarded_relayers.unrewarded_relayer_entries,
+			max_unrewarded_relayer_entries_at_target,
+		),
+		percent(unrewarded_relayers.total_messages, max_unconfirmed_nonces_at_target),
+	)
+}
+
 #[async_trait]
 impl<P, SC, TC> RaceStrategy<SourceHeaderIdOf<P>, TargetHeaderIdOf<P>, P::MessagesProof>
 	for MessageDeliveryStrategy<P, SC, TC>
@@ -678,7 +927,52 @@ where
 		&self,
 		race_state: RS,
 	) -> Option<(RangeInclusive<MessageNonce>, Self::ProofParameters)> {
-		self.select_race_action(race_state).await
+		let selected = self.select_race_action(race_state).await?;
+		if self.fee_budget.is_exhausted() {
+			log::info!(
+				target: "bridge",
+				"Deferring delivery of {} -> {} messages {:?}: fee budget is exhausted",
+				P::SOURCE_NAME,
+				P::TARGET_NAME,
+				selected.0,
+			);
+			self.fee_budget.note_deferral();
+			return None
+		}
+		if let Some(ref companion_lane) = self.companion_lane {
+			if companion_lane.summary.is_stalled(companion_lane.staleness_window) {
+				log::info!(
+					target: "bridge",
+					"Deferring delivery of {} -> {} messages {:?}: companion lane {} hasn't \
+					confirmed any messages within {}s",
+					P::SOURCE_NAME,
+					P::TARGET_NAME,
+					selected.0,
+					companion_lane.name,
+					companion_lane.staleness_window.as_secs(),
+				);
+				return None
+			}
+		}
+		if let Some(ref min_batch) = self.min_batch {
+			if !min_batch.allows(&selected.0) {
+				log::debug!(
+					target: "bridge",
+					"Deferring delivery of {} -> {} messages {:?}: waiting for at least {} messages \
+					or {}s",
+					P::SOURCE_NAME,
+					P::TARGET_NAME,
+					selected.0,
+					min_batch.size,
+					min_batch.timeout.as_secs(),
+				);
+				return None
+			}
+		}
+
+		self.throughput_budget
+			.spend(selected.0.end().saturating_add(1).saturating_sub(*selected.0.start()));
+		Some(selected)
 	}
 }
 
@@ -710,12 +1004,13 @@ mod tests {
 				TestSourceChainBalance, TestSourceClient, TestSourceHeaderId, TestTargetClient,
 				TestTargetHeaderId,
 			},
-			MessageDetails,
+			LaneErrorCategory, MessageDetails,
 		},
 		message_race_loop::RaceStateImpl,
 	};
 
 	use super::*;
+	use relay_utils::throughput_budget::ThroughputBudget;
 
 	const DEFAULT_DISPATCH_WEIGHT: Weight = Weight::from_parts(1, 0);
 	const DEFAULT_SIZE: u32 = 1;
@@ -769,6 +1064,21 @@ mod tests {
 			max_messages_in_single_batch: 4,
 			max_messages_weight_in_single_batch: Weight::from_parts(4, 0),
 			max_messages_size_in_single_batch: 4,
+			fee_budget: FeeBudget::default(),
+			max_extrinsic_weight_at_target: Weight::from_parts(100, 0),
+			oversized_weight_policy: OversizedWeightPolicy::WarnOnly,
+			max_message_dispatch_weight: None,
+			error_state: None,
+			control: RelayControl::default(),
+			stall_tracker: None,
+			companion_lane: None,
+			throughput_budget: Default::default(),
+			min_batch: None,
+			batch_tuner: None,
+			weight_headroom: None,
+			message_grace_period_blocks: 0,
+			confirmations_priority_threshold: None,
+			is_prioritizing_confirmations: AtomicBool::new(false),
 			latest_confirmed_nonces_at_source: vec![(header_id(1), 19)].into_iter().collect(),
 			lane_source_client: TestSourceClient::default(),
 			lane_target_client: TestTargetClient::default(),
@@ -939,6 +1249,30 @@ mod tests {
 		);
 	}
 
+	#[async_std::test]
+	async fn message_delivery_strategy_fits_batch_into_weight_headroom() {
+		let (state, mut strategy) = prepare_strategy();
+
+		// headroom isn't known yet
+		let weight_headroom = WeightHeadroom::default();
+		strategy.weight_headroom = Some(weight_headroom.clone());
+		assert_eq!(
+			strategy.select_nonces_to_deliver(state.clone()).await,
+			Some(((20..=23), proof_parameters(false, 4)))
+		);
+
+		// only some messages fit into the target block
+		weight_headroom.set(Weight::from_parts(2, 0));
+		assert_eq!(
+			strategy.select_nonces_to_deliver(state.clone()).await,
+			Some(((20..=21), proof_parameters(false, 2)))
+		);
+
+		// delivery is deferred if even one message doesn't fit
+		weight_headroom.set(Weight::from_parts(0, 0));
+		assert_eq!(strategy.select_nonces_to_deliver(state).await, None);
+	}
+
 	#[async_std::test]
 	async fn message_delivery_strategy_accepts_single_message_even_if_its_weight_overflows_maximal_weight(
 	) {
@@ -954,6 +1288,77 @@ mod tests {
 		);
 	}
 
+	#[async_std::test]
+	async fn oversized_message_is_retried_with_warn_only_policy() {
+		let (state, mut strategy) = prepare_strategy();
+
+		// first message can never be delivered, because its weight (200) overflows max
+		// extrinsic weight (100)
+		strategy.strategy.source_queue_mut()[0].1.get_mut(&20).unwrap().dispatch_weight =
+			Weight::from_parts(200, 0);
+		assert_eq!(
+			strategy.select_nonces_to_deliver(state).await,
+			Some(((20..=20), proof_parameters(false, 200)))
+		);
+		assert_eq!(strategy.control.halt_reason(), None);
+	}
+
+	#[async_std::test]
+	async fn oversized_message_halts_relay_with_halt_policy() {
+		let (state, mut strategy) = prepare_strategy();
+
+		strategy.oversized_weight_policy = OversizedWeightPolicy::Halt;
+		strategy.strategy.source_queue_mut()[0].1.get_mut(&20).unwrap().dispatch_weight =
+			Weight::from_parts(200, 0);
+		assert_eq!(strategy.select_nonces_to_deliver(state).await, None);
+		assert!(strategy.control.halt_reason().is_some());
+	}
+
+	#[async_std::test]
+	async fn oversized_message_stalls_lane_with_reprobe_policy() {
+		let (state, mut strategy) = prepare_strategy();
+
+		strategy.oversized_weight_policy = OversizedWeightPolicy::Reprobe(Duration::ZERO);
+		strategy.stall_tracker = Some(StallTracker::new(Duration::ZERO));
+		strategy.strategy.source_queue_mut()[0].1.get_mut(&20).unwrap().dispatch_weight =
+			Weight::from_parts(200, 0);
+		assert_eq!(strategy.select_nonces_to_deliver(state.clone()).await, None);
+		assert_eq!(strategy.control.halt_reason(), None);
+
+		// the message is delivered once it fits into the delivery transaction
+		strategy.strategy.source_queue_mut()[0].1.get_mut(&20).unwrap().dispatch_weight =
+			Weight::from_parts(1, 0);
+		assert!(strategy.select_nonces_to_deliver(state).await.is_some());
+	}
+
+	#[async_std::test]
+	async fn messages_preceding_too_heavy_message_are_delivered() {
+		let (state, mut strategy) = prepare_strategy();
+
+		// third message declares dispatch weight (50) that exceeds the configured limit (10),
+		// even though it fits in the delivery transaction
+		strategy.max_message_dispatch_weight = Some(Weight::from_parts(10, 0));
+		strategy.max_messages_weight_in_single_batch = Weight::from_parts(100, 0);
+		strategy.strategy.source_queue_mut()[0].1.get_mut(&22).unwrap().dispatch_weight =
+			Weight::from_parts(50, 0);
+		assert_eq!(
+			strategy.select_nonces_to_deliver(state).await,
+			Some(((20..=21), proof_parameters(false, 2)))
+		);
+		assert_eq!(strategy.control.halt_reason(), None);
+	}
+
+	#[async_std::test]
+	async fn too_heavy_message_halts_relay_when_it_is_the_next_message() {
+		let (state, mut strategy) = prepare_strategy();
+
+		strategy.max_message_dispatch_weight = Some(Weight::from_parts(10, 0));
+		strategy.strategy.source_queue_mut()[0].1.get_mut(&20).unwrap().dispatch_weight =
+			Weight::from_parts(50, 0);
+		assert_eq!(strategy.select_nonces_to_deliver(state).await, None);
+		assert!(strategy.control.halt_reason().is_some());
+	}
+
 	#[async_std::test]
 	async fn message_delivery_strategy_limits_batch_by_messages_size() {
 		let (state, mut strategy) = prepare_strategy();
@@ -966,6 +1371,155 @@ mod tests {
 		);
 	}
 
+	#[async_std::test]
+	async fn lane_error_state_reports_stalled_lane() {
+		let (state, mut strategy) = prepare_strategy();
+		let error_state = LaneErrorState::new(None).unwrap();
+		strategy.error_state = Some(error_state.clone());
+
+		// the lane is stalled by the message that can't be delivered
+		strategy.max_message_dispatch_weight = Some(Weight::from_parts(10, 0));
+		strategy.stall_tracker = Some(StallTracker::new(Duration::ZERO));
+		strategy.strategy.source_queue_mut()[0].1.get_mut(&20).unwrap().dispatch_weight =
+			Weight::from_parts(50, 0);
+		assert_eq!(strategy.select_nonces_to_deliver(state.clone()).await, None);
+		assert_eq!(error_state.category(), LaneErrorCategory::Stalled);
+
+		// and is resumed once the message may be delivered
+		strategy.max_message_dispatch_weight = None;
+		assert!(strategy.select_nonces_to_deliver(state).await.is_some());
+		assert_eq!(error_state.category(), LaneErrorCategory::Ok);
+	}
+
+	#[test]
+	fn nonces_too_far_ahead_of_confirmed_nonce_are_deferred() {
+		// without backpressure, all generated nonces are tracked
+		assert_eq!(new_nonces_to_track(10, 100, 5, None), Some(11..=100));
+		// with backpressure, only nonces within the window are tracked
+		assert_eq!(new_nonces_to_track(10, 100, 5, Some(20)), Some(11..=25));
+		assert_eq!(new_nonces_to_track(25, 100, 5, Some(20)), None);
+		// once confirmed nonce is increased, following nonces are tracked without gaps
+		assert_eq!(new_nonces_to_track(25, 100, 30, Some(20)), Some(26..=50));
+		assert_eq!(new_nonces_to_track(50, 100, 90, Some(20)), Some(51..=100));
+	}
+
+	#[async_std::test]
+	async fn message_delivery_is_deferred_when_fee_budget_is_exhausted() {
+		let (state, mut strategy) = prepare_strategy();
+
+		strategy.fee_budget = FeeBudget::new(100, None).unwrap();
+		strategy.fee_budget.spend(100);
+		assert_eq!(strategy.select_nonces_to_deliver(state).await, None);
+	}
+
+	#[async_std::test]
+	async fn message_delivery_waits_for_min_batch() {
+		let (state, mut strategy) = prepare_strategy();
+
+		let min_batch = MinBatch::new(5, Duration::from_secs(600));
+		strategy.min_batch = Some(min_batch.clone());
+		assert_eq!(strategy.select_nonces_to_deliver(state.clone()).await, None);
+		// held back messages are delivered on flush
+		assert_eq!(min_batch.flush(), Some(23));
+		assert_eq!(
+			strategy.select_nonces_to_deliver(state).await,
+			Some(((20..=23), proof_parameters(false, 4)))
+		);
+		assert_eq!(min_batch.flush(), None);
+	}
+
+	#[async_std::test]
+	async fn deliveries_are_throttled_near_unrewarded_relayers_saturation() {
+		let (state, mut strategy) = prepare_strategy();
+		strategy.confirmations_priority_threshold = Some(75);
+		let set_unrewarded_relayers = |strategy: &mut TestStrategy, entries| {
+			let nonces_data = &mut strategy.target_nonces.as_mut().unwrap().nonces_data;
+			nonces_data.unrewarded_relayers.unrewarded_relayer_entries = entries;
+			nonces_data.unrewarded_relayers.messages_in_oldest_entry = 1;
+		};
+
+		// the set is half full
+		set_unrewarded_relayers(&mut strategy, 2);
+		assert_eq!(
+			strategy.select_nonces_to_deliver(state.clone()).await,
+			Some(((20..=23), proof_parameters(false, 4)))
+		);
+		assert!(!strategy.is_prioritizing_confirmations.load(Ordering::SeqCst));
+
+		// the set is almost full => confirmations are prioritized
+		set_unrewarded_relayers(&mut strategy, 3);
+		assert_eq!(strategy.select_nonces_to_deliver(state.clone()).await, None);
+		assert!(strategy.is_prioritizing_confirmations.load(Ordering::SeqCst));
+
+		// the delivery, that brings confirmations to the target chain, isn't throttled
+		strategy.target_nonces.as_mut().unwrap().nonces_data.confirmed_nonce = 18;
+		assert_eq!(
+			strategy.select_nonces_to_deliver(state.clone()).await,
+			Some(((20..=23), proof_parameters(true, 4)))
+		);
+
+		// the set is drained
+		strategy.target_nonces.as_mut().unwrap().nonces_data.confirmed_nonce = 19;
+		set_unrewarded_relayers(&mut strategy, 1);
+		assert_eq!(
+			strategy.select_nonces_to_deliver(state).await,
+			Some(((20..=23), proof_parameters(false, 4)))
+		);
+		assert!(!strategy.is_prioritizing_confirmations.load(Ordering::SeqCst));
+	}
+
+	#[async_std::test]
+	async fn message_delivery_waits_for_grace_period() {
+		let (mut state, mut strategy) = prepare_strategy();
+		strategy.message_grace_period_blocks = 2;
+
+		// messages, generated at block 1, need to wait until block 3
+		state.best_finalized_source_header_id_at_source = Some(header_id(2));
+		assert_eq!(strategy.select_nonces_to_deliver(state.clone()).await, None);
+
+		state.best_finalized_source_header_id_at_source = Some(header_id(3));
+		assert_eq!(
+			strategy.select_nonces_to_deliver(state).await,
+			Some(((20..=23), proof_parameters(false, 4)))
+		);
+	}
+
+	#[async_std::test]
+	async fn message_delivery_is_limited_by_throughput_budget() {
+		let (state, mut strategy) = prepare_strategy();
+
+		let throughput_budget = ThroughputBudget::new(3, None).unwrap();
+		strategy.throughput_budget = throughput_budget.lane("A -> B".into());
+		assert_eq!(
+			strategy.select_nonces_to_deliver(state.clone()).await,
+			Some(((20..=22), proof_parameters(false, 3)))
+		);
+		// the budget is exhausted
+		assert_eq!(strategy.select_nonces_to_deliver(state).await, None);
+	}
+
+	#[async_std::test]
+	async fn message_delivery_is_deferred_while_companion_lane_is_stalled() {
+		let (state, mut strategy) = prepare_strategy();
+
+		let companion_summary = LaneSummary::default();
+		companion_summary.update_source_latest_generated_nonce(10);
+		companion_summary.update_source_latest_confirmed_nonce(5);
+		strategy.companion_lane = Some(CompanionLane {
+			name: "B -> A".into(),
+			summary: companion_summary.clone(),
+			staleness_window: Duration::ZERO,
+		});
+		assert_eq!(strategy.select_nonces_to_deliver(state.clone()).await, None);
+
+		// when the companion lane confirms all its messages, the delivery proceeds
+		companion_summary.update_source_latest_confirmed_nonce(10);
+		assert_eq!(
+			strategy.select_nonces_to_deliver(state).await,
+			Some(((20..=23), proof_parameters(false, 4)))
+		);
+	}
+
 	#[async_std::test]
 	async fn message_delivery_strategy_accepts_single_message_even_if_its_weight_overflows_maximal_size(
 	) {
@@ -1166,6 +1720,21 @@ mod tests {
 			max_messages_in_single_batch: 4,
 			max_messages_weight_in_single_batch: Weight::from_parts(4, 0),
 			max_messages_size_in_single_batch: 4,
+			fee_budget: FeeBudget::default(),
+			max_extrinsic_weight_at_target: Weight::from_parts(100, 0),
+			oversized_weight_policy: OversizedWeightPolicy::WarnOnly,
+			max_message_dispatch_weight: None,
+			error_state: None,
+			control: RelayControl::default(),
+			stall_tracker: None,
+			companion_lane: None,
+			throughput_budget: Default::default(),
+			min_batch: None,
+			batch_tuner: None,
+			weight_headroom: None,
+			message_grace_period_blocks: 0,
+			confirmations_priority_threshold: None,
+			is_prioritizing_confirmations: AtomicBool::new(false),
 			latest_confirmed_nonces_at_source: VecDeque::new(),
 			lane_source_client: TestSourceClient::default(),
 			lane_target_client: TestTargetClient::default(),
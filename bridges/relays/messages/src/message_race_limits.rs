@@ -20,14 +20,17 @@ use num_traits::Zero;
 use std::ops::RangeInclusive;
 
 use bp_messages::{MessageNonce, Weight};
+use relay_utils::control::RelayControl;
 
 use crate::{
 	message_lane::MessageLane,
 	message_lane_loop::{
-		MessageDetails, MessageDetailsMap, SourceClient as MessageLaneSourceClient,
+		LaneErrorCategory, LaneErrorState, MessageDetails, MessageDetailsMap,
+		OversizedWeightPolicy, SourceClient as MessageLaneSourceClient,
 		TargetClient as MessageLaneTargetClient,
 	},
 	message_race_loop::NoncesRange,
+	message_race_stall::StallTracker,
 	message_race_strategy::SourceRangesQueue,
 	metrics::MessageLaneLoopMetrics,
 };
@@ -87,6 +90,21 @@ pub struct RelayMessagesBatchReference<
 	>,
 	/// Range of indices within the `nonces_queue` that are available for selection.
 	pub nonces_queue_range: RangeInclusive<usize>,
+	/// Maximal weight of the delivery transaction at the target chain.
+	pub max_extrinsic_weight_at_target: Weight,
+	/// What to do with messages that are heavier than `max_extrinsic_weight_at_target`.
+	pub oversized_weight_policy: OversizedWeightPolicy,
+	/// Maximal acceptable declared dispatch weight of the single message.
+	pub max_message_dispatch_weight: Option<Weight>,
+	/// Relay control, used to halt the relay.
+	pub control: RelayControl,
+	/// If set, messages that can't be delivered stall the lane instead of halting the relay.
+	pub stall_tracker: Option<StallTracker>,
+	/// If set, the dispatch weight of the batch must fit into the weight headroom of the
+	/// target block.
+	pub weight_headroom: Option<Weight>,
+	/// If set, stalled lane is reported there.
+	pub error_state: Option<LaneErrorState>,
 }
 
 /// Limits of the message race transactions.
@@ -110,6 +128,19 @@ impl MessageRaceLimits {
 			reference.best_target_nonce + 1,
 			reference.nonces_queue[*reference.nonces_queue_range.start()].1.begin(),
 		);
+		let lane = format!("{} -> {}", P::SOURCE_NAME, P::TARGET_NAME);
+		let error_state = reference.error_state.clone();
+		let note_error = |category| {
+			if let Some(ref error_state) = error_state {
+				error_state.note(category);
+			}
+		};
+		if let Some(ref stall_tracker) = reference.stall_tracker {
+			if !stall_tracker.is_probe_allowed(&lane, hard_selected_begin_nonce) {
+				note_error(LaneErrorCategory::Stalled);
+				return None
+			}
+		}
 
 		// relay reference
 		let mut relay_reference = RelayReference {
@@ -146,6 +177,93 @@ impl MessageRaceLimits {
 			// with single message if message overflows these limits. The worst case would be if
 			// transaction will be rejected by the target runtime, but at least we have tried.
 
+			// the message that is heavier than the delivery transaction itself can't be delivered
+			// and blocks the lane forever
+			if selected_count == 0 &&
+				!details.dispatch_weight.all_lte(reference.max_extrinsic_weight_at_target)
+			{
+				let reason = format!(
+					"{} -> {} message {} has dispatch weight {} that overflows maximal delivery \
+					transaction weight {}. It can never be delivered",
+					P::SOURCE_NAME,
+					P::TARGET_NAME,
+					nonce,
+					details.dispatch_weight,
+					reference.max_extrinsic_weight_at_target,
+				);
+				match (reference.oversized_weight_policy, reference.stall_tracker.as_ref()) {
+					(OversizedWeightPolicy::Reprobe(_), Some(stall_tracker)) => {
+						let is_new_stall = stall_tracker.note_blocked(
+							&lane,
+							*nonce,
+							&reason,
+							"wait until the target runtime accepts heavier delivery transactions \
+							or until the message is delivered by other means",
+						);
+						if let (true, Some(ref metrics)) =
+							(is_new_stall, reference.metrics.as_ref())
+						{
+							metrics.note_lane_stall();
+						}
+						note_error(LaneErrorCategory::Stalled);
+						return None
+					},
+					(OversizedWeightPolicy::Halt, _) |
+					(OversizedWeightPolicy::Reprobe(_), None) => {
+						reference.control.halt(reason);
+						return None
+					},
+					(OversizedWeightPolicy::WarnOnly, _) =>
+						log::error!(target: "bridge", "{}", reason),
+				}
+			}
+
+			// the message that declares too large dispatch weight is never delivered. Lane
+			// messages are delivered in order, so it can't be skipped and the relay is halted
+			// once all preceding messages are delivered
+			if let Some(max_message_dispatch_weight) = reference.max_message_dispatch_weight {
+				if !details.dispatch_weight.all_lte(max_message_dispatch_weight) {
+					if selected_count != 0 {
+						break
+					}
+
+					let reason = format!(
+						"{} -> {} message {} has dispatch weight {} that exceeds maximal \
+						acceptable message dispatch weight {}",
+						P::SOURCE_NAME,
+						P::TARGET_NAME,
+						nonce,
+						details.dispatch_weight,
+						max_message_dispatch_weight,
+					);
+					if let Some(ref stall_tracker) = reference.stall_tracker {
+						let is_new_stall = stall_tracker.note_blocked(
+							&lane,
+							*nonce,
+							&reason,
+							"raise `--max-message-dispatch-weight` if the declared weight is \
+							acceptable",
+						);
+						if let (true, Some(ref metrics)) =
+							(is_new_stall, reference.metrics.as_ref())
+						{
+							metrics.note_message_skipped_for_weight();
+							metrics.note_lane_stall();
+						}
+						note_error(LaneErrorCategory::Stalled);
+						return None
+					}
+
+					if reference.control.halt_reason().is_none() {
+						if let Some(ref metrics) = reference.metrics {
+							metrics.note_message_skipped_for_weight();
+						}
+					}
+					reference.control.halt(reason);
+					return None
+				}
+			}
+
 			// limit messages in the batch by weight
 			let new_selected_weight = match selected_weight.checked_add(&details.dispatch_weight) {
 				Some(new_selected_weight)
@@ -165,6 +283,27 @@ impl MessageRaceLimits {
 				_ => break,
 			};
 
+			// limit messages in the batch by the weight headroom of the target block
+			if let Some(weight_headroom) = reference.weight_headroom {
+				if !new_selected_weight.all_lte(weight_headroom) {
+					if selected_count == 0 {
+						log::info!(
+							target: "bridge",
+							"Deferring delivery of {} -> {} message {}: its dispatch weight {} \
+							doesn't fit into the weight headroom {} of the {} block",
+							P::SOURCE_NAME,
+							P::TARGET_NAME,
+							nonce,
+							details.dispatch_weight,
+							weight_headroom,
+							P::TARGET_NAME,
+						);
+						return None
+					}
+					break
+				}
+			}
+
 			// limit messages in the batch by size
 			let new_selected_size = match relay_reference.selected_size.checked_add(details.size) {
 				Some(new_selected_size)
@@ -195,6 +334,13 @@ impl MessageRaceLimits {
 			selected_count = new_selected_count;
 		}
 
+		if let (true, Some(stall_tracker)) = (hard_selected_count != 0, &reference.stall_tracker) {
+			stall_tracker.note_unblocked(&lane);
+		}
+		if let (true, Some(error_state)) = (hard_selected_count != 0, &error_state) {
+			error_state.clear(LaneErrorCategory::Stalled);
+		}
+
 		if hard_selected_count != 0 {
 			let selected_max_nonce =
 				hard_selected_begin_nonce + hard_selected_count as MessageNonce - 1;
@@ -20,16 +20,24 @@
 //! associated data - like messages, lane state, etc) to the target node by
 //! generating and submitting proof.
 
-use crate::message_lane_loop::{BatchTransaction, ClientState, NoncesSubmitArtifacts};
+use crate::{
+	message_lane_loop::{
+		BatchAutoTuner, BatchTransaction, ClientState, LaneErrorCategory, LaneErrorState,
+		MessageCorrelation, NoncesSubmitArtifacts, RetryBudget,
+	},
+	message_race_serializer::RaceSerializer,
+};
 
 use async_trait::async_trait;
 use bp_messages::MessageNonce;
 use futures::{
-	future::{FutureExt, TryFutureExt},
+	future::{Future, FutureExt, TryFutureExt},
 	stream::{FusedStream, StreamExt},
 };
 use relay_utils::{
-	process_future_result, retry_backoff, FailedClient, MaybeConnectionError,
+	control::RelayControl,
+	otlp::{Span, SpanContext},
+	process_future_result, retry_backoff, FailedClient, FailureKind, MaybeConnectionError,
 	TrackedTransactionStatus, TransactionTracker,
 };
 use std::{
@@ -38,6 +46,10 @@ use std::{
 	time::{Duration, Instant},
 };
 
+/// Number of consecutive proof generation timeouts, after which the batch tuner shrinks the
+/// batch.
+const PROOF_GENERATION_TIMEOUTS_BEFORE_SHRINK: u32 = 2;
+
 /// One of races within lane.
 pub trait MessageRace {
 	/// Header id of the race source.
@@ -330,6 +342,37 @@ where
 	}
 }
 
+/// Name of the race, used to report its progress.
+pub(crate) fn race_name<P: MessageRace>() -> String {
+	format!("{} -> {}", P::source_name(), P::target_name())
+}
+
+/// Race loop parameters, shared by the delivery and the receiving races.
+#[derive(Clone)]
+pub struct RaceLoopParams {
+	/// Relay control, used to pause the race and to report its status.
+	pub control: RelayControl,
+	/// Number of new blocks that the race must see at both nodes before submitting
+	/// transactions.
+	pub warm_up_blocks: u32,
+	/// Delay after start, before the race submits the first transaction.
+	pub first_submit_delay: Duration,
+	/// Number of consecutive idle iterations, after which the race iteration log is escalated.
+	pub inactivity_log_threshold: Option<u32>,
+	/// Budget of attempts to deliver every nonce.
+	pub retry_budget: Option<RetryBudget>,
+	/// Tuner of the batch size.
+	pub batch_tuner: Option<BatchAutoTuner>,
+	/// Correlation of submitted nonces with message ids.
+	pub correlation: Option<MessageCorrelation>,
+	/// Serializer of client calls of all lane races, if races are serialized.
+	pub race_serializer: Option<RaceSerializer>,
+	/// Lane error state, updated by the race.
+	pub error_state: Option<LaneErrorState>,
+	/// Context of the lane span.
+	pub span_context: SpanContext,
+}
+
 /// Run race loop until connection with target or source node is lost.
 pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 	race_source: SC,
@@ -344,14 +387,51 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 		ProofParameters = SC::ProofParameters,
 		TargetNoncesData = TC::TargetNoncesData,
 	>,
+	params: RaceLoopParams,
 ) -> Result<(), FailedClient> {
+	let RaceLoopParams {
+		control,
+		warm_up_blocks,
+		first_submit_delay,
+		inactivity_log_threshold,
+		retry_budget,
+		batch_tuner,
+		correlation,
+		race_serializer,
+		error_state,
+		span_context,
+	} = params;
+	let note_error = |category| {
+		if let Some(ref error_state) = error_state {
+			error_state.note(category);
+		}
+	};
 	let mut progress_context = Instant::now();
 	let mut race_state = RaceStateImpl::default();
+	let mut warm_up = WarmUp::new(warm_up_blocks, first_submit_delay);
+	let mut is_ready = false;
+	control.set_race_ready(race_name::<P>(), false);
+	let mut inactivity_log = InactivityLog::new(inactivity_log_threshold);
+	if warm_up.is_active() {
+		log::info!(
+			target: "bridge",
+			"Warming up {} -> {} race: waiting for {} new blocks at both nodes and for {}s \
+			before submitting transactions",
+			P::source_name(),
+			P::target_name(),
+			warm_up_blocks,
+			first_submit_delay.as_secs(),
+		);
+	}
+	let span_context = span_context
+		.with_inherited_attribute("race", format!("{} -> {}", P::source_name(), P::target_name()));
+	let mut submitted_transaction_span: Option<Span> = None;
 
 	let mut source_retry_backoff = retry_backoff();
 	let mut source_client_is_online = true;
 	let mut source_nonces_required = false;
 	let mut source_required_header = None;
+	let mut source_proof_generation_timeouts = 0;
 	let source_nonces = futures::future::Fuse::terminated();
 	let source_generate_proof = futures::future::Fuse::terminated();
 	let source_go_offline_future = futures::future::Fuse::terminated();
@@ -390,6 +470,9 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 					let is_source_state_updated = race_state.best_finalized_source_header_id_at_source.as_ref()
 						!= Some(&source_state.best_finalized_self);
 					if is_source_state_updated {
+						if race_state.best_finalized_source_header_id_at_source.is_some() {
+							warm_up.source_block_observed::<P>();
+						}
 						source_nonces_required = true;
 						race_state.best_finalized_source_header_id_at_source
 							= Some(source_state.best_finalized_self);
@@ -402,6 +485,9 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 						!= Some(&target_state.best_self);
 
 					if is_target_best_state_updated {
+						if race_state.best_target_header_id.is_some() {
+							warm_up.target_block_observed::<P>();
+						}
 						target_best_nonces_required = true;
 						race_state.best_target_header_id = Some(target_state.best_self);
 						race_state.best_finalized_source_header_id_at_best_target
@@ -514,7 +600,23 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 				).fail_if_connection_error(FailedClient::Target)?;
 			},
 			proof = source_generate_proof => {
-				source_client_is_online = process_future_result(
+				// the proof, generated by the slow node, may be stale. We abandon it and generate
+				// it again later, without restarting the loop
+				let is_proof_generation_timeout =
+					matches!(proof, Err(ref e) if e.is_proof_generation_timeout());
+				if is_proof_generation_timeout {
+					source_proof_generation_timeouts += 1;
+					if source_proof_generation_timeouts >= PROOF_GENERATION_TIMEOUTS_BEFORE_SHRINK {
+						source_proof_generation_timeouts = 0;
+						if let Some(ref batch_tuner) = batch_tuner {
+							log_batch_size_change::<P>(batch_tuner.note_resources_exhausted());
+						}
+					}
+				} else if proof.is_ok() {
+					source_proof_generation_timeouts = 0;
+				}
+
+				let proof_result = process_future_result(
 					proof,
 					&mut source_retry_backoff,
 					|(at_block, nonces_range, proof, batch_transaction)| {
@@ -531,9 +633,35 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 					&mut source_go_offline_future,
 					async_std::task::sleep,
 					|| format!("Error generating proof at {}", P::source_name()),
-				).fail_if_error(FailedClient::Source).map(|_| true)?;
+				);
+				source_client_is_online = if is_proof_generation_timeout {
+					proof_result.fail_if_connection_error(FailedClient::Source)?
+				} else {
+					proof_result.fail_if_error(FailedClient::Source).map(|_| true)?
+				};
 			},
 			proof_submit_result = target_submit_proof => {
+				control.set_submission_in_progress(race_name::<P>(), false);
+				if let Err(ref e) = proof_submit_result {
+					control.set_last_error(
+						race_name::<P>(),
+						format!("failed to submit proof to {}: {e:?}", P::target_name()),
+					);
+					if let Some(mut span) = submitted_transaction_span.take() {
+						span.set_attribute("outcome", "failed");
+						span.set_error(format!("{e:?}"));
+					}
+					if let (Some(kind), Some((_, nonces, _))) =
+						(e.failure_kind(), race_state.nonces_to_submit.as_ref())
+					{
+						note_failed_attempt::<P>(&retry_budget, &control, *nonces.start(), kind);
+						note_error(LaneErrorCategory::Rejected);
+					}
+					if let (true, Some(ref batch_tuner)) = (e.exhausts_resources(), &batch_tuner) {
+						log_batch_size_change::<P>(batch_tuner.note_resources_exhausted());
+					}
+				}
+
 				target_client_is_online = process_future_result(
 					proof_submit_result,
 					&mut target_retry_backoff,
@@ -545,6 +673,7 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 							P::target_name(),
 						);
 
+						control.set_last_submitted_nonce(race_name::<P>(), *artifacts.nonces.end());
 						race_state.nonces_submitted = Some(artifacts.nonces);
 						target_tx_tracker.set(artifacts.tx_tracker.wait().fuse());
 					},
@@ -563,6 +692,34 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 				}
 			},
 			target_transaction_status = target_tx_tracker => {
+				let mut transaction_span = submitted_transaction_span.take();
+				if let Some(ref mut span) = transaction_span {
+					match target_transaction_status {
+						TrackedTransactionStatus::Finalized(_) =>
+							span.set_attribute("outcome", "finalized"),
+						TrackedTransactionStatus::Lost => {
+							span.set_attribute("outcome", "lost");
+							span.set_error("transaction has been lost");
+						},
+					}
+				}
+
+				if let (Some(ref correlation), Some(nonces_submitted)) =
+					(correlation.as_ref(), race_state.nonces_submitted.as_ref())
+				{
+					log::info!(
+						target: "bridge",
+						"{} -> {} transaction with messages {} is {}",
+						P::source_name(),
+						P::target_name(),
+						correlation.ids(nonces_submitted.clone()),
+						match target_transaction_status {
+							TrackedTransactionStatus::Finalized(_) => "finalized",
+							TrackedTransactionStatus::Lost => "lost",
+						},
+					);
+				}
+
 				match (target_transaction_status, race_state.nonces_submitted.as_ref()) {
 					(TrackedTransactionStatus::Finalized(at_block), Some(nonces_submitted)) => {
 						// our transaction has been mined, but was it successful or not? let's check the best
@@ -572,12 +729,24 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 							.map_err(|e| format!("failed to read nonces from target node: {e:?}"))
 							.and_then(|(_, nonces_at_target)| {
 								if nonces_at_target.latest_nonce < *nonces_submitted.end() {
+									// the transaction has been included, so it would fail again
+									note_failed_attempt::<P>(
+										&retry_budget,
+										&control,
+										*nonces_submitted.start(),
+										FailureKind::Deterministic,
+									);
+									note_error(LaneErrorCategory::Rejected);
 									Err(format!(
 										"best nonce at target after tx is {:?} and we've submitted {:?}",
 										nonces_at_target.latest_nonce,
 										nonces_submitted.end(),
 									))
 								} else {
+									if let Some(ref batch_tuner) = batch_tuner {
+										log_batch_size_change::<P>(batch_tuner.note_delivered());
+									}
+									note_error(LaneErrorCategory::Ok);
 									Ok(())
 								}
 							})
@@ -589,11 +758,28 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 									P::target_name(),
 									e,
 								);
+								control.set_last_error(race_name::<P>(), e.clone());
+								if let Some(ref mut span) = transaction_span {
+									span.set_error(&e);
+								}
 
 								race_state.reset_nonces_submitted();
 							});
 					},
-					(TrackedTransactionStatus::Lost, _) => {
+					(TrackedTransactionStatus::Lost, nonces_submitted) => {
+						if let Some(nonces_submitted) = nonces_submitted {
+							note_failed_attempt::<P>(
+								&retry_budget,
+								&control,
+								*nonces_submitted.start(),
+								FailureKind::Transient,
+							);
+						}
+						note_error(LaneErrorCategory::Rejected);
+						control.set_last_error(
+							race_name::<P>(),
+							"transaction has been lost".into(),
+						);
 						log::warn!(
 							target: "bridge",
 							"{} -> {} race transaction has been lost. State: {:?}. Strategy: {:?}",
@@ -620,6 +806,16 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 
 		progress_context = print_race_progress::<P, _>(progress_context, &strategy);
 
+		// the race is ready once it has seen states of both nodes and has warmed up
+		if !is_ready &&
+			!warm_up.is_active() &&
+			race_state.best_finalized_source_header_id_at_source.is_some() &&
+			race_state.best_target_header_id.is_some()
+		{
+			is_ready = true;
+			control.set_race_ready(race_name::<P>(), true);
+		}
+
 		if source_client_is_online {
 			source_client_is_online = false;
 
@@ -647,6 +843,24 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 			let nonces_to_deliver = select_nonces_to_deliver(expected_race_state, &strategy).await;
 			let best_at_source = strategy.best_at_source();
 
+			let is_active = nonces_to_deliver.is_some() ||
+				race_state.nonces_to_submit.is_some() ||
+				race_state.nonces_submitted.is_some();
+			let log_level = inactivity_log.on_iteration(is_active, !strategy.is_empty());
+			if !is_active {
+				log::log!(
+					target: "bridge",
+					log_level,
+					"{} -> {} race has selected nothing to deliver in {} consecutive iterations. \
+					State: {:?}. Strategy: {:?}",
+					P::source_name(),
+					P::target_name(),
+					inactivity_log.idle_iterations,
+					race_state,
+					strategy,
+				);
+			}
+
 			if let Some((at_block, nonces_range, proof_parameters)) = nonces_to_deliver {
 				log::debug!(
 					target: "bridge",
@@ -657,12 +871,14 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 				);
 
 				source_generate_proof.set(
-					race_source
-						.generate_proof(at_block, nonces_range, proof_parameters)
-						.and_then(|(at_source_block, nonces, proof)| async {
-							Ok((at_source_block, nonces, proof, target_batch_transaction))
-						})
-						.fuse(),
+					serialized::<P, _>(
+						race_serializer.as_ref(),
+						race_source.generate_proof(at_block, nonces_range, proof_parameters),
+					)
+					.and_then(|(at_source_block, nonces, proof)| async {
+						Ok((at_source_block, nonces, proof, target_batch_transaction))
+					})
+					.fuse(),
 				);
 			} else if let (true, Some(best_at_source)) = (source_nonces_required, best_at_source) {
 				log::debug!(target: "bridge", "Asking {} about message nonces", P::source_name());
@@ -674,7 +890,13 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 						best_finalized_source_header_id_at_source is Some; qed",
 					)
 					.clone();
-				source_nonces.set(race_source.nonces(at_block, best_at_source).fuse());
+				source_nonces.set(
+					serialized::<P, _>(
+						race_serializer.as_ref(),
+						race_source.nonces(at_block, best_at_source),
+					)
+					.fuse(),
+				);
 			} else {
 				source_client_is_online = true;
 			}
@@ -683,7 +905,12 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 		if target_client_is_online {
 			target_client_is_online = false;
 
-			if let Some((at_block, nonces_range, proof)) = race_state.nonces_to_submit.as_ref() {
+			// while relay is paused or warming up, we keep prepared proof and keep tracking
+			// nonces, but we do not submit anything to the target node
+			let is_paused = control.is_paused() || warm_up.is_active();
+			if let (false, Some((at_block, nonces_range, proof))) =
+				(is_paused, race_state.nonces_to_submit.as_ref())
+			{
 				log::debug!(
 					target: "bridge",
 					"Going to submit proof of messages in range {:?} to {} node{}",
@@ -695,17 +922,38 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 					).unwrap_or_default(),
 				);
 
+				let mut span = span_context.child("submit_proof");
+				span.set_attribute("nonces", format!("{nonces_range:?}"));
+				span.set_attribute("at_source_block", format!("{at_block:?}"));
+				if let Some(ref correlation) = correlation {
+					let correlation_ids = correlation.ids(nonces_range.clone());
+					log::info!(
+						target: "bridge",
+						"Submitting {} -> {} transaction with messages {}",
+						P::source_name(),
+						P::target_name(),
+						correlation_ids,
+					);
+					span.set_attribute("correlation_ids", correlation_ids);
+				}
+				submitted_transaction_span = Some(span);
+
+				control.set_submission_in_progress(race_name::<P>(), true);
 				target_submit_proof.set(
-					race_target
-						.submit_proof(
+					serialized::<P, _>(
+						race_serializer.as_ref(),
+						race_target.submit_proof(
 							race_state.nonces_to_submit_batch.clone(),
 							at_block.clone(),
 							nonces_range.clone(),
 							proof.clone(),
-						)
-						.fuse(),
+						),
+					)
+					.fuse(),
 				);
-			} else if let Some(source_required_header) = source_required_header.clone() {
+			} else if let (false, Some(source_required_header)) =
+				(is_paused, source_required_header.clone())
+			{
 				log::debug!(
 					target: "bridge",
 					"Going to require {} header {:?} at {}",
@@ -722,7 +970,13 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 					.as_ref()
 					.expect("target_best_nonces_required is only true when best_target_header_id is Some; qed")
 					.clone();
-				target_best_nonces.set(race_target.nonces(at_block, false).fuse());
+				target_best_nonces.set(
+					serialized::<P, _>(
+						race_serializer.as_ref(),
+						race_target.nonces(at_block, false),
+					)
+					.fuse(),
+				);
 			} else if target_finalized_nonces_required {
 				log::debug!(target: "bridge", "Asking {} about finalized message nonces", P::target_name());
 				let at_block = race_state
@@ -733,7 +987,13 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 						best_finalized_target_header_id is Some; qed",
 					)
 					.clone();
-				target_finalized_nonces.set(race_target.nonces(at_block, true).fuse());
+				target_finalized_nonces.set(
+					serialized::<P, _>(
+						race_serializer.as_ref(),
+						race_target.nonces(at_block, true),
+					)
+					.fuse(),
+				);
 			} else {
 				target_client_is_online = true;
 			}
@@ -741,6 +1001,144 @@ pub async fn run<P: MessageRace, SC: SourceClient<P>, TC: TargetClient<P>>(
 	}
 }
 
+/// Race warm-up state.
+///
+/// Right after connecting, the relay view of both chains may be incomplete and the target
+/// node may not reliably accept transactions yet. So the race waits until it sees given
+/// number of new blocks at both nodes and until the first submit delay passes, before
+/// submitting transactions. The race state is still read meanwhile.
+#[derive(Debug)]
+struct WarmUp {
+	blocks: u32,
+	source_blocks: u32,
+	target_blocks: u32,
+	/// The race doesn't submit transactions before this moment.
+	submit_not_before: Instant,
+}
+
+impl WarmUp {
+	/// Create new warm-up state.
+	fn new(blocks: u32, first_submit_delay: Duration) -> Self {
+		WarmUp {
+			blocks,
+			source_blocks: 0,
+			target_blocks: 0,
+			submit_not_before: Instant::now() + first_submit_delay,
+		}
+	}
+
+	/// Returns true if the race is still warming up.
+	fn is_active(&self) -> bool {
+		self.source_blocks < self.blocks ||
+			self.target_blocks < self.blocks ||
+			Instant::now() < self.submit_not_before
+	}
+
+	/// Called when new block is observed at the race source.
+	fn source_block_observed<P: MessageRace>(&mut self) {
+		let was_active = self.is_active();
+		self.source_blocks = self.source_blocks.saturating_add(1);
+		self.log_completion::<P>(was_active);
+	}
+
+	/// Called when new block is observed at the race target.
+	fn target_block_observed<P: MessageRace>(&mut self) {
+		let was_active = self.is_active();
+		self.target_blocks = self.target_blocks.saturating_add(1);
+		self.log_completion::<P>(was_active);
+	}
+
+	fn log_completion<P: MessageRace>(&self, was_active: bool) {
+		if was_active && !self.is_active() {
+			log::info!(
+				target: "bridge",
+				"{} -> {} race has warmed up",
+				P::source_name(),
+				P::target_name(),
+			);
+		}
+	}
+}
+
+/// Verbosity of the race iteration logs.
+///
+/// When the race selects nothing to deliver, it is logged at debug level. Once that happens
+/// in `threshold` consecutive iterations, the log is escalated to info if there's nothing to
+/// deliver, or to warn if the race doesn't deliver nonces that it has. The escalated log is
+/// repeated every `threshold` idle iterations.
+#[derive(Debug)]
+struct InactivityLog {
+	threshold: Option<u32>,
+	idle_iterations: u32,
+}
+
+impl InactivityLog {
+	/// Create new inactivity log. If `threshold` is `None`, the log is never escalated.
+	fn new(threshold: Option<u32>) -> Self {
+		InactivityLog { threshold, idle_iterations: 0 }
+	}
+
+	/// Called after every race iteration. Returns the level of the iteration log.
+	fn on_iteration(&mut self, is_active: bool, has_undelivered_nonces: bool) -> log::Level {
+		if is_active {
+			self.idle_iterations = 0;
+			return log::Level::Debug
+		}
+
+		self.idle_iterations = self.idle_iterations.saturating_add(1);
+		match self.threshold {
+			Some(threshold) if threshold != 0 && self.idle_iterations % threshold == 0 =>
+				if has_undelivered_nonces {
+					log::Level::Warn
+				} else {
+					log::Level::Info
+				},
+			_ => log::Level::Debug,
+		}
+	}
+}
+
+/// Remember failed attempt to deliver nonces, starting with given nonce. The relay is halted
+/// if the retry budget of this nonce is exhausted.
+fn note_failed_attempt<P: MessageRace>(
+	retry_budget: &Option<RetryBudget>,
+	control: &RelayControl,
+	nonce: MessageNonce,
+	kind: FailureKind,
+) {
+	if let Some(retry_budget) = retry_budget {
+		if let Err(reason) = retry_budget.note_failure(nonce, kind) {
+			control.halt(format!("{} -> {} race: {}", P::source_name(), P::target_name(), reason));
+		}
+	}
+}
+
+/// Run the client future, serialized with other races of the lane, if races are serialized.
+async fn serialized<P: MessageRace, T>(
+	race_serializer: Option<&RaceSerializer>,
+	future: impl Future<Output = T>,
+) -> T {
+	match race_serializer {
+		Some(race_serializer) => race_serializer.run(&race_name::<P>(), future).await,
+		None => future.await,
+	}
+}
+
+/// Log change of the batch size, made by the batch tuner.
+fn log_batch_size_change<P: MessageRace>(change: Option<(MessageNonce, MessageNonce)>) {
+	if let Some((prev_size, size)) = change {
+		log::info!(
+			target: "bridge",
+			"{} -> {} race has {} the batch size from {} to {} messages",
+			P::source_name(),
+			P::target_name(),
+			if size > prev_size { "increased" } else { "decreased" },
+			prev_size,
+			size,
+		);
+	}
+}
+
 /// Print race progress.
 fn print_race_progress<P, S>(prev_time: Instant, strategy: &S) -> Instant
 where
@@ -832,4 +1230,73 @@ mod tests {
 			Some((HeaderId(BEST_AT_TARGET, BEST_AT_TARGET), 6..=10, (),))
 		);
 	}
+
+	#[test]
+	fn inactivity_log_is_escalated_after_threshold() {
+		let mut inactivity_log = InactivityLog::new(Some(2));
+		assert_eq!(inactivity_log.on_iteration(false, false), log::Level::Debug);
+		assert_eq!(inactivity_log.on_iteration(false, false), log::Level::Info);
+		assert_eq!(inactivity_log.on_iteration(false, true), log::Level::Debug);
+		assert_eq!(inactivity_log.on_iteration(false, true), log::Level::Warn);
+
+		// active iteration resets the counter
+		assert_eq!(inactivity_log.on_iteration(true, true), log::Level::Debug);
+		assert_eq!(inactivity_log.on_iteration(false, true), log::Level::Debug);
+		assert_eq!(inactivity_log.on_iteration(false, true), log::Level::Warn);
+
+		// without threshold the log is never escalated
+		let mut inactivity_log = InactivityLog::new(None);
+		for _ in 0..10 {
+			assert_eq!(inactivity_log.on_iteration(false, true), log::Level::Debug);
+		}
+	}
+
+	struct TestRace;
+
+	impl MessageRace for TestRace {
+		type SourceHeaderId = HeaderId<u64, u64>;
+		type TargetHeaderId = HeaderId<u64, u64>;
+		type MessageNonce = u64;
+		type Proof = ();
+
+		fn source_name() -> String {
+			"Source".into()
+		}
+
+		fn target_name() -> String {
+			"Target".into()
+		}
+	}
+
+	#[test]
+	fn warm_up_requires_new_blocks_at_both_nodes() {
+		type Race = TestRace;
+
+		assert!(!WarmUp::new(0, Duration::ZERO).is_active());
+
+		let mut warm_up = WarmUp::new(2, Duration::ZERO);
+		assert!(warm_up.is_active());
+		warm_up.source_block_observed::<Race>();
+		warm_up.source_block_observed::<Race>();
+		warm_up.source_block_observed::<Race>();
+		assert!(warm_up.is_active());
+		warm_up.target_block_observed::<Race>();
+		assert!(warm_up.is_active());
+		warm_up.target_block_observed::<Race>();
+		assert!(!warm_up.is_active());
+	}
+
+	#[test]
+	fn warm_up_holds_off_first_submission() {
+		type Race = TestRace;
+
+		let mut warm_up = WarmUp::new(1, Duration::from_secs(3600));
+		warm_up.source_block_observed::<Race>();
+		warm_up.target_block_observed::<Race>();
+		assert!(warm_up.is_active());
+
+		// the delay has passed
+		warm_up.submit_not_before = Instant::now();
+		assert!(!warm_up.is_active());
+	}
 }
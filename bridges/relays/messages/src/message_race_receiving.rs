@@ -16,11 +16,11 @@
 use crate::{
 	message_lane::{MessageLane, SourceHeaderIdOf, TargetHeaderIdOf},
 	message_lane_loop::{
-		NoncesSubmitArtifacts, SourceClient as MessageLaneSourceClient, SourceClientState,
-		TargetClient as MessageLaneTargetClient, TargetClientState,
+		ConfirmationDeadline, NoncesSubmitArtifacts, SourceClient as MessageLaneSourceClient,
+		SourceClientState, TargetClient as MessageLaneTargetClient, TargetClientState,
 	},
 	message_race_loop::{
-		MessageRace, NoncesRange, SourceClient, SourceClientNonces, TargetClient,
+		MessageRace, NoncesRange, RaceLoopParams, SourceClient, SourceClientNonces, TargetClient,
 		TargetClientNonces,
 	},
 	message_race_strategy::BasicStrategy,
@@ -30,7 +30,9 @@ use crate::{
 use async_trait::async_trait;
 use bp_messages::MessageNonce;
 use futures::stream::FusedStream;
-use relay_utils::{FailedClient, TrackedTransactionStatus, TransactionTracker};
+use relay_utils::{
+	run_summary::LaneSummary, FailedClient, TrackedTransactionStatus, TransactionTracker,
+};
 use std::{marker::PhantomData, ops::RangeInclusive};
 
 /// Message receiving confirmations delivery strategy.
@@ -50,21 +52,29 @@ pub async fn run<P: MessageLane>(
 	target_client: impl MessageLaneTargetClient<P>,
 	target_state_updates: impl FusedStream<Item = TargetClientState<P>>,
 	metrics_msg: Option<MessageLaneLoopMetrics>,
+	lane_summary: LaneSummary,
+	confirmation_deadline: Option<ConfirmationDeadline>,
+	race_params: RaceLoopParams,
 ) -> Result<(), FailedClient> {
 	crate::message_race_loop::run(
 		ReceivingConfirmationsRaceSource {
 			client: target_client,
 			metrics_msg: metrics_msg.clone(),
+			lane_summary: lane_summary.clone(),
+			confirmation_deadline: confirmation_deadline.clone(),
 			_phantom: Default::default(),
 		},
 		target_state_updates,
 		ReceivingConfirmationsRaceTarget {
 			client: source_client,
 			metrics_msg,
+			lane_summary,
+			confirmation_deadline,
 			_phantom: Default::default(),
 		},
 		source_state_updates,
 		ReceivingConfirmationsBasicStrategy::<P>::new(),
+		RaceLoopParams { retry_budget: None, batch_tuner: None, ..race_params },
 	)
 	.await
 }
@@ -106,6 +116,11 @@ pub async fn relay_messages_delivery_confirmation<P: MessageLane>(
 	}
 }
 
+/// Name of the messages receiving confirmations race, used to report its progress.
+pub(crate) fn receiving_race_name<P: MessageLane>() -> String {
+	crate::message_race_loop::race_name::<ReceivingConfirmationsRace<P>>()
+}
+
 /// Messages receiving confirmations race.
 struct ReceivingConfirmationsRace<P>(std::marker::PhantomData<P>);
 
@@ -129,6 +144,8 @@ impl<P: MessageLane> MessageRace for ReceivingConfirmationsRace<P> {
 struct ReceivingConfirmationsRaceSource<P: MessageLane, C> {
 	client: C,
 	metrics_msg: Option<MessageLaneLoopMetrics>,
+	lane_summary: LaneSummary,
+	confirmation_deadline: Option<ConfirmationDeadline>,
 	_phantom: PhantomData<P>,
 }
 
@@ -151,6 +168,10 @@ where
 		if let Some(metrics_msg) = self.metrics_msg.as_ref() {
 			metrics_msg.update_target_latest_received_nonce(latest_received_nonce);
 		}
+		self.lane_summary.update_target_latest_received_nonce(latest_received_nonce);
+		if let Some(confirmation_deadline) = self.confirmation_deadline.as_ref() {
+			confirmation_deadline.note_delivered(latest_received_nonce);
+		}
 		Ok((
 			at_block,
 			SourceClientNonces {
@@ -181,6 +202,8 @@ where
 struct ReceivingConfirmationsRaceTarget<P: MessageLane, C> {
 	client: C,
 	metrics_msg: Option<MessageLaneLoopMetrics>,
+	lane_summary: LaneSummary,
+	confirmation_deadline: Option<ConfirmationDeadline>,
 	_phantom: PhantomData<P>,
 }
 
@@ -213,6 +236,10 @@ where
 			if let Some(metrics_msg) = self.metrics_msg.as_ref() {
 				metrics_msg.update_source_latest_confirmed_nonce(latest_confirmed_nonce);
 			}
+			self.lane_summary.update_source_latest_confirmed_nonce(latest_confirmed_nonce);
+		}
+		if let Some(confirmation_deadline) = self.confirmation_deadline.as_ref() {
+			confirmation_deadline.note_confirmed(latest_confirmed_nonce);
 		}
 		Ok((at_block, TargetClientNonces { latest_nonce: latest_confirmed_nonce, nonces_data: () }))
 	}
@@ -0,0 +1,174 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Serialization of the delivery and confirmation races of the lane.
+//!
+//! By default, both races of the lane are talking to nodes concurrently. When races are
+//! serialized, only one race at a time may read nonces, generate proof or submit transaction
+//! and races take turns when both of them are waiting. That lowers the number of concurrent
+//! requests to nodes, but every race has to wait for the other one, so messages are delivered
+//! and confirmed later.
+
+use futures::{channel::oneshot, Future};
+use parking_lot::Mutex;
+use std::{collections::VecDeque, sync::Arc};
+
+/// Serializer of races, shared by all races of the lane.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RaceSerializer {
+	state: Arc<Mutex<SerializerState>>,
+}
+
+#[derive(Debug, Default)]
+struct SerializerState {
+	/// True if some race holds the turn.
+	is_busy: bool,
+	/// Race that has got the latest turn.
+	latest: Option<String>,
+	/// Races, waiting for their turn, in order of arrival.
+	waiting: VecDeque<(String, oneshot::Sender<Turn>)>,
+}
+
+impl RaceSerializer {
+	/// Run the future of given race, when it is its turn.
+	pub async fn run<T>(&self, race: &str, future: impl Future<Output = T>) -> T {
+		let _turn = self.turn(race).await;
+		future.await
+	}
+
+	/// Wait for the turn of given race.
+	async fn turn(&self, race: &str) -> Turn {
+		let receiver = {
+			let mut state = self.state.lock();
+			if !state.is_busy {
+				state.is_busy = true;
+				state.latest = Some(race.into());
+				return Turn { state: Some(self.state.clone()) }
+			}
+
+			let (sender, receiver) = oneshot::channel();
+			state.waiting.push_back((race.into(), sender));
+			receiver
+		};
+		receiver
+			.await
+			.expect("waiting races are only dropped after getting the turn; qed")
+	}
+}
+
+/// Turn of the race. The turn is passed to the next waiting race when dropped.
+#[derive(Debug)]
+struct Turn {
+	state: Option<Arc<Mutex<SerializerState>>>,
+}
+
+impl Drop for Turn {
+	fn drop(&mut self) {
+		let shared_state = match self.state.take() {
+			Some(shared_state) => shared_state,
+			None => return,
+		};
+
+		let mut state = shared_state.lock();
+		loop {
+			// the other race goes first, if it is waiting
+			let next = state
+				.waiting
+				.iter()
+				.position(|(race, _)| Some(race) != state.latest.as_ref())
+				.or_else(|| (!state.waiting.is_empty()).then_some(0));
+			let (race, sender) = match next.and_then(|next| state.waiting.remove(next)) {
+				Some(next) => next,
+				None => {
+					state.is_busy = false;
+					return
+				},
+			};
+
+			match sender.send(Turn { state: Some(shared_state.clone()) }) {
+				Ok(()) => {
+					state.latest = Some(race);
+					return
+				},
+				Err(mut turn) => {
+					// the race has stopped waiting
+					turn.state = None;
+				},
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::FutureExt;
+
+	async fn race(
+		serializer: RaceSerializer,
+		order: Arc<Mutex<Vec<&'static str>>>,
+		name: &'static str,
+		steps: usize,
+	) {
+		for _ in 0..steps {
+			serializer
+				.run(name, async {
+					order.lock().push(name);
+					async_std::task::yield_now().await;
+				})
+				.await;
+		}
+	}
+
+	#[async_std::test]
+	async fn serialized_races_take_turns() {
+		let serializer = RaceSerializer::default();
+		let order = Arc::new(Mutex::new(Vec::new()));
+		futures::join!(
+			race(serializer.clone(), order.clone(), "delivery", 3),
+			race(serializer.clone(), order.clone(), "confirmation", 2),
+		);
+
+		// races alternate while both are waiting
+		assert_eq!(
+			*order.lock(),
+			vec!["delivery", "confirmation", "delivery", "confirmation", "delivery"],
+		);
+		assert!(!serializer.state.lock().is_busy);
+	}
+
+	#[async_std::test]
+	async fn turn_is_passed_over_races_that_have_stopped_waiting() {
+		let serializer = RaceSerializer::default();
+		let delivery_turn = serializer.turn("delivery").await;
+
+		// confirmation race stops waiting for its turn
+		let mut confirmation_turn = serializer.turn("confirmation").boxed();
+		assert!((&mut confirmation_turn).now_or_never().is_none());
+		drop(confirmation_turn);
+
+		// the turn is passed to the next waiting race
+		let mut next_delivery_turn = serializer.turn("delivery").boxed();
+		assert!((&mut next_delivery_turn).now_or_never().is_none());
+		drop(delivery_turn);
+		let next_delivery_turn = next_delivery_turn.now_or_never();
+		assert!(next_delivery_turn.is_some());
+		assert!(serializer.state.lock().waiting.is_empty());
+
+		drop(next_delivery_turn);
+		assert!(!serializer.state.lock().is_busy);
+	}
+}
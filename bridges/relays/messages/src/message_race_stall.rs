@@ -0,0 +1,160 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tracking of the lane stall, caused by the message that can't be delivered.
+//!
+//! Messages are delivered in nonce order, so the message that can't be delivered blocks all
+//! following messages. Instead of halting the relay, the delivery race may hold back the lane
+//! and periodically check whether the message may be delivered again. The lane recovers once
+//! the message passes the checks or once it is delivered by someone else.
+
+use bp_messages::MessageNonce;
+use parking_lot::Mutex;
+use std::{
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+/// Stall of the lane.
+#[derive(Debug)]
+struct Stall {
+	/// Nonce of the message that blocks the lane.
+	nonce: MessageNonce,
+	/// When the blocking message is checked again.
+	next_probe_at: Instant,
+}
+
+/// Lane stall tracker, shared by all clones.
+#[derive(Clone, Debug)]
+pub(crate) struct StallTracker {
+	/// Interval between two consecutive checks of the blocking message.
+	reprobe_interval: Duration,
+	/// Current stall of the lane, if any.
+	stall: Arc<Mutex<Option<Stall>>>,
+}
+
+impl StallTracker {
+	/// Create tracker that checks the blocking message with given interval.
+	pub fn new(reprobe_interval: Duration) -> Self {
+		StallTracker { reprobe_interval, stall: Default::default() }
+	}
+
+	/// Returns true if messages, starting from given nonce, may be checked now.
+	///
+	/// If the blocking message has already been delivered, the stall is over.
+	pub fn is_probe_allowed(&self, lane: &str, first_nonce: MessageNonce) -> bool {
+		let mut stall = self.stall.lock();
+		match *stall {
+			Some(ref current) if current.nonce < first_nonce => {
+				log::info!(
+					target: "bridge",
+					"{} lane has recovered: blocking message {} has been delivered",
+					lane,
+					current.nonce,
+				);
+				*stall = None;
+				true
+			},
+			Some(ref current) => Instant::now() >= current.next_probe_at,
+			None => true,
+		}
+	}
+
+	/// Note that the message with given nonce blocks the lane.
+	///
+	/// Returns true if the stall has just started.
+	pub fn note_blocked(
+		&self,
+		lane: &str,
+		nonce: MessageNonce,
+		reason: &str,
+		suggested_action: &str,
+	) -> bool {
+		let mut stall = self.stall.lock();
+		let next_probe_at = Instant::now() + self.reprobe_interval;
+		match *stall {
+			Some(ref mut current) if current.nonce == nonce => {
+				log::debug!(
+					target: "bridge",
+					"{} lane is still blocked by message {}: {}",
+					lane,
+					nonce,
+					reason,
+				);
+				current.next_probe_at = next_probe_at;
+				false
+			},
+			_ => {
+				log::error!(
+					target: "bridge",
+					"{} lane is stalled at message {}: {}. Suggested action: {}. The message is \
+					checked again every {}s and delivery is resumed once it may be delivered",
+					lane,
+					nonce,
+					reason,
+					suggested_action,
+					self.reprobe_interval.as_secs(),
+				);
+				*stall = Some(Stall { nonce, next_probe_at });
+				true
+			},
+		}
+	}
+
+	/// Note that messages, starting from the blocking message, may be delivered again.
+	pub fn note_unblocked(&self, lane: &str) {
+		if let Some(stall) = self.stall.lock().take() {
+			log::info!(
+				target: "bridge",
+				"{} lane has recovered: message {} may be delivered now",
+				lane,
+				stall.nonce,
+			);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn lane_recovers_when_blocking_message_is_unblocked_or_delivered() {
+		let tracker = StallTracker::new(Duration::from_secs(3600));
+		assert!(tracker.is_probe_allowed("A -> B", 10));
+
+		// stall starts once and blocking message isn't checked until the reprobe interval
+		assert!(tracker.note_blocked("A -> B", 10, "too heavy", "wait"));
+		assert!(!tracker.note_blocked("A -> B", 10, "too heavy", "wait"));
+		assert!(!tracker.is_probe_allowed("A -> B", 10));
+
+		// the stall is over when the message is delivered by someone else
+		assert!(tracker.is_probe_allowed("A -> B", 11));
+		assert!(tracker.stall.lock().is_none());
+
+		// or when the message may be delivered
+		assert!(tracker.note_blocked("A -> B", 11, "too heavy", "wait"));
+		tracker.note_unblocked("A -> B");
+		assert!(tracker.is_probe_allowed("A -> B", 11));
+	}
+
+	#[test]
+	fn blocking_message_is_reprobed_after_interval() {
+		let tracker = StallTracker::new(Duration::ZERO);
+		assert!(tracker.note_blocked("A -> B", 10, "too heavy", "wait"));
+		assert!(tracker.is_probe_allowed("A -> B", 10));
+	}
+}
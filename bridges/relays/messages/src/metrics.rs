@@ -23,9 +23,21 @@ use crate::{
 
 use bp_messages::MessageNonce;
 use finality_relay::SyncLoopMetrics;
-use relay_utils::metrics::{
-	metric_name, register, GaugeVec, Metric, Opts, PrometheusError, Registry, U64,
+use parking_lot::Mutex;
+use relay_utils::{
+	metrics::{
+		metric_name, register, Counter, GaugeVec, Metric, Opts, PrometheusError, Registry, U64,
+	},
+	UniqueSaturatedInto,
 };
+use std::{
+	collections::VecDeque,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+/// Window, within which the recent maximal finality lag is computed.
+const FINALITY_LAG_WINDOW: Duration = Duration::from_secs(600);
 
 /// Message lane relay metrics.
 ///
@@ -39,6 +51,18 @@ pub struct MessageLaneLoopMetrics {
 	/// Lane state nonces: "source_latest_generated", "source_latest_confirmed",
 	/// "target_latest_received", "target_latest_confirmed".
 	lane_state_nonces: GaugeVec<U64>,
+	/// Number of messages that haven't been delivered, because their declared dispatch weight
+	/// exceeds the maximal acceptable message dispatch weight.
+	messages_skipped_for_weight: Counter<U64>,
+	/// Number of times the lane has been stalled by the message that can't be delivered.
+	lane_stalls: Counter<U64>,
+	/// Number of blocks between the best and the best finalized block of the chain:
+	/// "current" and "recent_max".
+	finality_lag_blocks: GaugeVec<U64>,
+	/// Recent finality lag samples of the source chain.
+	source_finality_lag: Arc<Mutex<FinalityLagSamples>>,
+	/// Recent finality lag samples of the target chain.
+	target_finality_lag: Arc<Mutex<FinalityLagSamples>>,
 }
 
 impl MessageLaneLoopMetrics {
@@ -59,11 +83,35 @@ impl MessageLaneLoopMetrics {
 				Opts::new(metric_name(prefix, "lane_state_nonces"), "Nonces of the lane state"),
 				&["type"],
 			)?,
+			messages_skipped_for_weight: Counter::new(
+				metric_name(prefix, "messages_skipped_for_weight"),
+				"Number of messages, that haven't been delivered because their declared dispatch \
+				weight exceeds the maximal acceptable message dispatch weight",
+			)?,
+			lane_stalls: Counter::new(
+				metric_name(prefix, "lane_stalls"),
+				"Number of times the lane has been stalled by the message that can't be delivered",
+			)?,
+			finality_lag_blocks: GaugeVec::new(
+				Opts::new(
+					metric_name(prefix, "finality_lag_blocks"),
+					"Number of blocks between the best and the best finalized block of the chain",
+				),
+				&["chain", "type"],
+			)?,
+			source_finality_lag: Default::default(),
+			target_finality_lag: Default::default(),
 		})
 	}
 
 	/// Update source client state metrics.
 	pub fn update_source_state<P: MessageLane>(&self, source_client_state: SourceClientState<P>) {
+		self.update_finality_lag(
+			P::SOURCE_NAME,
+			&self.source_finality_lag,
+			source_client_state.best_self.0.unique_saturated_into(),
+			source_client_state.best_finalized_self.0.unique_saturated_into(),
+		);
 		self.source_to_target_finality_metrics
 			.update_best_block_at_source(source_client_state.best_self.0);
 		if let Some(best_finalized_peer_at_best_self) =
@@ -83,6 +131,12 @@ impl MessageLaneLoopMetrics {
 
 	/// Update target client state metrics.
 	pub fn update_target_state<P: MessageLane>(&self, target_client_state: TargetClientState<P>) {
+		self.update_finality_lag(
+			P::TARGET_NAME,
+			&self.target_finality_lag,
+			target_client_state.best_self.0.unique_saturated_into(),
+			target_client_state.best_finalized_self.0.unique_saturated_into(),
+		);
 		self.target_to_source_finality_metrics
 			.update_best_block_at_source(target_client_state.best_self.0);
 		if let Some(best_finalized_peer_at_best_self) =
@@ -136,6 +190,53 @@ impl MessageLaneLoopMetrics {
 			.with_label_values(&["target_latest_confirmed"])
 			.set(target_latest_confirmed_nonce);
 	}
+
+	/// Note that message hasn't been delivered, because its declared dispatch weight is too
+	/// large.
+	pub fn note_message_skipped_for_weight(&self) {
+		self.messages_skipped_for_weight.inc();
+	}
+
+	/// Note that the lane has been stalled by the message that can't be delivered.
+	pub fn note_lane_stall(&self) {
+		self.lane_stalls.inc();
+	}
+
+	/// Update finality lag of the chain.
+	fn update_finality_lag(
+		&self,
+		chain: &str,
+		samples: &Mutex<FinalityLagSamples>,
+		best_number: u64,
+		best_finalized_number: u64,
+	) {
+		let lag = best_number.saturating_sub(best_finalized_number);
+		let recent_max_lag = samples.lock().push(Instant::now(), lag);
+		self.finality_lag_blocks.with_label_values(&[chain, "current"]).set(lag);
+		self.finality_lag_blocks
+			.with_label_values(&[chain, "recent_max"])
+			.set(recent_max_lag);
+	}
+}
+
+/// Finality lag samples of the chain, seen within the `FINALITY_LAG_WINDOW`.
+#[derive(Debug, Default)]
+struct FinalityLagSamples(VecDeque<(Instant, u64)>);
+
+impl FinalityLagSamples {
+	/// Remember the new sample and return the maximal lag within the window.
+	fn push(&mut self, now: Instant, lag: u64) -> u64 {
+		while self
+			.0
+			.front()
+			.map(|(at, _)| now.duration_since(*at) >= FINALITY_LAG_WINDOW)
+			.unwrap_or(false)
+		{
+			self.0.pop_front();
+		}
+		self.0.push_back((now, lag));
+		self.0.iter().map(|(_, lag)| *lag).max().unwrap_or(lag)
+	}
 }
 
 impl Metric for MessageLaneLoopMetrics {
@@ -143,6 +244,26 @@ impl Metric for MessageLaneLoopMetrics {
 		self.source_to_target_finality_metrics.register(registry)?;
 		self.target_to_source_finality_metrics.register(registry)?;
 		register(self.lane_state_nonces.clone(), registry)?;
+		register(self.messages_skipped_for_weight.clone(), registry)?;
+		register(self.lane_stalls.clone(), registry)?;
+		register(self.finality_lag_blocks.clone(), registry)?;
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn recent_max_finality_lag_is_computed_within_window() {
+		let mut samples = FinalityLagSamples::default();
+		let start = Instant::now();
+
+		assert_eq!(samples.push(start, 2), 2);
+		assert_eq!(samples.push(start + Duration::from_secs(60), 10), 10);
+		assert_eq!(samples.push(start + Duration::from_secs(120), 1), 10);
+		// the largest lag leaves the window
+		assert_eq!(samples.push(start + FINALITY_LAG_WINDOW + Duration::from_secs(60), 3), 3);
+	}
+}
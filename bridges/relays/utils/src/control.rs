@@ -0,0 +1,554 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Runtime control of running relays.
+//!
+//! The relay may be paused and resumed without restart. While relay is paused, it keeps its
+//! connections and tracked state, but it doesn't submit any transactions. The control may be
+//! exposed over the Unix socket that accepts line-delimited `pause`, `resume` and `status`
+//! commands.
+//!
+//! Relay loops may also halt the relay when they detect a condition that can't be resolved
+//! without operator intervention. Halted relay loops exit with the halt reason.
+//!
+//! Submission of transactions to the target chain may also be restricted to the set of target
+//! runtime spec versions that the operator has checked. The set may be updated using the
+//! `allow-target-spec-versions` command.
+//!
+//! The `stats` command returns the rolling history of the relay activity as a single-line JSON
+//! document, if the history is tracked.
+//!
+//! The control also keeps the most recent error of every relay race, so that it may be shown
+//! on the relay dashboard.
+
+use crate::run_summary::RunSummary;
+
+use async_std::{
+	io::{prelude::BufReadExt, BufReader, WriteExt},
+	os::unix::net::{UnixListener, UnixStream},
+	stream::StreamExt,
+};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt::{Display, Formatter},
+	ops::RangeInclusive,
+	path::{Path, PathBuf},
+	str::FromStr,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Set of runtime spec versions, e.g. `1000,1002-1005`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpecVersions(Vec<RangeInclusive<u32>>);
+
+impl SpecVersions {
+	/// Returns true if given spec version belongs to the set.
+	pub fn contains(&self, spec_version: u32) -> bool {
+		self.0.iter().any(|range| range.contains(&spec_version))
+	}
+}
+
+impl FromStr for SpecVersions {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let parse_version = |version: &str| {
+			version
+				.trim()
+				.parse::<u32>()
+				.map_err(|e| format!("Invalid spec version '{version}': {e}"))
+		};
+		let ranges = s
+			.split(',')
+			.map(|range| match range.split_once('-') {
+				Some((start, end)) => {
+					let (start, end) = (parse_version(start)?, parse_version(end)?);
+					if start > end {
+						return Err(format!("Invalid spec versions range '{range}'"))
+					}
+					Ok(start..=end)
+				},
+				None => parse_version(range).map(|version| version..=version),
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+		Ok(SpecVersions(ranges))
+	}
+}
+
+impl Display for SpecVersions {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		let ranges = self
+			.0
+			.iter()
+			.map(|range| {
+				if range.start() == range.end() {
+					range.start().to_string()
+				} else {
+					format!("{}-{}", range.start(), range.end())
+				}
+			})
+			.collect::<Vec<_>>();
+		write!(f, "{}", ranges.join(","))
+	}
+}
+
+/// The most recent error, reported by the relay race.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LastError {
+	/// Unix timestamp (in seconds) of the error.
+	pub at: u64,
+	/// Error description.
+	pub error: String,
+}
+
+/// Shared relay control state.
+#[derive(Clone, Debug, Default)]
+pub struct RelayControl {
+	paused: Arc<AtomicBool>,
+	halt_reason: Arc<Mutex<Option<String>>>,
+	last_submitted_nonces: Arc<Mutex<BTreeMap<String, u64>>>,
+	last_errors: Arc<Mutex<BTreeMap<String, LastError>>>,
+	submissions_in_progress: Arc<Mutex<BTreeSet<String>>>,
+	races_ready: Arc<Mutex<BTreeMap<String, bool>>>,
+	allowed_target_spec_versions: Arc<Mutex<Option<SpecVersions>>>,
+	summary: RunSummary,
+}
+
+impl RelayControl {
+	/// Returns true if relay is paused and shall not submit any transactions.
+	pub fn is_paused(&self) -> bool {
+		self.paused.load(Ordering::SeqCst)
+	}
+
+	/// Pause relay.
+	pub fn pause(&self) {
+		self.paused.store(true, Ordering::SeqCst);
+	}
+
+	/// Resume relay.
+	pub fn resume(&self) {
+		self.paused.store(false, Ordering::SeqCst);
+	}
+
+	/// Halt relay. Only the first halt reason is kept.
+	pub fn halt(&self, reason: String) {
+		let mut halt_reason = self.halt_reason.lock();
+		if halt_reason.is_none() {
+			log::error!(target: "bridge", "Relay is halted: {}", reason);
+			*halt_reason = Some(reason);
+		}
+	}
+
+	/// Returns the reason of relay halt, if it has been halted.
+	pub fn halt_reason(&self) -> Option<String> {
+		self.halt_reason.lock().clone()
+	}
+
+	/// Remember latest nonce that has been submitted by given race.
+	pub fn set_last_submitted_nonce(&self, race: String, nonce: u64) {
+		self.last_submitted_nonces.lock().insert(race, nonce);
+	}
+
+	/// Returns latest nonce that has been submitted by given race.
+	pub fn last_submitted_nonce(&self, race: &str) -> Option<u64> {
+		self.last_submitted_nonces.lock().get(race).cloned()
+	}
+
+	/// Remember the most recent error of given race.
+	pub fn set_last_error(&self, race: String, error: String) {
+		let at = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|since_epoch| since_epoch.as_secs())
+			.unwrap_or_default();
+		self.last_errors.lock().insert(race, LastError { at, error });
+	}
+
+	/// Returns the most recent error of given race.
+	pub fn last_error(&self, race: &str) -> Option<LastError> {
+		self.last_errors.lock().get(race).cloned()
+	}
+
+	/// Remember whether given race is submitting its transaction right now.
+	pub fn set_submission_in_progress(&self, race: String, in_progress: bool) {
+		let mut submissions_in_progress = self.submissions_in_progress.lock();
+		if in_progress {
+			submissions_in_progress.insert(race);
+		} else {
+			submissions_in_progress.remove(&race);
+		}
+	}
+
+	/// Returns true if given race is submitting its transaction right now.
+	pub fn is_submission_in_progress(&self, race: &str) -> bool {
+		self.submissions_in_progress.lock().contains(race)
+	}
+
+	/// Remember whether given race has connected to both nodes and has warmed up.
+	pub fn set_race_ready(&self, race: String, is_ready: bool) {
+		self.races_ready.lock().insert(race, is_ready);
+	}
+
+	/// Returns true if at least one race has been started and all started races are ready.
+	pub fn is_ready(&self) -> bool {
+		let races_ready = self.races_ready.lock();
+		!races_ready.is_empty() && races_ready.values().all(|is_ready| *is_ready)
+	}
+
+	/// Only allow submitting transactions to the target chain if its runtime spec version
+	/// belongs to given set. Any version is allowed if `None` is passed.
+	pub fn set_allowed_target_spec_versions(&self, spec_versions: Option<SpecVersions>) {
+		*self.allowed_target_spec_versions.lock() = spec_versions;
+	}
+
+	/// Returns true if transactions may be submitted to the target chain with given runtime
+	/// spec version.
+	pub fn is_target_spec_version_allowed(&self, spec_version: u32) -> bool {
+		self.allowed_target_spec_versions
+			.lock()
+			.as_ref()
+			.map(|spec_versions| spec_versions.contains(spec_version))
+			.unwrap_or(true)
+	}
+
+	/// Returns summary of the relay run.
+	pub fn summary(&self) -> &RunSummary {
+		&self.summary
+	}
+
+	/// Returns current mode of the relay: `running`, `paused` or `halted`.
+	pub fn mode(&self) -> &'static str {
+		if self.halt_reason().is_some() {
+			"halted"
+		} else if self.is_paused() {
+			"paused"
+		} else {
+			"running"
+		}
+	}
+
+	/// Returns the relay state as a JSON value. The state only includes values that are
+	/// already known to the relay, so it is cheap to build.
+	pub fn to_json(&self) -> Value {
+		let races = self.races_ready.lock().clone();
+		let mut races_json = races
+			.into_iter()
+			.map(|(race, is_ready)| {
+				let race_json = json!({
+					"ready": is_ready,
+					"submission_in_progress": self.is_submission_in_progress(&race),
+					"last_submitted_nonce": self.last_submitted_nonce(&race),
+				});
+				(race, race_json)
+			})
+			.collect::<serde_json::Map<_, _>>();
+		for (race, last_error) in self.last_errors.lock().iter() {
+			let race_json = races_json.entry(race.clone()).or_insert_with(|| json!({}));
+			race_json["last_error"] = json!({ "at": last_error.at, "error": last_error.error });
+		}
+		json!({
+			"mode": self.mode(),
+			"halt_reason": self.halt_reason(),
+			"races": races_json,
+			"summary": self.summary.state_to_json(),
+		})
+	}
+
+	/// Returns single-line human-readable relay status.
+	pub fn status(&self) -> String {
+		let mode = self.mode();
+		let allowed_target_spec_versions = self
+			.allowed_target_spec_versions
+			.lock()
+			.as_ref()
+			.map(|spec_versions| format!("allowed target spec versions: {spec_versions}"));
+		let last_submitted_nonces = self.last_submitted_nonces.lock();
+		std::iter::once(format!("mode: {mode}"))
+			.chain(allowed_target_spec_versions)
+			.chain(
+				last_submitted_nonces
+					.iter()
+					.map(|(race, nonce)| format!("{race}: last submitted nonce {nonce}")),
+			)
+			.collect::<Vec<_>>()
+			.join("; ")
+	}
+
+	/// Process single control command and return the response.
+	pub fn process_command(&self, command: &str) -> String {
+		match command.trim() {
+			"pause" => {
+				self.pause();
+				log::info!(target: "bridge", "Relay has been paused");
+				"ok".into()
+			},
+			"resume" => {
+				self.resume();
+				log::info!(target: "bridge", "Relay has been resumed");
+				"ok".into()
+			},
+			"status" => self.status(),
+			"stats" => match self.summary.history() {
+				Some(history) => history.to_json().to_string(),
+				None => "error: stats history is not tracked".into(),
+			},
+			"allow-target-spec-versions any" => {
+				self.set_allowed_target_spec_versions(None);
+				log::info!(target: "bridge", "Any target spec version is now allowed");
+				"ok".into()
+			},
+			command => match command.strip_prefix("allow-target-spec-versions ") {
+				Some(spec_versions) => match spec_versions.parse::<SpecVersions>() {
+					Ok(spec_versions) => {
+						log::info!(
+							target: "bridge",
+							"Allowed target spec versions are now: {}",
+							spec_versions,
+						);
+						self.set_allowed_target_spec_versions(Some(spec_versions));
+						"ok".into()
+					},
+					Err(e) => format!("error: {e}"),
+				},
+				None => format!(
+					"error: unknown command '{command}'. Supported commands: pause, resume, \
+					status, stats, allow-target-spec-versions <versions|any>"
+				),
+			},
+		}
+	}
+
+	/// Start serving control commands at given Unix socket path.
+	///
+	/// The stale socket file (e.g. left by the previous relay run) is removed. If there's
+	/// other file at given path, it is kept and an error is returned. The socket is only
+	/// accessible by the relay user, because its commands may halt the relay.
+	pub fn serve(&self, path: &Path) -> anyhow::Result<()> {
+		use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+		match std::fs::symlink_metadata(path) {
+			Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)
+				.map_err(|e| {
+					anyhow::format_err!("Failed to remove stale control socket {:?}: {}", path, e)
+				})?,
+			Ok(_) =>
+				return Err(anyhow::format_err!(
+					"Failed to bind control socket {:?}: the file exists and is not a socket",
+					path,
+				)),
+			Err(_) => (),
+		}
+
+		let listener = std::os::unix::net::UnixListener::bind(path)
+			.map_err(|e| anyhow::format_err!("Failed to bind control socket {:?}: {}", path, e))?;
+		std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).map_err(|e| {
+			anyhow::format_err!("Failed to restrict access to control socket {:?}: {}", path, e)
+		})?;
+		let listener = UnixListener::from(listener);
+		log::info!(target: "bridge", "Listening for control commands at {:?}", path);
+
+		let control = self.clone();
+		let path = PathBuf::from(path);
+		async_std::task::spawn(async move {
+			let mut incoming = listener.incoming();
+			while let Some(stream) = incoming.next().await {
+				match stream {
+					Ok(stream) => {
+						async_std::task::spawn(serve_connection(control.clone(), stream));
+					},
+					Err(e) => log::warn!(
+						target: "bridge",
+						"Failed to accept connection at control socket {:?}: {}",
+						path,
+						e,
+					),
+				}
+			}
+		});
+
+		Ok(())
+	}
+}
+
+/// Serve commands from single control socket connection.
+async fn serve_connection(control: RelayControl, stream: UnixStream) {
+	let mut writer = stream.clone();
+	let mut lines = BufReader::new(stream).lines();
+	while let Some(Ok(command)) = lines.next().await {
+		let response = control.process_command(&command);
+		if writer.write_all(format!("{response}\n").as_bytes()).await.is_err() {
+			break
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::stats_history::StatsHistory;
+
+	#[test]
+	fn pause_and_resume_commands_work() {
+		let control = RelayControl::default();
+		assert!(!control.is_paused());
+
+		assert_eq!(control.process_command("pause\n"), "ok");
+		assert!(control.is_paused());
+		assert_eq!(control.process_command("status"), "mode: paused");
+
+		assert_eq!(control.process_command("resume"), "ok");
+		assert!(!control.is_paused());
+		assert_eq!(control.process_command("status"), "mode: running");
+
+		assert!(control.process_command("stop").starts_with("error"));
+	}
+
+	#[test]
+	fn control_socket_is_only_accessible_by_owner() {
+		use std::os::unix::fs::PermissionsExt;
+
+		let path = std::env::temp_dir().join("relay_utils_control_socket_is_only_accessible");
+		let _ = std::fs::remove_file(&path);
+		async_std::task::block_on(async {
+			RelayControl::default().serve(&path).unwrap();
+			// the stale socket is replaced
+			RelayControl::default().serve(&path).unwrap();
+		});
+		let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+		assert_eq!(mode & 0o777, 0o600);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn control_socket_does_not_replace_other_files() {
+		let path = std::env::temp_dir().join("relay_utils_control_socket_does_not_replace");
+		std::fs::write(&path, "data").unwrap();
+		assert!(RelayControl::default().serve(&path).is_err());
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn submissions_in_progress_are_tracked_per_race() {
+		let control = RelayControl::default();
+		control.set_submission_in_progress("A -> B".into(), true);
+		assert!(control.is_submission_in_progress("A -> B"));
+		assert!(!control.is_submission_in_progress("B -> A"));
+
+		control.set_submission_in_progress("A -> B".into(), false);
+		assert!(!control.is_submission_in_progress("A -> B"));
+	}
+
+	#[test]
+	fn stats_command_returns_history() {
+		let control = RelayControl::default();
+		assert_eq!(control.process_command("stats"), "error: stats history is not tracked");
+
+		control
+			.summary()
+			.track_history(StatsHistory::new(std::time::Duration::from_secs(3600)));
+		assert_eq!(control.process_command("stats"), r#"{"bucket_secs":3600,"buckets":[]}"#);
+	}
+
+	#[test]
+	fn relay_is_ready_when_all_races_are_ready() {
+		let control = RelayControl::default();
+		assert!(!control.is_ready());
+
+		control.set_race_ready("A -> B".into(), false);
+		control.set_race_ready("B -> A".into(), true);
+		assert!(!control.is_ready());
+
+		control.set_race_ready("A -> B".into(), true);
+		assert!(control.is_ready());
+
+		// race is restarted after reconnect
+		control.set_race_ready("B -> A".into(), false);
+		assert!(!control.is_ready());
+	}
+
+	#[test]
+	fn first_halt_reason_is_kept() {
+		let control = RelayControl::default();
+		assert_eq!(control.halt_reason(), None);
+
+		control.halt("first".into());
+		control.halt("second".into());
+		assert_eq!(control.halt_reason(), Some("first".into()));
+		assert_eq!(control.status(), "mode: halted");
+	}
+
+	#[test]
+	fn status_includes_last_submitted_nonces() {
+		let control = RelayControl::default();
+		control.set_last_submitted_nonce("A -> B".into(), 10);
+		control.set_last_submitted_nonce("A -> B".into(), 12);
+		control.set_last_submitted_nonce("B -> A".into(), 5);
+		assert_eq!(
+			control.status(),
+			"mode: running; A -> B: last submitted nonce 12; B -> A: last submitted nonce 5",
+		);
+	}
+
+	#[test]
+	fn state_includes_most_recent_race_errors() {
+		let control = RelayControl::default();
+		control.set_race_ready("A -> B".into(), true);
+		control.set_last_error("A -> B".into(), "first".into());
+		control.set_last_error("A -> B".into(), "second".into());
+		control.set_last_error("A_to_B_MessageLane_00000001".into(), "connection lost".into());
+		control.halt("halted".into());
+
+		let state = control.to_json();
+		assert_eq!(state["mode"], "halted");
+		assert_eq!(state["halt_reason"], "halted");
+		assert_eq!(state["races"]["A -> B"]["ready"], true);
+		assert_eq!(state["races"]["A -> B"]["last_error"]["error"], "second");
+		assert_eq!(
+			state["races"]["A_to_B_MessageLane_00000001"]["last_error"]["error"],
+			"connection lost",
+		);
+		assert_eq!(control.last_error("B -> A"), None);
+	}
+
+	#[test]
+	fn allowed_target_spec_versions_are_updated_by_command() {
+		let control = RelayControl::default();
+		assert!(control.is_target_spec_version_allowed(1000));
+
+		assert_eq!(control.process_command("allow-target-spec-versions 1000,1002-1005"), "ok");
+		assert!(control.is_target_spec_version_allowed(1000));
+		assert!(!control.is_target_spec_version_allowed(1001));
+		assert!(control.is_target_spec_version_allowed(1004));
+		assert!(!control.is_target_spec_version_allowed(1006));
+		assert_eq!(control.status(), "mode: running; allowed target spec versions: 1000,1002-1005",);
+
+		assert!(control
+			.process_command("allow-target-spec-versions 1005-1002")
+			.starts_with("error"));
+		assert!(control.process_command("allow-target-spec-versions v1").starts_with("error"));
+		assert!(!control.is_target_spec_version_allowed(1001));
+
+		assert_eq!(control.process_command("allow-target-spec-versions any"), "ok");
+		assert!(control.is_target_spec_version_allowed(1001));
+		assert_eq!(control.status(), "mode: running");
+	}
+}
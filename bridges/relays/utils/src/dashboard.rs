@@ -0,0 +1,234 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Human-readable status page of the relay.
+//!
+//! Small deployments often don't run the Prometheus stack, so the relay may serve the status
+//! page at [`DASHBOARD_PATH`]. The page is self-contained: its embedded script periodically
+//! fetches the relay state from [`DASHBOARD_STATE_PATH`] and renders it. The state is built
+//! from the relay control and the run summary, so requests are never forwarded to nodes.
+
+use crate::control::RelayControl;
+
+use async_std::{
+	io::{prelude::BufReadExt, BufReader, WriteExt},
+	net::{SocketAddr, TcpListener, TcpStream},
+	stream::StreamExt,
+};
+
+/// Path of the dashboard page.
+pub const DASHBOARD_PATH: &str = "/dashboard";
+/// Path of the JSON relay state, rendered by the dashboard page.
+pub const DASHBOARD_STATE_PATH: &str = "/dashboard/state";
+
+/// Maximal number of request header lines that we're reading before responding.
+const MAX_HEADER_LINES: usize = 64;
+
+/// The dashboard page.
+const DASHBOARD_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Bridge relay</title>
+<style>
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: left; }
+th { background: #f0f0f0; }
+.running { color: green; } .paused { color: orange; } .halted, .error { color: red; }
+</style>
+</head>
+<body>
+<h1>Bridge relay: <span id="mode">unknown</span></h1>
+<p id="details"></p>
+<h2>Lanes</h2><table id="lanes"></table>
+<h2>Races</h2><table id="races"></table>
+<h2>Balances</h2><table id="balances"></table>
+<h2>Recent throughput</h2><table id="history"></table>
+<script>
+function cell(tag, text, cls) {
+	const element = document.createElement(tag);
+	element.textContent = text === null || text === undefined ? "-" : String(text);
+	if (cls) { element.className = cls; }
+	return element;
+}
+function table(id, header, rows) {
+	const element = document.getElementById(id);
+	element.replaceChildren();
+	const head = document.createElement("tr");
+	header.forEach(name => head.appendChild(cell("th", name)));
+	element.appendChild(head);
+	rows.forEach(row => {
+		const tr = document.createElement("tr");
+		row.forEach(value => tr.appendChild(
+			value !== null && typeof value === "object" ? cell("td", value.text, value.cls) : cell("td", value)));
+		element.appendChild(tr);
+	});
+}
+function render(state) {
+	const summary = state.summary;
+	const mode = document.getElementById("mode");
+	mode.textContent = state.mode;
+	mode.className = state.mode;
+	document.getElementById("details").textContent = [
+		"uptime: " + summary.uptime_secs + "s",
+		"delivered: " + summary.total_delivered,
+		"confirmed: " + summary.total_confirmed,
+		state.halt_reason ? "halt reason: " + state.halt_reason : null,
+	].filter(item => item !== null).join(", ");
+	table("lanes",
+		["lane", "generated", "received", "confirmed", "delivered", "confirmed by relay", "fees spent"],
+		Object.entries(summary.lanes).map(([name, lane]) => [name,
+			lane.source_latest_generated_nonce, lane.target_latest_received_nonce,
+			lane.source_latest_confirmed_nonce, lane.delivered, lane.confirmed, lane.fees_spent]));
+	table("races", ["race", "ready", "submitting", "last submitted nonce", "last error", "at"],
+		Object.entries(state.races).map(([name, race]) => [name, race.ready,
+			race.submission_in_progress, race.last_submitted_nonce,
+			race.last_error ? { text: race.last_error.error, cls: "error" } : null,
+			race.last_error ? new Date(race.last_error.at * 1000).toISOString() : null]));
+	table("balances", ["account", "balance"], Object.entries(summary.balances));
+	table("history", ["since", "delivered", "confirmed", "fees spent"],
+		(summary.history ? summary.history.buckets : []).slice().reverse().map(bucket => [
+			new Date(bucket.started_at * 1000).toISOString(),
+			bucket.delivered, bucket.confirmed, bucket.fees_spent]));
+}
+async function refresh() {
+	try {
+		const response = await fetch("/dashboard/state");
+		render(await response.json());
+	} catch (e) {
+		const mode = document.getElementById("mode");
+		mode.textContent = "unreachable";
+		mode.className = "halted";
+	}
+}
+refresh();
+setInterval(refresh, 5000);
+</script>
+</body>
+</html>
+"#;
+
+/// Start serving the dashboard at given address.
+pub fn serve(control: RelayControl, address: SocketAddr) -> anyhow::Result<()> {
+	let listener = async_std::task::block_on(TcpListener::bind(address))
+		.map_err(|e| anyhow::format_err!("Failed to bind dashboard at {}: {}", address, e))?;
+	log::info!(target: "bridge", "Serving dashboard at http://{}{}", address, DASHBOARD_PATH);
+
+	async_std::task::spawn(async move {
+		let mut incoming = listener.incoming();
+		while let Some(stream) = incoming.next().await {
+			match stream {
+				Ok(stream) => {
+					async_std::task::spawn(serve_connection(control.clone(), stream));
+				},
+				Err(e) => log::warn!(
+					target: "bridge",
+					"Failed to accept dashboard connection at {}: {}",
+					address,
+					e,
+				),
+			}
+		}
+	});
+
+	Ok(())
+}
+
+/// Serve single request of the dashboard connection.
+async fn serve_connection(control: RelayControl, stream: TcpStream) {
+	let mut writer = stream.clone();
+	let mut lines = BufReader::new(stream).lines();
+	let request_line = match lines.next().await {
+		Some(Ok(request_line)) => request_line,
+		_ => return,
+	};
+	// we don't need request headers, but the client may not read the response until they're
+	// sent
+	let mut header_lines = 0;
+	while let Some(Ok(line)) = lines.next().await {
+		header_lines += 1;
+		if line.is_empty() || header_lines >= MAX_HEADER_LINES {
+			break
+		}
+	}
+
+	let _ = writer.write_all(&respond(&control, &request_line)).await;
+}
+
+/// Returns HTTP response to the request with given request line.
+fn respond(control: &RelayControl, request_line: &str) -> Vec<u8> {
+	let mut parts = request_line.split_whitespace();
+	let (method, path) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+	let path = path.split('?').next().unwrap_or_default();
+	let (status, content_type, body) = match (method, path) {
+		("GET", DASHBOARD_PATH) => ("200 OK", "text/html; charset=utf-8", DASHBOARD_PAGE.into()),
+		("GET", DASHBOARD_STATE_PATH) =>
+			("200 OK", "application/json", control.to_json().to_string()),
+		("GET", _) => ("404 Not Found", "text/plain", "Not found".into()),
+		_ => ("405 Method Not Allowed", "text/plain", "Method not allowed".into()),
+	};
+	format!(
+		"HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+		Cache-Control: no-store\r\nConnection: close\r\n\r\n{body}",
+		body.len(),
+	)
+	.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn response(control: &RelayControl, request_line: &str) -> (String, String) {
+		let response = String::from_utf8(respond(control, request_line)).unwrap();
+		let (head, body) = response.split_once("\r\n\r\n").unwrap();
+		(head.lines().next().unwrap().to_string(), body.to_string())
+	}
+
+	#[test]
+	fn dashboard_is_served_from_relay_state() {
+		let control = RelayControl::default();
+		control.set_race_ready("A -> B".into(), true);
+		control.set_last_submitted_nonce("A -> B".into(), 10);
+		control.set_last_error("A -> B".into(), "transaction has been lost".into());
+		control.summary().lane("A_to_B_MessageLane_00000001".into());
+
+		let (status, body) = response(&control, "GET /dashboard HTTP/1.1");
+		assert_eq!(status, "HTTP/1.1 200 OK");
+		assert!(body.contains(DASHBOARD_STATE_PATH));
+		assert!(!body.contains("http://") && !body.contains("https://"));
+
+		let (status, body) = response(&control, "GET /dashboard/state?t=1 HTTP/1.1");
+		assert_eq!(status, "HTTP/1.1 200 OK");
+		let state: serde_json::Value = serde_json::from_str(&body).unwrap();
+		assert_eq!(state["mode"], "running");
+		assert_eq!(state["races"]["A -> B"]["last_submitted_nonce"], 10);
+		assert_eq!(state["races"]["A -> B"]["last_error"]["error"], "transaction has been lost");
+		assert!(state["summary"]["lanes"]["A_to_B_MessageLane_00000001"].is_object());
+	}
+
+	#[test]
+	fn unknown_requests_are_rejected() {
+		let control = RelayControl::default();
+		assert_eq!(response(&control, "GET /metrics HTTP/1.1").0, "HTTP/1.1 404 Not Found");
+		assert_eq!(
+			response(&control, "POST /dashboard HTTP/1.1").0,
+			"HTTP/1.1 405 Method Not Allowed"
+		);
+		assert_eq!(response(&control, "").0, "HTTP/1.1 405 Method Not Allowed");
+	}
+}
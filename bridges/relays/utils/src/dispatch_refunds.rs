@@ -0,0 +1,91 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Accounting of the dispatch weight, that has been declared by message senders, but has not
+//! been spent by message dispatch at the target chain.
+//!
+//! The unspent weight is refunded to the relayer, so the actual cost of the delivery transaction
+//! is lower than the estimated one. Large unspent weight means that senders overestimate the
+//! dispatch weight of their messages.
+
+use crate::metrics::{
+	metric_name, register, CounterVec, Metric, Opts, PrometheusError, Registry, U64,
+};
+
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// Unspent dispatch weight of delivered messages, shared by all clones.
+#[derive(Clone, Debug)]
+pub struct DispatchRefunds {
+	unspent: Arc<Mutex<(u64, u64)>>,
+	unspent_metric: CounterVec<U64>,
+}
+
+impl DispatchRefunds {
+	/// Create new accounting.
+	pub fn new(prefix: Option<&str>) -> Result<Self, PrometheusError> {
+		Ok(DispatchRefunds {
+			unspent: Default::default(),
+			unspent_metric: CounterVec::new(
+				Opts::new(
+					metric_name(prefix, "unspent_dispatch_weight"),
+					"Dispatch weight of delivered messages, which has not been spent by the dispatch",
+				),
+				&["component"],
+			)?,
+		})
+	}
+
+	/// Remember that delivered messages have not spent given dispatch weight components.
+	pub fn note(&self, ref_time: u64, proof_size: u64) {
+		{
+			let mut unspent = self.unspent.lock();
+			unspent.0 = unspent.0.saturating_add(ref_time);
+			unspent.1 = unspent.1.saturating_add(proof_size);
+		}
+		self.unspent_metric.with_label_values(&["ref_time"]).inc_by(ref_time);
+		self.unspent_metric.with_label_values(&["proof_size"]).inc_by(proof_size);
+	}
+
+	/// Returns unspent `ref_time` and `proof_size` components of the dispatch weight.
+	pub fn unspent(&self) -> (u64, u64) {
+		*self.unspent.lock()
+	}
+}
+
+impl Metric for DispatchRefunds {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		register(self.unspent_metric.clone(), registry)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn unspent_weight_is_accumulated() {
+		let refunds = DispatchRefunds::new(None).unwrap();
+		refunds.note(100, 1_000);
+		refunds.clone().note(50, 0);
+
+		assert_eq!(refunds.unspent(), (150, 1_000));
+		assert_eq!(refunds.unspent_metric.with_label_values(&["ref_time"]).get(), 150);
+		assert_eq!(refunds.unspent_metric.with_label_values(&["proof_size"]).get(), 1_000);
+	}
+}
@@ -0,0 +1,119 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Accounting of transaction fees, spent by the relay, by the transaction type.
+//!
+//! Unlike the fee budget, which only limits the spending, the accounting answers where
+//! fees are going: e.g. if confirmation fees dominate, the relay may confirm deliveries less
+//! often.
+
+use crate::metrics::{
+	metric_name, register, CounterVec, Metric, Opts, PrometheusError, Registry, U64,
+};
+
+use parking_lot::Mutex;
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Type of the transaction, submitted by the relay.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TransactionType {
+	/// Messages delivery transaction.
+	MessagesDelivery,
+	/// Messages delivery confirmation transaction.
+	DeliveryConfirmation,
+}
+
+impl TransactionType {
+	/// Returns name of the transaction type, used in metric labels and in the summary.
+	pub fn name(&self) -> &'static str {
+		match *self {
+			TransactionType::MessagesDelivery => "delivery",
+			TransactionType::DeliveryConfirmation => "confirmation",
+		}
+	}
+}
+
+/// Fees of submitted transactions by the transaction type, shared by all clones.
+#[derive(Clone, Debug)]
+pub struct FeeAccounting {
+	spent: Arc<Mutex<BTreeMap<TransactionType, u128>>>,
+	spent_metric: CounterVec<U64>,
+}
+
+impl FeeAccounting {
+	/// Create new accounting.
+	pub fn new(prefix: Option<&str>) -> Result<Self, PrometheusError> {
+		Ok(FeeAccounting {
+			spent: Default::default(),
+			spent_metric: CounterVec::new(
+				Opts::new(
+					metric_name(prefix, "transaction_fees_spent"),
+					"Transaction fees, spent by the relay, by the transaction type",
+				),
+				&["type"],
+			)?,
+		})
+	}
+
+	/// Remember that the fee has been spent on the transaction of given type.
+	pub fn note(&self, transaction_type: TransactionType, fee: u128) {
+		{
+			let mut spent = self.spent.lock();
+			let spent = spent.entry(transaction_type).or_default();
+			*spent = spent.saturating_add(fee);
+		}
+		self.spent_metric
+			.with_label_values(&[transaction_type.name()])
+			.inc_by(fee.try_into().unwrap_or(u64::MAX));
+	}
+
+	/// Returns fees, spent on transactions of every type.
+	pub fn spent(&self) -> BTreeMap<TransactionType, u128> {
+		self.spent.lock().clone()
+	}
+}
+
+impl Metric for FeeAccounting {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		register(self.spent_metric.clone(), registry)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn fees_are_accounted_by_transaction_type() {
+		let accounting = FeeAccounting::new(None).unwrap();
+		accounting.note(TransactionType::MessagesDelivery, 100);
+		accounting.clone().note(TransactionType::DeliveryConfirmation, 10);
+		accounting.note(TransactionType::MessagesDelivery, 50);
+
+		assert_eq!(
+			accounting.spent(),
+			vec![
+				(TransactionType::MessagesDelivery, 150),
+				(TransactionType::DeliveryConfirmation, 10)
+			]
+			.into_iter()
+			.collect(),
+		);
+		assert_eq!(accounting.spent_metric.with_label_values(&["delivery"]).get(), 150);
+		assert_eq!(accounting.spent_metric.with_label_values(&["confirmation"]).get(), 10);
+	}
+}
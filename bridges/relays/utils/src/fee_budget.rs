@@ -0,0 +1,175 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Limit of transaction fees that the relay may spend within a rolling hour.
+
+use crate::metrics::{
+	metric_name, register, Counter, Gauge, Metric, PrometheusError, Registry, U64,
+};
+
+use parking_lot::Mutex;
+use std::{
+	collections::VecDeque,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+/// Duration of the fee budget window.
+const WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Transaction fees budget, shared by all clones.
+///
+/// Default budget is unlimited.
+#[derive(Clone, Debug, Default)]
+pub struct FeeBudget(Option<Arc<FeeBudgetData>>);
+
+#[derive(Debug)]
+struct FeeBudgetData {
+	max_fees_per_hour: u128,
+	/// Fees, spent within the window, ordered by time.
+	spent: Mutex<VecDeque<(Instant, u128)>>,
+	/// All fees, spent since the budget has been created.
+	total_spent: Mutex<u128>,
+	remaining_metric: Gauge<U64>,
+	deferrals_metric: Counter<U64>,
+}
+
+impl FeeBudget {
+	/// Create budget that allows spending at most `max_fees_per_hour` within any hour.
+	pub fn new(max_fees_per_hour: u128, prefix: Option<&str>) -> Result<Self, PrometheusError> {
+		let budget = FeeBudget(Some(Arc::new(FeeBudgetData {
+			max_fees_per_hour,
+			spent: Mutex::new(VecDeque::new()),
+			total_spent: Mutex::new(0),
+			remaining_metric: Gauge::new(
+				metric_name(prefix, "fee_budget_remaining"),
+				"Transaction fees that may be spent within the current hour",
+			)?,
+			deferrals_metric: Counter::new(
+				metric_name(prefix, "fee_budget_deferrals"),
+				"Number of times when transaction submission has been deferred because the \
+				fee budget is exhausted",
+			)?,
+		})));
+		budget.is_exhausted();
+		Ok(budget)
+	}
+
+	/// Returns true if the budget is unlimited.
+	pub fn is_unlimited(&self) -> bool {
+		self.0.is_none()
+	}
+
+	/// Returns true if no more transactions may be submitted within the current hour.
+	pub fn is_exhausted(&self) -> bool {
+		self.is_exhausted_at(Instant::now())
+	}
+
+	/// Remember that the fee has been spent.
+	pub fn spend(&self, fee: u128) {
+		self.spend_at(Instant::now(), fee)
+	}
+
+	/// Returns all fees, spent since the budget has been created. Returns `None` if the budget
+	/// is unlimited, because fees are not tracked then.
+	pub fn total_spent(&self) -> Option<u128> {
+		self.0.as_ref().map(|data| *data.total_spent.lock())
+	}
+
+	/// Remember that the transaction submission has been deferred because budget is exhausted.
+	pub fn note_deferral(&self) {
+		if let Some(ref data) = self.0 {
+			data.deferrals_metric.inc();
+		}
+	}
+
+	fn is_exhausted_at(&self, now: Instant) -> bool {
+		match self.0 {
+			Some(ref data) => data.remaining_at(now) == 0,
+			None => false,
+		}
+	}
+
+	fn spend_at(&self, now: Instant, fee: u128) {
+		if let Some(ref data) = self.0 {
+			data.spent.lock().push_back((now, fee));
+			{
+				let mut total_spent = data.total_spent.lock();
+				*total_spent = total_spent.saturating_add(fee);
+			}
+			data.remaining_at(now);
+		}
+	}
+}
+
+impl FeeBudgetData {
+	/// Forget fees that have left the window and return remaining budget.
+	fn remaining_at(&self, now: Instant) -> u128 {
+		let mut spent = self.spent.lock();
+		while spent.front().map(|(at, _)| now.duration_since(*at) >= WINDOW).unwrap_or(false) {
+			spent.pop_front();
+		}
+		let total_spent = spent.iter().fold(0u128, |total, (_, fee)| total.saturating_add(*fee));
+		let remaining = self.max_fees_per_hour.saturating_sub(total_spent);
+		self.remaining_metric.set(remaining.try_into().unwrap_or(u64::MAX));
+		remaining
+	}
+}
+
+impl Metric for FeeBudget {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		if let Some(ref data) = self.0 {
+			register(data.remaining_metric.clone(), registry)?;
+			register(data.deferrals_metric.clone(), registry)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn unlimited_budget_is_never_exhausted() {
+		let budget = FeeBudget::default();
+		budget.spend(u128::MAX);
+		assert!(!budget.is_exhausted());
+	}
+
+	#[test]
+	fn budget_is_restored_when_window_rolls() {
+		let budget = FeeBudget::new(100, None).unwrap();
+		let start = Instant::now();
+
+		budget.spend_at(start, 60);
+		assert!(!budget.is_exhausted_at(start));
+		budget.spend_at(start + Duration::from_secs(60), 40);
+		assert!(budget.is_exhausted_at(start + Duration::from_secs(60)));
+
+		// first fee has left the window
+		assert!(!budget.is_exhausted_at(start + WINDOW));
+		budget.spend_at(start + WINDOW, 60);
+		assert!(budget.is_exhausted_at(start + WINDOW));
+
+		// second fee has left the window
+		assert!(!budget.is_exhausted_at(start + WINDOW + Duration::from_secs(60)));
+
+		// but all fees are still counted in total
+		assert_eq!(budget.total_spent(), Some(160));
+		assert_eq!(FeeBudget::default().total_spent(), None);
+	}
+}
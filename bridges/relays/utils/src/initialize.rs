@@ -16,8 +16,16 @@
 
 //! Relayer initialization functions.
 
+use log::{LevelFilter, Log, Metadata, Record};
 use parking_lot::Mutex;
-use std::{cell::RefCell, fmt::Display, io::Write};
+use std::{
+	cell::{Cell, RefCell},
+	fmt::Display,
+	io::Write,
+};
+
+/// Prefix of log targets, which level may be overridden by relay loops.
+const BRIDGE_LOG_TARGET: &str = "bridge";
 
 /// Relayer version that is provided as metric. Must be set by a binary
 /// (get it with `option_env!("CARGO_PKG_VERSION")` from a binary package code).
@@ -25,6 +33,7 @@ pub static RELAYER_VERSION: Mutex<Option<String>> = Mutex::new(None);
 
 async_std::task_local! {
 	pub(crate) static LOOP_NAME: RefCell<String> = RefCell::new(String::default());
+	static LOOP_LOG_LEVEL: Cell<Option<LevelFilter>> = Cell::new(None);
 }
 
 /// Initialize relay environment.
@@ -34,6 +43,16 @@ pub fn initialize_relay() {
 
 /// Initialize Relay logger instance.
 pub fn initialize_logger(with_timestamp: bool) {
+	let logger = RelayLogger {
+		default: logger_builder(with_timestamp, false).build(),
+		verbose: logger_builder(with_timestamp, true).build(),
+	};
+	log::set_max_level(logger.default.filter());
+	log::set_boxed_logger(Box::new(logger)).expect("relay logger is only initialized once");
+}
+
+/// Returns builder of the relay logger. The verbose logger accepts all records.
+fn logger_builder(with_timestamp: bool, verbose: bool) -> env_logger::Builder {
 	let format = time::format_description::parse(
 		"[year]-[month]-[day] \
 		[hour repr:24]:[minute]:[second] [offset_hour sign:mandatory]",
@@ -41,9 +60,13 @@ pub fn initialize_logger(with_timestamp: bool) {
 	.expect("static format string is valid");
 
 	let mut builder = env_logger::Builder::new();
-	builder.filter_level(log::LevelFilter::Warn);
-	builder.filter_module("bridge", log::LevelFilter::Info);
-	builder.parse_default_env();
+	if verbose {
+		builder.filter_level(LevelFilter::Trace);
+	} else {
+		builder.filter_level(LevelFilter::Warn);
+		builder.filter_module(BRIDGE_LOG_TARGET, LevelFilter::Info);
+		builder.parse_default_env();
+	}
 	if with_timestamp {
 		builder.format(move |buf, record| {
 			let timestamp = time::OffsetDateTime::now_local()
@@ -77,12 +100,60 @@ pub fn initialize_logger(with_timestamp: bool) {
 		});
 	}
 
-	builder.init();
+	builder
+}
+
+/// Relay logger, which respects log level overrides of relay loops.
+struct RelayLogger {
+	/// Logger, configured by the `RUST_LOG` environment variable.
+	default: env_logger::Logger,
+	/// Logger for records that have passed the loop log level check.
+	verbose: env_logger::Logger,
+}
+
+impl RelayLogger {
+	/// Returns log level override of the current loop, if it applies to given record.
+	fn loop_log_level(&self, metadata: &Metadata) -> Option<LevelFilter> {
+		if !metadata.target().starts_with(BRIDGE_LOG_TARGET) {
+			return None
+		}
+
+		// try_with to avoid panic outside of async-std task context
+		LOOP_LOG_LEVEL.try_with(|log_level| log_level.get()).ok().flatten()
+	}
+}
+
+impl Log for RelayLogger {
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		match self.loop_log_level(metadata) {
+			Some(log_level) => metadata.level() <= log_level,
+			None => self.default.enabled(metadata),
+		}
+	}
+
+	fn log(&self, record: &Record) {
+		match self.loop_log_level(record.metadata()) {
+			Some(log_level) if record.level() <= log_level => self.verbose.log(record),
+			Some(_) => (),
+			None => self.default.log(record),
+		}
+	}
+
+	fn flush(&self) {
+		self.default.flush();
+	}
 }
 
 /// Initialize relay loop. Must only be called once per every loop task.
-pub(crate) fn initialize_loop(loop_name: String) {
+pub(crate) fn initialize_loop(loop_name: String, log_level: Option<LevelFilter>) {
 	LOOP_NAME.with(|g_loop_name| *g_loop_name.borrow_mut() = loop_name);
+	if let Some(log_level) = log_level {
+		LOOP_LOG_LEVEL.with(|g_log_level| g_log_level.set(Some(log_level)));
+		// records above the global max level never reach the logger
+		if log_level > log::max_level() {
+			log::set_max_level(log_level);
+		}
+	}
 }
 
 /// Returns loop name prefix to use in logs. The prefix is initialized with the `initialize_loop`
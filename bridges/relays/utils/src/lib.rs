@@ -44,10 +44,20 @@ pub const MAX_BACKOFF_INTERVAL: Duration = Duration::from_secs(60);
 /// reconnection again.
 pub const CONNECTION_ERROR_DELAY: Duration = Duration::from_secs(10);
 
+pub mod control;
+pub mod dashboard;
+pub mod dispatch_refunds;
 pub mod error;
+pub mod fee_accounting;
+pub mod fee_budget;
 pub mod initialize;
 pub mod metrics;
+pub mod otlp;
 pub mod relay_loop;
+pub mod run_summary;
+pub mod secrets;
+pub mod stats_history;
+pub mod throughput_budget;
 
 /// Block number traits shared by all chains that relay is able to serve.
 pub trait BlockNumberBase:
@@ -120,6 +130,33 @@ macro_rules! bail_on_arg_error {
 pub trait MaybeConnectionError {
 	/// Returns true if error (maybe) represents connection error.
 	fn is_connection_error(&self) -> bool;
+
+	/// Returns kind of the transaction failure, if error means that the transaction has been
+	/// rejected. Returns `None` if the error isn't related to the transaction itself.
+	fn failure_kind(&self) -> Option<FailureKind> {
+		None
+	}
+
+	/// Returns true if error means that the transaction has been rejected, because it would
+	/// exhaust resources (e.g. weight or size limits) of the block.
+	fn exhausts_resources(&self) -> bool {
+		false
+	}
+
+	/// Returns true if error means that the proof generation has taken longer than allowed. The
+	/// proof may become stale before it's submitted, so it is generated again later.
+	fn is_proof_generation_timeout(&self) -> bool {
+		false
+	}
+}
+
+/// Kind of the relay transaction failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+	/// The same transaction may succeed later (e.g. when the transaction pool is full).
+	Transient,
+	/// The same transaction would fail again (e.g. when it is rejected by the runtime).
+	Deterministic,
 }
 
 /// Final status of the tracked transaction.
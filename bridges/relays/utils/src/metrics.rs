@@ -23,7 +23,7 @@ pub use prometheus_endpoint::{
 
 use async_std::sync::{Arc, RwLock};
 use async_trait::async_trait;
-use std::{fmt::Debug, time::Duration};
+use std::{collections::HashMap, fmt::Debug, time::Duration};
 
 mod float_json_value;
 mod global;
@@ -100,14 +100,19 @@ impl Default for MetricsAddress {
 
 impl MetricsParams {
 	/// Creates metrics params from metrics address.
+	///
+	/// The `prefix` and `labels` are applied to all metrics, registered in the registry.
 	pub fn new(
 		address: Option<MetricsAddress>,
 		relay_version: String,
 		relay_commit: String,
+		prefix: Option<String>,
+		labels: HashMap<String, String>,
 	) -> Result<Self, PrometheusError> {
 		const BUILD_INFO_METRIC: &str = "substrate_relay_build_info";
 
-		let registry = Registry::new();
+		let labels = if labels.is_empty() { None } else { Some(labels) };
+		let registry = Registry::new_custom(prefix, labels)?;
 		register(
 			Gauge::<U64>::with_opts(
 				Opts::new(
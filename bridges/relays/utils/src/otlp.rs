@@ -0,0 +1,340 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Minimal OpenTelemetry traces exporter.
+//!
+//! Spans are exported to the OTLP collector using the OTLP/HTTP JSON protocol. Until the
+//! exporter is started with [`start_exporter`], all spans are no-op: they don't allocate and
+//! aren't exported anywhere.
+//!
+//! Span context is propagated explicitly: the [`SpanContext`] of the parent span is passed
+//! to the code that needs to start child spans. The context also carries attributes (like lane
+//! id) that are inherited by all child spans.
+
+use futures::{
+	channel::mpsc::{unbounded, UnboundedSender},
+	StreamExt,
+};
+use parking_lot::Mutex;
+use std::{
+	collections::hash_map::RandomState,
+	hash::{BuildHasher, Hasher},
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Maximal number of spans in single export request.
+const MAX_SPANS_IN_EXPORT_REQUEST: usize = 512;
+
+/// Sender of finished spans to the exporter task. `None` if exporter isn't started.
+static EXPORTER: Mutex<Option<UnboundedSender<FinishedSpan>>> = Mutex::new(None);
+
+/// Returns true if spans are exported.
+pub fn is_enabled() -> bool {
+	EXPORTER.lock().is_some()
+}
+
+/// Start exporting spans to the OTLP collector at given endpoint (e.g. `http://localhost:4318`).
+pub fn start_exporter(endpoint: &str, service_name: &str) -> anyhow::Result<()> {
+	let client = isahc::HttpClient::new()?;
+	let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+	let service_name = service_name.to_owned();
+	let (sender, receiver) = unbounded();
+
+	async_std::task::spawn(async move {
+		use isahc::Request;
+
+		let mut batches = receiver.ready_chunks(MAX_SPANS_IN_EXPORT_REQUEST);
+		while let Some(spans) = batches.next().await {
+			let result = match Request::post(&url)
+				.header("Content-Type", "application/json")
+				.body(encode_export_request(&service_name, &spans).to_string())
+			{
+				Ok(request) => client.send_async(request).await.map_err(anyhow::Error::from),
+				Err(e) => Err(e.into()),
+			};
+			match result {
+				Ok(response) if response.status().is_success() => (),
+				Ok(response) => log::debug!(
+					target: "bridge",
+					"OTLP collector at {} has rejected {} spans: {}",
+					url,
+					spans.len(),
+					response.status(),
+				),
+				Err(e) => log::debug!(
+					target: "bridge",
+					"Failed to export {} spans to OTLP collector at {}: {:?}",
+					spans.len(),
+					url,
+					e,
+				),
+			}
+		}
+	});
+
+	*EXPORTER.lock() = Some(sender);
+	log::info!(target: "bridge", "Exporting traces to OTLP collector at {}", endpoint);
+	Ok(())
+}
+
+/// Context of the span that may be used to start child spans.
+#[derive(Clone, Debug, Default)]
+pub struct SpanContext(Option<Arc<SpanContextData>>);
+
+#[derive(Debug)]
+struct SpanContextData {
+	trace_id: u128,
+	span_id: u64,
+	inherited_attributes: Vec<(&'static str, String)>,
+}
+
+impl SpanContext {
+	/// Returns context that inherits all attributes of this context and additionally passes
+	/// given attribute to child spans.
+	pub fn with_inherited_attribute(
+		&self,
+		key: &'static str,
+		value: impl std::fmt::Display,
+	) -> SpanContext {
+		SpanContext(self.0.as_ref().map(|data| {
+			let mut inherited_attributes = data.inherited_attributes.clone();
+			inherited_attributes.push((key, value.to_string()));
+			Arc::new(SpanContextData {
+				trace_id: data.trace_id,
+				span_id: data.span_id,
+				inherited_attributes,
+			})
+		}))
+	}
+
+	/// Start child span.
+	pub fn child(&self, name: &'static str) -> Span {
+		Span(self.0.as_ref().map(|parent| {
+			Box::new(SpanData {
+				trace_id: parent.trace_id,
+				span_id: random_u64(),
+				parent_span_id: Some(parent.span_id),
+				name,
+				start_time: now_unix_nanos(),
+				attributes: parent.inherited_attributes.clone(),
+				error: None,
+			})
+		}))
+	}
+}
+
+/// Span that is exported when dropped.
+#[derive(Debug)]
+pub struct Span(Option<Box<SpanData>>);
+
+#[derive(Debug)]
+struct SpanData {
+	trace_id: u128,
+	span_id: u64,
+	parent_span_id: Option<u64>,
+	name: &'static str,
+	start_time: u128,
+	attributes: Vec<(&'static str, String)>,
+	error: Option<String>,
+}
+
+impl Span {
+	/// Start new root span. It is a no-op span if exporter isn't started.
+	pub fn root(name: &'static str) -> Span {
+		if !is_enabled() {
+			return Span(None)
+		}
+
+		Span(Some(Box::new(SpanData {
+			trace_id: (random_u64() as u128) << 64 | random_u64() as u128,
+			span_id: random_u64(),
+			parent_span_id: None,
+			name,
+			start_time: now_unix_nanos(),
+			attributes: Vec::new(),
+			error: None,
+		})))
+	}
+
+	/// Returns context of this span.
+	pub fn context(&self) -> SpanContext {
+		SpanContext(self.0.as_ref().map(|data| {
+			Arc::new(SpanContextData {
+				trace_id: data.trace_id,
+				span_id: data.span_id,
+				inherited_attributes: data.attributes.clone(),
+			})
+		}))
+	}
+
+	/// Set span attribute.
+	pub fn set_attribute(&mut self, key: &'static str, value: impl std::fmt::Display) {
+		if let Some(ref mut data) = self.0 {
+			data.attributes.push((key, value.to_string()));
+		}
+	}
+
+	/// Mark span as failed.
+	pub fn set_error(&mut self, error: impl std::fmt::Display) {
+		if let Some(ref mut data) = self.0 {
+			data.error = Some(error.to_string());
+		}
+	}
+}
+
+impl Drop for Span {
+	fn drop(&mut self) {
+		if let Some(data) = self.0.take() {
+			let span = FinishedSpan { data: *data, end_time: now_unix_nanos() };
+			if let Some(exporter) = EXPORTER.lock().as_ref() {
+				let _ = exporter.unbounded_send(span);
+			}
+		}
+	}
+}
+
+/// Span that is ready to be exported.
+struct FinishedSpan {
+	data: SpanData,
+	end_time: u128,
+}
+
+/// Encode OTLP `ExportTraceServiceRequest` in JSON format.
+fn encode_export_request(service_name: &str, spans: &[FinishedSpan]) -> serde_json::Value {
+	let spans = spans
+		.iter()
+		.map(|span| {
+			let mut encoded = serde_json::json!({
+				"traceId": format!("{:032x}", span.data.trace_id),
+				"spanId": format!("{:016x}", span.data.span_id),
+				"name": span.data.name,
+				// SPAN_KIND_INTERNAL
+				"kind": 1,
+				"startTimeUnixNano": span.data.start_time.to_string(),
+				"endTimeUnixNano": span.end_time.to_string(),
+				"attributes": encode_attributes(
+					span.data.attributes.iter().map(|(key, value)| (*key, value.as_str()))
+				),
+				"status": match span.data.error {
+					// STATUS_CODE_OK
+					None => serde_json::json!({ "code": 1 }),
+					// STATUS_CODE_ERROR
+					Some(ref error) => serde_json::json!({ "code": 2, "message": error }),
+				},
+			});
+			if let Some(parent_span_id) = span.data.parent_span_id {
+				encoded["parentSpanId"] = format!("{parent_span_id:016x}").into();
+			}
+			encoded
+		})
+		.collect::<Vec<_>>();
+
+	serde_json::json!({
+		"resourceSpans": [{
+			"resource": {
+				"attributes": encode_attributes(std::iter::once(("service.name", service_name))),
+			},
+			"scopeSpans": [{
+				"scope": { "name": "bridges-relay" },
+				"spans": spans,
+			}],
+		}],
+	})
+}
+
+/// Encode OTLP string attributes in JSON format.
+fn encode_attributes<'a>(
+	attributes: impl Iterator<Item = (&'a str, &'a str)>,
+) -> Vec<serde_json::Value> {
+	attributes
+		.map(|(key, value)| serde_json::json!({ "key": key, "value": { "stringValue": value } }))
+		.collect()
+}
+
+/// Current time in nanoseconds since UNIX epoch.
+fn now_unix_nanos() -> u128 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0)
+}
+
+/// Generate random non-zero identifier.
+fn random_u64() -> u64 {
+	static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+	let mut hasher = RandomState::new().build_hasher();
+	hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+	hasher.finish().max(1)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn spans_are_no_op_when_exporter_is_not_started() {
+		let mut span = Span::root("test");
+		span.set_attribute("lane", "00000000");
+		assert!(span.0.is_none());
+		assert!(span.context().child("child").0.is_none());
+	}
+
+	#[test]
+	fn child_spans_inherit_context() {
+		let root = Span(Some(Box::new(SpanData {
+			trace_id: 42,
+			span_id: 1,
+			parent_span_id: None,
+			name: "root",
+			start_time: 0,
+			attributes: vec![("lane", "00000000".into())],
+			error: None,
+		})));
+		let child = root.context().with_inherited_attribute("race", "delivery").child("child");
+		let child = child.0.as_ref().unwrap();
+		assert_eq!(child.trace_id, 42);
+		assert_eq!(child.parent_span_id, Some(1));
+		assert_eq!(
+			child.attributes,
+			vec![("lane", "00000000".into()), ("race", "delivery".into())],
+		);
+	}
+
+	#[test]
+	fn export_request_is_encoded() {
+		let span = FinishedSpan {
+			data: SpanData {
+				trace_id: 1,
+				span_id: 2,
+				parent_span_id: Some(3),
+				name: "submit_proof",
+				start_time: 10,
+				attributes: vec![("nonces", "1..=5".into())],
+				error: Some("lost".into()),
+			},
+			end_time: 20,
+		};
+		let request = encode_export_request("relay", &[span]);
+		let encoded_span = &request["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+		assert_eq!(encoded_span["traceId"], "00000000000000000000000000000001");
+		assert_eq!(encoded_span["parentSpanId"], "0000000000000003");
+		assert_eq!(encoded_span["endTimeUnixNano"], "20");
+		assert_eq!(encoded_span["attributes"][0]["value"]["stringValue"], "1..=5");
+		assert_eq!(encoded_span["status"]["code"], 2);
+	}
+}
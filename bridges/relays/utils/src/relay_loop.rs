@@ -21,6 +21,7 @@ use crate::{
 };
 
 use async_trait::async_trait;
+use log::LevelFilter;
 use prometheus_endpoint::{init_prometheus, Registry};
 use std::{fmt::Debug, future::Future, net::SocketAddr, time::Duration};
 
@@ -67,7 +68,13 @@ impl Client for () {
 
 /// Returns generic loop that may be customized and started.
 pub fn relay_loop<SC, TC>(source_client: SC, target_client: TC) -> Loop<SC, TC, ()> {
-	Loop { reconnect_delay: RECONNECT_DELAY, source_client, target_client, loop_metric: None }
+	Loop {
+		reconnect_delay: RECONNECT_DELAY,
+		log_level: None,
+		source_client,
+		target_client,
+		loop_metric: None,
+	}
 }
 
 /// Returns generic relay loop metrics that may be customized and used in one or several relay
@@ -76,6 +83,7 @@ pub fn relay_metrics(params: MetricsParams) -> LoopMetrics<(), (), ()> {
 	LoopMetrics {
 		relay_loop: Loop {
 			reconnect_delay: RECONNECT_DELAY,
+			log_level: None,
 			source_client: (),
 			target_client: (),
 			loop_metric: None,
@@ -89,6 +97,7 @@ pub fn relay_metrics(params: MetricsParams) -> LoopMetrics<(), (), ()> {
 /// Generic relay loop.
 pub struct Loop<SC, TC, LM> {
 	reconnect_delay: Duration,
+	log_level: Option<LevelFilter>,
 	source_client: SC,
 	target_client: TC,
 	loop_metric: Option<LM>,
@@ -110,11 +119,20 @@ impl<SC, TC, LM> Loop<SC, TC, LM> {
 		self
 	}
 
+	/// Override level of `bridge` logs, emitted by the loop. If `None`, the level of the relay
+	/// logger is used.
+	#[must_use]
+	pub fn log_level(mut self, log_level: Option<LevelFilter>) -> Self {
+		self.log_level = log_level;
+		self
+	}
+
 	/// Start building loop metrics using given prefix.
 	pub fn with_metrics(self, params: MetricsParams) -> LoopMetrics<SC, TC, ()> {
 		LoopMetrics {
 			relay_loop: Loop {
 				reconnect_delay: self.reconnect_delay,
+				log_level: self.log_level,
 				source_client: self.source_client,
 				target_client: self.target_client,
 				loop_metric: None,
@@ -139,7 +157,7 @@ impl<SC, TC, LM> Loop<SC, TC, LM> {
 		LM: 'static + Send + Clone,
 	{
 		let run_loop_task = async move {
-			crate::initialize::initialize_loop(loop_name);
+			crate::initialize::initialize_loop(loop_name, self.log_level);
 
 			loop {
 				let loop_metric = self.loop_metric.clone();
@@ -238,6 +256,7 @@ impl<SC, TC, LM> LoopMetrics<SC, TC, LM> {
 
 		Ok(Loop {
 			reconnect_delay: self.relay_loop.reconnect_delay,
+			log_level: self.relay_loop.log_level,
 			source_client: self.relay_loop.source_client,
 			target_client: self.relay_loop.target_client,
 			loop_metric: self.loop_metric,
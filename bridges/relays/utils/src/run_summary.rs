@@ -0,0 +1,452 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Summary of the relay run, that may be written to the file when the relay exits.
+//!
+//! The summary is a single JSON document with the exit reason, relay uptime and the state of
+//! every lane, serviced by the relay. It is meant to be read by CI and other automation tools.
+//! The same state (without the exit reason) is shown on the relay dashboard while the relay is
+//! running.
+
+use crate::{
+	dispatch_refunds::DispatchRefunds,
+	fee_accounting::FeeAccounting,
+	fee_budget::FeeBudget,
+	metrics::F64SharedRef,
+	stats_history::{StatsHistory, StatsSample},
+};
+
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::{
+	collections::BTreeMap,
+	path::Path,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+/// Summary of the relay run, shared by all clones.
+#[derive(Clone, Debug)]
+pub struct RunSummary {
+	started_at: Instant,
+	lanes: Arc<Mutex<BTreeMap<String, LaneSummary>>>,
+	history: Arc<Mutex<Option<StatsHistory>>>,
+	balances: Arc<Mutex<BTreeMap<String, F64SharedRef>>>,
+}
+
+impl Default for RunSummary {
+	fn default() -> Self {
+		RunSummary {
+			started_at: Instant::now(),
+			lanes: Default::default(),
+			history: Default::default(),
+			balances: Default::default(),
+		}
+	}
+}
+
+impl RunSummary {
+	/// Returns summary of the lane with given name. The lane is added to the run summary
+	/// when it is requested for the first time.
+	pub fn lane(&self, name: String) -> LaneSummary {
+		self.lanes.lock().entry(name).or_default().clone()
+	}
+
+	/// Returns total activity of all lanes since the relay has been started.
+	///
+	/// Fees of lanes that don't track them are not included.
+	pub fn sample(&self) -> StatsSample {
+		let lanes = self.lanes.lock();
+		StatsSample {
+			delivered: lanes.values().map(|lane| lane.delivered()).sum(),
+			confirmed: lanes.values().map(|lane| lane.confirmed()).sum(),
+			fees_spent: lanes
+				.values()
+				.filter_map(|lane| lane.fees_spent())
+				.fold(0u128, |total, fees| total.saturating_add(fees)),
+		}
+	}
+
+	/// Keep the rolling history of the relay activity, starting with given history.
+	pub fn track_history(&self, history: StatsHistory) {
+		*self.history.lock() = Some(history);
+	}
+
+	/// Add activity that has happened at given Unix timestamp (in seconds) to the history, if
+	/// it is tracked.
+	pub fn record_history(&self, now: u64, stats: StatsSample) {
+		if let Some(ref mut history) = *self.history.lock() {
+			history.record(now, stats);
+		}
+	}
+
+	/// Returns the rolling history of the relay activity, if it is tracked.
+	pub fn history(&self) -> Option<StatsHistory> {
+		self.history.lock().clone()
+	}
+
+	/// Report balance of the relay account with given name. The balance is updated by its
+	/// metric.
+	pub fn track_balance(&self, name: String, balance: F64SharedRef) {
+		self.balances.lock().insert(name, balance);
+	}
+
+	/// Returns the summary as a JSON value.
+	pub fn to_json(&self, exit_reason: &str) -> Value {
+		let mut summary = self.state_to_json();
+		summary["exit_reason"] = exit_reason.into();
+		summary
+	}
+
+	/// Returns the summary of the running relay as a JSON value.
+	///
+	/// Balances that are being updated right now are reported as missing.
+	pub fn state_to_json(&self) -> Value {
+		let lanes = self.lanes.lock();
+		let lanes_json = lanes
+			.iter()
+			.map(|(name, lane)| (name.clone(), lane.to_json()))
+			.collect::<serde_json::Map<_, _>>();
+		let total_fees_spent = lanes
+			.values()
+			.map(|lane| lane.fees_spent())
+			.try_fold(0u128, |total, fees| fees.map(|fees| total.saturating_add(fees)));
+		let mut total_fees_spent_by_transaction_type = BTreeMap::<&str, u128>::new();
+		for lane in lanes.values() {
+			for (transaction_type, fees) in
+				lane.fees_spent_by_transaction_type().unwrap_or_default()
+			{
+				let total =
+					total_fees_spent_by_transaction_type.entry(transaction_type).or_default();
+				*total = total.saturating_add(fees);
+			}
+		}
+		let balances_json = self
+			.balances
+			.lock()
+			.iter()
+			.map(|(name, balance)| {
+				(name.clone(), balance.try_read().and_then(|balance| *balance).into())
+			})
+			.collect::<serde_json::Map<_, _>>();
+		json!({
+			"uptime_secs": self.started_at.elapsed().as_secs(),
+			"total_delivered": lanes.values().map(|lane| lane.delivered()).sum::<u64>(),
+			"total_confirmed": lanes.values().map(|lane| lane.confirmed()).sum::<u64>(),
+			"total_fees_spent": total_fees_spent.map(|fees| fees.to_string()),
+			"total_fees_spent_by_transaction_type": fees_by_transaction_type_json(
+				total_fees_spent_by_transaction_type
+			),
+			"lanes": lanes_json,
+			"history": self.history().map(|history| history.to_json()),
+			"balances": balances_json,
+		})
+	}
+
+	/// Write the summary to the file at given path.
+	pub fn write(&self, path: &Path, exit_reason: &str) -> anyhow::Result<()> {
+		let summary = serde_json::to_string_pretty(&self.to_json(exit_reason))?;
+		std::fs::write(path, summary).map_err(|e| {
+			anyhow::format_err!("Failed to write exit summary to {:?}: {}", path, e)
+		})?;
+		log::info!(target: "bridge", "Exit summary has been written to {:?}", path);
+		Ok(())
+	}
+}
+
+/// Summary of single lane, shared by all clones.
+#[derive(Clone, Debug, Default)]
+pub struct LaneSummary(Arc<Mutex<LaneSummaryData>>);
+
+#[derive(Debug, Default)]
+struct LaneSummaryData {
+	initial_target_latest_received_nonce: Option<u64>,
+	initial_source_latest_confirmed_nonce: Option<u64>,
+	source_latest_generated_nonce: Option<u64>,
+	source_latest_confirmed_nonce: Option<u64>,
+	source_latest_confirmed_nonce_advanced_at: Option<Instant>,
+	target_latest_received_nonce: Option<u64>,
+	target_latest_confirmed_nonce: Option<u64>,
+	fee_budget: FeeBudget,
+	fee_accounting: Option<FeeAccounting>,
+	dispatch_refunds: Option<DispatchRefunds>,
+}
+
+impl LaneSummary {
+	/// Update latest generated nonce at the source chain.
+	pub fn update_source_latest_generated_nonce(&self, nonce: u64) {
+		self.0.lock().source_latest_generated_nonce = Some(nonce);
+	}
+
+	/// Update latest confirmed nonce at the source chain.
+	pub fn update_source_latest_confirmed_nonce(&self, nonce: u64) {
+		let mut data = self.0.lock();
+		data.initial_source_latest_confirmed_nonce.get_or_insert(nonce);
+		if data.source_latest_confirmed_nonce.map_or(true, |latest| nonce > latest) {
+			data.source_latest_confirmed_nonce_advanced_at = Some(Instant::now());
+		}
+		data.source_latest_confirmed_nonce = Some(nonce);
+	}
+
+	/// Returns true if the lane has messages, which delivery is not yet confirmed at the
+	/// source chain, and the latest confirmed nonce hasn't advanced within given window.
+	///
+	/// The lane that has never reported its confirmed nonce is not considered stalled.
+	pub fn is_stalled(&self, window: Duration) -> bool {
+		let data = self.0.lock();
+		let has_unconfirmed_messages =
+			match (data.source_latest_generated_nonce, data.source_latest_confirmed_nonce) {
+				(Some(generated), Some(confirmed)) => generated > confirmed,
+				_ => false,
+			};
+		has_unconfirmed_messages &&
+			data.source_latest_confirmed_nonce_advanced_at
+				.map_or(false, |advanced_at| advanced_at.elapsed() >= window)
+	}
+
+	/// Update latest received nonce at the target chain.
+	pub fn update_target_latest_received_nonce(&self, nonce: u64) {
+		let mut data = self.0.lock();
+		data.initial_target_latest_received_nonce.get_or_insert(nonce);
+		data.target_latest_received_nonce = Some(nonce);
+	}
+
+	/// Update latest confirmed nonce at the target chain.
+	pub fn update_target_latest_confirmed_nonce(&self, nonce: u64) {
+		self.0.lock().target_latest_confirmed_nonce = Some(nonce);
+	}
+
+	/// Use given budget to report fees, spent by the lane relay.
+	pub fn track_fees(&self, fee_budget: FeeBudget) {
+		self.0.lock().fee_budget = fee_budget;
+	}
+
+	/// Use given accounting to report fees, spent by the lane relay on every transaction type.
+	pub fn track_fees_by_transaction_type(&self, fee_accounting: FeeAccounting) {
+		self.0.lock().fee_accounting = Some(fee_accounting);
+	}
+
+	/// Use given accounting to report dispatch weight, that has not been spent by messages,
+	/// delivered by the lane relay.
+	pub fn track_dispatch_refunds(&self, dispatch_refunds: DispatchRefunds) {
+		self.0.lock().dispatch_refunds = Some(dispatch_refunds);
+	}
+
+	/// Number of messages, delivered to the target chain while the relay was running.
+	///
+	/// Messages could be delivered by other relayers too.
+	pub fn delivered(&self) -> u64 {
+		let data = self.0.lock();
+		nonces_difference(
+			data.initial_target_latest_received_nonce,
+			data.target_latest_received_nonce,
+		)
+	}
+
+	/// Number of messages, which delivery has been confirmed at the source chain while
+	/// the relay was running.
+	///
+	/// Deliveries could be confirmed by other relayers too.
+	pub fn confirmed(&self) -> u64 {
+		let data = self.0.lock();
+		nonces_difference(
+			data.initial_source_latest_confirmed_nonce,
+			data.source_latest_confirmed_nonce,
+		)
+	}
+
+	/// Estimated fees of transactions, submitted by the lane relay. Fees are only tracked
+	/// if the lane relay has limited fee budget.
+	pub fn fees_spent(&self) -> Option<u128> {
+		self.0.lock().fee_budget.total_spent()
+	}
+
+	/// Estimated fees of transactions, submitted by the lane relay, by the transaction type.
+	/// Returns `None` if fees are not accounted by the lane relay.
+	pub fn fees_spent_by_transaction_type(&self) -> Option<BTreeMap<&'static str, u128>> {
+		self.0.lock().fee_accounting.as_ref().map(|fee_accounting| {
+			fee_accounting
+				.spent()
+				.into_iter()
+				.map(|(transaction_type, fees)| (transaction_type.name(), fees))
+				.collect()
+		})
+	}
+
+	fn to_json(&self) -> Value {
+		let (delivered, confirmed, fees_spent, fees_spent_by_transaction_type) = (
+			self.delivered(),
+			self.confirmed(),
+			self.fees_spent(),
+			self.fees_spent_by_transaction_type(),
+		);
+		let data = self.0.lock();
+		json!({
+			"delivered": delivered,
+			"confirmed": confirmed,
+			"fees_spent": fees_spent.map(|fees| fees.to_string()),
+			"fees_spent_by_transaction_type":
+				fees_spent_by_transaction_type.map(fees_by_transaction_type_json),
+			"unspent_dispatch_weight": data.dispatch_refunds.as_ref().map(|dispatch_refunds| {
+				let (ref_time, proof_size) = dispatch_refunds.unspent();
+				json!({ "ref_time": ref_time, "proof_size": proof_size })
+			}),
+			"source_latest_generated_nonce": data.source_latest_generated_nonce,
+			"source_latest_confirmed_nonce": data.source_latest_confirmed_nonce,
+			"target_latest_received_nonce": data.target_latest_received_nonce,
+			"target_latest_confirmed_nonce": data.target_latest_confirmed_nonce,
+		})
+	}
+}
+
+/// Fees are serialized as strings, because they may not fit into JSON numbers.
+fn fees_by_transaction_type_json(fees: BTreeMap<&str, u128>) -> Value {
+	fees.into_iter()
+		.map(|(transaction_type, fees)| (transaction_type.to_string(), fees.to_string().into()))
+		.collect::<serde_json::Map<_, _>>()
+		.into()
+}
+
+fn nonces_difference(initial: Option<u64>, latest: Option<u64>) -> u64 {
+	match (initial, latest) {
+		(Some(initial), Some(latest)) => latest.saturating_sub(initial),
+		_ => 0,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::fee_accounting::TransactionType;
+
+	#[test]
+	fn summary_is_serialized() {
+		let summary = RunSummary::default();
+		let lane = summary.lane("A -> B".into());
+		lane.update_source_latest_generated_nonce(20);
+		lane.update_source_latest_confirmed_nonce(5);
+		lane.update_target_latest_received_nonce(10);
+		lane.update_target_latest_confirmed_nonce(5);
+		lane.update_target_latest_received_nonce(15);
+		lane.update_source_latest_confirmed_nonce(8);
+		summary.lane("B -> A".into()).update_target_latest_received_nonce(3);
+
+		let json = summary.to_json("exit signal");
+		assert_eq!(json["exit_reason"], "exit signal");
+		assert_eq!(json["total_delivered"], 5);
+		assert_eq!(json["total_confirmed"], 3);
+		assert_eq!(json["total_fees_spent"], Value::Null);
+		assert_eq!(json["total_fees_spent_by_transaction_type"], json!({}));
+		assert_eq!(
+			json["lanes"]["A -> B"],
+			json!({
+				"delivered": 5,
+				"confirmed": 3,
+				"fees_spent": null,
+				"fees_spent_by_transaction_type": null,
+				"unspent_dispatch_weight": null,
+				"source_latest_generated_nonce": 20,
+				"source_latest_confirmed_nonce": 8,
+				"target_latest_received_nonce": 15,
+				"target_latest_confirmed_nonce": 5,
+			}),
+		);
+		assert_eq!(json["lanes"]["B -> A"]["delivered"], 0);
+	}
+
+	#[test]
+	fn fees_are_reported_if_tracked_by_all_lanes() {
+		let summary = RunSummary::default();
+		let fee_budget = FeeBudget::new(1_000, None).unwrap();
+		fee_budget.spend(100);
+		summary.lane("A -> B".into()).track_fees(fee_budget.clone());
+		assert_eq!(summary.to_json("")["total_fees_spent"], "100");
+		assert_eq!(summary.to_json("")["lanes"]["A -> B"]["fees_spent"], "100");
+
+		summary.lane("B -> A".into());
+		assert_eq!(summary.to_json("")["total_fees_spent"], Value::Null);
+	}
+
+	#[test]
+	fn fees_are_reported_by_transaction_type() {
+		let summary = RunSummary::default();
+		let fee_accounting = FeeAccounting::new(None).unwrap();
+		fee_accounting.note(TransactionType::MessagesDelivery, 100);
+		fee_accounting.note(TransactionType::DeliveryConfirmation, 10);
+		summary.lane("A -> B".into()).track_fees_by_transaction_type(fee_accounting);
+		let fee_accounting = FeeAccounting::new(None).unwrap();
+		fee_accounting.note(TransactionType::MessagesDelivery, 50);
+		summary.lane("B -> A".into()).track_fees_by_transaction_type(fee_accounting);
+		summary.lane("C -> D".into());
+
+		let json = summary.to_json("");
+		assert_eq!(
+			json["lanes"]["A -> B"]["fees_spent_by_transaction_type"],
+			json!({ "delivery": "100", "confirmation": "10" }),
+		);
+		assert_eq!(json["lanes"]["C -> D"]["fees_spent_by_transaction_type"], Value::Null);
+		assert_eq!(
+			json["total_fees_spent_by_transaction_type"],
+			json!({ "delivery": "150", "confirmation": "10" }),
+		);
+	}
+
+	#[test]
+	fn unspent_dispatch_weight_is_reported_if_tracked() {
+		let summary = RunSummary::default();
+		let dispatch_refunds = DispatchRefunds::new(None).unwrap();
+		dispatch_refunds.note(100, 10);
+		summary.lane("A -> B".into()).track_dispatch_refunds(dispatch_refunds);
+		summary.lane("B -> A".into());
+
+		let json = summary.to_json("");
+		assert_eq!(
+			json["lanes"]["A -> B"]["unspent_dispatch_weight"],
+			json!({ "ref_time": 100, "proof_size": 10 }),
+		);
+		assert_eq!(json["lanes"]["B -> A"]["unspent_dispatch_weight"], Value::Null);
+	}
+
+	#[test]
+	fn balances_are_reported_once_known() {
+		let summary = RunSummary::default();
+		let balance = F64SharedRef::new(async_std::sync::RwLock::new(None));
+		summary.track_balance("A relay account".into(), balance.clone());
+		assert_eq!(summary.state_to_json()["balances"], json!({ "A relay account": null }));
+
+		*async_std::task::block_on(balance.write()) = Some(10.5);
+		assert_eq!(summary.state_to_json()["balances"], json!({ "A relay account": 10.5 }));
+		assert_eq!(summary.state_to_json()["exit_reason"], Value::Null);
+		assert_eq!(summary.to_json("exit signal")["exit_reason"], "exit signal");
+	}
+
+	#[test]
+	fn lane_is_stalled_only_if_it_has_unconfirmed_messages() {
+		let lane = LaneSummary::default();
+		assert!(!lane.is_stalled(Duration::ZERO));
+
+		lane.update_source_latest_generated_nonce(10);
+		lane.update_source_latest_confirmed_nonce(10);
+		assert!(!lane.is_stalled(Duration::ZERO));
+
+		lane.update_source_latest_generated_nonce(12);
+		assert!(lane.is_stalled(Duration::ZERO));
+		assert!(!lane.is_stalled(Duration::from_secs(3600)));
+
+		lane.update_source_latest_confirmed_nonce(12);
+		assert!(!lane.is_stalled(Duration::ZERO));
+	}
+}
@@ -0,0 +1,340 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Resolving CLI values (connection URIs, signer SURIs and passwords) from secret stores.
+//!
+//! A value that looks like `<scheme>://<location>` is treated as a reference to the secret,
+//! stored in the external secret store. Currently supported schemes are:
+//!
+//! - `file://<path>` - the secret is the content of the file at given path;
+//! - `env://<name>` - the secret is the value of the environment variable with given name;
+//! - `vault://<path>#<key>` - the secret is the `<key>` field of the HashiCorp Vault secret at
+//!   `<path>`. The Vault address is read from the `VAULT_ADDR` environment variable and the access
+//!   token is read from the `VAULT_TOKEN` environment variable.
+//!
+//! All other values are returned as-is.
+
+use async_trait::async_trait;
+use std::{path::PathBuf, time::Duration};
+
+/// Environment variable with the HashiCorp Vault server address.
+pub const VAULT_ADDR_ENV: &str = "VAULT_ADDR";
+/// Environment variable with the HashiCorp Vault access token.
+pub const VAULT_TOKEN_ENV: &str = "VAULT_TOKEN";
+/// Default HashiCorp Vault server address.
+pub const DEFAULT_VAULT_ADDR: &str = "http://127.0.0.1:8200";
+/// Timeout of the HashiCorp Vault request.
+pub const VAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Printed instead of the redacted secret.
+pub const REDACTED_SECRET: &str = "<redacted>";
+
+/// Secret store that is able to resolve secrets, referenced with given URI scheme.
+#[async_trait]
+pub trait SecretProvider: Send + Sync {
+	/// URI scheme (without `://` suffix) of secrets, that are served by this provider.
+	fn scheme(&self) -> &'static str;
+	/// Fetch secret at given location. The location is the rest of the secret URI,
+	/// following the `<scheme>://` prefix.
+	async fn fetch(&self, location: &str) -> anyhow::Result<String>;
+}
+
+/// Provider that reads secrets from files.
+pub struct FileSecretProvider;
+
+#[async_trait]
+impl SecretProvider for FileSecretProvider {
+	fn scheme(&self) -> &'static str {
+		"file"
+	}
+
+	async fn fetch(&self, location: &str) -> anyhow::Result<String> {
+		let path = PathBuf::from(location);
+		std::fs::read_to_string(&path)
+			.map_err(|e| anyhow::format_err!("Failed to read secret from file {:?}: {}", path, e))
+	}
+}
+
+/// Provider that reads secrets from environment variables.
+pub struct EnvSecretProvider;
+
+#[async_trait]
+impl SecretProvider for EnvSecretProvider {
+	fn scheme(&self) -> &'static str {
+		"env"
+	}
+
+	async fn fetch(&self, location: &str) -> anyhow::Result<String> {
+		std::env::var(location).map_err(|e| {
+			anyhow::format_err!(
+				"Failed to read secret from environment variable {}: {}",
+				location,
+				e
+			)
+		})
+	}
+}
+
+/// Provider that reads secrets from the HashiCorp Vault KV secrets engine (both v1 and v2).
+pub struct VaultSecretProvider;
+
+#[async_trait]
+impl SecretProvider for VaultSecretProvider {
+	fn scheme(&self) -> &'static str {
+		"vault"
+	}
+
+	async fn fetch(&self, location: &str) -> anyhow::Result<String> {
+		use isahc::{config::Configurable, AsyncReadResponseExt, Request, RequestExt};
+
+		let (path, key) = location.split_once('#').ok_or_else(|| {
+			anyhow::format_err!("Vault secret reference must be in `vault://<path>#<key>` form")
+		})?;
+		let vault_addr =
+			std::env::var(VAULT_ADDR_ENV).unwrap_or_else(|_| DEFAULT_VAULT_ADDR.into());
+		let vault_token = std::env::var(VAULT_TOKEN_ENV).map_err(|_| {
+			anyhow::format_err!(
+				"{} environment variable must be set to read secrets from Vault",
+				VAULT_TOKEN_ENV,
+			)
+		})?;
+
+		let url = format!("{}/v1/{}", vault_addr.trim_end_matches('/'), path.trim_matches('/'));
+		let mut response = Request::get(&url)
+			.header("X-Vault-Token", vault_token)
+			.header("Accept", "application/json")
+			.timeout(VAULT_REQUEST_TIMEOUT)
+			.body(())?
+			.send_async()
+			.await
+			.map_err(|e| anyhow::format_err!("Failed to fetch Vault secret {}: {}", path, e))?;
+		if !response.status().is_success() {
+			return Err(anyhow::format_err!(
+				"Failed to fetch Vault secret {}: server has responded with {}",
+				path,
+				response.status(),
+			))
+		}
+
+		let raw_response = response.text().await?;
+		parse_vault_response(&raw_response, key)
+			.map_err(|e| anyhow::format_err!("Failed to read Vault secret {}: {}", path, e))
+	}
+}
+
+/// Set of secret providers.
+pub struct SecretsResolver {
+	providers: Vec<Box<dyn SecretProvider>>,
+}
+
+impl Default for SecretsResolver {
+	fn default() -> Self {
+		SecretsResolver {
+			providers: vec![
+				Box::new(FileSecretProvider),
+				Box::new(EnvSecretProvider),
+				Box::new(VaultSecretProvider),
+			],
+		}
+	}
+}
+
+impl SecretsResolver {
+	/// Add custom secret provider.
+	#[must_use]
+	pub fn with_provider(mut self, provider: impl SecretProvider + 'static) -> Self {
+		self.providers.push(Box::new(provider));
+		self
+	}
+
+	/// Resolve the value.
+	///
+	/// If the `value` isn't a secret reference or if it uses one of `literal_schemes`, it is
+	/// returned as-is. Otherwise the secret is fetched using the provider of the scheme.
+	/// An error is returned, if the scheme is not supported, or if the provider has failed
+	/// to fetch the secret.
+	pub async fn resolve(&self, value: &str, literal_schemes: &[&str]) -> anyhow::Result<String> {
+		let (scheme, location) = match split_secret_reference(value) {
+			Some((scheme, location)) if !literal_schemes.contains(&scheme) => (scheme, location),
+			_ => return Ok(value.into()),
+		};
+
+		let provider =
+			self.providers.iter().find(|provider| provider.scheme() == scheme).ok_or_else(
+				|| {
+					anyhow::format_err!(
+						"Unsupported secret scheme '{}://'. Supported schemes: {}",
+						scheme,
+						self.providers
+							.iter()
+							.map(|provider| format!("'{}://'", provider.scheme()))
+							.collect::<Vec<_>>()
+							.join(", "),
+					)
+				},
+			)?;
+		provider
+			.fetch(location)
+			.await
+			.map(|secret| secret.trim_end_matches(['\r', '\n']).to_owned())
+	}
+}
+
+/// Resolve given value using default set of secret providers.
+pub async fn resolve_secret(value: &str, literal_schemes: &[&str]) -> anyhow::Result<String> {
+	SecretsResolver::default().resolve(value, literal_schemes).await
+}
+
+/// Returns version of the value that is safe to print.
+///
+/// References to secrets of default providers are returned as-is, because they only point to
+/// the secret location. All other values are redacted.
+pub fn redact_secret(value: &str) -> String {
+	let is_secret_reference = split_secret_reference(value)
+		.map(|(scheme, _)| {
+			SecretsResolver::default()
+				.providers
+				.iter()
+				.any(|provider| provider.scheme() == scheme)
+		})
+		.unwrap_or(false);
+	if is_secret_reference {
+		value.into()
+	} else {
+		REDACTED_SECRET.into()
+	}
+}
+
+/// Splits `<scheme>://<location>` into scheme and location. Returns `None` if value is not a
+/// secret reference.
+fn split_secret_reference(value: &str) -> Option<(&str, &str)> {
+	let (scheme, location) = value.split_once("://")?;
+	let mut scheme_chars = scheme.chars();
+	let is_valid_scheme = scheme_chars.next().map(|c| c.is_ascii_alphabetic()).unwrap_or(false) &&
+		scheme_chars.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+	if is_valid_scheme {
+		Some((scheme, location))
+	} else {
+		None
+	}
+}
+
+/// Select secret value from the Vault response.
+fn parse_vault_response(response: &str, key: &str) -> anyhow::Result<String> {
+	let json: serde_json::Value = serde_json::from_str(response)?;
+	// KV v2 engine wraps secret into additional `data` object
+	let data = &json["data"];
+	let value = data["data"].get(key).or_else(|| data.get(key));
+	match value {
+		Some(serde_json::Value::String(value)) => Ok(value.clone()),
+		Some(_) => Err(anyhow::format_err!("the '{}' field is not a string", key)),
+		None => Err(anyhow::format_err!("the '{}' field is missing", key)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn resolve(value: &str, literal_schemes: &[&str]) -> anyhow::Result<String> {
+		async_std::task::block_on(resolve_secret(value, literal_schemes))
+	}
+
+	#[test]
+	fn literal_values_are_not_resolved() {
+		assert_eq!(resolve("//Alice", &[]).unwrap(), "//Alice");
+		assert_eq!(
+			resolve(
+				"bottom drive obey lake curtain smoke basket hold race lonely fit walk//Alice",
+				&[]
+			)
+			.unwrap(),
+			"bottom drive obey lake curtain smoke basket hold race lonely fit walk//Alice",
+		);
+		assert_eq!(
+			resolve("wss://rpc.polkadot.io:443", &["ws", "wss"]).unwrap(),
+			"wss://rpc.polkadot.io:443",
+		);
+	}
+
+	#[test]
+	fn only_secret_references_are_not_redacted() {
+		assert_eq!(redact_secret("//Alice"), REDACTED_SECRET);
+		assert_eq!(redact_secret("aws-sm://relayer-signer"), REDACTED_SECRET);
+		assert_eq!(redact_secret("env://RELAYER_SIGNER"), "env://RELAYER_SIGNER");
+		assert_eq!(redact_secret("vault://secret/relayer#suri"), "vault://secret/relayer#suri");
+	}
+
+	#[test]
+	fn unknown_scheme_is_rejected() {
+		assert!(resolve("aws-sm://relayer-signer", &[]).is_err());
+		assert!(resolve("wss://rpc.polkadot.io:443", &[]).is_err());
+	}
+
+	#[test]
+	fn secret_is_read_from_file() {
+		let path = std::env::temp_dir().join("relay_utils_secret_is_read_from_file");
+		std::fs::write(&path, "//Bob\n").unwrap();
+		assert_eq!(resolve(&format!("file://{}", path.display()), &[]).unwrap(), "//Bob");
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn missing_file_is_reported() {
+		assert!(resolve("file:///this/file/does/not/exist", &[]).is_err());
+	}
+
+	#[test]
+	fn secret_is_read_from_env() {
+		std::env::set_var("RELAY_UTILS_SECRET_IS_READ_FROM_ENV", "//Charlie");
+		assert_eq!(resolve("env://RELAY_UTILS_SECRET_IS_READ_FROM_ENV", &[]).unwrap(), "//Charlie",);
+	}
+
+	#[test]
+	fn custom_provider_is_used() {
+		struct TestProvider;
+
+		#[async_trait]
+		impl SecretProvider for TestProvider {
+			fn scheme(&self) -> &'static str {
+				"test"
+			}
+
+			async fn fetch(&self, location: &str) -> anyhow::Result<String> {
+				Ok(format!("//{location}"))
+			}
+		}
+
+		let resolver = SecretsResolver::default().with_provider(TestProvider);
+		assert_eq!(
+			async_std::task::block_on(resolver.resolve("test://Dave", &[])).unwrap(),
+			"//Dave",
+		);
+	}
+
+	#[test]
+	fn vault_response_is_parsed() {
+		// KV v2
+		assert_eq!(
+			parse_vault_response(r#"{"data":{"data":{"suri":"//Eve"},"metadata":{}}}"#, "suri")
+				.unwrap(),
+			"//Eve",
+		);
+		// KV v1
+		assert_eq!(parse_vault_response(r#"{"data":{"suri":"//Eve"}}"#, "suri").unwrap(), "//Eve");
+		// missing key
+		assert!(parse_vault_response(r#"{"data":{"data":{"other":"//Eve"}}}"#, "suri").is_err());
+	}
+}
@@ -0,0 +1,282 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Rolling history of the relay throughput.
+//!
+//! The run summary only reports totals since the relay has been started. The history splits
+//! the recent relay activity into buckets of fixed duration, so that operators may see trends
+//! of delivered and confirmed messages and spent fees. Buckets that are older than the
+//! retention period are dropped, so the history size is bounded. The history may be persisted
+//! to the file, so that trends survive restarts.
+
+use crate::run_summary::RunSummary;
+
+use serde_json::{json, Value};
+use std::{
+	collections::VecDeque,
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Only buckets of this period are kept in the history.
+pub const RETENTION_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+/// Maximal interval between two consecutive samples of the run summary.
+const MAX_SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Relay activity within some period.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StatsSample {
+	/// Number of delivered messages.
+	pub delivered: u64,
+	/// Number of messages, which delivery has been confirmed.
+	pub confirmed: u64,
+	/// Estimated fees of submitted transactions.
+	pub fees_spent: u128,
+}
+
+impl StatsSample {
+	/// Returns activity since the `earlier` sample of cumulative counters.
+	pub fn since(&self, earlier: &StatsSample) -> StatsSample {
+		StatsSample {
+			delivered: self.delivered.saturating_sub(earlier.delivered),
+			confirmed: self.confirmed.saturating_sub(earlier.confirmed),
+			fees_spent: self.fees_spent.saturating_sub(earlier.fees_spent),
+		}
+	}
+
+	fn add(&mut self, other: &StatsSample) {
+		self.delivered = self.delivered.saturating_add(other.delivered);
+		self.confirmed = self.confirmed.saturating_add(other.confirmed);
+		self.fees_spent = self.fees_spent.saturating_add(other.fees_spent);
+	}
+}
+
+/// Relay activity within single bucket of the history.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct StatsBucket {
+	/// Unix timestamp of the bucket start, in seconds.
+	started_at: u64,
+	/// Relay activity within the bucket.
+	stats: StatsSample,
+}
+
+/// Rolling history of the relay activity.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatsHistory {
+	/// Duration of single bucket in seconds.
+	bucket_secs: u64,
+	/// Buckets of the history, oldest first. Buckets without any activity are not stored.
+	buckets: VecDeque<StatsBucket>,
+}
+
+impl StatsHistory {
+	/// Create empty history with given bucket duration.
+	pub fn new(bucket_duration: Duration) -> Self {
+		StatsHistory { bucket_secs: bucket_duration.as_secs().max(1), buckets: VecDeque::new() }
+	}
+
+	/// Read the history from given file. Empty history is returned if the file doesn't
+	/// exist or if it has been written with different bucket duration.
+	pub fn load(path: &Path, bucket_duration: Duration) -> anyhow::Result<Self> {
+		let history = Self::new(bucket_duration);
+		let contents = match std::fs::read_to_string(path) {
+			Ok(contents) => contents,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(history),
+			Err(e) =>
+				return Err(anyhow::format_err!("Failed to read stats history {:?}: {}", path, e)),
+		};
+		let json: Value = serde_json::from_str(&contents)
+			.map_err(|e| anyhow::format_err!("Failed to parse stats history {:?}: {}", path, e))?;
+		if json["bucket_secs"].as_u64() != Some(history.bucket_secs) {
+			log::warn!(
+				target: "bridge",
+				"Stats history {:?} has different bucket duration. Starting new history",
+				path,
+			);
+			return Ok(history)
+		}
+
+		Self::from_json(&json, bucket_duration)
+			.ok_or_else(|| anyhow::format_err!("Stats history {:?} is malformed", path))
+	}
+
+	/// Write the history to given file.
+	///
+	/// The history is written to the temporary file first, so that it isn't corrupted if the
+	/// relay is killed in the middle of writing.
+	pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+		let mut temp_path = path.as_os_str().to_owned();
+		temp_path.push(".tmp");
+		let temp_path = PathBuf::from(temp_path);
+		std::fs::write(&temp_path, self.to_json().to_string())
+			.and_then(|_| std::fs::rename(&temp_path, path))
+			.map_err(|e| anyhow::format_err!("Failed to write stats history {:?}: {}", path, e))
+	}
+
+	/// Add activity that has happened at given Unix timestamp (in seconds) to the history.
+	pub fn record(&mut self, now: u64, stats: StatsSample) {
+		let started_at = now - now % self.bucket_secs;
+		match self.buckets.back_mut() {
+			// if the clock goes backwards, activity is added to the latest bucket
+			Some(latest) if latest.started_at >= started_at => latest.stats.add(&stats),
+			_ if stats == StatsSample::default() => (),
+			_ => self.buckets.push_back(StatsBucket { started_at, stats }),
+		}
+
+		let retention_secs = RETENTION_PERIOD.as_secs();
+		while self
+			.buckets
+			.front()
+			.map_or(false, |oldest| oldest.started_at.saturating_add(retention_secs) <= started_at)
+		{
+			self.buckets.pop_front();
+		}
+	}
+
+	/// Returns total activity within the history.
+	pub fn total(&self) -> StatsSample {
+		let mut total = StatsSample::default();
+		for bucket in &self.buckets {
+			total.add(&bucket.stats);
+		}
+		total
+	}
+
+	/// Returns the history as a JSON value.
+	pub fn to_json(&self) -> Value {
+		json!({
+			"bucket_secs": self.bucket_secs,
+			"buckets": self
+				.buckets
+				.iter()
+				.map(|bucket| json!({
+					"started_at": bucket.started_at,
+					"delivered": bucket.stats.delivered,
+					"confirmed": bucket.stats.confirmed,
+					// fees may not fit into JSON numbers
+					"fees_spent": bucket.stats.fees_spent.to_string(),
+				}))
+				.collect::<Vec<_>>(),
+		})
+	}
+
+	fn from_json(json: &Value, bucket_duration: Duration) -> Option<Self> {
+		let mut history = Self::new(bucket_duration);
+		for bucket in json["buckets"].as_array()? {
+			history.buckets.push_back(StatsBucket {
+				started_at: bucket["started_at"].as_u64()?,
+				stats: StatsSample {
+					delivered: bucket["delivered"].as_u64()?,
+					confirmed: bucket["confirmed"].as_u64()?,
+					fees_spent: bucket["fees_spent"].as_str()?.parse().ok()?,
+				},
+			});
+		}
+		Some(history)
+	}
+}
+
+/// Start background task that periodically adds activity from the run summary to the history
+/// of the summary and writes the history to given file, if it is set.
+pub fn spawn_history_tracker(summary: RunSummary, path: Option<PathBuf>) {
+	let sample_interval = match summary.history() {
+		Some(history) =>
+			std::cmp::min(Duration::from_secs(history.bucket_secs), MAX_SAMPLE_INTERVAL),
+		None => return,
+	};
+
+	async_std::task::spawn(async move {
+		let mut previous = summary.sample();
+		loop {
+			async_std::task::sleep(sample_interval).await;
+
+			let current = summary.sample();
+			let now = SystemTime::now()
+				.duration_since(UNIX_EPOCH)
+				.map(|since_epoch| since_epoch.as_secs())
+				.unwrap_or_default();
+			summary.record_history(now, current.since(&previous));
+			previous = current;
+
+			if let (Some(path), Some(history)) = (path.as_ref(), summary.history()) {
+				if let Err(e) = history.save(path) {
+					log::warn!(target: "bridge", "{:?}", e);
+				}
+			}
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const HOUR: u64 = 60 * 60;
+
+	fn sample(delivered: u64) -> StatsSample {
+		StatsSample { delivered, confirmed: delivered / 2, fees_spent: delivered as u128 * 10 }
+	}
+
+	#[test]
+	fn activity_is_bucketed_within_retention_period() {
+		let mut history = StatsHistory::new(Duration::from_secs(HOUR));
+
+		// activity of the same hour goes to the same bucket
+		history.record(100 * HOUR, sample(2));
+		history.record(100 * HOUR + 10, sample(4));
+		// idle hours are not stored
+		history.record(101 * HOUR, StatsSample::default());
+		history.record(102 * HOUR + 10, sample(6));
+		assert_eq!(
+			history.to_json()["buckets"],
+			json!([
+				{ "started_at": 100 * HOUR, "delivered": 6, "confirmed": 3, "fees_spent": "60" },
+				{ "started_at": 102 * HOUR, "delivered": 6, "confirmed": 3, "fees_spent": "60" },
+			]),
+		);
+
+		// buckets leave the history after the retention period
+		history.record(124 * HOUR, sample(8));
+		assert_eq!(history.buckets.len(), 2);
+		assert_eq!(history.total(), StatsSample { delivered: 14, confirmed: 7, fees_spent: 140 });
+		history.record(126 * HOUR + 10, sample(2));
+		assert_eq!(history.buckets.len(), 2);
+		assert_eq!(history.total(), StatsSample { delivered: 10, confirmed: 5, fees_spent: 100 });
+	}
+
+	#[test]
+	fn history_survives_restarts() {
+		let dir = std::env::temp_dir().join(format!("stats-history-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("stats-history.json");
+
+		// there's no history yet
+		let mut history = StatsHistory::load(&path, Duration::from_secs(HOUR)).unwrap();
+		assert_eq!(history, StatsHistory::new(Duration::from_secs(HOUR)));
+
+		history.record(100 * HOUR, sample(u64::MAX));
+		history.save(&path).unwrap();
+		assert_eq!(StatsHistory::load(&path, Duration::from_secs(HOUR)).unwrap(), history);
+
+		// history with different bucket duration is discarded
+		assert_eq!(
+			StatsHistory::load(&path, Duration::from_secs(60)).unwrap(),
+			StatsHistory::new(Duration::from_secs(60)),
+		);
+
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -0,0 +1,208 @@
+// Copyright 2019-2021 Parity Technologies (UK) Ltd.
+// This file is part of Parity Bridges Common.
+
+// Parity Bridges Common is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity Bridges Common is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity Bridges Common.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Limit of messages that all lanes of the relay process may deliver within a rolling minute.
+//!
+//! The budget is shared fairly: every lane that has messages to deliver may use at most
+//! an equal share of the budget. A lane stops competing for the budget if it has no messages
+//! to deliver for a whole minute.
+
+use crate::metrics::{metric_name, register, Gauge, Metric, PrometheusError, Registry, U64};
+
+use parking_lot::Mutex;
+use std::{
+	collections::{BTreeMap, VecDeque},
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+/// Duration of the throughput budget window.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Messages throughput budget, shared by all clones.
+///
+/// Default budget is unlimited.
+#[derive(Clone, Debug, Default)]
+pub struct ThroughputBudget(Option<Arc<ThroughputBudgetData>>);
+
+#[derive(Debug)]
+struct ThroughputBudgetData {
+	messages_per_minute: u64,
+	state: Mutex<ThroughputBudgetState>,
+	remaining_metric: Gauge<U64>,
+}
+
+#[derive(Debug, Default)]
+struct ThroughputBudgetState {
+	/// Messages, selected for delivery within the window, ordered by time.
+	spent: VecDeque<(Instant, String, u64)>,
+	/// Lanes and the latest moment when they had messages to deliver.
+	demand: BTreeMap<String, Instant>,
+}
+
+impl ThroughputBudget {
+	/// Create budget that allows delivering at most `messages_per_minute` messages within any
+	/// minute.
+	pub fn new(messages_per_minute: u64, prefix: Option<&str>) -> Result<Self, PrometheusError> {
+		let remaining_metric = Gauge::new(
+			metric_name(prefix, "messages_throughput_budget_remaining"),
+			"Number of messages that all lanes may deliver within the current minute",
+		)?;
+		remaining_metric.set(messages_per_minute);
+		Ok(ThroughputBudget(Some(Arc::new(ThroughputBudgetData {
+			messages_per_minute,
+			state: Mutex::new(ThroughputBudgetState::default()),
+			remaining_metric,
+		}))))
+	}
+
+	/// Returns true if the budget is unlimited.
+	pub fn is_unlimited(&self) -> bool {
+		self.0.is_none()
+	}
+
+	/// Returns handle of the budget, used by the lane with given name.
+	pub fn lane(&self, name: String) -> LaneThroughputBudget {
+		LaneThroughputBudget { budget: self.clone(), name }
+	}
+}
+
+impl Metric for ThroughputBudget {
+	fn register(&self, registry: &Registry) -> Result<(), PrometheusError> {
+		if let Some(ref data) = self.0 {
+			register(data.remaining_metric.clone(), registry)?;
+		}
+		Ok(())
+	}
+}
+
+/// Handle of the throughput budget, used by single lane.
+#[derive(Clone, Debug, Default)]
+pub struct LaneThroughputBudget {
+	budget: ThroughputBudget,
+	name: String,
+}
+
+impl LaneThroughputBudget {
+	/// Returns number of messages that the lane may deliver right now. The lane must only call
+	/// it when it has messages to deliver.
+	pub fn available(&self) -> u64 {
+		self.available_at(Instant::now())
+	}
+
+	/// Remember that given number of messages has been selected for delivery.
+	pub fn spend(&self, messages: u64) {
+		self.spend_at(Instant::now(), messages)
+	}
+
+	fn available_at(&self, now: Instant) -> u64 {
+		let data = match self.budget.0 {
+			Some(ref data) => data,
+			None => return u64::MAX,
+		};
+
+		let mut state = data.state.lock();
+		state.demand.insert(self.name.clone(), now);
+		let (total_spent, lane_spent) = data.prune(&mut state, now, &self.name);
+		let remaining = data.messages_per_minute.saturating_sub(total_spent);
+		// every lane, competing for the budget, gets an equal share of it
+		let active_lanes = state.demand.len() as u64;
+		let fair_share = (data.messages_per_minute + active_lanes - 1) / active_lanes;
+		std::cmp::min(remaining, fair_share.saturating_sub(lane_spent))
+	}
+
+	fn spend_at(&self, now: Instant, messages: u64) {
+		if let Some(ref data) = self.budget.0 {
+			let mut state = data.state.lock();
+			state.spent.push_back((now, self.name.clone(), messages));
+			data.prune(&mut state, now, &self.name);
+		}
+	}
+}
+
+impl ThroughputBudgetData {
+	/// Forget messages and lanes demand that have left the window. Returns number of messages,
+	/// spent by all lanes and by given lane within the window.
+	fn prune(&self, state: &mut ThroughputBudgetState, now: Instant, lane: &str) -> (u64, u64) {
+		while state
+			.spent
+			.front()
+			.map(|(at, _, _)| now.duration_since(*at) >= WINDOW)
+			.unwrap_or(false)
+		{
+			state.spent.pop_front();
+		}
+		state.demand.retain(|_, at| now.duration_since(*at) < WINDOW);
+
+		let sum =
+			|total: u64, (_, _, messages): &(Instant, String, u64)| total.saturating_add(*messages);
+		let total_spent = state.spent.iter().fold(0, sum);
+		let lane_spent = state.spent.iter().filter(|(_, name, _)| name == lane).fold(0, sum);
+		self.remaining_metric.set(self.messages_per_minute.saturating_sub(total_spent));
+		(total_spent, lane_spent)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn unlimited_budget_is_never_exhausted() {
+		let lane = ThroughputBudget::default().lane("A".into());
+		lane.spend(u64::MAX);
+		assert_eq!(lane.available(), u64::MAX);
+	}
+
+	#[test]
+	fn busy_lane_does_not_starve_other_lanes() {
+		let budget = ThroughputBudget::new(100, None).unwrap();
+		let lane_a = budget.lane("A".into());
+		let lane_b = budget.lane("B".into());
+		let start = Instant::now();
+
+		// while lane A is the only lane with messages, it may use the whole budget
+		assert_eq!(lane_a.available_at(start), 100);
+		lane_a.spend_at(start, 30);
+
+		// when lane B gets messages, lane A may only use its share of the budget
+		assert_eq!(lane_b.available_at(start), 50);
+		assert_eq!(lane_a.available_at(start), 20);
+		lane_a.spend_at(start, 20);
+		assert_eq!(lane_a.available_at(start), 0);
+		assert_eq!(lane_b.available_at(start), 50);
+		lane_b.spend_at(start, 50);
+		assert_eq!(lane_b.available_at(start), 0);
+
+		// the budget is restored when the window rolls
+		assert_eq!(lane_b.available_at(start + WINDOW), 100);
+		assert_eq!(lane_a.available_at(start + WINDOW), 50);
+	}
+
+	#[test]
+	fn idle_lane_stops_competing_for_budget() {
+		let budget = ThroughputBudget::new(100, None).unwrap();
+		let lane_a = budget.lane("A".into());
+		let lane_b = budget.lane("B".into());
+		let start = Instant::now();
+
+		assert_eq!(lane_b.available_at(start), 100);
+		assert_eq!(lane_a.available_at(start + Duration::from_secs(30)), 50);
+
+		// lane B has no messages to deliver for a whole minute
+		assert_eq!(lane_a.available_at(start + WINDOW), 100);
+	}
+}